@@ -0,0 +1,705 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::prelude::*;
+
+const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Must match `BIN_COUNT` of the metering shaders
+const HISTOGRAM_BIN_COUNT: usize = 256;
+const HISTOGRAM_WORKGROUP_SIZE: u32 = 16;
+/// Luminances metered by the histogram, `2^-10` to `2^12`
+const MIN_LOG_LUMINANCE: f32 = -10.;
+const LOG_LUMINANCE_RANGE: f32 = 22.;
+/// Middle grey, the average luminance auto-exposure maps the scene to
+const KEY_VALUE: f32 = 0.18;
+
+struct AutoExposureApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    model_indices: Vec<u32>,
+
+    camera: Camera,
+    camera_controller: CameraController,
+    settings: ExposureSettings,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    scene_pipeline_layout: vk::PipelineLayout,
+    scene_pipeline: vk::Pipeline,
+    histogram_pipeline_layout: vk::PipelineLayout,
+    histogram_pipeline: vk::Pipeline,
+    exposure_pipeline_layout: vk::PipelineLayout,
+    exposure_pipeline: vk::Pipeline,
+    tonemap_pipeline_layout: vk::PipelineLayout,
+    tonemap_pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    uniform_buffers: PerFrame<Buffer<MVPMatrix>>,
+    #[allow(dead_code)]
+    texture_image: Texture,
+
+    /// Scene radiance, kept at the initial window size and stretched over the swapchain
+    hdr_target: OffscreenRenderTarget,
+    // Frames run on the graphic queue one after another, so a single histogram and
+    // exposure are shared by the frames in flight, the exposure adapting across frames
+    #[allow(dead_code)]
+    histogram_buffer: Buffer<u32>,
+    #[allow(dead_code)]
+    exposure_buffer: Buffer<ExposureState>,
+}
+
+impl WindowApp for AutoExposureApp {
+    impl_window_fns!(AutoExposureApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let hdr_target = OffscreenRenderTarget::new(
+            fixed_vulkan_stuff.surface.extent(),
+            HDR_FORMAT,
+            fixed_vulkan_stuff.depth_stencil.format(),
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .unwrap();
+
+        let (model_vertices, model_indices) =
+            vulkan_example_rs::mesh::load_obj_model("examples/meshes/viking_room/viking_room.obj")
+                .unwrap();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&model_vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&model_indices)
+            .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<MVPMatrix>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let mut texture_image = Texture::from_rgba8_picture(
+            "examples/textures/viking_room/viking_room.png",
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap();
+        texture_image.spawn_image_view().unwrap();
+        texture_image.spawn_sampler(vk::Filter::LINEAR).unwrap();
+
+        let histogram_buffer = fixed_vulkan_stuff
+            .device_local_storage_buffer(&[0u32; HISTOGRAM_BIN_COUNT])
+            .unwrap();
+        let exposure_buffer = fixed_vulkan_stuff
+            .device_local_storage_buffer(&[ExposureState {
+                average_luminance: KEY_VALUE,
+                exposure: 1.,
+            }])
+            .unwrap();
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .combined_image_sampler(1, &texture_image)
+                .image_info(
+                    2,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    hdr_target.color_descriptor(),
+                )
+                .storage_buffer(3, &histogram_buffer)
+                .storage_buffer(4, &exposure_buffer)
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (scene_pipeline_layout, scene_pipeline) = ScenePipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: hdr_target.extent(),
+            render_pass: hdr_target.render_pass(),
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap();
+
+        let (histogram_pipeline_layout, histogram_pipeline) =
+            ComputePipelineBuilder::with_spv_path(
+                "examples/shaders/auto_exposure/histogram.comp.spv",
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .set_layouts(&[descriptor_set_layout])
+            .push_constant_ranges(&[vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(std::mem::size_of::<HistogramPushConstBlock>() as u32)
+                .build()])
+            .pipeline_cache(fixed_vulkan_stuff.pipeline_cache)
+            .build()
+            .unwrap();
+
+        let (exposure_pipeline_layout, exposure_pipeline) = ComputePipelineBuilder::with_spv_path(
+            "examples/shaders/auto_exposure/exposure.comp.spv",
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .set_layouts(&[descriptor_set_layout])
+        .push_constant_ranges(&[vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(std::mem::size_of::<ExposurePushConstBlock>() as u32)
+            .build()])
+        .pipeline_cache(fixed_vulkan_stuff.pipeline_cache)
+        .build()
+        .unwrap();
+
+        let (tonemap_pipeline_layout, tonemap_pipeline) =
+            Self::create_tonemap_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        AutoExposureApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            model_indices,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 3., PI, 0.)
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            settings: ExposureSettings::default(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            scene_pipeline_layout,
+            scene_pipeline,
+            histogram_pipeline_layout,
+            histogram_pipeline,
+            exposure_pipeline_layout,
+            exposure_pipeline,
+            tonemap_pipeline_layout,
+            tonemap_pipeline,
+            vertex_buffer,
+            indice_buffer,
+            uniform_buffers,
+            texture_image,
+            hdr_target,
+            histogram_buffer,
+            exposure_buffer,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.uniform_buffers[frame.frame_index]
+            .load_data_when_mapped(&[self.camera.mvp_matrix(Mat4::IDENTITY)], 0);
+
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        let mut settings = self.settings;
+        self.update_ui_with(&[name], |ui| settings.draw(ui));
+        self.settings = settings;
+    }
+
+    /// Scene into the HDR target, metering and adaptation in compute,
+    /// then the tonemapped scene and the UI into the swapchain image
+    fn record(&mut self, frame: &FrameContext) {
+        let delta_time = (self.frame_counter().fps.recip() as f32).min(1. / 30.);
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+        let hdr_extent = self.hdr_target.extent();
+        unsafe {
+            self.hdr_target
+                .cmd_begin_render_pass(command_buffer, &Self::clear_value().to_array());
+            self.hdr_target.cmd_set_viewport_and_scissor(command_buffer);
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.scene_pipeline,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.scene_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.scene_pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.settings.light_intensity),
+            );
+            device.cmd_draw_indexed(command_buffer, self.model_indices.len() as u32, 1, 0, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+
+            // The render pass only makes its writes visible to fragment shaders, the tonemap
+            // pass of the last frame has to be done reading the exposure, and the histogram
+            // cleared by the last frame has to be visible before it is counted into again
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(
+                        vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::SHADER_WRITE,
+                    )
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.histogram_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.histogram_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.histogram_pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&HistogramPushConstBlock {
+                    min_log_luminance: MIN_LOG_LUMINANCE,
+                    log_luminance_range: LOG_LUMINANCE_RANGE,
+                }),
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                hdr_extent.width.div_ceil(HISTOGRAM_WORKGROUP_SIZE),
+                hdr_extent.height.div_ceil(HISTOGRAM_WORKGROUP_SIZE),
+                1,
+            );
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.exposure_pipeline,
+            );
+            // Rebound, the layouts differ in their push constant ranges
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.exposure_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.exposure_pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&ExposurePushConstBlock {
+                    min_log_luminance: MIN_LOG_LUMINANCE,
+                    log_luminance_range: LOG_LUMINANCE_RANGE,
+                    pixel_count: (hdr_extent.width * hdr_extent.height) as f32,
+                    delta_time,
+                    adaptation_speed: self.settings.adaptation_speed,
+                    key_value: KEY_VALUE * self.settings.exposure_compensation.exp2(),
+                    manual_exposure: self.settings.manual_exposure,
+                    auto_exposure: self.settings.auto_exposure as u32,
+                }),
+            );
+            device.cmd_dispatch(command_buffer, 1, 1, 1);
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            self.fixed_vulkan_stuff.cmd_begin_renderpass(
+                frame_index,
+                image_index,
+                &Self::clear_value(),
+            );
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.tonemap_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.tonemap_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+        }
+    }
+
+    /// Only the tonemap pass draws into the swapchain, the HDR target keeps its format
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.tonemap_pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.tonemap_pipeline_layout);
+            (self.tonemap_pipeline_layout, self.tonemap_pipeline) =
+                Self::create_tonemap_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        [
+            (vk::DescriptorType::UNIFORM_BUFFER, 1),
+            (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+            (vk::DescriptorType::STORAGE_BUFFER, 2),
+        ]
+        .into_iter()
+        .map(|(ty, count)| {
+            vk::DescriptorPoolSize::builder()
+                .ty(ty)
+                .descriptor_count(count * FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build()
+        })
+        .collect()
+    }
+
+    /// One set for every pass: the scene reads 0 and 1, the histogram 2 and 3,
+    /// the adaptation 3 and 4, and the tonemap 2 and 4
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        [
+            (
+                vk::DescriptorType::UNIFORM_BUFFER,
+                vk::ShaderStageFlags::VERTEX,
+            ),
+            (
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::FRAGMENT,
+            ),
+            (
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::FRAGMENT,
+            ),
+            (
+                vk::DescriptorType::STORAGE_BUFFER,
+                vk::ShaderStageFlags::COMPUTE,
+            ),
+            (
+                vk::DescriptorType::STORAGE_BUFFER,
+                vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::FRAGMENT,
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(binding, (ty, stage_flags))| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding as u32)
+                .descriptor_type(ty)
+                .descriptor_count(1)
+                .stage_flags(stage_flags)
+                .build()
+        })
+        .collect()
+    }
+}
+
+impl AutoExposureApp {
+    fn create_tonemap_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        TonemapPipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+}
+
+impl Drop for AutoExposureApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            [
+                (self.scene_pipeline, self.scene_pipeline_layout),
+                (self.histogram_pipeline, self.histogram_pipeline_layout),
+                (self.exposure_pipeline, self.exposure_pipeline_layout),
+                (self.tonemap_pipeline, self.tonemap_pipeline_layout),
+            ]
+            .into_iter()
+            .for_each(|(pipeline, layout)| {
+                device.destroy_pipeline(pipeline, None);
+                device.destroy_pipeline_layout(layout, None);
+            });
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ExposureSettings {
+    auto_exposure: bool,
+    /// How fast the metered luminance follows the scene, per second
+    adaptation_speed: f32,
+    /// In stops, added to the key value auto-exposure aims for
+    exposure_compensation: f32,
+    /// Used while auto-exposure is off
+    manual_exposure: f32,
+    /// Scales the radiance of the scene, for the exposure to adapt to
+    light_intensity: f32,
+}
+
+impl Default for ExposureSettings {
+    fn default() -> Self {
+        Self {
+            auto_exposure: true,
+            adaptation_speed: 1.5,
+            exposure_compensation: 0.,
+            manual_exposure: 1.,
+            light_intensity: 1.,
+        }
+    }
+}
+
+impl ExposureSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Exposure", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider_config("Light intensity", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.light_intensity);
+            ui.checkbox("Auto-exposure", &mut self.auto_exposure);
+            if self.auto_exposure {
+                ui.slider("Adaptation speed", 0.1, 10., &mut self.adaptation_speed);
+                ui.slider(
+                    "Compensation (EV)",
+                    -4.,
+                    4.,
+                    &mut self.exposure_compensation,
+                );
+            } else {
+                ui.slider_config("Exposure", 0.01, 100.)
+                    .flags(imgui::SliderFlags::LOGARITHMIC)
+                    .build(&mut self.manual_exposure);
+            }
+        }
+    }
+}
+
+/// Layout of the `Exposure` buffer of the metering shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ExposureState {
+    average_luminance: f32,
+    exposure: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct HistogramPushConstBlock {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+}
+
+unsafe impl bytemuck::Pod for HistogramPushConstBlock {}
+unsafe impl bytemuck::Zeroable for HistogramPushConstBlock {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ExposurePushConstBlock {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    pixel_count: f32,
+    delta_time: f32,
+    adaptation_speed: f32,
+    key_value: f32,
+    manual_exposure: f32,
+    auto_exposure: u32,
+}
+
+unsafe impl bytemuck::Pod for ExposurePushConstBlock {}
+unsafe impl bytemuck::Zeroable for ExposurePushConstBlock {}
+
+struct ScenePipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for ScenePipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/auto_exposure/scene.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/auto_exposure/scene.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<f32>() as u32)
+            .build();
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[push_constant_range])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+}
+
+struct TonemapPipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for TonemapPipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/auto_exposure/tonemap.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/auto_exposure/tonemap.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    // The fullscreen triangle is generated in the vertex shader, from either winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = AutoExposureApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -0,0 +1,418 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{app::DepthInspector, prelude::*};
+
+struct BloomApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    model_indices: Vec<u32>,
+
+    camera: Camera,
+    camera_controller: CameraController,
+    settings: BloomSettings,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    scene_pipeline_layout: vk::PipelineLayout,
+    scene_pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    uniform_buffers: PerFrame<Buffer<MVPMatrix>>,
+    #[allow(dead_code)]
+    texture_image: Texture,
+
+    /// Scene radiance, resized along with the swapchain, and the tonemapping of the output of
+    /// `bloom_pass` into the swapchain image
+    hdr_target: HdrTarget,
+    /// Adds the blurred bright parts of `hdr_target` to it, into its own HDR target
+    bloom_pass: FullscreenPass,
+    /// Replaces the tonemapping with the depth of `hdr_target` when shown
+    depth_inspector: DepthInspector,
+}
+
+impl WindowApp for BloomApp {
+    impl_window_fns!(BloomApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let mut hdr_target = HdrTarget::from_fixed_vulkan_stuff(&fixed_vulkan_stuff).unwrap();
+
+        let bloom_pass = FullscreenPass::offscreen(
+            "examples/shaders/bloom/bloom.frag.spv",
+            std::mem::size_of::<BloomPushConstBlock>() as u32,
+            hdr_target.extent(),
+            HdrTarget::FORMAT,
+            fixed_vulkan_stuff.pipeline_cache,
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .unwrap();
+        bloom_pass.set_input(hdr_target.color_descriptor());
+        hdr_target.set_tonemap_input(Some(bloom_pass.output_descriptor()));
+
+        let camera = Camera::builder()
+            .orbit(Vec3::ZERO, 3., PI, 0.)
+            .move_speed(100.)
+            .rotate_speed(40.)
+            .build();
+        let depth_inspector = DepthInspector::from_fixed_vulkan_stuff(
+            &fixed_vulkan_stuff,
+            hdr_target.depth_descriptor(),
+            camera.z_limits(),
+        )
+        .unwrap();
+
+        let (model_vertices, model_indices) =
+            vulkan_example_rs::mesh::load_obj_model("examples/meshes/viking_room/viking_room.obj")
+                .unwrap();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&model_vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&model_indices)
+            .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<MVPMatrix>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let mut texture_image = Texture::from_rgba8_picture(
+            "examples/textures/viking_room/viking_room.png",
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap();
+        texture_image.spawn_image_view().unwrap();
+        texture_image.spawn_sampler(vk::Filter::LINEAR).unwrap();
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .combined_image_sampler(1, &texture_image)
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (scene_pipeline_layout, scene_pipeline) = ScenePipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: hdr_target.extent(),
+            render_pass: hdr_target.render_pass(),
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        BloomApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            model_indices,
+            camera,
+            camera_controller: CameraController::new(),
+            settings: BloomSettings::default(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            scene_pipeline_layout,
+            scene_pipeline,
+            vertex_buffer,
+            indice_buffer,
+            uniform_buffers,
+            texture_image,
+            hdr_target,
+            bloom_pass,
+            depth_inspector,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.uniform_buffers[frame.frame_index]
+            .load_data_when_mapped(&[self.camera.mvp_matrix(Mat4::IDENTITY)], 0);
+        self.depth_inspector.read_back(frame.frame_index);
+        self.depth_inspector.view.z_limits = self.camera.z_limits();
+
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        let mut settings = self.settings;
+        let mut tonemap_settings = self.hdr_target.settings;
+        let mut depth_view = self.depth_inspector.view;
+        self.update_ui_with(&[name], |ui| {
+            settings.draw(ui);
+            if settings.show_depth {
+                depth_view.draw(ui);
+            } else {
+                tonemap_settings.draw(ui);
+            }
+        });
+        self.settings = settings;
+        self.hdr_target.settings = tonemap_settings;
+        self.depth_inspector.view = depth_view;
+    }
+
+    /// Scene into the HDR target, bloom into its own HDR target,
+    /// then the tonemapped result and the UI into the swapchain image
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+        unsafe {
+            self.hdr_target
+                .cmd_begin_render_pass(command_buffer, &Self::clear_value());
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.scene_pipeline,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.scene_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.scene_pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.settings.light_intensity),
+            );
+            device.cmd_draw_indexed(command_buffer, self.model_indices.len() as u32, 1, 0, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+        self.depth_inspector
+            .cmd_reduce(command_buffer, frame_index, self.hdr_target.extent());
+
+        self.bloom_pass.cmd_run(
+            command_buffer,
+            bytemuck::bytes_of(&BloomPushConstBlock {
+                threshold: self.settings.threshold,
+                intensity: self.settings.intensity,
+                radius: self.settings.radius,
+            }),
+        );
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        if self.settings.show_depth {
+            self.depth_inspector.cmd_draw(command_buffer, frame_index);
+        } else {
+            self.hdr_target.cmd_tonemap(command_buffer);
+        }
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.hdr_target
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        self.depth_inspector
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        if change.extent_changed {
+            let extent = self.fixed_vulkan_stuff.surface.extent();
+            self.bloom_pass.resize_output(extent).unwrap();
+            self.bloom_pass
+                .set_input(self.hdr_target.color_descriptor());
+            self.hdr_target
+                .set_tonemap_input(Some(self.bloom_pass.output_descriptor()));
+            self.depth_inspector
+                .set_depth(self.hdr_target.depth_descriptor());
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        [
+            (vk::DescriptorType::UNIFORM_BUFFER, 1),
+            (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+        ]
+        .into_iter()
+        .map(|(ty, count)| {
+            vk::DescriptorPoolSize::builder()
+                .ty(ty)
+                .descriptor_count(count * FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build()
+        })
+        .collect()
+    }
+
+    /// Only used by the scene pass, the fullscreen passes own their sets
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+        ]
+    }
+}
+
+impl Drop for BloomApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.scene_pipeline, None);
+            device.destroy_pipeline_layout(self.scene_pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct BloomSettings {
+    /// Scales the radiance of the scene, above 1 its highlights start to bloom
+    light_intensity: f32,
+    /// Luminance above which the scene blooms
+    threshold: f32,
+    intensity: f32,
+    /// In texels, between two taps of the blur
+    radius: f32,
+    /// Shows the depth of the scene instead of the tonemapped result
+    show_depth: bool,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            light_intensity: 4.,
+            threshold: 1.,
+            intensity: 1.,
+            radius: 2.,
+            show_depth: false,
+        }
+    }
+}
+
+impl BloomSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Bloom", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider_config("Light intensity", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.light_intensity);
+            ui.slider("Threshold", 0., 10., &mut self.threshold);
+            ui.slider("Intensity", 0., 4., &mut self.intensity);
+            ui.slider("Radius", 0.5, 8., &mut self.radius);
+            ui.checkbox("Show depth", &mut self.show_depth);
+        }
+    }
+}
+
+/// Layout of the push constants of `bloom.frag`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BloomPushConstBlock {
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+}
+
+unsafe impl bytemuck::Pod for BloomPushConstBlock {}
+unsafe impl bytemuck::Zeroable for BloomPushConstBlock {}
+
+struct ScenePipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for ScenePipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/bloom/scene.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/bloom/scene.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<f32>() as u32)
+            .build();
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[push_constant_range])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = BloomApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
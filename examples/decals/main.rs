@@ -0,0 +1,366 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{
+    app::{
+        Decal, DecalPipelineCreator, DepthBias, ProceduralPattern, SimpleMeshRenderer,
+        TextureGenerator,
+    },
+    mesh::ray_cast,
+    prelude::*,
+};
+
+const TEXTURE_PATH: &str = "examples/textures/viking_room/viking_room.png";
+const MAX_DECALS: usize = 256;
+
+struct DecalsApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    mesh_renderer: SimpleMeshRenderer,
+    /// Triangles clicks are cast against
+    model_positions: Vec<Vec3>,
+    model_indices: Vec<u32>,
+
+    /// Sampled through `descriptor_set`
+    #[allow(dead_code)]
+    decal_texture: Texture,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_set: vk::DescriptorSet,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+
+    decals: Vec<Decal>,
+    settings: DecalSettings,
+}
+
+impl WindowApp for DecalsApp {
+    impl_window_fns!(DecalsApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        let device = fixed_vulkan_stuff.device.clone();
+        let (model_vertices, model_indices) =
+            vulkan_example_rs::mesh::load_obj_model("examples/meshes/viking_room/viking_room.obj")
+                .unwrap();
+
+        let texture_generator =
+            TextureGenerator::new(fixed_vulkan_stuff.pipeline_cache, device.clone()).unwrap();
+        let generate = |pattern: &ProceduralPattern| {
+            texture_generator.generate(
+                pattern,
+                256,
+                256,
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &device.graphic_queue(),
+            )
+        };
+        let room_texture = Texture::from_rgba8_picture(
+            TEXTURE_PATH,
+            device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &device.graphic_queue(),
+        )
+        .and_then(|mut texture| {
+            texture.spawn_image_view()?;
+            texture.spawn_sampler(vk::Filter::LINEAR)?;
+            Ok(texture)
+        })
+        .unwrap_or_else(|e| {
+            println!("Fail to load {TEXTURE_PATH}, using a generated texture: {e}");
+            generate(&ProceduralPattern::MISSING).unwrap()
+        });
+        // Transparent cells let the surface show through the decal
+        let decal_texture = generate(&ProceduralPattern::Checkerboard {
+            cells: 4,
+            color_a: Vec4::ONE,
+            color_b: Vec4::ZERO,
+        })
+        .unwrap();
+
+        let mesh_renderer = SimpleMeshRenderer::builder(
+            "examples/shaders/viking_room/shader.vert.spv",
+            "examples/shaders/viking_room/shader.frag.spv",
+        )
+        .texture(room_texture)
+        .build(&fixed_vulkan_stuff, &model_vertices, &model_indices)
+        .unwrap();
+
+        let bindings = DecalPipelineCreator::bindings();
+        let (descriptor_pool, descriptor_set_layout) = unsafe {
+            (
+                device
+                    .create_descriptor_pool(
+                        &vk::DescriptorPoolCreateInfo::builder()
+                            .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                                .descriptor_count(1)
+                                .build()])
+                            .max_sets(1)
+                            .build(),
+                        None,
+                    )
+                    .unwrap(),
+                device
+                    .create_descriptor_set_layout(
+                        &vk::DescriptorSetLayoutCreateInfo::builder()
+                            .bindings(&bindings)
+                            .build(),
+                        None,
+                    )
+                    .unwrap(),
+            )
+        };
+        let descriptor_set = DescriptorSetBuilder::new(&bindings, device.clone())
+            .combined_image_sampler(0, &decal_texture)
+            .build(descriptor_pool, descriptor_set_layout)
+            .unwrap();
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        DecalsApp {
+            window,
+
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 3., PI, 0.)
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+
+            fixed_vulkan_stuff,
+            mesh_renderer,
+            model_positions: model_vertices.iter().map(Vertex::pos).collect(),
+            model_indices,
+
+            decal_texture,
+            descriptor_pool,
+            descriptor_set_layout,
+            descriptor_set,
+            pipeline_layout,
+            pipeline,
+
+            decals: vec![],
+            settings: DecalSettings::default(),
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        if let Some(click) = self.camera_controller.take_click() {
+            self.stamp_decal(click);
+        }
+        self.mesh_renderer
+            .update_mvp(frame.frame_index, &self.camera.mvp_matrix(Mat4::IDENTITY));
+
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        let mut settings = self.settings;
+        let decal_count = self.decals.len();
+        let mut clear = false;
+        self.update_ui_with(&[name], |ui| clear = settings.draw(ui, decal_count));
+        self.settings = settings;
+        if clear {
+            self.decals.clear();
+        }
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+        unsafe {
+            self.fixed_vulkan_stuff.cmd_begin_renderpass(
+                frame_index,
+                image_index,
+                &Self::clear_value(),
+            );
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+            self.mesh_renderer.cmd_draw(command_buffer, frame_index);
+
+            if !self.decals.is_empty() {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline,
+                );
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    0,
+                    &[self.descriptor_set],
+                    &[],
+                );
+                self.settings.depth_bias().cmd_set(&device, command_buffer);
+                let view_projection = self.camera.perspective_mat() * self.camera.view_mat();
+                self.decals.iter().for_each(|decal| {
+                    decal.cmd_draw(
+                        &device,
+                        command_buffer,
+                        self.pipeline_layout,
+                        view_projection,
+                    )
+                });
+            }
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+        }
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.mesh_renderer
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![]
+    }
+}
+
+impl DecalsApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        DecalPipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            subpass: 0,
+            dynamic_rendering_formats: None,
+            set_layouts: &[descriptor_set_layout],
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+
+    /// Stamps a decal where the ray through `cursor` first hits the room
+    fn stamp_decal(&mut self, cursor: Vec2) {
+        let extent = self.fixed_vulkan_stuff.surface.extent();
+        let (origin, direction) = self
+            .camera
+            .screen_ray(cursor, Vec2::new(extent.width as f32, extent.height as f32));
+        let Some(hit) = ray_cast(
+            &self.model_positions,
+            &self.model_indices,
+            origin,
+            direction,
+        ) else {
+            return;
+        };
+        if self.decals.len() == MAX_DECALS {
+            self.decals.remove(0);
+        }
+        let mut decal = Decal::new(hit.position, hit.normal, self.settings.size);
+        decal.color = Vec4::from(self.settings.color);
+        // Varies the orientation of consecutive stamps
+        decal.rotation = self.decals.len() as f32 * 0.7;
+        self.decals.push(decal);
+    }
+}
+
+impl Drop for DecalsApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DecalSettings {
+    /// Off, decals z-fight with the surface they lie on
+    depth_bias_enabled: bool,
+    depth_bias: DepthBias,
+    size: f32,
+    color: [f32; 4],
+}
+
+impl Default for DecalSettings {
+    fn default() -> Self {
+        Self {
+            depth_bias_enabled: true,
+            depth_bias: DepthBias::DECAL,
+            size: 0.3,
+            color: [1., 0.5, 0.2, 0.9],
+        }
+    }
+}
+
+impl DecalSettings {
+    fn depth_bias(&self) -> DepthBias {
+        match self.depth_bias_enabled {
+            true => self.depth_bias,
+            false => DepthBias {
+                constant_factor: 0.,
+                slope_factor: 0.,
+            },
+        }
+    }
+
+    /// Returns whether the decals are to be cleared
+    fn draw(&mut self, ui: &imgui::Ui, decal_count: usize) -> bool {
+        let mut clear = false;
+        if ui.collapsing_header("Decals", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.text(format!("{decal_count} decals, click the room to stamp one"));
+            ui.checkbox("Depth bias", &mut self.depth_bias_enabled);
+            ui.slider(
+                "Constant factor",
+                -8.,
+                0.,
+                &mut self.depth_bias.constant_factor,
+            );
+            ui.slider("Slope factor", -8., 0., &mut self.depth_bias.slope_factor);
+            ui.slider("Size", 0.05, 1., &mut self.size);
+            ui.color_edit4("Color", &mut self.color);
+            clear = ui.button("Clear");
+        }
+        clear
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = DecalsApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
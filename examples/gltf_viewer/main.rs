@@ -0,0 +1,377 @@
+use std::f32::consts::PI;
+
+#[cfg(feature = "docking")]
+use vulkan_example_rs::app::ui_layout_path;
+use vulkan_example_rs::{
+    app::{DrawList, DrawStats, ObjectBuffer},
+    gltf::{self, Scene},
+    prelude::*,
+};
+
+const DEFAULT_SCENE_PATH: &str = "examples/meshes/cube/cube.gltf";
+
+struct GltfViewerApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    uniform_buffers: PerFrame<Buffer<MVPMatrix>>,
+    /// World transforms of the primitives drawn, at set 0 binding 1
+    object_buffer: ObjectBuffer,
+    draw_list: DrawList,
+    scene_path: String,
+    scene: Scene,
+    /// Of the last recorded frame, whose items are the primitives left by frustum culling
+    draw_stats: DrawStats,
+}
+
+impl WindowApp for GltfViewerApp {
+    impl_window_fns!(GltfViewerApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let scene_path = std::env::args()
+            .skip(1)
+            .find(|arg| !arg.starts_with("--"))
+            .unwrap_or_else(|| DEFAULT_SCENE_PATH.to_owned());
+        let scene = Scene::load(
+            &scene_path,
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.upload_queue(),
+        )
+        .unwrap_or_else(|e| panic!("Fail to load {scene_path}: {e}"));
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<MVPMatrix>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        // One object per primitive, instances of a mesh are merged by the draw list
+        let mut primitive_count = 0;
+        scene.walk(|_, mesh| primitive_count += mesh.primitives.len());
+        let object_buffer = ObjectBuffer::new(
+            primitive_count,
+            fixed_vulkan_stuff.frames_in_flight(),
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (frame_index, (descriptor_set, uniform_buffer)) in descriptor_sets
+            .iter()
+            .zip(uniform_buffers.iter())
+            .enumerate()
+        {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .storage_buffer(1, object_buffer.buffer(frame_index))
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (pipeline_layout, pipeline) = Self::create_pipeline(
+            &fixed_vulkan_stuff,
+            &[descriptor_set_layout, scene.descriptor_set_layout],
+        );
+
+        // Frames the whole scene whatever its units
+        let aabb = scene.aabb();
+        let radius = aabb.size().length().max(f32::EPSILON) * 1.5;
+        let camera = Camera::builder()
+            .orbit(aabb.center(), radius, PI, 0.)
+            .z_limits([radius * 0.01, radius * 10.])
+            .move_speed(100.)
+            .rotate_speed(40.)
+            .build();
+
+        let mut ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+        // Validation and asset messages stay readable without a terminal
+        ui_overlay.enable_log_console();
+        // Windows can be docked or dragged out of the main window, their layout is kept
+        #[cfg(feature = "docking")]
+        {
+            ui_overlay.enable_layout_persistence(ui_layout_path(&Self::window_title()));
+            ui_overlay.enable_viewports(&fixed_vulkan_stuff).unwrap();
+        }
+
+        GltfViewerApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera,
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            uniform_buffers,
+            object_buffer,
+            draw_list: DrawList::new(),
+            scene_path,
+            scene,
+            draw_stats: DrawStats::default(),
+        }
+    }
+
+    /// Uploads of large scenes should not hold back the rendering
+    fn queue_requests() -> Vec<QueueRequest> {
+        vec![QueueRequest::new(
+            Device::UPLOAD_QUEUE,
+            vk::QueueFlags::TRANSFER,
+            0.25,
+        )]
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.uniform_buffers[frame.frame_index]
+            .load_data_when_mapped(&[self.camera.mvp_matrix(Mat4::IDENTITY)], 0);
+
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            self.scene_path.clone(),
+            format!(
+                "{} nodes, {} meshes, {} materials, {} textures",
+                self.scene.nodes.len(),
+                self.scene.meshes.len(),
+                self.scene.materials.len() - 1,
+                self.scene.textures.len()
+            ),
+            format!(
+                "{} primitives drawn in {} draw calls",
+                self.draw_stats.items, self.draw_stats.draw_calls
+            ),
+            format!(
+                "{} pipeline and {} material binds",
+                self.draw_stats.pipeline_binds, self.draw_stats.material_binds
+            ),
+        ];
+        // Docked and detached windows are only interactive when rebuilt every frame
+        #[cfg(feature = "docking")]
+        self.update_ui_with(&infos, |_| {});
+        #[cfg(not(feature = "docking"))]
+        self.update_ui(&infos);
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+        }
+        let frustum = self.camera.frustum();
+        self.draw_list.clear();
+        self.scene
+            .push_draws(&mut self.draw_list, &frustum, |_| self.pipeline);
+        let draw_batches = self.draw_list.build();
+        self.object_buffer
+            .update(frame_index, &draw_batches.objects)
+            .unwrap();
+        self.scene.cmd_bind_buffers(command_buffer);
+        draw_batches.cmd_draw(&device, command_buffer, |material| unsafe {
+            let material = &self.scene.materials[material];
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                1,
+                &[material.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&MaterialPushConstBlock {
+                    base_color_factor: material.base_color_factor,
+                }),
+            );
+        });
+        self.draw_stats = draw_batches.stats;
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) = Self::create_pipeline(
+                &self.fixed_vulkan_stuff,
+                &[self.descriptor_set_layout, self.scene.descriptor_set_layout],
+            );
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![
+            vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build(),
+            vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build(),
+        ]
+    }
+
+    /// Set 0, set 1 is the material set of the scene
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .descriptor_count(1)
+                .build(),
+            ObjectBuffer::binding(1, vk::ShaderStageFlags::VERTEX),
+        ]
+    }
+}
+
+impl GltfViewerApp {
+    /// `set_layouts` are set 0 and the material set of the scene
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        set_layouts: &[vk::DescriptorSetLayout],
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts,
+            vertex_bindings: &[gltf::Vertex::binding_description()],
+            vertex_attributes: &gltf::Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+}
+
+impl_drop_trait!(GltfViewerApp);
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MaterialPushConstBlock {
+    base_color_factor: Vec4,
+}
+
+unsafe impl bytemuck::Pod for MaterialPushConstBlock {}
+unsafe impl bytemuck::Zeroable for MaterialPushConstBlock {}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/gltf_viewer/scene.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/gltf_viewer/scene.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<MaterialPushConstBlock>() as u32)
+            .build();
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[push_constant_range])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    // The scene is mirrored to the left-handed space of the examples, which flips its winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = GltfViewerApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -0,0 +1,585 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{
+    gltf::{self, Scene},
+    prelude::*,
+};
+
+const DEFAULT_SCENE_PATH: &str = "examples/meshes/cube/cube.gltf";
+/// Copies of the scene along x and z, each primitive of a copy is culled on its own
+const GRID_SIZE: usize = 48;
+/// Must match `local_size_x` of the culling shader
+const WORKGROUP_SIZE: u32 = 64;
+
+struct GpuCullingApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    cull_pipeline_layout: vk::PipelineLayout,
+    cull_pipeline: vk::Pipeline,
+    object_count: u32,
+    /// Read by the culling pass and by the vertex shader for the model matrices
+    #[allow(dead_code)]
+    object_buffer: Buffer<Object>,
+    /// One slot per object, written by the culling pass and consumed by the indirect draw
+    draw_buffers: PerFrame<Buffer<vk::DrawIndexedIndirectCommand>>,
+    /// Visible object count, read back once the frame has completed
+    stats_buffers: PerFrame<Buffer<u32>>,
+    uniform_buffers: PerFrame<Buffer<SceneUbo>>,
+    scene: Scene,
+    settings: CullingSettings,
+    /// Planes tested by the culling pass, kept while frozen
+    frustum_planes: [Vec4; 6],
+    visible_count: u32,
+}
+
+impl WindowApp for GpuCullingApp {
+    impl_window_fns!(GpuCullingApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        // The object index reaches the vertex shader as the first instance of its draw
+        assert!(
+            fixed_vulkan_stuff
+                .device
+                .enabled_features()
+                .draw_indirect_first_instance
+                == vk::TRUE,
+            "The drawIndirectFirstInstance feature is required"
+        );
+
+        let scene_path = std::env::args()
+            .skip(1)
+            .find(|arg| !arg.starts_with("--"))
+            .unwrap_or_else(|| DEFAULT_SCENE_PATH.to_owned());
+        let scene = Scene::load(
+            &scene_path,
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap_or_else(|e| panic!("Fail to load {scene_path}: {e}"));
+
+        let objects = grid_objects(&scene, GRID_SIZE);
+        let object_count = objects.len() as u32;
+        let object_buffer = fixed_vulkan_stuff
+            .device_local_storage_buffer(&objects)
+            .unwrap();
+        let draw_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            Buffer::<vk::DrawIndexedIndirectCommand>::storage(
+                objects.len(),
+                vk::BufferUsageFlags::INDIRECT_BUFFER,
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap()
+        });
+        let stats_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            Buffer::<u32>::host_storage(
+                1,
+                vk::BufferUsageFlags::TRANSFER_DST,
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap()
+        });
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<SceneUbo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (frame_index, descriptor_set) in descriptor_sets.iter().enumerate() {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .storage_buffer(0, &object_buffer)
+                .storage_buffer(1, &draw_buffers[frame_index])
+                .storage_buffer(2, &stats_buffers[frame_index])
+                .uniform_buffer(3, &uniform_buffers[frame_index])
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (cull_pipeline_layout, cull_pipeline) = ComputePipelineBuilder::with_spv_path(
+            "examples/shaders/gpu_culling/cull.comp.spv",
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .set_layouts(&[descriptor_set_layout])
+        .push_constant_ranges(&[vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(std::mem::size_of::<CullPushConstBlock>() as u32)
+            .build()])
+        .pipeline_cache(fixed_vulkan_stuff.pipeline_cache)
+        .build()
+        .unwrap();
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        // Looks over the whole grid from one of its corners
+        let grid_aabb = objects.iter().fold(gltf::AABB::EMPTY, |aabb, object| {
+            aabb.union(&gltf::AABB::new(
+                object.aabb_min.truncate(),
+                object.aabb_max.truncate(),
+            ))
+        });
+        let radius = grid_aabb.size().length().max(f32::EPSILON) * 0.6;
+        let camera = Camera::builder()
+            .orbit(grid_aabb.center(), radius, PI * 0.75, 0.5)
+            .z_limits([radius * 0.01, radius * 4.])
+            .move_speed(100.)
+            .rotate_speed(40.)
+            .build();
+        let frustum_planes = camera.frustum_planes();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        GpuCullingApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera,
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            cull_pipeline_layout,
+            cull_pipeline,
+            object_count,
+            object_buffer,
+            draw_buffers,
+            stats_buffers,
+            uniform_buffers,
+            scene,
+            settings: CullingSettings::default(),
+            frustum_planes,
+            visible_count: object_count,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        // The fence of the frame has been waited for, its culling pass is complete
+        if self.frame_counter().frame_count >= FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u64 {
+            self.visible_count = unsafe {
+                std::ptr::read_volatile(
+                    self.stats_buffers[frame.frame_index].mapped_ptr().unwrap() as *const u32
+                )
+            };
+        }
+        if !self.settings.freeze_frustum {
+            self.frustum_planes = self.camera.frustum_planes();
+        }
+        self.uniform_buffers[frame.frame_index].load_data_when_mapped(
+            &[SceneUbo {
+                view: self.camera.view_mat(),
+                projection: self.camera.perspective_mat(),
+            }],
+            0,
+        );
+
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!("visible: {} / {}", self.visible_count, self.object_count),
+        ];
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui));
+        self.settings = settings;
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.record_cull_commands(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+        }
+        // The draws come from the culling pass
+        self.scene.cmd_bind_buffers(command_buffer);
+        let draw_buffer = self.draw_buffers[frame_index].buffer();
+        let stride = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+        unsafe {
+            if device.enabled_features().multi_draw_indirect == vk::TRUE {
+                device.cmd_draw_indexed_indirect(
+                    command_buffer,
+                    draw_buffer,
+                    0,
+                    self.object_count,
+                    stride,
+                );
+            } else {
+                for i in 0..self.object_count {
+                    device.cmd_draw_indexed_indirect(
+                        command_buffer,
+                        draw_buffer,
+                        (i * stride) as vk::DeviceSize,
+                        1,
+                        stride,
+                    );
+                }
+            }
+        }
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    /// The culling pass does not depend on the swapchain
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![
+            vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32 * 3)
+                .build(),
+            vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build(),
+        ]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        let binding = |binding, descriptor_type, stage_flags| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .stage_flags(stage_flags)
+                .descriptor_count(1)
+                .build()
+        };
+        vec![
+            binding(
+                0,
+                vk::DescriptorType::STORAGE_BUFFER,
+                vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX,
+            ),
+            binding(
+                1,
+                vk::DescriptorType::STORAGE_BUFFER,
+                vk::ShaderStageFlags::COMPUTE,
+            ),
+            binding(
+                2,
+                vk::DescriptorType::STORAGE_BUFFER,
+                vk::ShaderStageFlags::COMPUTE,
+            ),
+            binding(
+                3,
+                vk::DescriptorType::UNIFORM_BUFFER,
+                vk::ShaderStageFlags::VERTEX,
+            ),
+        ]
+    }
+}
+
+impl GpuCullingApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[gltf::Vertex::binding_description()],
+            vertex_attributes: &gltf::Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+
+    /// The culling pass runs in the graphic command buffer, ahead of the render pass
+    /// drawing its output
+    fn record_cull_commands(&self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        let device = &self.fixed_vulkan_stuff.device;
+        unsafe {
+            device.cmd_fill_buffer(
+                command_buffer,
+                self.stats_buffers[frame_index].buffer(),
+                0,
+                vk::WHOLE_SIZE,
+                0,
+            );
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.cull_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.cull_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.cull_pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&CullPushConstBlock {
+                    planes: self.frustum_planes,
+                    object_count: self.object_count,
+                    culling_enabled: self.settings.culling as u32,
+                    _padding: [0; 2],
+                }),
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                self.object_count.div_ceil(WORKGROUP_SIZE),
+                1,
+                1,
+            );
+
+            // The draw commands feed the indirect draw, the visible count is read by the host
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(
+                        vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::HOST_READ,
+                    )
+                    .build()],
+                &[],
+                &[],
+            );
+        }
+    }
+}
+
+impl Drop for GpuCullingApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_pipeline(self.cull_pipeline, None);
+            device.destroy_pipeline_layout(self.cull_pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CullingSettings {
+    culling: bool,
+    /// Keeps culling against the frustum of the moment it was frozen, so that the
+    /// camera can move out and look at what was culled
+    freeze_frustum: bool,
+}
+
+impl Default for CullingSettings {
+    fn default() -> Self {
+        Self {
+            culling: true,
+            freeze_frustum: false,
+        }
+    }
+}
+
+impl CullingSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Culling", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.checkbox("Frustum culling", &mut self.culling);
+            ui.checkbox("Freeze frustum", &mut self.freeze_frustum);
+        }
+    }
+}
+
+/// Layout of `Object` in the shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Object {
+    model: Mat4,
+    aabb_min: Vec4,
+    aabb_max: Vec4,
+    /// First index and index count of the primitive
+    indices: [u32; 4],
+}
+
+/// Layout of `SceneUbo` in the shaders
+#[repr(C)]
+struct SceneUbo {
+    view: Mat4,
+    projection: Mat4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CullPushConstBlock {
+    planes: [Vec4; 6],
+    object_count: u32,
+    culling_enabled: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for CullPushConstBlock {}
+unsafe impl bytemuck::Zeroable for CullPushConstBlock {}
+
+/// One object per primitive of each copy of the scene, with its bounds in world space
+fn grid_objects(scene: &Scene, grid_size: usize) -> Vec<Object> {
+    let mut primitives = vec![];
+    scene.walk(|transform, mesh| {
+        for primitive in &mesh.primitives {
+            primitives.push((
+                transform,
+                primitive.aabb,
+                [primitive.first_index, primitive.index_count, 0, 0],
+            ));
+        }
+    });
+    let spacing = scene.aabb().size().max_element().max(f32::EPSILON) * 2.;
+
+    let mut objects = Vec::with_capacity(grid_size * grid_size * primitives.len());
+    for x in 0..grid_size {
+        for z in 0..grid_size {
+            let offset = Mat4::from_translation(vec3(x as f32, 0., z as f32) * spacing);
+            for (transform, aabb, indices) in &primitives {
+                let model = offset * *transform;
+                let aabb = aabb.transformed(model);
+                objects.push(Object {
+                    model,
+                    aabb_min: aabb.min.extend(0.),
+                    aabb_max: aabb.max.extend(0.),
+                    indices: *indices,
+                });
+            }
+        }
+    }
+    objects
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/gpu_culling/scene.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/gpu_culling/scene.frag.spv"
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    // The scene is mirrored along z when loaded, its winding is not relied on
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = GpuCullingApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -0,0 +1,194 @@
+use vulkan_example_rs::{app::HeadlessVulkanStuff, prelude::*};
+
+const EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 800,
+    height: 600,
+};
+const OUTPUT_PATH: &str = "headless_triangle.png";
+
+/// Renders the triangle example once without a window and writes it to a PNG file.
+/// `--validation` turns the validation layer on, which CI machines may not have installed.
+fn main() {
+    let debug_strategy = if std::env::args().any(|arg| arg == "--validation") {
+        VulkanDebugInfoStrategy::DEFAULT_PANIC_ON_ERRORS
+    } else {
+        VulkanDebugInfoStrategy::Idle
+    };
+    let instance = Rc::new(
+        Instance::builder()
+            .app_name_and_version("headless_triangle", 0)
+            .debug_strategy(debug_strategy)
+            .build()
+            .unwrap(),
+    );
+    let headless = HeadlessVulkanStuff::new(instance, EXTENT).unwrap();
+    let device = headless.device.clone();
+
+    let bindings = [vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .descriptor_count(1)
+        .build()];
+    let descriptor_set_layout = unsafe {
+        device
+            .create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder()
+                    .bindings(&bindings)
+                    .build(),
+                None,
+            )
+            .unwrap()
+    };
+    let descriptor_pool = unsafe {
+        device
+            .create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::builder()
+                    .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                        .ty(vk::DescriptorType::UNIFORM_BUFFER)
+                        .descriptor_count(1)
+                        .build()])
+                    .max_sets(1)
+                    .build(),
+                None,
+            )
+            .unwrap()
+    };
+    let descriptor_set = unsafe {
+        device
+            .allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(descriptor_pool)
+                    .set_layouts(&[descriptor_set_layout])
+                    .build(),
+            )
+            .unwrap()[0]
+    };
+
+    let (pipeline_layout, pipeline) = PipelineCreator {
+        device: device.clone(),
+        extent: EXTENT,
+        render_pass: headless.render_target.render_pass(),
+        set_layouts: &[descriptor_set_layout],
+        vertex_bindings: &[Vertex::binding_description()],
+        vertex_attributes: &Vertex::attr_descriptions(),
+        pipeline_cache: headless.pipeline_cache,
+    }
+    .build()
+    .unwrap();
+
+    let vertices = [vec3(-0.5, -0.5, 0.), vec3(0.5, -0.5, 0.), vec3(0., 0.5, 0.)]
+        .map(|v3| Vertex::new(v3).with_color(v3 + vec3(0.5, 0.4, 0.3)));
+    let vertex_buffer = Buffer::device_local_vertex(
+        &vertices,
+        device.clone(),
+        &headless.graphic_command_pool,
+        &device.graphic_queue(),
+    )
+    .unwrap();
+    let indice_buffer = Buffer::device_local_index(
+        &[0u32, 1, 2, 1, 0, 2],
+        device.clone(),
+        &headless.graphic_command_pool,
+        &device.graphic_queue(),
+    )
+    .unwrap();
+
+    let camera = Camera::builder()
+        .translation(Vec3::new(0., 0., -3.))
+        .aspect_ratio(EXTENT.width as f32 / EXTENT.height as f32)
+        .build();
+    let mut uniform_buffer = Buffer::<MVPMatrix>::new(
+        1,
+        vk::BufferUsageFlags::UNIFORM_BUFFER,
+        device.host_write_memory_properties(),
+        device.clone(),
+    )
+    .unwrap();
+    uniform_buffer
+        .load_data(&[camera.mvp_matrix(Mat4::IDENTITY)], 0)
+        .unwrap();
+    DescriptorSetBuilder::new(&bindings, device.clone())
+        .uniform_buffer(0, &uniform_buffer)
+        .update(descriptor_set)
+        .unwrap();
+
+    let clear_values = [
+        vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0., 0., 0., 1.],
+            },
+        },
+        vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.,
+                stencil: 0,
+            },
+        },
+    ];
+    headless
+        .render_frame(&clear_values, |command_buffer| unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_draw_indexed(command_buffer, 6, 1, 0, 0, 0);
+        })
+        .unwrap();
+    headless.save_png(OUTPUT_PATH).unwrap();
+    println!(
+        "Rendered {}x{} on {} to {OUTPUT_PATH}",
+        EXTENT.width,
+        EXTENT.height,
+        device.physical_device_name()
+    );
+
+    unsafe {
+        device.destroy_pipeline(pipeline, None);
+        device.destroy_pipeline_layout(pipeline_layout, None);
+        device.destroy_descriptor_pool(descriptor_pool, None);
+        device.destroy_descriptor_set_layout(descriptor_set_layout, None);
+    }
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "examples/shaders/triangle/shader.vert.spv",
+        "examples/shaders/triangle/shader.frag.spv"
+    );
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use std::{f32::consts::PI, time::Instant};
+
+use vulkan_example_rs::{mesh::InstanceTransform, prelude::*, vulkan_wrappers::InstanceBuffer};
+
+/// Rocks in the belt, all drawn with one instanced draw call
+const MAX_ROCK_COUNT: usize = 16384;
+const BELT_RADII: [f32; 2] = [24., 40.];
+const BELT_THICKNESS: f32 = 3.;
+
+struct InstancingApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    /// Static, placed once at startup
+    instance_buffer: InstanceBuffer<InstanceTransform>,
+    uniform_buffers: PerFrame<Buffer<SceneUbo>>,
+    start: Instant,
+    settings: BeltSettings,
+}
+
+impl WindowApp for InstancingApp {
+    impl_window_fns!(InstancingApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<SceneUbo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        let (vertices, indices) = rock();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&indices)
+            .unwrap();
+        let instance_buffer = InstanceBuffer::from_data(
+            &belt(MAX_ROCK_COUNT),
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        InstancingApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 70., PI, 0.4)
+                .z_limits([0.1, 500.])
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            vertex_buffer,
+            indice_buffer,
+            index_count: indices.len() as u32,
+            instance_buffer,
+            uniform_buffers,
+            start: Instant::now(),
+            settings: BeltSettings::default(),
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let time = self.start.elapsed().as_secs_f32();
+        let view = self.camera.view_mat();
+        self.uniform_buffers[frame.frame_index].load_data_when_mapped(
+            &[SceneUbo {
+                view,
+                projection: self.camera.perspective_mat(),
+                belt: Mat4::from_rotation_y(time * self.settings.belt_speed),
+                camera_position: view.inverse().w_axis.truncate(),
+                time: time * self.settings.spin_speed,
+                light_direction: vec3(-1., -0.6, -0.3).normalize(),
+                _padding: 0.,
+            }],
+            0,
+        );
+
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!(
+                "{} rocks of {} triangles in one draw call",
+                self.settings.rock_count,
+                self.index_count / 3
+            ),
+        ];
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui));
+        self.settings = settings;
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+        }
+        self.instance_buffer.cmd_bind(&device, command_buffer, 1);
+        unsafe {
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            device.cmd_draw_indexed(
+                command_buffer,
+                self.index_count,
+                self.settings
+                    .rock_count
+                    .min(self.instance_buffer.len() as u32),
+                0,
+                0,
+                0,
+            );
+        }
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+            .build()]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build()]
+    }
+}
+
+impl InstancingApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        // Vertices at binding 0, model matrices at binding 1 from location 3
+        let (vertex_bindings, vertex_attributes) = Vertex::with_instances::<InstanceTransform>();
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &vertex_bindings,
+            vertex_attributes: &vertex_attributes,
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+}
+
+impl_drop_trait!(InstancingApp);
+
+#[derive(Clone, Copy)]
+struct BeltSettings {
+    /// Instances drawn, the first ones of the buffer
+    rock_count: u32,
+    /// Of the belt around the up axis, in radians per second
+    belt_speed: f32,
+    /// Scales the spin of every rock around its own axis
+    spin_speed: f32,
+}
+
+impl Default for BeltSettings {
+    fn default() -> Self {
+        Self {
+            rock_count: MAX_ROCK_COUNT as u32 / 2,
+            belt_speed: 0.02,
+            spin_speed: 1.,
+        }
+    }
+}
+
+impl BeltSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Belt", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider("Rocks", 0, MAX_ROCK_COUNT as u32, &mut self.rock_count);
+            ui.slider("Belt speed", -0.2, 0.2, &mut self.belt_speed);
+            ui.slider("Spin speed", 0., 4., &mut self.spin_speed);
+        }
+    }
+}
+
+/// Layout of `SceneUbo` in the shaders
+#[repr(C, align(16))]
+struct SceneUbo {
+    view: Mat4,
+    projection: Mat4,
+    belt: Mat4,
+    camera_position: Vec3,
+    time: f32,
+    light_direction: Vec3,
+    _padding: f32,
+}
+
+/// Xorshift, enough to scatter rocks without a dependency
+struct Rng(u32);
+
+impl Rng {
+    /// Uniform in [min, max)
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        min + (max - min) * (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn unit_vector(&mut self) -> Vec3 {
+        let z = self.range(-1., 1.);
+        let angle = self.range(0., 2. * PI);
+        let r = (1. - z * z).sqrt();
+        vec3(r * angle.cos(), r * angle.sin(), z)
+    }
+}
+
+/// Icosahedron with its corners pushed in and out, the instances scale it unevenly
+/// so the rocks do not look alike
+fn rock() -> (Vec<Vertex>, Vec<u32>) {
+    let t = (1. + 5f32.sqrt()) / 2.;
+    let corners = [
+        vec3(-1., t, 0.),
+        vec3(1., t, 0.),
+        vec3(-1., -t, 0.),
+        vec3(1., -t, 0.),
+        vec3(0., -1., t),
+        vec3(0., 1., t),
+        vec3(0., -1., -t),
+        vec3(0., 1., -t),
+        vec3(t, 0., -1.),
+        vec3(t, 0., 1.),
+        vec3(-t, 0., -1.),
+        vec3(-t, 0., 1.),
+    ];
+    let mut rng = Rng(0x2545_f491);
+    let vertices = corners
+        .into_iter()
+        .map(|corner| {
+            let shade = rng.range(0.8, 1.);
+            Vertex::new(corner.normalize() * rng.range(0.75, 1.15)).with_color(Vec3::splat(shade))
+        })
+        .collect();
+    let indices = vec![
+        0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11, 1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7,
+        1, 8, 3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9, 4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9,
+        8, 1,
+    ];
+    (vertices, indices)
+}
+
+/// Rocks scattered in a flat ring around the origin, denser towards its middle radius
+fn belt(count: usize) -> Vec<InstanceTransform> {
+    let mut rng = Rng(0x9e37_79b9);
+    (0..count)
+        .map(|_| {
+            let angle = rng.range(0., 2. * PI);
+            let radius = (rng.range(BELT_RADII[0], BELT_RADII[1])
+                + rng.range(BELT_RADII[0], BELT_RADII[1]))
+                / 2.;
+            let height = rng.range(-1., 1.) * BELT_THICKNESS / 2.;
+            let size = rng.range(0.15, 0.8);
+            let scale = Vec3::splat(size)
+                * vec3(rng.range(0.7, 1.3), rng.range(0.6, 1.), rng.range(0.7, 1.3));
+            let rotation = Quat::from_axis_angle(rng.unit_vector(), rng.range(0., 2. * PI));
+            InstanceTransform {
+                model: Mat4::from_scale_rotation_translation(
+                    scale,
+                    rotation,
+                    vec3(radius * angle.cos(), height, radius * angle.sin()),
+                ),
+            }
+        })
+        .collect()
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/instancing/rock.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/instancing/rock.frag.spv"
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    // Flat shading does not depend on the winding, which the projection flips
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = InstancingApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
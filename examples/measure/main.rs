@@ -0,0 +1,298 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{
+    app::{DebugLines, ProceduralPattern, SimpleMeshRenderer, TextureGenerator},
+    mesh::{ray_cast, RayHit},
+    prelude::*,
+};
+
+const MODEL_PATH: &str = "examples/meshes/viking_room/viking_room.obj";
+const TEXTURE_PATH: &str = "examples/textures/viking_room/viking_room.png";
+
+const HOVER_COLOR: Vec3 = Vec3::new(1., 0.9, 0.2);
+const NORMAL_COLOR: Vec3 = Vec3::new(0.2, 0.9, 1.);
+const RAY_COLOR: Vec3 = Vec3::new(1., 0.3, 0.3);
+const MEASURE_COLOR: Vec3 = Vec3::new(0.3, 1., 0.4);
+
+struct MeasureApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    mesh_renderer: SimpleMeshRenderer,
+    debug_lines: DebugLines,
+    /// Triangles the cursor ray is cast against
+    model_positions: Vec<Vec3>,
+    model_indices: Vec<u32>,
+
+    /// Under the cursor in the last frame
+    hover: Option<RayHit>,
+    /// Origin and hit of the ray of the last click, seen from the side once the camera moves
+    click_ray: Option<(Vec3, RayHit)>,
+    /// Clicked hits, the distance is measured once there are two
+    points: Vec<RayHit>,
+    settings: MeasureSettings,
+}
+
+impl WindowApp for MeasureApp {
+    impl_window_fns!(MeasureApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        let device = fixed_vulkan_stuff.device.clone();
+        let (model_vertices, model_indices) =
+            vulkan_example_rs::mesh::load_obj_model(MODEL_PATH).unwrap();
+
+        let texture = Texture::from_rgba8_picture(
+            TEXTURE_PATH,
+            device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &device.graphic_queue(),
+        )
+        .and_then(|mut texture| {
+            texture.spawn_image_view()?;
+            texture.spawn_sampler(vk::Filter::LINEAR)?;
+            Ok(texture)
+        })
+        .unwrap_or_else(|e| {
+            println!("Fail to load {TEXTURE_PATH}, using a generated texture: {e}");
+            TextureGenerator::new(fixed_vulkan_stuff.pipeline_cache, device.clone())
+                .and_then(|generator| {
+                    generator.generate(
+                        &ProceduralPattern::MISSING,
+                        256,
+                        256,
+                        &fixed_vulkan_stuff.graphic_command_pool,
+                        &device.graphic_queue(),
+                    )
+                })
+                .unwrap()
+        });
+
+        let mesh_renderer = SimpleMeshRenderer::builder(
+            "examples/shaders/viking_room/shader.vert.spv",
+            "examples/shaders/viking_room/shader.frag.spv",
+        )
+        .texture(texture)
+        .build(&fixed_vulkan_stuff, &model_vertices, &model_indices)
+        .unwrap();
+        let debug_lines = DebugLines::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 64).unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        MeasureApp {
+            window,
+
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 3., PI, 0.)
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+
+            fixed_vulkan_stuff,
+            mesh_renderer,
+            debug_lines,
+            model_positions: model_vertices.iter().map(Vertex::pos).collect(),
+            model_indices,
+
+            hover: None,
+            click_ray: None,
+            points: vec![],
+            settings: MeasureSettings::default(),
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.hover = self
+            .camera_controller
+            .cursor_position()
+            .and_then(|cursor| self.cast(cursor).1);
+        if let Some(click) = self.camera_controller.take_click() {
+            if let (origin, Some(hit)) = self.cast(click) {
+                self.click_ray = Some((origin, hit));
+                if self.points.len() == 2 {
+                    self.points.clear();
+                }
+                self.points.push(hit);
+            }
+        }
+        self.queue_debug_lines();
+        self.mesh_renderer
+            .update_mvp(frame.frame_index, &self.camera.mvp_matrix(Mat4::IDENTITY));
+
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        let mut settings = self.settings;
+        let (hover, points) = (self.hover, self.points.clone());
+        let mut clear = false;
+        self.update_ui_with(&[name], |ui| clear = settings.draw(ui, hover, &points));
+        self.settings = settings;
+        if clear {
+            self.points.clear();
+            self.click_ray = None;
+        }
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        self.mesh_renderer.cmd_draw(command_buffer, frame_index);
+        self.debug_lines
+            .cmd_draw(
+                command_buffer,
+                frame_index,
+                self.camera.perspective_mat() * self.camera.view_mat(),
+            )
+            .unwrap();
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.mesh_renderer
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        self.debug_lines
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![]
+    }
+}
+
+impl MeasureApp {
+    /// Origin of the ray through `cursor` and where it first hits the room
+    fn cast(&self, cursor: Vec2) -> (Vec3, Option<RayHit>) {
+        let extent = self.fixed_vulkan_stuff.surface.extent();
+        let (origin, direction) = self
+            .camera
+            .screen_ray(cursor, Vec2::new(extent.width as f32, extent.height as f32));
+        let hit = ray_cast(
+            &self.model_positions,
+            &self.model_indices,
+            origin,
+            direction,
+        );
+        (origin, hit)
+    }
+
+    fn queue_debug_lines(&mut self) {
+        let size = self.settings.marker_size;
+        if let Some(hover) = self.hover {
+            self.debug_lines.cross(hover.position, size, HOVER_COLOR);
+            if self.settings.show_normal {
+                self.debug_lines.line(
+                    hover.position,
+                    hover.position + hover.normal * size * 4.,
+                    NORMAL_COLOR,
+                );
+            }
+        }
+        if let (true, Some((origin, hit))) = (self.settings.show_click_ray, self.click_ray) {
+            self.debug_lines.line(origin, hit.position, RAY_COLOR);
+        }
+        for point in &self.points {
+            self.debug_lines.cross(point.position, size, MEASURE_COLOR);
+        }
+        if let [a, b] = self.points[..] {
+            self.debug_lines.line(a.position, b.position, MEASURE_COLOR);
+        }
+    }
+}
+
+impl Drop for MeasureApp {
+    fn drop(&mut self) {
+        unsafe { self.fixed_vulkan_stuff.device.device_wait_idle().unwrap() };
+    }
+}
+
+#[derive(Clone, Copy)]
+struct MeasureSettings {
+    show_normal: bool,
+    show_click_ray: bool,
+    /// Of the crosses marking points, in world units
+    marker_size: f32,
+}
+
+impl Default for MeasureSettings {
+    fn default() -> Self {
+        Self {
+            show_normal: true,
+            show_click_ray: true,
+            marker_size: 0.05,
+        }
+    }
+}
+
+impl MeasureSettings {
+    /// Returns whether the measured points are to be cleared
+    fn draw(&mut self, ui: &imgui::Ui, hover: Option<RayHit>, points: &[RayHit]) -> bool {
+        let mut clear = false;
+        if ui.collapsing_header("Measure", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            match hover {
+                Some(hit) => {
+                    ui.text(format!("Hover {:.3}", hit.position));
+                    ui.text(format!("Normal {:.3}", hit.normal));
+                    ui.text(format!("{:.3} from the camera", hit.distance));
+                }
+                None => ui.text("Nothing under the cursor"),
+            }
+            ui.separator();
+            match points {
+                [] => ui.text("Click the room to place a first point"),
+                [a] => {
+                    ui.text(format!("A {:.3}", a.position));
+                    ui.text("Click a second point");
+                }
+                [a, b, ..] => {
+                    ui.text(format!("A {:.3}", a.position));
+                    ui.text(format!("B {:.3}", b.position));
+                    ui.text(format!("Distance {:.4}", a.position.distance(b.position)));
+                }
+            }
+            ui.checkbox("Hit normal", &mut self.show_normal);
+            ui.checkbox("Ray of the last click", &mut self.show_click_ray);
+            ui.slider("Marker size", 0.005, 0.2, &mut self.marker_size);
+            clear = ui.button("Clear");
+        }
+        clear
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = MeasureApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
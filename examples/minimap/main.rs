@@ -0,0 +1,404 @@
+use std::f32::consts::FRAC_PI_2;
+
+use vulkan_example_rs::{
+    app::{InsetPushConstBlock, PictureInPicture},
+    prelude::*,
+};
+
+const MINIMAP_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 400,
+    height: 400,
+};
+
+struct MinimapApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    #[allow(dead_code)]
+    model_vertices: Vec<Vertex>,
+    model_indices: Vec<u32>,
+
+    camera: Camera,
+    camera_controller: CameraController,
+    minimap_camera: Camera,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    minimap_descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    uniform_buffers: PerFrame<Buffer<MVPMatrix>>,
+    minimap_uniform_buffers: PerFrame<Buffer<MVPMatrix>>,
+    #[allow(dead_code)]
+    texture_image: Texture,
+
+    minimap_target: OffscreenRenderTarget,
+    picture_in_picture: PictureInPicture,
+}
+
+impl WindowApp for MinimapApp {
+    impl_window_fns!(MinimapApp);
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.uniform_buffers[frame.frame_index]
+            .load_data_when_mapped(&[self.camera.mvp_matrix(Mat4::IDENTITY)], 0);
+        self.minimap_uniform_buffers[frame.frame_index]
+            .load_data_when_mapped(&[self.minimap_camera.mvp_matrix(Mat4::IDENTITY)], 0);
+
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        self.update_ui(&[name]);
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let indice_num = self.model_indices.len() as u32;
+        unsafe {
+            // Secondary camera into the offscreen target
+            self.minimap_target
+                .cmd_begin_render_pass(command_buffer, &Self::clear_value().to_array());
+            self.minimap_target
+                .cmd_set_viewport_and_scissor(command_buffer);
+            self.cmd_draw_scene(
+                command_buffer,
+                self.minimap_descriptor_sets[frame_index],
+                indice_num,
+            );
+            self.fixed_vulkan_stuff
+                .device
+                .cmd_end_render_pass(command_buffer);
+
+            // Main camera into the swapchain image
+            self.fixed_vulkan_stuff.cmd_begin_renderpass(
+                frame_index,
+                image_index,
+                &Self::clear_value(),
+            );
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+            self.cmd_draw_scene(
+                command_buffer,
+                self.descriptor_sets[frame_index],
+                indice_num,
+            );
+
+            self.picture_in_picture.draw(
+                command_buffer,
+                &InsetPushConstBlock::top_right(
+                    self.fixed_vulkan_stuff.surface.extent(),
+                    MINIMAP_EXTENT,
+                    20.,
+                    4.,
+                    Vec4::new(1., 0., 0., 1.),
+                ),
+            );
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+        }
+    }
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+        let minimap_descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        // Same attachment formats as the swapchain render pass, so the render passes are
+        // compatible and the scene pipeline can be used for both cameras
+        let minimap_target = OffscreenRenderTarget::new(
+            MINIMAP_EXTENT,
+            fixed_vulkan_stuff.surface.format(),
+            fixed_vulkan_stuff.depth_stencil.format(),
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .unwrap();
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        let (model_vertices, model_indices) =
+            vulkan_example_rs::mesh::load_obj_model("examples/meshes/viking_room/viking_room.obj")
+                .unwrap();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&model_vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&model_indices)
+            .unwrap();
+
+        let create_uniform_buffer = |_| {
+            let mut buffer = Buffer::<MVPMatrix>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        };
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(create_uniform_buffer);
+        let minimap_uniform_buffers = fixed_vulkan_stuff.per_frame(create_uniform_buffer);
+
+        let mut texture_image = Texture::from_rgba8_picture(
+            "examples/textures/viking_room/viking_room.png",
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap();
+        texture_image.spawn_image_view().unwrap();
+        texture_image.spawn_sampler(vk::Filter::LINEAR).unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (sets, buffers) in [
+            (&descriptor_sets, &uniform_buffers),
+            (&minimap_descriptor_sets, &minimap_uniform_buffers),
+        ] {
+            for (set, buffer) in sets.iter().zip(buffers.iter()) {
+                DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                    .uniform_buffer(0, buffer)
+                    .combined_image_sampler(1, &texture_image)
+                    .update(*set)
+                    .unwrap();
+            }
+        }
+
+        let picture_in_picture = PictureInPicture::from_fixed_vulkan_stuff(
+            &fixed_vulkan_stuff,
+            minimap_target.color_descriptor(),
+        )
+        .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        MinimapApp {
+            window,
+
+            frame_counter: FrameCounter::default(),
+
+            model_vertices,
+            model_indices,
+
+            camera: Camera::builder()
+                .translation(Vec3::new(0., 0., -3.))
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            // Looks straight down onto the scene from above
+            minimap_camera: Camera::builder()
+                .translation(Vec3::new(0., 0., -3.))
+                .rotation((FRAC_PI_2, 0., 0.))
+                .build(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            minimap_descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            vertex_buffer,
+            indice_buffer,
+            uniform_buffers,
+            minimap_uniform_buffers,
+            texture_image,
+            minimap_target,
+            picture_in_picture,
+            ui_overlay,
+        }
+    }
+
+    /// The minimap target follows the swapchain format, so the scene pipeline stays
+    /// compatible with both render passes
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.picture_in_picture
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        if change.format_changed {
+            self.minimap_target = OffscreenRenderTarget::new(
+                MINIMAP_EXTENT,
+                self.fixed_vulkan_stuff.surface.format(),
+                self.fixed_vulkan_stuff.depth_stencil.format(),
+                self.fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            self.picture_in_picture
+                .set_source(self.minimap_target.color_descriptor());
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![
+            vk::DescriptorType::UNIFORM_BUFFER,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ]
+        .into_iter()
+        .map(|ty| {
+            vk::DescriptorPoolSize::builder()
+                .ty(ty)
+                .descriptor_count(2 * FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build()
+        })
+        .collect()
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        let ubo_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .descriptor_count(1)
+            .build();
+        let sampler_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        vec![ubo_layout_binding, sampler_layout_binding]
+    }
+
+    /// One set per camera per frame in flight
+    fn create_descriptor_pool(device: &Device) -> RenderResult<vk::DescriptorPool> {
+        let pool_sizes = Self::descriptor_pool_sizes();
+        let create_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(2 * FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+            .build();
+        Ok(unsafe { device.create_descriptor_pool(&create_info, None)? })
+    }
+}
+
+impl MinimapApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn cmd_draw_scene(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: vk::DescriptorSet,
+        indice_num: u32,
+    ) {
+        unsafe {
+            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.fixed_vulkan_stuff.device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.vertex_buffer.buffer()],
+                &[0],
+            );
+            self.fixed_vulkan_stuff.device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            self.fixed_vulkan_stuff.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            self.fixed_vulkan_stuff
+                .device
+                .cmd_draw_indexed(command_buffer, indice_num, 1, 0, 0, 0);
+        }
+    }
+}
+
+impl_drop_trait!(MinimapApp);
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/viking_room/shader.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/viking_room/shader.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = MinimapApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -0,0 +1,382 @@
+use std::{
+    f32::consts::PI,
+    time::{Duration, Instant},
+};
+
+use vulkan_example_rs::{
+    app::SecondaryInheritance, ash::prelude::VkResult, jobs::JobSystem, mesh::primitives,
+    prelude::*, vulkan_wrappers::PushConstants,
+};
+
+/// Cubes per side of the grid, split into one chunk per recording thread
+const MAX_GRID_SIZE: u32 = 64;
+const CUBE_SPACING: f32 = 2.;
+
+struct MultithreadedRecordingApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    push_constants: PushConstants<CubePush>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    /// Threads recording chunks, the command pool after theirs is the overlay's
+    max_chunks: u32,
+    start: Instant,
+    record_time: Duration,
+    settings: RecordingSettings,
+}
+
+impl WindowApp for MultithreadedRecordingApp {
+    impl_window_fns!(MultithreadedRecordingApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let mut fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        let max_chunks = JobSystem::global().thread_count();
+        fixed_vulkan_stuff
+            .enable_secondary_command_pools(max_chunks + 1)
+            .unwrap();
+
+        let push_constants =
+            PushConstants::new(vk::ShaderStageFlags::VERTEX, &fixed_vulkan_stuff.device).unwrap();
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, push_constants);
+
+        let (vertices, indices) = primitives::cube();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&indices)
+            .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        MultithreadedRecordingApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 80., PI / 4., 0.6)
+                .z_limits([0.1, 500.])
+                .move_speed(80.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            push_constants,
+            pipeline_layout,
+            pipeline,
+            vertex_buffer,
+            indice_buffer,
+            index_count: indices.len() as u32,
+            max_chunks: max_chunks as u32,
+            start: Instant::now(),
+            record_time: Duration::ZERO,
+            settings: RecordingSettings {
+                grid_size: 32,
+                chunk_count: max_chunks as u32,
+                speed: 1.,
+                tint_chunks: true,
+            },
+        }
+    }
+
+    fn prepare(&mut self, _frame: &FrameContext) {
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!(
+                "{} draws recorded on {} threads in {:.2} ms",
+                self.settings.grid_size * self.settings.grid_size,
+                self.settings.chunk_count,
+                self.record_time.as_secs_f64() * 1000.
+            ),
+        ];
+        let max_chunks = self.max_chunks;
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui, max_chunks));
+        self.settings = settings;
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        self.record_render_commands(frame.frame_index, frame.image_index)
+            .unwrap();
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.push_constants);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![]
+    }
+}
+
+impl MultithreadedRecordingApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        push_constants: PushConstants<CubePush>,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+            push_constant_range: push_constants.range(),
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn record_render_commands(&mut self, frame_index: usize, image_index: usize) -> VkResult<()> {
+        let device = self.fixed_vulkan_stuff.device.clone();
+        let inheritance = self.fixed_vulkan_stuff.secondary_inheritance(image_index);
+        let chunk_count = self.settings.chunk_count as usize;
+        let pools = self
+            .fixed_vulkan_stuff
+            .secondary_command_pools
+            .as_mut()
+            .unwrap();
+        let mut secondaries = (0..chunk_count)
+            .map(|thread| Ok(pools.command_buffers(thread, frame_index, 1)?[0]))
+            .collect::<VkResult<Vec<_>>>()?;
+        let overlay_command_buffer =
+            pools.command_buffers(self.max_chunks as usize, frame_index, 1)?[0];
+
+        let recorder = ChunkRecorder {
+            device: (**device).clone(),
+            inheritance,
+            pipeline: self.pipeline,
+            pipeline_layout: self.pipeline_layout,
+            push_constants: self.push_constants,
+            vertex_buffer: self.vertex_buffer.buffer(),
+            indice_buffer: self.indice_buffer.buffer(),
+            index_count: self.index_count,
+            grid_size: self.settings.grid_size,
+            chunk_count: self.settings.chunk_count,
+            time: self.start.elapsed().as_secs_f32() * self.settings.speed,
+            view_projection: self.camera.perspective_mat() * self.camera.view_mat(),
+            tint_chunks: self.settings.tint_chunks,
+        };
+        let started = Instant::now();
+        // Each chunk records into the pool of its own index, so no pool is shared
+        JobSystem::global()
+            .map(
+                "record chunk",
+                secondaries.clone().into_iter().enumerate(),
+                move |(chunk, command_buffer)| recorder.record(chunk, command_buffer),
+            )
+            .into_iter()
+            .collect::<VkResult<()>>()?;
+        self.record_time = started.elapsed();
+
+        // The overlay can not be drawn inline in a pass executing secondaries
+        inheritance.begin(
+            &device,
+            overlay_command_buffer,
+            vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        )?;
+        self.ui_overlay.draw(overlay_command_buffer, frame_index);
+        unsafe { device.end_command_buffer(overlay_command_buffer)? };
+        secondaries.push(overlay_command_buffer);
+
+        self.fixed_vulkan_stuff
+            .cmd_begin_renderpass_for_secondaries(frame_index, image_index, &Self::clear_value());
+        self.fixed_vulkan_stuff
+            .cmd_execute_secondaries(frame_index, &secondaries);
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+        Ok(())
+    }
+}
+
+impl Drop for MultithreadedRecordingApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+/// Everything a worker thread needs to record a chunk, plain handles and values
+struct ChunkRecorder {
+    device: ash::Device,
+    inheritance: SecondaryInheritance,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    push_constants: PushConstants<CubePush>,
+    vertex_buffer: vk::Buffer,
+    indice_buffer: vk::Buffer,
+    index_count: u32,
+    grid_size: u32,
+    chunk_count: u32,
+    time: f32,
+    view_projection: Mat4,
+    tint_chunks: bool,
+}
+
+impl ChunkRecorder {
+    /// Draws the cubes of `chunk`, a contiguous run of the grid in row order
+    fn record(&self, chunk: usize, command_buffer: vk::CommandBuffer) -> VkResult<()> {
+        let device = &self.device;
+        self.inheritance.begin(
+            device,
+            command_buffer,
+            vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        )?;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer,
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+
+        let cube_count = (self.grid_size * self.grid_size) as usize;
+        let chunk_count = self.chunk_count as usize;
+        let cubes = cube_count * chunk / chunk_count..cube_count * (chunk + 1) / chunk_count;
+        let chunk_hue = chunk as f32 / chunk_count as f32;
+        for cube in cubes {
+            let (x, z) = (cube as u32 / self.grid_size, cube as u32 % self.grid_size);
+            let mut push = cube_push(x, z, self.grid_size, self.time, self.view_projection);
+            if self.tint_chunks {
+                push.color = hue_to_rgb(chunk_hue).extend(1.);
+            }
+            self.push_constants
+                .cmd_push(device, command_buffer, self.pipeline_layout, &push);
+            unsafe { device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0) };
+        }
+        unsafe { device.end_command_buffer(command_buffer) }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct RecordingSettings {
+    grid_size: u32,
+    /// Chunks recorded at once, one per worker thread
+    chunk_count: u32,
+    speed: f32,
+    /// Colors every cube by the chunk recording it
+    tint_chunks: bool,
+}
+
+impl RecordingSettings {
+    fn draw(&mut self, ui: &imgui::Ui, max_chunks: u32) {
+        if ui.collapsing_header("Recording", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider("Grid size", 1, MAX_GRID_SIZE, &mut self.grid_size);
+            ui.slider("Threads", 1, max_chunks, &mut self.chunk_count);
+            ui.slider("Speed", 0., 4., &mut self.speed);
+            ui.checkbox("Tint by thread", &mut self.tint_chunks);
+        }
+    }
+}
+
+/// Same block as the push constants example, whose shaders this one uses
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CubePush {
+    mvp: Mat4,
+    color: Vec4,
+}
+
+unsafe impl bytemuck::Pod for CubePush {}
+unsafe impl bytemuck::Zeroable for CubePush {}
+
+fn cube_push(x: u32, z: u32, grid_size: u32, time: f32, view_projection: Mat4) -> CubePush {
+    let half = (grid_size - 1) as f32 / 2.;
+    let position = vec3(x as f32 - half, 0., z as f32 - half) * CUBE_SPACING;
+    let phase = (x * 7 + z * 13) as f32 * 0.37;
+    let model = Mat4::from_translation(position + Vec3::Y * (time * 2. + phase).sin() * 0.5)
+        * Mat4::from_rotation_y(time + phase);
+    CubePush {
+        mvp: view_projection * model,
+        color: hue_to_rgb((time * 0.1 + phase / (2. * PI)).fract()).extend(1.),
+    }
+}
+
+fn hue_to_rgb(hue: f32) -> Vec3 {
+    let channel = |offset: f32| {
+        let k = (hue * 6. + offset) % 6.;
+        1. - (k.min(4. - k).clamp(0., 1.))
+    };
+    vec3(channel(5.), channel(3.), channel(1.))
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    push_constant_range: vk::PushConstantRange,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/push_constants/cube.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/push_constants/cube.frag.spv"
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![self.push_constant_range]
+    }
+
+    // The projection flips the winding, both faces go through the depth test instead
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = MultithreadedRecordingApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
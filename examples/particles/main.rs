@@ -0,0 +1,494 @@
+use vulkan_example_rs::prelude::*;
+
+const PARTICLE_COUNT: u32 = 64 * 1024;
+/// Must match `local_size_x` of the compute shader
+const WORKGROUP_SIZE: u32 = 256;
+
+struct ParticlesApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+    simulation: SimulationSettings,
+    simulation_time: f32,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    compute_pipeline_layout: vk::PipelineLayout,
+    compute_pipeline: vk::Pipeline,
+    // The compute pass of a frame reads the particles of the previous frame and writes its own,
+    // which are then drawn as vertex buffer
+    particle_buffers: PerFrame<Buffer<Particle>>,
+}
+
+impl WindowApp for ParticlesApp {
+    impl_window_fns!(ParticlesApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let mut fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        if let Err(e) = fixed_vulkan_stuff.enable_dynamic_rendering() {
+            println!("Rendering with a render pass: {}", e);
+        }
+
+        let initial_particles = initial_particles(PARTICLE_COUNT);
+        let particle_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            fixed_vulkan_stuff
+                .device_local_storage_buffer(&initial_particles)
+                .unwrap()
+        });
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (frame_index, descriptor_set) in descriptor_sets.iter().enumerate() {
+            let last_frame_index = (frame_index + FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT - 1)
+                % FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT;
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .storage_buffer(0, &particle_buffers[last_frame_index])
+                .storage_buffer(1, &particle_buffers[frame_index])
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (compute_pipeline_layout, compute_pipeline) = ComputePipelineBuilder::with_spv_path(
+            "examples/shaders/particles/particles.comp.spv",
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .set_layouts(&[descriptor_set_layout])
+        .push_constant_ranges(&[vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(std::mem::size_of::<SimulationPushConstBlock>() as u32)
+            .build()])
+        .pipeline_cache(fixed_vulkan_stuff.pipeline_cache)
+        .build()
+        .unwrap();
+
+        let (pipeline_layout, pipeline) = Self::create_pipeline(&fixed_vulkan_stuff);
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        ParticlesApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .translation(Vec3::new(0., 0., -3.))
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            simulation: SimulationSettings::default(),
+            simulation_time: 0.,
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            compute_pipeline_layout,
+            compute_pipeline,
+            particle_buffers,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        let mut simulation = self.simulation;
+        self.update_ui_with(&[name, format!("particles: {PARTICLE_COUNT}")], |ui| {
+            simulation.draw(ui)
+        });
+        self.simulation = simulation;
+
+        // A paused simulation still runs with a zero time step, so that the particles
+        // of the previous frame keep being copied to the buffer drawn by this frame
+        let delta_time = if self.simulation.paused {
+            0.
+        } else {
+            (self.frame_counter().fps.recip() as f32).min(1. / 30.)
+        };
+        self.simulation_time += delta_time;
+
+        self.record_compute_commands(frame.frame_index, delta_time);
+        self.fixed_vulkan_stuff
+            .frame_compute_queue_submit(frame.frame_index)
+            .unwrap();
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        unsafe {
+            if self.fixed_vulkan_stuff.uses_dynamic_rendering() {
+                self.fixed_vulkan_stuff
+                    .cmd_begin_rendering(frame_index, image_index, &Self::clear_value())
+                    .unwrap();
+            } else {
+                self.fixed_vulkan_stuff.cmd_begin_renderpass(
+                    frame_index,
+                    image_index,
+                    &Self::clear_value(),
+                );
+            }
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+
+            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.fixed_vulkan_stuff.device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.particle_buffers[frame_index].buffer()],
+                &[0],
+            );
+            self.fixed_vulkan_stuff
+                .device
+                .cmd_draw(command_buffer, PARTICLE_COUNT, 1, 0, 0);
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            if self.fixed_vulkan_stuff.uses_dynamic_rendering() {
+                self.fixed_vulkan_stuff
+                    .cmd_end_rendering(frame_index, image_index);
+            } else {
+                self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+            }
+        }
+    }
+
+    /// The color format rendered to is the surface format, the simulation does not depend on it
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) = Self::create_pipeline(&self.fixed_vulkan_stuff);
+        }
+    }
+
+    fn vulkan_api_version() -> VulkanApiVersion {
+        // Dynamic rendering is core in Vulkan 1.3, older devices fall back to the extension
+        VulkanApiVersion::V1_3
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32 * 2)
+            .build()]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        (0..2)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .descriptor_count(1)
+                    .build()
+            })
+            .collect()
+    }
+}
+
+impl ParticlesApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[],
+            vertex_bindings: &[Particle::binding_description()],
+            vertex_attributes: &Particle::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+            dynamic_rendering_formats: fixed_vulkan_stuff.dynamic_rendering_formats(),
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn record_compute_commands(&mut self, frame_index: usize, delta_time: f32) {
+        let command_buffer = self.fixed_vulkan_stuff.compute_command_buffers[frame_index];
+        let device = &self.fixed_vulkan_stuff.device;
+        unsafe {
+            device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())
+                .expect("Fail to reset command buffer");
+            device
+                .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())
+                .expect("Fail to begin command buffer");
+
+            // The particles read here were written by the compute submission of the last frame
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.compute_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.compute_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.compute_pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&SimulationPushConstBlock {
+                    delta_time,
+                    time: self.simulation_time,
+                    attractor_strength: self.simulation.attractor_strength,
+                    drag: self.simulation.drag,
+                    particle_count: PARTICLE_COUNT,
+                }),
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                PARTICLE_COUNT.div_ceil(WORKGROUP_SIZE),
+                1,
+                1,
+            );
+
+            device
+                .end_command_buffer(command_buffer)
+                .expect("Fail to end command buffer");
+        }
+    }
+}
+
+impl Drop for ParticlesApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_pipeline(self.compute_pipeline, None);
+            device.destroy_pipeline_layout(self.compute_pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+/// Matches the std430 layout of `Particle` in the compute shader
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    color: Vec4,
+}
+
+impl Particle {
+    fn binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(0)
+            .stride(std::mem::size_of::<Particle>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build()
+    }
+
+    fn attr_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(0)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(memoffset::offset_of!(Particle, position) as u32)
+                .build(),
+            vk::VertexInputAttributeDescription::builder()
+                .binding(0)
+                .location(1)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(memoffset::offset_of!(Particle, color) as u32)
+                .build(),
+        ]
+    }
+}
+
+/// Particles on a ring, slowly orbiting around the center
+fn initial_particles(count: u32) -> Vec<Particle> {
+    // Xorshift, good enough to scatter particles without a rand dependency
+    let mut state = 0x2545_f491_u32;
+    let mut next_random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+
+    (0..count)
+        .map(|_| {
+            let angle = next_random() * std::f32::consts::TAU;
+            let radius = 0.4 + 0.3 * next_random();
+            let direction = Vec2::from_angle(angle);
+            Particle {
+                position: direction * radius,
+                velocity: direction.perp() * 0.2,
+                color: Vec4::new(0.1, 0.3, 1., 0.8),
+            }
+        })
+        .collect()
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SimulationPushConstBlock {
+    delta_time: f32,
+    time: f32,
+    attractor_strength: f32,
+    drag: f32,
+    particle_count: u32,
+}
+
+unsafe impl bytemuck::Pod for SimulationPushConstBlock {}
+unsafe impl bytemuck::Zeroable for SimulationPushConstBlock {}
+
+#[derive(Clone, Copy)]
+struct SimulationSettings {
+    attractor_strength: f32,
+    drag: f32,
+    paused: bool,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            attractor_strength: 0.3,
+            drag: 0.5,
+            paused: false,
+        }
+    }
+}
+
+impl SimulationSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Simulation", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider("Attractor strength", 0., 2., &mut self.attractor_strength);
+            ui.slider("Drag", 0., 4., &mut self.drag);
+            ui.checkbox("Paused", &mut self.paused);
+        }
+    }
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    dynamic_rendering_formats: Option<(Vec<vk::Format>, vk::Format)>,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        self.dynamic_rendering_formats.clone()
+    }
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/particles/particle.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/particles/particle.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    fn input_assembly_state_create_info(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::POINT_LIST)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    /// Additive blending, overlapping particles get brighter
+    fn color_blend_attach_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = ParticlesApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
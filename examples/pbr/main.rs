@@ -0,0 +1,526 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{
+    app::{DebugPush, DebugView, IblResources},
+    gltf::{self, MaterialFactors, Scene},
+    prelude::*,
+};
+
+const DEFAULT_SCENE_PATH: &str = "examples/meshes/cube/cube.gltf";
+/// Texels per side of the procedural sky cubemap
+const ENVIRONMENT_SIZE: u32 = 256;
+const SKYBOX_FRAG_SPV_PATH: &str = "examples/shaders/pbr/skybox.frag.spv";
+
+struct PbrApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    /// Same layout as `pipeline`, with its own handle
+    overdraw_pipeline_layout: vk::PipelineLayout,
+    overdraw_pipeline: vk::Pipeline,
+    debug_view: DebugView,
+    uniform_buffers: PerFrame<Buffer<SceneUbo>>,
+    scene_path: String,
+    scene: Scene,
+    light: Light,
+    /// Both baked once, the sun of the sky does not follow the light
+    environment: Texture,
+    ibl: IblResources,
+    skybox: FullscreenPass,
+    skybox_push_const_block: SkyboxPushConstBlock,
+}
+
+impl WindowApp for PbrApp {
+    impl_window_fns!(PbrApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let scene_path = std::env::args()
+            .skip(1)
+            .find(|arg| !arg.starts_with("--"))
+            .unwrap_or_else(|| DEFAULT_SCENE_PATH.to_owned());
+        let scene = Scene::load(
+            &scene_path,
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap_or_else(|e| panic!("Fail to load {scene_path}: {e}"));
+
+        // An equirectangular .hdr panorama given with `--environment=<path>` replaces the sky
+        let light = Light::default();
+        let environment = match std::env::args()
+            .find_map(|arg| arg.strip_prefix("--environment=").map(str::to_owned))
+        {
+            Some(path) => Texture::cubemap_from_equirect_hdr(
+                &path,
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            )
+            .unwrap_or_else(|e| panic!("Fail to load {path}: {e}")),
+            None => IblResources::procedural_sky(
+                ENVIRONMENT_SIZE,
+                -light.direction(),
+                fixed_vulkan_stuff.pipeline_cache,
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            )
+            .unwrap(),
+        };
+        let ibl = IblResources::generate(
+            &environment,
+            fixed_vulkan_stuff.pipeline_cache,
+            fixed_vulkan_stuff.device.clone(),
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &fixed_vulkan_stuff.device.graphic_queue(),
+        )
+        .unwrap();
+        let skybox = Self::create_skybox(&fixed_vulkan_stuff, &environment);
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<SceneUbo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .combined_image_sampler(1, &ibl.irradiance)
+                .combined_image_sampler(2, &ibl.prefiltered)
+                .combined_image_sampler(3, &ibl.brdf_lut)
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let ((pipeline_layout, pipeline), (overdraw_pipeline_layout, overdraw_pipeline)) =
+            Self::create_pipelines(
+                &fixed_vulkan_stuff,
+                &[descriptor_set_layout, scene.descriptor_set_layout],
+            );
+
+        // Frames the whole scene whatever its units
+        let aabb = scene.aabb();
+        let radius = aabb.size().length().max(f32::EPSILON) * 1.5;
+        let camera = Camera::builder()
+            .orbit(aabb.center(), radius, PI, 0.)
+            .z_limits([radius * 0.01, radius * 10.])
+            .move_speed(100.)
+            .rotate_speed(40.)
+            .build();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        PbrApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera,
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            overdraw_pipeline_layout,
+            overdraw_pipeline,
+            debug_view: DebugView::default(),
+            uniform_buffers,
+            scene_path,
+            scene,
+            light,
+            environment,
+            ibl,
+            skybox,
+            skybox_push_const_block: SkyboxPushConstBlock::default(),
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let view = self.camera.view_mat();
+        let projection = self.camera.perspective_mat();
+        // Without translation, the sky stays infinitely far away
+        let mut view_rotation = view;
+        view_rotation.w_axis = Vec4::W;
+        self.skybox_push_const_block = SkyboxPushConstBlock {
+            inverse_view_projection: (projection * view_rotation).inverse(),
+            exposure: self.light.exposure * self.light.environment_intensity,
+            _padding: Vec3::ZERO,
+        };
+        self.uniform_buffers[frame.frame_index].load_data_when_mapped(
+            &[SceneUbo {
+                model: Mat4::IDENTITY,
+                view,
+                projection,
+                camera_position: view.inverse().w_axis.truncate(),
+                exposure: self.light.exposure,
+                light_direction: self.light.direction(),
+                environment_intensity: self.light.environment_intensity,
+                light_color: (Vec3::ONE * self.light.intensity).extend(1.),
+            }],
+            0,
+        );
+
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            self.scene_path.clone(),
+            format!(
+                "{} nodes, {} meshes, {} materials, {} textures",
+                self.scene.nodes.len(),
+                self.scene.meshes.len(),
+                self.scene.materials.len() - 1,
+                self.scene.textures.len()
+            ),
+            format!(
+                "Environment {}px, {} prefiltered mips",
+                self.environment.extent2d().width,
+                self.ibl.prefiltered.mip_levels()
+            ),
+        ];
+        let mut light = self.light;
+        let mut debug_view = self.debug_view;
+        self.update_ui_with(&infos, |ui| {
+            light.draw(ui);
+            debug_view.draw(ui);
+        });
+        self.light = light;
+        self.debug_view = debug_view;
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        // The overdraw heatmap starts from the black clear color
+        let (pipeline_layout, pipeline) = if self.debug_view == DebugView::Overdraw {
+            (self.overdraw_pipeline_layout, self.overdraw_pipeline)
+        } else {
+            // Without depth test, the scene is drawn over it
+            self.skybox.cmd_draw(
+                command_buffer,
+                bytemuck::bytes_of(&self.skybox_push_const_block),
+            );
+            (self.pipeline_layout, self.pipeline)
+        };
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        }
+        DebugPush::new(self.debug_view).cmd_push(&device, command_buffer, pipeline_layout);
+        // Consecutive primitives of one material keep its set bound
+        let mut binder = DescriptorSetBinder::new(
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            device.clone(),
+        );
+        binder.bind(
+            command_buffer,
+            SetIndex::Frame,
+            self.descriptor_sets[frame_index],
+        );
+        self.scene
+            .cmd_draw(command_buffer, |transform, material| unsafe {
+                binder.bind(command_buffer, SetIndex::Material, material.descriptor_set);
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytemuck::bytes_of(&NodePushConstBlock {
+                        node: transform,
+                        factors: material.factors(),
+                    }),
+                );
+            });
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.skybox = Self::create_skybox(&self.fixed_vulkan_stuff, &self.environment);
+            let garbage = &mut self.fixed_vulkan_stuff.frame_garbage;
+            garbage.retire(self.pipeline);
+            garbage.retire(self.pipeline_layout);
+            garbage.retire(self.overdraw_pipeline);
+            garbage.retire(self.overdraw_pipeline_layout);
+            (
+                (self.pipeline_layout, self.pipeline),
+                (self.overdraw_pipeline_layout, self.overdraw_pipeline),
+            ) = Self::create_pipelines(
+                &self.fixed_vulkan_stuff,
+                &[self.descriptor_set_layout, self.scene.descriptor_set_layout],
+            );
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![
+            vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build(),
+            vk::DescriptorPoolSize::builder()
+                .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(3 * FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build(),
+        ]
+    }
+
+    /// Frame set with the scene uniforms and the image based lighting, the material set
+    /// is the one of the scene
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        let mut bindings = vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build()];
+        // Irradiance, prefiltered radiance and BRDF LUT
+        bindings.extend((1..4).map(|binding| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .descriptor_count(1)
+                .build()
+        }));
+        bindings
+    }
+}
+
+impl PbrApp {
+    /// The shaded scene and its overdraw view, `set_layouts` are set 0 and the material set
+    /// of the scene
+    fn create_pipelines(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        set_layouts: &[vk::DescriptorSetLayout],
+    ) -> (
+        (vk::PipelineLayout, vk::Pipeline),
+        (vk::PipelineLayout, vk::Pipeline),
+    ) {
+        let pipeline_creator = PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts,
+            vertex_bindings: &[gltf::Vertex::binding_description()],
+            vertex_attributes: &gltf::Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        };
+        (
+            pipeline_creator.build().unwrap(),
+            pipeline_creator.build_overdraw().unwrap(),
+        )
+    }
+
+    fn create_skybox(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        environment: &Texture,
+    ) -> FullscreenPass {
+        let skybox = FullscreenPass::from_fixed_vulkan_stuff(
+            fixed_vulkan_stuff,
+            SKYBOX_FRAG_SPV_PATH,
+            std::mem::size_of::<SkyboxPushConstBlock>() as u32,
+        )
+        .unwrap();
+        skybox.set_input(environment.descriptor_default());
+        skybox
+    }
+}
+
+impl Drop for PbrApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_pipeline(self.overdraw_pipeline, None);
+            device.destroy_pipeline_layout(self.overdraw_pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Light {
+    /// Angle around the up axis, in degrees
+    azimuth: f32,
+    /// Angle above the ground, in degrees
+    elevation: f32,
+    intensity: f32,
+    /// Scale of the light coming from the environment map
+    environment_intensity: f32,
+    exposure: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            azimuth: 45.,
+            elevation: 50.,
+            intensity: 3.,
+            environment_intensity: 1.,
+            exposure: 1.,
+        }
+    }
+}
+
+impl Light {
+    /// Direction the light travels in, glTF scenes are y-up
+    fn direction(&self) -> Vec3 {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        let horizontal = vec3(azimuth.cos(), 0., azimuth.sin()) * elevation.cos();
+        (horizontal - Vec3::Y * elevation.sin()).normalize()
+    }
+
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Light", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider("Azimuth", 0., 360., &mut self.azimuth);
+            ui.slider("Elevation", -85., 85., &mut self.elevation);
+            ui.slider_config("Intensity", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.intensity);
+            ui.slider("Environment", 0., 2., &mut self.environment_intensity);
+            ui.slider_config("Exposure", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.exposure);
+        }
+    }
+}
+
+/// Layout of `SceneUbo` in the shaders
+#[repr(C, align(16))]
+struct SceneUbo {
+    model: Mat4,
+    view: Mat4,
+    projection: Mat4,
+    camera_position: Vec3,
+    exposure: f32,
+    light_direction: Vec3,
+    environment_intensity: f32,
+    light_color: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct NodePushConstBlock {
+    node: Mat4,
+    factors: MaterialFactors,
+}
+
+unsafe impl bytemuck::Pod for NodePushConstBlock {}
+unsafe impl bytemuck::Zeroable for NodePushConstBlock {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct SkyboxPushConstBlock {
+    inverse_view_projection: Mat4,
+    exposure: f32,
+    _padding: Vec3,
+}
+
+unsafe impl bytemuck::Pod for SkyboxPushConstBlock {}
+unsafe impl bytemuck::Zeroable for SkyboxPushConstBlock {}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/pbr/pbr.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/pbr/pbr.frag.spv"
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<NodePushConstBlock>() as u32)
+            .build()]
+    }
+
+    // The scene is mirrored to the left-handed space of the examples, which flips its winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = PbrApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
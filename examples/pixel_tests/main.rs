@@ -0,0 +1,305 @@
+use vulkan_example_rs::{app::HeadlessVulkanStuff, prelude::*};
+
+const EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 16,
+    height: 16,
+};
+/// Exact in 8 bits, 51, 102 and 153 out of 255
+const CLEAR_COLOR: [f32; 4] = [0.2, 0.4, 0.6, 1.];
+
+#[derive(Clone, Copy)]
+enum Pipeline {
+    /// Blend, depth and cull states of [`PipelineBuilder`] left as they are
+    Default,
+    /// Source over destination for colors, alpha accumulated
+    AlphaBlend,
+}
+
+/// Triangle covering the whole target with one flat color
+#[derive(Clone, Copy)]
+struct Draw {
+    pipeline: Pipeline,
+    color: Vec4,
+    depth: f32,
+    clockwise: bool,
+}
+
+impl Draw {
+    const fn opaque(color: Vec4, depth: f32) -> Self {
+        Self {
+            pipeline: Pipeline::Default,
+            color,
+            depth,
+            clockwise: false,
+        }
+    }
+}
+
+struct PixelTest {
+    name: &'static str,
+    draws: &'static [Draw],
+    expected: [u8; 4],
+    /// Blending may round either way in 8 bits, draws that only write stay exact
+    tolerance: u8,
+}
+
+const RED: Vec4 = Vec4::new(1., 0., 0., 1.);
+const GREEN: Vec4 = Vec4::new(0., 1., 0., 1.);
+
+const TESTS: &[PixelTest] = &[
+    PixelTest {
+        name: "clear",
+        draws: &[],
+        expected: [51, 102, 153, 255],
+        tolerance: 0,
+    },
+    PixelTest {
+        name: "blending disabled by default",
+        draws: &[Draw::opaque(Vec4::new(1., 0.6, 0., 0.6), 0.5)],
+        expected: [255, 153, 0, 153],
+        tolerance: 0,
+    },
+    PixelTest {
+        name: "nearer fragment kept by depth test",
+        draws: &[Draw::opaque(GREEN, 0.3), Draw::opaque(RED, 0.6)],
+        expected: [0, 255, 0, 255],
+        tolerance: 0,
+    },
+    PixelTest {
+        name: "equal depth rejected by LESS",
+        draws: &[Draw::opaque(GREEN, 0.3), Draw::opaque(RED, 0.3)],
+        expected: [0, 255, 0, 255],
+        tolerance: 0,
+    },
+    PixelTest {
+        name: "clockwise triangle culled",
+        draws: &[Draw {
+            clockwise: true,
+            ..Draw::opaque(RED, 0.5)
+        }],
+        expected: [51, 102, 153, 255],
+        tolerance: 0,
+    },
+    PixelTest {
+        // 0.6 * (1, 0, 0) + 0.4 * (0.2, 0.4, 0.6), alpha 0.6 + 0.4 * 1
+        name: "source alpha blending",
+        draws: &[Draw {
+            pipeline: Pipeline::AlphaBlend,
+            ..Draw::opaque(Vec4::new(1., 0., 0., 0.6), 0.5)
+        }],
+        expected: [173, 41, 61, 255],
+        tolerance: 1,
+    },
+];
+
+/// Renders tiny frames with known states into a UNORM target and compares the center
+/// pixel against exact values, exits with 1 when any test fails.
+/// `--validation` turns the validation layer on, which CI machines may not have installed.
+fn main() {
+    let debug_strategy = if std::env::args().any(|arg| arg == "--validation") {
+        VulkanDebugInfoStrategy::DEFAULT_PANIC_ON_ERRORS
+    } else {
+        VulkanDebugInfoStrategy::Idle
+    };
+    let instance = Rc::new(
+        Instance::builder()
+            .app_name_and_version("pixel_tests", 0)
+            .debug_strategy(debug_strategy)
+            .build()
+            .unwrap(),
+    );
+    let headless =
+        HeadlessVulkanStuff::with_color_format(instance, EXTENT, vk::Format::R8G8B8A8_UNORM)
+            .unwrap();
+    let device = headless.device.clone();
+
+    let creator = PipelineCreator {
+        device: device.clone(),
+        extent: EXTENT,
+        render_pass: headless.render_target.render_pass(),
+        vertex_bindings: &[],
+        vertex_attributes: &[],
+        pipeline_cache: headless.pipeline_cache,
+    };
+    let (default_layout, default_pipeline) = creator.build().unwrap();
+    let (blend_layout, blend_pipeline) = AlphaBlendPipelineCreator(creator).build().unwrap();
+
+    let clear_values = [
+        vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: CLEAR_COLOR,
+            },
+        },
+        vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.,
+                stencil: 0,
+            },
+        },
+    ];
+
+    let mut failures = 0;
+    for test in TESTS {
+        headless
+            .render_frame(&clear_values, |command_buffer| {
+                for draw in test.draws {
+                    let (layout, pipeline) = match draw.pipeline {
+                        Pipeline::Default => (default_layout, default_pipeline),
+                        Pipeline::AlphaBlend => (blend_layout, blend_pipeline),
+                    };
+                    unsafe {
+                        device.cmd_bind_pipeline(
+                            command_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline,
+                        );
+                        device.cmd_push_constants(
+                            command_buffer,
+                            layout,
+                            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                            0,
+                            bytemuck::bytes_of(&DrawPushConstBlock {
+                                color: draw.color,
+                                depth: draw.depth,
+                                clockwise: draw.clockwise as u32,
+                                _padding: Vec2::ZERO,
+                            }),
+                        );
+                        device.cmd_draw(command_buffer, 3, 1, 0, 0);
+                    }
+                }
+            })
+            .unwrap();
+
+        let pixel = headless.pixel(EXTENT.width / 2, EXTENT.height / 2);
+        let passed = pixel
+            .iter()
+            .zip(test.expected)
+            .all(|(&got, expected)| got.abs_diff(expected) <= test.tolerance);
+        if passed {
+            println!("ok      {}", test.name);
+        } else {
+            failures += 1;
+            println!(
+                "FAILED  {}: got {pixel:?}, expected {:?}",
+                test.name, test.expected
+            );
+        }
+    }
+    println!(
+        "{} passed, {failures} failed on {}",
+        TESTS.len() - failures,
+        device.physical_device_name()
+    );
+
+    unsafe {
+        device.destroy_pipeline(default_pipeline, None);
+        device.destroy_pipeline_layout(default_layout, None);
+        device.destroy_pipeline(blend_pipeline, None);
+        device.destroy_pipeline_layout(blend_layout, None);
+    }
+    if failures > 0 {
+        drop(headless);
+        std::process::exit(1);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DrawPushConstBlock {
+    color: Vec4,
+    depth: f32,
+    clockwise: u32,
+    _padding: Vec2,
+}
+
+unsafe impl bytemuck::Pod for DrawPushConstBlock {}
+unsafe impl bytemuck::Zeroable for DrawPushConstBlock {}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "examples/shaders/pixel_tests/flat.vert.spv",
+        "examples/shaders/pixel_tests/flat.frag.spv"
+    );
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        push_constant_pipeline_layout(&self.device)
+    }
+}
+
+/// Same pipeline with blending on, every other state keeps the defaults under test
+struct AlphaBlendPipelineCreator<'a>(PipelineCreator<'a>);
+
+impl<'a> PipelineBuilder<'a, &'a str> for AlphaBlendPipelineCreator<'a> {
+    fn device(&self) -> Rc<Device> {
+        self.0.device()
+    }
+
+    fn extent(&self) -> vk::Extent2D {
+        self.0.extent()
+    }
+
+    fn render_pass(&self) -> vk::RenderPass {
+        self.0.render_pass()
+    }
+
+    fn pipeline_cache(&self) -> vk::PipelineCache {
+        self.0.pipeline_cache()
+    }
+
+    fn vertex_binding_descriptions(&self) -> &'a [vk::VertexInputBindingDescription] {
+        self.0.vertex_binding_descriptions()
+    }
+
+    fn vertex_attribute_descriptions(&self) -> &'a [vk::VertexInputAttributeDescription] {
+        self.0.vertex_attribute_descriptions()
+    }
+
+    impl_embedded_spv_fns!(
+        "examples/shaders/pixel_tests/flat.vert.spv",
+        "examples/shaders/pixel_tests/flat.frag.spv"
+    );
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        push_constant_pipeline_layout(&self.0.device)
+    }
+
+    fn color_blend_attach_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+}
+
+fn push_constant_pipeline_layout(device: &Device) -> vk::PipelineLayout {
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+        .size(std::mem::size_of::<DrawPushConstBlock>() as u32)
+        .build();
+    let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .push_constant_ranges(&[push_constant_range])
+        .build();
+    unsafe {
+        device
+            .create_pipeline_layout(&pipeline_layout_info, None)
+            .unwrap()
+    }
+}
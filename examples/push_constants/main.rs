@@ -0,0 +1,290 @@
+use std::{f32::consts::PI, time::Instant};
+
+use vulkan_example_rs::{mesh::primitives, prelude::*, vulkan_wrappers::PushConstants};
+
+/// Cubes per side of the grid, each drawn with its own push constants
+const MAX_GRID_SIZE: u32 = 16;
+const CUBE_SPACING: f32 = 2.;
+
+struct PushConstantsApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    /// No descriptor sets, everything the shaders read is pushed
+    push_constants: PushConstants<CubePush>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    start: Instant,
+    settings: GridSettings,
+}
+
+impl WindowApp for PushConstantsApp {
+    impl_window_fns!(PushConstantsApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let push_constants =
+            PushConstants::new(vk::ShaderStageFlags::VERTEX, &fixed_vulkan_stuff.device).unwrap();
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, push_constants);
+
+        let (vertices, indices) = primitives::cube();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&indices)
+            .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        PushConstantsApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 30., PI / 4., 0.5)
+                .z_limits([0.1, 200.])
+                .move_speed(40.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            push_constants,
+            pipeline_layout,
+            pipeline,
+            vertex_buffer,
+            indice_buffer,
+            index_count: indices.len() as u32,
+            start: Instant::now(),
+            settings: GridSettings::default(),
+        }
+    }
+
+    fn prepare(&mut self, _frame: &FrameContext) {
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!(
+                "{} draws of {} bytes of push constants",
+                self.settings.grid_size * self.settings.grid_size,
+                PushConstants::<CubePush>::SIZE
+            ),
+        ];
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui));
+        self.settings = settings;
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+
+        let time = self.start.elapsed().as_secs_f32() * self.settings.speed;
+        let view_projection = self.camera.perspective_mat() * self.camera.view_mat();
+        let grid_size = self.settings.grid_size;
+        for (x, z) in (0..grid_size).flat_map(|x| (0..grid_size).map(move |z| (x, z))) {
+            let push = cube_push(x, z, grid_size, time, view_projection);
+            self.push_constants
+                .cmd_push(&device, command_buffer, self.pipeline_layout, &push);
+            unsafe { device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0) };
+        }
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.push_constants);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![]
+    }
+}
+
+impl PushConstantsApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        push_constants: PushConstants<CubePush>,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+            push_constant_range: push_constants.range(),
+        }
+        .build()
+        .unwrap()
+    }
+}
+
+impl Drop for PushConstantsApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct GridSettings {
+    grid_size: u32,
+    /// Scales the bobbing, spinning and color cycling
+    speed: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            grid_size: 8,
+            speed: 1.,
+        }
+    }
+}
+
+impl GridSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Grid", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider("Size", 1, MAX_GRID_SIZE, &mut self.grid_size);
+            ui.slider("Speed", 0., 4., &mut self.speed);
+        }
+    }
+}
+
+/// Layout of the push constant block in the vertex shader, ends well before the
+/// [`vulkan_example_rs::app::DebugPush`] block
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CubePush {
+    mvp: Mat4,
+    color: Vec4,
+}
+
+unsafe impl bytemuck::Pod for CubePush {}
+unsafe impl bytemuck::Zeroable for CubePush {}
+
+/// Every cube bobs and spins with a phase of its own, its hue cycling along
+fn cube_push(x: u32, z: u32, grid_size: u32, time: f32, view_projection: Mat4) -> CubePush {
+    let half = (grid_size - 1) as f32 / 2.;
+    let position = vec3(x as f32 - half, 0., z as f32 - half) * CUBE_SPACING;
+    let phase = (x * 7 + z * 13) as f32 * 0.37;
+    let model = Mat4::from_translation(position + Vec3::Y * (time * 2. + phase).sin() * 0.5)
+        * Mat4::from_rotation_y(time + phase)
+        * Mat4::from_rotation_x((time * 0.7 + phase).sin() * 0.5);
+    let hue = (time * 0.1 + phase / (2. * PI)).fract();
+    CubePush {
+        mvp: view_projection * model,
+        color: hue_to_rgb(hue).extend(1.),
+    }
+}
+
+fn hue_to_rgb(hue: f32) -> Vec3 {
+    let channel = |offset: f32| {
+        let k = (hue * 6. + offset) % 6.;
+        1. - (k.min(4. - k).clamp(0., 1.))
+    };
+    vec3(channel(5.), channel(3.), channel(1.))
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    push_constant_range: vk::PushConstantRange,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/push_constants/cube.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/push_constants/cube.frag.spv"
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![self.push_constant_range]
+    }
+
+    // The projection flips the winding, both faces go through the depth test instead
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = PushConstantsApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -0,0 +1,23 @@
+#version 450
+
+layout(binding = 2) uniform sampler2D hdrImage;
+
+layout(std430, binding = 4) readonly buffer Exposure {
+    float averageLuminance;
+    float exposure;
+} exposure;
+
+layout(location = 0) in vec2 fragUV;
+
+layout(location = 0) out vec4 outColor;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 radiance = texture(hdrImage, fragUV).rgb;
+    // The swapchain format is sRGB, so the output stays linear
+    outColor = vec4(aces(radiance * exposure.exposure), 1.0);
+}
@@ -0,0 +1,38 @@
+#version 450
+
+layout(binding = 0) uniform sampler2D hdrImage;
+
+layout(push_constant) uniform PushConsts {
+    float threshold;
+    float intensity;
+    // In texels of the input, between two taps of the blur
+    float radius;
+} pushConsts;
+
+layout(location = 0) in vec2 inUV;
+
+layout(location = 0) out vec4 outColor;
+
+const int TAP_RADIUS = 6;
+
+vec3 brightPart(vec2 uv) {
+    vec3 color = texture(hdrImage, uv).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    return color * max(luminance - pushConsts.threshold, 0.0) / max(luminance, 1e-4);
+}
+
+// Gaussian blur of the parts above the threshold, added back to the scene
+void main() {
+    vec2 texelStep = pushConsts.radius / vec2(textureSize(hdrImage, 0));
+    vec3 bloom = vec3(0.0);
+    float weightSum = 0.0;
+    for (int y = -TAP_RADIUS; y <= TAP_RADIUS; y++) {
+        for (int x = -TAP_RADIUS; x <= TAP_RADIUS; x++) {
+            float weight = exp(-float(x * x + y * y) / float(TAP_RADIUS * TAP_RADIUS));
+            bloom += brightPart(inUV + vec2(x, y) * texelStep) * weight;
+            weightSum += weight;
+        }
+    }
+    vec3 scene = texture(hdrImage, inUV).rgb;
+    outColor = vec4(scene + bloom / weightSum * pushConsts.intensity, 1.0);
+}
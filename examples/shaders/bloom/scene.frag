@@ -0,0 +1,18 @@
+#version 450
+
+layout(binding = 1) uniform sampler2D texSampler;
+
+layout(push_constant) uniform PushConsts {
+    float lightIntensity;
+} pushConsts;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+
+// Linear radiance, unbounded, written to the HDR target
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 albedo = fragColor * texture(texSampler, fragTexCoord).rgb;
+    outColor = vec4(albedo * pushConsts.lightIntensity, 1.0);
+}
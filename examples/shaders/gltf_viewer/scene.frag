@@ -0,0 +1,18 @@
+#version 450
+
+layout(set = 1, binding = 0) uniform sampler2D baseColorTexture;
+
+// Factor of the material of the drawn primitive
+layout(push_constant) uniform PushConsts {
+    vec4 baseColorFactor;
+} pushConsts;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 baseColor = pushConsts.baseColorFactor * texture(baseColorTexture, fragTexCoord);
+    outColor = vec4(fragColor * baseColor.rgb, baseColor.a);
+}
@@ -0,0 +1,23 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform MVPMatrix {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+// World transforms of the drawn primitives, each instanced draw starts at its first one
+#include "../../../src/app/shaders/object_data.glsl"
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+
+void main() {
+    gl_Position = ubo.proj * ubo.view * ubo.model * objects[gl_InstanceIndex].model * vec4(inPosition, 1.0);
+    fragColor = inColor;
+    fragTexCoord = inTexCoord;
+}
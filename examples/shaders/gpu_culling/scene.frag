@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec3 fragNormal;
+
+layout(location = 0) out vec4 outColor;
+
+const vec3 lightDirection = vec3(-0.5, -1.0, -0.3);
+
+void main() {
+    // Two sided, the winding of the mirrored scene is not relied on
+    float diffuse = abs(dot(normalize(fragNormal), normalize(-lightDirection)));
+    outColor = vec4(fragColor * (0.15 + 0.85 * diffuse), 1.0);
+}
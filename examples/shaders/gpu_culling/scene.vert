@@ -0,0 +1,41 @@
+#version 450
+
+struct Object {
+    mat4 model;
+    vec4 aabbMin;
+    vec4 aabbMax;
+    uvec4 indices;
+};
+
+layout(std430, binding = 0) readonly buffer Objects {
+    Object objects[];
+};
+
+layout(binding = 3) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 3) in vec3 inNormal;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec3 fragNormal;
+
+float hash(uint n) {
+    n = (n << 13U) ^ n;
+    n = n * (n * n * 15731U + 789221U) + 1376312589U;
+    return float(n & 0x7fffffffU) / float(0x7fffffff);
+}
+
+void main() {
+    // Written by the culling pass as the first instance of the draw of the object
+    uint index = uint(gl_InstanceIndex);
+    mat4 model = objects[index].model;
+
+    gl_Position = ubo.projection * ubo.view * model * vec4(inPosition, 1.0);
+    vec3 tint = vec3(hash(index), hash(index + 7919U), hash(index + 104729U));
+    fragColor = inColor * mix(vec3(0.4), vec3(1.0), tint);
+    fragNormal = mat3(model) * inNormal;
+}
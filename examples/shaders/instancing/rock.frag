@@ -0,0 +1,27 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    mat4 belt;
+    vec3 cameraPosition;
+    float time;
+    vec3 lightDirection;
+} ubo;
+
+layout(location = 0) in vec3 fragWorldPosition;
+layout(location = 1) in vec3 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Flat faces, the normal comes from the screen space derivatives and is turned
+    // towards the camera
+    vec3 v = normalize(ubo.cameraPosition - fragWorldPosition);
+    vec3 n = normalize(cross(dFdx(fragWorldPosition), dFdy(fragWorldPosition)));
+    n = dot(n, v) < 0.0 ? -n : n;
+
+    float diffuse = max(dot(n, normalize(-ubo.lightDirection)), 0.0);
+    vec3 color = fragColor * (0.08 + diffuse);
+    outColor = vec4(color, 1.0);
+}
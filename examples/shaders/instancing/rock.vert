@@ -0,0 +1,45 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    // Turns the whole belt around the up axis
+    mat4 belt;
+    vec3 cameraPosition;
+    float time;
+    vec3 lightDirection;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+// Per instance, takes locations 3 to 6
+layout(location = 3) in mat4 instanceModel;
+
+layout(location = 0) out vec3 fragWorldPosition;
+layout(location = 1) out vec3 fragColor;
+
+float hash(uint n) {
+    n = (n << 13U) ^ n;
+    n = n * (n * n * 15731U + 789221U) + 1376312589U;
+    return float(n & 0x7fffffffU) / float(0x7fffffff);
+}
+
+mat3 rotation(vec3 axis, float angle) {
+    float s = sin(angle);
+    float c = cos(angle);
+    mat3 k = mat3(0.0, axis.z, -axis.y, -axis.z, 0.0, axis.x, axis.y, -axis.x, 0.0);
+    return mat3(1.0) + s * k + (1.0 - c) * k * k;
+}
+
+void main() {
+    // Every rock spins around an axis and at a speed of its own
+    uint id = uint(gl_InstanceIndex);
+    vec3 axis = normalize(vec3(hash(id), hash(id + 7919U), hash(id + 104729U)) - 0.5 + 1e-3);
+    float spin = ubo.time * mix(0.2, 1.5, hash(id + 1299709U));
+
+    vec4 worldPosition = ubo.belt * instanceModel * vec4(rotation(axis, spin) * inPosition, 1.0);
+    fragWorldPosition = worldPosition.xyz;
+    fragColor = inColor * mix(vec3(0.45, 0.4, 0.35), vec3(0.8, 0.75, 0.7), hash(id + 15485863U));
+    gl_Position = ubo.projection * ubo.view * worldPosition;
+}
@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Round points fading out towards their border
+    float alpha = 1.0 - smoothstep(0.5, 1.0, length(gl_PointCoord * 2.0 - 1.0));
+    outColor = vec4(fragColor.rgb, fragColor.a * alpha);
+}
@@ -0,0 +1,137 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform SceneUbo {
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float exposure;
+    vec3 lightDirection;
+    float environmentIntensity;
+    vec4 lightColor;
+} ubo;
+
+// Image based lighting of the environment, see `IblResources`
+layout(set = 0, binding = 1) uniform samplerCube irradianceMap;
+layout(set = 0, binding = 2) uniform samplerCube prefilteredMap;
+layout(set = 0, binding = 3) uniform sampler2D brdfLut;
+
+layout(set = 1, binding = 0) uniform sampler2D baseColorTexture;
+layout(set = 1, binding = 1) uniform sampler2D normalTexture;
+layout(set = 1, binding = 2) uniform sampler2D metallicRoughnessTexture;
+layout(set = 1, binding = 3) uniform sampler2D occlusionTexture;
+layout(set = 1, binding = 4) uniform sampler2D emissiveTexture;
+
+layout(push_constant) uniform PushConsts {
+    mat4 node;
+    vec4 baseColorFactor;
+    vec3 emissiveFactor;
+    float metallicFactor;
+    float roughnessFactor;
+    float normalScale;
+    float occlusionStrength;
+    // `DebugPush`
+    layout(offset = 112) uint debugView;
+    float debugParams[3];
+} pushConsts;
+
+const uint DEBUG_SHADED = 0;
+const uint DEBUG_NORMALS = 1;
+const uint DEBUG_UVS = 2;
+const uint DEBUG_OVERDRAW = 3;
+
+layout(location = 0) in vec3 fragWorldPosition;
+layout(location = 1) in vec3 fragColor;
+layout(location = 2) in vec2 fragTexCoord;
+layout(location = 3) in vec3 fragNormal;
+layout(location = 4) in vec4 fragTangent;
+
+layout(location = 0) out vec4 outColor;
+
+const float PI = 3.14159265359;
+
+vec3 shadingNormal() {
+    vec3 normal = normalize(fragNormal);
+    vec3 tangent = normalize(fragTangent.xyz - normal * dot(normal, fragTangent.xyz));
+    vec3 bitangent = cross(normal, tangent) * fragTangent.w;
+    vec3 tangentNormal = texture(normalTexture, fragTexCoord).xyz * 2.0 - 1.0;
+    tangentNormal.xy *= pushConsts.normalScale;
+    vec3 n = normalize(mat3(tangent, bitangent, normal) * tangentNormal);
+    // Back faces of double sided materials are lit from their side
+    return gl_FrontFacing ? n : -n;
+}
+
+float distributionGGX(float nDotH, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+float visibilitySmithGGX(float nDotV, float nDotL, float alpha) {
+    float k = alpha / 2.0;
+    float gv = nDotV / (nDotV * (1.0 - k) + k);
+    float gl = nDotL / (nDotL * (1.0 - k) + k);
+    return gv * gl / max(4.0 * nDotV * nDotL, 1e-4);
+}
+
+vec3 fresnelSchlick(float vDotH, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - vDotH, 5.0);
+}
+
+// Fresnel averaged over the lobe, rough surfaces reflect less at grazing angles
+vec3 fresnelSchlickRoughness(float nDotV, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - nDotV, 5.0);
+}
+
+void main() {
+    switch (pushConsts.debugView) {
+    case DEBUG_NORMALS:
+        outColor = vec4(shadingNormal() * 0.5 + 0.5, 1.0);
+        return;
+    case DEBUG_UVS:
+        outColor = vec4(fract(fragTexCoord), 0.0, 1.0);
+        return;
+    case DEBUG_OVERDRAW:
+        // Added up, red saturates first, then green and blue
+        outColor = vec4(0.2, 0.06, 0.02, 1.0);
+        return;
+    }
+
+    vec4 baseColor = pushConsts.baseColorFactor * texture(baseColorTexture, fragTexCoord);
+    baseColor.rgb *= fragColor;
+    vec4 metallicRoughness = texture(metallicRoughnessTexture, fragTexCoord);
+    float metallic = clamp(pushConsts.metallicFactor * metallicRoughness.b, 0.0, 1.0);
+    float roughness = clamp(pushConsts.roughnessFactor * metallicRoughness.g, 0.04, 1.0);
+    float alpha = roughness * roughness;
+
+    vec3 n = shadingNormal();
+    vec3 v = normalize(ubo.cameraPosition - fragWorldPosition);
+    vec3 l = -normalize(ubo.lightDirection);
+    vec3 h = normalize(v + l);
+    float nDotL = max(dot(n, l), 0.0);
+    float nDotV = max(dot(n, v), 1e-4);
+    float nDotH = max(dot(n, h), 0.0);
+    float vDotH = max(dot(v, h), 0.0);
+
+    vec3 f0 = mix(vec3(0.04), baseColor.rgb, metallic);
+    vec3 f = fresnelSchlick(vDotH, f0);
+    vec3 specular = f * distributionGGX(nDotH, alpha) * visibilitySmithGGX(nDotV, nDotL, alpha);
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * baseColor.rgb / PI;
+    vec3 color = (diffuse + specular) * ubo.lightColor.rgb * nDotL;
+
+    // Split sum image based lighting, darkened by the occlusion map
+    vec3 fAmbient = fresnelSchlickRoughness(nDotV, f0, roughness);
+    vec3 diffuseIbl = (1.0 - fAmbient) * (1.0 - metallic) * baseColor.rgb * texture(irradianceMap, n).rgb;
+    float lod = roughness * float(textureQueryLevels(prefilteredMap) - 1);
+    vec3 prefiltered = textureLod(prefilteredMap, reflect(-v, n), lod).rgb;
+    vec2 brdf = texture(brdfLut, vec2(nDotV, roughness)).rg;
+    vec3 specularIbl = prefiltered * (fAmbient * brdf.x + brdf.y);
+    float occlusion = texture(occlusionTexture, fragTexCoord).r;
+    color += (diffuseIbl + specularIbl) * ubo.environmentIntensity
+        * mix(1.0, occlusion, pushConsts.occlusionStrength);
+    color += pushConsts.emissiveFactor * texture(emissiveTexture, fragTexCoord).rgb;
+
+    // Reinhard tone mapping, the sRGB swapchain encodes the result
+    color *= ubo.exposure;
+    outColor = vec4(color / (1.0 + color), baseColor.a);
+}
@@ -0,0 +1,43 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform SceneUbo {
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float exposure;
+    vec3 lightDirection;
+    float environmentIntensity;
+    vec4 lightColor;
+} ubo;
+
+// World transform of the node, the material factors are only read by the fragment shader
+layout(push_constant) uniform PushConsts {
+    mat4 node;
+} pushConsts;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+layout(location = 3) in vec3 inNormal;
+layout(location = 4) in vec4 inTangent;
+
+layout(location = 0) out vec3 fragWorldPosition;
+layout(location = 1) out vec3 fragColor;
+layout(location = 2) out vec2 fragTexCoord;
+layout(location = 3) out vec3 fragNormal;
+layout(location = 4) out vec4 fragTangent;
+
+void main() {
+    mat4 world = ubo.model * pushConsts.node;
+    vec4 worldPosition = world * vec4(inPosition, 1.0);
+    gl_Position = ubo.projection * ubo.view * worldPosition;
+
+    mat3 normalMatrix = transpose(inverse(mat3(world)));
+    fragWorldPosition = worldPosition.xyz;
+    fragColor = inColor;
+    fragTexCoord = inTexCoord;
+    fragNormal = normalMatrix * inNormal;
+    // A mirroring transform flips the bitangent
+    fragTangent = vec4(mat3(world) * inTangent.xyz, inTangent.w * sign(determinant(mat3(world))));
+}
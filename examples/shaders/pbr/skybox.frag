@@ -0,0 +1,21 @@
+#version 450
+
+layout(binding = 0) uniform samplerCube environment;
+
+layout(push_constant) uniform PushConsts {
+    // Of the view without its translation
+    mat4 inverseViewProjection;
+    float exposure;
+} pushConsts;
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 farPoint = pushConsts.inverseViewProjection * vec4(fragUv * 2.0 - 1.0, 1.0, 1.0);
+    vec3 direction = normalize(farPoint.xyz / farPoint.w);
+    vec3 color = textureLod(environment, direction, 0.0).rgb * pushConsts.exposure;
+    // Same tone mapping as the scene
+    outColor = vec4(color / (1.0 + color), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(push_constant) uniform PushConsts {
+    vec4 color;
+    float depth;
+    uint clockwise;
+} pushConsts;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = pushConsts.color;
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(push_constant) uniform PushConsts {
+    vec4 color;
+    float depth;
+    uint clockwise;
+} pushConsts;
+
+void main() {
+    // Counterclockwise triangle covering the whole viewport
+    vec2 positions[3] = vec2[](vec2(-1.0, -1.0), vec2(-1.0, 3.0), vec2(3.0, -1.0));
+    int index = pushConsts.clockwise != 0u && gl_VertexIndex != 0 ? 3 - gl_VertexIndex : gl_VertexIndex;
+    gl_Position = vec4(positions[index], pushConsts.depth, 1.0);
+}
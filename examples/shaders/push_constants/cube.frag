@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Darkened towards the face edges, so the cubes read without lighting
+    vec2 edge = min(fragTexCoord, 1.0 - fragTexCoord);
+    float shade = mix(0.35, 1.0, smoothstep(0.0, 0.08, min(edge.x, edge.y)));
+    outColor = vec4(fragColor * shade, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+// Pushed before every draw, no uniform buffer involved
+layout(push_constant) uniform CubePush {
+    mat4 mvp;
+    vec4 color;
+} cube;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+
+void main() {
+    fragColor = cube.color.rgb;
+    fragTexCoord = inTexCoord;
+    gl_Position = cube.mvp * vec4(inPosition, 1.0);
+}
@@ -0,0 +1,49 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 projection;
+    mat4 view;
+    mat4 lightSpace;
+    vec4 lightDirection;
+    vec4 cameraPosition;
+} ubo;
+
+layout(binding = 1) uniform sampler2D shadowMap;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec3 fragWorldPos;
+layout(location = 2) in vec4 fragShadowCoord;
+
+layout(location = 0) out vec4 outColor;
+
+const float AMBIENT = 0.2;
+const float BIAS = 0.002;
+
+// 3x3 percentage closer filtering, 1.0 is fully lit
+float shadowFactor(vec4 shadowCoord) {
+    vec3 projected = shadowCoord.xyz / shadowCoord.w;
+    if (projected.z > 1.0) {
+        return 1.0;
+    }
+    vec2 uv = projected.xy * 0.5 + 0.5;
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMap, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            float closestDepth = texture(shadowMap, uv + vec2(x, y) * texelSize).r;
+            lit += projected.z - BIAS > closestDepth ? 0.0 : 1.0;
+        }
+    }
+    return lit / 9.0;
+}
+
+void main() {
+    // Flat normal from screen space derivatives, turned towards the camera
+    vec3 normal = normalize(cross(dFdx(fragWorldPos), dFdy(fragWorldPos)));
+    if (dot(normal, ubo.cameraPosition.xyz - fragWorldPos) < 0.0) {
+        normal = -normal;
+    }
+    float diffuse = max(dot(normal, -ubo.lightDirection.xyz), 0.0);
+    float lighting = AMBIENT + (1.0 - AMBIENT) * diffuse * shadowFactor(fragShadowCoord);
+    outColor = vec4(fragColor * lighting, 1.0);
+}
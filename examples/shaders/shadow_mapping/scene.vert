@@ -0,0 +1,24 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 projection;
+    mat4 view;
+    mat4 lightSpace;
+    vec4 lightDirection;
+    vec4 cameraPosition;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec3 fragWorldPos;
+layout(location = 2) out vec4 fragShadowCoord;
+
+void main() {
+    gl_Position = ubo.projection * ubo.view * vec4(inPosition, 1.0);
+    fragColor = inColor;
+    fragWorldPos = inPosition;
+    fragShadowCoord = ubo.lightSpace * vec4(inPosition, 1.0);
+}
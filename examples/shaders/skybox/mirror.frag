@@ -0,0 +1,32 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float exposure;
+} ubo;
+layout(binding = 1) uniform samplerCube environment;
+
+layout(push_constant) uniform PushConsts {
+    mat4 model;
+    float roughness;
+} pushConsts;
+
+layout(location = 0) in vec3 fragWorldPosition;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Flat faces, the normal comes from the screen space derivatives and is turned
+    // towards the camera
+    vec3 v = normalize(ubo.cameraPosition - fragWorldPosition);
+    vec3 n = normalize(cross(dFdx(fragWorldPosition), dFdy(fragWorldPosition)));
+    n = dot(n, v) < 0.0 ? -n : n;
+
+    // Blurrier mips stand in for a rough surface
+    float lod = pushConsts.roughness * float(textureQueryLevels(environment) - 1);
+    vec3 color = textureLod(environment, reflect(-v, n), lod).rgb * vec3(0.95, 0.9, 0.8);
+    color *= ubo.exposure;
+    outColor = vec4(color / (1.0 + color), 1.0);
+}
@@ -0,0 +1,23 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float exposure;
+} ubo;
+
+layout(push_constant) uniform PushConsts {
+    mat4 model;
+    float roughness;
+} pushConsts;
+
+layout(location = 0) in vec3 inPosition;
+
+layout(location = 0) out vec3 fragWorldPosition;
+
+void main() {
+    vec4 worldPosition = pushConsts.model * vec4(inPosition, 1.0);
+    fragWorldPosition = worldPosition.xyz;
+    gl_Position = ubo.projection * ubo.view * worldPosition;
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float exposure;
+} ubo;
+layout(binding = 1) uniform samplerCube environment;
+
+layout(location = 0) in vec3 fragDirection;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 color = textureLod(environment, normalize(fragDirection), 0.0).rgb * ubo.exposure;
+    // Reinhard tone mapping, the sRGB swapchain encodes the result
+    outColor = vec4(color / (1.0 + color), 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float exposure;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+
+layout(location = 0) out vec3 fragDirection;
+
+void main() {
+    fragDirection = inPosition;
+    // The view without its translation keeps the cube around the camera, and z = w puts
+    // it on the far plane where LESS_OR_EQUAL only lets it fill uncovered pixels
+    vec4 position = ubo.projection * mat4(mat3(ubo.view)) * vec4(inPosition, 1.0);
+    gl_Position = position.xyww;
+}
@@ -0,0 +1,43 @@
+#version 450
+
+// Set per pipeline, the branches on them are resolved when the pipeline is created
+layout(constant_id = 0) const uint LIGHTING_MODEL = 0;
+layout(constant_id = 1) const float TOON_BANDS = 4.0;
+layout(constant_id = 2) const float SHININESS = 32.0;
+
+const uint LAMBERT = 0;
+const uint BLINN_PHONG = 1;
+const uint TOON = 2;
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    vec3 lightDirection;
+} ubo;
+
+layout(location = 0) in vec3 fragWorldPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec3 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 n = normalize(fragNormal);
+    vec3 l = normalize(-ubo.lightDirection);
+    vec3 v = normalize(ubo.cameraPosition - fragWorldPosition);
+    float diffuse = max(dot(n, l), 0.0);
+
+    vec3 color;
+    if (LIGHTING_MODEL == BLINN_PHONG) {
+        float specular = pow(max(dot(n, normalize(l + v)), 0.0), SHININESS);
+        color = fragColor * (0.1 + diffuse) + vec3(specular);
+    } else if (LIGHTING_MODEL == TOON) {
+        float band = floor(diffuse * TOON_BANDS) / (TOON_BANDS - 1.0);
+        float rim = 1.0 - smoothstep(0.2, 0.3, dot(n, v));
+        color = fragColor * (0.2 + 0.8 * min(band, 1.0)) * (1.0 - rim);
+    } else {
+        color = fragColor * (0.1 + diffuse);
+    }
+    outColor = vec4(color, 1.0);
+}
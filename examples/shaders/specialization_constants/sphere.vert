@@ -0,0 +1,29 @@
+#version 450
+
+layout(binding = 0) uniform SceneUbo {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    vec3 lightDirection;
+} ubo;
+
+layout(push_constant) uniform ModelPush {
+    mat4 model;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec3 fragWorldPosition;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out vec3 fragColor;
+
+void main() {
+    vec4 worldPosition = push.model * vec4(inPosition, 1.0);
+    fragWorldPosition = worldPosition.xyz;
+    // The sphere is centered on the origin of its model space
+    fragNormal = mat3(push.model) * inPosition;
+    fragColor = inColor;
+    gl_Position = ubo.projection * ubo.view * worldPosition;
+}
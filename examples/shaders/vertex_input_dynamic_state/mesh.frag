@@ -0,0 +1,17 @@
+#version 450
+
+layout(push_constant) uniform MeshPush {
+    mat4 mvp;
+    vec4 color;
+} mesh;
+
+layout(location = 0) in vec3 fragPosition;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Face normal from the screen space derivatives, no format has to carry normals
+    vec3 normal = normalize(cross(dFdx(fragPosition), dFdy(fragPosition)));
+    float shade = 0.4 + 0.6 * abs(dot(normal, normalize(vec3(0.3, 0.8, 0.5))));
+    outColor = vec4(mesh.color.rgb * shade, 1.0);
+}
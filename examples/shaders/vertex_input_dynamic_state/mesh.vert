@@ -0,0 +1,16 @@
+#version 450
+
+layout(push_constant) uniform MeshPush {
+    mat4 mvp;
+    vec4 color;
+} mesh;
+
+// The one attribute every vertex format has, the others are left unread
+layout(location = 0) in vec3 inPosition;
+
+layout(location = 0) out vec3 fragPosition;
+
+void main() {
+    fragPosition = inPosition;
+    gl_Position = mesh.mvp * vec4(inPosition, 1.0);
+}
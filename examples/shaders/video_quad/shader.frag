@@ -0,0 +1,12 @@
+#version 450
+
+// Immutable sampler with a YCbCr conversion, sampling returns RGB
+layout(binding = 0) uniform sampler2D videoFrame;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(texture(videoFrame, fragTexCoord).rgb, 1.0);
+}
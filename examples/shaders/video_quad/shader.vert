@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) out vec2 fragTexCoord;
+
+void main() {
+    // Triangle strip quad: (0, 0), (1, 0), (0, 1), (1, 1)
+    fragTexCoord = vec2(gl_VertexIndex & 1, (gl_VertexIndex >> 1) & 1);
+    gl_Position = vec4(fragTexCoord * 1.6 - 0.8, 0.0, 1.0);
+}
@@ -0,0 +1,575 @@
+use vulkan_example_rs::{
+    app::{DepthOnlyPipelineCreator, DepthOnlyPush, InsetPushConstBlock, PictureInPicture},
+    prelude::*,
+    vulkan_wrappers::DepthStencil,
+};
+
+const SHADOW_MAP_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 2048,
+    height: 2048,
+};
+
+// The projection does not flip Y, so -Y points up on screen
+const UP: Vec3 = Vec3::NEG_Y;
+
+struct ShadowMappingApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    model_indices: Vec<u32>,
+
+    camera: Camera,
+    camera_controller: CameraController,
+    light: DirectionalLight,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    shadow_pipeline_layout: vk::PipelineLayout,
+    shadow_pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    uniform_buffers: PerFrame<Buffer<SceneUbo>>,
+
+    shadow_target: OffscreenRenderTarget,
+    picture_in_picture: PictureInPicture,
+}
+
+impl WindowApp for ShadowMappingApp {
+    impl_window_fns!(ShadowMappingApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let mut fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        if let Err(e) = fixed_vulkan_stuff.enable_gpu_timer(2) {
+            println!("No GPU pass times: {}", e);
+        }
+
+        let shadow_target = OffscreenRenderTarget::depth_only(
+            SHADOW_MAP_EXTENT,
+            DepthStencil::find_depth_format(&fixed_vulkan_stuff.device).unwrap(),
+            fixed_vulkan_stuff.device.clone(),
+        )
+        .unwrap();
+
+        let (model_vertices, model_indices) = build_scene();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&model_vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&model_indices)
+            .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<SceneUbo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .image_info(
+                    1,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    shadow_target.depth_descriptor(),
+                )
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        // Only the position attribute is consumed by the shadow pass
+        let (shadow_pipeline_layout, shadow_pipeline) = DepthOnlyPipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            render_pass: shadow_target.render_pass(),
+            subpass: 0,
+            dynamic_rendering_depth_format: None,
+            color_attachment_count: 0,
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions()[..1],
+            set_layouts: &[],
+            alpha_test: false,
+            cull_mode: vk::CullModeFlags::NONE,
+            depth_bias: Some((1.25, 1.75)),
+            // Casters between the light and the near plane of its projection still shadow
+            depth_clamp: fixed_vulkan_stuff.device.supports_depth_clamp(),
+            depth_clip: None,
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap();
+
+        let picture_in_picture = PictureInPicture::from_fixed_vulkan_stuff(
+            &fixed_vulkan_stuff,
+            shadow_target.depth_descriptor(),
+        )
+        .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        ShadowMappingApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            model_indices,
+            camera: Camera::builder()
+                .translation(Vec3::new(0., 3., -10.))
+                .rotation((-0.29, 0., 0.))
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            light: DirectionalLight::default(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            shadow_pipeline_layout,
+            shadow_pipeline,
+            vertex_buffer,
+            indice_buffer,
+            uniform_buffers,
+            shadow_target,
+            picture_in_picture,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        let mut light = self.light;
+        self.update_ui_with(&[name], |ui| light.draw(ui));
+        self.light = light;
+
+        // After the UI, so that the shadow pass and the scene see the same light
+        let view = self.camera.view_mat();
+        self.uniform_buffers[frame.frame_index].load_data_when_mapped(
+            &[SceneUbo {
+                projection: self.camera.perspective_mat(),
+                view,
+                light_space: self.light.light_space_mat(),
+                light_direction: self.light.direction().extend(0.),
+                camera_position: view.inverse().w_axis,
+            }],
+            0,
+        );
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let light_space = self.light.light_space_mat();
+        unsafe {
+            self.fixed_vulkan_stuff
+                .cmd_begin_gpu_timer_frame(frame_index);
+
+            // Shadow pass, scene depth seen from the light
+            self.fixed_vulkan_stuff
+                .cmd_begin_gpu_region(frame_index, "shadow pass");
+            self.shadow_target
+                .cmd_begin_render_pass(command_buffer, &[Self::clear_value().depth_stencil]);
+            self.shadow_target
+                .cmd_set_viewport_and_scissor(command_buffer);
+            self.cmd_draw_shadow_casters(command_buffer, light_space);
+            self.fixed_vulkan_stuff
+                .device
+                .cmd_end_render_pass(command_buffer);
+            self.fixed_vulkan_stuff.cmd_end_gpu_region(frame_index);
+
+            // Main pass, sampling the shadow map
+            self.fixed_vulkan_stuff
+                .cmd_begin_gpu_region(frame_index, "main pass");
+            self.fixed_vulkan_stuff.cmd_begin_renderpass(
+                frame_index,
+                image_index,
+                &Self::clear_value(),
+            );
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+            self.cmd_draw_scene(
+                command_buffer,
+                frame_index,
+                self.pipeline,
+                self.pipeline_layout,
+            );
+
+            if self.light.show_shadow_map {
+                self.picture_in_picture.draw(
+                    command_buffer,
+                    &InsetPushConstBlock::top_right(
+                        self.fixed_vulkan_stuff.surface.extent(),
+                        vk::Extent2D {
+                            width: 300,
+                            height: 300,
+                        },
+                        20.,
+                        2.,
+                        Vec4::new(1., 1., 1., 1.),
+                    ),
+                );
+            }
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+            self.fixed_vulkan_stuff.cmd_end_gpu_region(frame_index);
+        }
+    }
+
+    /// The shadow pass does not depend on the swapchain
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.picture_in_picture
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![
+            vk::DescriptorType::UNIFORM_BUFFER,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ]
+        .into_iter()
+        .map(|ty| {
+            vk::DescriptorPoolSize::builder()
+                .ty(ty)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build()
+        })
+        .collect()
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        let ubo_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build();
+        let shadow_map_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .build();
+        vec![ubo_layout_binding, shadow_map_layout_binding]
+    }
+}
+
+impl ShadowMappingApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+
+    /// The scene is in world space, so the light space is the whole transform
+    fn cmd_draw_shadow_casters(&self, command_buffer: vk::CommandBuffer, light_space: Mat4) {
+        let device = &self.fixed_vulkan_stuff.device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.shadow_pipeline,
+            );
+        }
+        self.cmd_bind_geometry(command_buffer);
+        DepthOnlyPush::new(light_space).cmd_push(
+            device,
+            command_buffer,
+            self.shadow_pipeline_layout,
+        );
+        unsafe {
+            device.cmd_draw_indexed(command_buffer, self.model_indices.len() as u32, 1, 0, 0, 0);
+        }
+    }
+
+    fn cmd_draw_scene(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        pipeline: vk::Pipeline,
+        pipeline_layout: vk::PipelineLayout,
+    ) {
+        let device = &self.fixed_vulkan_stuff.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        }
+        self.cmd_bind_geometry(command_buffer);
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_draw_indexed(command_buffer, self.model_indices.len() as u32, 1, 0, 0, 0);
+        }
+    }
+
+    fn cmd_bind_geometry(&self, command_buffer: vk::CommandBuffer) {
+        let device = &self.fixed_vulkan_stuff.device;
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+    }
+}
+
+impl Drop for ShadowMappingApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_pipeline(self.shadow_pipeline, None);
+            device.destroy_pipeline_layout(self.shadow_pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DirectionalLight {
+    /// Angle around the up axis, in degrees
+    azimuth: f32,
+    /// Angle above the ground, in degrees
+    elevation: f32,
+    show_shadow_map: bool,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            azimuth: 45.,
+            elevation: 50.,
+            show_shadow_map: true,
+        }
+    }
+}
+
+impl DirectionalLight {
+    /// Half extent of the orthographic light frustum, large enough for the whole scene
+    const FRUSTUM_HALF_EXTENT: f32 = 12.;
+    const DISTANCE: f32 = 20.;
+
+    /// Direction the light travels in
+    fn direction(&self) -> Vec3 {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        let horizontal = vec3(azimuth.cos(), 0., azimuth.sin()) * elevation.cos();
+        (horizontal - UP * elevation.sin()).normalize()
+    }
+
+    fn light_space_mat(&self) -> Mat4 {
+        let view = Mat4::look_at_rh(-self.direction() * Self::DISTANCE, Vec3::ZERO, UP);
+        let projection = Mat4::orthographic_rh(
+            -Self::FRUSTUM_HALF_EXTENT,
+            Self::FRUSTUM_HALF_EXTENT,
+            -Self::FRUSTUM_HALF_EXTENT,
+            Self::FRUSTUM_HALF_EXTENT,
+            0.1,
+            Self::DISTANCE * 2.,
+        );
+        projection * view
+    }
+
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Directional light", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider("Azimuth", 0., 360., &mut self.azimuth);
+            ui.slider("Elevation", 15., 85., &mut self.elevation);
+            ui.checkbox("Show shadow map", &mut self.show_shadow_map);
+        }
+    }
+}
+
+#[repr(C, align(16))]
+struct SceneUbo {
+    projection: Mat4,
+    view: Mat4,
+    light_space: Mat4,
+    light_direction: Vec4,
+    camera_position: Vec4,
+}
+
+/// A ground plane with a few boxes standing on it, in world space
+fn build_scene() -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    let ground_color = vec3(0.8, 0.8, 0.8);
+    [
+        vec3(-8., 0., -8.),
+        vec3(8., 0., -8.),
+        vec3(8., 0., 8.),
+        vec3(-8., 0., 8.),
+    ]
+    .into_iter()
+    .for_each(|pos| vertices.push(Vertex::new(pos).with_color(ground_color)));
+    indices.extend_from_slice(&[0, 1, 2, 0, 2, 3]);
+
+    [
+        (vec3(0., 0., 0.), vec3(1., 1.5, 1.), vec3(0.9, 0.3, 0.2)),
+        (
+            vec3(-3.5, 0., 2.),
+            vec3(0.75, 0.75, 0.75),
+            vec3(0.2, 0.7, 0.3),
+        ),
+        (vec3(3., 0., -2.5), vec3(0.6, 2.5, 0.6), vec3(0.2, 0.4, 0.9)),
+        (
+            vec3(2.5, 0., 3.),
+            vec3(1.25, 0.5, 0.75),
+            vec3(0.9, 0.8, 0.2),
+        ),
+    ]
+    .into_iter()
+    .for_each(|(base, half_extent, color)| {
+        push_box(&mut vertices, &mut indices, base, half_extent, color)
+    });
+
+    (vertices, indices)
+}
+
+/// Box resting on `base`, i.e. extending `2 * half_extent.y` towards [`UP`]
+fn push_box(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    base: Vec3,
+    half_extent: Vec3,
+    color: Vec3,
+) {
+    let center = base + UP * half_extent.y;
+    let first_index = vertices.len() as u32;
+    (0..8).for_each(|i| {
+        let corner = vec3(
+            if i & 1 == 0 { -1. } else { 1. },
+            if i & 2 == 0 { -1. } else { 1. },
+            if i & 4 == 0 { -1. } else { 1. },
+        );
+        vertices.push(Vertex::new(center + corner * half_extent).with_color(color));
+    });
+    // Culling is disabled, so the winding of the faces does not matter
+    [
+        [0, 1, 3, 2],
+        [4, 5, 7, 6],
+        [0, 1, 5, 4],
+        [2, 3, 7, 6],
+        [0, 2, 6, 4],
+        [1, 3, 7, 5],
+    ]
+    .into_iter()
+    .for_each(|[a, b, c, d]| {
+        indices.extend([a, b, c, a, c, d].map(|i| first_index + i));
+    });
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/shadow_mapping/scene.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/shadow_mapping/scene.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = ShadowMappingApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -0,0 +1,474 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{app::IblResources, mesh::primitives, prelude::*};
+
+/// Texels per side of the procedural sky cubemap
+const ENVIRONMENT_SIZE: u32 = 512;
+
+struct SkyboxApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    /// Mirror cube in the middle of the sky
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    skybox_pipeline_layout: vk::PipelineLayout,
+    skybox_pipeline: vk::Pipeline,
+    /// Unit cube shared by the mirror and the sky
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    uniform_buffers: PerFrame<Buffer<SceneUbo>>,
+    environment: Texture,
+    settings: SkyboxSettings,
+}
+
+impl WindowApp for SkyboxApp {
+    impl_window_fns!(SkyboxApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        // An equirectangular .hdr panorama given with `--environment=<path>` replaces the sky
+        let environment = match std::env::args()
+            .find_map(|arg| arg.strip_prefix("--environment=").map(str::to_owned))
+        {
+            Some(path) => Texture::cubemap_from_equirect_hdr(
+                &path,
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            )
+            .unwrap_or_else(|e| panic!("Fail to load {path}: {e}")),
+            None => IblResources::procedural_sky(
+                ENVIRONMENT_SIZE,
+                vec3(1., 0.4, 0.6),
+                fixed_vulkan_stuff.pipeline_cache,
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            )
+            .unwrap(),
+        };
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<SceneUbo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .combined_image_sampler(1, &environment)
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let ((pipeline_layout, pipeline), (skybox_pipeline_layout, skybox_pipeline)) =
+            Self::create_pipelines(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        let (vertices, indices) = primitives::cube();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&indices)
+            .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        SkyboxApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 3., PI, 0.)
+                .z_limits([0.1, 100.])
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            skybox_pipeline_layout,
+            skybox_pipeline,
+            vertex_buffer,
+            indice_buffer,
+            index_count: indices.len() as u32,
+            uniform_buffers,
+            environment,
+            settings: SkyboxSettings::default(),
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let view = self.camera.view_mat();
+        self.uniform_buffers[frame.frame_index].load_data_when_mapped(
+            &[SceneUbo {
+                view,
+                projection: self.camera.perspective_mat(),
+                camera_position: view.inverse().w_axis.truncate(),
+                exposure: self.settings.exposure,
+            }],
+            0,
+        );
+
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!(
+                "Environment {}px, {} mips",
+                self.environment.extent2d().width,
+                self.environment.mip_levels()
+            ),
+        ];
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui));
+        self.settings = settings;
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            // Both layouts have the same set 0
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&MirrorPushConstBlock {
+                    model: Mat4::from_rotation_y(self.settings.rotation.to_radians()),
+                    roughness: self.settings.roughness,
+                    _padding: Vec3::ZERO,
+                }),
+            );
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+
+            // Drawn last, the sky only shades the pixels the scene left at the far plane
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.skybox_pipeline,
+            );
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            let garbage = &mut self.fixed_vulkan_stuff.frame_garbage;
+            garbage.retire(self.pipeline);
+            garbage.retire(self.pipeline_layout);
+            garbage.retire(self.skybox_pipeline);
+            garbage.retire(self.skybox_pipeline_layout);
+            (
+                (self.pipeline_layout, self.pipeline),
+                (self.skybox_pipeline_layout, self.skybox_pipeline),
+            ) = Self::create_pipelines(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        [
+            vk::DescriptorType::UNIFORM_BUFFER,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ]
+        .into_iter()
+        .map(|ty| {
+            vk::DescriptorPoolSize::builder()
+                .ty(ty)
+                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                .build()
+        })
+        .collect()
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                .descriptor_count(1)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .descriptor_count(1)
+                .build(),
+        ]
+    }
+}
+
+impl SkyboxApp {
+    /// The reflective cube and the skybox behind it
+    fn create_pipelines(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (
+        (vk::PipelineLayout, vk::Pipeline),
+        (vk::PipelineLayout, vk::Pipeline),
+    ) {
+        let pipeline = PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap();
+        let skybox_pipeline = SkyboxPipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap();
+        (pipeline, skybox_pipeline)
+    }
+}
+
+impl Drop for SkyboxApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_pipeline(self.skybox_pipeline, None);
+            device.destroy_pipeline_layout(self.skybox_pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SkyboxSettings {
+    exposure: f32,
+    /// Picks blurrier mips of the sky in the mirror
+    roughness: f32,
+    /// Of the mirror cube around the up axis, in degrees
+    rotation: f32,
+}
+
+impl Default for SkyboxSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.,
+            roughness: 0.,
+            rotation: 30.,
+        }
+    }
+}
+
+impl SkyboxSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header("Skybox", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            ui.slider_config("Exposure", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.exposure);
+            ui.slider("Roughness", 0., 1., &mut self.roughness);
+            ui.slider("Rotation", 0., 360., &mut self.rotation);
+        }
+    }
+}
+
+/// Layout of `SceneUbo` in the shaders
+#[repr(C, align(16))]
+struct SceneUbo {
+    view: Mat4,
+    projection: Mat4,
+    camera_position: Vec3,
+    exposure: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MirrorPushConstBlock {
+    model: Mat4,
+    roughness: f32,
+    _padding: Vec3,
+}
+
+unsafe impl bytemuck::Pod for MirrorPushConstBlock {}
+unsafe impl bytemuck::Zeroable for MirrorPushConstBlock {}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/skybox/mirror.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/skybox/mirror.frag.spv"
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<MirrorPushConstBlock>() as u32)
+            .build()]
+    }
+
+    // Flat shading does not depend on the winding, which the projection flips
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        no_cull_rasterization_state()
+    }
+}
+
+struct SkyboxPipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for SkyboxPipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/skybox/skybox.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/skybox/skybox.frag.spv"
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    // Seen from inside the cube
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        no_cull_rasterization_state()
+    }
+
+    // The sky is at depth 1 which the cleared depth equals, it must pass on equality
+    // and leave the depth of the frame untouched
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
+
+fn no_cull_rasterization_state() -> vk::PipelineRasterizationStateCreateInfo {
+    vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false)
+        .build()
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = SkyboxApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
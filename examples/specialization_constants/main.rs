@@ -0,0 +1,444 @@
+use std::f32::consts::PI;
+
+use vulkan_example_rs::{
+    prelude::*,
+    vulkan_wrappers::{PushConstants, SpecializationConstants},
+};
+
+/// Constant IDs of `sphere.frag`
+const LIGHTING_MODEL_ID: u32 = 0;
+const TOON_BANDS_ID: u32 = 1;
+const SHININESS_ID: u32 = 2;
+
+/// One pipeline per model, the fragment shader branches on the constant selecting it
+#[derive(Clone, Copy)]
+enum LightingModel {
+    Lambert = 0,
+    BlinnPhong = 1,
+    Toon = 2,
+}
+
+impl LightingModel {
+    const ALL: [Self; 3] = [Self::Lambert, Self::BlinnPhong, Self::Toon];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Lambert => "Lambert",
+            Self::BlinnPhong => "Blinn-Phong",
+            Self::Toon => "Toon",
+        }
+    }
+}
+
+struct SpecializationConstantsApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    push_constants: PushConstants<ModelPush>,
+    /// Layout and pipeline of every [`LightingModel::ALL`]
+    pipelines: Vec<(vk::PipelineLayout, vk::Pipeline)>,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    uniform_buffers: PerFrame<Buffer<SceneUbo>>,
+    settings: LightingSettings,
+}
+
+impl WindowApp for SpecializationConstantsApp {
+    impl_window_fns!(SpecializationConstantsApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let descriptor_set_layout =
+            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<SceneUbo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            buffer.map_memory_all().unwrap();
+            buffer
+        });
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let push_constants =
+            PushConstants::new(vk::ShaderStageFlags::VERTEX, &fixed_vulkan_stuff.device).unwrap();
+        let settings = LightingSettings::default();
+        let pipelines = Self::create_pipelines(
+            &fixed_vulkan_stuff,
+            descriptor_set_layout,
+            push_constants,
+            settings,
+        );
+
+        let (vertices, indices) = sphere(32, 64);
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&vertices)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&indices)
+            .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        SpecializationConstantsApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 6., PI, 0.2)
+                .z_limits([0.1, 100.])
+                .move_speed(10.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            push_constants,
+            pipelines,
+            vertex_buffer,
+            indice_buffer,
+            index_count: indices.len() as u32,
+            uniform_buffers,
+            settings,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        let view = self.camera.view_mat();
+        self.uniform_buffers[frame.frame_index].load_data_when_mapped(
+            &[SceneUbo {
+                view,
+                projection: self.camera.perspective_mat(),
+                camera_position: view.inverse().w_axis.truncate(),
+                _padding: 0.,
+                light_direction: vec3(-1., -1., -0.5).normalize(),
+                _padding2: 0.,
+            }],
+            0,
+        );
+
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!(
+                "{} from left to right",
+                LightingModel::ALL.map(LightingModel::name).join(", ")
+            ),
+        ];
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui));
+        if settings != self.settings {
+            self.settings = settings;
+            self.recreate_pipelines();
+        }
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+        for (i, &(layout, pipeline)) in self.pipelines.iter().enumerate() {
+            let model = Mat4::from_translation(vec3((1. - i as f32) * 2.5, 0., 0.));
+            unsafe {
+                device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    layout,
+                    0,
+                    &[self.descriptor_sets[frame_index]],
+                    &[],
+                );
+            }
+            self.push_constants
+                .cmd_push(&device, command_buffer, layout, &ModelPush { model });
+            unsafe { device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0) };
+        }
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.recreate_pipelines();
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+            .build()]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build()]
+    }
+}
+
+impl SpecializationConstantsApp {
+    fn create_pipelines(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        push_constants: PushConstants<ModelPush>,
+        settings: LightingSettings,
+    ) -> Vec<(vk::PipelineLayout, vk::Pipeline)> {
+        LightingModel::ALL
+            .into_iter()
+            .map(|lighting_model| {
+                PipelineCreator {
+                    device: fixed_vulkan_stuff.device.clone(),
+                    extent: fixed_vulkan_stuff.surface.extent(),
+                    render_pass: fixed_vulkan_stuff.render_pass,
+                    set_layouts: &[descriptor_set_layout],
+                    vertex_bindings: &[Vertex::binding_description()],
+                    vertex_attributes: &Vertex::attr_descriptions(),
+                    pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+                    push_constant_range: push_constants.range(),
+                    lighting_model,
+                    settings,
+                }
+                .build()
+                .unwrap()
+            })
+            .collect()
+    }
+
+    /// Specialization constants and the render pass are baked in, new values or a new render
+    /// pass need new pipelines. The old ones are destroyed once the frames in flight using
+    /// them finished.
+    fn recreate_pipelines(&mut self) {
+        for (layout, pipeline) in self.pipelines.drain(..) {
+            self.fixed_vulkan_stuff.frame_garbage.retire(pipeline);
+            self.fixed_vulkan_stuff.frame_garbage.retire(layout);
+        }
+        self.pipelines = Self::create_pipelines(
+            &self.fixed_vulkan_stuff,
+            self.descriptor_set_layout,
+            self.push_constants,
+            self.settings,
+        );
+    }
+}
+
+impl Drop for SpecializationConstantsApp {
+    fn drop(&mut self) {
+        unsafe {
+            let device = &self.fixed_vulkan_stuff.device;
+            device.device_wait_idle().unwrap();
+            for &(layout, pipeline) in &self.pipelines {
+                device.destroy_pipeline(pipeline, None);
+                device.destroy_pipeline_layout(layout, None);
+            }
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+/// Baked into the pipelines, changing them rebuilds all of them
+#[derive(Clone, Copy, PartialEq)]
+struct LightingSettings {
+    toon_bands: u32,
+    shininess: f32,
+}
+
+impl Default for LightingSettings {
+    fn default() -> Self {
+        Self {
+            toon_bands: 4,
+            shininess: 32.,
+        }
+    }
+}
+
+impl LightingSettings {
+    fn draw(&mut self, ui: &imgui::Ui) {
+        if ui.collapsing_header(
+            "Specialization constants",
+            imgui::TreeNodeFlags::DEFAULT_OPEN,
+        ) {
+            ui.slider("Toon bands", 2, 8, &mut self.toon_bands);
+            ui.slider("Shininess", 2., 128., &mut self.shininess);
+        }
+    }
+}
+
+/// Places one of the spheres, pushed before its draw
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ModelPush {
+    model: Mat4,
+}
+
+unsafe impl bytemuck::Pod for ModelPush {}
+unsafe impl bytemuck::Zeroable for ModelPush {}
+
+/// Layout of `SceneUbo` in the shaders
+#[repr(C, align(16))]
+struct SceneUbo {
+    view: Mat4,
+    projection: Mat4,
+    camera_position: Vec3,
+    _padding: f32,
+    light_direction: Vec3,
+    _padding2: f32,
+}
+
+/// UV sphere of radius 1, its positions double as normals in the shaders
+fn sphere(rings: u32, segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = (0..=rings)
+        .flat_map(|ring| {
+            let theta = PI * ring as f32 / rings as f32;
+            (0..=segments).map(move |segment| {
+                let phi = 2. * PI * segment as f32 / segments as f32;
+                let position = vec3(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                Vertex::new(position)
+                    .with_color(vec3(0.9, 0.5, 0.3))
+                    .with_texture_coord(vec2(
+                        segment as f32 / segments as f32,
+                        ring as f32 / rings as f32,
+                    ))
+            })
+        })
+        .collect();
+    let indices = (0..rings)
+        .flat_map(|ring| {
+            (0..segments).flat_map(move |segment| {
+                let first = ring * (segments + 1) + segment;
+                let below = first + segments + 1;
+                [first, below, first + 1, first + 1, below, below + 1]
+            })
+        })
+        .collect();
+    (vertices, indices)
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    push_constant_range: vk::PushConstantRange,
+    lighting_model: LightingModel,
+    settings: LightingSettings,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/specialization_constants/sphere.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/specialization_constants/sphere.frag.spv"
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![self.push_constant_range]
+    }
+
+    fn specialization_constants(
+        &self,
+        stage: vk::ShaderStageFlags,
+    ) -> Option<SpecializationConstants> {
+        (stage == vk::ShaderStageFlags::FRAGMENT).then(|| {
+            SpecializationConstants::new()
+                .constant(LIGHTING_MODEL_ID, self.lighting_model as u32)
+                .constant(TOON_BANDS_ID, self.settings.toon_bands as f32)
+                .constant(SHININESS_ID, self.settings.shininess)
+        })
+    }
+
+    // The projection flips the winding, both faces go through the depth test instead
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = SpecializationConstantsApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -1,22 +1,12 @@
-use std::{cell::RefCell, rc::Rc};
+// The projection and view are written as `Mat4`s every frame, not through their fields
+#[allow(dead_code)]
+mod shader_structs;
 
-use ash::vk;
-use glam::{vec3, Mat4, Quat, Vec3, Vec4};
-use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
-
-use vulkan_example_rs::{
-    app::{FixedVulkanStuff, FrameCounter, PipelineBuilder, UIOverlay, WindowApp},
-    camera::Camera,
-    impl_drop_trait, impl_pipeline_builder_fns, impl_window_fns,
-    mesh::Vertex,
-    vulkan_wrappers::{Buffer, Device, Texture},
-};
-
-const MAX_ARRAY_COUNT: usize = 8;
+use shader_structs::Ubo;
+use vulkan_example_rs::{app::TextureInspector, prelude::*};
 
 struct TextureArrayExample {
     window: Window,
-    window_resized: bool,
 
     frame_counter: FrameCounter,
 
@@ -25,16 +15,19 @@ struct TextureArrayExample {
     model_indices: Vec<u32>,
 
     camera: Camera,
+    camera_controller: CameraController,
 
     fixed_vulkan_stuff: FixedVulkanStuff,
     descriptor_set_layout: vk::DescriptorSetLayout,
     descriptor_pool: vk::DescriptorPool,
-    descriptor_sets: [vk::DescriptorSet; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
     pipeline_layout: vk::PipelineLayout,
     pipeline: vk::Pipeline,
     vertex_buffer: Buffer<Vertex>,
     indice_buffer: Buffer<u32>,
-    uniform_buffers: [Buffer<Ubo>; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
+    uniform_buffers: PerFrame<Buffer<Ubo>>,
+    // Declared before the texture so its image view is destroyed first
+    texture_inspector: TextureInspector,
     #[allow(dead_code)]
     texture_image: Texture,
     layer_count: u32,
@@ -45,20 +38,8 @@ struct TextureArrayExample {
 impl WindowApp for TextureArrayExample {
     impl_window_fns!(TextureArrayExample);
 
-    fn draw_frame(&mut self) {
-        let frame_index = self.frame_counter.double_buffer_frame;
-        let image_index = {
-            let ret = self
-                .fixed_vulkan_stuff
-                .frame_get_image_index_to_draw(frame_index, &self.window)
-                .unwrap();
-            if ret.1 {
-                return;
-            }
-            ret.0
-        };
-
-        self.uniform_buffers[frame_index]
+    fn prepare(&mut self, frame: &FrameContext) {
+        self.uniform_buffers[frame.frame_index]
             .load_data_when_mapped(&[self.camera.perspective_mat(), self.camera.view_mat()], 0);
 
         let name = self
@@ -66,21 +47,88 @@ impl WindowApp for TextureArrayExample {
             .device
             .physical_device_name()
             .to_owned();
-        self.update_ui(&[name]);
-
-        self.record_render_commands(frame_index, image_index, self.model_indices.len() as u32);
+        let mut selection = self.texture_inspector.selection;
+        self.update_ui_with(&[name], |ui| {
+            selection.draw(ui);
+        });
+        self.texture_inspector.selection = selection;
+    }
 
-        self.window_resized = self
-            .fixed_vulkan_stuff
-            .frame_queue_submit_and_present(
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let indice_num = self.model_indices.len() as u32;
+        unsafe {
+            self.fixed_vulkan_stuff.cmd_begin_renderpass(
                 frame_index,
                 image_index,
-                &self.window,
-                self.window_resized,
-            )
-            .unwrap();
+                &Self::clear_value(),
+            );
+
+            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+
+            self.fixed_vulkan_stuff.device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.vertex_buffer.buffer()],
+                &[0],
+            );
+            self.fixed_vulkan_stuff.device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+
+            self.fixed_vulkan_stuff.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
 
-        self.frame_counter.update();
+            self.fixed_vulkan_stuff.device.cmd_draw_indexed(
+                command_buffer,
+                indice_num,
+                self.layer_count,
+                0,
+                0,
+                0,
+            );
+
+            let extent = self.fixed_vulkan_stuff.surface.extent();
+            let inspector_size = extent.height / 4;
+            self.texture_inspector.draw(
+                command_buffer,
+                extent,
+                vk::Rect2D {
+                    offset: vk::Offset2D {
+                        x: (extent.width - inspector_size - 20) as i32,
+                        y: (extent.height - inspector_size - 20) as i32,
+                    },
+                    extent: vk::Extent2D {
+                        width: inspector_size,
+                        height: inspector_size,
+                    },
+                },
+            );
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+        }
     }
 
     fn new(event_loop: &EventLoop<()>) -> Self {
@@ -88,12 +136,44 @@ impl WindowApp for TextureArrayExample {
 
         let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
 
-        let (mut texture_image, layer_count) = Texture::from_ktx(
-            "examples/textures/texture_array/texturearray_rgba.ktx",
-            fixed_vulkan_stuff.device.clone(),
-            &fixed_vulkan_stuff.graphic_command_pool,
-            &fixed_vulkan_stuff.device.graphic_queue(),
-        )
+        // Pictures given on the command line become the array layers in their order,
+        // a single KTX2 file is loaded as it is if the device can sample its format.
+        // Flags such as `--sync-validation` are left to the instance setup
+        let picture_paths = std::env::args()
+            .skip(1)
+            .filter(|arg| !arg.starts_with("--"))
+            .collect::<Vec<_>>();
+        let load_default_ktx = || {
+            Texture::from_ktx(
+                "examples/textures/texture_array/texturearray_rgba.ktx",
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            )
+        };
+        let (mut texture_image, layer_count) = if picture_paths.is_empty() {
+            load_default_ktx()
+        } else if picture_paths.len() == 1 && picture_paths[0].ends_with(".ktx2") {
+            match Texture::from_ktx2(
+                &picture_paths[0],
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            ) {
+                Err(RenderError::FormatNotSupported(reason)) => {
+                    println!("{reason}, falling back to the default texture array");
+                    load_default_ktx()
+                }
+                result => result,
+            }
+        } else {
+            Texture::array_from_paths(
+                &picture_paths,
+                fixed_vulkan_stuff.device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &fixed_vulkan_stuff.device.graphic_queue(),
+            )
+        }
         .unwrap();
         texture_image.spawn_image_view().unwrap();
         texture_image.set_sampler(Rc::new({
@@ -103,28 +183,26 @@ impl WindowApp for TextureArrayExample {
                 .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
                 .address_mode_v(vk::SamplerAddressMode::REPEAT)
                 .address_mode_w(vk::SamplerAddressMode::REPEAT)
-                .anisotropy_enable(true)
-                .max_anisotropy(unsafe {
+                .anisotropy_enable(
                     fixed_vulkan_stuff
                         .device
-                        .instance()
-                        .get_physical_device_properties(
-                            *fixed_vulkan_stuff
-                                .device
-                                .physical_device()
-                                .upgrade()
-                                .unwrap(),
-                        )
-                        .limits
+                        .capabilities()
                         .max_sampler_anisotropy
-                })
+                        > 1.,
+                )
+                .max_anisotropy(
+                    fixed_vulkan_stuff
+                        .device
+                        .capabilities()
+                        .max_sampler_anisotropy,
+                )
                 .border_color(vk::BorderColor::INT_OPAQUE_WHITE)
                 .unnormalized_coordinates(false)
                 .compare_enable(false)
                 .compare_op(vk::CompareOp::NEVER)
                 .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
                 .mip_lod_bias(0.)
-                .max_lod(0.)
+                .max_lod(texture_image.mip_levels() as f32)
                 .min_lod(0.)
                 .build();
 
@@ -178,53 +256,43 @@ impl WindowApp for TextureArrayExample {
             .device_local_indice_buffer(&model_indices)
             .unwrap();
 
-        let uniform_buffers: [_; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT] =
-            array_init::array_init(|_| {
-                let mut buffer = Buffer::<Ubo>::new(
-                    1,
-                    vk::BufferUsageFlags::UNIFORM_BUFFER,
-                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                    fixed_vulkan_stuff.device.clone(),
+        let uniform_buffers = fixed_vulkan_stuff.per_frame(|_| {
+            let mut buffer = Buffer::<Ubo>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                fixed_vulkan_stuff.device.host_write_memory_properties(),
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .unwrap();
+            let mut ubo_data: Ubo = bytemuck::Zeroable::zeroed();
+
+            let offset = -1.5;
+            let center = (layer_count as f32 * offset) / 2.0 - (offset * 0.5);
+            for i in 0..layer_count as usize {
+                // Instance model matrix
+                ubo_data.instance[i].model = Mat4::from_scale_rotation_translation(
+                    Vec3::ONE * 0.5,
+                    Quat::IDENTITY,
+                    vec3(i as f32 * offset - center, 0., 0.),
                 )
+                .to_cols_array_2d();
+                // Instance texture array index
+                ubo_data.instance[i].array_index[0] = i as f32;
+            }
+            let instances_offset = std::mem::offset_of!(Ubo, instance) as u64;
+            buffer
+                .load_data(&ubo_data.instance, instances_offset)
                 .unwrap();
-                let mut ubo_data = Ubo::default();
-
-                let offset = -1.5;
-                let center = (layer_count as f32 * offset) / 2.0 - (offset * 0.5);
-                for i in 0..layer_count as usize {
-                    // Instance model matrix
-                    ubo_data.instances[i].model = Mat4::from_scale_rotation_translation(
-                        Vec3::ONE * 0.5,
-                        Quat::IDENTITY,
-                        vec3(i as f32 * offset - center, 0., 0.),
-                    );
-                    // Instance texture array index
-                    ubo_data.instances[i].array_index.x = i as f32;
-                }
-                buffer
-                    .load_data(&ubo_data.instances, std::mem::size_of::<Mat4>() as u64 * 2)
-                    .unwrap();
-                buffer
-                    .map_memory(0, std::mem::size_of::<Mat4>() as u64 * 2)
-                    .unwrap();
-                buffer
-            });
+            buffer.map_memory(0, instances_offset).unwrap();
+            buffer
+        });
 
         let descriptor_set_layout =
             Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
         let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
 
-        let pipeline_creator = PipelineCreator {
-            device: fixed_vulkan_stuff.device.clone(),
-            extent: fixed_vulkan_stuff.surface.extent(),
-            render_pass: fixed_vulkan_stuff.render_pass,
-            set_layouts: &[descriptor_set_layout],
-            vertex_bindings: &[Vertex::binding_description()],
-            vertex_attributes: &Vertex::attr_descriptions(),
-            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
-        };
-
-        let (pipeline_layout, pipeline) = pipeline_creator.build().unwrap();
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
 
         let descriptor_sets = Self::create_descriptor_sets(
             descriptor_pool,
@@ -233,33 +301,18 @@ impl WindowApp for TextureArrayExample {
         )
         .unwrap();
 
-        {
-            for i in 0..FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT {
-                let uniform_descritptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_sets[i])
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&[uniform_buffers[i].descriptor_default()])
-                    .build();
-
-                let image_descritptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_sets[i])
-                    .dst_binding(1)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[texture_image.descriptor_default()])
-                    .build();
-
-                unsafe {
-                    fixed_vulkan_stuff.device.update_descriptor_sets(
-                        &[uniform_descritptor_write, image_descritptor_write],
-                        &[],
-                    )
-                }
-            }
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .uniform_buffer(0, uniform_buffer)
+                .combined_image_sampler(1, &texture_image)
+                .update(*descriptor_set)
+                .unwrap();
         }
 
+        let texture_inspector =
+            TextureInspector::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, &texture_image).unwrap();
+
         let ui_overlay = UIOverlay::new(
             fixed_vulkan_stuff.pipeline_cache,
             fixed_vulkan_stuff.render_pass,
@@ -270,7 +323,6 @@ impl WindowApp for TextureArrayExample {
 
         TextureArrayExample {
             window,
-            window_resized: false,
 
             frame_counter: FrameCounter::default(),
 
@@ -282,6 +334,7 @@ impl WindowApp for TextureArrayExample {
                 .move_speed(100.)
                 .rotate_speed(40.)
                 .build(),
+            camera_controller: CameraController::new(),
             fixed_vulkan_stuff,
             descriptor_set_layout,
             descriptor_pool,
@@ -291,12 +344,27 @@ impl WindowApp for TextureArrayExample {
             vertex_buffer,
             indice_buffer,
             uniform_buffers,
+            texture_inspector,
             texture_image,
             layer_count,
             ui_overlay,
         }
     }
 
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.texture_inspector
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
     fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
         vec![
             vk::DescriptorType::UNIFORM_BUFFER,
@@ -330,75 +398,21 @@ impl WindowApp for TextureArrayExample {
 }
 
 impl TextureArrayExample {
-    fn record_render_commands(&mut self, frame_index: usize, image_index: usize, indice_num: u32) {
-        let command_buffer = self.fixed_vulkan_stuff.graphic_command_buffers[frame_index];
-        unsafe {
-            self.fixed_vulkan_stuff
-                .device
-                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())
-                .expect("Fail to reset command buffer");
-
-            self.fixed_vulkan_stuff
-                .device
-                .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())
-                .expect("Fail to begin command buffer");
-
-            self.fixed_vulkan_stuff.cmd_begin_renderpass(
-                frame_index,
-                image_index,
-                &Self::clear_value(),
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_bind_vertex_buffers(
-                command_buffer,
-                0,
-                &[self.vertex_buffer.buffer()],
-                &[0],
-            );
-            self.fixed_vulkan_stuff.device.cmd_bind_index_buffer(
-                command_buffer,
-                self.indice_buffer.buffer(),
-                0,
-                vk::IndexType::UINT32,
-            );
-
-            self.fixed_vulkan_stuff
-                .cmd_set_viewport_and_scissor(frame_index);
-
-            self.fixed_vulkan_stuff.device.cmd_bind_descriptor_sets(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                0,
-                &[self.descriptor_sets[frame_index]],
-                &[],
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_draw_indexed(
-                command_buffer,
-                indice_num,
-                self.layer_count,
-                0,
-                0,
-                0,
-            );
-
-            self.ui_overlay.draw(command_buffer, frame_index);
-
-            self.fixed_vulkan_stuff
-                .device
-                .cmd_end_render_pass(command_buffer);
-            self.fixed_vulkan_stuff
-                .device
-                .end_command_buffer(command_buffer)
-                .unwrap();
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
         }
+        .build()
+        .unwrap()
     }
 }
 
@@ -450,21 +464,6 @@ impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
     }
 }
 
-#[derive(Default)]
-struct InstanceData {
-    pub model: Mat4,
-    pub array_index: Vec4,
-}
-
-#[derive(Default)]
-struct Ubo {
-    #[allow(dead_code)]
-    pub projection: Mat4,
-    #[allow(dead_code)]
-    pub view: Mat4,
-    pub instances: [InstanceData; MAX_ARRAY_COUNT],
-}
-
 fn main() {
     let mut event_loop = RefCell::new(EventLoop::new());
     let mut app = TextureArrayExample::new(&event_loop.borrow());
@@ -0,0 +1,36 @@
+// Generated by `cargo xtask shader-structs examples/shaders/texture_array/shader.vert.spv`
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Instance {
+    pub model: [[f32; 4]; 4],
+    pub array_index: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for Instance {}
+unsafe impl bytemuck::Zeroable for Instance {}
+
+const _: () = {
+    assert!(std::mem::offset_of!(Instance, model) == 0);
+    assert!(std::mem::offset_of!(Instance, array_index) == 64);
+    assert!(std::mem::size_of::<Instance>() == 80);
+};
+
+/// Uniform block at set 0 binding 0
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Ubo {
+    pub projection: [[f32; 4]; 4],
+    pub view: [[f32; 4]; 4],
+    pub instance: [Instance; 8],
+}
+
+unsafe impl bytemuck::Pod for Ubo {}
+unsafe impl bytemuck::Zeroable for Ubo {}
+
+const _: () = {
+    assert!(std::mem::offset_of!(Ubo, projection) == 0);
+    assert!(std::mem::offset_of!(Ubo, view) == 64);
+    assert!(std::mem::offset_of!(Ubo, instance) == 128);
+    assert!(std::mem::size_of::<Ubo>() == 768);
+};
@@ -1,35 +1,27 @@
-use std::{cell::RefCell, rc::Rc};
-
-use ash::vk;
-use glam::{vec3, Mat4, Vec3};
-use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
-
 use vulkan_example_rs::{
-    app::{FixedVulkanStuff, FrameCounter, PipelineBuilder, UIOverlay, WindowApp},
-    camera::{Camera, MVPMatrix},
-    impl_drop_trait, impl_pipeline_builder_fns, impl_window_fns,
-    mesh::Vertex,
-    vulkan_wrappers::{Buffer, Device},
+    app::{ReferenceOverlay, ReferenceSettings, SimpleMeshRenderer},
+    include_spv,
+    prelude::*,
 };
 
 struct DrawTriangleApp {
     window: Window,
-    window_resized: bool,
 
     frame_counter: FrameCounter,
     ui_overlay: UIOverlay,
 
     camera: Camera,
+    camera_controller: CameraController,
 
     fixed_vulkan_stuff: FixedVulkanStuff,
-    descriptor_set_layout: vk::DescriptorSetLayout,
-    descriptor_pool: vk::DescriptorPool,
-    descriptor_sets: [vk::DescriptorSet; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
-    pipeline_layout: vk::PipelineLayout,
-    pipeline: vk::Pipeline,
-    vertex_buffer: Buffer<Vertex>,
-    indice_buffer: Buffer<u32>,
-    uniform_buffers: [Buffer<MVPMatrix>; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
+    mesh_renderer: SimpleMeshRenderer,
+    /// Grid and axes around the lone triangle
+    reference_overlay: ReferenceOverlay,
+    /// Matrix and overlay of the last full frame, the scene only needs a redraw when they change
+    last_scene: Option<(MVPMatrix, ReferenceSettings)>,
+    /// Set by `prepare` when the scene is unchanged, the last full frame is then copied in
+    /// instead of drawn again
+    ui_only: bool,
 }
 
 impl WindowApp for DrawTriangleApp {
@@ -37,254 +29,133 @@ impl WindowApp for DrawTriangleApp {
 
     fn new(event_loop: &winit::event_loop::EventLoop<()>) -> Self {
         let window = Self::build_window(event_loop);
-        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
-        let descriptor_set_layout =
-            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
-        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
-        let descriptor_sets = Self::create_descriptor_sets(
-            descriptor_pool,
-            descriptor_set_layout,
-            &fixed_vulkan_stuff.device,
-        )
-        .unwrap();
-
-        let pipeline_creator = PipelineCreator {
-            device: fixed_vulkan_stuff.device.clone(),
-            extent: fixed_vulkan_stuff.surface.extent(),
-            render_pass: fixed_vulkan_stuff.render_pass,
-            set_layouts: &[descriptor_set_layout],
-            vertex_bindings: &[Vertex::binding_description()],
-            vertex_attributes: &Vertex::attr_descriptions(),
-            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
-        };
-        let (pipeline_layout, pipeline) = pipeline_creator.build().unwrap();
-
+        let mut fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        if let Err(e) = fixed_vulkan_stuff.enable_ui_only_redraw() {
+            println!("UI-only redraw disabled: {e}");
+        }
         let model_vertices = vec![vec3(-0.5, -0.5, 0.), vec3(0.5, -0.5, 0.), vec3(0., 0.5, 0.)]
             .into_iter()
             .map(|v3| Vertex::new(v3).with_color(v3 + vec3(0.5, 0.4, 0.3)))
             .collect::<Vec<_>>();
+        let mesh_renderer = SimpleMeshRenderer::builder(
+            "examples/shaders/triangle/shader.vert.spv",
+            "examples/shaders/triangle/shader.frag.spv",
+        )
+        .embedded_spv(
+            include_spv!("examples/shaders/triangle/shader.vert.spv"),
+            include_spv!("examples/shaders/triangle/shader.frag.spv"),
+        )
+        .build(&fixed_vulkan_stuff, &model_vertices, &[0, 1, 2, 1, 0, 2])
+        .unwrap();
 
-        let vertex_buffer = fixed_vulkan_stuff
-            .device_local_vertex_buffer(&model_vertices)
-            .unwrap();
-        let indice_buffer = fixed_vulkan_stuff
-            .device_local_indice_buffer(&[0, 1, 2, 1, 0, 2])
-            .unwrap();
-
-        let uniform_buffers: [_; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT] =
-            array_init::array_init(|_| {
-                let mut buffer = Buffer::<MVPMatrix>::new(
-                    1,
-                    vk::BufferUsageFlags::UNIFORM_BUFFER,
-                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                    fixed_vulkan_stuff.device.clone(),
-                )
-                .unwrap();
-                buffer.map_memory_all().unwrap();
-                buffer
-            });
-
-        {
-            for i in 0..FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT {
-                let uniform_buffer_info = uniform_buffers[i].descriptor_default();
-                let uniform_descritptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_sets[i])
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&[uniform_buffer_info])
-                    .build();
-
-                unsafe {
-                    fixed_vulkan_stuff
-                        .device
-                        .update_descriptor_sets(&[uniform_descritptor_write], &[])
-                }
-            }
-        }
+        let mut reference_overlay =
+            ReferenceOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff).unwrap();
+        // Level with the lowest vertex on screen, +y points down as in the clip space of Vulkan
+        reference_overlay.settings.height = 0.5;
 
         let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
 
         DrawTriangleApp {
             window,
-            window_resized: false,
             fixed_vulkan_stuff,
-            vertex_buffer,
-            indice_buffer,
-            uniform_buffers,
-            pipeline_layout,
-            pipeline,
+            mesh_renderer,
+            reference_overlay,
+            last_scene: None,
+            ui_only: false,
             frame_counter: FrameCounter::default(),
             camera: Camera::builder()
                 .translation(Vec3::new(0., 0., -3.))
                 .move_speed(100.)
                 .rotate_speed(40.)
                 .build(),
-            descriptor_set_layout,
-            descriptor_pool,
-            descriptor_sets,
+            camera_controller: CameraController::new(),
             ui_overlay,
         }
     }
 
-    fn draw_frame(&mut self) {
-        let frame_index = self.frame_counter().double_buffer_frame;
-        let image_index = {
-            let ret = self
-                .fixed_vulkan_stuff
-                .frame_get_image_index_to_draw(frame_index, &self.window)
-                .unwrap();
-            if ret.1 {
-                return;
-            }
-            ret.0
-        };
-
-        self.uniform_buffers[frame_index]
-            .load_data_when_mapped(&[self.camera.mvp_matrix(Mat4::IDENTITY)], 0);
-
+    fn prepare(&mut self, frame: &FrameContext) {
         let name = self
             .fixed_vulkan_stuff
             .device
             .physical_device_name()
             .to_owned();
-        self.update_ui(&[name]);
-
-        self.record_render_commands(frame_index, image_index, 6);
-
-        self.window_resized = self
-            .fixed_vulkan_stuff
-            .frame_queue_submit_and_present(
-                frame_index,
-                image_index,
-                &self.window,
-                self.window_resized,
-            )
-            .unwrap();
-
-        self.frame_counter.update();
-    }
-
-    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
-        vec![vk::DescriptorPoolSize::builder()
-            .ty(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
-            .build()]
-    }
-
-    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
-        let ubo_layout_binding = vk::DescriptorSetLayoutBinding::builder()
-            .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .descriptor_count(1)
-            .build();
-
-        vec![ubo_layout_binding]
+        let mut settings = self.reference_overlay.settings;
+        self.update_ui_with(&[name], |ui| {
+            settings.draw(ui);
+        });
+        self.reference_overlay.settings = settings;
+
+        let scene = (self.camera.mvp_matrix(Mat4::IDENTITY), settings);
+        self.ui_only =
+            self.fixed_vulkan_stuff.can_redraw_ui_only() && self.last_scene == Some(scene);
+        if !self.ui_only {
+            self.mesh_renderer.update_mvp(frame.frame_index, &scene.0);
+            self.last_scene = Some(scene);
+        }
     }
-}
 
-impl DrawTriangleApp {
-    fn record_render_commands(&mut self, frame_index: usize, image_index: usize, indice_num: u32) {
-        let command_buffer = self.fixed_vulkan_stuff.graphic_command_buffers[frame_index];
-        unsafe {
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        if self.ui_only {
             self.fixed_vulkan_stuff
-                .device
-                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())
-                .expect("Fail to reset command buffer");
-
+                .cmd_restore_scene(frame_index, image_index);
             self.fixed_vulkan_stuff
-                .device
-                .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())
-                .expect("Fail to begin command buffer");
-
-            self.fixed_vulkan_stuff.cmd_begin_renderpass(
-                frame_index,
-                image_index,
-                &Self::clear_value(),
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_bind_vertex_buffers(
-                command_buffer,
-                0,
-                &[self.vertex_buffer.buffer()],
-                &[0],
-            );
-            self.fixed_vulkan_stuff.device.cmd_bind_index_buffer(
-                command_buffer,
-                self.indice_buffer.buffer(),
-                0,
-                vk::IndexType::UINT32,
-            );
-
+                .cmd_begin_overlay_renderpass(frame_index, image_index);
             self.fixed_vulkan_stuff
                 .cmd_set_viewport_and_scissor(frame_index);
-
-            self.fixed_vulkan_stuff.device.cmd_bind_descriptor_sets(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                0,
-                &[self.descriptor_sets[frame_index]],
-                &[],
-            );
-
-            self.fixed_vulkan_stuff
-                .device
-                .cmd_draw_indexed(command_buffer, indice_num, 1, 0, 0, 0);
-
             self.ui_overlay.draw(command_buffer, frame_index);
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+            return;
+        }
 
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        self.mesh_renderer.cmd_draw(command_buffer, frame_index);
+        self.reference_overlay.cmd_draw(
+            command_buffer,
+            &self.camera,
+            self.fixed_vulkan_stuff.surface.extent(),
+        );
+
+        // The UI goes into its own pass when the scene is kept for UI-only frames
+        if self.fixed_vulkan_stuff.scene_cache.is_some() {
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
             self.fixed_vulkan_stuff
-                .device
-                .cmd_end_render_pass(command_buffer);
+                .cmd_store_scene(frame_index, image_index);
             self.fixed_vulkan_stuff
-                .device
-                .end_command_buffer(command_buffer)
-                .unwrap();
+                .cmd_begin_overlay_renderpass(frame_index, image_index);
         }
-    }
-}
-
-impl_drop_trait!(DrawTriangleApp);
 
-struct PipelineCreator<'a> {
-    device: Rc<Device>,
-    extent: vk::Extent2D,
-    render_pass: vk::RenderPass,
-    set_layouts: &'a [vk::DescriptorSetLayout],
-    vertex_bindings: &'a [vk::VertexInputBindingDescription],
-    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
-    pipeline_cache: vk::PipelineCache,
-}
+        self.ui_overlay.draw(command_buffer, frame_index);
 
-impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
-    impl_pipeline_builder_fns!();
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
 
-    fn vertex_spv_path(&self) -> &'a str {
-        "examples/shaders/triangle/shader.vert.spv"
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.mesh_renderer
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+        self.reference_overlay
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
     }
 
-    fn frag_spv_path(&self) -> &'a str {
-        "examples/shaders/triangle/shader.frag.spv"
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![]
     }
 
-    fn pipeline_layout(&self) -> vk::PipelineLayout {
-        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
-            .set_layouts(self.set_layouts)
-            .push_constant_ranges(&[])
-            .build();
-        unsafe {
-            self.device
-                .create_pipeline_layout(&pipeline_layout_info, None)
-                .unwrap()
-        }
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![]
     }
 }
 
@@ -0,0 +1,112 @@
+use std::time::Instant;
+
+use winit::window::WindowBuilder;
+
+use vulkan_example_rs::prelude::*;
+
+const UPLOAD_SIZES_IN_BYTES: [usize; 4] = [64 << 10, 1 << 20, 16 << 20, 64 << 20];
+const ITERATIONS: usize = 8;
+
+/// Throughput in MiB/s of creating and filling `ITERATIONS` vertex buffers of `size_in_bytes`
+fn measure<F: FnMut(&[u32]) -> RenderResult<Buffer<u32>>>(
+    size_in_bytes: usize,
+    mut upload: F,
+) -> RenderResult<f64> {
+    let data = (0..size_in_bytes / 4).map(|i| i as u32).collect::<Vec<_>>();
+    // Warm up allocations and pipelines of the driver first
+    upload(&data)?;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        upload(&data)?;
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    Ok((size_in_bytes * ITERATIONS) as f64 / (1024. * 1024.) / seconds)
+}
+
+fn main() -> RenderResult<()> {
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("UploadBenchmark")
+        .with_inner_size(PhysicalSize::new(64, 64))
+        .with_visible(false)
+        .build(&event_loop)?;
+    let instance = Rc::new(
+        Instance::builder()
+            .window(&window)
+            .app_name_and_version("UploadBenchmark", 0)
+            .engine_name_and_version("No Engine", 0)
+            .debug_strategy(VulkanDebugInfoStrategy::DEFAULT_PANIC_ON_ERRORS)
+            .build()?,
+    );
+    let fixed_vulkan_stuff = FixedVulkanStuff::new(&window, instance)?;
+    let device = &fixed_vulkan_stuff.device;
+
+    println!("{}", device.physical_device_name());
+    match device.upload_heap_size() {
+        Some(size) => println!(
+            "host visible device local heap: {} MiB, direct upload {}",
+            size >> 20,
+            if device.supports_direct_upload() {
+                "preferred"
+            } else {
+                "not preferred"
+            }
+        ),
+        None => println!("no host visible device local heap, direct upload unavailable"),
+    }
+
+    // The preferred upload path must land the data unchanged before it is timed
+    let data = (0..1024).collect::<Vec<u32>>();
+    let uploaded = Buffer::new_device_local(
+        &data,
+        vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC,
+        device.clone(),
+        &fixed_vulkan_stuff.graphic_command_pool,
+        &device.graphic_queue(),
+    )?;
+    assert!(
+        uploaded.read_back(
+            &fixed_vulkan_stuff.graphic_command_pool,
+            &device.graphic_queue()
+        )? == data,
+        "uploaded data differs from what was read back"
+    );
+
+    println!(
+        "{:>10} {:>14} {:>14}",
+        "size", "staged MiB/s", "direct MiB/s"
+    );
+    for size_in_bytes in UPLOAD_SIZES_IN_BYTES {
+        let staged = measure(size_in_bytes, |data| {
+            Buffer::new_device_local_staged(
+                data,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                device.clone(),
+                &fixed_vulkan_stuff.graphic_command_pool,
+                &device.graphic_queue(),
+            )
+        })?;
+        let direct = match device.upload_heap_size() {
+            // The whole upload must fit into the heap next to what the driver keeps there
+            Some(heap_size) if heap_size as usize > 2 * size_in_bytes => {
+                let direct = measure(size_in_bytes, |data| {
+                    Buffer::new_device_local_direct(
+                        data,
+                        vk::BufferUsageFlags::VERTEX_BUFFER,
+                        device.clone(),
+                    )
+                })?;
+                format!("{direct:.1}")
+            }
+            _ => "n/a".to_string(),
+        };
+        println!(
+            "{:>7} KiB {:>14.1} {:>14}",
+            size_in_bytes >> 10,
+            staged,
+            direct
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,371 @@
+use std::{f32::consts::PI, time::Instant};
+
+use vulkan_example_rs::{
+    app::{VertexFormat, VertexFormatPipelines},
+    mesh::{PosNormalUv, PosOnly},
+    prelude::*,
+    vulkan_wrappers::PushConstants,
+};
+
+const MESH_SPACING: f32 = 3.;
+
+struct VertexInputDynamicStateApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    push_constants: PushConstants<MeshPush>,
+    /// One pipeline for the three vertex formats, or one each without the extension
+    pipelines: VertexFormatPipelines,
+    vertex_buffer: Buffer<Vertex>,
+    pos_only_buffer: Buffer<PosOnly>,
+    pos_normal_uv_buffer: Buffer<PosNormalUv>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    start: Instant,
+    settings: VertexInputSettings,
+}
+
+impl WindowApp for VertexInputDynamicStateApp {
+    impl_window_fns!(VertexInputDynamicStateApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+
+        let push_constants = PushConstants::new(
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+        let settings = VertexInputSettings {
+            dynamic: fixed_vulkan_stuff
+                .device
+                .supports_vertex_input_dynamic_state(),
+        };
+        let pipelines = Self::create_pipelines(&fixed_vulkan_stuff, push_constants, settings);
+
+        // The same cube in three vertex formats
+        let (corners, indices) = cube();
+        let vertices = corners
+            .iter()
+            .map(|&pos| Vertex::new(pos))
+            .collect::<Vec<_>>();
+        let pos_only = corners
+            .iter()
+            .map(|&pos| PosOnly { pos })
+            .collect::<Vec<_>>();
+        let pos_normal_uv = corners
+            .iter()
+            .map(|&pos| PosNormalUv {
+                pos,
+                normal: pos.normalize(),
+                uv: Vec2::ZERO,
+            })
+            .collect::<Vec<_>>();
+        let vertex_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&vertices)
+            .unwrap();
+        let pos_only_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&pos_only)
+            .unwrap();
+        let pos_normal_uv_buffer = fixed_vulkan_stuff
+            .device_local_vertex_buffer(&pos_normal_uv)
+            .unwrap();
+        let indice_buffer = fixed_vulkan_stuff
+            .device_local_indice_buffer(&indices)
+            .unwrap();
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+
+        VertexInputDynamicStateApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .orbit(Vec3::ZERO, 10., PI / 4., 0.4)
+                .move_speed(20.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            push_constants,
+            pipelines,
+            vertex_buffer,
+            pos_only_buffer,
+            pos_normal_uv_buffer,
+            indice_buffer,
+            index_count: indices.len() as u32,
+            start: Instant::now(),
+            settings,
+        }
+    }
+
+    fn prepare(&mut self, _frame: &FrameContext) {
+        let supported = self
+            .fixed_vulkan_stuff
+            .device
+            .supports_vertex_input_dynamic_state();
+        let infos = [
+            self.fixed_vulkan_stuff
+                .device
+                .physical_device_name()
+                .to_owned(),
+            format!(
+                "3 vertex formats, {} pipeline(s), vertex input {}",
+                self.pipelines.pipeline_count(),
+                if self.pipelines.is_dynamic() {
+                    "dynamic"
+                } else {
+                    "baked"
+                }
+            ),
+        ];
+        let mut settings = self.settings;
+        self.update_ui_with(&infos, |ui| settings.draw(ui, supported));
+        if settings.dynamic != self.settings.dynamic {
+            self.settings = settings;
+            self.recreate_pipelines();
+        }
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let device = self.fixed_vulkan_stuff.device.clone();
+
+        self.fixed_vulkan_stuff.cmd_begin_renderpass(
+            frame_index,
+            image_index,
+            &Self::clear_value(),
+        );
+        self.fixed_vulkan_stuff
+            .cmd_set_viewport_and_scissor(frame_index);
+        unsafe {
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+
+        // Each format is bound with its own vertex input, the pipeline may stay the same
+        self.pipelines.cmd_bind::<Vertex>(command_buffer).unwrap();
+        self.cmd_draw_cube(command_buffer, self.vertex_buffer.buffer(), 0);
+        self.pipelines.cmd_bind::<PosOnly>(command_buffer).unwrap();
+        self.cmd_draw_cube(command_buffer, self.pos_only_buffer.buffer(), 1);
+        self.pipelines
+            .cmd_bind::<PosNormalUv>(command_buffer)
+            .unwrap();
+        self.cmd_draw_cube(command_buffer, self.pos_normal_uv_buffer.buffer(), 2);
+
+        self.ui_overlay.draw(command_buffer, frame_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.recreate_pipelines();
+        }
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        vec![]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![]
+    }
+}
+
+impl VertexInputDynamicStateApp {
+    fn create_pipelines(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        push_constants: PushConstants<MeshPush>,
+        settings: VertexInputSettings,
+    ) -> VertexFormatPipelines {
+        let formats = vec![
+            VertexFormat::of::<Vertex>(),
+            VertexFormat::of::<PosOnly>(),
+            VertexFormat::of::<PosNormalUv>(),
+        ];
+        let build = |format: Option<&VertexFormat>| {
+            PipelineCreator {
+                device: fixed_vulkan_stuff.device.clone(),
+                extent: fixed_vulkan_stuff.surface.extent(),
+                render_pass: fixed_vulkan_stuff.render_pass,
+                vertex_bindings: format.map_or(&[], |format| &format.bindings),
+                vertex_attributes: format.map_or(&[], |format| &format.attributes),
+                pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+                push_constant_range: push_constants.range(),
+                dynamic_vertex_input: format.is_none(),
+            }
+            .build()
+        };
+        let device = fixed_vulkan_stuff.device.clone();
+        match settings.dynamic {
+            true => VertexFormatPipelines::new(formats, device, build),
+            false => VertexFormatPipelines::per_format(formats, device, build),
+        }
+        .unwrap()
+    }
+
+    /// The old pipelines are destroyed once the frames in flight using them finished
+    fn recreate_pipelines(&mut self) {
+        let pipelines =
+            Self::create_pipelines(&self.fixed_vulkan_stuff, self.push_constants, self.settings);
+        let old_pipelines = std::mem::replace(&mut self.pipelines, pipelines);
+        self.fixed_vulkan_stuff
+            .frame_garbage
+            .retire_owned(old_pipelines);
+    }
+
+    fn cmd_draw_cube(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        vertex_buffer: vk::Buffer,
+        slot: usize,
+    ) {
+        const COLORS: [Vec3; 3] = [
+            vec3(0.9, 0.3, 0.2),
+            vec3(0.2, 0.7, 0.3),
+            vec3(0.2, 0.4, 0.9),
+        ];
+        let device = &self.fixed_vulkan_stuff.device;
+        let time = self.start.elapsed().as_secs_f32();
+        let model = Mat4::from_translation(Vec3::X * (slot as f32 - 1.) * MESH_SPACING)
+            * Mat4::from_rotation_y(time + slot as f32);
+        self.push_constants.cmd_push(
+            device,
+            command_buffer,
+            self.pipelines.pipeline_layout(),
+            &MeshPush {
+                mvp: self.camera.perspective_mat() * self.camera.view_mat() * model,
+                color: COLORS[slot].extend(1.),
+            },
+        );
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer], &[0]);
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+}
+
+impl Drop for VertexInputDynamicStateApp {
+    fn drop(&mut self) {
+        unsafe { self.fixed_vulkan_stuff.device.device_wait_idle().unwrap() };
+    }
+}
+
+#[derive(Clone, Copy)]
+struct VertexInputSettings {
+    /// One pipeline with a dynamic vertex input instead of one per format
+    dynamic: bool,
+}
+
+impl VertexInputSettings {
+    fn draw(&mut self, ui: &imgui::Ui, supported: bool) {
+        if ui.collapsing_header("Vertex input", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            if supported {
+                ui.checkbox("Dynamic vertex input", &mut self.dynamic);
+            } else {
+                ui.text_disabled("VK_EXT_vertex_input_dynamic_state is not supported");
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MeshPush {
+    mvp: Mat4,
+    color: Vec4,
+}
+
+unsafe impl bytemuck::Pod for MeshPush {}
+unsafe impl bytemuck::Zeroable for MeshPush {}
+
+/// Corners of a unit cube and the triangles of its faces
+fn cube() -> (Vec<Vec3>, Vec<u32>) {
+    let corners = (0..8)
+        .map(|i| {
+            vec3(
+                if i & 1 == 0 { -1. } else { 1. },
+                if i & 2 == 0 { -1. } else { 1. },
+                if i & 4 == 0 { -1. } else { 1. },
+            )
+        })
+        .collect();
+    let indices = [
+        [0, 1, 3, 2],
+        [4, 5, 7, 6],
+        [0, 1, 5, 4],
+        [2, 3, 7, 6],
+        [0, 2, 6, 4],
+        [1, 3, 7, 5],
+    ]
+    .into_iter()
+    .flat_map(|[a, b, c, d]| [a, b, c, a, c, d])
+    .collect();
+    (corners, indices)
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    push_constant_range: vk::PushConstantRange,
+    dynamic_vertex_input: bool,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/vertex_input_dynamic_state/mesh.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/vertex_input_dynamic_state/mesh.frag.spv"
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![self.push_constant_range]
+    }
+
+    fn dynamic_vertex_input(&self) -> bool {
+        self.dynamic_vertex_input
+    }
+
+    // The projection flips the winding, both faces go through the depth test instead
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = VertexInputDynamicStateApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
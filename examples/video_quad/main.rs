@@ -0,0 +1,370 @@
+use vulkan_example_rs::{
+    prelude::*,
+    vulkan_wrappers::{H264DecodeProfile, Nv12Texture, VideoDecodeCapabilities, YcbcrSampler},
+};
+
+const VIDEO_WIDTH: u32 = 640;
+const VIDEO_HEIGHT: u32 = 360;
+
+struct VideoQuadApp {
+    window: Window,
+
+    frame_counter: FrameCounter,
+    ui_overlay: UIOverlay,
+
+    camera: Camera,
+    camera_controller: CameraController,
+
+    fixed_vulkan_stuff: FixedVulkanStuff,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    // One texture per frame in flight, so a frame can be uploaded while the other is sampled
+    video_frames: PerFrame<Nv12Texture>,
+    decoder: PatternDecoder,
+    hardware_decode_info: String,
+}
+
+impl WindowApp for VideoQuadApp {
+    impl_window_fns!(VideoQuadApp);
+
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let window = Self::build_window(event_loop);
+        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        assert!(
+            fixed_vulkan_stuff.device.supports_ycbcr_conversion(),
+            "VK_KHR_sampler_ycbcr_conversion is not supported by {}",
+            fixed_vulkan_stuff.device.physical_device_name()
+        );
+
+        let ycbcr_sampler = Rc::new(
+            YcbcrSampler::new(Nv12Texture::FORMAT, fixed_vulkan_stuff.device.clone()).unwrap(),
+        );
+        let video_frames = fixed_vulkan_stuff.per_frame(|_| {
+            Nv12Texture::with_sampler(VIDEO_WIDTH, VIDEO_HEIGHT, ycbcr_sampler.clone()).unwrap()
+        });
+
+        // The YCbCr sampler has to be immutable, so the layout can not come from
+        // `descriptor_set_layout_bindings`
+        let descriptor_set_layout = {
+            let bindings = [ycbcr_sampler.layout_binding(0, vk::ShaderStageFlags::FRAGMENT)];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&bindings)
+                .build();
+            unsafe {
+                fixed_vulkan_stuff
+                    .device
+                    .create_descriptor_set_layout(&create_info, None)
+                    .unwrap()
+            }
+        };
+        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
+        let descriptor_sets = Self::create_descriptor_sets(
+            descriptor_pool,
+            descriptor_set_layout,
+            &fixed_vulkan_stuff.device,
+        )
+        .unwrap();
+
+        let bindings = Self::descriptor_set_layout_bindings();
+        for (descriptor_set, video_frame) in descriptor_sets.iter().zip(video_frames.iter()) {
+            DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .image_info(
+                    0,
+                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    video_frame.descriptor(),
+                )
+                .update(*descriptor_set)
+                .unwrap();
+        }
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(&fixed_vulkan_stuff, descriptor_set_layout);
+
+        let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
+        let hardware_decode_info = hardware_decode_info(&fixed_vulkan_stuff.device);
+
+        VideoQuadApp {
+            window,
+            frame_counter: FrameCounter::default(),
+            ui_overlay,
+            camera: Camera::builder()
+                .translation(Vec3::new(0., 0., -3.))
+                .move_speed(100.)
+                .rotate_speed(40.)
+                .build(),
+            camera_controller: CameraController::new(),
+            fixed_vulkan_stuff,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            video_frames,
+            decoder: PatternDecoder::new(VIDEO_WIDTH, VIDEO_HEIGHT),
+            hardware_decode_info,
+        }
+    }
+
+    fn prepare(&mut self, frame: &FrameContext) {
+        // The fence of `frame.frame_index` has been waited on, so its video texture is no longer sampled
+        let (y_plane, cbcr_plane) = self.decoder.decode_next_frame();
+        self.video_frames[frame.frame_index]
+            .upload_frame(
+                &y_plane,
+                &cbcr_plane,
+                &self.fixed_vulkan_stuff.graphic_command_pool,
+                &self.fixed_vulkan_stuff.device.graphic_queue(),
+            )
+            .unwrap();
+
+        let name = self
+            .fixed_vulkan_stuff
+            .device
+            .physical_device_name()
+            .to_owned();
+        self.update_ui(&[
+            name,
+            format!("video frame: {}", self.decoder.frame_number),
+            self.hardware_decode_info.clone(),
+        ]);
+    }
+
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        unsafe {
+            self.fixed_vulkan_stuff.cmd_begin_renderpass(
+                frame_index,
+                image_index,
+                &Self::clear_value(),
+            );
+
+            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+
+            self.fixed_vulkan_stuff
+                .cmd_set_viewport_and_scissor(frame_index);
+
+            self.fixed_vulkan_stuff.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+
+            self.fixed_vulkan_stuff
+                .device
+                .cmd_draw(command_buffer, 4, 1, 0, 0);
+
+            self.ui_overlay.draw(command_buffer, frame_index);
+
+            self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
+        }
+    }
+
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        if change.format_changed {
+            self.fixed_vulkan_stuff.frame_garbage.retire(self.pipeline);
+            self.fixed_vulkan_stuff
+                .frame_garbage
+                .retire(self.pipeline_layout);
+            (self.pipeline_layout, self.pipeline) =
+                Self::create_pipeline(&self.fixed_vulkan_stuff, self.descriptor_set_layout);
+        }
+    }
+
+    fn vulkan_api_version() -> VulkanApiVersion {
+        // Needed for hardware H.264 decoding to be reported
+        VulkanApiVersion::V1_1
+    }
+
+    fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
+        // An immutable YCbCr sampler may consume one descriptor per plane
+        vec![vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32 * 3)
+            .build()]
+    }
+
+    fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build()]
+    }
+}
+
+impl VideoQuadApp {
+    fn create_pipeline(
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> (vk::PipelineLayout, vk::Pipeline) {
+        PipelineCreator {
+            device: fixed_vulkan_stuff.device.clone(),
+            extent: fixed_vulkan_stuff.surface.extent(),
+            render_pass: fixed_vulkan_stuff.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
+        }
+        .build()
+        .unwrap()
+    }
+}
+
+impl_drop_trait!(VideoQuadApp);
+
+/// Frames still come from `PatternDecoder`, feeding a real H.264 stream to a
+/// `DecodedPictureBuffer` also needs a bitstream parser for the session parameters
+fn hardware_decode_info(device: &Device) -> String {
+    if !device.supports_video_decode() {
+        return "H.264 decode: not available".to_string();
+    }
+    match VideoDecodeCapabilities::query(device, &H264DecodeProfile::main()) {
+        Ok(capabilities) => format!(
+            "H.264 decode: up to {}x{}, {} DPB slots",
+            capabilities.max_coded_extent.width,
+            capabilities.max_coded_extent.height,
+            capabilities.max_dpb_slots
+        ),
+        Err(e) => format!("H.264 decode: {e}"),
+    }
+}
+
+/// Stands in for a CPU video decoder, producing NV12 frames of scrolling color bars
+struct PatternDecoder {
+    width: u32,
+    height: u32,
+    frame_number: u64,
+}
+
+impl PatternDecoder {
+    const BARS: [[f32; 3]; 8] = [
+        [1., 1., 1.],
+        [1., 1., 0.],
+        [0., 1., 1.],
+        [0., 1., 0.],
+        [1., 0., 1.],
+        [1., 0., 0.],
+        [0., 0., 1.],
+        [0., 0., 0.],
+    ];
+
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            frame_number: 0,
+        }
+    }
+
+    /// BT.709 narrow range, matching the conversion of the YCbCr sampler
+    fn rgb_to_ycbcr(rgb: [f32; 3]) -> [u8; 3] {
+        let y = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+        let cb = (rgb[2] - y) / 1.8556;
+        let cr = (rgb[0] - y) / 1.5748;
+        [
+            (16. + 219. * y).round() as u8,
+            (128. + 224. * cb).round() as u8,
+            (128. + 224. * cr).round() as u8,
+        ]
+    }
+
+    fn color_at(&self, x: u32, y: u32) -> [u8; 3] {
+        let scroll = (self.frame_number * 2 % self.width as u64) as u32;
+        let bar = ((x + scroll) % self.width) as usize * Self::BARS.len() / self.width as usize;
+        let shade = 1. - 0.5 * y as f32 / self.height as f32;
+        Self::rgb_to_ycbcr(Self::BARS[bar].map(|c| c * shade))
+    }
+
+    /// Returns the Y plane and the interleaved CbCr plane of the next frame
+    fn decode_next_frame(&mut self) -> (Vec<u8>, Vec<u8>) {
+        let (width, height) = (self.width, self.height);
+        let y_plane = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.color_at(x, y)[0])
+            .collect();
+        let cbcr_plane = (0..height / 2)
+            .flat_map(|y| (0..width / 2).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let [_, cb, cr] = self.color_at(x * 2, y * 2);
+                [cb, cr]
+            })
+            .collect();
+        self.frame_number += 1;
+        (y_plane, cbcr_plane)
+    }
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        "examples/shaders/video_quad/shader.vert.spv"
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        "examples/shaders/video_quad/shader.frag.spv"
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    fn input_assembly_state_create_info(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
+
+fn main() {
+    let mut event_loop = RefCell::new(EventLoop::new());
+    let mut app = VideoQuadApp::new(&event_loop.borrow());
+    app.run(&mut event_loop);
+}
@@ -1,331 +1,231 @@
-use std::{cell::RefCell, rc::Rc};
-
-use ash::vk;
-use glam::{Mat4, Vec3};
-use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
+use std::f32::consts::PI;
 
 use vulkan_example_rs::{
-    app::{FixedVulkanStuff, FrameCounter, PipelineBuilder, UIOverlay, WindowApp},
-    camera::{Camera, MVPMatrix},
-    impl_drop_trait, impl_pipeline_builder_fns, impl_window_fns,
-    mesh::Vertex,
-    vulkan_wrappers::{Buffer, Device, Texture},
+    app::{ProceduralPattern, SamplerPanel, SimpleMeshRenderer, TextureGenerator},
+    prelude::*,
+    vulkan_wrappers::{DescriptorUpdateTemplate, SamplerCache, SamplerSettings},
 };
 
+const TEXTURE_PATH: &str = "examples/textures/viking_room/viking_room.png";
+
 struct VikingRoomApp {
     window: Window,
-    window_resized: bool,
 
     frame_counter: FrameCounter,
     ui_overlay: UIOverlay,
 
-    #[allow(dead_code)]
-    model_vertices: Vec<Vertex>,
-    model_indices: Vec<u32>,
-
     camera: Camera,
+    camera_controller: CameraController,
 
     fixed_vulkan_stuff: FixedVulkanStuff,
-    descriptor_set_layout: vk::DescriptorSetLayout,
-    descriptor_pool: vk::DescriptorPool,
-    descriptor_sets: [vk::DescriptorSet; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
-    pipeline_layout: vk::PipelineLayout,
-    pipeline: vk::Pipeline,
-    vertex_buffer: Buffer<Vertex>,
-    indice_buffer: Buffer<u32>,
-    uniform_buffers: [Buffer<MVPMatrix>; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
-    #[allow(dead_code)]
-    texture_image: Texture,
+    /// Owns the texture, whose sampler the panel changes
+    mesh_renderer: SimpleMeshRenderer,
+    /// Matrix the recorded scene was drawn with, it is recorded again when this changes
+    last_scene_matrix: Option<MVPMatrix>,
+
+    sampler_cache: SamplerCache,
+    sampler_panel: SamplerPanel,
+    /// Rewrites the texture descriptor when the sampler changes
+    texture_update_template: DescriptorUpdateTemplate,
 }
 
 impl WindowApp for VikingRoomApp {
     impl_window_fns!(VikingRoomApp);
 
-    fn draw_frame(&mut self) {
-        let frame_index = self.frame_counter().double_buffer_frame;
-        let image_index = {
-            let ret = self
-                .fixed_vulkan_stuff
-                .frame_get_image_index_to_draw(frame_index, &self.window)
-                .unwrap();
-            if ret.1 {
-                return;
-            }
-            ret.0
-        };
-
-        self.uniform_buffers[frame_index]
-            .load_data_when_mapped(&[self.camera.mvp_matrix(Mat4::IDENTITY)], 0);
+    fn prepare(&mut self, _frame: &FrameContext) {
+        let scene_matrix = self.camera.mvp_matrix(Mat4::IDENTITY);
+        if self.last_scene_matrix != Some(scene_matrix) {
+            self.mark_scene_dirty();
+            // Recorded scenes bind the uniform buffer of whichever frame recorded them
+            self.mesh_renderer.update_mvp_all_frames(&scene_matrix);
+            self.last_scene_matrix = Some(scene_matrix);
+        }
 
         let name = self
             .fixed_vulkan_stuff
             .device
             .physical_device_name()
             .to_owned();
-        self.update_ui(&[name]);
-
-        self.record_render_commands(frame_index, image_index, self.model_indices.len() as u32);
+        let mut sampler_panel = self.sampler_panel;
+        let mut sampler_changed = false;
+        self.update_ui_with(&[name], |ui| sampler_changed = sampler_panel.draw(ui));
+        self.sampler_panel = sampler_panel;
+        if sampler_changed {
+            self.apply_sampler_settings();
+            self.mark_scene_dirty();
+        }
+    }
 
-        self.window_resized = self
-            .fixed_vulkan_stuff
-            .frame_queue_submit_and_present(
-                frame_index,
+    /// The scene is recorded only for swapchain images without a recorded scene yet,
+    /// the UI overlay is recorded every frame
+    fn record(&mut self, frame: &FrameContext) {
+        let FrameContext {
+            frame_index,
+            image_index,
+            command_buffer,
+        } = *frame;
+        let render_pass = self.fixed_vulkan_stuff.render_pass;
+        let framebuffer = self.fixed_vulkan_stuff.swapchain_framebuffers[image_index];
+        let extent = self.fixed_vulkan_stuff.surface.extent();
+        let prerecorded_scene = self.fixed_vulkan_stuff.prerecorded_scene.as_mut().unwrap();
+
+        prerecorded_scene
+            .record_scene_if_dirty(
                 image_index,
-                &self.window,
-                self.window_resized,
+                render_pass,
+                framebuffer,
+                extent,
+                |command_buffer| self.mesh_renderer.cmd_draw(command_buffer, frame_index),
+            )
+            .unwrap();
+        prerecorded_scene
+            .record_overlay(
+                frame_index,
+                render_pass,
+                framebuffer,
+                extent,
+                |command_buffer| self.ui_overlay.draw(command_buffer, frame_index),
             )
             .unwrap();
 
-        self.frame_counter.update();
+        self.fixed_vulkan_stuff
+            .cmd_begin_renderpass_for_secondaries(frame_index, image_index, &Self::clear_value());
+        self.fixed_vulkan_stuff
+            .prerecorded_scene
+            .as_ref()
+            .unwrap()
+            .cmd_execute(command_buffer, frame_index, image_index);
+
+        self.fixed_vulkan_stuff.cmd_end_renderpass(frame_index);
     }
 
     fn new(event_loop: &EventLoop<()>) -> Self {
         let window = Self::build_window(event_loop);
-        let fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
-        let descriptor_set_layout =
-            Self::create_descriptor_set_layout(&fixed_vulkan_stuff.device).unwrap();
-        let descriptor_pool = Self::create_descriptor_pool(&fixed_vulkan_stuff.device).unwrap();
-        let descriptor_sets = Self::create_descriptor_sets(
-            descriptor_pool,
-            descriptor_set_layout,
-            &fixed_vulkan_stuff.device,
-        )
-        .unwrap();
-
-        let pipeline_creator = PipelineCreator {
-            device: fixed_vulkan_stuff.device.clone(),
-            extent: fixed_vulkan_stuff.surface.extent(),
-            render_pass: fixed_vulkan_stuff.render_pass,
-            set_layouts: &[descriptor_set_layout],
-            vertex_bindings: &[Vertex::binding_description()],
-            vertex_attributes: &Vertex::attr_descriptions(),
-            pipeline_cache: fixed_vulkan_stuff.pipeline_cache,
-        };
-
-        let (pipeline_layout, pipeline) = pipeline_creator.build().unwrap();
-
+        let mut fixed_vulkan_stuff = Self::create_fixed_vulkan_stuff(&window).unwrap();
+        fixed_vulkan_stuff.enable_prerecorded_scene().unwrap();
         let (model_vertices, model_indices) =
             vulkan_example_rs::mesh::load_obj_model("examples/meshes/viking_room/viking_room.obj")
                 .unwrap();
-        let vertex_buffer = fixed_vulkan_stuff
-            .device_local_vertex_buffer(&model_vertices)
-            .unwrap();
-        let indice_buffer = fixed_vulkan_stuff
-            .device_local_indice_buffer(&model_indices)
-            .unwrap();
-
-        let uniform_buffers: [_; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT] =
-            array_init::array_init(|_| {
-                let mut buffer = Buffer::<MVPMatrix>::new(
-                    1,
-                    vk::BufferUsageFlags::UNIFORM_BUFFER,
-                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                    fixed_vulkan_stuff.device.clone(),
-                )
-                .unwrap();
-                buffer.map_memory_all().unwrap();
-                buffer
-            });
-
         let mut texture_image = Texture::from_rgba8_picture(
-            "examples/textures/viking_room/viking_room.png",
+            TEXTURE_PATH,
             fixed_vulkan_stuff.device.clone(),
             &fixed_vulkan_stuff.graphic_command_pool,
             &fixed_vulkan_stuff.device.graphic_queue(),
         )
+        .and_then(|mut texture| {
+            texture.spawn_image_view()?;
+            Ok(texture)
+        })
+        .unwrap_or_else(|e| {
+            println!("Fail to load {TEXTURE_PATH}, using a generated texture: {e}");
+            TextureGenerator::new(
+                fixed_vulkan_stuff.pipeline_cache,
+                fixed_vulkan_stuff.device.clone(),
+            )
+            .and_then(|generator| {
+                generator.generate(
+                    &ProceduralPattern::MISSING,
+                    512,
+                    512,
+                    &fixed_vulkan_stuff.graphic_command_pool,
+                    &fixed_vulkan_stuff.device.graphic_queue(),
+                )
+            })
+            .unwrap()
+        });
+
+        let sampler_cache = SamplerCache::new(fixed_vulkan_stuff.device.clone());
+        let sampler_panel = SamplerPanel::new(
+            SamplerSettings::default().with_max_anisotropy(sampler_cache.max_anisotropy_limit()),
+            sampler_cache.max_anisotropy_limit(),
+        );
+        texture_image.set_sampler(sampler_cache.get(&sampler_panel.settings).unwrap());
+
+        let mesh_renderer = SimpleMeshRenderer::builder(
+            "examples/shaders/viking_room/shader.vert.spv",
+            "examples/shaders/viking_room/shader.frag.spv",
+        )
+        .texture(texture_image)
+        .build(&fixed_vulkan_stuff, &model_vertices, &model_indices)
+        .unwrap();
+        let bindings = mesh_renderer.bindings();
+        let texture_update_template = DescriptorUpdateTemplate::new(
+            &DescriptorSetBuilder::new(&bindings, fixed_vulkan_stuff.device.clone())
+                .combined_image_sampler(
+                    SimpleMeshRenderer::TEXTURE_BINDING,
+                    mesh_renderer.texture().unwrap(),
+                ),
+            mesh_renderer.descriptor_set_layout(),
+        )
         .unwrap();
-        texture_image.spawn_image_view().unwrap();
-        texture_image.spawn_sampler(vk::Filter::LINEAR).unwrap();
-
-        {
-            for i in 0..FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT {
-                let uniform_descritptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_sets[i])
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&[uniform_buffers[i].descriptor_default()])
-                    .build();
-
-                let image_descritptor_write = vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_sets[i])
-                    .dst_binding(1)
-                    .dst_array_element(0)
-                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[texture_image.descriptor_default()])
-                    .build();
-
-                unsafe {
-                    fixed_vulkan_stuff.device.update_descriptor_sets(
-                        &[uniform_descritptor_write, image_descritptor_write],
-                        &[],
-                    )
-                }
-            }
-        }
 
         let ui_overlay = UIOverlay::from_fixed_vulkan_stuff(&fixed_vulkan_stuff, 1.0).unwrap();
 
         VikingRoomApp {
             window,
-            window_resized: false,
 
             frame_counter: FrameCounter::default(),
 
-            model_vertices,
-            model_indices,
-
             camera: Camera::builder()
-                .translation(Vec3::new(0., 0., -3.))
+                .orbit(Vec3::ZERO, 3., PI, 0.)
                 .move_speed(100.)
                 .rotate_speed(40.)
                 .build(),
+            camera_controller: CameraController::new(),
             fixed_vulkan_stuff,
-            descriptor_set_layout,
-            descriptor_pool,
-            descriptor_sets,
-            pipeline_layout,
-            pipeline,
-            vertex_buffer,
-            indice_buffer,
-            uniform_buffers,
-            texture_image,
+            mesh_renderer,
+            last_scene_matrix: None,
+            sampler_cache,
+            sampler_panel,
+            texture_update_template,
             ui_overlay,
         }
     }
 
+    /// Scenes are recorded again after every refit, then with the rebuilt pipeline
+    fn on_surface_changed(&mut self, change: SurfaceChange) {
+        self.mesh_renderer
+            .on_surface_changed(&self.fixed_vulkan_stuff, change)
+            .unwrap();
+    }
+
     fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize> {
-        vec![
-            vk::DescriptorType::UNIFORM_BUFFER,
-            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-        ]
-        .into_iter()
-        .map(|ty| {
-            vk::DescriptorPoolSize::builder()
-                .ty(ty)
-                .descriptor_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
-                .build()
-        })
-        .collect()
+        vec![]
     }
 
     fn descriptor_set_layout_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
-        let ubo_layout_binding = vk::DescriptorSetLayoutBinding::builder()
-            .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .descriptor_count(1)
-            .build();
-        let sampler_layout_binding = vk::DescriptorSetLayoutBinding::builder()
-            .binding(1)
-            .descriptor_count(1)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-            .build();
-        vec![ubo_layout_binding, sampler_layout_binding]
+        vec![]
     }
 }
 
 impl VikingRoomApp {
-    fn record_render_commands(&mut self, frame_index: usize, image_index: usize, indice_num: u32) {
-        let command_buffer = self.fixed_vulkan_stuff.graphic_command_buffers[frame_index];
-        unsafe {
-            self.fixed_vulkan_stuff
-                .device
-                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())
-                .expect("Fail to reset command buffer");
-
-            self.fixed_vulkan_stuff
-                .device
-                .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())
-                .expect("Fail to begin command buffer");
-
-            self.fixed_vulkan_stuff.cmd_begin_renderpass(
-                frame_index,
-                image_index,
-                &Self::clear_value(),
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
-
-            self.fixed_vulkan_stuff.device.cmd_bind_vertex_buffers(
-                command_buffer,
-                0,
-                &[self.vertex_buffer.buffer()],
-                &[0],
-            );
-            self.fixed_vulkan_stuff.device.cmd_bind_index_buffer(
-                command_buffer,
-                self.indice_buffer.buffer(),
-                0,
-                vk::IndexType::UINT32,
-            );
-
-            self.fixed_vulkan_stuff
-                .cmd_set_viewport_and_scissor(frame_index);
-
-            self.fixed_vulkan_stuff.device.cmd_bind_descriptor_sets(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                0,
-                &[self.descriptor_sets[frame_index]],
-                &[],
-            );
-
-            self.fixed_vulkan_stuff
-                .device
-                .cmd_draw_indexed(command_buffer, indice_num, 1, 0, 0, 0);
-
-            self.ui_overlay.draw(command_buffer, frame_index);
-
-            self.fixed_vulkan_stuff
-                .device
-                .cmd_end_render_pass(command_buffer);
-            self.fixed_vulkan_stuff
-                .device
-                .end_command_buffer(command_buffer)
-                .unwrap();
-        }
-    }
-}
-
-impl_drop_trait!(VikingRoomApp);
-
-struct PipelineCreator<'a> {
-    device: Rc<Device>,
-    extent: vk::Extent2D,
-    render_pass: vk::RenderPass,
-    set_layouts: &'a [vk::DescriptorSetLayout],
-    vertex_bindings: &'a [vk::VertexInputBindingDescription],
-    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
-    pipeline_cache: vk::PipelineCache,
-}
-
-impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
-    impl_pipeline_builder_fns!();
-
-    fn vertex_spv_path(&self) -> &'a str {
-        "examples/shaders/viking_room/shader.vert.spv"
-    }
-
-    fn frag_spv_path(&self) -> &'a str {
-        "examples/shaders/viking_room/shader.frag.spv"
+    fn apply_sampler_settings(&mut self) {
+        let device = self.fixed_vulkan_stuff.device.clone();
+        // The descriptor sets of both frames in flight are rewritten
+        unsafe { device.device_wait_idle().unwrap() };
+        let sampler = self
+            .sampler_cache
+            .get(&self.sampler_panel.settings)
+            .unwrap();
+        self.mesh_renderer
+            .texture_mut()
+            .unwrap()
+            .set_sampler(sampler);
+        let bindings = self.mesh_renderer.bindings();
+        let builder = DescriptorSetBuilder::new(&bindings, device.clone()).combined_image_sampler(
+            SimpleMeshRenderer::TEXTURE_BINDING,
+            self.mesh_renderer.texture().unwrap(),
+        );
+        self.mesh_renderer.descriptor_sets().iter().for_each(|set| {
+            builder
+                .update_with_template(&self.texture_update_template, *set)
+                .unwrap()
+        });
     }
 
-    fn pipeline_layout(&self) -> vk::PipelineLayout {
-        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
-            .set_layouts(self.set_layouts)
-            .push_constant_ranges(&[])
-            .build();
-        unsafe {
-            self.device
-                .create_pipeline_layout(&pipeline_layout_info, None)
-                .unwrap()
+    fn mark_scene_dirty(&mut self) {
+        if let Some(prerecorded_scene) = &mut self.fixed_vulkan_stuff.prerecorded_scene {
+            prerecorded_scene.mark_dirty().unwrap();
         }
     }
 }
@@ -1,11 +1,112 @@
 mod fixed_stuff;
-pub use fixed_stuff::FixedVulkanStuff;
+pub use fixed_stuff::{ClearValue, FixedVulkanStuff};
 
+mod per_frame;
+pub use per_frame::PerFrame;
+
+#[cfg(feature = "ui")]
 mod window_app;
-pub use window_app::{ClearValue, FrameCounter, WindowApp};
+#[cfg(feature = "ui")]
+pub use window_app::{FrameContext, FrameCounter, WindowApp};
 
 mod pipeline;
-pub use pipeline::PipelineBuilder;
+pub use pipeline::{ComputePipelineBuilder, PipelineBuilder};
+
+mod debug_view;
+pub use debug_view::{DebugPush, DebugView};
 
+#[cfg(feature = "ui")]
 mod ui_overlay;
-pub use ui_overlay::{UIOverlay, UIPushConstBlock};
+#[cfg(feature = "ui")]
+pub use ui_overlay::{ui_layout_path, UIOverlay, UIPushConstBlock};
+
+#[cfg(feature = "ui")]
+mod log_console;
+#[cfg(feature = "ui")]
+pub use log_console::LogConsole;
+
+#[cfg(feature = "docking")]
+mod ui_viewports;
+#[cfg(feature = "docking")]
+pub use ui_viewports::UiViewports;
+
+mod picture_in_picture;
+pub use picture_in_picture::{InsetPushConstBlock, PictureInPicture};
+
+mod fullscreen_pass;
+pub use fullscreen_pass::FullscreenPass;
+
+mod hdr_target;
+pub use hdr_target::{HdrTarget, TonemapOperator, TonemapSettings};
+
+mod depth_inspector;
+pub use depth_inspector::{linear_distance, DepthDisplay, DepthInspector, DepthStats, DepthView};
+
+mod texture_generator;
+pub use texture_generator::{ProceduralPattern, TextureGenerator};
+
+mod ibl;
+pub use ibl::IblResources;
+
+#[cfg(feature = "ui")]
+mod sampler_panel;
+#[cfg(feature = "ui")]
+pub use sampler_panel::SamplerPanel;
+
+#[cfg(feature = "ui")]
+mod memory_panel;
+#[cfg(feature = "ui")]
+pub use memory_panel::MemoryPanel;
+
+#[cfg(feature = "ui")]
+mod texture_inspector;
+#[cfg(feature = "ui")]
+pub use texture_inspector::{TextureInspector, TextureSelection};
+
+mod monitor;
+pub use monitor::{toggle_fullscreen, MonitorInfo};
+
+mod frame_latency;
+pub use frame_latency::FrameLatency;
+
+mod scene_cache;
+pub use scene_cache::SceneCache;
+
+mod prerecorded_scene;
+pub use prerecorded_scene::PrerecordedScene;
+
+mod simple_mesh_renderer;
+pub use simple_mesh_renderer::{SimpleMeshRenderer, SimpleMeshRendererBuilder};
+
+mod depth_only;
+pub use depth_only::{DepthOnlyPipelineCreator, DepthOnlyPush};
+
+mod decal;
+pub use decal::{Decal, DecalPipelineCreator, DecalPush, DepthBias};
+
+mod vertex_format_pipelines;
+pub use vertex_format_pipelines::{VertexFormat, VertexFormatPipelines};
+
+mod frame_garbage;
+pub use frame_garbage::{FrameGarbage, Garbage};
+
+mod secondary_commands;
+pub use secondary_commands::{SecondaryCommandPools, SecondaryInheritance};
+
+mod gpu_timer;
+pub use gpu_timer::GpuTimer;
+
+mod headless;
+pub use headless::HeadlessVulkanStuff;
+
+mod object_buffer;
+pub use object_buffer::{ObjectBuffer, ObjectData};
+
+mod draw_list;
+pub use draw_list::{DrawBatch, DrawBatches, DrawItem, DrawList, DrawStats};
+
+mod reference_overlay;
+pub use reference_overlay::{ReferenceOverlay, ReferenceSettings};
+
+mod debug_lines;
+pub use debug_lines::{DebugLines, DebugVertex};
@@ -0,0 +1,227 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::{Mat4, Vec3};
+
+use super::{FixedVulkanStuff, PerFrame, PipelineBuilder};
+use crate::{
+    error::RenderResult,
+    impl_embedded_spv_fns, impl_pipeline_builder_fns, impl_vertex_layout,
+    mesh::VertexLayout,
+    vulkan_wrappers::{Buffer, Device, SurfaceChange},
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DebugVertex {
+    pub pos: Vec3,
+    pub color: Vec3,
+}
+
+impl_vertex_layout!(DebugVertex {
+    pos: vk::Format::R32G32B32_SFLOAT,
+    color: vk::Format::R32G32B32_SFLOAT,
+});
+
+/// Immediate mode world space lines for debugging, queued during a frame and dropped once
+/// drawn by [`Self::cmd_draw`]. Lines past the capacity are dropped as well.
+pub struct DebugLines {
+    /// Line list queued for the next draw
+    vertices: Vec<DebugVertex>,
+    /// Host visible vertex buffer of every frame in flight
+    buffers: PerFrame<Buffer<DebugVertex>>,
+    device: Rc<Device>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl DebugLines {
+    /// Draws into subpass 0 of `render_pass`, depth tested against what was drawn before
+    pub fn new(
+        max_lines: usize,
+        frame_count: usize,
+        render_pass: vk::RenderPass,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let buffers = PerFrame::try_new(frame_count, |_| {
+            let mut buffer = Buffer::new(
+                max_lines.max(1) * 2,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                device.host_write_memory_properties(),
+                device.clone(),
+            )?;
+            buffer.map_memory_all()?;
+            RenderResult::Ok(buffer)
+        })?;
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(render_pass, pipeline_cache, device.clone())?;
+        Ok(Self {
+            vertices: vec![],
+            buffers,
+            device,
+            pipeline_layout,
+            pipeline,
+        })
+    }
+
+    /// Draws into the swapchain render pass
+    pub fn from_fixed_vulkan_stuff(s: &FixedVulkanStuff, max_lines: usize) -> RenderResult<Self> {
+        Self::new(
+            max_lines,
+            s.frames_in_flight(),
+            s.render_pass,
+            s.pipeline_cache,
+            s.device.clone(),
+        )
+    }
+
+    /// Rebuilds the pipeline against the new render pass of `s` when its format changed. The
+    /// device must be idle, as after [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.format_changed {
+            let (pipeline_layout, pipeline) =
+                Self::create_pipeline(s.render_pass, s.pipeline_cache, self.device.clone())?;
+            unsafe {
+                self.device.destroy_pipeline(self.pipeline, None);
+                self.device
+                    .destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
+    fn create_pipeline(
+        render_pass: vk::RenderPass,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        PipelineCreator {
+            device,
+            extent: vk::Extent2D::default(),
+            render_pass,
+            vertex_bindings: &[DebugVertex::binding_description(0)],
+            vertex_attributes: &DebugVertex::attribute_descriptions(0),
+            pipeline_cache,
+        }
+        .build()
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Vec3) {
+        self.vertices.extend([
+            DebugVertex { pos: from, color },
+            DebugVertex { pos: to, color },
+        ]);
+    }
+
+    /// Three axis aligned segments of `size` centered on `center`
+    pub fn cross(&mut self, center: Vec3, size: f32, color: Vec3) {
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let half = axis * size * 0.5;
+            self.line(center - half, center + half, color);
+        }
+    }
+
+    /// Queued lines, including those past the capacity
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Draws the queued lines into the frame `frame_index` and clears them
+    pub fn cmd_draw(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        view_projection: Mat4,
+    ) -> RenderResult<()> {
+        let buffer = &mut self.buffers[frame_index];
+        let vertex_count = self.vertices.len().min(buffer.element_num());
+        if vertex_count > 0 {
+            buffer.load_data_when_mapped(&self.vertices[..vertex_count], 0);
+            buffer.flush()?;
+            unsafe {
+                self.device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline,
+                );
+                self.device
+                    .cmd_bind_vertex_buffers(command_buffer, 0, &[buffer.buffer()], &[0]);
+                self.device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytemuck::cast_slice(&view_projection.to_cols_array()),
+                );
+                self.device
+                    .cmd_draw(command_buffer, vertex_count as u32, 1, 0, 0);
+            }
+        }
+        self.vertices.clear();
+        Ok(())
+    }
+}
+
+impl Drop for DebugLines {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "src/app/shaders/debug_lines.vert.spv",
+        "src/app/shaders/vertex_color.frag.spv"
+    );
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(std::mem::size_of::<Mat4>() as u32)
+            .build()]
+    }
+
+    fn input_assembly_state_create_info(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::LINE_LIST)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    /// Hidden behind the scene, without hiding each other
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
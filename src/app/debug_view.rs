@@ -0,0 +1,138 @@
+use ash::vk;
+
+use crate::vulkan_wrappers::Device;
+
+/// Alternative outputs of fragment shaders, switched at runtime through [`DebugPush`]
+/// without any extra pipeline but the one of [`super::PipelineBuilder::build_overdraw`]
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Shaded = 0,
+    /// World space normals remapped to [0, 1]
+    Normals = 1,
+    /// Texture coordinates in red and green
+    Uvs = 2,
+    /// Constant color added up by the overdraw pipeline, brighter where more
+    /// fragments land on the same pixel
+    Overdraw = 3,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 4] = [Self::Shaded, Self::Normals, Self::Uvs, Self::Overdraw];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Shaded => "Shaded",
+            Self::Normals => "Normals",
+            Self::Uvs => "UVs",
+            Self::Overdraw => "Overdraw heatmap",
+        }
+    }
+
+    /// Returns `true` when the user picked another view
+    #[cfg(feature = "ui")]
+    pub fn draw(&mut self, ui: &imgui::Ui) -> bool {
+        let names = Self::ALL.map(Self::name);
+        let mut index = *self as usize;
+        if ui.combo_simple_string("Debug view", &mut index, &names) && Self::ALL[index] != *self {
+            *self = Self::ALL[index];
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Push constants shared by every pipeline built with the default
+/// [`super::PipelineBuilder::pipeline_layout`], in the last 16 of the 128 bytes every
+/// implementation supports. Shaders declare it after their own members, which must
+/// fit in the first 112 bytes:
+///
+/// ```glsl
+/// layout(push_constant) uniform PushConsts {
+///     mat4 node;
+///     layout(offset = 112) uint debugView;
+///     float debugParams[3];
+/// } pushConsts;
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DebugPush {
+    /// A [`DebugView`], or a larger index for views of a single example
+    pub view: u32,
+    /// Free for views which need tuning, e.g. a scale
+    pub params: [f32; 3],
+}
+
+unsafe impl bytemuck::Pod for DebugPush {}
+unsafe impl bytemuck::Zeroable for DebugPush {}
+
+impl DebugPush {
+    pub const OFFSET: u32 = 112;
+    pub const SIZE: u32 = std::mem::size_of::<Self>() as u32;
+    pub const STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::from_raw(
+        vk::ShaderStageFlags::VERTEX.as_raw() | vk::ShaderStageFlags::FRAGMENT.as_raw(),
+    );
+
+    pub fn new(view: DebugView) -> Self {
+        Self {
+            view: view as u32,
+            params: [0.; 3],
+        }
+    }
+
+    /// Extends the vertex and fragment ranges of `ranges` over the block, or adds a
+    /// range for the stages none of them has. Ranges must end before [`Self::OFFSET`].
+    pub fn merge_push_constant_ranges(
+        mut ranges: Vec<vk::PushConstantRange>,
+    ) -> Vec<vk::PushConstantRange> {
+        let mut uncovered = Self::STAGES;
+        for range in &mut ranges {
+            let shared = range.stage_flags & Self::STAGES;
+            if shared.is_empty() {
+                continue;
+            }
+            assert!(
+                range.offset + range.size <= Self::OFFSET,
+                "Push constants of {:?} overlap the debug block",
+                range.stage_flags
+            );
+            range.size = Self::OFFSET + Self::SIZE - range.offset;
+            uncovered &= !shared;
+        }
+        if !uncovered.is_empty() {
+            ranges.push(
+                vk::PushConstantRange::builder()
+                    .stage_flags(uncovered)
+                    .offset(Self::OFFSET)
+                    .size(Self::SIZE)
+                    .build(),
+            );
+        }
+        ranges
+    }
+
+    pub fn cmd_push(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        pipeline_layout: vk::PipelineLayout,
+    ) {
+        unsafe {
+            device.cmd_push_constants(
+                command_buffer,
+                pipeline_layout,
+                Self::STAGES,
+                Self::OFFSET,
+                bytemuck::bytes_of(self),
+            );
+        }
+    }
+}
+
+impl From<DebugView> for DebugPush {
+    fn from(view: DebugView) -> Self {
+        Self::new(view)
+    }
+}
@@ -0,0 +1,278 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+use super::PipelineBuilder;
+use crate::{
+    error::RenderResult,
+    include_spv,
+    vulkan_wrappers::{Device, ShaderCreate, ShaderModule},
+};
+
+const VERTEX_SPV_PATH: &str = "src/app/shaders/decal.vert.spv";
+const FRAG_SPV_PATH: &str = "src/app/shaders/decal.frag.spv";
+
+/// Textured quad stamped onto a surface, drawn after it by a [`DecalPipelineCreator`]
+/// pipeline and kept in front of it by a [`DepthBias`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decal {
+    /// Center, on the surface
+    pub position: Vec3,
+    /// Of the surface, the quad faces it
+    pub normal: Vec3,
+    pub size: Vec2,
+    /// Radians around the normal
+    pub rotation: f32,
+    /// Multiplies the texture
+    pub color: Vec4,
+}
+
+impl Decal {
+    pub fn new(position: Vec3, normal: Vec3, size: f32) -> Self {
+        Self {
+            position,
+            normal,
+            size: Vec2::splat(size),
+            rotation: 0.,
+            color: Vec4::ONE,
+        }
+    }
+
+    /// World space from decal space, whose unit quad lies in the XY plane facing +Z
+    pub fn model_matrix(&self) -> Mat4 {
+        let orientation = Quat::from_rotation_arc(Vec3::Z, self.normal.normalize())
+            * Quat::from_rotation_z(self.rotation);
+        Mat4::from_scale_rotation_translation(self.size.extend(1.), orientation, self.position)
+    }
+
+    /// Pushes the decal and draws its quad, `layout` must come from a [`DecalPipelineCreator`]
+    pub fn cmd_draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        view_projection: Mat4,
+    ) {
+        DecalPush {
+            mvp: view_projection * self.model_matrix(),
+            color: self.color,
+        }
+        .cmd_push(device, command_buffer, layout);
+        unsafe { device.cmd_draw(command_buffer, 6, 1, 0, 0) };
+    }
+}
+
+/// Push constant block of the decal shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DecalPush {
+    pub mvp: Mat4,
+    pub color: Vec4,
+}
+
+unsafe impl bytemuck::Pod for DecalPush {}
+unsafe impl bytemuck::Zeroable for DecalPush {}
+
+impl DecalPush {
+    pub fn range() -> vk::PushConstantRange {
+        vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<Self>() as u32)
+            .build()
+    }
+
+    pub fn cmd_push(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+    ) {
+        unsafe {
+            device.cmd_push_constants(
+                command_buffer,
+                layout,
+                Self::range().stage_flags,
+                0,
+                bytemuck::bytes_of(self),
+            )
+        };
+    }
+}
+
+/// Polygon offset added to the depth of the fragments of a pipeline, negative factors
+/// pull them towards the camera, against z-fighting with coplanar surfaces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthBias {
+    /// In units of the smallest depth difference the depth format resolves
+    pub constant_factor: f32,
+    /// Scales the depth slope of the polygon, offsetting surfaces seen at grazing
+    /// angles further
+    pub slope_factor: f32,
+}
+
+impl DepthBias {
+    /// Enough for decals on surfaces seen from most angles with a 32 bit depth buffer
+    pub const DECAL: Self = Self {
+        constant_factor: -1.,
+        slope_factor: -1.5,
+    };
+
+    /// Sets the bias of pipelines with a dynamic `DEPTH_BIAS` state, such as decal ones
+    pub fn cmd_set(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_set_depth_bias(command_buffer, self.constant_factor, 0., self.slope_factor)
+        };
+    }
+}
+
+impl Default for DepthBias {
+    fn default() -> Self {
+        Self::DECAL
+    }
+}
+
+/// [`PipelineBuilder`] preset for [`Decal`]s, alpha blended over what the pass drew before
+/// them without writing depth.
+///
+/// The depth bias is dynamic, [`DepthBias::cmd_set`] must be recorded before drawing.
+/// Set 0 binding 0 is the decal texture, laid out as [`Self::bindings`].
+pub struct DecalPipelineCreator<'a> {
+    pub device: Rc<Device>,
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    /// Builds for dynamic rendering to these color and depth formats instead
+    pub dynamic_rendering_formats: Option<(Vec<vk::Format>, vk::Format)>,
+    pub set_layouts: &'a [vk::DescriptorSetLayout],
+    pub pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> DecalPipelineCreator<'a> {
+    /// Set 0
+    pub fn bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build()]
+    }
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for DecalPipelineCreator<'a> {
+    fn device(&self) -> Rc<Device> {
+        self.device.clone()
+    }
+
+    fn vertex_spv_path(&self) -> &'a str {
+        VERTEX_SPV_PATH
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        FRAG_SPV_PATH
+    }
+
+    fn extent(&self) -> vk::Extent2D {
+        vk::Extent2D::default()
+    }
+
+    fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    fn subpass(&self) -> u32 {
+        self.subpass
+    }
+
+    fn pipeline_cache(&self) -> vk::PipelineCache {
+        self.pipeline_cache
+    }
+
+    fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        self.dynamic_rendering_formats.clone()
+    }
+
+    /// The quad is generated from the vertex index
+    fn vertex_binding_descriptions(&self) -> &'a [vk::VertexInputBindingDescription] {
+        &[]
+    }
+
+    fn vertex_attribute_descriptions(&self) -> &'a [vk::VertexInputAttributeDescription] {
+        &[]
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![DecalPush::range()]
+    }
+
+    fn shader_stage_create_infos(
+        &self,
+    ) -> RenderResult<(Vec<vk::PipelineShaderStageCreateInfo>, Vec<ShaderModule>)> {
+        Ok([
+            ShaderCreate::from_bytes(
+                include_spv!("src/app/shaders/decal.vert.spv"),
+                vk::ShaderStageFlags::VERTEX,
+                self.device(),
+            )?,
+            ShaderCreate::from_bytes(
+                include_spv!("src/app/shaders/decal.frag.spv"),
+                vk::ShaderStageFlags::FRAGMENT,
+                self.device(),
+            )?,
+        ]
+        .into_iter()
+        .map(|shader_create| (shader_create.stage_create_info, shader_create.module))
+        .unzip())
+    }
+
+    fn dynamic_state_create_info(&self) -> vk::PipelineDynamicStateCreateInfo {
+        vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&[
+                vk::DynamicState::VIEWPORT,
+                vk::DynamicState::SCISSOR,
+                vk::DynamicState::DEPTH_BIAS,
+            ])
+            .build()
+    }
+
+    // Either side of the quad may face the camera once the projection flips the winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(true)
+            .build()
+    }
+
+    fn color_blend_attach_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+
+    /// Tested against the surface, but not written, so that overlapping decals all blend
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
@@ -0,0 +1,551 @@
+use std::rc::Rc;
+
+use ash::vk;
+
+use super::{ComputePipelineBuilder, FixedVulkanStuff, PerFrame, PipelineBuilder};
+use crate::{
+    error::RenderResult,
+    impl_embedded_spv_fns, impl_pipeline_builder_fns, include_spv,
+    vulkan_wrappers::{Buffer, Device, SurfaceChange},
+};
+
+const FRAMES: usize = FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT;
+
+/// How [`DepthInspector`] maps depth values to gray levels, from bright at
+/// [`DepthView::range`] start to dark at its end
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DepthDisplay {
+    /// Distances to the camera, so that far geometry is not crushed against the far plane
+    #[default]
+    Linear,
+    /// Depth values as stored, which shows where the precision of the buffer goes
+    Raw,
+}
+
+/// Depth values of the geometry of a frame, texels left at the cleared depth of 1
+/// are not counted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthStats {
+    pub min_depth: f32,
+    pub max_depth: f32,
+    pub covered_texels: u32,
+}
+
+impl DepthStats {
+    /// Distances to the camera of the nearest and farthest geometry
+    pub fn distance_range(&self, z_limits: [f32; 2]) -> [f32; 2] {
+        [
+            linear_distance(self.min_depth, z_limits),
+            linear_distance(self.max_depth, z_limits),
+        ]
+    }
+}
+
+/// Inverse of the [0, 1] depth of [`glam::Mat4::perspective_rh`] with `z_limits` near and far
+pub fn linear_distance(depth: f32, z_limits: [f32; 2]) -> f32 {
+    let [near, far] = z_limits;
+    near * far / (far - depth * (far - near))
+}
+
+/// Settings and measurements of a [`DepthInspector`], copied out to be edited in a UI
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthView {
+    pub display: DepthDisplay,
+    /// Fits the displayed range to the geometry measured in an earlier frame
+    pub auto_range: bool,
+    /// Displayed range when not [`Self::auto_range`], in the unit of [`Self::display`]
+    pub manual_range: [f32; 2],
+    /// Near and far planes of the projection which wrote the depth
+    pub z_limits: [f32; 2],
+    /// Result of the last reduction read back, `None` before the first one or when
+    /// nothing was drawn
+    pub stats: Option<DepthStats>,
+}
+
+impl DepthView {
+    pub fn new(z_limits: [f32; 2]) -> Self {
+        Self {
+            display: DepthDisplay::default(),
+            auto_range: true,
+            manual_range: z_limits,
+            z_limits,
+            stats: None,
+        }
+    }
+
+    /// Range shown by [`DepthInspector::cmd_draw`], in distances for
+    /// [`DepthDisplay::Linear`] and in depth values for [`DepthDisplay::Raw`]
+    pub fn range(&self) -> [f32; 2] {
+        match (self.auto_range, self.stats, self.display) {
+            (true, Some(stats), DepthDisplay::Linear) => stats.distance_range(self.z_limits),
+            (true, Some(stats), DepthDisplay::Raw) => [stats.min_depth, stats.max_depth],
+            (true, None, DepthDisplay::Linear) => self.z_limits,
+            (true, None, DepthDisplay::Raw) => [0., 1.],
+            (false, ..) => self.manual_range,
+        }
+    }
+
+    /// Display settings and the measured range, returns `true` when a setting was changed
+    #[cfg(feature = "ui")]
+    pub fn draw(&mut self, ui: &imgui::Ui) -> bool {
+        let mut changed = false;
+        if ui.collapsing_header("Depth inspector", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            let mut linear = self.display == DepthDisplay::Linear;
+            if ui.checkbox("Linear distances", &mut linear) {
+                self.display = match linear {
+                    true => DepthDisplay::Linear,
+                    false => DepthDisplay::Raw,
+                };
+                // Keep showing the same geometry in the new unit
+                self.manual_range = self.manual_range.map(|value| match linear {
+                    true => linear_distance(value, self.z_limits),
+                    false => depth_of_distance(value, self.z_limits),
+                });
+                changed = true;
+            }
+            changed |= ui.checkbox("Fit range to geometry", &mut self.auto_range);
+            if !self.auto_range {
+                let [min, max] = match self.display {
+                    DepthDisplay::Linear => self.z_limits,
+                    DepthDisplay::Raw => [0., 1.],
+                };
+                changed |= ui
+                    .slider_config("Range", min, max)
+                    .build_array(&mut self.manual_range);
+            }
+
+            ui.text(format!(
+                "Near {:.3}, far {:.3}, ratio {:.0}",
+                self.z_limits[0],
+                self.z_limits[1],
+                self.z_limits[1] / self.z_limits[0]
+            ));
+            match self.stats {
+                Some(stats) => {
+                    let [nearest, farthest] = stats.distance_range(self.z_limits);
+                    ui.text(format!(
+                        "Depth {:.6} to {:.6}",
+                        stats.min_depth, stats.max_depth
+                    ));
+                    ui.text(format!("Distance {nearest:.3} to {farthest:.3}"));
+                    ui.text(format!("{} texels covered", stats.covered_texels));
+                }
+                None => ui.text("Nothing drawn"),
+            }
+        }
+        changed
+    }
+}
+
+// Only read by the shaders through `bytemuck::bytes_of`
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DepthViewPushConstBlock {
+    near: f32,
+    far: f32,
+    range_min: f32,
+    range_max: f32,
+    linear: u32,
+}
+
+unsafe impl bytemuck::Pod for DepthViewPushConstBlock {}
+unsafe impl bytemuck::Zeroable for DepthViewPushConstBlock {}
+
+/// Fullscreen view of a depth attachment, with the depth range of the geometry measured
+/// by a compute reduction, to track down z-fighting and precision problems.
+///
+/// The depth must be readable in `SHADER_READ_ONLY_OPTIMAL` by fragment and compute
+/// shaders, as left by targets created with
+/// [`crate::vulkan_wrappers::OffscreenRenderTarget::with_sampled_depth`]. Every frame
+/// [`Self::cmd_reduce`] records the reduction after the depth was written, and
+/// [`Self::read_back`] gets its result once the frame completed, in the next use of the
+/// same frame index.
+pub struct DepthInspector {
+    pub device: Rc<Device>,
+    pub view: DepthView,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    /// One per frame in flight, for the result buffer of the reduction
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    reduce_pipeline_layout: vk::PipelineLayout,
+    reduce_pipeline: vk::Pipeline,
+    results: PerFrame<Buffer<u32>>,
+    /// Whether the result of a frame index was recorded since its last read back
+    pending: PerFrame<bool>,
+}
+
+impl DepthInspector {
+    /// Draws into subpass 0 of `render_pass`, `depth` is read by both the view and the
+    /// reduction and must stay alive as long as the inspector or until [`Self::set_depth`]
+    pub fn new(
+        depth: vk::DescriptorImageInfo,
+        z_limits: [f32; 2],
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let descriptor_pool = {
+            let pool_sizes = [
+                vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(FRAMES as u32)
+                    .build(),
+                vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(FRAMES as u32)
+                    .build(),
+            ];
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&pool_sizes)
+                .max_sets(FRAMES as u32)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        // Shared by the view, which ignores the result buffer, and the reduction
+        let descriptor_set_layout = {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding::builder()
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT | vk::ShaderStageFlags::COMPUTE)
+                    .binding(0)
+                    .descriptor_count(1)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .binding(1)
+                    .descriptor_count(1)
+                    .build(),
+            ];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        let descriptor_sets = unsafe {
+            let set_layouts = [descriptor_set_layout; FRAMES];
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+            PerFrame::from_vec(device.allocate_descriptor_sets(&allocate_info)?)
+        };
+
+        let results = PerFrame::try_new(FRAMES, |_| {
+            Buffer::<u32>::host_storage(3, vk::BufferUsageFlags::TRANSFER_DST, device.clone())
+        })?;
+
+        let (pipeline_layout, pipeline) = Self::create_pipeline(
+            descriptor_set_layout,
+            render_pass,
+            extent,
+            pipeline_cache,
+            device.clone(),
+        )?;
+
+        let (reduce_pipeline_layout, reduce_pipeline) = ComputePipelineBuilder::with_spv_bytes(
+            include_spv!("src/app/shaders/depth_min_max.comp.spv"),
+            device.clone(),
+        )
+        .set_layouts(&[descriptor_set_layout])
+        .pipeline_cache(pipeline_cache)
+        .build()?;
+
+        let inspector = Self {
+            device,
+            view: DepthView::new(z_limits),
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            reduce_pipeline_layout,
+            reduce_pipeline,
+            results,
+            pending: PerFrame::new(FRAMES, |_| false),
+        };
+        inspector.set_depth(depth);
+        Ok(inspector)
+    }
+
+    /// Draws into the swapchain render pass, e.g. instead of the last pass of a frame
+    pub fn from_fixed_vulkan_stuff(
+        s: &FixedVulkanStuff,
+        depth: vk::DescriptorImageInfo,
+        z_limits: [f32; 2],
+    ) -> RenderResult<Self> {
+        Self::new(
+            depth,
+            z_limits,
+            s.render_pass,
+            s.surface.extent(),
+            s.pipeline_cache,
+            s.device.clone(),
+        )
+    }
+
+    /// Rebuilds the view pipeline against the new render pass of `s` when its format changed,
+    /// the reduction does not depend on it. The device must be idle, as after
+    /// [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.format_changed {
+            let (pipeline_layout, pipeline) = Self::create_pipeline(
+                self.descriptor_set_layout,
+                s.render_pass,
+                s.surface.extent(),
+                s.pipeline_cache,
+                self.device.clone(),
+            )?;
+            unsafe {
+                self.device.destroy_pipeline(self.pipeline, None);
+                self.device
+                    .destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
+    fn create_pipeline(
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        PipelineCreator {
+            device,
+            render_pass,
+            extent,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache,
+        }
+        .build()
+    }
+
+    /// Points the inspector at another depth image, e.g. after a resize. Must not be
+    /// called while a frame using the inspector is in flight.
+    pub fn set_depth(&self, depth: vk::DescriptorImageInfo) {
+        for (descriptor_set, result) in self.descriptor_sets.iter().zip(&self.results) {
+            let descriptor_writes = [
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*descriptor_set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&[depth])
+                    .build(),
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(*descriptor_set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&[result.descriptor_default()])
+                    .build(),
+            ];
+            unsafe { self.device.update_descriptor_sets(&descriptor_writes, &[]) };
+        }
+    }
+
+    /// Reads the reduction last recorded with `frame_index`, whose fence must have been
+    /// waited for, e.g. after [`FixedVulkanStuff::frame_get_image_index_to_draw`]
+    pub fn read_back(&mut self, frame_index: usize) {
+        if !std::mem::take(&mut self.pending[frame_index]) {
+            return;
+        }
+        let result = &self.results[frame_index];
+        let [min_depth, max_depth, covered_texels] =
+            unsafe { *(result.mapped_ptr().unwrap() as *const [u32; 3]) };
+        self.view.stats = (covered_texels > 0).then(|| DepthStats {
+            min_depth: f32::from_bits(min_depth),
+            max_depth: f32::from_bits(max_depth),
+            covered_texels,
+        });
+    }
+
+    /// Measures the depth range of the `extent` sized depth image, outside of any render
+    /// pass and after the one writing the depth
+    pub fn cmd_reduce(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        extent: vk::Extent2D,
+    ) {
+        let result = self.results[frame_index].buffer();
+        unsafe {
+            self.device
+                .cmd_fill_buffer(command_buffer, result, 0, 4, u32::MAX);
+            self.device.cmd_fill_buffer(command_buffer, result, 4, 8, 0);
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .build()],
+                &[],
+                &[],
+            );
+
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.reduce_pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.reduce_pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            // Must match the local size of the compute shader
+            self.device.cmd_dispatch(
+                command_buffer,
+                extent.width.div_ceil(16),
+                extent.height.div_ceil(16),
+                1,
+            );
+
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::HOST_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+        }
+        self.pending[frame_index] = true;
+    }
+
+    /// Draws the depth over the whole viewport inside the render pass currently begun
+    /// on `command_buffer`
+    pub fn cmd_draw(&self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        let [range_min, range_max] = self.view.range();
+        let push_const_block = DepthViewPushConstBlock {
+            near: self.view.z_limits[0],
+            far: self.view.z_limits[1],
+            range_min,
+            range_max,
+            linear: (self.view.display == DepthDisplay::Linear) as u32,
+        };
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push_const_block),
+            );
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+}
+
+impl Drop for DepthInspector {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_pipeline(self.reduce_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.reduce_pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+/// Inverse of [`linear_distance`]
+#[cfg(feature = "ui")]
+fn depth_of_distance(distance: f32, z_limits: [f32; 2]) -> f32 {
+    let [near, far] = z_limits;
+    (far - near * far / distance) / (far - near)
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "src/app/shaders/fullscreen.vert.spv",
+        "src/app/shaders/depth_view.frag.spv"
+    );
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<DepthViewPushConstBlock>() as u32)
+            .build()]
+    }
+
+    // The triangle is generated in the vertex shader, from either winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
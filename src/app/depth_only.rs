@@ -0,0 +1,229 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::Mat4;
+
+use super::PipelineBuilder;
+use crate::{
+    error::RenderResult,
+    include_spv,
+    vulkan_wrappers::{Device, ShaderCreate, ShaderModule},
+};
+
+const VERTEX_SPV_PATH: &str = "src/app/shaders/depth_only.vert.spv";
+const ALPHA_TEST_VERTEX_SPV_PATH: &str = "src/app/shaders/depth_only_alpha_test.vert.spv";
+const ALPHA_TEST_FRAG_SPV_PATH: &str = "src/app/shaders/depth_only_alpha_test.frag.spv";
+
+/// Push constant block of the depth-only shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DepthOnlyPush {
+    /// Clip space from model space, e.g. the light space of a shadow map times the model
+    pub mvp: Mat4,
+    /// Texels with a lower alpha are discarded, only read by alpha tested pipelines
+    pub alpha_cutoff: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Pod for DepthOnlyPush {}
+unsafe impl bytemuck::Zeroable for DepthOnlyPush {}
+
+impl DepthOnlyPush {
+    pub fn new(mvp: Mat4) -> Self {
+        Self::alpha_tested(mvp, 0.)
+    }
+
+    pub fn alpha_tested(mvp: Mat4, alpha_cutoff: f32) -> Self {
+        Self {
+            mvp,
+            alpha_cutoff,
+            _padding: [0.; 3],
+        }
+    }
+
+    pub fn range() -> vk::PushConstantRange {
+        vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<Self>() as u32)
+            .build()
+    }
+
+    /// `layout` must come from a [`DepthOnlyPipelineCreator`]
+    pub fn cmd_push(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+    ) {
+        unsafe {
+            device.cmd_push_constants(
+                command_buffer,
+                layout,
+                Self::range().stage_flags,
+                0,
+                bytemuck::bytes_of(self),
+            )
+        };
+    }
+}
+
+/// [`PipelineBuilder`] preset for passes writing only depth, such as shadow maps, depth
+/// pre-passes and picking, drawn with the library's depth-only shaders and a [`DepthOnlyPush`].
+///
+/// Without `alpha_test` the pipeline has no fragment stage at all. With it, set 0 binding 0
+/// is the texture whose alpha is tested, laid out as [`Self::alpha_test_bindings`].
+pub struct DepthOnlyPipelineCreator<'a> {
+    pub device: Rc<Device>,
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    /// Builds for dynamic rendering to a depth attachment of this format instead
+    pub dynamic_rendering_depth_format: Option<vk::Format>,
+    /// Color attachments of the subpass, e.g. of a depth pre-pass in the main pass,
+    /// none of them is written
+    pub color_attachment_count: u32,
+    /// Position at location 0, plus the texture coordinate at location 2 when alpha tested,
+    /// as in [`crate::mesh::Vertex`]
+    pub vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    /// Layouts of the alpha tested pipeline, empty otherwise
+    pub set_layouts: &'a [vk::DescriptorSetLayout],
+    pub alpha_test: bool,
+    pub cull_mode: vk::CullModeFlags,
+    /// Constant and slope factors, against shadow acne
+    pub depth_bias: Option<(f32, f32)>,
+    /// See [`PipelineBuilder::depth_clamp`], for casters behind the near plane of a
+    /// directional light
+    pub depth_clamp: bool,
+    /// See [`PipelineBuilder::depth_clip`]
+    pub depth_clip: Option<bool>,
+    pub pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> DepthOnlyPipelineCreator<'a> {
+    /// Set 0 of alpha tested pipelines
+    pub fn alpha_test_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+        vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .descriptor_count(1)
+            .build()]
+    }
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for DepthOnlyPipelineCreator<'a> {
+    fn device(&self) -> Rc<Device> {
+        self.device.clone()
+    }
+
+    fn vertex_spv_path(&self) -> &'a str {
+        match self.alpha_test {
+            true => ALPHA_TEST_VERTEX_SPV_PATH,
+            false => VERTEX_SPV_PATH,
+        }
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        ALPHA_TEST_FRAG_SPV_PATH
+    }
+
+    fn extent(&self) -> vk::Extent2D {
+        vk::Extent2D::default()
+    }
+
+    fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    fn subpass(&self) -> u32 {
+        self.subpass
+    }
+
+    fn pipeline_cache(&self) -> vk::PipelineCache {
+        self.pipeline_cache
+    }
+
+    fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        self.dynamic_rendering_depth_format
+            .map(|depth_format| (vec![], depth_format))
+    }
+
+    fn vertex_binding_descriptions(&self) -> &'a [vk::VertexInputBindingDescription] {
+        self.vertex_bindings
+    }
+
+    fn vertex_attribute_descriptions(&self) -> &'a [vk::VertexInputAttributeDescription] {
+        self.vertex_attributes
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![DepthOnlyPush::range()]
+    }
+
+    fn shader_stage_create_infos(
+        &self,
+    ) -> RenderResult<(Vec<vk::PipelineShaderStageCreateInfo>, Vec<ShaderModule>)> {
+        let mut shader_creates = vec![match self.alpha_test {
+            true => ShaderCreate::from_bytes(
+                include_spv!("src/app/shaders/depth_only_alpha_test.vert.spv"),
+                vk::ShaderStageFlags::VERTEX,
+                self.device(),
+            )?,
+            false => ShaderCreate::from_bytes(
+                include_spv!("src/app/shaders/depth_only.vert.spv"),
+                vk::ShaderStageFlags::VERTEX,
+                self.device(),
+            )?,
+        }];
+        // Depth is written without a fragment shader
+        if self.alpha_test {
+            shader_creates.push(ShaderCreate::from_bytes(
+                include_spv!("src/app/shaders/depth_only_alpha_test.frag.spv"),
+                vk::ShaderStageFlags::FRAGMENT,
+                self.device(),
+            )?);
+        }
+        Ok(shader_creates
+            .into_iter()
+            .map(|shader_create| (shader_create.stage_create_info, shader_create.module))
+            .unzip())
+    }
+
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        let (constant_factor, slope_factor) = self.depth_bias.unwrap_or_default();
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(self.cull_mode)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(self.depth_bias.is_some())
+            .depth_bias_constant_factor(constant_factor)
+            .depth_bias_slope_factor(slope_factor)
+            .build()
+    }
+
+    fn depth_clamp(&self) -> bool {
+        self.depth_clamp
+    }
+
+    fn depth_clip(&self) -> Option<bool> {
+        self.depth_clip
+    }
+
+    fn color_blend_attach_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::empty())
+            .blend_enable(false)
+            .build()
+    }
+
+    fn color_blend_attach_states(&self) -> Vec<vk::PipelineColorBlendAttachmentState> {
+        vec![self.color_blend_attach_state(); self.color_attachment_count as usize]
+    }
+}
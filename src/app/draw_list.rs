@@ -0,0 +1,184 @@
+use ash::vk;
+
+use super::ObjectData;
+
+/// Indexed draw of one object, as a scene traversal emits it
+#[derive(Clone, Copy, Debug)]
+pub struct DrawItem {
+    pub pipeline: vk::Pipeline,
+    /// Index of the material of the caller, e.g. into [`crate::gltf::Scene::materials`]
+    pub material: usize,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_offset: i32,
+    pub object: ObjectData,
+}
+
+impl DrawItem {
+    fn mesh(&self) -> (u32, u32, i32) {
+        (self.first_index, self.index_count, self.vertex_offset)
+    }
+}
+
+/// Instanced draw of the [`DrawItem`]s sharing a pipeline, a material and a mesh, whose
+/// objects are at `first_instance..first_instance + instance_count` of [`DrawBatches::objects`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrawBatch {
+    pub pipeline: vk::Pipeline,
+    pub material: usize,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_offset: i32,
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+/// Draw calls and state changes of a frame, with and without batching
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    /// Draw calls of the items one by one
+    pub items: usize,
+    pub draw_calls: usize,
+    pub pipeline_binds: usize,
+    pub material_binds: usize,
+}
+
+/// Collects the draws of a frame in any order, see [`Self::build`]
+#[derive(Clone, Debug, Default)]
+pub struct DrawList {
+    items: Vec<DrawItem>,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, item: DrawItem) {
+        self.items.push(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Keeps the capacity, for the next frame
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Sorts the items by pipeline, then material, then mesh, and merges the runs drawing the
+    /// same mesh into instanced draws. The objects are reordered to match, for an
+    /// [`super::ObjectBuffer`] indexed by `gl_InstanceIndex`.
+    pub fn build(&mut self) -> DrawBatches {
+        self.items.sort_by_key(|item| {
+            (
+                vk::Handle::as_raw(item.pipeline),
+                item.material,
+                item.mesh(),
+            )
+        });
+
+        let mut batches = Vec::<DrawBatch>::new();
+        let mut objects = Vec::with_capacity(self.items.len());
+        for item in &self.items {
+            match batches.last_mut() {
+                Some(batch)
+                    if batch.pipeline == item.pipeline
+                        && batch.material == item.material
+                        && (batch.first_index, batch.index_count, batch.vertex_offset)
+                            == item.mesh() =>
+                {
+                    batch.instance_count += 1
+                }
+                _ => batches.push(DrawBatch {
+                    pipeline: item.pipeline,
+                    material: item.material,
+                    first_index: item.first_index,
+                    index_count: item.index_count,
+                    vertex_offset: item.vertex_offset,
+                    first_instance: objects.len() as u32,
+                    instance_count: 1,
+                }),
+            }
+            objects.push(item.object);
+        }
+
+        let mut stats = DrawStats {
+            items: self.items.len(),
+            draw_calls: batches.len(),
+            ..Default::default()
+        };
+        for (_, pipeline_changed, material_changed) in state_changes(&batches) {
+            stats.pipeline_binds += pipeline_changed as usize;
+            stats.material_binds += material_changed as usize;
+        }
+
+        DrawBatches {
+            batches,
+            objects,
+            stats,
+        }
+    }
+}
+
+/// Result of [`DrawList::build`]
+#[derive(Clone, Debug, Default)]
+pub struct DrawBatches {
+    pub batches: Vec<DrawBatch>,
+    /// To write to the [`super::ObjectBuffer`] of the frame
+    pub objects: Vec<ObjectData>,
+    pub stats: DrawStats,
+}
+
+impl DrawBatches {
+    /// Draws the batches with the index and vertex buffers bound by the caller. Pipelines
+    /// are bound when they change, and `bind_material` is called when the material does or
+    /// after a pipeline bind, as many times as [`DrawStats::material_binds`].
+    pub fn cmd_draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        mut bind_material: impl FnMut(usize),
+    ) {
+        for (batch, pipeline_changed, material_changed) in state_changes(&self.batches) {
+            if pipeline_changed {
+                unsafe {
+                    device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        batch.pipeline,
+                    )
+                };
+            }
+            if material_changed {
+                bind_material(batch.material);
+            }
+            unsafe {
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    batch.index_count,
+                    batch.instance_count,
+                    batch.first_index,
+                    batch.vertex_offset,
+                    batch.first_instance,
+                )
+            };
+        }
+    }
+}
+
+/// Each batch with whether its pipeline, then its material, must be bound before it
+fn state_changes(batches: &[DrawBatch]) -> impl Iterator<Item = (&DrawBatch, bool, bool)> {
+    batches.iter().enumerate().map(|(i, batch)| {
+        let previous = i.checked_sub(1).map(|i| &batches[i]);
+        let pipeline_changed = !matches!(previous, Some(p) if p.pipeline == batch.pipeline);
+        let material_changed =
+            pipeline_changed || !matches!(previous, Some(p) if p.material == batch.material);
+        (batch, pipeline_changed, material_changed)
+    })
+}
@@ -1,15 +1,39 @@
-use std::rc::Rc;
+use std::{
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use ash::{prelude::VkResult, vk};
+use log::Level;
 use winit::window::Window;
 
+use super::{
+    FrameGarbage, FrameLatency, GpuTimer, PerFrame, PrerecordedScene, SceneCache,
+    SecondaryCommandPools, SecondaryInheritance,
+};
 use crate::{
     error::{RenderError, RenderResult},
+    logging::report,
     vulkan_wrappers::{
-        extent_helper, Buffer, DepthStencil, Device, Instance, QueueInfo, Surface, SwapChainBatch,
+        aspect_mask, extent_helper, load_pipeline_cache, pipeline_cache_path, save_pipeline_cache,
+        Buffer, DepthStencil, Device, Instance, QueueInfo, QueueRequest, RenderPassBuilder,
+        SubmitGraph, SubmitNode, Surface, SurfaceChange, SwapChainBatch,
     },
 };
 
+#[derive(Clone, Copy)]
+pub struct ClearValue {
+    pub color: vk::ClearValue,
+    pub depth_stencil: vk::ClearValue,
+}
+
+impl ClearValue {
+    pub fn to_array(&self) -> [vk::ClearValue; 2] {
+        [self.color, self.depth_stencil]
+    }
+}
+
 pub struct FrameSyncPrimitive {
     pub in_flight_fence: vk::Fence,
     pub image_available_semaphore: vk::Semaphore,
@@ -22,24 +46,76 @@ pub struct FixedVulkanStuff {
     pub swapchain_batch: SwapChainBatch,
     pub swapchain_framebuffers: Vec<vk::Framebuffer>,
     pub graphic_command_pool: vk::CommandPool,
-    pub graphic_command_buffers: [vk::CommandBuffer; Self::MAX_FRAMES_IN_FLIGHT],
-    pub frame_sync_primitives: [FrameSyncPrimitive; Self::MAX_FRAMES_IN_FLIGHT],
+    pub graphic_command_buffers: PerFrame<vk::CommandBuffer>,
+    pub compute_command_pool: vk::CommandPool,
+    pub compute_command_buffers: PerFrame<vk::CommandBuffer>,
+    pub frame_sync_primitives: PerFrame<FrameSyncPrimitive>,
     pub depth_stencil: DepthStencil,
     pub render_pass: vk::RenderPass,
+    /// Loaded from and saved back to [`pipeline_cache_path`]
     pub pipeline_cache: vk::PipelineCache,
+    pipeline_cache_path: PathBuf,
+    pub frame_latency: FrameLatency,
+    /// Set by [`Self::enable_ui_only_redraw`]
+    pub scene_cache: Option<SceneCache>,
+    /// Set by [`Self::enable_prerecorded_scene`]
+    pub prerecorded_scene: Option<PrerecordedScene>,
+    /// Set by [`Self::enable_secondary_command_pools`]
+    pub secondary_command_pools: Option<SecondaryCommandPools>,
+    /// Set by [`Self::enable_dynamic_rendering`]
+    dynamic_rendering: bool,
+    /// Set by [`Self::enable_gpu_timer`]
+    pub gpu_timer: Option<GpuTimer>,
+    /// Per frame submissions, the draw one presents. Nodes added by the app are submitted
+    /// along the draw with [`Self::frame_queue_node_commands`].
+    pub submit_graph: SubmitGraph,
+    compute_node: SubmitNode,
+    draw_node: SubmitNode,
+    queued_commands: PerFrame<Vec<(SubmitNode, vk::CommandBuffer)>>,
+    /// Destroys what frames in flight may still use once they finished, see [`FrameGarbage`]
+    pub frame_garbage: FrameGarbage,
+    /// Frames presented so far, numbers the queue labels
+    frame_number: u64,
+    /// First and latest time a refit was requested since the last refit
+    pending_refit: Option<(Instant, Instant)>,
+    /// Changes of the refits since the last [`Self::take_surface_change`]
+    surface_change: Option<SurfaceChange>,
 }
 
 impl FixedVulkanStuff {
     pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
     pub const DEFAULT_SURFACE_FORMAT: vk::Format = vk::Format::B8G8R8A8_SRGB;
+    /// Label of the swapchain pass begun by [`Self::cmd_begin_renderpass`] and its variants
+    pub const SCENE_PASS_LABEL: &str = "Scene pass";
+    /// Label of the pass begun by [`Self::cmd_begin_overlay_renderpass`]
+    pub const OVERLAY_PASS_LABEL: &str = "UI overlay pass";
+    const FRAME_LABEL_COLOR: [f32; 4] = [0.2, 0.6, 1.0, 1.0];
+    const PASS_LABEL_COLOR: [f32; 4] = [0.4, 0.8, 0.4, 1.0];
+    /// A requested refit waits until no new request came for this long
+    pub const REFIT_DEBOUNCE: Duration = Duration::from_millis(50);
+    /// ...but no longer than this, so continuous resizing still catches up
+    pub const REFIT_MAX_DELAY: Duration = Duration::from_millis(250);
 
     pub fn new(window: &Window, instance: Rc<Instance>) -> RenderResult<Self> {
+        Self::with_queue_requests(window, instance, &[])
+    }
+
+    /// Also creates the given extra queues, see [`Device::named_queue`]
+    pub fn with_queue_requests(
+        window: &Window,
+        instance: Rc<Instance>,
+        queue_requests: &[QueueRequest],
+    ) -> RenderResult<Self> {
         let surface = Rc::new(Surface::new(
             window,
             instance.clone(),
             Self::DEFAULT_SURFACE_FORMAT,
         )?);
-        let device = Rc::new(Device::new(instance, QueueInfo::new(&surface)?)?);
+        let device = Rc::new(Device::with_queue_requests(
+            instance,
+            QueueInfo::new(&surface)?,
+            queue_requests,
+        )?);
         let swapchain_batch = SwapChainBatch::new(surface.clone(), device.clone())?;
         let graphic_command_pool = {
             let create_info = vk::CommandPoolCreateInfo::builder()
@@ -48,21 +124,31 @@ impl FixedVulkanStuff {
                 .build();
             unsafe { device.create_command_pool(&create_info, None)? }
         };
-        let graphic_command_buffers: [_; Self::MAX_FRAMES_IN_FLIGHT] = {
+        let graphic_command_buffers = {
             let allocate_info = vk::CommandBufferAllocateInfo::builder()
                 .command_pool(graphic_command_pool)
                 .level(vk::CommandBufferLevel::PRIMARY)
                 .command_buffer_count(Self::MAX_FRAMES_IN_FLIGHT as u32)
                 .build();
-            unsafe {
-                device
-                    .allocate_command_buffers(&allocate_info)?
-                    .try_into()
-                    .unwrap()
-            }
+            PerFrame::from_vec(unsafe { device.allocate_command_buffers(&allocate_info)? })
         };
-        let frame_sync_primitives: [_; Self::MAX_FRAMES_IN_FLIGHT] =
-            array_init::try_array_init(|_| -> Result<_, vk::Result> {
+        let compute_command_pool = {
+            let create_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                .queue_family_index(device.compute_queue_family_index())
+                .build();
+            unsafe { device.create_command_pool(&create_info, None)? }
+        };
+        let compute_command_buffers = {
+            let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(compute_command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(Self::MAX_FRAMES_IN_FLIGHT as u32)
+                .build();
+            PerFrame::from_vec(unsafe { device.allocate_command_buffers(&allocate_info)? })
+        };
+        let frame_sync_primitives =
+            PerFrame::try_new(Self::MAX_FRAMES_IN_FLIGHT, |_| -> Result<_, vk::Result> {
                 Ok(unsafe {
                     FrameSyncPrimitive {
                         in_flight_fence: device.create_fence(
@@ -91,8 +177,17 @@ impl FixedVulkanStuff {
             &device,
             depth_stencil.image_view(),
         )?;
-        let pipeline_cache =
-            unsafe { device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None)? };
+        let pipeline_cache_path = pipeline_cache_path(&device);
+        let pipeline_cache = load_pipeline_cache(&pipeline_cache_path, &device)?;
+        let mut submit_graph = SubmitGraph::new(device.clone(), Self::MAX_FRAMES_IN_FLIGHT);
+        let compute_node = submit_graph.add_node("compute", device.compute_queue());
+        let draw_node = submit_graph.add_node("draw", device.graphic_queue());
+        submit_graph.add_dependency(
+            compute_node,
+            draw_node,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+        )?;
+        let frame_garbage = FrameGarbage::new(Self::MAX_FRAMES_IN_FLIGHT, device.clone());
 
         Ok(Self {
             surface,
@@ -100,19 +195,83 @@ impl FixedVulkanStuff {
             swapchain_batch,
             graphic_command_pool,
             graphic_command_buffers,
+            compute_command_pool,
+            compute_command_buffers,
             frame_sync_primitives,
             depth_stencil,
             render_pass,
             swapchain_framebuffers,
             pipeline_cache,
+            pipeline_cache_path,
+            frame_latency: FrameLatency::new(),
+            scene_cache: None,
+            prerecorded_scene: None,
+            secondary_command_pools: None,
+            dynamic_rendering: false,
+            gpu_timer: None,
+            submit_graph,
+            compute_node,
+            draw_node,
+            queued_commands: PerFrame::new(Self::MAX_FRAMES_IN_FLIGHT, |_| vec![]),
+            frame_garbage,
+            frame_number: 0,
+            pending_refit: None,
+            surface_change: None,
         })
     }
 
+    /// Frames recorded ahead of the GPU, the length of every [`PerFrame`] indexed by the
+    /// `frame_index` of the frame methods
+    pub fn frames_in_flight(&self) -> usize {
+        self.frame_sync_primitives.len()
+    }
+
+    pub fn per_frame<T>(&self, f: impl FnMut(usize) -> T) -> PerFrame<T> {
+        PerFrame::new(self.frames_in_flight(), f)
+    }
+
+    pub fn try_per_frame<T, E>(
+        &self,
+        f: impl FnMut(usize) -> Result<T, E>,
+    ) -> Result<PerFrame<T>, E> {
+        PerFrame::try_new(self.frames_in_flight(), f)
+    }
+
     pub fn refit_window(&mut self, window: &Window) -> RenderResult<()> {
+        // A zero sized swapchain is invalid, refit once the window is restored
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            self.request_refit();
+            return Ok(());
+        }
         unsafe {
             self.device.device_wait_idle()?;
-            self.surface.refit_surface_attribute(window)?;
+            // Occluded windows may keep their size while the surface reports a zero extent
+            let Some(change) = self.surface.refit_surface_attribute(window)? else {
+                self.request_refit();
+                return Ok(());
+            };
+            self.pending_refit = None;
             self.swapchain_batch.recreate()?;
+            if change.format_changed {
+                // Pipelines built against the old render pass are rebuilt by the app once it
+                // takes the change
+                self.device.destroy_render_pass(self.render_pass, None);
+                self.render_pass = create_renderpass(
+                    self.surface.format(),
+                    self.depth_stencil.format(),
+                    &self.device,
+                )?;
+                if self.scene_cache.is_some() {
+                    self.enable_ui_only_redraw()?;
+                }
+            }
+            if change.any() {
+                let pending = self.surface_change.get_or_insert_with(Default::default);
+                pending.extent_changed |= change.extent_changed;
+                pending.format_changed |= change.format_changed;
+            }
+            self.frame_latency.reset();
             self.depth_stencil = DepthStencil::new(
                 self.surface.extent(),
                 self.depth_stencil.format(),
@@ -128,19 +287,195 @@ impl FixedVulkanStuff {
                 &self.device,
                 self.depth_stencil.image_view(),
             )?;
+            if let Some(prerecorded_scene) = &mut self.prerecorded_scene {
+                prerecorded_scene.refit(self.swapchain_batch.images().len())?;
+            }
+            if let Some(scene_cache) = &mut self.scene_cache {
+                scene_cache.refit(self.surface.extent(), self.swapchain_batch.images().len())?;
+            }
             Ok(())
         }
     }
 
-    pub fn frame_wait_last_finished(&self, frame_index: usize) -> VkResult<()> {
+    /// Keeps a copy of the scene so frames where only the UI changed can skip rendering it,
+    /// see [`SceneCache`]. Needs swapchain images which can be copied from and to, and the
+    /// render pass, its overlay pass can not follow dynamic rendering.
+    pub fn enable_ui_only_redraw(&mut self) -> RenderResult<()> {
+        if self.dynamic_rendering {
+            return Err(RenderError::ConfigurationInvalid(
+                "UI-only redraw can not be combined with dynamic rendering".to_string(),
+            ));
+        }
+        let transfer = vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST;
+        if !self.swapchain_batch.image_usage().contains(transfer) {
+            return Err(RenderError::PhysicalDeviceNotSupported(
+                "swapchain images can not be copied from and to".to_string(),
+            ));
+        }
+        self.scene_cache = Some(SceneCache::new(
+            self.surface.extent(),
+            self.surface.format(),
+            self.depth_stencil.format(),
+            self.swapchain_batch.images().len(),
+            self.device.clone(),
+        )?);
+        Ok(())
+    }
+
+    /// Records scene commands once per swapchain image instead of every frame,
+    /// see [`PrerecordedScene`]
+    pub fn enable_prerecorded_scene(&mut self) -> RenderResult<()> {
+        self.prerecorded_scene = Some(PrerecordedScene::new(
+            self.swapchain_batch.images().len(),
+            self.device.clone(),
+        )?);
+        Ok(())
+    }
+
+    /// Lets `thread_count` threads record secondary command buffers at once, each from its
+    /// own pool, see [`SecondaryCommandPools`]
+    pub fn enable_secondary_command_pools(&mut self, thread_count: usize) -> RenderResult<()> {
+        self.secondary_command_pools = Some(SecondaryCommandPools::new(
+            thread_count,
+            self.frames_in_flight(),
+            self.device.clone(),
+        )?);
+        Ok(())
+    }
+
+    /// For secondary command buffers executed in the pass begun by
+    /// [`Self::cmd_begin_renderpass_for_secondaries`]
+    pub fn secondary_inheritance(&self, image_index: usize) -> SecondaryInheritance {
+        SecondaryInheritance {
+            render_pass: self.render_pass,
+            subpass: 0,
+            framebuffer: self.swapchain_framebuffers[image_index],
+            extent: self.surface.extent(),
+        }
+    }
+
+    /// Renders to the swapchain with [`Self::cmd_begin_rendering`] instead of the render pass,
+    /// pipelines then have to be built with [`Self::dynamic_rendering_formats`]. Not
+    /// available along [`Self::enable_ui_only_redraw`].
+    pub fn enable_dynamic_rendering(&mut self) -> RenderResult<()> {
+        if self.scene_cache.is_some() {
+            return Err(RenderError::ConfigurationInvalid(
+                "dynamic rendering can not be combined with UI-only redraw".to_string(),
+            ));
+        }
+        if !self.device.supports_dynamic_rendering() {
+            return Err(RenderError::PhysicalDeviceNotSupported(
+                "VK_KHR_dynamic_rendering is not supported".to_string(),
+            ));
+        }
+        self.dynamic_rendering = true;
+        Ok(())
+    }
+
+    pub fn uses_dynamic_rendering(&self) -> bool {
+        self.dynamic_rendering
+    }
+
+    /// Color and depth formats of the swapchain rendering, for
+    /// [`super::PipelineBuilder::dynamic_rendering_formats`]
+    pub fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        self.dynamic_rendering
+            .then(|| (vec![self.surface.format()], self.depth_stencil.format()))
+    }
+
+    /// Measures regions of the graphic command buffers, see [`GpuTimer`]
+    pub fn enable_gpu_timer(&mut self, max_regions: u32) -> RenderResult<()> {
+        self.gpu_timer = Some(GpuTimer::new(max_regions, self.device.clone())?);
+        Ok(())
+    }
+
+    /// Call right after beginning the graphic command buffer, does nothing without a GPU timer
+    pub fn cmd_begin_gpu_timer_frame(&mut self, frame_index: usize) {
+        let command_buffer = self.graphic_command_buffers[frame_index];
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.cmd_begin_frame(command_buffer, frame_index);
+        }
+    }
+
+    pub fn cmd_begin_gpu_region(&mut self, frame_index: usize, name: &'static str) {
+        let command_buffer = self.graphic_command_buffers[frame_index];
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin_region(command_buffer, frame_index, name);
+        }
+    }
+
+    pub fn cmd_end_gpu_region(&mut self, frame_index: usize) {
+        let command_buffer = self.graphic_command_buffers[frame_index];
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_region(command_buffer, frame_index);
+        }
+    }
+
+    /// Whether the scene of the last full frame can be re-presented under a new UI
+    pub fn can_redraw_ui_only(&self) -> bool {
+        self.scene_cache
+            .as_ref()
+            .is_some_and(|scene_cache| scene_cache.is_valid())
+    }
+
+    /// Call after ending the main render pass, then draw the UI
+    /// in [`Self::cmd_begin_overlay_renderpass`]
+    pub fn cmd_store_scene(&mut self, frame_index: usize, image_index: usize) {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        let image = self.swapchain_batch.images()[image_index];
+        self.scene_cache
+            .as_mut()
+            .expect("UI-only redraw is not enabled")
+            .cmd_store(
+                self.graphic_command_buffers[frame_index],
+                image_index,
+                image,
+            );
+    }
+
+    /// Call instead of the main render pass when [`Self::can_redraw_ui_only`],
+    /// then draw the UI in [`Self::cmd_begin_overlay_renderpass`]
+    pub fn cmd_restore_scene(&mut self, frame_index: usize, image_index: usize) {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        let image = self.swapchain_batch.images()[image_index];
+        self.scene_cache
+            .as_mut()
+            .expect("UI-only redraw is not enabled")
+            .cmd_restore(
+                self.graphic_command_buffers[frame_index],
+                image_index,
+                image,
+            );
+    }
+
+    pub fn cmd_begin_overlay_renderpass(&mut self, frame_index: usize, image_index: usize) {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        self.cmd_begin_pass_label(frame_index, Self::OVERLAY_PASS_LABEL);
+        let extent = self.surface.extent();
+        self.scene_cache
+            .as_mut()
+            .expect("UI-only redraw is not enabled")
+            .cmd_begin_overlay_renderpass(
+                self.graphic_command_buffers[frame_index],
+                image_index,
+                self.swapchain_framebuffers[image_index],
+                extent,
+            );
+    }
+
+    /// Also destroys what was retired to [`Self::frame_garbage`] during the last use of
+    /// `frame_index`
+    pub fn frame_wait_last_finished(&mut self, frame_index: usize) -> VkResult<()> {
         debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
         unsafe {
             self.device.wait_for_fences(
                 &[self.frame_sync_primitives[frame_index].in_flight_fence],
                 true,
                 u64::MAX,
-            )
+            )?;
         }
+        self.frame_garbage.collect(frame_index);
+        Ok(())
     }
 
     pub fn frame_acquire_next_image(&self, frame_index: usize) -> VkResult<(u32, bool)> {
@@ -157,22 +492,53 @@ impl FixedVulkanStuff {
         }
     }
 
-    pub fn frame_draw_queue_submit(&self, frame_index: usize) -> VkResult<()> {
+    /// Queues `compute_command_buffers[frame_index]` for the compute queue, the next
+    /// draw submission of the frame waits for it before reading vertex input
+    pub fn frame_compute_queue_submit(&mut self, frame_index: usize) -> VkResult<()> {
+        self.frame_queue_node_commands(
+            frame_index,
+            self.compute_node,
+            self.compute_command_buffers[frame_index],
+        );
+        Ok(())
+    }
+
+    /// Queues `command_buffer` for `node` of [`Self::submit_graph`], submitted along the
+    /// next draw submission of the frame
+    pub fn frame_queue_node_commands(
+        &mut self,
+        frame_index: usize,
+        node: SubmitNode,
+        command_buffer: vk::CommandBuffer,
+    ) {
         debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
-        let submit_info = vk::SubmitInfo::builder()
-            .wait_semaphores(&[self.frame_sync_primitives[frame_index].image_available_semaphore])
-            .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
-            .command_buffers(&[self.graphic_command_buffers[frame_index]])
-            .signal_semaphores(&[self.frame_sync_primitives[frame_index].render_finished_semaphore])
-            .build();
+        self.queued_commands[frame_index].push((node, command_buffer));
+    }
 
-        unsafe {
-            self.device.queue_submit(
-                self.device.graphic_queue(),
-                &[submit_info],
-                self.frame_sync_primitives[frame_index].in_flight_fence,
+    /// Node of [`Self::submit_graph`] the frame is drawn by, for app nodes to precede
+    pub fn draw_node(&self) -> SubmitNode {
+        self.draw_node
+    }
+
+    pub fn frame_draw_queue_submit(&mut self, frame_index: usize) -> VkResult<()> {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        let queued_commands = std::mem::take(&mut self.queued_commands[frame_index]);
+        let sync_primitive = &self.frame_sync_primitives[frame_index];
+        queued_commands
+            .into_iter()
+            .fold(
+                self.submit_graph.frame(frame_index),
+                |frame, (node, command_buffer)| frame.commands(node, &[command_buffer]),
             )
-        }
+            .commands(self.draw_node, &[self.graphic_command_buffers[frame_index]])
+            .wait(
+                self.draw_node,
+                sync_primitive.image_available_semaphore,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )
+            .signal(self.draw_node, sync_primitive.render_finished_semaphore)
+            .fence(self.draw_node, sync_primitive.in_flight_fence)
+            .submit()
     }
 
     pub fn frame_queue_present(&self, frame_index: usize, image_index: usize) -> VkResult<bool> {
@@ -191,12 +557,15 @@ impl FixedVulkanStuff {
         window: &Window,
     ) -> RenderResult<(usize, bool)> {
         self.frame_wait_last_finished(frame_index)?;
+        self.frame_latency.begin_acquire(&self.swapchain_batch);
         let result = self.frame_acquire_next_image(frame_index);
         match result {
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
                 self.refit_window(window)?;
                 return Ok((usize::MAX, true));
             }
+            // Still presentable, refitted along with the resize events
+            Ok((_, true)) => self.request_refit(),
             Ok(_) => {}
             Err(e) => return Err(RenderError::VkResult(e)),
         }
@@ -204,52 +573,167 @@ impl FixedVulkanStuff {
         Ok((result?.0 as usize, false))
     }
 
+    /// Resets and begins `graphic_command_buffers[frame_index]`, once the fence of the frame
+    /// has been waited for in [`Self::frame_get_image_index_to_draw`]
+    pub fn frame_begin_command_buffer(&self, frame_index: usize) -> VkResult<vk::CommandBuffer> {
+        let command_buffer = self.graphic_command_buffers[frame_index];
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())?;
+            self.device
+                .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())?;
+        }
+        Ok(command_buffer)
+    }
+
+    pub fn frame_end_command_buffer(&self, frame_index: usize) -> VkResult<()> {
+        unsafe {
+            self.device
+                .end_command_buffer(self.graphic_command_buffers[frame_index])
+        }
+    }
+
+    /// Resize events reach the swapchain through [`Self::request_refit`]
     pub fn frame_queue_submit_and_present(
         &mut self,
         frame_index: usize,
         image_index: usize,
         window: &Window,
-        window_resized: bool,
-    ) -> RenderResult<bool> {
-        self.frame_draw_queue_submit(frame_index)?;
-        let result = self.frame_queue_present(frame_index, image_index);
-        let need_recreate = match result {
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) | Ok(true) => true,
-            Ok(_) => false,
+    ) -> RenderResult<()> {
+        // Profilers group the submission and the present of a frame under its label
+        let graphic_queue = self.device.graphic_queue();
+        self.device.queue_begin_debug_label(
+            graphic_queue,
+            &format!("Frame {}", self.frame_number),
+            Self::FRAME_LABEL_COLOR,
+        );
+        let result = self
+            .frame_draw_queue_submit(frame_index)
+            .and_then(|_| self.frame_queue_present(frame_index, image_index));
+        self.device.queue_end_debug_label(graphic_queue);
+        self.frame_number += 1;
+        self.frame_latency.end_present(&self.swapchain_batch);
+        match result {
+            // An out of date swapchain can not be presented to anymore, so it is refitted at once
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.refit_window(window)?,
             Err(e) => return Err(RenderError::VkResult(e)),
-        };
-        if need_recreate || window_resized {
-            self.refit_window(window)?;
-        };
+            Ok(suboptimal) => {
+                if suboptimal {
+                    self.request_refit();
+                }
+                if self.refit_due() {
+                    self.refit_window(window)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What the refits since the last call changed, for the app to rebuild what depends on
+    /// the extent or format of the swapchain, e.g. from [`super::WindowApp::on_surface_changed`]
+    pub fn take_surface_change(&mut self) -> Option<SurfaceChange> {
+        self.surface_change.take()
+    }
+
+    /// Coalesces refit requests of resize events and suboptimal presents,
+    /// frames keep using the current swapchain until [`Self::refit_due`]
+    pub fn request_refit(&mut self) {
+        let now = Instant::now();
+        self.pending_refit = Some(match self.pending_refit {
+            Some((first, _)) => (first, now),
+            None => (now, now),
+        });
+    }
 
-        Ok(false)
+    pub fn refit_due(&self) -> bool {
+        self.pending_refit.is_some_and(|(first, latest)| {
+            latest.elapsed() >= Self::REFIT_DEBOUNCE || first.elapsed() >= Self::REFIT_MAX_DELAY
+        })
+    }
+
+    /// Present mode and [`FrameLatency::summary`], with a note when display times are unknown
+    pub fn latency_summary(&self) -> Vec<String> {
+        let mut summary = vec![format!("present mode: {:?}", self.surface.present_mode())];
+        summary.extend(self.frame_latency.summary());
+        if self.device.present_wait().is_none() {
+            summary.push("VK_KHR_present_wait unavailable, no display times".to_string());
+        }
+        summary
     }
 
+    /// Covers the swapchain images, for passes rendering into other targets use
+    /// [`Self::cmd_set_viewport_and_scissor_to`]
     pub fn cmd_set_viewport_and_scissor(&self, frame_index: usize) {
+        self.cmd_set_viewport_and_scissor_to(frame_index, self.surface.extent());
+    }
+
+    /// Covers `extent`, the extent of the target of the active render pass, e.g.
+    /// [`crate::vulkan_wrappers::OffscreenRenderTarget::extent`]
+    pub fn cmd_set_viewport_and_scissor_to(&self, frame_index: usize, extent: vk::Extent2D) {
         debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
-        unsafe {
-            self.device.cmd_set_viewport(
-                self.graphic_command_buffers[frame_index],
-                0,
-                &[extent_helper::viewport_from_extent(self.surface.extent())],
-            );
+        extent_helper::cmd_set_viewport_and_scissor(
+            &self.device,
+            self.graphic_command_buffers[frame_index],
+            extent,
+        );
+    }
 
-            self.device.cmd_set_scissor(
+    /// Begins the swapchain pass under [`Self::SCENE_PASS_LABEL`],
+    /// end it with [`Self::cmd_end_renderpass`]
+    pub fn cmd_begin_renderpass(
+        &self,
+        frame_index: usize,
+        image_index: usize,
+        clear_value: &ClearValue,
+    ) {
+        self.cmd_begin_renderpass_with_contents(
+            frame_index,
+            image_index,
+            clear_value,
+            vk::SubpassContents::INLINE,
+        );
+    }
+
+    /// Like [`Self::cmd_begin_renderpass`], but the pass may only execute
+    /// secondary command buffers such as those of [`PrerecordedScene`]
+    pub fn cmd_begin_renderpass_for_secondaries(
+        &self,
+        frame_index: usize,
+        image_index: usize,
+        clear_value: &ClearValue,
+    ) {
+        self.cmd_begin_renderpass_with_contents(
+            frame_index,
+            image_index,
+            clear_value,
+            vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
+        );
+    }
+
+    pub fn cmd_execute_secondaries(
+        &self,
+        frame_index: usize,
+        secondary_command_buffers: &[vk::CommandBuffer],
+    ) {
+        unsafe {
+            self.device.cmd_execute_commands(
                 self.graphic_command_buffers[frame_index],
-                0,
-                &[extent_helper::scissor_from_extent(self.surface.extent())],
+                secondary_command_buffers,
             );
         }
     }
 
-    pub fn cmd_begin_renderpass(
+    fn cmd_begin_renderpass_with_contents(
         &self,
         frame_index: usize,
         image_index: usize,
-        clear_value: &super::ClearValue,
+        clear_value: &ClearValue,
+        contents: vk::SubpassContents,
     ) {
         debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
         debug_assert!(image_index < self.swapchain_batch.images().len());
+        self.cmd_begin_pass_label(frame_index, Self::SCENE_PASS_LABEL);
         unsafe {
             self.device.cmd_begin_render_pass(
                 self.graphic_command_buffers[frame_index],
@@ -259,28 +743,171 @@ impl FixedVulkanStuff {
                     .render_area(extent_helper::scissor_from_extent(self.surface.extent()))
                     .clear_values(&clear_value.to_array())
                     .build(),
-                vk::SubpassContents::INLINE,
+                contents,
+            );
+        }
+    }
+
+    /// Dynamic rendering counterpart of [`Self::cmd_begin_renderpass`], clears the swapchain
+    /// image and the depth attachment, end it with [`Self::cmd_end_rendering`]
+    pub fn cmd_begin_rendering(
+        &self,
+        frame_index: usize,
+        image_index: usize,
+        clear_value: &ClearValue,
+    ) -> RenderResult<()> {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        debug_assert!(self.dynamic_rendering);
+        let command_buffer = self.graphic_command_buffers[frame_index];
+        self.cmd_begin_pass_label(frame_index, Self::SCENE_PASS_LABEL);
+        // Without a render pass the layout transitions are recorded by hand,
+        // previous contents of both attachments are not needed
+        let barriers = [
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .src_access_mask(vk::AccessFlags::NONE)
+                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(self.swapchain_batch.images()[image_index])
+                .subresource_range(single_subresource_range(vk::ImageAspectFlags::COLOR))
+                .build(),
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(*self.depth_stencil.buffer().image())
+                .subresource_range(single_subresource_range(aspect_mask(
+                    self.depth_stencil.format(),
+                )))
+                .build(),
+        ];
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers,
+            );
+        }
+
+        let color_attachments = [vk::RenderingAttachmentInfo::builder()
+            .image_view(self.swapchain_batch.image_views()[image_index])
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(clear_value.color)
+            .build()];
+        let depth_attachment = vk::RenderingAttachmentInfo::builder()
+            .image_view(*self.depth_stencil.image_view())
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(clear_value.depth_stencil)
+            .build();
+        self.device.cmd_begin_dynamic_rendering(
+            command_buffer,
+            &vk::RenderingInfo::builder()
+                .render_area(extent_helper::scissor_from_extent(self.surface.extent()))
+                .layer_count(1)
+                .color_attachments(&color_attachments)
+                .depth_attachment(&depth_attachment)
+                .build(),
+        )
+    }
+
+    /// Ends any of the swapchain render passes, together with its label
+    pub fn cmd_end_renderpass(&self, frame_index: usize) {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        unsafe {
+            self.device
+                .cmd_end_render_pass(self.graphic_command_buffers[frame_index]);
+        }
+        self.cmd_end_pass_label(frame_index);
+    }
+
+    /// Opens a label region in the graphic command buffer, for passes of examples which
+    /// are not begun by this struct. Close it with [`Self::cmd_end_pass_label`].
+    pub fn cmd_begin_pass_label(&self, frame_index: usize, name: &str) {
+        self.device.cmd_begin_debug_label(
+            self.graphic_command_buffers[frame_index],
+            name,
+            Self::PASS_LABEL_COLOR,
+        );
+    }
+
+    pub fn cmd_end_pass_label(&self, frame_index: usize) {
+        self.device
+            .cmd_end_debug_label(self.graphic_command_buffers[frame_index]);
+    }
+
+    /// Ends [`Self::cmd_begin_rendering`] and makes the swapchain image presentable
+    pub fn cmd_end_rendering(&self, frame_index: usize, image_index: usize) {
+        debug_assert!(frame_index < Self::MAX_FRAMES_IN_FLIGHT);
+        let command_buffer = self.graphic_command_buffers[frame_index];
+        self.device.cmd_end_dynamic_rendering(command_buffer);
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::NONE)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.swapchain_batch.images()[image_index])
+            .subresource_range(single_subresource_range(vk::ImageAspectFlags::COLOR))
+            .build();
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
             );
         }
+        self.cmd_end_pass_label(frame_index);
     }
 
     pub fn device_local_vertex_buffer<T>(&self, vertices: &[T]) -> RenderResult<Buffer<T>> {
-        Buffer::new_device_local(
+        Buffer::device_local_vertex(
             vertices,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
             self.device.clone(),
             &self.graphic_command_pool,
-            &self.device.graphic_queue(),
+            &self.device.upload_queue(),
         )
     }
 
-    pub fn device_local_indice_buffer<T>(&self, indices: &[T]) -> RenderResult<Buffer<T>> {
+    /// Storage buffer also usable as vertex buffer, e.g. for particles simulated by a compute shader
+    pub fn device_local_storage_buffer<T>(&self, elements: &[T]) -> RenderResult<Buffer<T>> {
         Buffer::new_device_local(
+            elements,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER,
+            self.device.clone(),
+            &self.graphic_command_pool,
+            &self.device.upload_queue(),
+        )
+    }
+
+    pub fn device_local_indice_buffer<T>(&self, indices: &[T]) -> RenderResult<Buffer<T>> {
+        Buffer::device_local_index(
             indices,
-            vk::BufferUsageFlags::INDEX_BUFFER,
             self.device.clone(),
             &self.graphic_command_pool,
-            &self.device.graphic_queue(),
+            &self.device.upload_queue(),
         )
     }
 }
@@ -297,10 +924,27 @@ impl Drop for FixedVulkanStuff {
             });
             self.device
                 .destroy_command_pool(self.graphic_command_pool, None);
+            self.device
+                .destroy_command_pool(self.compute_command_pool, None);
             self.swapchain_framebuffers
                 .iter()
                 .for_each(|fb| self.device.destroy_framebuffer(*fb, None));
             self.device.destroy_render_pass(self.render_pass, None);
+        }
+        // Pipelines created this run speed up the next launch
+        if let Err(e) =
+            save_pipeline_cache(self.pipeline_cache, &self.pipeline_cache_path, &self.device)
+        {
+            report(
+                Level::Warn,
+                "pipeline cache",
+                format_args!(
+                    "Fail to save pipeline cache to {}: {e}",
+                    self.pipeline_cache_path.display()
+                ),
+            );
+        }
+        unsafe {
             self.device
                 .destroy_pipeline_cache(self.pipeline_cache, None);
         }
@@ -333,71 +977,65 @@ fn create_swapchain_frame_buffer(
 fn create_renderpass(
     color_format: vk::Format,
     depth_format: vk::Format,
-    device: &Device,
+    device: &Rc<Device>,
 ) -> VkResult<vk::RenderPass> {
-    let color_attach = vk::AttachmentDescription::builder()
-        .format(color_format)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .load_op(vk::AttachmentLoadOp::CLEAR)
-        .store_op(vk::AttachmentStoreOp::STORE)
-        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-        .build();
-    let depth_attach = vk::AttachmentDescription::builder()
-        .format(depth_format)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .load_op(vk::AttachmentLoadOp::CLEAR)
-        .store_op(vk::AttachmentStoreOp::STORE)
-        .stencil_load_op(vk::AttachmentLoadOp::LOAD)
-        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-        .build();
-    let color_attach_ref = vk::AttachmentReference::builder()
-        .attachment(0)
-        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-        .build();
-    let depth_attach_ref = vk::AttachmentReference::builder()
-        .attachment(1)
-        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
-    let subpass_desc = vk::SubpassDescription::builder()
-        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-        .color_attachments(&[color_attach_ref])
-        .depth_stencil_attachment(&depth_attach_ref)
-        .build();
-    let dependency_0 = vk::SubpassDependency::builder()
-        .src_subpass(vk::SUBPASS_EXTERNAL)
-        .dst_subpass(0)
-        .src_stage_mask(
-            vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+    RenderPassBuilder::new(device.clone())
+        .attachment(
+            color_format,
+            vk::AttachmentLoadOp::CLEAR,
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::PRESENT_SRC_KHR,
         )
-        .dst_stage_mask(
-            vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+        .attachment(
+            depth_format,
+            vk::AttachmentLoadOp::CLEAR,
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
         )
-        .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
-        .dst_access_mask(
-            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        .subpass(&[0], Some(1))
+        .dependency(
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .build(),
         )
-        .build();
-    let dependency_1 = vk::SubpassDependency::builder()
-        .src_subpass(vk::SUBPASS_EXTERNAL)
-        .dst_subpass(0)
-        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-        .src_access_mask(vk::AccessFlags::default())
-        .dst_access_mask(
-            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        .dependency(
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::default())
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .build(),
         )
-        .build();
-    let renderpass_create_info = vk::RenderPassCreateInfo::builder()
-        .attachments(&[color_attach, depth_attach])
-        .subpasses(&[subpass_desc])
-        .dependencies(&[dependency_0, dependency_1])
-        .build();
-    Ok(unsafe { device.create_render_pass(&renderpass_create_info, None)? })
+        .build()
+}
+
+fn single_subresource_range(aspect_mask: vk::ImageAspectFlags) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange::builder()
+        .aspect_mask(aspect_mask)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1)
+        .build()
 }
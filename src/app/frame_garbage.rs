@@ -0,0 +1,119 @@
+use std::{any::Any, rc::Rc};
+
+use ash::vk;
+
+use super::PerFrame;
+use crate::vulkan_wrappers::Device;
+
+/// Handle whose destruction waits in a [`FrameGarbage`]
+pub enum Garbage {
+    Buffer(vk::Buffer),
+    Image(vk::Image),
+    ImageView(vk::ImageView),
+    DeviceMemory(vk::DeviceMemory),
+    Sampler(vk::Sampler),
+    Framebuffer(vk::Framebuffer),
+    RenderPass(vk::RenderPass),
+    Pipeline(vk::Pipeline),
+    PipelineLayout(vk::PipelineLayout),
+    DescriptorPool(vk::DescriptorPool),
+    /// Wrapper destroying its handles when dropped, such as a [`crate::vulkan_wrappers::Buffer`]
+    Owned(Box<dyn Any>),
+}
+
+macro_rules! impl_garbage_from {
+    ($($handle: ident),+) => {
+        $(impl From<vk::$handle> for Garbage {
+            fn from(handle: vk::$handle) -> Self {
+                Garbage::$handle(handle)
+            }
+        })+
+    };
+}
+
+impl_garbage_from!(
+    Buffer,
+    Image,
+    ImageView,
+    DeviceMemory,
+    Sampler,
+    Framebuffer,
+    RenderPass,
+    Pipeline,
+    PipelineLayout,
+    DescriptorPool
+);
+
+/// Objects retired while frames in flight may still use them, e.g. pipelines replaced on
+/// a hot reload, destroyed without waiting for the device to be idle.
+///
+/// What is retired during a frame is destroyed the next time that frame's fence has been
+/// waited for, which also covers every earlier submission to the graphic queue.
+/// [`super::FixedVulkanStuff::frame_wait_last_finished`] collects it.
+pub struct FrameGarbage {
+    /// Per frame in flight, retired since its fence was last waited for
+    pending: PerFrame<Vec<Garbage>>,
+    current_frame: usize,
+    device: Rc<Device>,
+}
+
+impl FrameGarbage {
+    pub fn new(frames_in_flight: usize, device: Rc<Device>) -> Self {
+        Self {
+            pending: PerFrame::new(frames_in_flight, |_| vec![]),
+            current_frame: 0,
+            device,
+        }
+    }
+
+    /// Destroys `garbage` once no frame submitted so far uses it anymore. The caller must
+    /// not record it into any later frame.
+    pub fn retire(&mut self, garbage: impl Into<Garbage>) {
+        self.pending[self.current_frame].push(garbage.into());
+    }
+
+    pub fn retire_owned(&mut self, value: impl Any) {
+        self.retire(Garbage::Owned(Box::new(value)));
+    }
+
+    /// Objects waiting for a frame to finish
+    pub fn pending_count(&self) -> usize {
+        self.pending.iter().map(Vec::len).sum()
+    }
+
+    /// Destroys what was retired during the last use of `frame_index`, whose fence must
+    /// have signaled, then tags what is retired from now on with it
+    pub fn collect(&mut self, frame_index: usize) {
+        let garbage = std::mem::take(&mut self.pending[frame_index]);
+        garbage
+            .into_iter()
+            .for_each(|garbage| self.destroy(garbage));
+        self.current_frame = frame_index;
+    }
+
+    fn destroy(&self, garbage: Garbage) {
+        let device = &self.device;
+        unsafe {
+            match garbage {
+                Garbage::Buffer(buffer) => device.destroy_buffer(buffer, None),
+                Garbage::Image(image) => device.destroy_image(image, None),
+                Garbage::ImageView(view) => device.destroy_image_view(view, None),
+                Garbage::DeviceMemory(memory) => device.free_tracked_memory(memory),
+                Garbage::Sampler(sampler) => device.destroy_sampler(sampler, None),
+                Garbage::Framebuffer(framebuffer) => device.destroy_framebuffer(framebuffer, None),
+                Garbage::RenderPass(render_pass) => device.destroy_render_pass(render_pass, None),
+                Garbage::Pipeline(pipeline) => device.destroy_pipeline(pipeline, None),
+                Garbage::PipelineLayout(layout) => device.destroy_pipeline_layout(layout, None),
+                Garbage::DescriptorPool(pool) => device.destroy_descriptor_pool(pool, None),
+                Garbage::Owned(value) => drop(value),
+            }
+        }
+    }
+}
+
+impl Drop for FrameGarbage {
+    fn drop(&mut self) {
+        unsafe { self.device.device_wait_idle().unwrap() };
+        (0..self.pending.len()).for_each(|frame_index| self.collect(frame_index));
+    }
+}
@@ -0,0 +1,148 @@
+use std::{collections::VecDeque, time::Instant};
+
+use crate::vulkan_wrappers::SwapChainBatch;
+
+/// Weight of the newest sample in the smoothed latencies
+const SMOOTHING: f64 = 0.1;
+/// Bounding the latency gives up instead of stalling forever, e.g. on a minimized window
+const BOUND_WAIT_TIMEOUT_NS: u64 = 100_000_000;
+
+struct PendingPresent {
+    present_id: u64,
+    acquire_time: Instant,
+    present_time: Instant,
+}
+
+/// Acquire to present to display latency of presented frames, in milliseconds.
+///
+/// Display times come from `VK_KHR_present_wait`. They are polled once per frame,
+/// so without a latency bound they are upper bounds off by up to a frame time
+pub struct FrameLatency {
+    pending: VecDeque<PendingPresent>,
+    acquire_time: Option<Instant>,
+    acquire_to_present: Option<f64>,
+    present_to_display: Option<f64>,
+    acquire_to_display: Option<f64>,
+    /// Wait before acquiring until at most this many presents are not displayed yet
+    pub max_queued_presents: Option<u64>,
+}
+
+impl FrameLatency {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            acquire_time: None,
+            acquire_to_present: None,
+            present_to_display: None,
+            acquire_to_display: None,
+            max_queued_presents: None,
+        }
+    }
+
+    pub fn acquire_to_present_ms(&self) -> Option<f64> {
+        self.acquire_to_present
+    }
+
+    pub fn present_to_display_ms(&self) -> Option<f64> {
+        self.present_to_display
+    }
+
+    pub fn acquire_to_display_ms(&self) -> Option<f64> {
+        self.acquire_to_display
+    }
+
+    /// Called right before acquiring a swapchain image, applies the latency bound first
+    pub fn begin_acquire(&mut self, swapchain_batch: &SwapChainBatch) {
+        if let (Some(max_queued_presents), Some(last_present_id)) =
+            (self.max_queued_presents, swapchain_batch.last_present_id())
+        {
+            let present_id = (last_present_id + 1).saturating_sub(max_queued_presents.max(1));
+            if present_id > 0 {
+                let _ = swapchain_batch.wait_for_present(present_id, BOUND_WAIT_TIMEOUT_NS);
+            }
+        }
+        self.collect_displayed(swapchain_batch);
+        self.acquire_time = Some(Instant::now());
+    }
+
+    /// Called right after the image acquired by the last [`Self::begin_acquire`] is presented
+    pub fn end_present(&mut self, swapchain_batch: &SwapChainBatch) {
+        let (Some(acquire_time), present_time) = (self.acquire_time.take(), Instant::now()) else {
+            return;
+        };
+        smooth(
+            &mut self.acquire_to_present,
+            (present_time - acquire_time).as_secs_f64() * 1000.,
+        );
+        if let Some(present_id) = swapchain_batch.last_present_id() {
+            if self
+                .pending
+                .back()
+                .is_none_or(|x| x.present_id < present_id)
+            {
+                self.pending.push_back(PendingPresent {
+                    present_id,
+                    acquire_time,
+                    present_time,
+                });
+            }
+        }
+    }
+
+    /// Presents of a destroyed swapchain are never reported as displayed
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.acquire_time = None;
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        let format_ms =
+            |latency: Option<f64>| latency.map_or("n/a".to_string(), |x| format!("{x:.2} ms"));
+        vec![
+            format!("acquire -> present: {}", format_ms(self.acquire_to_present)),
+            format!("present -> display: {}", format_ms(self.present_to_display)),
+            format!("acquire -> display: {}", format_ms(self.acquire_to_display)),
+            match self.max_queued_presents {
+                Some(n) => format!("latency bound (F10): {n} queued present(s)"),
+                None => "latency bound (F10): off".to_string(),
+            },
+        ]
+    }
+
+    fn collect_displayed(&mut self, swapchain_batch: &SwapChainBatch) {
+        while let Some(pending) = self.pending.front() {
+            match swapchain_batch.wait_for_present(pending.present_id, 0) {
+                Ok(true) => {
+                    let display_time = Instant::now();
+                    smooth(
+                        &mut self.present_to_display,
+                        (display_time - pending.present_time).as_secs_f64() * 1000.,
+                    );
+                    smooth(
+                        &mut self.acquire_to_display,
+                        (display_time - pending.acquire_time).as_secs_f64() * 1000.,
+                    );
+                    self.pending.pop_front();
+                }
+                Ok(false) => break,
+                Err(_) => {
+                    self.pending.clear();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Default for FrameLatency {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn smooth(average: &mut Option<f64>, sample: f64) {
+    *average = Some(match *average {
+        Some(average) => average + (sample - average) * SMOOTHING,
+        None => sample,
+    });
+}
@@ -0,0 +1,304 @@
+use std::{path::Path, rc::Rc};
+
+use ash::vk;
+
+use super::{FixedVulkanStuff, PipelineBuilder};
+use crate::{
+    error::RenderResult,
+    impl_pipeline_builder_fns, include_spv,
+    vulkan_wrappers::{Device, OffscreenRenderTarget},
+};
+
+const VERTEX_SPV_PATH: &str = "src/app/shaders/fullscreen.vert.spv";
+
+/// Post-processing step drawing a single triangle over the whole viewport, without
+/// vertex buffer. The fragment shader gets the uv at location 0, its one sampled input
+/// at binding 0 and optional push constants.
+///
+/// Passes built with [`FullscreenPass::offscreen`] render into their own color target,
+/// whose [`FullscreenPass::output_descriptor`] is the input of the next pass of a chain.
+/// The last pass usually draws into the swapchain render pass instead.
+pub struct FullscreenPass {
+    pub device: Rc<Device>,
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub descriptor_pool: vk::DescriptorPool,
+    pub descriptor_set: vk::DescriptorSet,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub pipeline: vk::Pipeline,
+    /// `None` when drawing into a render pass owned by the caller
+    output: Option<OffscreenRenderTarget>,
+}
+
+impl FullscreenPass {
+    /// Renders into a color-only target of `extent` and `format`, cleared at every run
+    pub fn offscreen<P: AsRef<Path>>(
+        frag_spv_path: P,
+        push_constant_size: u32,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let output = OffscreenRenderTarget::color_only(extent, format, device.clone())?;
+        let mut pass = Self::with_render_pass(
+            frag_spv_path,
+            push_constant_size,
+            output.render_pass(),
+            extent,
+            pipeline_cache,
+            device,
+        )?;
+        pass.output = Some(output);
+        Ok(pass)
+    }
+
+    /// Draws into subpass 0 of `render_pass`, begun by the caller
+    pub fn with_render_pass<P: AsRef<Path>>(
+        frag_spv_path: P,
+        push_constant_size: u32,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let descriptor_pool = {
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .build()])
+                .max_sets(1)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        let descriptor_set_layout = {
+            let bindings = [vk::DescriptorSetLayoutBinding::builder()
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .binding(0)
+                .descriptor_count(1)
+                .build()];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        let descriptor_set = unsafe {
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&[descriptor_set_layout])
+                .build();
+            device.allocate_descriptor_sets(&allocate_info)?[0]
+        };
+
+        let (pipeline_layout, pipeline) = PipelineCreator {
+            device: device.clone(),
+            render_pass,
+            extent,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache,
+            frag_spv_path: frag_spv_path.as_ref(),
+            push_constant_size,
+        }
+        .build()?;
+
+        Ok(Self {
+            device,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            pipeline_layout,
+            pipeline,
+            output: None,
+        })
+    }
+
+    /// Draws into the swapchain render pass, before the UI overlay
+    pub fn from_fixed_vulkan_stuff<P: AsRef<Path>>(
+        s: &FixedVulkanStuff,
+        frag_spv_path: P,
+        push_constant_size: u32,
+    ) -> RenderResult<Self> {
+        Self::with_render_pass(
+            frag_spv_path,
+            push_constant_size,
+            s.render_pass,
+            s.surface.extent(),
+            s.pipeline_cache,
+            s.device.clone(),
+        )
+    }
+
+    /// Points the pass at another image, must not be called while a frame using it is in flight
+    pub fn set_input(&self, input: vk::DescriptorImageInfo) {
+        let image_descritptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&[input])
+            .build();
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(&[image_descritptor_write], &[])
+        };
+    }
+
+    pub fn output(&self) -> Option<&OffscreenRenderTarget> {
+        self.output.as_ref()
+    }
+
+    /// Panics for passes drawing into a render pass of the caller
+    pub fn output_descriptor(&self) -> vk::DescriptorImageInfo {
+        self.output
+            .as_ref()
+            .expect("Fullscreen pass has no output target")
+            .color_descriptor()
+    }
+
+    /// Recreates the output target, the next pass of a chain needs its input set again
+    pub fn resize_output(&mut self, extent: vk::Extent2D) -> RenderResult<()> {
+        match &mut self.output {
+            Some(output) => output.resize(extent),
+            None => Ok(()),
+        }
+    }
+
+    /// Draws the triangle inside the render pass currently begun on `command_buffer`,
+    /// `push_constants` may be empty when the pass has none
+    pub fn cmd_draw(&self, command_buffer: vk::CommandBuffer, push_constants: &[u8]) {
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            if !push_constants.is_empty() {
+                self.device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    push_constants,
+                );
+            }
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    /// Begins the render pass of the output target, draws and ends it. The output is
+    /// left ready to be sampled by fragment shaders of later passes.
+    pub fn cmd_run(&self, command_buffer: vk::CommandBuffer, push_constants: &[u8]) {
+        let output = self
+            .output
+            .as_ref()
+            .expect("Fullscreen pass has no output target");
+        output.cmd_begin_render_pass(
+            command_buffer,
+            &[vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0., 0., 0., 0.],
+                },
+            }],
+        );
+        output.cmd_set_viewport_and_scissor(command_buffer);
+        self.cmd_draw(command_buffer, push_constants);
+        unsafe { self.device.cmd_end_render_pass(command_buffer) };
+    }
+}
+
+impl Drop for FullscreenPass {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    frag_spv_path: &'a Path,
+    push_constant_size: u32,
+}
+
+impl<'a> PipelineBuilder<'a, &'a Path> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a Path {
+        Path::new(VERTEX_SPV_PATH)
+    }
+
+    fn frag_spv_path(&self) -> &'a Path {
+        self.frag_spv_path
+    }
+
+    fn vertex_spv_bytes(&self) -> Option<&'static [u8]> {
+        Some(include_spv!("src/app/shaders/fullscreen.vert.spv"))
+    }
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_ranges = [vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .size(self.push_constant_size)
+            .build()];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(match self.push_constant_size {
+                0 => &[],
+                _ => &push_constant_ranges,
+            })
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    // The triangle is generated in the vertex shader, from either winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
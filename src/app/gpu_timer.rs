@@ -0,0 +1,194 @@
+use std::rc::Rc;
+
+use ash::vk;
+
+use super::{FixedVulkanStuff, PerFrame};
+use crate::{
+    error::{RenderError, RenderResult},
+    vulkan_wrappers::Device,
+};
+
+/// Weight of the newest sample in the smoothed region times
+const SMOOTHING: f64 = 0.1;
+
+/// GPU time of named regions of the graphic command buffers, measured with timestamp queries.
+///
+/// Each frame in flight owns `2 * max_regions` queries. They are read back and reset by
+/// [`GpuTimer::cmd_begin_frame`], once the fence of the frame has been waited on,
+/// so the times shown lag [`FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT`] frames behind.
+pub struct GpuTimer {
+    query_pool: vk::QueryPool,
+    max_regions: u32,
+    /// Nanoseconds per timestamp tick
+    timestamp_period: f64,
+    timestamp_mask: u64,
+    /// Regions written by each frame in flight, in the order they were begun
+    frame_regions: PerFrame<Vec<&'static str>>,
+    /// Regions begun but not ended yet, innermost last
+    open_regions: Vec<u32>,
+    /// Smoothed milliseconds per region name, in the order regions first appeared
+    region_times: Vec<(&'static str, f64)>,
+    device: Rc<Device>,
+}
+
+impl GpuTimer {
+    pub fn new(max_regions: u32, device: Rc<Device>) -> RenderResult<Self> {
+        let physical_device = *device.physical_device().upgrade().unwrap();
+        let instance = device.instance();
+        let timestamp_period = unsafe { instance.get_physical_device_properties(physical_device) }
+            .limits
+            .timestamp_period;
+        let timestamp_valid_bits =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                [device.graphic_queue_family_index() as usize]
+                .timestamp_valid_bits;
+        if timestamp_valid_bits == 0 || timestamp_period <= 0. {
+            return Err(RenderError::QueueFamilyNotSupported(
+                "the graphic queue does not support timestamps".to_string(),
+            ));
+        }
+
+        let query_pool = unsafe {
+            device.create_query_pool(
+                &vk::QueryPoolCreateInfo::builder()
+                    .query_type(vk::QueryType::TIMESTAMP)
+                    .query_count(2 * max_regions * FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                    .build(),
+                None,
+            )?
+        };
+
+        Ok(Self {
+            query_pool,
+            max_regions,
+            timestamp_period: timestamp_period as f64,
+            timestamp_mask: match timestamp_valid_bits {
+                64 => u64::MAX,
+                bits => (1 << bits) - 1,
+            },
+            frame_regions: PerFrame::new(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT, |_| vec![]),
+            open_regions: vec![],
+            region_times: vec![],
+            device,
+        })
+    }
+
+    /// Collects the regions the last submission of `frame_index` measured and resets its
+    /// queries. Record it first, outside of any render pass.
+    pub fn cmd_begin_frame(&mut self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        debug_assert!(frame_index < FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT);
+        self.collect(frame_index);
+        self.frame_regions[frame_index].clear();
+        self.open_regions.clear();
+        unsafe {
+            self.device.cmd_reset_query_pool(
+                command_buffer,
+                self.query_pool,
+                self.first_query(frame_index),
+                2 * self.max_regions,
+            );
+        }
+    }
+
+    /// Begins a region which ends at the matching [`Self::end_region`], regions may nest.
+    /// Regions beyond `max_regions` in a frame are not measured.
+    pub fn begin_region(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        name: &'static str,
+    ) {
+        let region = self.frame_regions[frame_index].len() as u32;
+        if region >= self.max_regions {
+            // Still tracked, so that the matching end is ignored as well
+            self.open_regions.push(u32::MAX);
+            return;
+        }
+        self.frame_regions[frame_index].push(name);
+        self.open_regions.push(region);
+        unsafe {
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool,
+                self.first_query(frame_index) + 2 * region,
+            );
+        }
+    }
+
+    pub fn end_region(&mut self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        let region = self
+            .open_regions
+            .pop()
+            .expect("end_region without a matching begin_region");
+        if region == u32::MAX {
+            return;
+        }
+        unsafe {
+            self.device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool,
+                self.first_query(frame_index) + 2 * region + 1,
+            );
+        }
+    }
+
+    /// Smoothed milliseconds of every region measured so far
+    pub fn region_times_ms(&self) -> &[(&'static str, f64)] {
+        &self.region_times
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.region_times
+            .iter()
+            .map(|(name, ms)| format!("gpu {name}: {ms:.3} ms"))
+            .collect()
+    }
+
+    fn first_query(&self, frame_index: usize) -> u32 {
+        2 * self.max_regions * frame_index as u32
+    }
+
+    fn collect(&mut self, frame_index: usize) {
+        let regions = &self.frame_regions[frame_index];
+        if regions.is_empty() {
+            return;
+        }
+        let mut timestamps = vec![0u64; 2 * regions.len()];
+        // Not ready when the frame was never submitted, e.g. after a swapchain refit
+        if unsafe {
+            self.device.get_query_pool_results(
+                self.query_pool,
+                self.first_query(frame_index),
+                timestamps.len() as u32,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        }
+        .is_err()
+        {
+            return;
+        }
+        for (name, timestamps) in regions.iter().zip(timestamps.chunks_exact(2)) {
+            let ticks = timestamps[1].wrapping_sub(timestamps[0]) & self.timestamp_mask;
+            let ms = ticks as f64 * self.timestamp_period / 1_000_000.;
+            match self
+                .region_times
+                .iter_mut()
+                .find(|(region_name, _)| region_name == name)
+            {
+                Some((_, smoothed)) => *smoothed += (ms - *smoothed) * SMOOTHING,
+                None => self.region_times.push((name, ms)),
+            }
+        }
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_query_pool(self.query_pool, None);
+        }
+    }
+}
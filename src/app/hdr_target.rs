@@ -0,0 +1,218 @@
+use ash::vk;
+
+use super::{ClearValue, FixedVulkanStuff, FullscreenPass};
+use crate::{
+    error::RenderResult,
+    vulkan_wrappers::{OffscreenRenderTarget, SurfaceChange},
+};
+
+const TONEMAP_FRAG_SPV_PATH: &str = "src/app/shaders/tonemap.frag.spv";
+
+/// Curve `tonemap.frag` maps the exposed radiance to [0, 1] with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TonemapOperator {
+    /// Filmic, desaturates the highlights
+    #[default]
+    Aces,
+    Reinhard,
+    /// Clips everything above 1, to compare with
+    Clamp,
+}
+
+impl TonemapOperator {
+    pub const ALL: [Self; 3] = [Self::Aces, Self::Reinhard, Self::Clamp];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Aces => "ACES",
+            Self::Reinhard => "Reinhard",
+            Self::Clamp => "Clamp",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TonemapSettings {
+    /// Scales the radiance before the curve
+    pub exposure: f32,
+    pub operator: TonemapOperator,
+}
+
+impl Default for TonemapSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.,
+            operator: TonemapOperator::default(),
+        }
+    }
+}
+
+impl TonemapSettings {
+    /// Returns `true` when a setting was changed
+    #[cfg(feature = "ui")]
+    pub fn draw(&mut self, ui: &imgui::Ui) -> bool {
+        let mut changed = false;
+        if ui.collapsing_header("Tonemapping", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            changed |= ui
+                .slider_config("Exposure", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.exposure);
+            let mut index = TonemapOperator::ALL
+                .iter()
+                .position(|operator| *operator == self.operator)
+                .unwrap();
+            if ui.combo("Operator", &mut index, &TonemapOperator::ALL, |operator| {
+                operator.name().into()
+            }) {
+                self.operator = TonemapOperator::ALL[index];
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// `R16G16B16A16_SFLOAT` color and depth target the scene is lit into, tonemapped into the
+/// SDR swapchain image by [`HdrTarget::cmd_tonemap`].
+///
+/// The depth attachment is left sampled, e.g. for [`super::DepthInspector`]. Passes between
+/// the scene and the tonemapping, such as bloom, can take [`HdrTarget::color_descriptor`] as
+/// input and hand their output to [`HdrTarget::set_tonemap_input`].
+pub struct HdrTarget {
+    target: OffscreenRenderTarget,
+    tonemap_pass: FullscreenPass,
+    /// Read by the tonemap pass instead of the HDR color, e.g. the output of a bloom pass
+    tonemap_input: Option<vk::DescriptorImageInfo>,
+    /// The swapchain format is UNORM, so `tonemap.frag` has to encode to sRGB itself
+    encode_srgb: bool,
+    pub settings: TonemapSettings,
+}
+
+impl HdrTarget {
+    pub const FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    /// Sized after the swapchain, with the depth format of `s`
+    pub fn from_fixed_vulkan_stuff(s: &FixedVulkanStuff) -> RenderResult<Self> {
+        let target = OffscreenRenderTarget::with_sampled_depth(
+            s.surface.extent(),
+            Self::FORMAT,
+            s.depth_stencil.format(),
+            s.device.clone(),
+        )?;
+        let tonemap_pass = FullscreenPass::from_fixed_vulkan_stuff(
+            s,
+            TONEMAP_FRAG_SPV_PATH,
+            std::mem::size_of::<TonemapPushConstBlock>() as u32,
+        )?;
+        tonemap_pass.set_input(target.color_descriptor());
+        Ok(Self {
+            target,
+            tonemap_pass,
+            tonemap_input: None,
+            encode_srgb: !is_srgb_format(s.surface.format()),
+            settings: TonemapSettings::default(),
+        })
+    }
+
+    /// Follows the swapchain: resizes the target, and rebuilds the tonemap pipeline against
+    /// the new render pass of `s` when its format changed. Descriptors of the target held
+    /// elsewhere, and a resized [`HdrTarget::set_tonemap_input`], have to be written again
+    /// after an extent change.
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.extent_changed {
+            self.target.resize(s.surface.extent())?;
+        }
+        if change.format_changed {
+            self.tonemap_pass = FullscreenPass::from_fixed_vulkan_stuff(
+                s,
+                TONEMAP_FRAG_SPV_PATH,
+                std::mem::size_of::<TonemapPushConstBlock>() as u32,
+            )?;
+            self.encode_srgb = !is_srgb_format(s.surface.format());
+        }
+        if change.any() {
+            self.tonemap_pass.set_input(
+                self.tonemap_input
+                    .unwrap_or_else(|| self.target.color_descriptor()),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn target(&self) -> &OffscreenRenderTarget {
+        &self.target
+    }
+
+    /// Render pass pipelines drawing the lit scene are built against
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.target.render_pass()
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.target.extent()
+    }
+
+    pub fn color_descriptor(&self) -> vk::DescriptorImageInfo {
+        self.target.color_descriptor()
+    }
+
+    pub fn depth_descriptor(&self) -> vk::DescriptorImageInfo {
+        self.target.depth_descriptor()
+    }
+
+    /// Tonemaps `input` instead of the HDR color, `None` goes back to it. Must not be called
+    /// while a frame using the tonemap pass is in flight.
+    pub fn set_tonemap_input(&mut self, input: Option<vk::DescriptorImageInfo>) {
+        self.tonemap_input = input;
+        self.tonemap_pass
+            .set_input(input.unwrap_or_else(|| self.target.color_descriptor()));
+    }
+
+    /// Begins the render pass of the target, cleared to `clear_value`, with the viewport and
+    /// scissor covering it
+    pub fn cmd_begin_render_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        clear_value: &ClearValue,
+    ) {
+        self.target
+            .cmd_begin_render_pass(command_buffer, &clear_value.to_array());
+        self.target.cmd_set_viewport_and_scissor(command_buffer);
+    }
+
+    /// Draws the tonemapped image inside the swapchain render pass begun on
+    /// `command_buffer`, before the UI overlay
+    pub fn cmd_tonemap(&self, command_buffer: vk::CommandBuffer) {
+        self.tonemap_pass.cmd_draw(
+            command_buffer,
+            bytemuck::bytes_of(&TonemapPushConstBlock {
+                exposure: self.settings.exposure,
+                operator: self.settings.operator as u32,
+                encode_srgb: self.encode_srgb as u32,
+            }),
+        );
+    }
+}
+
+fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
+
+/// Layout of the push constants of `tonemap.frag`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TonemapPushConstBlock {
+    exposure: f32,
+    operator: u32,
+    encode_srgb: u32,
+}
+
+unsafe impl bytemuck::Pod for TonemapPushConstBlock {}
+unsafe impl bytemuck::Zeroable for TonemapPushConstBlock {}
@@ -0,0 +1,245 @@
+use std::{path::Path, rc::Rc};
+
+use ash::vk;
+
+use crate::{
+    error::{RenderError, RenderResult},
+    vulkan_wrappers::{
+        load_pipeline_cache, Buffer, DepthStencil, Device, Instance, OffscreenRenderTarget,
+        QueueInfo,
+    },
+};
+
+/// Counterpart of [`super::FixedVulkanStuff`] without surface nor swapchain, for machines
+/// without a display such as CI runners. Frames are rendered into an offscreen color
+/// target one at a time and read back to the host.
+///
+/// The instance is expected to be built without a window, the device then skips
+/// `VK_KHR_swapchain`.
+pub struct HeadlessVulkanStuff {
+    pub device: Rc<Device>,
+    pub render_target: OffscreenRenderTarget,
+    pub graphic_command_pool: vk::CommandPool,
+    pub graphic_command_buffer: vk::CommandBuffer,
+    /// Not persisted between runs, unlike the one of [`super::FixedVulkanStuff`]
+    pub pipeline_cache: vk::PipelineCache,
+    fence: vk::Fence,
+    /// Texels of the last rendered frame
+    readback_buffer: Buffer<u8>,
+}
+
+impl HeadlessVulkanStuff {
+    /// Four bytes per texel, laid out as PNG expects them
+    pub const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+    pub fn new(instance: Rc<Instance>, extent: vk::Extent2D) -> RenderResult<Self> {
+        Self::with_color_format(instance, extent, Self::COLOR_FORMAT)
+    }
+
+    /// `color_format` must have four bytes per texel, e.g. a UNORM format when pixels
+    /// are compared with exact values rather than saved
+    pub fn with_color_format(
+        instance: Rc<Instance>,
+        extent: vk::Extent2D,
+        color_format: vk::Format,
+    ) -> RenderResult<Self> {
+        let queue_info = QueueInfo::headless(&instance)?;
+        let device = Rc::new(Device::new(instance, queue_info)?);
+
+        let render_target = OffscreenRenderTarget::new(
+            extent,
+            color_format,
+            DepthStencil::find_depth_format(&device)?,
+            device.clone(),
+        )?;
+        let graphic_command_pool = unsafe {
+            device.create_command_pool(
+                &vk::CommandPoolCreateInfo::builder()
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                    .queue_family_index(device.graphic_queue_family_index())
+                    .build(),
+                None,
+            )?
+        };
+        let graphic_command_buffer = unsafe {
+            device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(graphic_command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1)
+                    .build(),
+            )?[0]
+        };
+        let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
+        let pipeline_cache =
+            unsafe { device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None)? };
+        let mut readback_buffer = Buffer::<u8>::new(
+            (extent.width * extent.height * 4) as usize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            device.clone(),
+        )?;
+        readback_buffer.map_memory_all()?;
+
+        Ok(Self {
+            device,
+            render_target,
+            graphic_command_pool,
+            graphic_command_buffer,
+            pipeline_cache,
+            fence,
+            readback_buffer,
+        })
+    }
+
+    /// Builds a pipeline cache from a file written by [`crate::vulkan_wrappers::save_pipeline_cache`],
+    /// e.g. to reuse the cache of earlier windowed runs
+    pub fn load_pipeline_cache(&mut self, path: &Path) -> RenderResult<()> {
+        let pipeline_cache = load_pipeline_cache(path, &self.device)?;
+        unsafe {
+            self.device
+                .destroy_pipeline_cache(self.pipeline_cache, None);
+        }
+        self.pipeline_cache = pipeline_cache;
+        Ok(())
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.render_target.extent()
+    }
+
+    /// Records `draw` inside the render pass of the target, submits it and waits for it,
+    /// after which [`Self::pixels`] holds the frame
+    pub fn render_frame(
+        &self,
+        clear_values: &[vk::ClearValue],
+        draw: impl FnOnce(vk::CommandBuffer),
+    ) -> RenderResult<()> {
+        let command_buffer = self.graphic_command_buffer;
+        let color = self.render_target.color().unwrap();
+        let extent = self.extent();
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())?;
+            self.device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+                    .build(),
+            )?;
+
+            self.render_target
+                .cmd_begin_render_pass(command_buffer, clear_values);
+            self.render_target
+                .cmd_set_viewport_and_scissor(command_buffer);
+            draw(command_buffer);
+            self.device.cmd_end_render_pass(command_buffer);
+
+            let subresource_range = vk::ImageSubresourceRange::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .level_count(1)
+                .layer_count(1)
+                .build();
+            // The render pass leaves the color attachment ready to be sampled, its outgoing
+            // dependency ends at fragment shaders, which this barrier chains with
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_READ)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(*color.image())
+                    .subresource_range(subresource_range)
+                    .build()],
+            );
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                *color.image(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.readback_buffer.buffer(),
+                &[vk::BufferImageCopy::builder()
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::builder()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .layer_count(1)
+                            .build(),
+                    )
+                    .image_extent(vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    })
+                    .build()],
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::HOST_READ)
+                    .build()],
+                &[],
+                &[],
+            );
+            self.device.end_command_buffer(command_buffer)?;
+
+            self.device.queue_submit(
+                self.device.graphic_queue(),
+                &[vk::SubmitInfo::builder()
+                    .command_buffers(&[command_buffer])
+                    .build()],
+                self.fence,
+            )?;
+            self.device.wait_for_fences(&[self.fence], true, u64::MAX)?;
+            self.device.reset_fences(&[self.fence])?;
+        }
+        Ok(())
+    }
+
+    /// RGBA texels of the last frame, row after row
+    pub fn pixels(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self.readback_buffer.mapped_ptr().unwrap() as *const u8,
+                self.readback_buffer.size_in_bytes() as usize,
+            )
+        }
+    }
+
+    /// Texel at column `x` and row `y` of the last frame
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.extent().width + x) * 4) as usize;
+        self.pixels()[offset..offset + 4].try_into().unwrap()
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> RenderResult<()> {
+        let extent = self.extent();
+        image_loader::RgbaImage::from_raw(extent.width, extent.height, self.pixels().to_vec())
+            .ok_or_else(|| RenderError::ImageDataInvalid("Readback buffer too small".into()))?
+            .save(path)?;
+        Ok(())
+    }
+}
+
+impl Drop for HeadlessVulkanStuff {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().ok();
+            self.device.destroy_fence(self.fence, None);
+            self.device
+                .destroy_command_pool(self.graphic_command_pool, None);
+            self.device
+                .destroy_pipeline_cache(self.pipeline_cache, None);
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::Vec3;
+
+use super::ComputePipelineBuilder;
+use crate::{
+    error::RenderResult,
+    include_spv,
+    vulkan_wrappers::{Device, OneTimeCommand, SamplerSettings, Texture},
+};
+
+/// Prefiltered lighting of an environment cubemap for the split sum approximation of
+/// image based lighting, baked once in compute shaders
+pub struct IblResources {
+    /// Cosine weighted irradiance, for the diffuse term
+    pub irradiance: Texture,
+    /// GGX prefiltered radiance, mip `i` is for roughness `i / (mip_levels - 1)`
+    pub prefiltered: Texture,
+    /// Scale and bias of F0 in the red and green channels, for n.v along u and the
+    /// roughness along v
+    pub brdf_lut: Texture,
+}
+
+impl IblResources {
+    /// Storage images of this format are supported by every implementation
+    pub const FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+    pub const IRRADIANCE_SIZE: u32 = 32;
+    pub const PREFILTERED_SIZE: u32 = 128;
+    pub const BRDF_LUT_SIZE: u32 = 256;
+    /// GGX samples per texel of the prefiltered cubemap
+    pub const PREFILTER_SAMPLE_COUNT: u32 = 1024;
+
+    /// `environment` must be a cube texture in `SHADER_READ_ONLY_OPTIMAL` with an image
+    /// view and a sampler, ideally with a full mip chain which filters the bright spots
+    /// out of the rough mips. `queue` must support compute and waits for the baking.
+    pub fn generate(
+        environment: &Texture,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        let prefiltered_mip_levels = mip_levels(Self::PREFILTERED_SIZE);
+        let mut irradiance = cube_texture(Self::IRRADIANCE_SIZE, 1, device.clone())?;
+        let mut prefiltered = cube_texture(
+            Self::PREFILTERED_SIZE,
+            prefiltered_mip_levels,
+            device.clone(),
+        )?;
+        let mut brdf_lut = Texture::builder(
+            Self::BRDF_LUT_SIZE,
+            Self::BRDF_LUT_SIZE,
+            Self::FORMAT,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            device.clone(),
+        )
+        .build()?;
+
+        let mut baker = IblBaker::new(2 + prefiltered_mip_levels, device.clone())?;
+        let irradiance_pipeline = baker.pipeline(
+            include_spv!("src/app/shaders/ibl_irradiance.comp.spv"),
+            pipeline_cache,
+        )?;
+        let prefilter_pipeline = baker.pipeline(
+            include_spv!("src/app/shaders/ibl_prefilter.comp.spv"),
+            pipeline_cache,
+        )?;
+        let brdf_lut_pipeline = baker.pipeline(
+            include_spv!("src/app/shaders/ibl_brdf_lut.comp.spv"),
+            pipeline_cache,
+        )?;
+
+        let command = OneTimeCommand::new_and_begin(&device, command_pool)?;
+        let command_buffer = *command.command_buffer();
+        for texture in [&mut irradiance, &mut prefiltered, &mut brdf_lut] {
+            texture.transition_layout(
+                command_buffer,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+            );
+        }
+        let environment_descriptor = environment.descriptor_default();
+        baker.dispatch(
+            command_buffer,
+            irradiance_pipeline,
+            Some(environment_descriptor),
+            &irradiance,
+            0,
+            &[],
+        )?;
+        for mip_level in 0..prefiltered_mip_levels {
+            let push_const_block = PrefilterPushConstBlock {
+                roughness: mip_level as f32 / (prefiltered_mip_levels - 1) as f32,
+                sample_count: Self::PREFILTER_SAMPLE_COUNT,
+            };
+            baker.dispatch(
+                command_buffer,
+                prefilter_pipeline,
+                Some(environment_descriptor),
+                &prefiltered,
+                mip_level,
+                bytemuck::bytes_of(&push_const_block),
+            )?;
+        }
+        baker.dispatch(command_buffer, brdf_lut_pipeline, None, &brdf_lut, 0, &[])?;
+        for texture in [&mut irradiance, &mut prefiltered, &mut brdf_lut] {
+            texture.transition_layout(
+                command_buffer,
+                vk::ImageLayout::GENERAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+            );
+        }
+        command.end_and_submit(queue)?;
+
+        // Clamped, so the LUT does not wrap from smooth to rough at its edges
+        let sampler = Rc::new(clamped_sampler(&device)?);
+        for texture in [&mut irradiance, &mut prefiltered, &mut brdf_lut] {
+            texture.spawn_image_view()?;
+            texture.set_sampler(sampler.clone());
+        }
+
+        Ok(Self {
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        })
+    }
+
+    /// Blue sky cubemap of `size` texels per side with a full mip chain, lit by a sun
+    /// towards `sun_direction` with y up, for scenes without an environment map.
+    /// Returned in `SHADER_READ_ONLY_OPTIMAL` with an image view and a sampler.
+    pub fn procedural_sky(
+        size: u32,
+        sun_direction: Vec3,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Texture> {
+        let sky_mip_levels = mip_levels(size);
+        let mut sky = cube_texture(size, sky_mip_levels, device.clone())?;
+
+        let mut baker = IblBaker::new(sky_mip_levels, device.clone())?;
+        let sky_pipeline = baker.pipeline(
+            include_spv!("src/app/shaders/ibl_sky.comp.spv"),
+            pipeline_cache,
+        )?;
+        let sun_direction = sun_direction.normalize().extend(0.).to_array();
+
+        let command = OneTimeCommand::new_and_begin(&device, command_pool)?;
+        let command_buffer = *command.command_buffer();
+        sky.transition_layout(
+            command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+        );
+        // Each mip is evaluated on its own rather than downsampled, the sun widens
+        // with the texel size
+        for mip_level in 0..sky_mip_levels {
+            baker.dispatch(
+                command_buffer,
+                sky_pipeline,
+                None,
+                &sky,
+                mip_level,
+                bytemuck::bytes_of(&sun_direction),
+            )?;
+        }
+        sky.transition_layout(
+            command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+        command.end_and_submit(queue)?;
+
+        sky.spawn_image_view()?;
+        sky.set_sampler(Rc::new(clamped_sampler(&device)?));
+        Ok(sky)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PrefilterPushConstBlock {
+    roughness: f32,
+    sample_count: u32,
+}
+
+unsafe impl bytemuck::Pod for PrefilterPushConstBlock {}
+unsafe impl bytemuck::Zeroable for PrefilterPushConstBlock {}
+
+fn mip_levels(size: u32) -> u32 {
+    size.ilog2() + 1
+}
+
+fn cube_texture(size: u32, mip_levels: u32, device: Rc<Device>) -> RenderResult<Texture> {
+    Texture::builder(
+        size,
+        size,
+        IblResources::FORMAT,
+        vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+        device,
+    )
+    .mip_levels(mip_levels)
+    .array_layers(6)
+    .cube_compatible()
+    .build()
+}
+
+fn clamped_sampler(device: &Device) -> RenderResult<vk::Sampler> {
+    let create_info = SamplerSettings::default()
+        .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .create_info();
+    Ok(unsafe { device.create_sampler(&create_info, None)? })
+}
+
+/// Compute pipelines sampling binding 0 and storing into binding 1, with the sets and
+/// per mip storage views of one baking, which must have completed before the drop
+struct IblBaker {
+    device: Rc<Device>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    pipelines: Vec<(vk::PipelineLayout, vk::Pipeline)>,
+    storage_views: Vec<vk::ImageView>,
+}
+
+impl IblBaker {
+    /// Room for `max_dispatches` calls of [`Self::dispatch`]
+    fn new(max_dispatches: u32, device: Rc<Device>) -> RenderResult<Self> {
+        let descriptor_pool = {
+            let pool_sizes = [
+                vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(max_dispatches)
+                    .build(),
+                vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(max_dispatches)
+                    .build(),
+            ];
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&pool_sizes)
+                .max_sets(max_dispatches)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        let descriptor_set_layout = {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding::builder()
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .binding(0)
+                    .descriptor_count(1)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .binding(1)
+                    .descriptor_count(1)
+                    .build(),
+            ];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        Ok(Self {
+            device,
+            descriptor_set_layout,
+            descriptor_pool,
+            pipelines: Vec::new(),
+            storage_views: Vec::new(),
+        })
+    }
+
+    fn pipeline(
+        &mut self,
+        spv_bytes: &[u8],
+        pipeline_cache: vk::PipelineCache,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        let pipeline = ComputePipelineBuilder::with_spv_bytes(spv_bytes, self.device.clone())
+            .set_layouts(&[self.descriptor_set_layout])
+            .push_constant_ranges(&[vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .size(16)
+                .build()])
+            .pipeline_cache(pipeline_cache)
+            .build()?;
+        self.pipelines.push(pipeline);
+        Ok(pipeline)
+    }
+
+    /// Runs one invocation per texel of `mip_level` of `output`, which is in `GENERAL`.
+    /// Dispatches are not synchronized with each other.
+    fn dispatch(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        (pipeline_layout, pipeline): (vk::PipelineLayout, vk::Pipeline),
+        input: Option<vk::DescriptorImageInfo>,
+        output: &Texture,
+        mip_level: u32,
+        push_constants: &[u8],
+    ) -> RenderResult<()> {
+        let storage_view = {
+            let view_type = if output.is_cube() {
+                vk::ImageViewType::TYPE_2D_ARRAY
+            } else {
+                vk::ImageViewType::TYPE_2D
+            };
+            let create_info = vk::ImageViewCreateInfo::builder()
+                .image(*output.image())
+                .view_type(view_type)
+                .format(output.format())
+                .subresource_range(
+                    vk::ImageSubresourceRange::builder()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .base_mip_level(mip_level)
+                        .level_count(1)
+                        .base_array_layer(0)
+                        .layer_count(output.array_layers())
+                        .build(),
+                )
+                .build();
+            unsafe { self.device.create_image_view(&create_info, None)? }
+        };
+        self.storage_views.push(storage_view);
+
+        let descriptor_set = unsafe {
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(self.descriptor_pool)
+                .set_layouts(&[self.descriptor_set_layout])
+                .build();
+            self.device.allocate_descriptor_sets(&allocate_info)?[0]
+        };
+        let output_info = [vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::GENERAL)
+            .image_view(storage_view)
+            .build()];
+        let input_info = input.map(|input| [input]);
+        let mut descriptor_writes = vec![vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(1)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .image_info(&output_info)
+            .build()];
+        if let Some(input_info) = &input_info {
+            descriptor_writes.push(
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(input_info)
+                    .build(),
+            );
+        }
+
+        let extent = output.extent2d();
+        unsafe {
+            self.device.update_descriptor_sets(&descriptor_writes, &[]);
+            self.device
+                .cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline);
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            if !push_constants.is_empty() {
+                self.device.cmd_push_constants(
+                    command_buffer,
+                    pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    push_constants,
+                );
+            }
+            // Must match the local size of the compute shaders
+            self.device.cmd_dispatch(
+                command_buffer,
+                (extent.width >> mip_level).max(1).div_ceil(8),
+                (extent.height >> mip_level).max(1).div_ceil(8),
+                output.array_layers(),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for IblBaker {
+    fn drop(&mut self) {
+        unsafe {
+            for &view in &self.storage_views {
+                self.device.destroy_image_view(view, None);
+            }
+            for &(layout, pipeline) in &self.pipelines {
+                self.device.destroy_pipeline(pipeline, None);
+                self.device.destroy_pipeline_layout(layout, None);
+            }
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
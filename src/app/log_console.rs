@@ -0,0 +1,98 @@
+use imgui::{Condition, Ui};
+use log::Level;
+
+use crate::logging::{LogBuffer, LogRecord};
+
+/// Overlay window listing the records of a [`LogBuffer`], filtered by severity and
+/// a case insensitive search, see [`super::UIOverlay::enable_log_console`]
+#[derive(Clone)]
+pub struct LogConsole {
+    pub open: bool,
+    /// Shown levels, indexed from [`Level::Error`] to [`Level::Trace`]
+    pub levels: [bool; 5],
+    pub search: String,
+    /// Follows new records while the view is scrolled to the bottom
+    pub auto_scroll: bool,
+}
+
+impl Default for LogConsole {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogConsole {
+    pub fn new() -> Self {
+        Self {
+            open: true,
+            levels: [true, true, true, false, false],
+            search: String::new(),
+            auto_scroll: true,
+        }
+    }
+
+    pub fn shows(&self, record: &LogRecord) -> bool {
+        if !self.levels[record.level as usize - 1] {
+            return false;
+        }
+        let search = self.search.trim();
+        search.is_empty() || {
+            let search = search.to_lowercase();
+            record.message.to_lowercase().contains(&search)
+                || record.target.to_lowercase().contains(&search)
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui, buffer: &LogBuffer) {
+        if !self.open {
+            return;
+        }
+        let mut open = self.open;
+        ui.window("Log")
+            .opened(&mut open)
+            .size([720., 320.], Condition::FirstUseEver)
+            .build(|| {
+                for level in Level::iter() {
+                    ui.checkbox(level.as_str(), &mut self.levels[level as usize - 1]);
+                    ui.same_line();
+                }
+                ui.checkbox("Auto-scroll", &mut self.auto_scroll);
+                ui.same_line();
+                if ui.button("Clear") {
+                    buffer.clear();
+                }
+                ui.input_text("Search", &mut self.search).build();
+                ui.separator();
+
+                ui.child_window("records").build(|| {
+                    let records = buffer.records();
+                    for record in records.iter().filter(|record| self.shows(record)) {
+                        ui.text_colored(
+                            level_color(record.level),
+                            format!(
+                                "{:8.3} {:<5} {}: {}",
+                                record.time.as_secs_f32(),
+                                record.level,
+                                record.target,
+                                record.message
+                            ),
+                        );
+                    }
+                    if self.auto_scroll && ui.scroll_y() >= ui.scroll_max_y() {
+                        ui.set_scroll_here_y_with_ratio(1.);
+                    }
+                });
+            });
+        self.open = open;
+    }
+}
+
+fn level_color(level: Level) -> [f32; 4] {
+    match level {
+        Level::Error => [1., 0.4, 0.4, 1.],
+        Level::Warn => [1., 0.8, 0.3, 1.],
+        Level::Info => [1., 1., 1., 1.],
+        Level::Debug => [0.6, 0.8, 1., 1.],
+        Level::Trace => [0.6, 0.6, 0.6, 1.],
+    }
+}
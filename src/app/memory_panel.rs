@@ -0,0 +1,114 @@
+use ash::vk;
+use imgui::Ui;
+
+use crate::vulkan_wrappers::{Device, MemoryTypeUsage};
+
+const MIB: f64 = 1024. * 1024.;
+const WARNING_COLOR: [f32; 4] = [1., 0.4, 0.3, 1.];
+
+/// ImGui listing of the memory heaps and types of the physical device, with the live
+/// allocations of [`Device::allocate_tracked_memory`] per type. Memory types the host sees
+/// but the GPU reads through the bus are flagged when they back vertex, index, storage
+/// buffers or sampled images, which then cost bandwidth every frame.
+pub struct MemoryPanel {
+    heaps: Vec<vk::MemoryHeap>,
+    types: Vec<vk::MemoryType>,
+    usage: Vec<MemoryTypeUsage>,
+}
+
+impl MemoryPanel {
+    /// Snapshot of the current usage
+    pub fn new(device: &Device) -> Self {
+        let properties = device.memory_properties();
+        Self {
+            heaps: properties.memory_heaps[..properties.memory_heap_count as usize].to_vec(),
+            types: properties.memory_types[..properties.memory_type_count as usize].to_vec(),
+            usage: device.memory_type_usage(),
+        }
+    }
+
+    pub fn draw(&self, ui: &Ui) {
+        if !ui.collapsing_header("Memory", imgui::TreeNodeFlags::empty()) {
+            return;
+        }
+        for (heap_index, heap) in self.heaps.iter().enumerate() {
+            let types_in_heap = self
+                .types
+                .iter()
+                .zip(self.usage.iter())
+                .enumerate()
+                .filter(|(_, (memory_type, _))| memory_type.heap_index as usize == heap_index)
+                .collect::<Vec<_>>();
+            let used = types_in_heap
+                .iter()
+                .map(|(_, (_, usage))| usage.bytes)
+                .sum::<u64>();
+            ui.text(format!(
+                "heap {heap_index}: {:.1} / {:.0} MiB {}",
+                used as f64 / MIB,
+                heap.size as f64 / MIB,
+                if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
+                    "device local"
+                } else {
+                    "system"
+                }
+            ));
+            ui.indent();
+            types_in_heap
+                .into_iter()
+                .for_each(|(type_index, (memory_type, usage))| {
+                    ui.text(format!(
+                        "type {type_index}: {}",
+                        property_names(memory_type.property_flags)
+                    ));
+                    if usage.allocations > 0 {
+                        ui.same_line();
+                        ui.text_disabled(format!(
+                            "{} allocations, {:.1} MiB",
+                            usage.allocations,
+                            usage.bytes as f64 / MIB
+                        ));
+                    }
+                    if is_misplaced(memory_type, usage) {
+                        ui.text_colored(
+                            WARNING_COLOR,
+                            format!(
+                                "  {:.1} MiB of GPU read resources in host memory",
+                                usage.hot_bytes as f64 / MIB
+                            ),
+                        );
+                    }
+                });
+            ui.unindent();
+        }
+    }
+}
+
+fn is_misplaced(memory_type: &vk::MemoryType, usage: &MemoryTypeUsage) -> bool {
+    let flags = memory_type.property_flags;
+    usage.hot_bytes > 0
+        && flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+        && !flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+}
+
+fn property_names(flags: vk::MemoryPropertyFlags) -> String {
+    let names = [
+        (vk::MemoryPropertyFlags::DEVICE_LOCAL, "device local"),
+        (vk::MemoryPropertyFlags::HOST_VISIBLE, "host visible"),
+        (vk::MemoryPropertyFlags::HOST_COHERENT, "coherent"),
+        (vk::MemoryPropertyFlags::HOST_CACHED, "cached"),
+        (
+            vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+            "lazily allocated",
+        ),
+        (vk::MemoryPropertyFlags::PROTECTED, "protected"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .map(|(_, name)| name)
+    .collect::<Vec<_>>();
+    match names.is_empty() {
+        true => "none".to_string(),
+        false => names.join(", "),
+    }
+}
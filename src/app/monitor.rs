@@ -0,0 +1,92 @@
+use std::fmt::Display;
+
+use winit::{
+    dpi::PhysicalSize,
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window},
+};
+
+use crate::{error::RenderResult, vulkan_wrappers::Surface};
+
+/// Resolution and refresh rate of a connected display
+#[derive(Clone, Debug)]
+pub struct MonitorInfo {
+    pub name: String,
+    pub size: PhysicalSize<u32>,
+    pub refresh_rate_millihertz: Option<u32>,
+    pub scale_factor: f64,
+    /// The window is (mostly) on this monitor
+    pub is_current: bool,
+    pub is_primary: bool,
+    /// Only known for the current monitor, through the color spaces of the window surface
+    pub hdr_capable: Option<bool>,
+}
+
+impl MonitorInfo {
+    fn new(monitor: &MonitorHandle, window: &Window) -> Self {
+        Self {
+            name: monitor.name().unwrap_or_else(|| "Unknown".to_string()),
+            size: monitor.size(),
+            refresh_rate_millihertz: monitor.refresh_rate_millihertz(),
+            scale_factor: monitor.scale_factor(),
+            is_current: window.current_monitor().as_ref() == Some(monitor),
+            is_primary: window.primary_monitor().as_ref() == Some(monitor),
+            hdr_capable: None,
+        }
+    }
+
+    /// All monitors, the current one filled with HDR capability when `surface` is given
+    pub fn enumerate(window: &Window, surface: Option<&Surface>) -> RenderResult<Vec<Self>> {
+        let hdr_capable = match surface {
+            Some(surface) => Some(!surface.hdr_color_spaces()?.is_empty()),
+            None => None,
+        };
+        Ok(window
+            .available_monitors()
+            .map(|monitor| {
+                let mut info = Self::new(&monitor, window);
+                if info.is_current {
+                    info.hdr_capable = hdr_capable;
+                }
+                info
+            })
+            .collect())
+    }
+
+    pub fn current(window: &Window) -> Option<Self> {
+        window
+            .current_monitor()
+            .map(|monitor| Self::new(&monitor, window))
+    }
+
+    pub fn refresh_rate_hz(&self) -> Option<f64> {
+        self.refresh_rate_millihertz
+            .map(|millihertz| millihertz as f64 / 1000.)
+    }
+}
+
+impl Display for MonitorInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}x{}", self.name, self.size.width, self.size.height)?;
+        if let Some(refresh_rate) = self.refresh_rate_hz() {
+            write!(f, " @ {refresh_rate:.2} Hz")?;
+        }
+        match self.hdr_capable {
+            Some(true) => write!(f, ", HDR"),
+            Some(false) => write!(f, ", SDR"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Switches between windowed and borderless fullscreen on the monitor the window is on,
+/// returns whether the window is fullscreen afterwards
+pub fn toggle_fullscreen(window: &Window) -> bool {
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+        false
+    } else {
+        window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+        true
+    }
+}
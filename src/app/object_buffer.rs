@@ -0,0 +1,92 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::Mat4;
+
+use super::PerFrame;
+use crate::{
+    error::RenderResult,
+    vulkan_wrappers::{Buffer, Device},
+};
+
+/// Per-object block of `src/app/shaders/object_data.glsl`, read by the vertex shader as
+/// `objects[gl_InstanceIndex]`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObjectData {
+    pub model: Mat4,
+    /// Inverse transpose of `model`, a `mat4` to keep the std430 layout trivial
+    pub normal: Mat4,
+}
+
+unsafe impl bytemuck::Pod for ObjectData {}
+unsafe impl bytemuck::Zeroable for ObjectData {}
+
+impl ObjectData {
+    pub fn new(model: Mat4) -> Self {
+        Self {
+            model,
+            normal: model.inverse().transpose(),
+        }
+    }
+}
+
+/// Data of every object drawn in a frame in one storage buffer, instead of a descriptor set
+/// or push constants per object.
+///
+/// Each draw passes the index of its object as `firstInstance`, so that a single descriptor
+/// set bound once serves the whole frame and indirect draws can address objects as well.
+/// There is one buffer per frame in flight, the one of a frame is rewritten while the
+/// others are read. The capacity is fixed, the descriptors point to the whole buffers.
+pub struct ObjectBuffer {
+    buffers: PerFrame<Buffer<ObjectData>>,
+}
+
+impl ObjectBuffer {
+    pub fn new(capacity: usize, frame_count: usize, device: Rc<Device>) -> RenderResult<Self> {
+        let buffers = PerFrame::try_new(frame_count, |_| {
+            let mut buffer = Buffer::new(
+                capacity.max(1),
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                device.host_write_memory_properties(),
+                device.clone(),
+            )?;
+            buffer.map_memory_all()?;
+            RenderResult::Ok(buffer)
+        })?;
+        Ok(Self { buffers })
+    }
+
+    /// At `binding` of the set `object_data.glsl` is included with, read by `stages`
+    pub fn binding(binding: u32, stages: vk::ShaderStageFlags) -> vk::DescriptorSetLayoutBinding {
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(binding)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .stage_flags(stages)
+            .descriptor_count(1)
+            .build()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffers[0].element_num()
+    }
+
+    /// Of frame `frame_index`, for [`crate::vulkan_wrappers::DescriptorSetBuilder::storage_buffer`]
+    pub fn buffer(&self, frame_index: usize) -> &Buffer<ObjectData> {
+        &self.buffers[frame_index]
+    }
+
+    /// Writes `objects` from index 0 for the frame `frame_index`, before its submission
+    pub fn update(&mut self, frame_index: usize, objects: &[ObjectData]) -> RenderResult<()> {
+        assert!(
+            objects.len() <= self.capacity(),
+            "{} objects exceed the capacity of {}",
+            objects.len(),
+            self.capacity()
+        );
+        let buffer = &mut self.buffers[frame_index];
+        buffer.load_data_when_mapped(objects, 0);
+        buffer.flush_range(0, std::mem::size_of_val(objects) as vk::DeviceSize)?;
+        Ok(())
+    }
+}
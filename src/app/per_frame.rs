@@ -0,0 +1,77 @@
+use std::ops::{Deref, DerefMut};
+
+/// One `T` per frame in flight, indexed by the `frame_index` of the
+/// [`super::FixedVulkanStuff`] frame methods. Sized when created rather than by a const,
+/// see [`super::FixedVulkanStuff::frames_in_flight`] and [`super::FixedVulkanStuff::per_frame`].
+#[derive(Clone, Debug, Default)]
+pub struct PerFrame<T>(Vec<T>);
+
+impl<T> PerFrame<T> {
+    pub fn new(frame_count: usize, f: impl FnMut(usize) -> T) -> Self {
+        Self((0..frame_count).map(f).collect())
+    }
+
+    pub fn try_new<E>(frame_count: usize, f: impl FnMut(usize) -> Result<T, E>) -> Result<Self, E> {
+        (0..frame_count).map(f).collect()
+    }
+
+    /// E.g. the descriptor sets allocated for every frame at once
+    pub fn from_vec(values: Vec<T>) -> Self {
+        Self(values)
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> PerFrame<U> {
+        PerFrame(self.0.iter().map(f).collect())
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> Deref for PerFrame<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PerFrame<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+}
+
+impl<T> FromIterator<T> for PerFrame<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<T> IntoIterator for PerFrame<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PerFrame<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut PerFrame<T> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
@@ -0,0 +1,322 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::{Vec2, Vec4};
+
+use super::{FixedVulkanStuff, PipelineBuilder};
+use crate::{
+    error::RenderResult,
+    impl_embedded_spv_fns, impl_pipeline_builder_fns,
+    vulkan_wrappers::{Device, SurfaceChange},
+};
+
+/// Placement of an inset quad, `offset` and `size` are in normalized device coordinates
+#[derive(Clone, Copy)]
+pub struct InsetPushConstBlock {
+    offset: Vec2,
+    size: Vec2,
+    border_color: Vec4,
+    border_size: Vec2,
+    _padding: Vec2,
+}
+
+unsafe impl bytemuck::Pod for InsetPushConstBlock {}
+unsafe impl bytemuck::Zeroable for InsetPushConstBlock {}
+
+impl InsetPushConstBlock {
+    pub fn new(offset: Vec2, size: Vec2, border_color: Vec4, border_size: Vec2) -> Self {
+        Self {
+            offset,
+            size,
+            border_color,
+            border_size,
+            _padding: Vec2::ZERO,
+        }
+    }
+
+    /// Inset anchored to the top right corner of `viewport`, all lengths in pixels
+    pub fn top_right(
+        viewport: vk::Extent2D,
+        inset: vk::Extent2D,
+        margin: f32,
+        border_width: f32,
+        border_color: Vec4,
+    ) -> Self {
+        let viewport = Vec2::new(viewport.width as f32, viewport.height as f32);
+        let inset = Vec2::new(inset.width as f32, inset.height as f32);
+        let top_left = Vec2::new(viewport.x - margin - inset.x, margin);
+        Self::new(
+            top_left * 2. / viewport - Vec2::ONE,
+            inset * 2. / viewport,
+            border_color,
+            Vec2::splat(border_width) / inset,
+        )
+    }
+
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    pub fn border_color(&self) -> Vec4 {
+        self.border_color
+    }
+
+    pub fn border_size(&self) -> Vec2 {
+        self.border_size
+    }
+}
+
+/// Composites a sampled image as a bordered quad over the current render pass,
+/// e.g. a minimap rendered from a secondary camera
+pub struct PictureInPicture {
+    pub device: Rc<Device>,
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub descriptor_pool: vk::DescriptorPool,
+    pub descriptor_set: vk::DescriptorSet,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub pipeline: vk::Pipeline,
+}
+
+impl PictureInPicture {
+    pub fn new(
+        pipeline_cache: vk::PipelineCache,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        source: vk::DescriptorImageInfo,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let descriptor_pool = {
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .build()])
+                .max_sets(1)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        let descriptor_set_layout = {
+            let bindings = [vk::DescriptorSetLayoutBinding::builder()
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .binding(0)
+                .descriptor_count(1)
+                .build()];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        let descriptor_set = unsafe {
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&[descriptor_set_layout])
+                .build();
+            device.allocate_descriptor_sets(&allocate_info)?[0]
+        };
+
+        let (pipeline_layout, pipeline) = Self::create_pipeline(
+            descriptor_set_layout,
+            render_pass,
+            extent,
+            pipeline_cache,
+            device.clone(),
+        )?;
+
+        let picture_in_picture = Self {
+            device,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            pipeline_layout,
+            pipeline,
+        };
+        picture_in_picture.set_source(source);
+
+        Ok(picture_in_picture)
+    }
+
+    pub fn from_fixed_vulkan_stuff(
+        s: &FixedVulkanStuff,
+        source: vk::DescriptorImageInfo,
+    ) -> RenderResult<Self> {
+        Self::new(
+            s.pipeline_cache,
+            s.render_pass,
+            s.surface.extent(),
+            source,
+            s.device.clone(),
+        )
+    }
+
+    /// Rebuilds the pipeline against the new render pass of `s` when its format changed, the
+    /// source is kept. The device must be idle, as after [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.format_changed {
+            let (pipeline_layout, pipeline) = Self::create_pipeline(
+                self.descriptor_set_layout,
+                s.render_pass,
+                s.surface.extent(),
+                s.pipeline_cache,
+                self.device.clone(),
+            )?;
+            unsafe {
+                self.device.destroy_pipeline(self.pipeline, None);
+                self.device
+                    .destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
+    fn create_pipeline(
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        PipelineCreator {
+            device,
+            render_pass,
+            extent,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache,
+        }
+        .build()
+    }
+
+    /// Points the inset at another image, must not be called while a frame using it is in flight
+    pub fn set_source(&self, source: vk::DescriptorImageInfo) {
+        let image_descritptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&[source])
+            .build();
+
+        unsafe {
+            self.device
+                .update_descriptor_sets(&[image_descritptor_write], &[])
+        };
+    }
+
+    /// Viewport and scissor of `command_buffer` should cover the whole framebuffer
+    pub fn draw(&self, command_buffer: vk::CommandBuffer, inset: &InsetPushConstBlock) {
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(inset),
+            );
+            self.device.cmd_draw(command_buffer, 4, 1, 0, 0);
+        }
+    }
+}
+
+impl Drop for PictureInPicture {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "src/app/shaders/inset.vert.spv",
+        "src/app/shaders/inset.frag.spv"
+    );
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<InsetPushConstBlock>() as u32)
+            .build();
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[push_constant_range])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    fn input_assembly_state_create_info(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
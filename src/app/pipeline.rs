@@ -1,21 +1,56 @@
-use std::{path::Path, rc::Rc};
+use std::{
+    ffi::CStr,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use ash::vk;
 
+use super::DebugPush;
 use crate::{
-    error::RenderResult,
-    vulkan_wrappers::{extent_helper, Device, ShaderCreate, ShaderModule},
+    error::{RenderError, RenderResult},
+    vulkan_wrappers::{Device, ShaderCreate, ShaderModule, SpecializationConstants},
 };
 
 pub trait PipelineBuilder<'a, P: AsRef<Path>> {
     fn device(&self) -> Rc<Device>;
     fn vertex_spv_path(&self) -> P;
     fn frag_spv_path(&self) -> P;
+    /// Only baked into pipelines overriding [`Self::viewport_state_create_info`] with
+    /// static viewports. The default viewport and scissor are dynamic, so that a pipeline
+    /// serves targets of any extent and survives resizes.
     fn extent(&self) -> vk::Extent2D;
     fn render_pass(&self) -> vk::RenderPass;
     fn vertex_binding_descriptions(&self) -> &'a [vk::VertexInputBindingDescription];
     fn vertex_attribute_descriptions(&self) -> &'a [vk::VertexInputAttributeDescription];
-    fn pipeline_layout(&self) -> vk::PipelineLayout;
+
+    /// Descriptor set layouts of the default [`Self::pipeline_layout`], element `i` is
+    /// set `i` of the shaders. Sets updated at different rates follow
+    /// [`crate::vulkan_wrappers::SetIndex`], which
+    /// [`crate::vulkan_wrappers::DescriptorSetBinder`] takes to bind only the sets that changed.
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        &[]
+    }
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![]
+    }
+
+    /// Created from [`Self::set_layouts`] and [`Self::push_constant_ranges`] with room for
+    /// a [`DebugPush`], destroyed by the caller along with the pipeline
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_ranges =
+            DebugPush::merge_push_constant_ranges(self.push_constant_ranges());
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts())
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        unsafe {
+            self.device()
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .expect("Fail to create pipeline layout")
+        }
+    }
 
     fn subpass(&self) -> u32 {
         0
@@ -25,6 +60,12 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
         vk::PipelineCache::null()
     }
 
+    /// Color and depth formats of the attachments for pipelines used with dynamic rendering,
+    /// which are then built without a render pass and `render_pass` is ignored
+    fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        None
+    }
+
     fn vertex_input_state_create_info(&self) -> vk::PipelineVertexInputStateCreateInfo {
         vk::PipelineVertexInputStateCreateInfo::builder()
             .vertex_binding_descriptions(self.vertex_binding_descriptions())
@@ -32,19 +73,43 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
             .build()
     }
 
+    /// SPIR-V embedded into the binary, used instead of reading [`Self::vertex_spv_path`]
+    fn vertex_spv_bytes(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    /// SPIR-V embedded into the binary, used instead of reading [`Self::frag_spv_path`]
+    fn frag_spv_bytes(&self) -> Option<&'static [u8]> {
+        None
+    }
+
+    /// Constants the shader of `stage` is specialized with, per pipeline built
+    fn specialization_constants(
+        &self,
+        _stage: vk::ShaderStageFlags,
+    ) -> Option<SpecializationConstants> {
+        None
+    }
+
     fn shader_stage_create_infos(
         &self,
     ) -> RenderResult<(Vec<vk::PipelineShaderStageCreateInfo>, Vec<ShaderModule>)> {
+        let shader_create = |spv_bytes: Option<&[u8]>, spv_path: P, stage_flag| match spv_bytes {
+            Some(bytes) => ShaderCreate::from_bytes(bytes, stage_flag, self.device()),
+            None => {
+                ShaderCreate::with_spv_path_default_start_name(spv_path, stage_flag, self.device())
+            }
+        };
         let shader_creates = vec![
-            ShaderCreate::with_spv_path_default_start_name(
+            shader_create(
+                self.vertex_spv_bytes(),
                 self.vertex_spv_path(),
                 vk::ShaderStageFlags::VERTEX,
-                self.device(),
             )?,
-            ShaderCreate::with_spv_path_default_start_name(
+            shader_create(
+                self.frag_spv_bytes(),
                 self.frag_spv_path(),
                 vk::ShaderStageFlags::FRAGMENT,
-                self.device(),
             )?,
         ];
         let mut infos = vec![];
@@ -56,9 +121,25 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
         Ok((infos, modules))
     }
 
+    /// Leaves the vertex input to [`Device::cmd_set_vertex_input`] when drawing, so that one
+    /// pipeline draws any vertex layout and the vertex descriptions are ignored. Needs
+    /// [`Device::supports_vertex_input_dynamic_state`], see [`super::VertexFormatPipelines`].
+    fn dynamic_vertex_input(&self) -> bool {
+        false
+    }
+
     fn dynamic_state_create_info(&self) -> vk::PipelineDynamicStateCreateInfo {
+        let dynamic_states: &[vk::DynamicState] = if self.dynamic_vertex_input() {
+            &[
+                vk::DynamicState::VIEWPORT,
+                vk::DynamicState::SCISSOR,
+                vk::DynamicState::VERTEX_INPUT_EXT,
+            ]
+        } else {
+            &[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]
+        };
         vk::PipelineDynamicStateCreateInfo::builder()
-            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
+            .dynamic_states(dynamic_states)
             .build()
     }
 
@@ -69,10 +150,13 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
             .build()
     }
 
+    /// Set at record time to the extent of the target with
+    /// [`crate::app::FixedVulkanStuff::cmd_set_viewport_and_scissor_to`] or
+    /// [`crate::vulkan_wrappers::OffscreenRenderTarget::cmd_set_viewport_and_scissor`]
     fn viewport_state_create_info(&self) -> vk::PipelineViewportStateCreateInfo {
         vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(&[extent_helper::viewport_from_extent(self.extent())])
-            .scissors(&[extent_helper::scissor_from_extent(self.extent())])
+            .viewport_count(1)
+            .scissor_count(1)
             .build()
     }
 
@@ -88,6 +172,21 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
             .build()
     }
 
+    /// Clamps fragment depths to the viewport range instead of clipping primitives at the
+    /// near and far planes, so that a directional shadow map still records casters behind
+    /// the light's near plane. Overrides `depth_clamp_enable` of
+    /// [`Self::rasterization_state_create_info`], needs [`Device::supports_depth_clamp`].
+    fn depth_clamp(&self) -> bool {
+        false
+    }
+
+    /// Clips primitives at the near and far planes, or not, whatever [`Self::depth_clamp`]
+    /// is. `None` clips exactly when depth is not clamped. Needs
+    /// [`Device::supports_depth_clip_enable`].
+    fn depth_clip(&self) -> Option<bool> {
+        None
+    }
+
     fn multisample_state_create_info(&self) -> vk::PipelineMultisampleStateCreateInfo {
         vk::PipelineMultisampleStateCreateInfo::builder()
             .sample_shading_enable(false)
@@ -102,6 +201,11 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
             .build()
     }
 
+    /// One state per color attachment of the subpass, empty for depth-only subpasses
+    fn color_blend_attach_states(&self) -> Vec<vk::PipelineColorBlendAttachmentState> {
+        vec![self.color_blend_attach_state()]
+    }
+
     fn color_blend_state_create_info(
         &self,
         attach_states: &[vk::PipelineColorBlendAttachmentState],
@@ -123,24 +227,115 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
     }
 
     fn build(&self) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        self.build_with_blend_and_depth(
+            &self.color_blend_attach_states(),
+            &self.depth_stencil_state_create_info(),
+        )
+    }
+
+    /// Same pipeline with additive blending and without depth test, for the overdraw
+    /// heatmap of [`super::DebugView::Overdraw`]. Its fragment shader outputs a small constant
+    /// color in that view, which adds up with every fragment landing on a pixel.
+    fn build_overdraw(&self) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        let additive_blend_attach_state = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ONE)
+            .dst_color_blend_factor(vk::BlendFactor::ONE)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build();
+        let attach_count = self.color_blend_attach_states().len();
+        self.build_with_blend_and_depth(
+            &vec![additive_blend_attach_state; attach_count],
+            &vk::PipelineDepthStencilStateCreateInfo::builder()
+                .depth_test_enable(false)
+                .depth_write_enable(false)
+                .build(),
+        )
+    }
+
+    /// [`Self::build`] with the blend and depth states replaced
+    fn build_with_blend_and_depth(
+        &self,
+        color_blend_attach_states: &[vk::PipelineColorBlendAttachmentState],
+        depth_stencil_state: &vk::PipelineDepthStencilStateCreateInfo,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        let mut rasterization_state = self.rasterization_state_create_info();
+        if self.depth_clamp() {
+            rasterization_state.depth_clamp_enable = vk::TRUE;
+        }
+        if rasterization_state.depth_clamp_enable == vk::TRUE
+            && !self.device().supports_depth_clamp()
+        {
+            return Err(RenderError::PhysicalDeviceNotSupported(
+                "Depth clamp feature is not enabled".to_string(),
+            ));
+        }
+        let mut depth_clip_state = vk::PipelineRasterizationDepthClipStateCreateInfoEXT::builder()
+            .depth_clip_enable(self.depth_clip().unwrap_or_default())
+            .build();
+        if self.depth_clip().is_some() {
+            if !self.device().supports_depth_clip_enable() {
+                return Err(RenderError::PhysicalDeviceNotSupported(
+                    "VK_EXT_depth_clip_enable is not supported".to_string(),
+                ));
+            }
+            depth_clip_state.p_next = rasterization_state.p_next;
+            rasterization_state.p_next = &depth_clip_state as *const _ as *const std::ffi::c_void;
+        }
+
         let layout = self.pipeline_layout();
-        let (shader_infos, _shader_modules) = self.shader_stage_create_infos()?;
-        let color_blend_attach_state = self.color_blend_attach_state();
+        let (mut shader_infos, _shader_modules) = self.shader_stage_create_infos()?;
+        // Outlive the pipeline creation, the stage infos point into them
+        let specializations = shader_infos
+            .iter()
+            .map(|info| self.specialization_constants(info.stage))
+            .collect::<Vec<_>>();
+        let specialization_infos = specializations
+            .iter()
+            .map(|constants| constants.as_ref().map(SpecializationConstants::info))
+            .collect::<Vec<_>>();
+        for (shader_info, specialization_info) in shader_infos.iter_mut().zip(&specialization_infos)
+        {
+            if let Some(specialization_info) = specialization_info {
+                shader_info.p_specialization_info = specialization_info;
+            }
+        }
+        let dynamic_rendering_formats = self.dynamic_rendering_formats();
+        let dynamic_rendering = dynamic_rendering_formats.is_some();
+        let render_pass = match dynamic_rendering {
+            true => vk::RenderPass::null(),
+            false => self.render_pass(),
+        };
+        let (color_formats, depth_format) =
+            dynamic_rendering_formats.unwrap_or((vec![], vk::Format::UNDEFINED));
+        let rendering_create_info = vk::PipelineRenderingCreateInfo::builder()
+            .color_attachment_formats(&color_formats)
+            .depth_attachment_format(depth_format)
+            .build();
 
-        let create_info = vk::GraphicsPipelineCreateInfo::builder()
+        let mut create_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_infos)
             .vertex_input_state(&self.vertex_input_state_create_info())
             .input_assembly_state(&self.input_assembly_state_create_info())
             .viewport_state(&self.viewport_state_create_info())
-            .rasterization_state(&self.rasterization_state_create_info())
+            .rasterization_state(&rasterization_state)
             .multisample_state(&self.multisample_state_create_info())
-            .color_blend_state(&self.color_blend_state_create_info(&[color_blend_attach_state]))
+            .color_blend_state(&self.color_blend_state_create_info(color_blend_attach_states))
             .dynamic_state(&self.dynamic_state_create_info())
             .layout(layout)
-            .render_pass(self.render_pass())
+            .render_pass(render_pass)
             .subpass(self.subpass())
-            .depth_stencil_state(&self.depth_stencil_state_create_info())
+            .depth_stencil_state(depth_stencil_state)
             .build();
+        // Only chained when rendering dynamically, the struct belongs to an extension the
+        // device may not have enabled otherwise
+        if dynamic_rendering {
+            create_info.p_next = &rendering_create_info as *const _ as *const std::ffi::c_void;
+        }
 
         let pipeline = unsafe {
             self.device()
@@ -152,6 +347,151 @@ pub trait PipelineBuilder<'a, P: AsRef<Path>> {
     }
 }
 
+enum ComputeShaderSource<'a> {
+    SpvPath(PathBuf),
+    SpvBinary(Vec<u32>),
+    EmbeddedSpv(&'a [u8]),
+    #[cfg(feature = "shaderc")]
+    GlslPath(PathBuf),
+}
+
+/// Builds a compute pipeline and its layout, both owned by the caller afterwards
+pub struct ComputePipelineBuilder<'a> {
+    shader: ComputeShaderSource<'a>,
+    start_name: &'a CStr,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    push_constant_ranges: &'a [vk::PushConstantRange],
+    specialization_constants: Option<&'a SpecializationConstants>,
+    pipeline_cache: vk::PipelineCache,
+    device: Rc<Device>,
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    pub fn with_spv_path<P: AsRef<Path>>(spv_path: P, device: Rc<Device>) -> Self {
+        Self::new(
+            ComputeShaderSource::SpvPath(spv_path.as_ref().to_path_buf()),
+            device,
+        )
+    }
+
+    pub fn with_spv_binary(spv_binary: Vec<u32>, device: Rc<Device>) -> Self {
+        Self::new(ComputeShaderSource::SpvBinary(spv_binary), device)
+    }
+
+    /// Compiles the GLSL source at [`Self::build`] instead of loading SPIR-V from build.rs
+    #[cfg(feature = "shaderc")]
+    pub fn with_glsl_path<P: AsRef<Path>>(glsl_path: P, device: Rc<Device>) -> Self {
+        Self::new(
+            ComputeShaderSource::GlslPath(glsl_path.as_ref().to_path_buf()),
+            device,
+        )
+    }
+
+    /// Decodes `spv_bytes`, e.g. from [`crate::include_spv!`], at [`Self::build`]
+    pub fn with_spv_bytes(spv_bytes: &'a [u8], device: Rc<Device>) -> Self {
+        Self::new(ComputeShaderSource::EmbeddedSpv(spv_bytes), device)
+    }
+
+    fn new(shader: ComputeShaderSource<'a>, device: Rc<Device>) -> Self {
+        Self {
+            shader,
+            start_name: ShaderCreate::DEFAULT_SHADER_START_NAME,
+            set_layouts: &[],
+            push_constant_ranges: &[],
+            specialization_constants: None,
+            pipeline_cache: vk::PipelineCache::null(),
+            device,
+        }
+    }
+
+    pub fn start_name(mut self, start_name: &'a CStr) -> Self {
+        self.start_name = start_name;
+        self
+    }
+
+    pub fn set_layouts(mut self, set_layouts: &'a [vk::DescriptorSetLayout]) -> Self {
+        self.set_layouts = set_layouts;
+        self
+    }
+
+    pub fn push_constant_ranges(mut self, ranges: &'a [vk::PushConstantRange]) -> Self {
+        self.push_constant_ranges = ranges;
+        self
+    }
+
+    pub fn specialization_constants(mut self, constants: &'a SpecializationConstants) -> Self {
+        self.specialization_constants = Some(constants);
+        self
+    }
+
+    pub fn pipeline_cache(mut self, pipeline_cache: vk::PipelineCache) -> Self {
+        self.pipeline_cache = pipeline_cache;
+        self
+    }
+
+    pub fn build(self) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        let shader_create = match self.shader {
+            ComputeShaderSource::SpvPath(path) => ShaderCreate::with_spv_path(
+                path,
+                vk::ShaderStageFlags::COMPUTE,
+                self.start_name,
+                self.device.clone(),
+            )?,
+            ComputeShaderSource::SpvBinary(binary) => ShaderCreate::new(
+                binary,
+                vk::ShaderStageFlags::COMPUTE,
+                self.start_name,
+                self.device.clone(),
+            )?,
+            ComputeShaderSource::EmbeddedSpv(bytes) => ShaderCreate::new(
+                ash::util::read_spv(&mut std::io::Cursor::new(bytes))?,
+                vk::ShaderStageFlags::COMPUTE,
+                self.start_name,
+                self.device.clone(),
+            )?,
+            #[cfg(feature = "shaderc")]
+            ComputeShaderSource::GlslPath(path) => ShaderCreate::from_glsl_path(
+                path,
+                vk::ShaderStageFlags::COMPUTE,
+                self.start_name,
+                self.device.clone(),
+            )?,
+        };
+
+        let layout = unsafe {
+            self.device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(self.set_layouts)
+                    .push_constant_ranges(self.push_constant_ranges)
+                    .build(),
+                None,
+            )?
+        };
+
+        let specialization_info = self
+            .specialization_constants
+            .map(SpecializationConstants::info);
+        let mut stage_create_info = shader_create.stage_create_info;
+        if let Some(specialization_info) = &specialization_info {
+            stage_create_info.p_specialization_info = specialization_info;
+        }
+        let create_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage_create_info)
+            .layout(layout)
+            .build();
+        let pipeline = unsafe {
+            self.device
+                .create_compute_pipelines(self.pipeline_cache, &[create_info], None)
+                .map_err(|e| {
+                    self.device.destroy_pipeline_layout(layout, None);
+                    e.1
+                })?[0]
+        };
+
+        Ok((layout, pipeline))
+    }
+}
+
 #[macro_export]
 macro_rules! impl_pipeline_builder_fns {
     () => {
@@ -182,3 +522,26 @@ macro_rules! impl_pipeline_builder_fns {
 }
 
 pub use impl_pipeline_builder_fns;
+
+/// Implements the spv path and bytes fns of [`PipelineBuilder`] for shaders embedded with
+/// [`crate::include_spv!`], the paths are relative to the manifest directory
+#[macro_export]
+macro_rules! impl_embedded_spv_fns {
+    ($vertex_spv_path: literal, $frag_spv_path: literal) => {
+        fn vertex_spv_path(&self) -> &'a str {
+            $vertex_spv_path
+        }
+
+        fn frag_spv_path(&self) -> &'a str {
+            $frag_spv_path
+        }
+
+        fn vertex_spv_bytes(&self) -> Option<&'static [u8]> {
+            Some($crate::include_spv!($vertex_spv_path))
+        }
+
+        fn frag_spv_bytes(&self) -> Option<&'static [u8]> {
+            Some($crate::include_spv!($frag_spv_path))
+        }
+    };
+}
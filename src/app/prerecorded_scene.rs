@@ -0,0 +1,173 @@
+use std::rc::Rc;
+
+use ash::{prelude::VkResult, vk};
+
+use super::{
+    secondary_commands::allocate_secondary_command_buffers, FixedVulkanStuff, PerFrame,
+    SecondaryInheritance,
+};
+use crate::vulkan_wrappers::Device;
+
+/// Scene commands recorded once per swapchain image into secondary command buffers
+/// and only recorded again after [`PrerecordedScene::mark_dirty`], plus one secondary
+/// command buffer per frame in flight for the UI overlay, which is recorded every frame.
+///
+/// Each frame the primary command buffer begins the render pass with
+/// `SECONDARY_COMMAND_BUFFERS` contents and calls [`PrerecordedScene::cmd_execute`].
+pub struct PrerecordedScene {
+    command_pool: vk::CommandPool,
+    scene_command_buffers: Vec<vk::CommandBuffer>,
+    recorded: Vec<bool>,
+    overlay_command_buffers: PerFrame<vk::CommandBuffer>,
+    device: Rc<Device>,
+}
+
+impl PrerecordedScene {
+    pub fn new(swapchain_image_count: usize, device: Rc<Device>) -> VkResult<Self> {
+        let command_pool = unsafe {
+            device.create_command_pool(
+                &vk::CommandPoolCreateInfo::builder()
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                    .queue_family_index(device.graphic_queue_family_index())
+                    .build(),
+                None,
+            )?
+        };
+        let scene_command_buffers =
+            allocate_secondary_command_buffers(command_pool, swapchain_image_count, &device)?;
+        let overlay_command_buffers = PerFrame::from_vec(allocate_secondary_command_buffers(
+            command_pool,
+            FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT,
+            &device,
+        )?);
+        Ok(Self {
+            command_pool,
+            scene_command_buffers,
+            recorded: vec![false; swapchain_image_count],
+            overlay_command_buffers,
+            device,
+        })
+    }
+
+    /// Reallocates the scene command buffers for a recreated swapchain,
+    /// the device must be idle as after [`FixedVulkanStuff::refit_window`]
+    pub fn refit(&mut self, swapchain_image_count: usize) -> VkResult<()> {
+        unsafe {
+            self.device
+                .free_command_buffers(self.command_pool, &self.scene_command_buffers);
+        }
+        self.scene_command_buffers = allocate_secondary_command_buffers(
+            self.command_pool,
+            swapchain_image_count,
+            &self.device,
+        )?;
+        self.recorded = vec![false; swapchain_image_count];
+        Ok(())
+    }
+
+    /// Waits until no recorded scene is pending, so the data its commands read can be
+    /// changed right after, then has every swapchain image record its scene again.
+    /// Call it when the scene changes, e.g. a pipeline is toggled or a uniform is updated.
+    pub fn mark_dirty(&mut self) -> VkResult<()> {
+        unsafe { self.device.queue_wait_idle(self.device.graphic_queue())? };
+        self.recorded.fill(false);
+        Ok(())
+    }
+
+    pub fn is_recorded(&self, image_index: usize) -> bool {
+        self.recorded[image_index]
+    }
+
+    /// Records the scene of `image_index` with `record` unless it is already recorded,
+    /// viewport and scissor are set to `extent` beforehand. Returns whether it recorded.
+    pub fn record_scene_if_dirty<F: FnOnce(vk::CommandBuffer)>(
+        &mut self,
+        image_index: usize,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        record: F,
+    ) -> VkResult<bool> {
+        if self.recorded[image_index] {
+            return Ok(false);
+        }
+        // Frames in flight may execute the scene of the same image at once
+        self.record_secondary(
+            self.scene_command_buffers[image_index],
+            vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
+            render_pass,
+            framebuffer,
+            extent,
+            record,
+        )?;
+        self.recorded[image_index] = true;
+        Ok(true)
+    }
+
+    /// Records the overlay of `frame_index`, whose previous submission must have finished
+    pub fn record_overlay<F: FnOnce(vk::CommandBuffer)>(
+        &self,
+        frame_index: usize,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        record: F,
+    ) -> VkResult<()> {
+        debug_assert!(frame_index < FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT);
+        self.record_secondary(
+            self.overlay_command_buffers[frame_index],
+            vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            render_pass,
+            framebuffer,
+            extent,
+            record,
+        )
+    }
+
+    /// Executes the scene of `image_index` and then the overlay of `frame_index`
+    pub fn cmd_execute(
+        &self,
+        primary_command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        image_index: usize,
+    ) {
+        debug_assert!(self.recorded[image_index]);
+        unsafe {
+            self.device.cmd_execute_commands(
+                primary_command_buffer,
+                &[
+                    self.scene_command_buffers[image_index],
+                    self.overlay_command_buffers[frame_index],
+                ],
+            );
+        }
+    }
+
+    fn record_secondary<F: FnOnce(vk::CommandBuffer)>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        usage: vk::CommandBufferUsageFlags,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        record: F,
+    ) -> VkResult<()> {
+        SecondaryInheritance {
+            render_pass,
+            subpass: 0,
+            framebuffer,
+            extent,
+        }
+        .begin(&self.device, command_buffer, usage)?;
+        record(command_buffer);
+        unsafe { self.device.end_command_buffer(command_buffer) }
+    }
+}
+
+impl Drop for PrerecordedScene {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::{Mat3, Mat4, Vec3};
+
+use super::{FixedVulkanStuff, PipelineBuilder};
+use crate::{
+    camera::Camera,
+    error::RenderResult,
+    impl_embedded_spv_fns, impl_pipeline_builder_fns,
+    vulkan_wrappers::{extent_helper, Device, SurfaceChange},
+};
+
+/// What [`ReferenceOverlay`] draws, switched per example
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferenceSettings {
+    pub show_grid: bool,
+    pub show_axes: bool,
+    /// Of the grid plane
+    pub height: f32,
+    /// World units between minor grid lines, every tenth line is a major one
+    pub cell_size: f32,
+    /// Distance from the camera at which the grid has faded out
+    pub fade_distance: f32,
+    /// Side of the axis triad, in pixels
+    pub axes_size: f32,
+}
+
+impl Default for ReferenceSettings {
+    fn default() -> Self {
+        Self {
+            show_grid: true,
+            show_axes: true,
+            height: 0.,
+            cell_size: 1.,
+            fade_distance: 50.,
+            axes_size: 96.,
+        }
+    }
+}
+
+impl ReferenceSettings {
+    /// Returns `true` when a setting was changed
+    #[cfg(feature = "ui")]
+    pub fn draw(&mut self, ui: &imgui::Ui) -> bool {
+        let mut changed = false;
+        if ui.collapsing_header("Reference overlay", imgui::TreeNodeFlags::empty()) {
+            changed |= ui.checkbox("Ground grid", &mut self.show_grid);
+            changed |= ui.checkbox("Axis triad", &mut self.show_axes);
+            changed |= ui.input_float("Grid height", &mut self.height).build();
+            changed |= ui
+                .slider_config("Cell size", 0.01, 100.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.cell_size);
+            changed |= ui
+                .slider_config("Fade distance", 1., 1000.)
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut self.fade_distance);
+            changed |= ui.slider("Triad size", 32., 256., &mut self.axes_size);
+        }
+        changed
+    }
+}
+
+/// Spatial orientation for scenes under development, even empty ones: an infinite grid on
+/// the y = [`ReferenceSettings::height`] plane, fading with the distance, and an axis triad
+/// in the bottom left corner.
+///
+/// Drawn after the opaque scene in a pass with a depth attachment, which the grid is
+/// tested against without writing it.
+pub struct ReferenceOverlay {
+    pub settings: ReferenceSettings,
+    device: Rc<Device>,
+    grid_pipeline_layout: vk::PipelineLayout,
+    grid_pipeline: vk::Pipeline,
+    axes_pipeline_layout: vk::PipelineLayout,
+    axes_pipeline: vk::Pipeline,
+}
+
+impl ReferenceOverlay {
+    /// Draws into subpass 0 of `render_pass`
+    pub fn new(
+        render_pass: vk::RenderPass,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let (grid_pipeline_layout, grid_pipeline) = GridPipelineCreator {
+            device: device.clone(),
+            extent: vk::Extent2D::default(),
+            render_pass,
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache,
+        }
+        .build()?;
+        let (axes_pipeline_layout, axes_pipeline) = AxesPipelineCreator {
+            device: device.clone(),
+            extent: vk::Extent2D::default(),
+            render_pass,
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache,
+        }
+        .build()?;
+        Ok(Self {
+            settings: ReferenceSettings::default(),
+            device,
+            grid_pipeline_layout,
+            grid_pipeline,
+            axes_pipeline_layout,
+            axes_pipeline,
+        })
+    }
+
+    /// Draws into the swapchain render pass
+    pub fn from_fixed_vulkan_stuff(s: &FixedVulkanStuff) -> RenderResult<Self> {
+        Self::new(s.render_pass, s.pipeline_cache, s.device.clone())
+    }
+
+    /// Rebuilt against the new render pass of `s` when its format changed, keeping the
+    /// settings. The device must be idle, as after [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.format_changed {
+            let settings = self.settings;
+            *self = Self::from_fixed_vulkan_stuff(s)?;
+            self.settings = settings;
+        }
+        Ok(())
+    }
+
+    /// Draws what the settings show, in a pass whose target covers `extent`. The viewport
+    /// covers it again afterwards.
+    pub fn cmd_draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        camera: &Camera,
+        extent: vk::Extent2D,
+    ) {
+        if self.settings.show_grid {
+            let push = GridPush {
+                view_projection: camera.perspective_mat() * camera.view_mat(),
+                height: self.settings.height,
+                cell_size: self.settings.cell_size,
+                fade_distance: self.settings.fade_distance,
+                _padding: 0.,
+            };
+            unsafe {
+                self.device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.grid_pipeline,
+                );
+                self.device.cmd_push_constants(
+                    command_buffer,
+                    self.grid_pipeline_layout,
+                    GridPush::STAGES,
+                    0,
+                    bytemuck::bytes_of(&push),
+                );
+                self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            }
+        }
+
+        if self.settings.show_axes {
+            // The rotation of the view only, seen from a fixed distance through a square
+            // viewport so that the axes keep their length
+            let rotation = Mat4::from_mat3(Mat3::from_mat4(camera.view_mat()));
+            let mvp = Mat4::perspective_rh(0.6, 1., 0.1, 10.)
+                * Mat4::from_translation(Vec3::new(0., 0., -3.5))
+                * rotation;
+            let size = self
+                .settings
+                .axes_size
+                .min(extent.width.min(extent.height) as f32);
+            let viewport = vk::Viewport::builder()
+                .x(0.)
+                .y(extent.height as f32 - size)
+                .width(size)
+                .height(size)
+                .min_depth(0.)
+                .max_depth(1.)
+                .build();
+            unsafe {
+                self.device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.axes_pipeline,
+                );
+                self.device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+                self.device.cmd_push_constants(
+                    command_buffer,
+                    self.axes_pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytemuck::cast_slice(&mvp.to_cols_array()),
+                );
+                self.device.cmd_draw(command_buffer, 6, 1, 0, 0);
+                self.device.cmd_set_viewport(
+                    command_buffer,
+                    0,
+                    &[extent_helper::viewport_from_extent(extent)],
+                );
+            }
+        }
+    }
+}
+
+impl Drop for ReferenceOverlay {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.grid_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.grid_pipeline_layout, None);
+            self.device.destroy_pipeline(self.axes_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.axes_pipeline_layout, None);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GridPush {
+    view_projection: Mat4,
+    height: f32,
+    cell_size: f32,
+    fade_distance: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Pod for GridPush {}
+unsafe impl bytemuck::Zeroable for GridPush {}
+
+impl GridPush {
+    const STAGES: vk::ShaderStageFlags = vk::ShaderStageFlags::from_raw(
+        vk::ShaderStageFlags::VERTEX.as_raw() | vk::ShaderStageFlags::FRAGMENT.as_raw(),
+    );
+}
+
+struct GridPipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for GridPipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "src/app/shaders/grid.vert.spv",
+        "src/app/shaders/grid.frag.spv"
+    );
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![vk::PushConstantRange::builder()
+            .stage_flags(GridPush::STAGES)
+            .size(std::mem::size_of::<GridPush>() as u32)
+            .build()]
+    }
+
+    // The triangle is generated in the vertex shader, from either winding
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+
+    fn color_blend_attach_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build()
+    }
+
+    /// Hidden by the scene, but not hiding what is drawn after it
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
+
+struct AxesPipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for AxesPipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "src/app/shaders/axes.vert.spv",
+        "src/app/shaders/vertex_color.frag.spv"
+    );
+
+    fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        vec![vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .size(std::mem::size_of::<Mat4>() as u32)
+            .build()]
+    }
+
+    fn input_assembly_state_create_info(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::LINE_LIST)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    /// Always on top of the scene
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
@@ -0,0 +1,102 @@
+use ash::vk;
+use imgui::Ui;
+
+use crate::vulkan_wrappers::SamplerSettings;
+
+const FILTERS: [vk::Filter; 2] = [vk::Filter::NEAREST, vk::Filter::LINEAR];
+const FILTER_NAMES: [&str; 2] = ["Nearest", "Linear"];
+
+const MIPMAP_MODES: [vk::SamplerMipmapMode; 2] = [
+    vk::SamplerMipmapMode::NEAREST,
+    vk::SamplerMipmapMode::LINEAR,
+];
+
+const ADDRESS_MODES: [vk::SamplerAddressMode; 4] = [
+    vk::SamplerAddressMode::REPEAT,
+    vk::SamplerAddressMode::MIRRORED_REPEAT,
+    vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    vk::SamplerAddressMode::CLAMP_TO_BORDER,
+];
+const ADDRESS_MODE_NAMES: [&str; 4] = [
+    "Repeat",
+    "Mirrored repeat",
+    "Clamp to edge",
+    "Clamp to border",
+];
+
+/// ImGui widgets editing [`SamplerSettings`], the new sampler is looked up
+/// from a [`crate::vulkan_wrappers::SamplerCache`] by the caller when they changed
+#[derive(Clone, Copy)]
+pub struct SamplerPanel {
+    pub settings: SamplerSettings,
+    max_anisotropy_limit: f32,
+}
+
+impl SamplerPanel {
+    pub fn new(settings: SamplerSettings, max_anisotropy_limit: f32) -> Self {
+        Self {
+            settings,
+            max_anisotropy_limit,
+        }
+    }
+
+    /// Returns `true` when any setting was changed by the user
+    pub fn draw(&mut self, ui: &Ui) -> bool {
+        let mut changed = false;
+
+        if ui.collapsing_header("Sampler", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            changed |= combo(
+                ui,
+                "Mag filter",
+                &mut self.settings.mag_filter,
+                &FILTERS,
+                &FILTER_NAMES,
+            );
+            changed |= combo(
+                ui,
+                "Min filter",
+                &mut self.settings.min_filter,
+                &FILTERS,
+                &FILTER_NAMES,
+            );
+            changed |= combo(
+                ui,
+                "Mipmap mode",
+                &mut self.settings.mipmap_mode,
+                &MIPMAP_MODES,
+                &FILTER_NAMES,
+            );
+            for (label, mode) in ["Wrap U", "Wrap V", "Wrap W"]
+                .into_iter()
+                .zip(self.settings.address_modes.iter_mut())
+            {
+                changed |= combo(ui, label, mode, &ADDRESS_MODES, &ADDRESS_MODE_NAMES);
+            }
+            changed |= ui.slider(
+                "Anisotropy",
+                1.,
+                self.max_anisotropy_limit.max(1.),
+                &mut self.settings.max_anisotropy,
+            );
+            changed |= ui.slider("LOD bias", -4., 4., &mut self.settings.mip_lod_bias);
+        }
+
+        changed
+    }
+}
+
+fn combo<T: Copy + PartialEq>(
+    ui: &Ui,
+    label: &str,
+    value: &mut T,
+    values: &[T],
+    names: &[&str],
+) -> bool {
+    let mut index = values.iter().position(|v| v == value).unwrap_or(0);
+    if ui.combo_simple_string(label, &mut index, names) {
+        *value = values[index];
+        true
+    } else {
+        false
+    }
+}
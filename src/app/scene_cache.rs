@@ -0,0 +1,338 @@
+use std::rc::Rc;
+
+use ash::{prelude::VkResult, vk};
+
+use crate::{
+    error::RenderResult,
+    vulkan_wrappers::{extent_helper, Device, RenderPassBuilder, Texture},
+};
+
+/// Copy of the last rendered scene, so frames where only the UI changed re-present it
+/// and redraw just the overlay instead of the whole scene.
+///
+/// A full frame ends the main render pass, calls [`SceneCache::cmd_store`] and draws the UI in
+/// the overlay render pass; a UI-only frame calls [`SceneCache::cmd_restore`] instead of the main
+/// render pass. Either way the overlay pass of [`SceneCache::cmd_begin_overlay_renderpass`] leaves
+/// the swapchain image ready to present.
+pub struct SceneCache {
+    image: Texture,
+    valid: bool,
+    /// Layout each swapchain image is in at the current point of command recording
+    swapchain_image_layouts: Vec<vk::ImageLayout>,
+    overlay_render_pass: vk::RenderPass,
+    device: Rc<Device>,
+}
+
+impl SceneCache {
+    /// The overlay render pass is compatible with the main render pass of
+    /// [`super::FixedVulkanStuff`], so framebuffers and UI pipelines work with both
+    pub fn new(
+        extent: vk::Extent2D,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+        swapchain_image_count: usize,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Ok(Self {
+            image: create_cache_image(extent, color_format, device.clone())?,
+            valid: false,
+            swapchain_image_layouts: vec![vk::ImageLayout::UNDEFINED; swapchain_image_count],
+            overlay_render_pass: create_overlay_renderpass(color_format, depth_format, &device)?,
+            device,
+        })
+    }
+
+    /// Drops the cached scene, e.g. after the swapchain was recreated with a new extent
+    pub fn refit(
+        &mut self,
+        extent: vk::Extent2D,
+        swapchain_image_count: usize,
+    ) -> RenderResult<()> {
+        self.image = create_cache_image(extent, self.image.format(), self.device.clone())?;
+        self.swapchain_image_layouts = vec![vk::ImageLayout::UNDEFINED; swapchain_image_count];
+        self.valid = false;
+        Ok(())
+    }
+
+    /// Whether a scene has been stored since the last [`SceneCache::invalidate`]
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Forces the next frame to render the scene, for changes the app can not tell otherwise
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    pub fn overlay_render_pass(&self) -> vk::RenderPass {
+        self.overlay_render_pass
+    }
+
+    /// Copies `swapchain_image` right after the main render pass, which leaves it in
+    /// `PRESENT_SRC_KHR`, then makes it a color attachment again for the overlay pass
+    pub fn cmd_store(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        swapchain_image: vk::Image,
+    ) {
+        self.swapchain_image_layouts[image_index] = vk::ImageLayout::PRESENT_SRC_KHR;
+        self.transition_swapchain_image(
+            command_buffer,
+            image_index,
+            swapchain_image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+        self.image.transition_layout(
+            command_buffer,
+            self.image.layout(),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+        );
+        self.cmd_copy(command_buffer, swapchain_image, *self.image.image());
+        self.image.transition_layout(
+            command_buffer,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+        );
+        self.transition_swapchain_image(
+            command_buffer,
+            image_index,
+            swapchain_image,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+        self.valid = true;
+    }
+
+    /// Copies the stored scene into a freshly acquired `swapchain_image`,
+    /// left as color attachment for the overlay pass. Needs [`SceneCache::is_valid`].
+    pub fn cmd_restore(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        swapchain_image: vk::Image,
+    ) {
+        assert!(self.valid);
+        // Whatever the image held is overwritten
+        self.swapchain_image_layouts[image_index] = vk::ImageLayout::UNDEFINED;
+        self.transition_swapchain_image(
+            command_buffer,
+            image_index,
+            swapchain_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+        self.cmd_copy(command_buffer, *self.image.image(), swapchain_image);
+        self.transition_swapchain_image(
+            command_buffer,
+            image_index,
+            swapchain_image,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        );
+    }
+
+    /// Begins the overlay render pass on `framebuffer`, which loads the color attachment
+    /// and leaves it in `PRESENT_SRC_KHR` at the end of the pass
+    pub fn cmd_begin_overlay_renderpass(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+    ) {
+        debug_assert_eq!(
+            self.swapchain_image_layouts[image_index],
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &vk::RenderPassBeginInfo::builder()
+                    .render_pass(self.overlay_render_pass)
+                    .framebuffer(framebuffer)
+                    .render_area(extent_helper::scissor_from_extent(extent))
+                    .build(),
+                vk::SubpassContents::INLINE,
+            );
+        }
+        self.swapchain_image_layouts[image_index] = vk::ImageLayout::PRESENT_SRC_KHR;
+    }
+
+    /// Moves a swapchain image from its tracked layout to `new_layout`, the source stage of
+    /// the first transition of a frame chains to the wait on the image available semaphore
+    fn transition_swapchain_image(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+        image: vk::Image,
+        new_layout: vk::ImageLayout,
+    ) {
+        let old_layout = self.swapchain_image_layouts[image_index];
+        let (src_stage_mask, src_access_mask) = match old_layout {
+            vk::ImageLayout::UNDEFINED => (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::NONE,
+            ),
+            vk::ImageLayout::PRESENT_SRC_KHR => (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ),
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+            ),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            _ => unreachable!("untracked swapchain layout {old_layout:?}"),
+        };
+        let (dst_stage_mask, dst_access_mask) = match new_layout {
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+            ),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ),
+            _ => unreachable!("untracked swapchain layout {new_layout:?}"),
+        };
+
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(color_subresource_range())
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask)
+            .build();
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage_mask,
+                dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+        self.swapchain_image_layouts[image_index] = new_layout;
+    }
+
+    fn cmd_copy(&self, command_buffer: vk::CommandBuffer, src: vk::Image, dst: vk::Image) {
+        let extent = self.image.extent2d();
+        let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        unsafe {
+            self.device.cmd_copy_image(
+                command_buffer,
+                src,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                dst,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::ImageCopy::builder()
+                    .src_subresource(subresource)
+                    .dst_subresource(subresource)
+                    .extent(vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    })
+                    .build()],
+            );
+        }
+    }
+}
+
+impl Drop for SceneCache {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_render_pass(self.overlay_render_pass, None);
+        }
+    }
+}
+
+fn color_subresource_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1)
+        .build()
+}
+
+fn create_cache_image(
+    extent: vk::Extent2D,
+    format: vk::Format,
+    device: Rc<Device>,
+) -> RenderResult<Texture> {
+    Texture::builder(
+        extent.width,
+        extent.height,
+        format,
+        vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST,
+        device,
+    )
+    .build()
+}
+
+/// Same attachments as the main render pass, but the color attachment keeps its contents
+fn create_overlay_renderpass(
+    color_format: vk::Format,
+    depth_format: vk::Format,
+    device: &Rc<Device>,
+) -> VkResult<vk::RenderPass> {
+    RenderPassBuilder::new(device.clone())
+        .attachment(
+            color_format,
+            vk::AttachmentLoadOp::LOAD,
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        )
+        .attachment(
+            depth_format,
+            vk::AttachmentLoadOp::DONT_CARE,
+            vk::AttachmentStoreOp::DONT_CARE,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        )
+        .subpass(&[0], Some(1))
+        // The depth attachment may still be in use by the main render pass of another frame
+        .dependency(
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .build(),
+        )
+        .build()
+}
@@ -0,0 +1,134 @@
+use std::rc::Rc;
+
+use ash::{prelude::VkResult, vk};
+
+use super::PerFrame;
+use crate::vulkan_wrappers::{extent_helper, Device};
+
+/// What a secondary command buffer continuing a render pass inherits, plain handles
+/// so it can be sent to the thread recording the buffer
+#[derive(Clone, Copy, Debug)]
+pub struct SecondaryInheritance {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    pub framebuffer: vk::Framebuffer,
+    /// Of the framebuffer, viewport and scissor are set to it by [`Self::begin`]
+    pub extent: vk::Extent2D,
+}
+
+impl SecondaryInheritance {
+    pub fn info(&self) -> vk::CommandBufferInheritanceInfo {
+        vk::CommandBufferInheritanceInfo::builder()
+            .render_pass(self.render_pass)
+            .subpass(self.subpass)
+            .framebuffer(self.framebuffer)
+            .build()
+    }
+
+    /// Resets and begins `command_buffer` to continue the render pass. Takes the
+    /// `ash::Device` which, unlike [`Device`], can be cloned into worker threads.
+    pub fn begin(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        usage: vk::CommandBufferUsageFlags,
+    ) -> VkResult<()> {
+        let inheritance_info = self.info();
+        unsafe {
+            device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::default())?;
+            device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::builder()
+                    .flags(usage | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+                    .inheritance_info(&inheritance_info)
+                    .build(),
+            )?;
+        }
+        // Dynamic state is not inherited from the primary command buffer
+        extent_helper::cmd_set_viewport_and_scissor(device, command_buffer, self.extent);
+        Ok(())
+    }
+}
+
+/// One graphic command pool per recording thread, each handing out secondary command
+/// buffers per frame in flight. A pool must not be used by two threads at once, so
+/// thread `i` only ever records the buffers of [`Self::command_buffers`] for `i`.
+pub struct SecondaryCommandPools {
+    command_pools: Vec<vk::CommandPool>,
+    /// Per pool, the buffers allocated so far for each frame in flight
+    command_buffers: Vec<PerFrame<Vec<vk::CommandBuffer>>>,
+    device: Rc<Device>,
+}
+
+impl SecondaryCommandPools {
+    pub fn new(thread_count: usize, frames_in_flight: usize, device: Rc<Device>) -> VkResult<Self> {
+        let command_pools = (0..thread_count)
+            .map(|_| unsafe {
+                device.create_command_pool(
+                    &vk::CommandPoolCreateInfo::builder()
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                        .queue_family_index(device.graphic_queue_family_index())
+                        .build(),
+                    None,
+                )
+            })
+            .collect::<VkResult<Vec<_>>>()?;
+        Ok(Self {
+            command_buffers: command_pools
+                .iter()
+                .map(|_| PerFrame::new(frames_in_flight, |_| vec![]))
+                .collect(),
+            command_pools,
+            device,
+        })
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.command_pools.len()
+    }
+
+    /// `count` secondary command buffers of `thread` for `frame_index`, allocated on first
+    /// use. The previous submission of the frame must have finished.
+    pub fn command_buffers(
+        &mut self,
+        thread: usize,
+        frame_index: usize,
+        count: usize,
+    ) -> VkResult<&[vk::CommandBuffer]> {
+        let allocated = &mut self.command_buffers[thread][frame_index];
+        if allocated.len() < count {
+            allocated.extend(allocate_secondary_command_buffers(
+                self.command_pools[thread],
+                count - allocated.len(),
+                &self.device,
+            )?);
+        }
+        Ok(&allocated[..count])
+    }
+}
+
+impl Drop for SecondaryCommandPools {
+    fn drop(&mut self) {
+        unsafe {
+            self.command_pools
+                .iter()
+                .for_each(|pool| self.device.destroy_command_pool(*pool, None));
+        }
+    }
+}
+
+pub(super) fn allocate_secondary_command_buffers(
+    command_pool: vk::CommandPool,
+    count: usize,
+    device: &Device,
+) -> VkResult<Vec<vk::CommandBuffer>> {
+    unsafe {
+        device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::SECONDARY)
+                .command_buffer_count(count as u32)
+                .build(),
+        )
+    }
+}
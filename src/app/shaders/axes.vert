@@ -0,0 +1,16 @@
+#version 450
+
+// Axis triad drawn as a line list of 6 vertices, X red, Y green and Z blue from the origin
+layout(push_constant) uniform AxesPush {
+    mat4 mvp;
+} push;
+
+layout(location = 0) out vec3 fragColor;
+
+const vec3 axes[3] = vec3[](vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0));
+
+void main() {
+    vec3 axis = axes[gl_VertexIndex / 2];
+    gl_Position = push.mvp * vec4(axis * float(gl_VertexIndex & 1), 1.0);
+    fragColor = axis;
+}
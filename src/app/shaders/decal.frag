@@ -0,0 +1,20 @@
+#version 450
+
+layout(push_constant) uniform DecalPush {
+    mat4 mvp;
+    vec4 color;
+} push;
+
+layout(set = 0, binding = 0) uniform sampler2D decalTexture;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+// Blended over the surface, transparent texels leave it untouched
+void main() {
+    outColor = texture(decalTexture, fragTexCoord) * push.color;
+    if (outColor.a < 1.0 / 255.0) {
+        discard;
+    }
+}
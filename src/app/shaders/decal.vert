@@ -0,0 +1,20 @@
+#version 450
+
+// Unit quad in the XY plane of decal space, facing +Z, drawn without vertex buffer
+layout(push_constant) uniform DecalPush {
+    mat4 mvp;
+    vec4 color;
+} push;
+
+layout(location = 0) out vec2 fragTexCoord;
+
+const vec2 corners[6] = vec2[](
+    vec2(-0.5, -0.5), vec2(0.5, -0.5), vec2(0.5, 0.5),
+    vec2(-0.5, -0.5), vec2(0.5, 0.5), vec2(-0.5, 0.5)
+);
+
+void main() {
+    vec2 corner = corners[gl_VertexIndex];
+    gl_Position = push.mvp * vec4(corner, 0.0, 1.0);
+    fragTexCoord = corner + 0.5;
+}
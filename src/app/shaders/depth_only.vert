@@ -0,0 +1,13 @@
+#version 450
+
+// Shared by every depth-only pass, see DepthOnlyPipelineCreator
+layout(push_constant) uniform DepthOnlyPush {
+    mat4 mvp;
+    float alphaCutoff;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+
+void main() {
+    gl_Position = push.mvp * vec4(inPosition, 1.0);
+}
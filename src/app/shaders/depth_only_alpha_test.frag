@@ -0,0 +1,17 @@
+#version 450
+
+layout(push_constant) uniform DepthOnlyPush {
+    mat4 mvp;
+    float alphaCutoff;
+} push;
+
+layout(set = 0, binding = 0) uniform sampler2D alphaTexture;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+// Writes nothing but depth, cut-out texels leave no depth either
+void main() {
+    if (texture(alphaTexture, fragTexCoord).a < push.alphaCutoff) {
+        discard;
+    }
+}
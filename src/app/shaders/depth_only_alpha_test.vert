@@ -0,0 +1,17 @@
+#version 450
+
+// depth_only.vert passing the texture coordinate on to the alpha test
+layout(push_constant) uniform DepthOnlyPush {
+    mat4 mvp;
+    float alphaCutoff;
+} push;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec2 fragTexCoord;
+
+void main() {
+    gl_Position = push.mvp * vec4(inPosition, 1.0);
+    fragTexCoord = inTexCoord;
+}
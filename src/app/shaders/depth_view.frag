@@ -0,0 +1,43 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+layout (binding = 0) uniform sampler2D depthImage;
+
+layout (push_constant) uniform PushConsts {
+	float near;
+	float far;
+	// In distances for the linear display, in depth values for the raw one
+	float rangeMin;
+	float rangeMax;
+	uint linear;
+} pushConsts;
+
+layout (location = 0) out vec4 outColor;
+
+// Inverse of the [0, 1] depth of a right-handed perspective projection
+float linearDistance(float depth)
+{
+	return pushConsts.near * pushConsts.far / (pushConsts.far - depth * (pushConsts.far - pushConsts.near));
+}
+
+void main()
+{
+	float depth = texture(depthImage, inUV).r;
+	// Cleared texels, nothing was drawn there
+	if (depth >= 1.0) {
+		outColor = vec4(0.05, 0.0, 0.1, 1.0);
+		return;
+	}
+
+	float value = pushConsts.linear != 0 ? linearDistance(depth) : depth;
+	float t = (value - pushConsts.rangeMin) / max(pushConsts.rangeMax - pushConsts.rangeMin, 1e-7);
+	// Bright near, dark far, tinted outside of the range
+	vec3 color = vec3(1.0 - clamp(t, 0.0, 1.0));
+	if (t < 0.0) {
+		color *= vec3(1.0, 0.4, 0.4);
+	} else if (t > 1.0) {
+		color = vec3(0.1, 0.1, 0.4);
+	}
+	outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,49 @@
+#version 450
+
+// Infinite grid on the y = height plane, where the view ray of the fragment hits it
+layout(push_constant) uniform GridPush {
+    mat4 viewProjection;
+    float height;
+    float cellSize;
+    float fadeDistance;
+} push;
+
+layout(location = 0) in vec4 nearPoint;
+layout(location = 1) in vec4 farPoint;
+
+layout(location = 0) out vec4 outColor;
+
+// Coverage of the lines every `spacing` world units, about a pixel wide at any distance
+float lines(vec2 coord, float spacing) {
+    vec2 scaled = coord / spacing;
+    vec2 distanceToLine = abs(fract(scaled - 0.5) - 0.5) / fwidth(scaled);
+    return 1.0 - min(min(distanceToLine.x, distanceToLine.y), 1.0);
+}
+
+void main() {
+    vec3 near = nearPoint.xyz / nearPoint.w;
+    vec3 far = farPoint.xyz / farPoint.w;
+    float t = (near.y - push.height) / (near.y - far.y);
+    vec3 position = mix(near, far, t);
+
+    vec2 coord = position.xz;
+    float minor = lines(coord, push.cellSize);
+    float major = lines(coord, push.cellSize * 10.0);
+    // The X axis runs along z = 0 and the Z axis along x = 0, colored as in the axis triad
+    vec2 axisWidth = fwidth(coord);
+    float xAxis = 1.0 - min(abs(coord.y) / axisWidth.y, 1.0);
+    float zAxis = 1.0 - min(abs(coord.x) / axisWidth.x, 1.0);
+
+    vec4 color = vec4(vec3(0.5), max(minor * 0.3, major * 0.6));
+    color = mix(color, vec4(0.9, 0.2, 0.2, 1.0), xAxis);
+    color = mix(color, vec4(0.2, 0.4, 0.9, 1.0), zAxis);
+    color.a *= 1.0 - smoothstep(0.0, push.fadeDistance, distance(position, near));
+
+    vec4 clip = push.viewProjection * vec4(position, 1.0);
+    gl_FragDepth = clip.z / clip.w;
+    // Also rejects rays parallel to the plane, whose t is not a number
+    if (!(t >= 0.0 && t <= 1.0) || color.a <= 0.0) {
+        discard;
+    }
+    outColor = color;
+}
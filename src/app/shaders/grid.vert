@@ -0,0 +1,21 @@
+#version 450
+
+// One triangle covering the viewport, unprojected to the near and far planes in world space
+layout(push_constant) uniform GridPush {
+    mat4 viewProjection;
+    float height;
+    float cellSize;
+    float fadeDistance;
+} push;
+
+// Homogeneous, divided per fragment
+layout(location = 0) out vec4 nearPoint;
+layout(location = 1) out vec4 farPoint;
+
+void main() {
+    vec2 ndc = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    mat4 worldFromClip = inverse(push.viewProjection);
+    nearPoint = worldFromClip * vec4(ndc, 0.0, 1.0);
+    farPoint = worldFromClip * vec4(ndc, 1.0, 1.0);
+    gl_Position = vec4(ndc, 0.0, 1.0);
+}
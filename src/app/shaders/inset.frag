@@ -0,0 +1,24 @@
+#version 450
+
+layout (binding = 0) uniform sampler2D insetSampler;
+
+layout (push_constant) uniform PushConstants {
+	vec2 offset;
+	vec2 size;
+	vec4 borderColor;
+	vec2 borderSize;
+} pushConstants;
+
+layout (location = 0) in vec2 inUV;
+
+layout (location = 0) out vec4 outColor;
+
+void main() 
+{
+	vec2 border = pushConstants.borderSize;
+	if (any(lessThan(inUV, border)) || any(greaterThan(inUV, vec2(1.0) - border))) {
+		outColor = pushConstants.borderColor;
+	} else {
+		outColor = texture(insetSampler, (inUV - border) / (vec2(1.0) - 2.0 * border));
+	}
+}
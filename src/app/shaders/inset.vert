@@ -0,0 +1,22 @@
+#version 450
+
+layout (push_constant) uniform PushConstants {
+	vec2 offset;
+	vec2 size;
+	vec4 borderColor;
+	vec2 borderSize;
+} pushConstants;
+
+layout (location = 0) out vec2 outUV;
+
+out gl_PerVertex 
+{
+	vec4 gl_Position;   
+};
+
+void main() 
+{
+	// Triangle strip quad: (0, 0), (1, 0), (0, 1), (1, 1)
+	outUV = vec2(gl_VertexIndex & 1, (gl_VertexIndex >> 1) & 1);
+	gl_Position = vec4(pushConstants.offset + outUV * pushConstants.size, 0.0, 1.0);
+}
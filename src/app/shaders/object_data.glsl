@@ -0,0 +1,19 @@
+// Per-object data of ObjectBuffer, indexed by the firstInstance of each draw:
+//     objects[gl_InstanceIndex].model
+// Define OBJECT_DATA_SET and OBJECT_DATA_BINDING before including to move the buffer
+
+#ifndef OBJECT_DATA_SET
+#define OBJECT_DATA_SET 0
+#endif
+#ifndef OBJECT_DATA_BINDING
+#define OBJECT_DATA_BINDING 1
+#endif
+
+struct ObjectData {
+    mat4 model;
+    mat4 normal;
+};
+
+layout(std430, set = OBJECT_DATA_SET, binding = OBJECT_DATA_BINDING) readonly buffer ObjectBuffer {
+    ObjectData objects[];
+};
@@ -0,0 +1,19 @@
+#version 450
+
+layout (binding = 0) uniform sampler2DArray inspectedSampler;
+
+layout (push_constant) uniform PushConstants {
+	vec2 offset;
+	vec2 size;
+	float mipLevel;
+	float arrayLayer;
+} pushConstants;
+
+layout (location = 0) in vec2 inUV;
+
+layout (location = 0) out vec4 outColor;
+
+void main() 
+{
+	outColor = textureLod(inspectedSampler, vec3(inUV, pushConstants.arrayLayer), pushConstants.mipLevel);
+}
@@ -0,0 +1,51 @@
+#version 450
+
+layout (location = 0) in vec2 inUV;
+
+layout (binding = 0) uniform sampler2D hdrImage;
+
+layout (push_constant) uniform PushConsts {
+	float exposure;
+	// 0 ACES, 1 Reinhard, 2 clamp
+	uint operator;
+	// Set when the swapchain format does not encode to sRGB itself
+	uint encodeSrgb;
+} pushConsts;
+
+layout (location = 0) out vec4 outColor;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x)
+{
+	return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 reinhard(vec3 x)
+{
+	return x / (1.0 + x);
+}
+
+vec3 linearToSrgb(vec3 x)
+{
+	return mix(12.92 * x, 1.055 * pow(x, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, x));
+}
+
+void main()
+{
+	vec3 radiance = texture(hdrImage, inUV).rgb * pushConsts.exposure;
+	vec3 color;
+	switch (pushConsts.operator) {
+	case 0:
+		color = aces(radiance);
+		break;
+	case 1:
+		color = reinhard(radiance);
+		break;
+	default:
+		color = clamp(radiance, 0.0, 1.0);
+	}
+	if (pushConsts.encodeSrgb != 0) {
+		color = linearToSrgb(color);
+	}
+	outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,382 @@
+use std::rc::Rc;
+
+use ash::vk;
+
+use super::{FixedVulkanStuff, PerFrame, PipelineBuilder};
+use crate::{
+    camera::MVPMatrix,
+    error::RenderResult,
+    impl_pipeline_builder_fns,
+    mesh::Vertex,
+    vulkan_wrappers::{Buffer, DescriptorSetBuilder, Device, SurfaceChange, Texture},
+};
+
+/// One indexed [`Vertex`] mesh drawn with an [`MVPMatrix`] uniform at binding 0 of set 0
+/// and optionally a texture at binding 1, the state the simplest examples share. An example
+/// built on it keeps only its scene setup, shaders and whatever technique it shows.
+pub struct SimpleMeshRenderer {
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: PerFrame<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    vertex_buffer: Buffer<Vertex>,
+    indice_buffer: Buffer<u32>,
+    index_count: u32,
+    uniform_buffers: PerFrame<Buffer<MVPMatrix>>,
+    texture: Option<Texture>,
+    /// The pipeline is rebuilt with them, see [`SimpleMeshRenderer::on_surface_changed`]
+    shaders: MeshShaders,
+    device: Rc<Device>,
+}
+
+impl SimpleMeshRenderer {
+    pub const UNIFORM_BINDING: u32 = 0;
+    pub const TEXTURE_BINDING: u32 = 1;
+
+    /// Paths are read at build time unless [`SimpleMeshRendererBuilder::embedded_spv`] is given
+    pub fn builder<'a>(
+        vertex_spv_path: &'a str,
+        frag_spv_path: &'a str,
+    ) -> SimpleMeshRendererBuilder<'a> {
+        SimpleMeshRendererBuilder {
+            vertex_spv_path,
+            frag_spv_path,
+            spv_bytes: None,
+            texture: None,
+            cull_mode: vk::CullModeFlags::BACK,
+        }
+    }
+
+    /// Layout of set 0, with the texture binding when `textured`
+    pub fn descriptor_set_layout_bindings(textured: bool) -> Vec<vk::DescriptorSetLayoutBinding> {
+        let mut bindings = vec![vk::DescriptorSetLayoutBinding::builder()
+            .binding(Self::UNIFORM_BINDING)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .descriptor_count(1)
+            .build()];
+        if textured {
+            bindings.push(
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(Self::TEXTURE_BINDING)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .descriptor_count(1)
+                    .build(),
+            );
+        }
+        bindings
+    }
+
+    pub fn bindings(&self) -> Vec<vk::DescriptorSetLayoutBinding> {
+        Self::descriptor_set_layout_bindings(self.texture.is_some())
+    }
+
+    /// Read by the next submission of `frame_index`
+    pub fn update_mvp(&mut self, frame_index: usize, mvp: &MVPMatrix) {
+        self.uniform_buffers[frame_index].load_data_when_mapped(&[*mvp], 0);
+    }
+
+    /// For commands recorded once and submitted from any frame, such as a
+    /// [`super::PrerecordedScene`], no frame in flight may still read the old matrix
+    pub fn update_mvp_all_frames(&mut self, mvp: &MVPMatrix) {
+        self.uniform_buffers
+            .iter_mut()
+            .for_each(|buffer| buffer.load_data_when_mapped(&[*mvp], 0));
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    /// E.g. to change the sampler, the texture descriptor then has to be written again
+    /// through [`Self::descriptor_sets`]
+    pub fn texture_mut(&mut self) -> Option<&mut Texture> {
+        self.texture.as_mut()
+    }
+
+    pub fn descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    pub fn descriptor_sets(&self) -> &PerFrame<vk::DescriptorSet> {
+        &self.descriptor_sets
+    }
+
+    pub fn pipeline_layout(&self) -> vk::PipelineLayout {
+        self.pipeline_layout
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Rebuilds the pipeline against the new render pass of `s` when its format changed. The
+    /// device must be idle, as after [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.format_changed {
+            let (pipeline_layout, pipeline) = self
+                .shaders
+                .create_pipeline(s, self.descriptor_set_layout)?;
+            unsafe {
+                self.device.destroy_pipeline(self.pipeline, None);
+                self.device
+                    .destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
+    /// Binds everything and draws the mesh, viewport and scissor must be set already
+    pub fn cmd_draw(&self, command_buffer: vk::CommandBuffer, frame_index: usize) {
+        let device = &self.device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.indice_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[frame_index]],
+                &[],
+            );
+            device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+        }
+    }
+}
+
+impl Drop for SimpleMeshRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+pub struct SimpleMeshRendererBuilder<'a> {
+    vertex_spv_path: &'a str,
+    frag_spv_path: &'a str,
+    spv_bytes: Option<(&'static [u8], &'static [u8])>,
+    texture: Option<Texture>,
+    cull_mode: vk::CullModeFlags,
+}
+
+impl<'a> SimpleMeshRendererBuilder<'a> {
+    /// Shaders embedded with [`crate::include_spv!`] from the builder paths
+    pub fn embedded_spv(mut self, vertex: &'static [u8], frag: &'static [u8]) -> Self {
+        self.spv_bytes = Some((vertex, frag));
+        self
+    }
+
+    /// Sampled at [`SimpleMeshRenderer::TEXTURE_BINDING`], its image view and sampler must be
+    /// spawned
+    pub fn texture(mut self, texture: Texture) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    /// Back faces are culled by default
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Uploads the mesh and builds the pipeline for the swapchain pass of `fixed_vulkan_stuff`
+    pub fn build(
+        self,
+        fixed_vulkan_stuff: &FixedVulkanStuff,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> RenderResult<SimpleMeshRenderer> {
+        let device = fixed_vulkan_stuff.device.clone();
+        let bindings = SimpleMeshRenderer::descriptor_set_layout_bindings(self.texture.is_some());
+        let descriptor_set_layout = unsafe {
+            device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder()
+                    .bindings(&bindings)
+                    .build(),
+                None,
+            )?
+        };
+        let frames_in_flight = fixed_vulkan_stuff.frames_in_flight() as u32;
+        let pool_sizes = bindings
+            .iter()
+            .map(|binding| {
+                vk::DescriptorPoolSize::builder()
+                    .ty(binding.descriptor_type)
+                    .descriptor_count(frames_in_flight)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let descriptor_pool = unsafe {
+            device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::builder()
+                    .pool_sizes(&pool_sizes)
+                    .max_sets(frames_in_flight)
+                    .build(),
+                None,
+            )?
+        };
+
+        let uniform_buffers = fixed_vulkan_stuff.try_per_frame(|_| {
+            let mut buffer = Buffer::<MVPMatrix>::new(
+                1,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                device.host_write_memory_properties(),
+                device.clone(),
+            )?;
+            buffer.map_memory_all()?;
+            RenderResult::Ok(buffer)
+        })?;
+        let descriptor_sets = uniform_buffers
+            .iter()
+            .map(|uniform_buffer| {
+                let builder = DescriptorSetBuilder::new(&bindings, device.clone())
+                    .uniform_buffer(SimpleMeshRenderer::UNIFORM_BINDING, uniform_buffer);
+                match &self.texture {
+                    Some(texture) => {
+                        builder.combined_image_sampler(SimpleMeshRenderer::TEXTURE_BINDING, texture)
+                    }
+                    None => builder,
+                }
+                .build(descriptor_pool, descriptor_set_layout)
+            })
+            .collect::<RenderResult<PerFrame<_>>>()?;
+
+        let shaders = MeshShaders {
+            vertex_spv_path: self.vertex_spv_path.to_owned(),
+            frag_spv_path: self.frag_spv_path.to_owned(),
+            spv_bytes: self.spv_bytes,
+            cull_mode: self.cull_mode,
+        };
+        let (pipeline_layout, pipeline) =
+            shaders.create_pipeline(fixed_vulkan_stuff, descriptor_set_layout)?;
+
+        Ok(SimpleMeshRenderer {
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            vertex_buffer: fixed_vulkan_stuff.device_local_vertex_buffer(vertices)?,
+            indice_buffer: fixed_vulkan_stuff.device_local_indice_buffer(indices)?,
+            index_count: indices.len() as u32,
+            uniform_buffers,
+            texture: self.texture,
+            shaders,
+            device,
+        })
+    }
+}
+
+struct MeshShaders {
+    vertex_spv_path: String,
+    frag_spv_path: String,
+    spv_bytes: Option<(&'static [u8], &'static [u8])>,
+    cull_mode: vk::CullModeFlags,
+}
+
+impl MeshShaders {
+    /// For the swapchain pass of `s`
+    fn create_pipeline(
+        &self,
+        s: &FixedVulkanStuff,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        MeshPipelineCreator {
+            device: s.device.clone(),
+            extent: s.surface.extent(),
+            render_pass: s.render_pass,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[Vertex::binding_description()],
+            vertex_attributes: &Vertex::attr_descriptions(),
+            pipeline_cache: s.pipeline_cache,
+            dynamic_rendering_formats: s.dynamic_rendering_formats(),
+            vertex_spv_path: &self.vertex_spv_path,
+            frag_spv_path: &self.frag_spv_path,
+            spv_bytes: self.spv_bytes,
+            cull_mode: self.cull_mode,
+        }
+        .build()
+    }
+}
+
+struct MeshPipelineCreator<'a> {
+    device: Rc<Device>,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+    dynamic_rendering_formats: Option<(Vec<vk::Format>, vk::Format)>,
+    vertex_spv_path: &'a str,
+    frag_spv_path: &'a str,
+    spv_bytes: Option<(&'static [u8], &'static [u8])>,
+    cull_mode: vk::CullModeFlags,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for MeshPipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    fn vertex_spv_path(&self) -> &'a str {
+        self.vertex_spv_path
+    }
+
+    fn frag_spv_path(&self) -> &'a str {
+        self.frag_spv_path
+    }
+
+    fn vertex_spv_bytes(&self) -> Option<&'static [u8]> {
+        self.spv_bytes.map(|(vertex, _)| vertex)
+    }
+
+    fn frag_spv_bytes(&self) -> Option<&'static [u8]> {
+        self.spv_bytes.map(|(_, frag)| frag)
+    }
+
+    fn set_layouts(&self) -> &'a [vk::DescriptorSetLayout] {
+        self.set_layouts
+    }
+
+    fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        self.dynamic_rendering_formats.clone()
+    }
+
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(self.cull_mode)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+}
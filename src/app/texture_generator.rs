@@ -0,0 +1,257 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::Vec4;
+
+use super::ComputePipelineBuilder;
+use crate::{
+    error::RenderResult,
+    include_spv,
+    vulkan_wrappers::{Device, OneTimeCommand, Texture},
+};
+
+/// Contents of a texture made by [`TextureGenerator`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProceduralPattern {
+    /// `cells` squares along each side, starting with `color_a` at the top left
+    Checkerboard {
+        cells: u32,
+        color_a: Vec4,
+        color_b: Vec4,
+    },
+    /// Grayscale fractal noise of `octaves` layers, the first one has `frequency`
+    /// lattice cells along each side
+    PerlinNoise {
+        frequency: f32,
+        octaves: u32,
+        seed: u32,
+    },
+    /// Same as [`ProceduralPattern::PerlinNoise`] on a simplex lattice, with fewer
+    /// axis-aligned artifacts
+    SimplexNoise {
+        frequency: f32,
+        octaves: u32,
+        seed: u32,
+    },
+    /// Linear ramp across the whole texture, `angle` is in radians from +u towards +v
+    Gradient { from: Vec4, to: Vec4, angle: f32 },
+}
+
+impl ProceduralPattern {
+    /// Magenta and black checkerboard, hard to mistake for a real texture
+    pub const MISSING: Self = Self::Checkerboard {
+        cells: 8,
+        color_a: Vec4::new(1., 0., 1., 1.),
+        color_b: Vec4::new(0., 0., 0., 1.),
+    };
+
+    fn push_const_block(&self) -> GeneratorPushConstBlock {
+        let (color_a, color_b, pattern, count, scale, seed) = match *self {
+            Self::Checkerboard {
+                cells,
+                color_a,
+                color_b,
+            } => (color_a, color_b, 0, cells, 0., 0),
+            Self::PerlinNoise {
+                frequency,
+                octaves,
+                seed,
+            } => (Vec4::W, Vec4::ONE, 1, octaves, frequency, seed),
+            Self::SimplexNoise {
+                frequency,
+                octaves,
+                seed,
+            } => (Vec4::W, Vec4::ONE, 2, octaves, frequency, seed),
+            Self::Gradient { from, to, angle } => (from, to, 3, 0, angle, 0),
+        };
+        GeneratorPushConstBlock {
+            color_a,
+            color_b,
+            pattern,
+            count,
+            scale,
+            seed,
+        }
+    }
+}
+
+impl Default for ProceduralPattern {
+    fn default() -> Self {
+        Self::MISSING
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GeneratorPushConstBlock {
+    color_a: Vec4,
+    color_b: Vec4,
+    pattern: u32,
+    count: u32,
+    scale: f32,
+    seed: u32,
+}
+
+unsafe impl bytemuck::Pod for GeneratorPushConstBlock {}
+unsafe impl bytemuck::Zeroable for GeneratorPushConstBlock {}
+
+/// Fills textures with a [`ProceduralPattern`] in a compute shader, for fallback and
+/// test textures that need no file on disk
+pub struct TextureGenerator {
+    device: Rc<Device>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl TextureGenerator {
+    /// Storage images of this format are supported by every implementation
+    pub const FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+    pub fn new(pipeline_cache: vk::PipelineCache, device: Rc<Device>) -> RenderResult<Self> {
+        let descriptor_pool = {
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(1)
+                    .build()])
+                .max_sets(1)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        let descriptor_set_layout = {
+            let bindings = [vk::DescriptorSetLayoutBinding::builder()
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .binding(0)
+                .descriptor_count(1)
+                .build()];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        let descriptor_set = unsafe {
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&[descriptor_set_layout])
+                .build();
+            device.allocate_descriptor_sets(&allocate_info)?[0]
+        };
+
+        let (pipeline_layout, pipeline) = ComputePipelineBuilder::with_spv_bytes(
+            include_spv!("src/app/shaders/texture_generator.comp.spv"),
+            device.clone(),
+        )
+        .set_layouts(&[descriptor_set_layout])
+        .push_constant_ranges(&[vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .size(std::mem::size_of::<GeneratorPushConstBlock>() as u32)
+            .build()])
+        .pipeline_cache(pipeline_cache)
+        .build()?;
+
+        Ok(Self {
+            device,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            pipeline_layout,
+            pipeline,
+        })
+    }
+
+    /// Returns a [`Self::FORMAT`] texture in `SHADER_READ_ONLY_OPTIMAL` with an image view
+    /// and a linear sampler. `queue` must support compute and waits for the generation.
+    pub fn generate(
+        &self,
+        pattern: &ProceduralPattern,
+        width: u32,
+        height: u32,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Texture> {
+        let mut texture = Texture::builder(
+            width,
+            height,
+            Self::FORMAT,
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+            self.device.clone(),
+        )
+        .storage()
+        .build()?;
+        texture.spawn_image_view()?;
+        texture.spawn_sampler(vk::Filter::LINEAR)?;
+
+        let image_descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.descriptor_set)
+            .dst_binding(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .image_info(&[texture.storage_descriptor()])
+            .build();
+        unsafe {
+            self.device
+                .update_descriptor_sets(&[image_descriptor_write], &[])
+        };
+
+        let command = OneTimeCommand::new_and_begin(&self.device, command_pool)?;
+        let command_buffer = *command.command_buffer();
+        texture.transition_to_general(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+        );
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&pattern.push_const_block()),
+            );
+            // Must match the local size of the compute shader
+            self.device
+                .cmd_dispatch(command_buffer, width.div_ceil(8), height.div_ceil(8), 1);
+        }
+        texture.transition_layout(
+            command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+        command.end_and_submit(queue)?;
+
+        Ok(texture)
+    }
+}
+
+impl Drop for TextureGenerator {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use std::rc::Rc;
+
+use ash::vk;
+use glam::Vec2;
+use imgui::Ui;
+
+use super::{FixedVulkanStuff, PipelineBuilder};
+use crate::{
+    error::{RenderError, RenderResult},
+    impl_embedded_spv_fns, impl_pipeline_builder_fns,
+    vulkan_wrappers::{Device, SurfaceChange, Texture},
+};
+
+// Only read by the shaders through `bytemuck::bytes_of`
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+struct InspectorPushConstBlock {
+    offset: Vec2,
+    size: Vec2,
+    mip_level: f32,
+    array_layer: f32,
+}
+
+unsafe impl bytemuck::Pod for InspectorPushConstBlock {}
+unsafe impl bytemuck::Zeroable for InspectorPushConstBlock {}
+
+/// Mip level and array layer shown by a [`TextureInspector`], with ImGui sliders to pick them
+#[derive(Clone, Copy)]
+pub struct TextureSelection {
+    pub mip_level: u32,
+    pub array_layer: u32,
+    mip_levels: u32,
+    array_layers: u32,
+}
+
+impl TextureSelection {
+    pub fn new(mip_levels: u32, array_layers: u32) -> Self {
+        Self {
+            mip_level: 0,
+            array_layer: 0,
+            mip_levels: mip_levels.max(1),
+            array_layers: array_layers.max(1),
+        }
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    pub fn array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
+    /// Returns `true` when the selection was changed by the user
+    pub fn draw(&mut self, ui: &Ui) -> bool {
+        let mut changed = false;
+
+        if ui.collapsing_header("Texture inspector", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+            if self.mip_levels > 1 {
+                changed |= ui.slider("Mip level", 0, self.mip_levels - 1, &mut self.mip_level);
+            } else {
+                ui.text("Mip level: 0 (no mipmaps)");
+            }
+            if self.array_layers > 1 {
+                changed |= ui.slider(
+                    "Array layer",
+                    0,
+                    self.array_layers - 1,
+                    &mut self.array_layer,
+                );
+            } else {
+                ui.text("Array layer: 0 (single layer)");
+            }
+        }
+
+        changed
+    }
+}
+
+/// Debug quad showing a single mip level and array layer of a 2D, array or cube texture.
+///
+/// The texture is viewed as a 2D array through a view owned by the inspector, so any
+/// sampled 2D color texture can be inspected regardless of the view type it is bound with.
+/// 3D textures can not be viewed as a 2D array and are refused.
+pub struct TextureInspector {
+    pub device: Rc<Device>,
+    pub selection: TextureSelection,
+    pub image_view: vk::ImageView,
+    pub sampler: vk::Sampler,
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub descriptor_pool: vk::DescriptorPool,
+    pub descriptor_set: vk::DescriptorSet,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub pipeline: vk::Pipeline,
+}
+
+impl TextureInspector {
+    /// `texture` must have been created with `SAMPLED` usage and stay alive as long as the inspector
+    pub fn new(
+        pipeline_cache: vk::PipelineCache,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        texture: &Texture,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let image_view = create_array_view(texture, &device)?;
+        let sampler = {
+            // Nearest filtering and mipmap mode so that exactly the selected level is shown
+            let create_info = vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::NEAREST)
+                .min_filter(vk::Filter::NEAREST)
+                .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .border_color(vk::BorderColor::FLOAT_OPAQUE_BLACK)
+                .min_lod(0.)
+                .max_lod(vk::LOD_CLAMP_NONE)
+                .build();
+            unsafe { device.create_sampler(&create_info, None)? }
+        };
+
+        let descriptor_pool = {
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(1)
+                    .build()])
+                .max_sets(1)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        let descriptor_set_layout = {
+            let bindings = [vk::DescriptorSetLayoutBinding::builder()
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .binding(0)
+                .descriptor_count(1)
+                .build()];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        let descriptor_set = unsafe {
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&[descriptor_set_layout])
+                .build();
+            device.allocate_descriptor_sets(&allocate_info)?[0]
+        };
+
+        let image_descritptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&[texture.descriptor(image_view, sampler)])
+            .build();
+        unsafe { device.update_descriptor_sets(&[image_descritptor_write], &[]) };
+
+        let (pipeline_layout, pipeline) = Self::create_pipeline(
+            descriptor_set_layout,
+            render_pass,
+            extent,
+            pipeline_cache,
+            device.clone(),
+        )?;
+
+        Ok(Self {
+            device,
+            selection: TextureSelection::new(texture.mip_levels(), texture.array_layers()),
+            image_view,
+            sampler,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            pipeline_layout,
+            pipeline,
+        })
+    }
+
+    pub fn from_fixed_vulkan_stuff(s: &FixedVulkanStuff, texture: &Texture) -> RenderResult<Self> {
+        Self::new(
+            s.pipeline_cache,
+            s.render_pass,
+            s.surface.extent(),
+            texture,
+            s.device.clone(),
+        )
+    }
+
+    /// Rebuilds the pipeline against the new render pass of `s` when its format changed, the
+    /// selection is kept. The device must be idle, as after [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        if change.format_changed {
+            let (pipeline_layout, pipeline) = Self::create_pipeline(
+                self.descriptor_set_layout,
+                s.render_pass,
+                s.surface.extent(),
+                s.pipeline_cache,
+                self.device.clone(),
+            )?;
+            unsafe {
+                self.device.destroy_pipeline(self.pipeline, None);
+                self.device
+                    .destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        Ok(())
+    }
+
+    fn create_pipeline(
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        pipeline_cache: vk::PipelineCache,
+        device: Rc<Device>,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        PipelineCreator {
+            device,
+            render_pass,
+            extent,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            pipeline_cache,
+        }
+        .build()
+    }
+
+    /// Draws the selected mip level and layer into `area` (in pixels) of a `viewport` sized
+    /// framebuffer, viewport and scissor of `command_buffer` should cover the whole framebuffer
+    pub fn draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        viewport: vk::Extent2D,
+        area: vk::Rect2D,
+    ) {
+        let viewport = Vec2::new(viewport.width as f32, viewport.height as f32);
+        let offset = Vec2::new(area.offset.x as f32, area.offset.y as f32);
+        let size = Vec2::new(area.extent.width as f32, area.extent.height as f32);
+        let push_const_block = InspectorPushConstBlock {
+            offset: offset * 2. / viewport - Vec2::ONE,
+            size: size * 2. / viewport,
+            mip_level: self.selection.mip_level as f32,
+            array_layer: self.selection.array_layer as f32,
+        };
+
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push_const_block),
+            );
+            self.device.cmd_draw(command_buffer, 4, 1, 0, 0);
+        }
+    }
+}
+
+impl Drop for TextureInspector {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            self.device.destroy_sampler(self.sampler, None);
+            self.device.destroy_image_view(self.image_view, None);
+        }
+    }
+}
+
+fn create_array_view(texture: &Texture, device: &Device) -> RenderResult<vk::ImageView> {
+    if texture.depth() > 1 {
+        return Err(RenderError::ImageDataInvalid(
+            "Texture inspector can not show 3D textures".to_string(),
+        ));
+    }
+    if texture.layout() != vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL {
+        return Err(RenderError::ImageDataInvalid(format!(
+            "Texture inspector expects SHADER_READ_ONLY_OPTIMAL, the texture is in {:?}",
+            texture.layout()
+        )));
+    }
+    let create_info = vk::ImageViewCreateInfo::builder()
+        .image(*texture.image())
+        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+        .format(texture.format())
+        .subresource_range(
+            vk::ImageSubresourceRange::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(texture.mip_levels())
+                .base_array_layer(0)
+                .layer_count(texture.array_layers())
+                .build(),
+        )
+        .build();
+    Ok(unsafe { device.create_image_view(&create_info, None)? })
+}
+
+struct PipelineCreator<'a> {
+    device: Rc<Device>,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
+    impl_pipeline_builder_fns!();
+
+    impl_embedded_spv_fns!(
+        "src/app/shaders/texture_inspector.vert.spv",
+        "src/app/shaders/texture_inspector.frag.spv"
+    );
+
+    fn pipeline_layout(&self) -> vk::PipelineLayout {
+        let push_constant_range = vk::PushConstantRange::builder()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .size(std::mem::size_of::<InspectorPushConstBlock>() as u32)
+            .build();
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(self.set_layouts)
+            .push_constant_ranges(&[push_constant_range])
+            .build();
+        unsafe {
+            self.device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+                .unwrap()
+        }
+    }
+
+    fn input_assembly_state_create_info(&self) -> vk::PipelineInputAssemblyStateCreateInfo {
+        vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+            .primitive_restart_enable(false)
+            .build()
+    }
+
+    fn rasterization_state_create_info(&self) -> vk::PipelineRasterizationStateCreateInfo {
+        vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(false)
+            .rasterizer_discard_enable(false)
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(false)
+            .build()
+    }
+
+    fn depth_stencil_state_create_info(&self) -> vk::PipelineDepthStencilStateCreateInfo {
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(false)
+            .depth_write_enable(false)
+            .depth_compare_op(vk::CompareOp::ALWAYS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build()
+    }
+}
@@ -1,14 +1,24 @@
-use std::rc::Rc;
+use std::{path::PathBuf, rc::Rc};
 
 use ash::vk;
 use glam::Vec2;
-use imgui::{Context, DrawCmd, DrawIdx, DrawVert, FontSource, StyleColor};
+use imgui::{Context, DrawCmd, DrawData, DrawIdx, DrawVert, FontSource, StyleColor};
+use log::Level;
+use winit::{
+    dpi::PhysicalPosition,
+    event::{MouseButton, MouseScrollDelta},
+};
+#[cfg(feature = "docking")]
+use winit::{event::WindowEvent, event_loop::EventLoopWindowTarget, window::WindowId};
 
-use super::{FixedVulkanStuff, PipelineBuilder};
+#[cfg(feature = "docking")]
+use super::ui_viewports::{MainWindow, UiViewports};
+use super::{FixedVulkanStuff, LogConsole, PerFrame, PipelineBuilder};
 use crate::{
-    error::{RenderError, RenderResult},
-    impl_pipeline_builder_fns,
-    vulkan_wrappers::{Buffer, Device, OneTimeCommand, Texture},
+    error::RenderResult,
+    impl_embedded_spv_fns, impl_pipeline_builder_fns,
+    logging::{report, LogBuffer},
+    vulkan_wrappers::{Buffer, Device, OneTimeCommand, ScissorGuard, SurfaceChange, Texture},
 };
 
 #[derive(Clone, Copy)]
@@ -38,8 +48,8 @@ pub struct UIOverlay {
     pub device: Rc<Device>,
     pub command_pool: vk::CommandPool,
 
-    pub vertex_buffers: [Buffer<DrawVert>; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
-    pub indice_buffers: [Buffer<DrawIdx>; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT],
+    pub vertex_buffers: PerFrame<Buffer<DrawVert>>,
+    pub indice_buffers: PerFrame<Buffer<DrawIdx>>,
 
     pub descriptor_set_layout: vk::DescriptorSetLayout,
     pub descriptor_pool: vk::DescriptorPool,
@@ -49,6 +59,19 @@ pub struct UIOverlay {
 
     pub font_texture: Texture,
     pub scale: f32,
+    /// Where the window layout is kept, see [`UIOverlay::enable_layout_persistence`]
+    pub layout_path: Option<PathBuf>,
+    /// Windows of viewports outside the main window, see [`UIOverlay::enable_viewports`]
+    #[cfg(feature = "docking")]
+    pub viewports: Option<UiViewports>,
+    /// imgui frame last drawn, viewports are updated once per frame after it
+    #[cfg(feature = "docking")]
+    rendered_frame: i32,
+    /// Extent of the framebuffer the overlay is drawn into, clip rects are clamped to it.
+    /// The display size is used when unknown, see [`UIOverlay::set_framebuffer_extent`]
+    pub framebuffer_extent: Option<vk::Extent2D>,
+    /// See [`UIOverlay::enable_log_console`]
+    pub log_console: Option<LogConsole>,
 
     pub imgui_context: Context,
 }
@@ -59,6 +82,18 @@ impl UIOverlay {
         render_pass: vk::RenderPass,
         scale: f32,
         device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Self::with_dynamic_rendering_formats(pipeline_cache, render_pass, None, scale, device)
+    }
+
+    /// The pipeline is built for dynamic rendering when `dynamic_rendering_formats` is set,
+    /// see [`super::PipelineBuilder::dynamic_rendering_formats`]
+    fn with_dynamic_rendering_formats(
+        pipeline_cache: vk::PipelineCache,
+        render_pass: vk::RenderPass,
+        dynamic_rendering_formats: Option<(Vec<vk::Format>, vk::Format)>,
+        scale: f32,
+        device: Rc<Device>,
     ) -> RenderResult<Self> {
         let mut imgui = Context::create();
         {
@@ -247,48 +282,19 @@ impl UIOverlay {
 
         unsafe { device.update_descriptor_sets(&[image_descritptor_write], &[]) };
 
-        let pipeline_builder = PipelineCreator {
-            device: device.clone(),
+        let (pipeline_layout, pipeline) = Self::create_pipeline(
+            descriptor_set_layout,
             render_pass,
-            extent: vk::Extent2D {
-                width: tex_width,
-                height: tex_height,
-            },
-            set_layouts: &[descriptor_set_layout],
-            vertex_bindings: &[vk::VertexInputBindingDescription::builder()
-                .binding(0)
-                .stride(std::mem::size_of::<DrawVert>() as u32)
-                .input_rate(vk::VertexInputRate::VERTEX)
-                .build()],
-            vertex_attributes: &[
-                vk::VertexInputAttributeDescription::builder()
-                    .binding(0)
-                    .location(0)
-                    .format(vk::Format::R32G32_SFLOAT)
-                    .offset(memoffset::offset_of!(DrawVert, pos) as u32)
-                    .build(),
-                vk::VertexInputAttributeDescription::builder()
-                    .binding(0)
-                    .location(1)
-                    .format(vk::Format::R32G32_SFLOAT)
-                    .offset(memoffset::offset_of!(DrawVert, uv) as u32)
-                    .build(),
-                vk::VertexInputAttributeDescription::builder()
-                    .binding(0)
-                    .location(2)
-                    .format(vk::Format::R8G8B8A8_UNORM)
-                    .offset(memoffset::offset_of!(DrawVert, col) as u32)
-                    .build(),
-            ],
+            dynamic_rendering_formats,
             pipeline_cache,
-        };
-
-        let (pipeline_layout, pipeline) = pipeline_builder.build()?;
+            font_texture.extent2d(),
+            device.clone(),
+        )?;
 
-        let vertex_buffers = array_init::try_array_init(|_| -> Result<_, RenderError> {
+        let vertex_buffers = PerFrame::try_new(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT, |_| {
             Self::vertex_buffer(device.clone(), 1)
         })?;
-        let indice_buffers = array_init::try_array_init(|_| -> Result<_, RenderError> {
+        let indice_buffers = PerFrame::try_new(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT, |_| {
             Self::indice_buffer(device.clone(), 1)
         })?;
 
@@ -304,145 +310,279 @@ impl UIOverlay {
             pipeline,
             font_texture,
             scale,
+            layout_path: None,
+            #[cfg(feature = "docking")]
+            viewports: None,
+            #[cfg(feature = "docking")]
+            rendered_frame: 0,
+            framebuffer_extent: None,
+            log_console: None,
             imgui_context: imgui,
         })
     }
 
     pub fn from_fixed_vulkan_stuff(s: &FixedVulkanStuff, scale: f32) -> RenderResult<Self> {
-        Self::new(s.pipeline_cache, s.render_pass, scale, s.device.clone())
+        let mut overlay = Self::with_dynamic_rendering_formats(
+            s.pipeline_cache,
+            s.render_pass,
+            s.dynamic_rendering_formats(),
+            scale,
+            s.device.clone(),
+        )?;
+        overlay.set_framebuffer_extent(s.surface.extent());
+        Ok(overlay)
     }
 
-    pub fn update(&mut self, frame_index: usize) -> RenderResult<bool> {
-        assert!(frame_index < FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT);
+    /// Follows the swapchain of `s`: clip rects follow its extent, and the pipeline is
+    /// rebuilt against its new render pass when its format changed. The device must be idle,
+    /// as after [`FixedVulkanStuff::refit_window`].
+    pub fn on_surface_changed(
+        &mut self,
+        s: &FixedVulkanStuff,
+        change: SurfaceChange,
+    ) -> RenderResult<()> {
+        self.set_framebuffer_extent(s.surface.extent());
+        if change.format_changed {
+            let (pipeline_layout, pipeline) = Self::create_pipeline(
+                self.descriptor_set_layout,
+                s.render_pass,
+                s.dynamic_rendering_formats(),
+                s.pipeline_cache,
+                self.font_texture.extent2d(),
+                self.device.clone(),
+            )?;
+            unsafe {
+                self.device.destroy_pipeline(self.pipeline, None);
+                self.device
+                    .destroy_pipeline_layout(self.pipeline_layout, None);
+            }
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        Ok(())
+    }
 
-        let mut update_command_buffers = false;
+    /// Must follow swapchain refits, which can lag behind the window size the display
+    /// size is taken from
+    pub fn set_framebuffer_extent(&mut self, extent: vk::Extent2D) {
+        self.framebuffer_extent = Some(extent);
+    }
 
-        let draw_data = self.imgui_context.render();
+    /// Keeps the window layout in `path` instead of imgui's `imgui.ini` in the working
+    /// directory, restoring it now when the file exists. It is saved whenever imgui marks
+    /// it changed, see [`ui_layout_path`] for a per app location
+    /// Installs [`LogBuffer::global`] as the logger and shows its records in a console
+    /// window, toggled with the grave key
+    pub fn enable_log_console(&mut self) {
+        LogBuffer::global();
+        self.log_console = Some(LogConsole::new());
+    }
 
-        if draw_data.total_vtx_count == 0 || draw_data.total_idx_count == 0 {
-            return Ok(false);
+    pub fn enable_layout_persistence(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.imgui_context.set_ini_filename(None);
+        if let Ok(layout) = std::fs::read_to_string(&path) {
+            self.imgui_context.load_ini_settings(&layout);
         }
+        self.layout_path = Some(path);
+    }
 
-        if self.vertex_buffers[frame_index].element_num() != draw_data.total_vtx_count as usize {
-            self.vertex_buffers[frame_index] =
-                Self::vertex_buffer(self.device.clone(), draw_data.total_vtx_count as usize)?;
-            update_command_buffers = true;
+    /// Writes the layout next to its file first and then renames it, like the pipeline cache
+    pub fn save_layout(&mut self) -> RenderResult<()> {
+        let Some(path) = &self.layout_path else {
+            return Ok(());
+        };
+        let mut layout = String::new();
+        self.imgui_context.save_ini_settings(&mut layout);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
         }
-        if self.indice_buffers[frame_index].element_num() != draw_data.total_idx_count as usize {
-            self.indice_buffers[frame_index] =
-                Self::indice_buffer(self.device.clone(), draw_data.total_idx_count as usize)?;
-            update_command_buffers = true;
+        let temporary_path = path.with_extension("tmp");
+        std::fs::write(&temporary_path, layout)?;
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    /// Windows can be docked into each other and into the main window, which stays
+    /// see-through where nothing is docked
+    #[cfg(feature = "docking")]
+    pub fn enable_docking(&mut self) {
+        self.imgui_context.io_mut().config_flags |= imgui::ConfigFlags::DOCKING_ENABLE;
+    }
+
+    #[cfg(feature = "docking")]
+    pub fn docking_enabled(&self) -> bool {
+        self.imgui_context
+            .io()
+            .config_flags
+            .contains(imgui::ConfigFlags::DOCKING_ENABLE)
+    }
+
+    /// Lets windows be dragged out of the main window into OS windows of their own, each
+    /// presented through an extra swapchain. Events of those windows have to reach
+    /// [`Self::on_viewport_event`] and [`Self::update_viewports`] has to run after every
+    /// frame, which [`super::WindowApp`] does. Enables docking as well
+    #[cfg(feature = "docking")]
+    pub fn enable_viewports(&mut self, s: &FixedVulkanStuff) -> RenderResult<()> {
+        self.enable_docking();
+        self.viewports = Some(UiViewports::new(
+            &mut self.imgui_context,
+            s.surface.format(),
+            s.pipeline_cache,
+            self.descriptor_set_layout,
+            self.font_texture.extent2d(),
+            self.device.clone(),
+        )?);
+        Ok(())
+    }
+
+    /// Passes the main window state on to imgui before a UI frame is built
+    #[cfg(feature = "docking")]
+    pub(crate) fn prepare_viewports(&mut self, main_window: MainWindow) {
+        if let Some(viewports) = &mut self.viewports {
+            viewports.set_main_window(&mut self.imgui_context, main_window);
         }
+    }
 
-        self.vertex_buffers[frame_index].map_memory_all()?;
-        self.indice_buffers[frame_index].map_memory_all()?;
-
-        let (mut vertex_offset, mut indice_offset) = (0, 0);
-        for draw_list in draw_data.draw_lists() {
-            self.vertex_buffers[frame_index]
-                .load_data_when_mapped(draw_list.vtx_buffer(), vertex_offset);
-            vertex_offset += draw_list.vtx_buffer().len() as u64;
-            self.indice_buffers[frame_index]
-                .load_data_when_mapped(draw_list.idx_buffer(), indice_offset);
-            indice_offset += draw_list.idx_buffer().len() as u64
+    /// Handles an event of a viewport window, events of other windows are ignored
+    #[cfg(feature = "docking")]
+    pub fn on_viewport_event(&mut self, window_id: WindowId, event: &WindowEvent) {
+        if let Some(viewports) = &mut self.viewports {
+            viewports.on_event(&mut self.imgui_context, window_id, event);
         }
+    }
 
-        self.vertex_buffers[frame_index].flush()?;
-        self.indice_buffers[frame_index].flush()?;
-        self.vertex_buffers[frame_index].unmap_memory();
-        self.indice_buffers[frame_index].unmap_memory();
+    /// Creates and destroys the windows imgui asked for and presents every viewport outside
+    /// the main window. Only does something once per UI frame, after [`Self::draw`]
+    #[cfg(feature = "docking")]
+    pub fn update_viewports(
+        &mut self,
+        window_target: &EventLoopWindowTarget<()>,
+    ) -> RenderResult<()> {
+        match &mut self.viewports {
+            Some(viewports) => viewports.update(
+                &mut self.imgui_context,
+                self.rendered_frame,
+                self.descriptor_set,
+                window_target,
+            ),
+            None => Ok(()),
+        }
+    }
 
-        Ok(update_command_buffers)
+    /// `position` is relative to the main window, imgui takes OS screen coordinates once
+    /// viewports are enabled
+    pub fn add_mouse_pos_event(&mut self, position: PhysicalPosition<f64>) {
+        let position = [position.x as f32, position.y as f32];
+        #[cfg(feature = "docking")]
+        let position = match &self.viewports {
+            Some(viewports) => viewports.to_screen(position),
+            None => position,
+        };
+        self.imgui_context.io_mut().add_mouse_pos_event(position);
+    }
+
+    pub fn update(&mut self, frame_index: usize) -> RenderResult<bool> {
+        assert!(frame_index < FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT);
+
+        if self.imgui_context.io().want_save_ini_settings {
+            self.report_save_layout();
+        }
+        let draw_data = self.imgui_context.render();
+        upload_draw_data(
+            draw_data,
+            &mut self.vertex_buffers[frame_index],
+            &mut self.indice_buffers[frame_index],
+            &self.device,
+        )
     }
 
     pub fn draw(&mut self, command_buffer: vk::CommandBuffer, frame_index: usize) {
         let display_size = self.imgui_context.io().display_size;
+        let framebuffer_extent = self.framebuffer_extent.unwrap_or(vk::Extent2D {
+            width: display_size[0] as u32,
+            height: display_size[1] as u32,
+        });
         let draw_data = self.imgui_context.render();
-
-        if draw_data.draw_lists_count() == 0 {
-            return;
+        cmd_draw_data(
+            &self.device,
+            command_buffer,
+            (self.pipeline_layout, self.pipeline),
+            self.descriptor_set,
+            draw_data,
+            (
+                &self.vertex_buffers[frame_index],
+                &self.indice_buffers[frame_index],
+            ),
+            framebuffer_extent,
+        );
+        #[cfg(feature = "docking")]
+        {
+            self.rendered_frame = unsafe { imgui::sys::igGetFrameCount() };
         }
+    }
 
-        unsafe {
-            self.device.cmd_bind_pipeline(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
-            self.device.cmd_bind_descriptor_sets(
-                command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout,
-                0,
-                &[self.descriptor_set],
-                &[],
-            );
-            self.device.cmd_push_constants(
-                command_buffer,
-                self.pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                bytemuck::bytes_of(&UIPushConstBlock::new(
-                    Vec2::ONE * 2.0 / Vec2::from(display_size),
-                    Vec2::NEG_ONE,
-                )),
-            );
-            self.device.cmd_bind_vertex_buffers(
-                command_buffer,
-                0,
-                &[self.vertex_buffers[frame_index].buffer()],
-                &[0],
-            );
-            self.device.cmd_bind_index_buffer(
-                command_buffer,
-                self.indice_buffers[frame_index].buffer(),
-                0,
-                vk::IndexType::UINT16,
-            );
+    fn report_save_layout(&mut self) {
+        if let Err(e) = self.save_layout() {
+            if let Some(path) = &self.layout_path {
+                report(
+                    Level::Warn,
+                    "ui",
+                    format_args!("Fail to save UI layout to {}: {e}", path.display()),
+                );
+            }
         }
+    }
 
-        let (mut vertex_offset, mut indice_offset) = (0, 0);
-        for draw_list in draw_data.draw_lists() {
-            for cmd in draw_list.commands() {
-                if let DrawCmd::Elements {
-                    count,
-                    cmd_params: paras,
-                } = cmd
-                {
-                    let scissor_rect = vk::Rect2D::builder()
-                        .extent(
-                            vk::Extent2D::builder()
-                                .width((paras.clip_rect[2] - paras.clip_rect[0]) as u32)
-                                .height((paras.clip_rect[3] - paras.clip_rect[1]) as u32)
-                                .build(),
-                        )
-                        .offset(
-                            vk::Offset2D::builder()
-                                .x((paras.clip_rect[0] as i32).max(0))
-                                .y((paras.clip_rect[1] as i32).max(0))
-                                .build(),
-                        )
-                        .build();
-                    unsafe {
-                        self.device
-                            .cmd_set_scissor(command_buffer, 0, &[scissor_rect]);
-                        self.device.cmd_draw_indexed(
-                            command_buffer,
-                            count as u32,
-                            1,
-                            indice_offset,
-                            vertex_offset as i32,
-                            0,
-                        )
-                    }
-                    indice_offset += count as u32;
-                }
-            }
-            vertex_offset += draw_list.vtx_buffer().len() as u32;
+    /// Pipeline drawing imgui vertices, textured with the font of `descriptor_set_layout`
+    pub(super) fn create_pipeline(
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        render_pass: vk::RenderPass,
+        dynamic_rendering_formats: Option<(Vec<vk::Format>, vk::Format)>,
+        pipeline_cache: vk::PipelineCache,
+        extent: vk::Extent2D,
+        device: Rc<Device>,
+    ) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)> {
+        PipelineCreator {
+            device,
+            render_pass,
+            extent,
+            set_layouts: &[descriptor_set_layout],
+            vertex_bindings: &[vk::VertexInputBindingDescription::builder()
+                .binding(0)
+                .stride(std::mem::size_of::<DrawVert>() as u32)
+                .input_rate(vk::VertexInputRate::VERTEX)
+                .build()],
+            vertex_attributes: &[
+                vk::VertexInputAttributeDescription::builder()
+                    .binding(0)
+                    .location(0)
+                    .format(vk::Format::R32G32_SFLOAT)
+                    .offset(memoffset::offset_of!(DrawVert, pos) as u32)
+                    .build(),
+                vk::VertexInputAttributeDescription::builder()
+                    .binding(0)
+                    .location(1)
+                    .format(vk::Format::R32G32_SFLOAT)
+                    .offset(memoffset::offset_of!(DrawVert, uv) as u32)
+                    .build(),
+                vk::VertexInputAttributeDescription::builder()
+                    .binding(0)
+                    .location(2)
+                    .format(vk::Format::R8G8B8A8_UNORM)
+                    .offset(memoffset::offset_of!(DrawVert, col) as u32)
+                    .build(),
+            ],
+            pipeline_cache,
+            dynamic_rendering_formats,
         }
+        .build()
     }
 
-    fn vertex_buffer(device: Rc<Device>, elem_num: usize) -> RenderResult<Buffer<DrawVert>> {
+    pub(super) fn vertex_buffer(
+        device: Rc<Device>,
+        elem_num: usize,
+    ) -> RenderResult<Buffer<DrawVert>> {
         Buffer::<DrawVert>::new(
             elem_num,
             vk::BufferUsageFlags::VERTEX_BUFFER,
@@ -451,7 +591,10 @@ impl UIOverlay {
         )
     }
 
-    fn indice_buffer(device: Rc<Device>, indice_num: usize) -> RenderResult<Buffer<DrawIdx>> {
+    pub(super) fn indice_buffer(
+        device: Rc<Device>,
+        indice_num: usize,
+    ) -> RenderResult<Buffer<DrawIdx>> {
         Buffer::<DrawIdx>::new(
             indice_num,
             vk::BufferUsageFlags::INDEX_BUFFER,
@@ -463,6 +606,9 @@ impl UIOverlay {
 
 impl Drop for UIOverlay {
     fn drop(&mut self) {
+        self.report_save_layout();
+        #[cfg(feature = "docking")]
+        self.viewports.take();
         unsafe {
             self.device.device_wait_idle().unwrap();
             self.device.destroy_command_pool(self.command_pool, None);
@@ -477,6 +623,163 @@ impl Drop for UIOverlay {
     }
 }
 
+/// One layout file per app, in the temporary directory next to the pipeline caches
+pub fn ui_layout_path(app_name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join("vulkan-examples-rs")
+        .join(format!("ui_layout_{app_name}.ini"))
+}
+
+pub(super) fn imgui_mouse_button(button: MouseButton) -> Option<imgui::MouseButton> {
+    match button {
+        MouseButton::Left => Some(imgui::MouseButton::Left),
+        MouseButton::Right => Some(imgui::MouseButton::Right),
+        MouseButton::Middle => Some(imgui::MouseButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+/// Horizontal and vertical scroll in wheel lines
+pub(super) fn wheel_lines(delta: MouseScrollDelta) -> [f32; 2] {
+    // Touchpads scroll in pixels, roughly this many make up a wheel line
+    const PIXELS_PER_LINE: f32 = 20.;
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => [x, y],
+        MouseScrollDelta::PixelDelta(position) => [
+            position.x as f32 / PIXELS_PER_LINE,
+            position.y as f32 / PIXELS_PER_LINE,
+        ],
+    }
+}
+
+/// Copies the vertices and indices of `draw_data` into the buffers, which are reallocated
+/// when their size changes. Returns whether they were
+pub(super) fn upload_draw_data(
+    draw_data: &DrawData,
+    vertex_buffer: &mut Buffer<DrawVert>,
+    indice_buffer: &mut Buffer<DrawIdx>,
+    device: &Rc<Device>,
+) -> RenderResult<bool> {
+    let mut update_command_buffers = false;
+
+    if draw_data.total_vtx_count == 0 || draw_data.total_idx_count == 0 {
+        return Ok(false);
+    }
+
+    if vertex_buffer.element_num() != draw_data.total_vtx_count as usize {
+        *vertex_buffer =
+            UIOverlay::vertex_buffer(device.clone(), draw_data.total_vtx_count as usize)?;
+        update_command_buffers = true;
+    }
+    if indice_buffer.element_num() != draw_data.total_idx_count as usize {
+        *indice_buffer =
+            UIOverlay::indice_buffer(device.clone(), draw_data.total_idx_count as usize)?;
+        update_command_buffers = true;
+    }
+
+    vertex_buffer.map_memory_all()?;
+    indice_buffer.map_memory_all()?;
+
+    let (mut vertex_offset, mut indice_offset) = (0, 0);
+    for draw_list in draw_data.draw_lists() {
+        vertex_buffer.load_data_when_mapped(draw_list.vtx_buffer(), vertex_offset);
+        vertex_offset += draw_list.vtx_buffer().len() as u64;
+        indice_buffer.load_data_when_mapped(draw_list.idx_buffer(), indice_offset);
+        indice_offset += draw_list.idx_buffer().len() as u64
+    }
+
+    vertex_buffer.flush()?;
+    indice_buffer.flush()?;
+    vertex_buffer.unmap_memory();
+    indice_buffer.unmap_memory();
+
+    Ok(update_command_buffers)
+}
+
+/// Draws `draw_data` uploaded by [`upload_draw_data`] into a framebuffer of
+/// `framebuffer_extent`, which shows imgui's coordinates from `draw_data.display_pos` on
+pub(super) fn cmd_draw_data(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    (pipeline_layout, pipeline): (vk::PipelineLayout, vk::Pipeline),
+    descriptor_set: vk::DescriptorSet,
+    draw_data: &DrawData,
+    (vertex_buffer, indice_buffer): (&Buffer<DrawVert>, &Buffer<DrawIdx>),
+    framebuffer_extent: vk::Extent2D,
+) {
+    if draw_data.draw_lists_count() == 0 {
+        return;
+    }
+
+    let scale = Vec2::ONE * 2.0 / Vec2::from(draw_data.display_size);
+    let display_pos = Vec2::from(draw_data.display_pos);
+    unsafe {
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            0,
+            &[descriptor_set],
+            &[],
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            bytemuck::bytes_of(&UIPushConstBlock::new(
+                scale,
+                Vec2::NEG_ONE - display_pos * scale,
+            )),
+        );
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer.buffer()], &[0]);
+        device.cmd_bind_index_buffer(
+            command_buffer,
+            indice_buffer.buffer(),
+            0,
+            vk::IndexType::UINT16,
+        );
+    }
+
+    let scissor = ScissorGuard::new(device, command_buffer, framebuffer_extent);
+    let (mut vertex_offset, mut indice_offset) = (0, 0);
+    for draw_list in draw_data.draw_lists() {
+        for cmd in draw_list.commands() {
+            if let DrawCmd::Elements {
+                count,
+                cmd_params: paras,
+            } = cmd
+            {
+                let [min_x, min_y, max_x, max_y] = paras.clip_rect;
+                let clip_rect = [
+                    min_x - display_pos.x,
+                    min_y - display_pos.y,
+                    max_x - display_pos.x,
+                    max_y - display_pos.y,
+                ];
+                // Clipped away or outside the framebuffer, the indices are still consumed
+                if !scissor.set(clip_rect) {
+                    indice_offset += count as u32;
+                    continue;
+                }
+                unsafe {
+                    device.cmd_draw_indexed(
+                        command_buffer,
+                        count as u32,
+                        1,
+                        indice_offset,
+                        vertex_offset as i32,
+                        0,
+                    )
+                }
+                indice_offset += count as u32;
+            }
+        }
+        vertex_offset += draw_list.vtx_buffer().len() as u32;
+    }
+}
+
 struct PipelineCreator<'a> {
     device: Rc<Device>,
     render_pass: vk::RenderPass,
@@ -485,18 +788,20 @@ struct PipelineCreator<'a> {
     vertex_bindings: &'a [vk::VertexInputBindingDescription],
     vertex_attributes: &'a [vk::VertexInputAttributeDescription],
     pipeline_cache: vk::PipelineCache,
+    dynamic_rendering_formats: Option<(Vec<vk::Format>, vk::Format)>,
 }
 
 impl<'a> PipelineBuilder<'a, &'a str> for PipelineCreator<'a> {
     impl_pipeline_builder_fns!();
 
-    fn vertex_spv_path(&self) -> &'a str {
-        "src/app/shaders/uioverlay.vert.spv"
+    fn dynamic_rendering_formats(&self) -> Option<(Vec<vk::Format>, vk::Format)> {
+        self.dynamic_rendering_formats.clone()
     }
 
-    fn frag_spv_path(&self) -> &'a str {
+    impl_embedded_spv_fns!(
+        "src/app/shaders/uioverlay.vert.spv",
         "src/app/shaders/uioverlay.frag.spv"
-    }
+    );
 
     fn pipeline_layout(&self) -> vk::PipelineLayout {
         let push_constant_range = vk::PushConstantRange::builder()
@@ -0,0 +1,775 @@
+use std::{cell::RefCell, collections::HashMap, ffi::c_void, rc::Rc};
+
+use ash::vk;
+use imgui::{
+    BackendFlags, ConfigFlags, Context, DrawData, DrawIdx, DrawVert, Id, PlatformMonitor,
+    PlatformViewportBackend, Viewport, ViewportFlags,
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, WindowEvent},
+    event_loop::EventLoopWindowTarget,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+use super::{
+    ui_overlay::{cmd_draw_data, imgui_mouse_button, upload_draw_data, wheel_lines},
+    FixedVulkanStuff, PerFrame, UIOverlay,
+};
+use crate::{
+    error::RenderResult,
+    vulkan_wrappers::{extent_helper, Buffer, Device, RenderPassBuilder, Surface, SwapChainBatch},
+};
+
+/// State of the main window imgui reads while building a frame, taken before it starts
+pub(crate) struct MainWindow {
+    origin: [f32; 2],
+    size: [f32; 2],
+    focused: bool,
+    minimized: bool,
+    monitors: Vec<PlatformMonitor>,
+}
+
+impl MainWindow {
+    pub(crate) fn of(window: &Window) -> Self {
+        let origin = window.inner_position().unwrap_or_default();
+        let size = window.inner_size();
+        let origin = [origin.x as f32, origin.y as f32];
+        let size = [size.width as f32, size.height as f32];
+        let mut monitors = window
+            .available_monitors()
+            .map(|monitor| {
+                let position = monitor.position();
+                let size = monitor.size();
+                let main_pos = [position.x as f32, position.y as f32];
+                let main_size = [size.width as f32, size.height as f32];
+                PlatformMonitor {
+                    main_pos,
+                    main_size,
+                    work_pos: main_pos,
+                    work_size: main_size,
+                    dpi_scale: monitor.scale_factor() as f32,
+                }
+            })
+            .collect::<Vec<_>>();
+        // imgui needs at least one monitor, some platforms do not list them
+        if monitors.is_empty() {
+            monitors.push(PlatformMonitor {
+                main_pos: origin,
+                main_size: size,
+                work_pos: origin,
+                work_size: size,
+                dpi_scale: window.scale_factor() as f32,
+            });
+        }
+        Self {
+            origin,
+            size,
+            focused: window.has_focus(),
+            minimized: window.is_minimized().unwrap_or(false),
+            monitors,
+        }
+    }
+}
+
+struct PlatformWindow {
+    /// Created in [`UiViewports::update`], winit only creates windows within the event loop
+    window: Option<Window>,
+    pos: [f32; 2],
+    size: [f32; 2],
+    title: String,
+    decorations: bool,
+    visible: bool,
+}
+
+/// Windows of the viewports, shared by [`UiViewports`] and the platform backend imgui calls
+#[derive(Default)]
+struct PlatformWindows {
+    main_id: Id,
+    main_origin: [f32; 2],
+    main_size: [f32; 2],
+    main_focused: bool,
+    main_minimized: bool,
+    windows: HashMap<Id, PlatformWindow>,
+    /// Destroyed by imgui, dropped once their swapchains are gone
+    retired: Vec<(Id, PlatformWindow)>,
+}
+
+struct WinitPlatform(Rc<RefCell<PlatformWindows>>);
+
+impl WinitPlatform {
+    fn with_window<R>(&self, id: Id, default: R, f: impl FnOnce(&mut PlatformWindow) -> R) -> R {
+        self.0.borrow_mut().windows.get_mut(&id).map_or(default, f)
+    }
+}
+
+impl PlatformViewportBackend for WinitPlatform {
+    fn create_window(&mut self, viewport: &mut Viewport) {
+        self.0.borrow_mut().windows.insert(
+            viewport.id,
+            PlatformWindow {
+                window: None,
+                pos: viewport.pos,
+                size: viewport.size,
+                title: String::new(),
+                decorations: !viewport.flags.contains(ViewportFlags::NO_DECORATION),
+                visible: false,
+            },
+        );
+    }
+
+    fn destroy_window(&mut self, viewport: &mut Viewport) {
+        let mut platform_windows = self.0.borrow_mut();
+        if let Some(window) = platform_windows.windows.remove(&viewport.id) {
+            platform_windows.retired.push((viewport.id, window));
+        }
+    }
+
+    fn show_window(&mut self, viewport: &mut Viewport) {
+        self.with_window(viewport.id, (), |platform_window| {
+            platform_window.visible = true;
+            if let Some(window) = &platform_window.window {
+                window.set_visible(true);
+            }
+        })
+    }
+
+    fn set_window_pos(&mut self, viewport: &mut Viewport, pos: [f32; 2]) {
+        self.with_window(viewport.id, (), |platform_window| {
+            platform_window.pos = pos;
+            if let Some(window) = &platform_window.window {
+                window.set_outer_position(PhysicalPosition::new(pos[0] as i32, pos[1] as i32));
+            }
+        })
+    }
+
+    fn get_window_pos(&mut self, viewport: &mut Viewport) -> [f32; 2] {
+        if viewport.id == self.0.borrow().main_id {
+            return self.0.borrow().main_origin;
+        }
+        self.with_window(
+            viewport.id,
+            viewport.pos,
+            |platform_window| match platform_window
+                .window
+                .as_ref()
+                .and_then(|window| window.inner_position().ok())
+            {
+                Some(position) => [position.x as f32, position.y as f32],
+                None => platform_window.pos,
+            },
+        )
+    }
+
+    fn set_window_size(&mut self, viewport: &mut Viewport, size: [f32; 2]) {
+        self.with_window(viewport.id, (), |platform_window| {
+            platform_window.size = size;
+            if let Some(window) = &platform_window.window {
+                window.set_inner_size(PhysicalSize::new(size[0] as u32, size[1] as u32));
+            }
+        })
+    }
+
+    fn get_window_size(&mut self, viewport: &mut Viewport) -> [f32; 2] {
+        if viewport.id == self.0.borrow().main_id {
+            return self.0.borrow().main_size;
+        }
+        self.with_window(
+            viewport.id,
+            viewport.size,
+            |platform_window| match &platform_window.window {
+                Some(window) => {
+                    let size = window.inner_size();
+                    [size.width as f32, size.height as f32]
+                }
+                None => platform_window.size,
+            },
+        )
+    }
+
+    fn set_window_focus(&mut self, viewport: &mut Viewport) {
+        self.with_window(viewport.id, (), |platform_window| {
+            if let Some(window) = &platform_window.window {
+                window.focus_window();
+            }
+        })
+    }
+
+    fn get_window_focus(&mut self, viewport: &mut Viewport) -> bool {
+        if viewport.id == self.0.borrow().main_id {
+            return self.0.borrow().main_focused;
+        }
+        self.with_window(viewport.id, false, |platform_window| {
+            platform_window
+                .window
+                .as_ref()
+                .is_some_and(|window| window.has_focus())
+        })
+    }
+
+    fn get_window_minimized(&mut self, viewport: &mut Viewport) -> bool {
+        if viewport.id == self.0.borrow().main_id {
+            return self.0.borrow().main_minimized;
+        }
+        self.with_window(viewport.id, false, |platform_window| {
+            platform_window
+                .window
+                .as_ref()
+                .and_then(|window| window.is_minimized())
+                .unwrap_or(false)
+        })
+    }
+
+    fn set_window_title(&mut self, viewport: &mut Viewport, title: &str) {
+        self.with_window(viewport.id, (), |platform_window| {
+            platform_window.title = title.to_string();
+            if let Some(window) = &platform_window.window {
+                window.set_title(title);
+            }
+        })
+    }
+
+    fn set_window_alpha(&mut self, _viewport: &mut Viewport, _alpha: f32) {}
+
+    fn update_window(&mut self, _viewport: &mut Viewport) {}
+
+    // Viewports are rendered and presented by `UiViewports::update`
+    fn render_window(&mut self, _viewport: &mut Viewport) {}
+
+    fn swap_buffers(&mut self, _viewport: &mut Viewport) {}
+
+    fn create_vk_surface(
+        &mut self,
+        _viewport: &mut Viewport,
+        _instance: u64,
+        _out_surface: &mut u64,
+    ) -> i32 {
+        vk::Result::ERROR_EXTENSION_NOT_PRESENT.as_raw()
+    }
+}
+
+/// Renders imgui viewports dragged out of the main window into OS windows of their own,
+/// each with its own surface and swapchain. Created by [`UIOverlay::enable_viewports`]
+pub struct UiViewports {
+    platform_windows: Rc<RefCell<PlatformWindows>>,
+    targets: HashMap<Id, ViewportTarget>,
+    format: vk::Format,
+    render_pass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    command_pool: vk::CommandPool,
+    /// imgui frame whose platform windows were last updated
+    platform_frame: i32,
+    device: Rc<Device>,
+}
+
+impl UiViewports {
+    pub(crate) fn new(
+        imgui: &mut Context,
+        format: vk::Format,
+        pipeline_cache: vk::PipelineCache,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        font_extent: vk::Extent2D,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let render_pass = create_renderpass(format, &device)?;
+        let (pipeline_layout, pipeline) = UIOverlay::create_pipeline(
+            descriptor_set_layout,
+            render_pass,
+            None,
+            pipeline_cache,
+            font_extent,
+            device.clone(),
+        )?;
+        let command_pool = unsafe {
+            device.create_command_pool(
+                &vk::CommandPoolCreateInfo::builder()
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+                    .queue_family_index(device.graphic_queue_family_index())
+                    .build(),
+                None,
+            )?
+        };
+
+        let platform_windows = Rc::new(RefCell::new(PlatformWindows {
+            main_id: imgui.main_viewport().id,
+            ..Default::default()
+        }));
+        imgui.set_platform_backend(WinitPlatform(platform_windows.clone()));
+        // imgui only checks the main viewport has a platform handle
+        imgui.main_viewport_mut().platform_handle = Rc::as_ptr(&platform_windows) as *mut c_void;
+        let io = imgui.io_mut();
+        io.backend_flags |=
+            BackendFlags::PLATFORM_HAS_VIEWPORTS | BackendFlags::RENDERER_HAS_VIEWPORTS;
+        io.config_flags |= ConfigFlags::VIEWPORTS_ENABLE;
+
+        Ok(Self {
+            platform_windows,
+            targets: HashMap::new(),
+            format,
+            render_pass,
+            pipeline_layout,
+            pipeline,
+            command_pool,
+            platform_frame: 0,
+            device,
+        })
+    }
+
+    pub(crate) fn set_main_window(&mut self, imgui: &mut Context, main_window: MainWindow) {
+        imgui
+            .platform_io_mut()
+            .monitors
+            .replace_from_slice(&main_window.monitors);
+        let mut platform_windows = self.platform_windows.borrow_mut();
+        platform_windows.main_origin = main_window.origin;
+        platform_windows.main_size = main_window.size;
+        platform_windows.main_focused = main_window.focused;
+        platform_windows.main_minimized = main_window.minimized;
+    }
+
+    /// Turns a position in the main window into OS screen coordinates
+    pub(crate) fn to_screen(&self, position: [f32; 2]) -> [f32; 2] {
+        let origin = self.platform_windows.borrow().main_origin;
+        [origin[0] + position[0], origin[1] + position[1]]
+    }
+
+    pub(crate) fn on_event(
+        &mut self,
+        imgui: &mut Context,
+        window_id: WindowId,
+        event: &WindowEvent,
+    ) {
+        let Some((id, origin)) = self.find_window(window_id) else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => {
+                if let Some(viewport) = imgui.viewport_by_id_mut(id) {
+                    viewport.platform_request_close = true;
+                }
+            }
+            WindowEvent::Moved(_) => {
+                if let Some(viewport) = imgui.viewport_by_id_mut(id) {
+                    viewport.platform_request_move = true;
+                }
+            }
+            WindowEvent::Resized(_) => {
+                if let Some(viewport) = imgui.viewport_by_id_mut(id) {
+                    viewport.platform_request_resize = true;
+                }
+                if let Some(target) = self.targets.get_mut(&id) {
+                    target.out_of_date = true;
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                imgui.io_mut().add_mouse_pos_event([
+                    origin[0] + position.x as f32,
+                    origin[1] + position.y as f32,
+                ]);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some(button) = imgui_mouse_button(*button) {
+                    imgui
+                        .io_mut()
+                        .add_mouse_button_event(button, *state == ElementState::Pressed);
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                imgui.io_mut().add_mouse_wheel_event(wheel_lines(*delta));
+            }
+            _ => {}
+        }
+    }
+
+    /// Creates and destroys the windows imgui asked for and presents the viewports outside the
+    /// main window. imgui allows this once per frame and only after it was rendered
+    pub(crate) fn update(
+        &mut self,
+        imgui: &mut Context,
+        rendered_frame: i32,
+        descriptor_set: vk::DescriptorSet,
+        window_target: &EventLoopWindowTarget<()>,
+    ) -> RenderResult<()> {
+        let frame = unsafe { imgui::sys::igGetFrameCount() };
+        if rendered_frame != frame || self.platform_frame == frame {
+            return Ok(());
+        }
+        self.platform_frame = frame;
+
+        imgui.update_platform_windows();
+        self.drop_retired_windows()?;
+        self.create_windows(window_target)?;
+
+        let main_id = imgui.main_viewport().id;
+        for viewport in imgui.viewports() {
+            if viewport.id != main_id && !viewport.flags.contains(ViewportFlags::MINIMIZED) {
+                self.present(viewport.id, viewport.draw_data(), descriptor_set)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_window(&self, window_id: WindowId) -> Option<(Id, [f32; 2])> {
+        self.platform_windows
+            .borrow()
+            .windows
+            .iter()
+            .find_map(|(id, platform_window)| {
+                let window = platform_window.window.as_ref()?;
+                (window.id() == window_id).then(|| {
+                    let origin = window.inner_position().unwrap_or_default();
+                    (*id, [origin.x as f32, origin.y as f32])
+                })
+            })
+    }
+
+    fn drop_retired_windows(&mut self) -> RenderResult<()> {
+        let retired = std::mem::take(&mut self.platform_windows.borrow_mut().retired);
+        if retired.is_empty() {
+            return Ok(());
+        }
+        unsafe { self.device.device_wait_idle()? };
+        retired.iter().for_each(|(id, _)| {
+            self.targets.remove(id);
+        });
+        // The windows go last, after the surfaces of their swapchains
+        drop(retired);
+        Ok(())
+    }
+
+    fn create_windows(&mut self, window_target: &EventLoopWindowTarget<()>) -> RenderResult<()> {
+        let mut platform_windows = self.platform_windows.borrow_mut();
+        for (id, platform_window) in platform_windows
+            .windows
+            .iter_mut()
+            .filter(|(_, platform_window)| platform_window.window.is_none())
+        {
+            let window = WindowBuilder::new()
+                .with_title(&platform_window.title)
+                .with_decorations(platform_window.decorations)
+                .with_visible(platform_window.visible)
+                .with_position(PhysicalPosition::new(
+                    platform_window.pos[0] as i32,
+                    platform_window.pos[1] as i32,
+                ))
+                .with_inner_size(PhysicalSize::new(
+                    platform_window.size[0].max(1.) as u32,
+                    platform_window.size[1].max(1.) as u32,
+                ))
+                .build(window_target)?;
+            self.targets.insert(
+                *id,
+                ViewportTarget::new(
+                    &window,
+                    self.format,
+                    self.render_pass,
+                    self.command_pool,
+                    self.device.clone(),
+                )?,
+            );
+            platform_window.window = Some(window);
+        }
+        Ok(())
+    }
+
+    fn present(
+        &mut self,
+        id: Id,
+        draw_data: &DrawData,
+        descriptor_set: vk::DescriptorSet,
+    ) -> RenderResult<()> {
+        let Some(target) = self.targets.get_mut(&id) else {
+            return Ok(());
+        };
+        if target.out_of_date {
+            let platform_windows = self.platform_windows.borrow();
+            let Some(window) = platform_windows
+                .windows
+                .get(&id)
+                .and_then(|platform_window| platform_window.window.as_ref())
+            else {
+                return Ok(());
+            };
+            // A zero sized swapchain is invalid, refit once the window is restored
+            if !target.refit(window)? {
+                return Ok(());
+            }
+        }
+
+        let frame = &mut target.frames[target.frame_index];
+        unsafe {
+            self.device
+                .wait_for_fences(&[frame.in_flight_fence], true, u64::MAX)?
+        };
+        let image_index = match target
+            .swapchain_batch
+            .acquire_next_image(frame.image_available_semaphore)
+        {
+            Ok((image_index, _)) => image_index,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                target.out_of_date = true;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        unsafe { self.device.reset_fences(&[frame.in_flight_fence])? };
+
+        upload_draw_data(
+            draw_data,
+            &mut frame.vertex_buffer,
+            &mut frame.indice_buffer,
+            &self.device,
+        )?;
+
+        let extent = target.surface.extent();
+        let command_buffer = frame.command_buffer;
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::default())?;
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &vk::RenderPassBeginInfo::builder()
+                    .render_pass(self.render_pass)
+                    .framebuffer(target.framebuffers[image_index as usize])
+                    .render_area(extent_helper::scissor_from_extent(extent))
+                    .clear_values(&[vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: [0., 0., 0., 1.],
+                        },
+                    }])
+                    .build(),
+                vk::SubpassContents::INLINE,
+            );
+        }
+        extent_helper::cmd_set_viewport_and_scissor(&self.device, command_buffer, extent);
+        cmd_draw_data(
+            &self.device,
+            command_buffer,
+            (self.pipeline_layout, self.pipeline),
+            descriptor_set,
+            draw_data,
+            (&frame.vertex_buffer, &frame.indice_buffer),
+            extent,
+        );
+        unsafe {
+            self.device.cmd_end_render_pass(command_buffer);
+            self.device.end_command_buffer(command_buffer)?;
+
+            let submit_info = vk::SubmitInfo::builder()
+                .wait_semaphores(&[frame.image_available_semaphore])
+                .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
+                .command_buffers(&[command_buffer])
+                .signal_semaphores(&[frame.render_finished_semaphore])
+                .build();
+            self.device.queue_submit(
+                self.device.graphic_queue(),
+                &[submit_info],
+                frame.in_flight_fence,
+            )?;
+        }
+
+        match target.swapchain_batch.queue_present(
+            image_index,
+            &[frame.render_finished_semaphore],
+            &self.device.graphic_queue(),
+        ) {
+            Ok(suboptimal) => target.out_of_date |= suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => target.out_of_date = true,
+            Err(e) => return Err(e.into()),
+        }
+        target.frame_index = (target.frame_index + 1) % FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT;
+        Ok(())
+    }
+}
+
+impl Drop for UiViewports {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.targets.clear();
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+        }
+    }
+}
+
+struct ViewportFrame {
+    command_buffer: vk::CommandBuffer,
+    in_flight_fence: vk::Fence,
+    image_available_semaphore: vk::Semaphore,
+    render_finished_semaphore: vk::Semaphore,
+    vertex_buffer: Buffer<DrawVert>,
+    indice_buffer: Buffer<DrawIdx>,
+}
+
+/// Swapchain of a viewport window and what is needed to draw into it
+struct ViewportTarget {
+    swapchain_batch: SwapChainBatch,
+    surface: Rc<Surface>,
+    framebuffers: Vec<vk::Framebuffer>,
+    frames: PerFrame<ViewportFrame>,
+    frame_index: usize,
+    /// The swapchain no longer matches the window and is refit before the next present
+    out_of_date: bool,
+    render_pass: vk::RenderPass,
+    command_pool: vk::CommandPool,
+    device: Rc<Device>,
+}
+
+impl ViewportTarget {
+    fn new(
+        window: &Window,
+        format: vk::Format,
+        render_pass: vk::RenderPass,
+        command_pool: vk::CommandPool,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let surface = Rc::new(Surface::new(window, device.instance().clone(), format)?);
+        let swapchain_batch = SwapChainBatch::new(surface.clone(), device.clone())?;
+        let framebuffers =
+            create_framebuffers(&swapchain_batch, render_pass, surface.extent(), &device)?;
+        let command_buffers = unsafe {
+            device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u32)
+                    .build(),
+            )?
+        };
+        let frames = PerFrame::try_new(command_buffers.len(), |i| -> RenderResult<_> {
+            Ok(unsafe {
+                ViewportFrame {
+                    command_buffer: command_buffers[i],
+                    in_flight_fence: device.create_fence(
+                        &vk::FenceCreateInfo::builder()
+                            .flags(vk::FenceCreateFlags::SIGNALED)
+                            .build(),
+                        None,
+                    )?,
+                    image_available_semaphore: device
+                        .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?,
+                    render_finished_semaphore: device
+                        .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?,
+                    vertex_buffer: UIOverlay::vertex_buffer(device.clone(), 1)?,
+                    indice_buffer: UIOverlay::indice_buffer(device.clone(), 1)?,
+                }
+            })
+        })?;
+
+        Ok(Self {
+            swapchain_batch,
+            surface,
+            framebuffers,
+            frames,
+            frame_index: 0,
+            out_of_date: false,
+            render_pass,
+            command_pool,
+            device,
+        })
+    }
+
+    /// Returns `false` while the window is minimized, the swapchain is kept until it is not
+    fn refit(&mut self, window: &Window) -> RenderResult<bool> {
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(false);
+        }
+        unsafe {
+            self.device.device_wait_idle()?;
+            if self.surface.refit_surface_attribute(window)?.is_none() {
+                return Ok(false);
+            }
+            self.swapchain_batch.recreate()?;
+            self.framebuffers
+                .iter()
+                .for_each(|framebuffer| self.device.destroy_framebuffer(*framebuffer, None));
+        }
+        self.framebuffers = create_framebuffers(
+            &self.swapchain_batch,
+            self.render_pass,
+            self.surface.extent(),
+            &self.device,
+        )?;
+        self.out_of_date = false;
+        Ok(true)
+    }
+}
+
+impl Drop for ViewportTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.frames.iter().for_each(|frame| {
+                self.device
+                    .destroy_semaphore(frame.image_available_semaphore, None);
+                self.device
+                    .destroy_semaphore(frame.render_finished_semaphore, None);
+                self.device.destroy_fence(frame.in_flight_fence, None);
+            });
+            let command_buffers = self
+                .frames
+                .iter()
+                .map(|frame| frame.command_buffer)
+                .collect::<Vec<_>>();
+            self.device
+                .free_command_buffers(self.command_pool, &command_buffers);
+            self.framebuffers
+                .iter()
+                .for_each(|framebuffer| self.device.destroy_framebuffer(*framebuffer, None));
+        }
+    }
+}
+
+fn create_framebuffers(
+    swapchain_batch: &SwapChainBatch,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    device: &Device,
+) -> RenderResult<Vec<vk::Framebuffer>> {
+    swapchain_batch
+        .image_views()
+        .iter()
+        .map(|image_view| {
+            let create_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass)
+                .attachments(&[*image_view])
+                .width(extent.width)
+                .height(extent.height)
+                .layers(1)
+                .build();
+            Ok(unsafe { device.create_framebuffer(&create_info, None)? })
+        })
+        .collect()
+}
+
+/// Color only, viewports hold nothing but the UI
+fn create_renderpass(format: vk::Format, device: &Rc<Device>) -> RenderResult<vk::RenderPass> {
+    Ok(RenderPassBuilder::new(device.clone())
+        .attachment(
+            format,
+            vk::AttachmentLoadOp::CLEAR,
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        )
+        .subpass(&[0], None)
+        .dependency(
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::default())
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )
+                .build(),
+        )
+        .build()?)
+}
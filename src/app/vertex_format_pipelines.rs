@@ -0,0 +1,181 @@
+use std::{any::TypeId, rc::Rc};
+
+use ash::vk;
+
+use crate::{error::RenderResult, mesh::VertexLayout, vulkan_wrappers::Device};
+
+/// Vertex input of a [`VertexLayout`], or of a layout and its per-instance data
+pub struct VertexFormat {
+    type_id: TypeId,
+    pub bindings: Vec<vk::VertexInputBindingDescription>,
+    pub attributes: Vec<vk::VertexInputAttributeDescription>,
+    dynamic_bindings: Vec<vk::VertexInputBindingDescription2EXT>,
+    dynamic_attributes: Vec<vk::VertexInputAttributeDescription2EXT>,
+}
+
+impl VertexFormat {
+    /// `V` at binding 0, bound with [`VertexFormatPipelines::cmd_bind`]`::<V>`
+    pub fn of<V: VertexLayout + 'static>() -> Self {
+        Self::new(
+            TypeId::of::<V>(),
+            vec![V::binding_description(0)],
+            V::attribute_descriptions(0),
+        )
+    }
+
+    /// [`VertexLayout::with_instances`], bound with [`VertexFormatPipelines::cmd_bind`]`::<(V, I)>`
+    pub fn with_instances<V: VertexLayout + 'static, I: VertexLayout + 'static>() -> Self {
+        let (bindings, attributes) = V::with_instances::<I>();
+        Self::new(TypeId::of::<(V, I)>(), bindings, attributes)
+    }
+
+    fn new(
+        type_id: TypeId,
+        bindings: Vec<vk::VertexInputBindingDescription>,
+        attributes: Vec<vk::VertexInputAttributeDescription>,
+    ) -> Self {
+        let dynamic_bindings = bindings
+            .iter()
+            .map(|binding| {
+                vk::VertexInputBindingDescription2EXT::builder()
+                    .binding(binding.binding)
+                    .stride(binding.stride)
+                    .input_rate(binding.input_rate)
+                    .divisor(1)
+                    .build()
+            })
+            .collect();
+        let dynamic_attributes = attributes
+            .iter()
+            .map(|attribute| {
+                vk::VertexInputAttributeDescription2EXT::builder()
+                    .location(attribute.location)
+                    .binding(attribute.binding)
+                    .format(attribute.format)
+                    .offset(attribute.offset)
+                    .build()
+            })
+            .collect();
+        Self {
+            type_id,
+            bindings,
+            attributes,
+            dynamic_bindings,
+            dynamic_attributes,
+        }
+    }
+}
+
+/// Pipelines differing only in the vertex format they draw: a single one whose vertex
+/// input is set when binding it where `VK_EXT_vertex_input_dynamic_state` is supported,
+/// one per format otherwise.
+pub struct VertexFormatPipelines {
+    formats: Vec<VertexFormat>,
+    dynamic: bool,
+    /// One per format, or a single one with a dynamic vertex input
+    pipelines: Vec<(vk::PipelineLayout, vk::Pipeline)>,
+    device: Rc<Device>,
+}
+
+impl VertexFormatPipelines {
+    /// `build` returns the pipeline of a format, or with `None` the one whose
+    /// [`super::PipelineBuilder::dynamic_vertex_input`] is set, which it is called with
+    /// when the device supports it
+    pub fn new<F>(formats: Vec<VertexFormat>, device: Rc<Device>, build: F) -> RenderResult<Self>
+    where
+        F: Fn(Option<&VertexFormat>) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)>,
+    {
+        let dynamic = device.supports_vertex_input_dynamic_state();
+        Self::build(formats, dynamic, device, build)
+    }
+
+    /// One pipeline per format even where the vertex input could be dynamic
+    pub fn per_format<F>(
+        formats: Vec<VertexFormat>,
+        device: Rc<Device>,
+        build: F,
+    ) -> RenderResult<Self>
+    where
+        F: Fn(Option<&VertexFormat>) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)>,
+    {
+        Self::build(formats, false, device, build)
+    }
+
+    fn build<F>(
+        formats: Vec<VertexFormat>,
+        dynamic: bool,
+        device: Rc<Device>,
+        build: F,
+    ) -> RenderResult<Self>
+    where
+        F: Fn(Option<&VertexFormat>) -> RenderResult<(vk::PipelineLayout, vk::Pipeline)>,
+    {
+        // Built into `pipelines` so that a failure destroys the ones built before
+        let mut pipelines = Self {
+            formats,
+            dynamic,
+            pipelines: vec![],
+            device,
+        };
+        if dynamic {
+            pipelines.pipelines.push(build(None)?);
+        } else {
+            for format_index in 0..pipelines.formats.len() {
+                let pipeline = build(Some(&pipelines.formats[format_index]))?;
+                pipelines.pipelines.push(pipeline);
+            }
+        }
+        Ok(pipelines)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    pub fn pipeline_count(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// The layouts of all pipelines are alike, so any one binds their descriptor sets
+    pub fn pipeline_layout(&self) -> vk::PipelineLayout {
+        self.pipelines[0].0
+    }
+
+    /// Binds the pipeline drawing `V`, the type a format was created `of`, and sets its
+    /// vertex input when dynamic
+    pub fn cmd_bind<V: 'static>(&self, command_buffer: vk::CommandBuffer) -> RenderResult<()> {
+        let format_index = self
+            .formats
+            .iter()
+            .position(|format| format.type_id == TypeId::of::<V>())
+            .expect("No pipeline for this vertex format");
+        let pipeline = match self.dynamic {
+            true => self.pipelines[0].1,
+            false => self.pipelines[format_index].1,
+        };
+        unsafe {
+            self.device
+                .cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline)
+        };
+        if self.dynamic {
+            let format = &self.formats[format_index];
+            self.device.cmd_set_vertex_input(
+                command_buffer,
+                &format.dynamic_bindings,
+                &format.dynamic_attributes,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VertexFormatPipelines {
+    fn drop(&mut self) {
+        self.pipelines
+            .iter()
+            .for_each(|&(layout, pipeline)| unsafe {
+                self.device.destroy_pipeline(pipeline, None);
+                self.device.destroy_pipeline_layout(layout, None);
+            });
+    }
+}
@@ -1,33 +1,35 @@
 use std::{cell::RefCell, rc::Rc, time::SystemTime};
 
 use ash::vk::{self, DescriptorSetLayoutBinding};
+use log::Level;
 use winit::{
-    dpi::PhysicalSize,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
     event_loop::EventLoop,
     platform::run_return::EventLoopExtRunReturn,
     window::{Window, WindowBuilder},
 };
 
-use super::{FixedVulkanStuff, UIOverlay};
+#[cfg(feature = "docking")]
+use super::ui_viewports::MainWindow;
+use super::{
+    ui_overlay::{imgui_mouse_button, wheel_lines},
+    ClearValue, FixedVulkanStuff, MemoryPanel, MonitorInfo, PerFrame, UIOverlay,
+};
 use crate::{
-    camera::{Camera, Direction},
-    error::RenderResult,
-    vulkan_wrappers::{Device, Instance, VulkanApiVersion, VulkanDebugInfoStrategy},
+    camera::{Camera, CameraController, Direction},
+    error::{RenderError, RenderResult},
+    jobs::JobSystem,
+    logging::{report, LogBuffer},
+    vulkan_wrappers::{
+        ApiDump, Device, Instance, Profile, QueueRequest, SurfaceChange, ValidationFeatures,
+        VulkanApiVersion, VulkanDebugInfoStrategy,
+    },
 };
 
-#[derive(Clone, Copy)]
-pub struct ClearValue {
-    pub color: vk::ClearValue,
-    pub depth_stencil: vk::ClearValue,
-}
-
-impl ClearValue {
-    pub fn to_array(&self) -> [vk::ClearValue; 2] {
-        [self.color, self.depth_stencil]
-    }
-}
-
 pub struct FrameCounter {
     pub double_buffer_frame: usize,
     pub frame_count: u64,
@@ -75,18 +77,90 @@ impl Default for FrameCounter {
     }
 }
 
+/// The frame being drawn by [`WindowApp::draw_frame`]
+#[derive(Clone, Copy, Debug)]
+pub struct FrameContext {
+    /// Index of the per-frame resources, see [`PerFrame`]
+    pub frame_index: usize,
+    /// Swapchain image the frame presents
+    pub image_index: usize,
+    /// `graphic_command_buffers[frame_index]`, begun before [`WindowApp::record`] and ended
+    /// after it
+    pub command_buffer: vk::CommandBuffer,
+}
+
 pub trait WindowApp {
     fn new(event_loop: &EventLoop<()>) -> Self;
-    fn draw_frame(&mut self);
 
-    fn on_window_resized(&mut self, size: PhysicalSize<u32>);
+    /// Updates what the frame reads once its fence has been waited for: uniform buffers,
+    /// read backs of earlier frames, the UI
+    fn prepare(&mut self, _frame: &FrameContext) {}
+
+    /// Records the frame into [`FrameContext::command_buffer`]
+    fn record(&mut self, frame: &FrameContext);
+
+    /// Submits the frame to the graphic queue and presents it
+    fn submit(&mut self, frame: &FrameContext) {
+        let (window, fixed_vulkan_stuff) = self.window_and_fixed_vulkan_stuff();
+        fixed_vulkan_stuff
+            .frame_queue_submit_and_present(frame.frame_index, frame.image_index, window)
+            .unwrap();
+    }
+
+    /// Acquires a swapchain image, then runs [`WindowApp::prepare`], [`WindowApp::record`]
+    /// and [`WindowApp::submit`]. The frame is skipped when the swapchain was out of date.
+    fn draw_frame(&mut self) {
+        let frame_index = self.frame_counter().double_buffer_frame;
+        let (window, fixed_vulkan_stuff) = self.window_and_fixed_vulkan_stuff();
+        let (image_index, skipped) = fixed_vulkan_stuff
+            .frame_get_image_index_to_draw(frame_index, window)
+            .unwrap();
+        if skipped {
+            return;
+        }
+        let frame = FrameContext {
+            frame_index,
+            image_index,
+            command_buffer: fixed_vulkan_stuff.graphic_command_buffers[frame_index],
+        };
+
+        self.prepare(&frame);
+        self.fixed_vulkan_stuff()
+            .frame_begin_command_buffer(frame_index)
+            .expect("Fail to begin command buffer");
+        self.record(&frame);
+        self.fixed_vulkan_stuff()
+            .frame_end_command_buffer(frame_index)
+            .expect("Fail to end command buffer");
+        self.submit(&frame);
+
+        self.frame_counter_mut().update();
+    }
+
+    /// Requests a refit of the swapchain, coalesced with the other resize events
+    fn on_window_resized(&mut self, _size: PhysicalSize<u32>) {
+        self.fixed_vulkan_stuff_mut().request_refit();
+    }
+
     fn window_title() -> String;
     fn window(&self) -> &Window;
 
     fn frame_counter(&self) -> &FrameCounter;
+    fn frame_counter_mut(&mut self) -> &mut FrameCounter;
     fn camera(&mut self) -> &mut Camera;
+    fn camera_with_controller(&mut self) -> (&mut Camera, &mut CameraController);
     fn ui(&mut self) -> &mut UIOverlay;
 
+    fn fixed_vulkan_stuff(&self) -> &FixedVulkanStuff;
+
+    fn fixed_vulkan_stuff_mut(&mut self) -> &mut FixedVulkanStuff;
+
+    /// Both at once, the swapchain is refitted to the window
+    fn window_and_fixed_vulkan_stuff(&mut self) -> (&Window, &mut FixedVulkanStuff);
+
+    /// Both at once, the UI overlay follows the swapchain
+    fn ui_and_fixed_vulkan_stuff(&mut self) -> (&mut UIOverlay, &FixedVulkanStuff);
+
     fn descriptor_pool_sizes() -> Vec<vk::DescriptorPoolSize>;
     fn descriptor_set_layout_bindings() -> Vec<DescriptorSetLayoutBinding>;
 
@@ -95,19 +169,66 @@ pub trait WindowApp {
             || self.frame_counter().count_since_last_update()
                 < FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT as u64
         {
-            let fps = self.frame_counter().fps;
-            let double_buffer_frame = self.frame_counter().double_buffer_frame;
-            self.ui().imgui_context.io_mut().display_size = self.window_size().into();
-            let ui = self.ui().imgui_context.new_frame();
-            ui.window("Vulkan Examples").build(|| {
-                ui.text(Self::window_title());
-                infos.iter().for_each(|info| ui.text(info));
-                ui.text(format!("fps: {fps:.2}"));
-            });
-            self.ui().update(double_buffer_frame).unwrap();
+            self.update_ui_with(infos, |_| {});
         }
     }
 
+    /// Like [`WindowApp::update_ui`], but rebuilt every frame so `widgets` stay interactive
+    fn update_ui_with<T: AsRef<str>, F: FnOnce(&imgui::Ui)>(&mut self, infos: &[T], widgets: F) {
+        let fps = self.frame_counter().fps;
+        let double_buffer_frame = self.frame_counter().double_buffer_frame;
+        // The refresh rate of the display bounds the fps of FIFO presentation
+        let monitor = MonitorInfo::current(self.window());
+        let latency = self.fixed_vulkan_stuff().latency_summary();
+        let upload = upload_info(&self.fixed_vulkan_stuff().device);
+        let memory = MemoryPanel::new(&self.fixed_vulkan_stuff().device);
+        let gpu_times = self
+            .fixed_vulkan_stuff()
+            .gpu_timer
+            .as_ref()
+            .map(|gpu_timer| gpu_timer.summary())
+            .unwrap_or_default();
+        let jobs = JobSystem::try_global()
+            .map(|jobs| jobs.summary())
+            .unwrap_or_default();
+        self.ui().imgui_context.io_mut().display_size = self.window_size().into();
+        let framebuffer_extent = self.fixed_vulkan_stuff().surface.extent();
+        self.ui().set_framebuffer_extent(framebuffer_extent);
+        #[cfg(feature = "docking")]
+        let docking = {
+            let main_window = MainWindow::of(self.window());
+            self.ui().prepare_viewports(main_window);
+            self.ui().docking_enabled()
+        };
+        let mut log_console = self.ui().log_console.take();
+        let ui = self.ui().imgui_context.new_frame();
+        #[cfg(feature = "docking")]
+        if docking {
+            ui.dockspace_over_main_viewport();
+        }
+        ui.window("Vulkan Examples").build(|| {
+            ui.text(Self::window_title());
+            infos.iter().for_each(|info| ui.text(info));
+            ui.text(format!("fps: {fps:.2}"));
+            if let Some(monitor) = monitor {
+                ui.text(format!("display: {monitor}"));
+            }
+            latency.iter().for_each(|line| ui.text(line));
+            ui.text(&upload);
+            gpu_times.iter().for_each(|line| ui.text(line));
+            if !jobs.is_empty() && ui.collapsing_header("Jobs", imgui::TreeNodeFlags::empty()) {
+                jobs.iter().for_each(|line| ui.text(line));
+            }
+            memory.draw(ui);
+            widgets(ui);
+        });
+        if let (Some(log_console), Some(buffer)) = (&mut log_console, LogBuffer::try_global()) {
+            log_console.draw(ui, buffer);
+        }
+        self.ui().log_console = log_console;
+        self.ui().update(double_buffer_frame).unwrap();
+    }
+
     fn window_size(&self) -> PhysicalSize<u32> {
         self.window().inner_size()
     }
@@ -123,9 +244,18 @@ pub trait WindowApp {
     fn render_loop(&mut self, event_loop: &RefCell<EventLoop<()>>) {
         event_loop
             .borrow_mut()
-            .run_return(|event, _, control_flow| {
+            .run_return(|event, _window_target, control_flow| {
                 control_flow.set_poll();
                 match event {
+                    // Viewport windows of the UI must not close the app or move the camera
+                    Event::WindowEvent { window_id, .. } if window_id != self.window().id() =>
+                    {
+                        #[cfg(feature = "docking")]
+                        if let Event::WindowEvent { event, .. } = &event {
+                            self.ui().on_viewport_event(window_id, event);
+                        }
+                    }
+
                     Event::WindowEvent {
                         event:
                             WindowEvent::CloseRequested
@@ -160,11 +290,38 @@ pub trait WindowApp {
                         ..
                     } => self.on_keyboard_input(key_code),
 
+                    Event::WindowEvent {
+                        event: WindowEvent::CursorMoved { position, .. },
+                        ..
+                    } => self.on_cursor_moved(position),
+
+                    Event::WindowEvent {
+                        event: WindowEvent::MouseInput { state, button, .. },
+                        ..
+                    } => self.on_mouse_input(button, state),
+
+                    Event::WindowEvent {
+                        event: WindowEvent::MouseWheel { delta, .. },
+                        ..
+                    } => self.on_mouse_wheel(delta),
+
                     Event::MainEventsCleared => {
                         let size = self.window_size();
                         if size.width > 0 && size.height > 0 {
+                            let pre_rotation =
+                                self.fixed_vulkan_stuff().surface.pre_rotation_angle();
+                            self.camera().set_pre_rotation(pre_rotation);
                             self.draw_frame();
+                            if let Some(change) =
+                                self.fixed_vulkan_stuff_mut().take_surface_change()
+                            {
+                                let (ui, fixed_vulkan_stuff) = self.ui_and_fixed_vulkan_stuff();
+                                ui.on_surface_changed(fixed_vulkan_stuff, change).unwrap();
+                                self.on_surface_changed(change);
+                            }
                         }
+                        #[cfg(feature = "docking")]
+                        self.ui().update_viewports(_window_target).unwrap();
                     }
                     _ => (),
                 }
@@ -191,6 +348,47 @@ pub trait WindowApp {
         }
     }
 
+    /// Vulkan version the instance is created with, some device extensions
+    /// such as `VK_KHR_video_queue` are only enabled from Vulkan 1.1
+    fn vulkan_api_version() -> VulkanApiVersion {
+        VulkanApiVersion::V1_0
+    }
+
+    /// Enables the validation layer's debugPrintf, printing `debugPrintfEXT` output of
+    /// shaders to the log, see [`crate::vulkan_wrappers::InstanceBuilder::shader_printf`]
+    fn shader_printf() -> bool {
+        false
+    }
+
+    /// Extra validation layer checks, by default chosen per run with the
+    /// `--gpu-assisted-validation`, `--sync-validation` and `--best-practices` flags
+    fn validation_features() -> ValidationFeatures {
+        ValidationFeatures::from_args(std::env::args().skip(1))
+    }
+
+    /// Call capture of a frame range, by default requested with `--api-dump=FIRST:COUNT`
+    fn api_dump() -> Option<ApiDump> {
+        ApiDump::from_args(std::env::args().skip(1))
+    }
+
+    /// Extra queues to create, e.g. a low priority [`Device::UPLOAD_QUEUE`] for the
+    /// device local uploads
+    fn queue_requests() -> Vec<QueueRequest> {
+        vec![]
+    }
+
+    /// Profile the device must satisfy, by default chosen with `--profile=roadmap-2022`
+    /// or `--profile=<profiles JSON file>`
+    fn required_profile() -> RenderResult<Option<Profile>> {
+        Profile::from_args(std::env::args().skip(1))
+    }
+
+    /// Called after the frame whose present refitted the swapchain to a new extent or format,
+    /// once the UI overlay followed it. Targets following the window size are resized here.
+    /// After a format change the render pass of [`FixedVulkanStuff`] is new, pipelines built
+    /// against it are rebuilt.
+    fn on_surface_changed(&mut self, _change: SurfaceChange) {}
+
     fn on_keyboard_input(&mut self, key_code: VirtualKeyCode) {
         let duration = self.frame_counter().fps.recip() as f32;
         match key_code {
@@ -206,21 +404,83 @@ pub trait WindowApp {
             VirtualKeyCode::L => self.camera().rotate_in_time(Direction::Right, duration),
             VirtualKeyCode::U => self.camera().rotate_in_time(Direction::Front, duration),
             VirtualKeyCode::O => self.camera().rotate_in_time(Direction::Back, duration),
+            // Bounds latency, only effective with VK_KHR_present_wait
+            VirtualKeyCode::F10 => {
+                let frame_latency = &mut self.fixed_vulkan_stuff_mut().frame_latency;
+                frame_latency.max_queued_presents = match frame_latency.max_queued_presents {
+                    Some(_) => None,
+                    None => Some(1),
+                };
+            }
+            VirtualKeyCode::F11 => {
+                super::toggle_fullscreen(self.window());
+            }
+            VirtualKeyCode::Grave => {
+                if let Some(log_console) = &mut self.ui().log_console {
+                    log_console.open = !log_console.open;
+                }
+            }
             _ => {}
         }
     }
 
+    fn on_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.ui().add_mouse_pos_event(position);
+        let (camera, controller) = self.camera_with_controller();
+        controller.on_cursor_moved(camera, position);
+    }
+
+    /// Presses over the UI do not reach the camera, releases always do to end drags
+    fn on_mouse_input(&mut self, button: MouseButton, state: ElementState) {
+        let pressed = state == ElementState::Pressed;
+        if !(pressed && self.ui().imgui_context.io().want_capture_mouse) {
+            self.camera_with_controller()
+                .1
+                .on_mouse_button(button, pressed);
+        }
+        if let Some(button) = imgui_mouse_button(button) {
+            self.ui()
+                .imgui_context
+                .io_mut()
+                .add_mouse_button_event(button, pressed);
+        }
+    }
+
+    fn on_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let [horizontal, vertical] = wheel_lines(delta);
+        let io = self.ui().imgui_context.io_mut();
+        io.add_mouse_wheel_event([horizontal, vertical]);
+        if !io.want_capture_mouse {
+            let (camera, controller) = self.camera_with_controller();
+            controller.on_mouse_wheel(camera, vertical);
+        }
+    }
+
     fn create_fixed_vulkan_stuff(window: &Window) -> RenderResult<FixedVulkanStuff> {
         let instance = Rc::new(
             Instance::builder()
                 .window(window)
                 .app_name_and_version(Self::window_title().as_str(), 0)
                 .engine_name_and_version("No Engine", 0)
-                .vulkan_api_version(VulkanApiVersion::V1_0)
+                .vulkan_api_version(Self::vulkan_api_version())
                 .debug_strategy(VulkanDebugInfoStrategy::DEFAULT_PRINT_ALL)
+                .shader_printf(Self::shader_printf())
+                .validation_features(Self::validation_features())
+                .api_dump(Self::api_dump())
                 .build()?,
         );
-        FixedVulkanStuff::new(window, instance)
+        let fixed_vulkan_stuff =
+            FixedVulkanStuff::with_queue_requests(window, instance, &Self::queue_requests())?;
+        if let Some(profile) = Self::required_profile()? {
+            let profile_report = profile.check(&fixed_vulkan_stuff.device)?;
+            if !profile_report.is_satisfied() {
+                return Err(RenderError::PhysicalDeviceNotSupported(
+                    profile_report.to_string(),
+                ));
+            }
+            report(Level::Info, "profile", format_args!("{profile_report}"));
+        }
+        Ok(fixed_vulkan_stuff)
     }
 
     fn create_descriptor_pool(device: &Device) -> RenderResult<vk::DescriptorPool> {
@@ -246,27 +506,36 @@ pub trait WindowApp {
         pool: vk::DescriptorPool,
         descriptor_set_layout: vk::DescriptorSetLayout,
         device: &Device,
-    ) -> RenderResult<[vk::DescriptorSet; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT]> {
+    ) -> RenderResult<PerFrame<vk::DescriptorSet>> {
         unsafe {
             let allocate_info = vk::DescriptorSetAllocateInfo::builder()
                 .descriptor_pool(pool)
                 .set_layouts(&[descriptor_set_layout; FixedVulkanStuff::MAX_FRAMES_IN_FLIGHT])
                 .build();
-            Ok(device
-                .allocate_descriptor_sets(&allocate_info)?
-                .try_into()
-                .unwrap())
+            Ok(PerFrame::from_vec(
+                device.allocate_descriptor_sets(&allocate_info)?,
+            ))
         }
     }
 }
 
+fn upload_info(device: &Device) -> String {
+    const MIB: f64 = 1024. * 1024.;
+    match device.upload_heap_size() {
+        Some(size) if device.supports_direct_upload() => {
+            format!(
+                "upload: direct, {:.0} MiB host visible VRAM",
+                size as f64 / MIB
+            )
+        }
+        Some(size) => format!("upload: staged, {:.0} MiB BAR", size as f64 / MIB),
+        None => "upload: staged".to_string(),
+    }
+}
+
 #[macro_export]
 macro_rules! impl_window_fns {
     ($app_ty: ty) => {
-        fn on_window_resized(&mut self, _size: PhysicalSize<u32>) {
-            self.window_resized = true;
-        }
-
         fn window_title() -> String {
             stringify!($app_ty).to_string()
         }
@@ -279,13 +548,37 @@ macro_rules! impl_window_fns {
             &self.frame_counter
         }
 
+        fn frame_counter_mut(&mut self) -> &mut FrameCounter {
+            &mut self.frame_counter
+        }
+
         fn camera(&mut self) -> &mut Camera {
             &mut self.camera
         }
 
+        fn camera_with_controller(&mut self) -> (&mut Camera, &mut CameraController) {
+            (&mut self.camera, &mut self.camera_controller)
+        }
+
         fn ui(&mut self) -> &mut UIOverlay {
             &mut self.ui_overlay
         }
+
+        fn fixed_vulkan_stuff(&self) -> &FixedVulkanStuff {
+            &self.fixed_vulkan_stuff
+        }
+
+        fn fixed_vulkan_stuff_mut(&mut self) -> &mut FixedVulkanStuff {
+            &mut self.fixed_vulkan_stuff
+        }
+
+        fn window_and_fixed_vulkan_stuff(&mut self) -> (&Window, &mut FixedVulkanStuff) {
+            (&self.window, &mut self.fixed_vulkan_stuff)
+        }
+
+        fn ui_and_fixed_vulkan_stuff(&mut self) -> (&mut UIOverlay, &FixedVulkanStuff) {
+            (&mut self.ui_overlay, &self.fixed_vulkan_stuff)
+        }
     };
 }
 
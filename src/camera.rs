@@ -1,13 +1,21 @@
 use std::f32::consts::{FRAC_PI_2, PI};
 
-use glam::{EulerRot, Mat4, Quat, Vec3};
+use glam::{EulerRot, Mat4, Quat, Vec2, Vec3, Vec4};
+use winit::{dpi::PhysicalPosition, event::MouseButton};
 
-#[derive(Clone, Copy, Debug)]
+use crate::mesh::AABB;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CameraType {
     FirstPerson,
     LookAt,
+    /// Looks at the orbit target from spherical angles around it, up is `+Y`
+    Orbit,
 }
 
+/// Pitch stays off the poles, where the view direction would be parallel to up
+const MAX_ORBIT_PITCH: f32 = FRAC_PI_2 - 0.01;
+
 pub struct CameraBuilder {
     translation: Vec3,
     rotation: (f32, f32, f32),
@@ -17,6 +25,7 @@ pub struct CameraBuilder {
     move_speed: f32,
     rotate_speed: f32,
     camera_type: CameraType,
+    orbit: Orbit,
 }
 
 impl Default for CameraBuilder {
@@ -32,6 +41,7 @@ impl Default for CameraBuilder {
             move_speed: 1.,
             rotate_speed: 1.,
             camera_type: CameraType::LookAt,
+            orbit: Orbit::default(),
         }
     }
 }
@@ -51,6 +61,8 @@ impl CameraBuilder {
             move_speed: self.move_speed,
             rotate_speed: self.rotate_speed,
             camera_type: self.camera_type,
+            orbit: self.orbit,
+            pre_rotation: 0.,
             view_mat: Mat4::IDENTITY,
             perspective_mat: Mat4::IDENTITY,
         };
@@ -100,6 +112,76 @@ impl CameraBuilder {
         self.camera_type = camera_type;
         self
     }
+
+    /// Makes an [`CameraType::Orbit`] camera at `radius` from `target`,
+    /// `yaw` and `pitch` in radius with `(0, 0)` looking from `+Z`
+    pub fn orbit(mut self, target: Vec3, radius: f32, yaw: f32, pitch: f32) -> Self {
+        assert!(radius > 0.);
+        self.camera_type = CameraType::Orbit;
+        self.orbit = Orbit {
+            target,
+            radius,
+            yaw,
+            pitch: pitch.clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH),
+        };
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Orbit {
+    target: Vec3,
+    radius: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            radius: 10.,
+            yaw: PI,
+            pitch: 0.,
+        }
+    }
+}
+
+impl Orbit {
+    fn eye(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        self.target + self.radius * Vec3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw)
+    }
+}
+
+/// Volume seen through a view-projection matrix, for culling on the CPU
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far planes in world space, `xyz` is the inward
+    /// normal and a point `p` is inside a plane when `dot(xyz, p) + w >= 0`
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// The depth of the projection goes from 0 to 1
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_projection.row(i));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z]
+                .map(|plane| plane / plane.truncate().length()),
+        }
+    }
+
+    /// Conservative, boxes near the corners of the frustum may pass while outside of it
+    pub fn intersects_aabb(&self, aabb: &AABB) -> bool {
+        !aabb.is_empty()
+            && self.planes.iter().all(|plane| {
+                // Corner of the box furthest along the normal
+                let corner = Vec3::select(plane.truncate().cmpge(Vec3::ZERO), aabb.max, aabb.min);
+                plane.truncate().dot(corner) + plane.w >= 0.
+            })
+    }
 }
 
 pub struct Camera {
@@ -111,6 +193,9 @@ pub struct Camera {
     move_speed: f32,
     rotate_speed: f32,
     camera_type: CameraType,
+    orbit: Orbit,
+    /// Clockwise clip space rotation matching the surface pre-transform
+    pre_rotation: f32,
     view_mat: Mat4,
     perspective_mat: Mat4,
 }
@@ -130,6 +215,14 @@ impl Camera {
         CameraBuilder::default()
     }
 
+    pub fn translation(&self) -> Vec3 {
+        self.translation
+    }
+
+    pub fn rotation(&self) -> (f32, f32, f32) {
+        self.rotation
+    }
+
     pub fn set_translation(&mut self, t: Vec3) {
         self.translation = t;
         self.update_view_mat();
@@ -152,11 +245,25 @@ impl Camera {
         self.update_perspective_mat();
     }
 
+    /// Distances of the near and far planes
+    pub fn z_limits(&self) -> [f32; 2] {
+        self.z_limits
+    }
+
     pub fn set_z_limits(&mut self, z_limits: [f32; 2]) {
         self.z_limits = z_limits;
         self.update_perspective_mat();
     }
 
+    /// Set the angle in radius of `Surface::pre_rotation_angle`,
+    /// the aspect ratio stays the one of the unrotated window
+    pub fn set_pre_rotation(&mut self, angle: f32) {
+        if self.pre_rotation != angle {
+            self.pre_rotation = angle;
+            self.update_perspective_mat();
+        }
+    }
+
     pub fn set_move_speed(&mut self, move_speed: f32) {
         self.move_speed = move_speed;
     }
@@ -170,7 +277,53 @@ impl Camera {
         self.update_view_mat();
     }
 
+    pub fn camera_type(&self) -> CameraType {
+        self.camera_type
+    }
+
+    pub fn orbit_target(&self) -> Vec3 {
+        self.orbit.target
+    }
+
+    pub fn set_orbit_target(&mut self, target: Vec3) {
+        self.orbit.target = target;
+        self.update_view_mat();
+    }
+
+    pub fn orbit_radius(&self) -> f32 {
+        self.orbit.radius
+    }
+
+    /// Moves around the orbit target by angles in radius, pitch is kept off the poles
+    pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        self.orbit.yaw = (self.orbit.yaw + delta_yaw) % (2. * PI);
+        self.orbit.pitch =
+            (self.orbit.pitch + delta_pitch).clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH);
+        self.update_view_mat();
+    }
+
+    /// Moves towards the orbit target for positive `delta`, never closer than the near plane
+    pub fn zoom(&mut self, delta: f32) {
+        self.orbit.radius = (self.orbit.radius - delta).max(self.z_limits[0]);
+        self.update_view_mat();
+    }
+
+    /// [`CameraType::Orbit`] zooms for [`Direction::Front`] and [`Direction::Back`]
+    /// and moves the target otherwise
     pub fn translate(&mut self, direction: Direction, distance: f32) {
+        if self.camera_type == CameraType::Orbit {
+            let view_to_world = self.view_mat.inverse();
+            let moving_direction = match direction {
+                Direction::Front => return self.zoom(distance),
+                Direction::Back => return self.zoom(-distance),
+                Direction::Up => Vec3::Y,
+                Direction::Down => Vec3::NEG_Y,
+                Direction::Left => Vec3::NEG_X,
+                Direction::Right => Vec3::X,
+            };
+            self.orbit.target += view_to_world.transform_vector3(moving_direction) * distance;
+            return self.update_view_mat();
+        }
         let moving_direction = match direction {
             Direction::Up => Vec3::Y,
             Direction::Down => Vec3::NEG_Y,
@@ -187,7 +340,17 @@ impl Camera {
         self.translate(direction, time * self.move_speed)
     }
 
+    /// [`CameraType::Orbit`] orbits for up, down, left and right and ignores the others
     pub fn rotate(&mut self, direction: Direction, angle: f32) {
+        if self.camera_type == CameraType::Orbit {
+            return match direction {
+                Direction::Up => self.orbit(0., angle),
+                Direction::Down => self.orbit(0., -angle),
+                Direction::Left => self.orbit(-angle, 0.),
+                Direction::Right => self.orbit(angle, 0.),
+                Direction::Front | Direction::Back => {}
+            };
+        }
         match direction {
             Direction::Up => self.rotation.1 += angle,
             Direction::Down => self.rotation.1 -= angle,
@@ -211,6 +374,15 @@ impl Camera {
         self.perspective_mat
     }
 
+    /// See [`Frustum::planes`]
+    pub fn frustum_planes(&self) -> [Vec4; 6] {
+        self.frustum().planes
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.perspective_mat * self.view_mat)
+    }
+
     fn update_view_mat(&mut self) {
         let (x, y, z) = self.rotation;
         let mat_rot = Mat4::from_euler(EulerRot::XYZ, x, y, z);
@@ -218,16 +390,30 @@ impl Camera {
         match self.camera_type {
             CameraType::FirstPerson => self.view_mat = mat_rot * mat_trans,
             CameraType::LookAt => self.view_mat = mat_trans * mat_rot,
+            CameraType::Orbit => {
+                self.view_mat = Mat4::look_at_rh(self.orbit.eye(), self.orbit.target, Vec3::Y)
+            }
         }
     }
 
     fn update_perspective_mat(&mut self) {
-        self.perspective_mat = Mat4::perspective_rh(
-            self.fov,
-            self.aspect_ratio,
-            self.z_limits[0],
-            self.z_limits[1],
-        );
+        self.perspective_mat = Mat4::from_rotation_z(self.pre_rotation)
+            * Mat4::perspective_rh(
+                self.fov,
+                self.aspect_ratio,
+                self.z_limits[0],
+                self.z_limits[1],
+            );
+    }
+
+    /// Origin on the near plane and direction of the world space ray through `position`,
+    /// in pixels from the top left of a viewport of `viewport_size` pixels
+    pub fn screen_ray(&self, position: Vec2, viewport_size: Vec2) -> (Vec3, Vec3) {
+        let ndc = position / viewport_size * 2. - Vec2::ONE;
+        let world_from_clip = (self.perspective_mat * self.view_mat).inverse();
+        let near = world_from_clip.project_point3(ndc.extend(0.));
+        let far = world_from_clip.project_point3(ndc.extend(1.));
+        (near, (far - near).normalize())
     }
 
     pub fn mvp_matrix(&self, model: Mat4) -> MVPMatrix {
@@ -239,7 +425,125 @@ impl Camera {
     }
 }
 
+/// Mouse navigation of a [`Camera`]: left drag looks around, or orbits an orbit camera,
+/// middle drag pans, right drag and the wheel dolly
+#[derive(Clone, Copy, Debug)]
+pub struct CameraController {
+    /// Radians per pixel of left drag
+    pub rotate_sensitivity: f32,
+    /// Distance per pixel of middle drag
+    pub pan_sensitivity: f32,
+    /// Distance per pixel of right drag
+    pub dolly_sensitivity: f32,
+    /// Distance per wheel line
+    pub zoom_sensitivity: f32,
+    left_pressed: bool,
+    middle_pressed: bool,
+    right_pressed: bool,
+    cursor_position: Option<Vec2>,
+    /// Where the left button went down, while it is held
+    left_press_position: Option<Vec2>,
+    click: Option<Vec2>,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            rotate_sensitivity: 0.005,
+            pan_sensitivity: 0.01,
+            dolly_sensitivity: 0.01,
+            zoom_sensitivity: 0.5,
+            left_pressed: false,
+            middle_pressed: false,
+            right_pressed: false,
+            cursor_position: None,
+            left_press_position: None,
+            click: None,
+        }
+    }
+}
+
+impl CameraController {
+    /// Pixels the cursor may move between press and release of a click
+    const CLICK_SLOP: f32 = 3.;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        match button {
+            MouseButton::Left => {
+                self.left_pressed = pressed;
+                if pressed {
+                    self.left_press_position = self.cursor_position;
+                } else if let (Some(press), Some(release)) =
+                    (self.left_press_position.take(), self.cursor_position)
+                {
+                    if press.distance(release) <= Self::CLICK_SLOP {
+                        self.click = Some(release);
+                    }
+                }
+            }
+            MouseButton::Middle => self.middle_pressed = pressed,
+            MouseButton::Right => self.right_pressed = pressed,
+            MouseButton::Other(_) => {}
+        }
+    }
+
+    /// Moves `camera` by the cursor delta since the last call while a button is held
+    pub fn on_cursor_moved(&mut self, camera: &mut Camera, position: PhysicalPosition<f64>) {
+        let position = Vec2::new(position.x as f32, position.y as f32);
+        let delta = position - self.cursor_position.unwrap_or(position);
+        self.cursor_position = Some(position);
+        if delta == Vec2::ZERO {
+            return;
+        }
+
+        if self.left_pressed && camera.camera_type() == CameraType::Orbit {
+            camera.orbit(
+                -delta.x * self.rotate_sensitivity,
+                delta.y * self.rotate_sensitivity,
+            );
+        } else if self.left_pressed {
+            let (x, y, z) = camera.rotation();
+            camera.set_rotation((
+                x + delta.y * self.rotate_sensitivity,
+                y - delta.x * self.rotate_sensitivity,
+                z,
+            ));
+        }
+        if self.middle_pressed && camera.camera_type() == CameraType::Orbit {
+            // The scene follows the cursor, so the target moves the other way
+            camera.translate(Direction::Left, delta.x * self.pan_sensitivity);
+            camera.translate(Direction::Down, delta.y * self.pan_sensitivity);
+        } else if self.middle_pressed {
+            camera.set_translation(camera.translation() + delta.extend(0.) * self.pan_sensitivity);
+        }
+        if self.right_pressed {
+            camera.translate(Direction::Front, -delta.y * self.dolly_sensitivity);
+        }
+    }
+
+    /// Last known cursor position over the window, in pixels from its top left
+    pub fn cursor_position(&self) -> Option<Vec2> {
+        self.cursor_position
+    }
+
+    /// Cursor position of the last left click, a press and release without dragging in
+    /// between, that was not taken yet. Presses over the UI do not count.
+    pub fn take_click(&mut self) -> Option<Vec2> {
+        self.click.take()
+    }
+
+    /// `lines` is positive when scrolling away from the user, which zooms in
+    pub fn on_mouse_wheel(&mut self, camera: &mut Camera, lines: f32) {
+        camera.translate(Direction::Front, lines * self.zoom_sensitivity);
+    }
+}
+
 #[repr(C, align(16))]
+#[derive(Clone, Copy, PartialEq)]
 pub struct MVPMatrix {
     pub model: Mat4,
     pub view: Mat4,
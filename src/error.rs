@@ -6,12 +6,21 @@ pub enum RenderError {
     WindowCreateError(winit::error::OsError),
     IOError(std::io::Error),
     ImageError(image_loader::error::ImageError),
+    #[cfg(feature = "obj")]
     ObjLoadError(tobj::LoadError),
+    #[cfg(feature = "shaderc")]
+    ShaderCompileError(shaderc::Error),
+    #[cfg(feature = "gltf")]
+    GltfError(gltf::Error),
     PhysicalDeviceNotSupported(String),
     FormatNotSupported(String),
     MemoryTypeNotSupported(String),
     LayoutTransitionNotSupported(String),
     QueueFamilyNotSupported(String),
+    ImageDataInvalid(String),
+    DescriptorSetInvalid(String),
+    ProfileInvalid(String),
+    ConfigurationInvalid(String),
 }
 
 impl From<ash::vk::Result> for RenderError {
@@ -38,12 +47,27 @@ impl From<image_loader::error::ImageError> for RenderError {
     }
 }
 
+#[cfg(feature = "obj")]
 impl From<tobj::LoadError> for RenderError {
     fn from(value: tobj::LoadError) -> Self {
         Self::ObjLoadError(value)
     }
 }
 
+#[cfg(feature = "gltf")]
+impl From<gltf::Error> for RenderError {
+    fn from(value: gltf::Error) -> Self {
+        Self::GltfError(value)
+    }
+}
+
+#[cfg(feature = "shaderc")]
+impl From<shaderc::Error> for RenderError {
+    fn from(value: shaderc::Error) -> Self {
+        Self::ShaderCompileError(value)
+    }
+}
+
 impl Display for RenderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -51,7 +75,12 @@ impl Display for RenderError {
             Self::WindowCreateError(e) => write!(f, "{e}"),
             Self::IOError(e) => write!(f, "{e}"),
             Self::ImageError(e) => write!(f, "{e}"),
+            #[cfg(feature = "obj")]
             Self::ObjLoadError(e) => write!(f, "{e}"),
+            #[cfg(feature = "shaderc")]
+            Self::ShaderCompileError(e) => write!(f, "{e}"),
+            #[cfg(feature = "gltf")]
+            Self::GltfError(e) => write!(f, "{e}"),
             Self::PhysicalDeviceNotSupported(s) => write!(f, "PHYSICAL DEVICE NOT SUPPORTED: {s}"),
             Self::FormatNotSupported(s) => write!(f, "FORMAT NOT SUPPORTED: {s}"),
             Self::MemoryTypeNotSupported(s) => write!(f, "MEMORY TYPE NOT SUPPORTED: {s}"),
@@ -61,6 +90,10 @@ impl Display for RenderError {
             Self::QueueFamilyNotSupported(s) => {
                 write!(f, "QUEUE FAMILY NOT SUPPORTED: {s}")
             }
+            Self::ImageDataInvalid(s) => write!(f, "IMAGE DATA INVALID: {s}"),
+            Self::DescriptorSetInvalid(s) => write!(f, "DESCRIPTOR SET INVALID: {s}"),
+            Self::ProfileInvalid(s) => write!(f, "PROFILE INVALID: {s}"),
+            Self::ConfigurationInvalid(s) => write!(f, "CONFIGURATION INVALID: {s}"),
         }
     }
 }
@@ -0,0 +1,795 @@
+use std::{path::Path, rc::Rc};
+
+use ash::vk;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+pub use crate::mesh::AABB;
+use crate::{
+    app::{DrawItem, DrawList, ObjectData},
+    camera::Frustum,
+    error::{RenderError, RenderResult},
+    impl_vertex_layout,
+    mesh::{PosNormalTangentUv, PosNormalUv, PosOnly, VertexLayout},
+    vulkan_wrappers::{Buffer, Device, Texture},
+};
+
+/// Vertex of [`Scene`], `pos`, `color` and `texture_coord` are at the same locations
+/// as in [`crate::mesh::Vertex`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct Vertex {
+    pos: Vec3,
+    color: Vec3,
+    texture_coord: Vec2,
+    normal: Vec3,
+    /// xyz along +u, w is the handedness of the bitangent `cross(normal, tangent.xyz)`
+    tangent: Vec4,
+}
+
+impl Vertex {
+    /// [`VertexLayout::binding_description`] at binding 0
+    pub fn binding_description() -> vk::VertexInputBindingDescription {
+        <Self as VertexLayout>::binding_description(0)
+    }
+
+    /// [`VertexLayout::attribute_descriptions`] at binding 0
+    pub fn attr_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        Self::attribute_descriptions(0)
+    }
+}
+
+impl_vertex_layout!(Vertex {
+    pos: vk::Format::R32G32B32_SFLOAT,
+    color: vk::Format::R32G32B32_SFLOAT,
+    texture_coord: vk::Format::R32G32_SFLOAT,
+    normal: vk::Format::R32G32B32_SFLOAT,
+    tangent: vk::Format::R32G32B32A32_SFLOAT,
+});
+
+impl From<Vertex> for PosOnly {
+    fn from(vertex: Vertex) -> Self {
+        Self { pos: vertex.pos }
+    }
+}
+
+impl From<Vertex> for PosNormalUv {
+    fn from(vertex: Vertex) -> Self {
+        Self {
+            pos: vertex.pos,
+            normal: vertex.normal,
+            uv: vertex.texture_coord,
+        }
+    }
+}
+
+impl From<Vertex> for PosNormalTangentUv {
+    fn from(vertex: Vertex) -> Self {
+        Self {
+            pos: vertex.pos,
+            normal: vertex.normal,
+            tangent: vertex.tangent,
+            uv: vertex.texture_coord,
+        }
+    }
+}
+
+/// Metallic-roughness material, textures are indices into [`Scene::textures`] and
+/// all of them use the first texture coordinates
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: Vec4,
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Metalness in the blue channel, roughness in the green one
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<usize>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: Vec3,
+    pub emissive_texture: Option<usize>,
+    pub double_sided: bool,
+    /// Written by [`Material::write_descriptors`] when the scene is loaded
+    pub descriptor_set: vk::DescriptorSet,
+}
+
+impl Material {
+    pub const BASE_COLOR_BINDING: u32 = 0;
+    pub const NORMAL_BINDING: u32 = 1;
+    pub const METALLIC_ROUGHNESS_BINDING: u32 = 2;
+    pub const OCCLUSION_BINDING: u32 = 3;
+    pub const EMISSIVE_BINDING: u32 = 4;
+    const TEXTURE_COUNT: u32 = 5;
+
+    /// Points every binding of a set of [`Scene::descriptor_set_layout`] at the textures
+    /// of the material. Missing ones fall back to a white texel, which leaves the factors
+    /// alone, or to a flat normal.
+    pub fn write_descriptors<V>(&self, descriptor_set: vk::DescriptorSet, scene: &Scene<V>) {
+        let texture =
+            |index: Option<usize>, fallback| index.map_or(fallback, |i| &scene.textures[i]);
+        let image_infos = [
+            (
+                Self::BASE_COLOR_BINDING,
+                texture(self.base_color_texture, &scene.white_texture),
+            ),
+            (
+                Self::NORMAL_BINDING,
+                texture(self.normal_texture, &scene.flat_normal_texture),
+            ),
+            (
+                Self::METALLIC_ROUGHNESS_BINDING,
+                texture(self.metallic_roughness_texture, &scene.white_texture),
+            ),
+            (
+                Self::OCCLUSION_BINDING,
+                texture(self.occlusion_texture, &scene.white_texture),
+            ),
+            (
+                Self::EMISSIVE_BINDING,
+                texture(self.emissive_texture, &scene.white_texture),
+            ),
+        ]
+        .map(|(binding, texture)| (binding, [texture.descriptor_default()]));
+        let writes = image_infos
+            .iter()
+            .map(|(binding, image_info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding(*binding)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(image_info)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        unsafe { scene.device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    pub fn factors(&self) -> MaterialFactors {
+        MaterialFactors {
+            base_color: self.base_color_factor,
+            emissive: self.emissive_factor,
+            metallic: self.metallic_factor,
+            roughness: self.roughness_factor,
+            normal_scale: self.normal_scale,
+            occlusion_strength: self.occlusion_strength,
+            _padding: 0.,
+        }
+    }
+}
+
+/// Scalar parameters of a [`Material`], laid out for push constants or uniform buffers
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MaterialFactors {
+    pub base_color: Vec4,
+    pub emissive: Vec3,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Pod for MaterialFactors {}
+unsafe impl bytemuck::Zeroable for MaterialFactors {}
+
+/// Indices of one primitive in the index buffer of the scene, which point into its
+/// single vertex buffer
+pub struct Primitive {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// Index into [`Scene::materials`], primitives without material use the last one
+    pub material: usize,
+    pub aabb: AABB,
+}
+
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+    pub aabb: AABB,
+}
+
+pub struct Node {
+    pub name: Option<String>,
+    /// Relative to the parent node
+    pub local_transform: Mat4,
+    /// Index into [`Scene::meshes`]
+    pub mesh: Option<usize>,
+    /// Indices into [`Scene::nodes`]
+    pub children: Vec<usize>,
+}
+
+/// Node hierarchy of a glTF 2.0 file with its meshes uploaded to device local buffers
+/// and its textures.
+///
+/// Like [`crate::mesh::load_obj_model`] the scene is mirrored along z, so pipelines
+/// drawing it should not rely on the glTF winding for culling.
+///
+/// Vertices are [`Vertex`] unless loaded with [`Scene::load_as`] into another
+/// [`VertexLayout`], such as [`PosNormalUv`] for pipelines without normal maps.
+pub struct Scene<V = Vertex> {
+    pub meshes: Vec<Mesh>,
+    /// One more than in the file, for primitives without material
+    pub materials: Vec<Material>,
+    pub nodes: Vec<Node>,
+    /// Nodes of the default scene of the file, indices into [`Scene::nodes`]
+    pub root_nodes: Vec<usize>,
+    pub textures: Vec<Texture>,
+    /// Layout of [`Material::descriptor_set`], one combined image sampler per texture of
+    /// the material at the `*_BINDING` constants of [`Material`]
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    aabb: AABB,
+    vertex_buffer: Buffer<V>,
+    index_buffer: Buffer<u32>,
+    white_texture: Texture,
+    flat_normal_texture: Texture,
+    device: Rc<Device>,
+}
+
+impl Scene {
+    /// Loads a `.gltf` or `.glb` file with the buffers and images it references.
+    /// Primitives other than triangle lists are skipped, vertex colors, texture
+    /// coordinates and indices are optional. Missing normals are smoothed from the
+    /// triangles and missing tangents derived from the texture coordinates.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        Self::load_as(path, device, command_pool, queue)
+    }
+}
+
+impl<V: From<Vertex>> Scene<V> {
+    /// Like [`Scene::load`], with the vertices converted to `V`
+    pub fn load_as<P: AsRef<Path>>(
+        path: P,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        let (document, buffers, images) = ::gltf::import(path)?;
+
+        // Only base color and emissive textures hold sRGB encoded colors
+        let color_textures = document
+            .materials()
+            .flat_map(|material| {
+                [
+                    material.pbr_metallic_roughness().base_color_texture(),
+                    material.emissive_texture(),
+                ]
+            })
+            .flatten()
+            .map(|info| info.texture().index())
+            .collect::<Vec<_>>();
+        let textures = document
+            .textures()
+            .map(|texture| {
+                let image = &images[texture.source().index()];
+                let mut texture = Texture::from_rgba8_pixels(
+                    image.width,
+                    image.height,
+                    &rgba8_pixels(image)?,
+                    if color_textures.contains(&texture.index()) {
+                        vk::Format::R8G8B8A8_SRGB
+                    } else {
+                        vk::Format::R8G8B8A8_UNORM
+                    },
+                    device.clone(),
+                    command_pool,
+                    queue,
+                )?;
+                texture.spawn_image_view()?;
+                texture.spawn_sampler(vk::Filter::LINEAR)?;
+                Ok(texture)
+            })
+            .collect::<RenderResult<Vec<_>>>()?;
+        let mut white_texture = Texture::from_rgba8_pixels(
+            1,
+            1,
+            &[255; 4],
+            vk::Format::R8G8B8A8_SRGB,
+            device.clone(),
+            command_pool,
+            queue,
+        )?;
+        white_texture.spawn_image_view()?;
+        white_texture.spawn_sampler(vk::Filter::NEAREST)?;
+        let mut flat_normal_texture = Texture::from_rgba8_pixels(
+            1,
+            1,
+            &[128, 128, 255, 255],
+            vk::Format::R8G8B8A8_UNORM,
+            device.clone(),
+            command_pool,
+            queue,
+        )?;
+        flat_normal_texture.spawn_image_view()?;
+        flat_normal_texture.spawn_sampler(vk::Filter::NEAREST)?;
+
+        let mut materials = document
+            .materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+                let normal = material.normal_texture();
+                let occlusion = material.occlusion_texture();
+                Material {
+                    name: material.name().map(str::to_owned),
+                    base_color_factor: Vec4::from(pbr.base_color_factor()),
+                    base_color_texture: pbr.base_color_texture().map(|info| info.texture().index()),
+                    metallic_factor: pbr.metallic_factor(),
+                    roughness_factor: pbr.roughness_factor(),
+                    metallic_roughness_texture: pbr
+                        .metallic_roughness_texture()
+                        .map(|info| info.texture().index()),
+                    normal_texture: normal.as_ref().map(|normal| normal.texture().index()),
+                    normal_scale: normal.as_ref().map_or(1., |normal| normal.scale()),
+                    occlusion_texture: occlusion
+                        .as_ref()
+                        .map(|occlusion| occlusion.texture().index()),
+                    occlusion_strength: occlusion
+                        .as_ref()
+                        .map_or(1., |occlusion| occlusion.strength()),
+                    emissive_factor: Vec3::from(material.emissive_factor()),
+                    emissive_texture: material
+                        .emissive_texture()
+                        .map(|info| info.texture().index()),
+                    double_sided: material.double_sided(),
+                    descriptor_set: vk::DescriptorSet::null(),
+                }
+            })
+            .collect::<Vec<_>>();
+        // Defaults of the glTF specification
+        materials.push(Material {
+            name: None,
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            metallic_factor: 1.,
+            roughness_factor: 1.,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.,
+            occlusion_texture: None,
+            occlusion_strength: 1.,
+            emissive_factor: Vec3::ZERO,
+            emissive_texture: None,
+            double_sided: false,
+            descriptor_set: vk::DescriptorSet::null(),
+        });
+        let default_material = materials.len() - 1;
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let meshes = document
+            .meshes()
+            .map(|mesh| {
+                let mut primitives = vec![];
+                for primitive in mesh.primitives() {
+                    if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                        continue;
+                    }
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let positions = reader.read_positions().ok_or_else(|| {
+                        RenderError::ImageDataInvalid(format!(
+                            "Primitive {} of mesh {} has no positions",
+                            primitive.index(),
+                            mesh.index()
+                        ))
+                    })?;
+
+                    let positions = positions.map(Vec3::from).collect::<Vec<_>>();
+                    let local_indices = match reader.read_indices() {
+                        Some(read) => read.into_u32().collect(),
+                        None => (0..positions.len() as u32).collect::<Vec<_>>(),
+                    };
+                    let tex_coords = reader.read_tex_coords(0).map(|tex_coords| {
+                        tex_coords.into_f32().map(Vec2::from).collect::<Vec<_>>()
+                    });
+                    let colors = reader
+                        .read_colors(0)
+                        .map(|colors| colors.into_rgb_f32().map(Vec3::from).collect::<Vec<_>>());
+                    let normals = match reader.read_normals() {
+                        Some(normals) => normals.map(Vec3::from).collect(),
+                        None => smooth_normals(&positions, &local_indices),
+                    };
+                    let tangents = match (reader.read_tangents(), &tex_coords) {
+                        (Some(tangents), _) => tangents.map(Vec4::from).collect(),
+                        (None, Some(tex_coords)) => {
+                            derive_tangents(&positions, &normals, tex_coords, &local_indices)
+                        }
+                        (None, None) => vec![Vec4::new(1., 0., 0., 1.); positions.len()],
+                    };
+
+                    let first_vertex = vertices.len() as u32;
+                    let mut aabb = AABB::EMPTY;
+                    for (i, &pos) in positions.iter().enumerate() {
+                        aabb.extend(pos);
+                        vertices.push(Vertex {
+                            pos,
+                            color: colors.as_ref().map_or(Vec3::ONE, |colors| colors[i]),
+                            texture_coord: tex_coords
+                                .as_ref()
+                                .map_or(Vec2::ZERO, |tex_coords| tex_coords[i]),
+                            normal: normals[i],
+                            tangent: tangents[i],
+                        });
+                    }
+
+                    let first_index = indices.len() as u32;
+                    indices.extend(local_indices.iter().map(|i| first_vertex + i));
+                    primitives.push(Primitive {
+                        first_index,
+                        index_count: indices.len() as u32 - first_index,
+                        material: primitive.material().index().unwrap_or(default_material),
+                        aabb,
+                    });
+                }
+                let aabb = primitives
+                    .iter()
+                    .fold(AABB::EMPTY, |aabb, primitive| aabb.union(&primitive.aabb));
+                Ok(Mesh {
+                    name: mesh.name().map(str::to_owned),
+                    primitives,
+                    aabb,
+                })
+            })
+            .collect::<RenderResult<Vec<_>>>()?;
+        if indices.is_empty() {
+            return Err(RenderError::ImageDataInvalid(
+                "glTF file holds no triangles".to_owned(),
+            ));
+        }
+
+        let nodes = document
+            .nodes()
+            .map(|node| Node {
+                name: node.name().map(str::to_owned),
+                local_transform: Mat4::from_cols_array_2d(&node.transform().matrix()),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect::<Vec<_>>();
+        let root_nodes = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            // Every node nobody has as child
+            None => (0..nodes.len())
+                .filter(|i| nodes.iter().all(|node| !node.children.contains(i)))
+                .collect(),
+        };
+
+        let vertices = vertices.into_iter().map(V::from).collect::<Vec<_>>();
+        let vertex_buffer =
+            Buffer::device_local_vertex(&vertices, device.clone(), command_pool, queue)?;
+        let index_buffer =
+            Buffer::device_local_index(&indices, device.clone(), command_pool, queue)?;
+
+        let descriptor_set_layout = {
+            let bindings = (0..Material::TEXTURE_COUNT)
+                .map(|binding| {
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                        .binding(binding)
+                        .descriptor_count(1)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+        let descriptor_pool = {
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&[vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .descriptor_count(materials.len() as u32 * Material::TEXTURE_COUNT)
+                    .build()])
+                .max_sets(materials.len() as u32)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+        let descriptor_sets = unsafe {
+            device.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(descriptor_pool)
+                    .set_layouts(&vec![descriptor_set_layout; materials.len()])
+                    .build(),
+            )?
+        };
+        for (material, descriptor_set) in materials.iter_mut().zip(descriptor_sets) {
+            material.descriptor_set = descriptor_set;
+        }
+
+        let mut scene = Self {
+            meshes,
+            materials,
+            nodes,
+            root_nodes,
+            textures,
+            descriptor_set_layout,
+            descriptor_pool,
+            aabb: AABB::EMPTY,
+            vertex_buffer,
+            index_buffer,
+            white_texture,
+            flat_normal_texture,
+            device,
+        };
+        for material in &scene.materials {
+            material.write_descriptors(material.descriptor_set, &scene);
+        }
+        let mut aabb = AABB::EMPTY;
+        scene.walk(|transform, mesh| aabb = aabb.union(&mesh.aabb.transformed(transform)));
+        scene.aabb = aabb;
+
+        Ok(scene)
+    }
+}
+
+impl<V> Scene<V> {
+    /// Bounds of every mesh instance, in world space
+    pub fn aabb(&self) -> AABB {
+        self.aabb
+    }
+
+    /// Visits the meshes of the hierarchy with the world transform of their node
+    pub fn walk(&self, mut visit: impl FnMut(Mat4, &Mesh)) {
+        let mut stack = self
+            .root_nodes
+            .iter()
+            .map(|&i| (i, Mat4::from_scale(Vec3::new(1., 1., -1.))))
+            .collect::<Vec<_>>();
+        while let Some((i, parent_transform)) = stack.pop() {
+            let node = &self.nodes[i];
+            let transform = parent_transform * node.local_transform;
+            if let Some(mesh) = node.mesh {
+                visit(transform, &self.meshes[mesh]);
+            }
+            stack.extend(node.children.iter().map(|&child| (child, transform)));
+        }
+    }
+
+    /// Vertex buffer at binding 0 and index buffer, for draws of [`Primitive::first_index`]
+    /// recorded elsewhere, e.g. indirect ones
+    pub fn cmd_bind_buffers(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[self.vertex_buffer.buffer()],
+                &[0],
+            );
+            self.device.cmd_bind_index_buffer(
+                command_buffer,
+                self.index_buffer.buffer(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+    }
+
+    /// Binds the vertex and index buffers, then draws every primitive of the hierarchy.
+    /// `per_primitive` is called before each draw with the world transform and the
+    /// material of the primitive, to push constants and bind [`Material::descriptor_set`].
+    pub fn cmd_draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        per_primitive: impl FnMut(Mat4, &Material),
+    ) {
+        self.cmd_draw_filtered(command_buffer, |_, _| true, instance_zero(per_primitive));
+    }
+
+    /// [`Self::cmd_draw`] skipping the primitives outside of `frustum`, returns the number
+    /// of primitives drawn
+    pub fn cmd_draw_culled(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frustum: &Frustum,
+        per_primitive: impl FnMut(Mat4, &Material),
+    ) -> usize {
+        self.cmd_draw_filtered(
+            command_buffer,
+            |transform, primitive| frustum.intersects_aabb(&primitive.aabb.transformed(transform)),
+            instance_zero(per_primitive),
+        )
+    }
+
+    /// [`Self::cmd_draw_culled`] for shaders reading the world transforms from an
+    /// [`crate::app::ObjectBuffer`] rather than push constants. The transforms of the drawn
+    /// mesh instances are appended to `transforms`, and each primitive is drawn with the
+    /// index of its own as first instance. `per_primitive` only has the material to bind.
+    pub fn cmd_draw_culled_objects(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frustum: &Frustum,
+        transforms: &mut Vec<Mat4>,
+        mut per_primitive: impl FnMut(&Material),
+    ) -> usize {
+        self.cmd_draw_filtered(
+            command_buffer,
+            |transform, primitive| frustum.intersects_aabb(&primitive.aabb.transformed(transform)),
+            |transform, material| {
+                // Primitives of a mesh instance are visited in a row and share its transform
+                if transforms.last() != Some(&transform) {
+                    transforms.push(transform);
+                }
+                per_primitive(material);
+                transforms.len() as u32 - 1
+            },
+        )
+    }
+
+    /// Pushes the primitives inside of `frustum` to `draw_list`, with the pipeline
+    /// `pipeline` picks for their material. The draws of [`DrawList::build`] then need the
+    /// buffers of [`Self::cmd_bind_buffers`].
+    pub fn push_draws(
+        &self,
+        draw_list: &mut DrawList,
+        frustum: &Frustum,
+        pipeline: impl Fn(&Material) -> vk::Pipeline,
+    ) {
+        self.walk(|transform, mesh| {
+            let object = ObjectData::new(transform);
+            for primitive in mesh
+                .primitives
+                .iter()
+                .filter(|primitive| frustum.intersects_aabb(&primitive.aabb.transformed(transform)))
+            {
+                draw_list.push(DrawItem {
+                    pipeline: pipeline(&self.materials[primitive.material]),
+                    material: primitive.material,
+                    first_index: primitive.first_index,
+                    index_count: primitive.index_count,
+                    vertex_offset: 0,
+                    object,
+                });
+            }
+        });
+    }
+
+    /// Mesh instances of the hierarchy, the object count of [`Self::cmd_draw_culled_objects`]
+    /// when nothing is culled
+    pub fn mesh_instance_count(&self) -> usize {
+        let mut count = 0;
+        self.walk(|_, _| count += 1);
+        count
+    }
+
+    /// `per_primitive` returns the first instance of the draw
+    fn cmd_draw_filtered(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        filter: impl Fn(Mat4, &Primitive) -> bool,
+        mut per_primitive: impl FnMut(Mat4, &Material) -> u32,
+    ) -> usize {
+        self.cmd_bind_buffers(command_buffer);
+        let mut draw_count = 0;
+        self.walk(|transform, mesh| {
+            for primitive in mesh
+                .primitives
+                .iter()
+                .filter(|primitive| filter(transform, primitive))
+            {
+                let first_instance = per_primitive(transform, &self.materials[primitive.material]);
+                unsafe {
+                    self.device.cmd_draw_indexed(
+                        command_buffer,
+                        primitive.index_count,
+                        1,
+                        primitive.first_index,
+                        0,
+                        first_instance,
+                    );
+                }
+                draw_count += 1;
+            }
+        });
+        draw_count
+    }
+}
+
+fn instance_zero(
+    mut per_primitive: impl FnMut(Mat4, &Material),
+) -> impl FnMut(Mat4, &Material) -> u32 {
+    move |transform, material| {
+        per_primitive(transform, material);
+        0
+    }
+}
+
+impl<V> Drop for Scene<V> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+/// Expands the 8 bit formats of glTF images to RGBA
+fn rgba8_pixels(image: &::gltf::image::Data) -> RenderResult<Vec<u8>> {
+    use ::gltf::image::Format;
+
+    let pixels = &image.pixels;
+    Ok(match image.format {
+        Format::R8G8B8A8 => pixels.clone(),
+        Format::R8G8B8 => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        Format::R8G8 => pixels
+            .chunks_exact(2)
+            .flat_map(|rg| [rg[0], rg[1], 0, 255])
+            .collect(),
+        Format::R8 => pixels.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+        format => {
+            return Err(RenderError::FormatNotSupported(format!(
+                "glTF image in {format:?}"
+            )))
+        }
+    })
+}
+
+/// Area weighted average of the normals of the triangles around each vertex
+fn smooth_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Z))
+        .collect()
+}
+
+/// Tangents along +u, orthogonalized against the normals, with the handedness of +v
+fn derive_tangents(
+    positions: &[Vec3],
+    normals: &[Vec3],
+    tex_coords: &[Vec2],
+    indices: &[u32],
+) -> Vec<Vec4> {
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let (edge1, edge2) = (positions[b] - positions[a], positions[c] - positions[a]);
+        let (duv1, duv2) = (tex_coords[b] - tex_coords[a], tex_coords[c] - tex_coords[a]);
+        let det = duv1.perp_dot(duv2);
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+    normals
+        .iter()
+        .zip(tangents.iter().zip(bitangents))
+        .map(|(&normal, (&tangent, bitangent))| {
+            let tangent = (tangent - normal * normal.dot(tangent))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0. {
+                -1.
+            } else {
+                1.
+            };
+            tangent.extend(handedness)
+        })
+        .collect()
+}
@@ -0,0 +1,195 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, OnceLock,
+    },
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+static GLOBAL: OnceLock<JobSystem> = OnceLock::new();
+
+/// Small thread pool running CPU heavy work, e.g. picture decoding, off the render thread.
+///
+/// Jobs are named so the ones still queued or running can be listed in the UI.
+pub struct JobSystem {
+    sender: Mutex<Option<mpsc::Sender<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+    state: Arc<JobState>,
+}
+
+#[derive(Default)]
+struct JobState {
+    /// Jobs queued or running, oldest first
+    outstanding: Mutex<Vec<(u64, &'static str)>>,
+    completed: AtomicUsize,
+    next_id: AtomicU64,
+}
+
+impl JobSystem {
+    pub fn new(thread_count: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..thread_count.max(1))
+            .map(|i| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("job worker {i}"))
+                    .spawn(move || loop {
+                        // The lock is released before running the job
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("Fail to spawn a job worker")
+            })
+            .collect();
+        Self {
+            sender: Mutex::new(Some(sender)),
+            workers,
+            state: Arc::default(),
+        }
+    }
+
+    /// Process wide pool with one worker per available core, created on first use
+    pub fn global() -> &'static Self {
+        GLOBAL.get_or_init(|| {
+            Self::new(
+                thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(4),
+            )
+        })
+    }
+
+    /// The process wide pool if anything used it already
+    pub fn try_global() -> Option<&'static Self> {
+        GLOBAL.get()
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn spawn<T, F>(&self, name: &'static str, f: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let id = self.state.next_id.fetch_add(1, Ordering::Relaxed);
+        self.state.outstanding.lock().unwrap().push((id, name));
+
+        let (result_sender, result_receiver) = mpsc::sync_channel(1);
+        let state = self.state.clone();
+        let job: Job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            state.outstanding.lock().unwrap().retain(|(i, _)| *i != id);
+            state.completed.fetch_add(1, Ordering::Relaxed);
+            // The handle may have been dropped without waiting
+            result_sender.send(result).ok();
+        });
+        self.sender
+            .lock()
+            .unwrap()
+            .as_ref()
+            .expect("Job system shut down")
+            .send(job)
+            .expect("All job workers exited");
+
+        JobHandle {
+            name,
+            receiver: result_receiver,
+        }
+    }
+
+    /// Runs `f` over every item on the pool and waits for all of them, results keep the
+    /// order of `items`
+    pub fn map<I, T, F>(&self, name: &'static str, items: I, f: F) -> Vec<T>
+    where
+        I: IntoIterator,
+        I::Item: Send + 'static,
+        T: Send + 'static,
+        F: Fn(I::Item) -> T + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let handles: Vec<_> = items
+            .into_iter()
+            .map(|item| {
+                let f = f.clone();
+                self.spawn(name, move || f(item))
+            })
+            .collect();
+        handles.into_iter().map(JobHandle::join).collect()
+    }
+
+    /// Names of the jobs queued or running, oldest first
+    pub fn outstanding(&self) -> Vec<&'static str> {
+        self.state
+            .outstanding
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    pub fn completed_count(&self) -> usize {
+        self.state.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        let outstanding = self.outstanding();
+        let mut summary = vec![format!(
+            "jobs: {} outstanding, {} done on {} threads",
+            outstanding.len(),
+            self.completed_count(),
+            self.thread_count()
+        )];
+        summary.extend(outstanding.iter().map(|name| format!("  {name}")));
+        summary
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        // Closing the channel lets the workers exit once the queue is drained
+        self.sender.lock().unwrap().take();
+        self.workers.drain(..).for_each(|worker| {
+            worker.join().ok();
+        });
+    }
+}
+
+/// Result of a job spawned with [`JobSystem::spawn`]
+pub struct JobHandle<T> {
+    name: &'static str,
+    receiver: mpsc::Receiver<thread::Result<T>>,
+}
+
+impl<T> JobHandle<T> {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Blocks until the job finished, a panic in the job is resumed here
+    pub fn join(self) -> T {
+        match self.receiver.recv().expect("Job dropped before finishing") {
+            Ok(value) => value,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// The result if the job finished, otherwise the handle back
+    pub fn try_join(self) -> Result<T, Self> {
+        match self.receiver.try_recv() {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(mpsc::TryRecvError::Empty) => Err(self),
+            Err(mpsc::TryRecvError::Disconnected) => panic!("Job dropped before finishing"),
+        }
+    }
+}
@@ -1,5 +1,17 @@
 pub mod app;
 pub mod camera;
 pub mod error;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod jobs;
+pub mod logging;
 pub mod mesh;
+pub mod prelude;
 pub mod vulkan_wrappers;
+
+pub use ash;
+pub use glam;
+// Used by `impl_vertex_layout!`
+#[doc(hidden)]
+pub use memoffset;
+pub use winit;
@@ -0,0 +1,103 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+static GLOBAL: OnceLock<LogBuffer> = OnceLock::new();
+
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// Since the buffer was created
+    pub time: Duration,
+}
+
+/// `log` backend keeping the latest records in memory, e.g. for the UI log console
+/// to show them when there is no terminal. Records are echoed to stdout as well.
+pub struct LogBuffer {
+    records: Mutex<VecDeque<LogRecord>>,
+    capacity: usize,
+    echo: bool,
+    start: Instant,
+}
+
+impl LogBuffer {
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    pub fn new(capacity: usize, echo: bool) -> Self {
+        assert!(capacity > 0);
+        Self {
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            echo,
+            start: Instant::now(),
+        }
+    }
+
+    /// Process wide buffer, installed as the logger on first use. Records of every level
+    /// are kept, nothing is captured when another logger was installed before
+    pub fn global() -> &'static Self {
+        let mut created = false;
+        let buffer = GLOBAL.get_or_init(|| {
+            created = true;
+            Self::new(Self::DEFAULT_CAPACITY, true)
+        });
+        if created && log::set_logger(buffer).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+        }
+        buffer
+    }
+
+    pub fn try_global() -> Option<&'static Self> {
+        GLOBAL.get()
+    }
+
+    /// Oldest first, the lock is held as long as the guard lives
+    pub fn records(&self) -> MutexGuard<'_, VecDeque<LogRecord>> {
+        self.records.lock().unwrap()
+    }
+
+    pub fn clear(&self) {
+        self.records().clear();
+    }
+}
+
+impl Log for LogBuffer {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let record = LogRecord {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            time: self.start.elapsed(),
+        };
+        if self.echo {
+            println!("[{} {}] {}", record.level, record.target, record.message);
+        }
+        let mut records = self.records();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Messages of the crate go to the log once a logger is installed, e.g. [`LogBuffer::global`],
+/// and are printed as before otherwise
+pub(crate) fn report(level: Level, target: &str, message: fmt::Arguments) {
+    if log::max_level() == LevelFilter::Off {
+        println!("{message}");
+    } else {
+        log::log!(target: target, level, "{message}");
+    }
+}
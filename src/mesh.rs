@@ -1,10 +1,25 @@
+#[cfg(feature = "obj")]
 use std::path::Path;
 
 use ash::vk;
 use glam::{Vec2, Vec3};
 
+#[cfg(feature = "obj")]
 use crate::error::RenderResult;
 
+pub mod primitives;
+
+mod aabb;
+pub use aabb::AABB;
+
+mod ray;
+pub use ray::{ray_cast, RayHit};
+
+mod layouts;
+pub use layouts::{
+    impl_vertex_layout, InstanceTransform, PosNormalTangentUv, PosNormalUv, PosOnly, VertexLayout,
+};
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Vertex {
@@ -32,39 +47,29 @@ impl Vertex {
         self
     }
 
+    pub fn pos(&self) -> Vec3 {
+        self.pos
+    }
+
+    /// [`VertexLayout::binding_description`] at binding 0
     pub fn binding_description() -> vk::VertexInputBindingDescription {
-        vk::VertexInputBindingDescription::builder()
-            .binding(0)
-            .stride(std::mem::size_of::<Vertex>() as u32)
-            .input_rate(vk::VertexInputRate::VERTEX)
-            .build()
+        <Self as VertexLayout>::binding_description(0)
     }
 
-    pub fn attr_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
-        [
-            vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .location(0)
-                .format(vk::Format::R32G32B32_SFLOAT)
-                .offset(memoffset::offset_of!(Vertex, pos) as u32)
-                .build(),
-            vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .location(1)
-                .format(vk::Format::R32G32B32_SFLOAT)
-                .offset(memoffset::offset_of!(Vertex, color) as u32)
-                .build(),
-            vk::VertexInputAttributeDescription::builder()
-                .binding(0)
-                .location(2)
-                .format(vk::Format::R32G32_SFLOAT)
-                .offset(memoffset::offset_of!(Vertex, texture_coord) as u32)
-                .build(),
-        ]
+    /// [`VertexLayout::attribute_descriptions`] at binding 0
+    pub fn attr_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        Self::attribute_descriptions(0)
     }
 }
 
+impl_vertex_layout!(Vertex {
+    pos: vk::Format::R32G32B32_SFLOAT,
+    color: vk::Format::R32G32B32_SFLOAT,
+    texture_coord: vk::Format::R32G32_SFLOAT,
+});
+
 // TODO: eliminate duplicated vertices
+#[cfg(feature = "obj")]
 pub fn load_obj_model<P: AsRef<Path> + core::fmt::Debug>(
     path: P,
 ) -> RenderResult<(Vec<Vertex>, Vec<u32>)> {
@@ -0,0 +1,57 @@
+use glam::{BVec3, Mat4, Vec3};
+
+/// Axis aligned bounding box, [`AABB::EMPTY`] until a point is added
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AABB {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl AABB {
+    pub const EMPTY: Self = Self {
+        min: Vec3::splat(f32::INFINITY),
+        max: Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn extend(&mut self, point: Vec3) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Box holding the 8 transformed corners of this one
+    pub fn transformed(&self, transform: Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let mut aabb = Self::EMPTY;
+        for i in 0..8 {
+            let corner = Vec3::select(
+                BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                self.max,
+                self.min,
+            );
+            aabb.extend(transform.transform_point3(corner));
+        }
+        aabb
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
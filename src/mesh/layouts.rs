@@ -0,0 +1,154 @@
+use ash::vk;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+/// Vertex type read from a single interleaved binding. Implemented with
+/// [`crate::impl_vertex_layout`], which lists the attributes in location order:
+///
+/// ```ignore
+/// impl_vertex_layout!(PosNormalUv {
+///     pos: vk::Format::R32G32B32_SFLOAT,
+///     normal: vk::Format::R32G32B32_SFLOAT,
+///     uv: vk::Format::R32G32_SFLOAT,
+/// });
+/// ```
+pub trait VertexLayout: Sized {
+    /// Format and byte offset of every attribute, the index is the shader location
+    fn attribute_formats() -> Vec<(vk::Format, u32)>;
+
+    fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(binding)
+            .stride(std::mem::size_of::<Self>() as u32)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .build()
+    }
+
+    /// Stepped once per instance instead of once per vertex
+    fn instance_binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::builder()
+            .binding(binding)
+            .stride(std::mem::size_of::<Self>() as u32)
+            .input_rate(vk::VertexInputRate::INSTANCE)
+            .build()
+    }
+
+    fn attribute_descriptions(binding: u32) -> Vec<vk::VertexInputAttributeDescription> {
+        Self::attribute_descriptions_from(binding, 0)
+    }
+
+    /// Locations count from `first_location`, e.g. to follow the attributes of another binding
+    fn attribute_descriptions_from(
+        binding: u32,
+        first_location: u32,
+    ) -> Vec<vk::VertexInputAttributeDescription> {
+        Self::attribute_formats()
+            .into_iter()
+            .enumerate()
+            .map(|(location, (format, offset))| {
+                vk::VertexInputAttributeDescription::builder()
+                    .binding(binding)
+                    .location(first_location + location as u32)
+                    .format(format)
+                    .offset(offset)
+                    .build()
+            })
+            .collect()
+    }
+
+    /// Per-vertex binding 0 of `Self` and per-instance binding 1 of `I`, whose attributes
+    /// follow the ones of `Self`, e.g. `layout(location = 3) in mat4 model` after
+    /// the three attributes of [`crate::mesh::Vertex`] for [`InstanceTransform`]
+    fn with_instances<I: VertexLayout>() -> (
+        Vec<vk::VertexInputBindingDescription>,
+        Vec<vk::VertexInputAttributeDescription>,
+    ) {
+        let mut attributes = Self::attribute_descriptions(0);
+        attributes.extend(I::attribute_descriptions_from(1, attributes.len() as u32));
+        (
+            vec![
+                Self::binding_description(0),
+                I::instance_binding_description(1),
+            ],
+            attributes,
+        )
+    }
+}
+
+/// Implements [`crate::mesh::VertexLayout`] for a `#[repr(C)]` struct, with one
+/// attribute per listed field at locations counting from 0
+#[macro_export]
+macro_rules! impl_vertex_layout {
+    ($vertex: ty { $($field: ident: $format: expr),* $(,)? }) => {
+        impl $crate::mesh::VertexLayout for $vertex {
+            fn attribute_formats() -> Vec<($crate::ash::vk::Format, u32)> {
+                vec![$(($format, $crate::memoffset::offset_of!($vertex, $field) as u32)),*]
+            }
+        }
+    };
+}
+
+pub use impl_vertex_layout;
+
+/// Positions only, e.g. for depth-only passes and skyboxes
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PosOnly {
+    pub pos: Vec3,
+}
+
+impl_vertex_layout!(PosOnly {
+    pos: vk::Format::R32G32B32_SFLOAT,
+});
+
+/// Lit and textured meshes without normal maps
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PosNormalUv {
+    pub pos: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+impl_vertex_layout!(PosNormalUv {
+    pos: vk::Format::R32G32B32_SFLOAT,
+    normal: vk::Format::R32G32B32_SFLOAT,
+    uv: vk::Format::R32G32_SFLOAT,
+});
+
+/// Normal mapped meshes, the tangent is the one of glTF
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PosNormalTangentUv {
+    pub pos: Vec3,
+    pub normal: Vec3,
+    /// xyz along +u, w is the handedness of the bitangent `cross(normal, tangent.xyz)`
+    pub tangent: Vec4,
+    pub uv: Vec2,
+}
+
+impl_vertex_layout!(PosNormalTangentUv {
+    pos: vk::Format::R32G32B32_SFLOAT,
+    normal: vk::Format::R32G32B32_SFLOAT,
+    tangent: vk::Format::R32G32B32A32_SFLOAT,
+    uv: vk::Format::R32G32_SFLOAT,
+});
+
+/// Per-instance model matrix, read as a `mat4` taking four consecutive locations
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InstanceTransform {
+    pub model: Mat4,
+}
+
+impl VertexLayout for InstanceTransform {
+    fn attribute_formats() -> Vec<(vk::Format, u32)> {
+        (0..4)
+            .map(|column| {
+                (
+                    vk::Format::R32G32B32A32_SFLOAT,
+                    column * std::mem::size_of::<Vec4>() as u32,
+                )
+            })
+            .collect()
+    }
+}
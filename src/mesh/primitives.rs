@@ -0,0 +1,34 @@
+//! Indexed meshes generated in code, for scenes and tests that need no model file.
+//! Triangles are counter-clockwise seen from outside in a right-handed space, y up.
+
+use glam::{vec2, Vec3};
+
+use super::Vertex;
+
+/// Axis-aligned cube of side 1 centered on the origin. Each face has its own four
+/// vertices with texture coordinates spanning [0, 1], so the edges stay sharp.
+pub fn cube() -> (Vec<Vertex>, Vec<u32>) {
+    // Normal, then the directions of u and v on the face
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::NEG_Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::NEG_Y),
+        (Vec3::Y, Vec3::X, Vec3::Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::Z, Vec3::X, Vec3::NEG_Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::NEG_Y),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v) in faces {
+        let first = vertices.len() as u32;
+        for corner in [vec2(0., 0.), vec2(1., 0.), vec2(1., 1.), vec2(0., 1.)] {
+            let position = (normal + (corner.x * 2. - 1.) * u + (corner.y * 2. - 1.) * v) * 0.5;
+            vertices.push(Vertex::new(position).with_texture_coord(corner));
+        }
+        // u x v points inwards, so the corners go clockwise seen from outside
+        indices.extend([0, 2, 1, 0, 3, 2].map(|i| first + i));
+    }
+
+    (vertices, indices)
+}
@@ -0,0 +1,51 @@
+use glam::Vec3;
+
+/// Closest intersection of a ray with a triangle mesh, see [`ray_cast`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    /// Along the ray, in units of its direction
+    pub distance: f32,
+    pub position: Vec3,
+    /// Of the hit triangle, facing the ray origin
+    pub normal: Vec3,
+    /// First of the three indices of the hit triangle
+    pub first_index: usize,
+}
+
+/// Casts the ray from `origin` along `direction` against the triangle list `indices` of
+/// `positions`, on the CPU with Möller–Trumbore. Both sides of the triangles are hit.
+pub fn ray_cast(
+    positions: &[Vec3],
+    indices: &[u32],
+    origin: Vec3,
+    direction: Vec3,
+) -> Option<RayHit> {
+    indices
+        .chunks_exact(3)
+        .enumerate()
+        .filter_map(|(triangle_index, triangle)| {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+            let (edge_1, edge_2) = (b - a, c - a);
+            let p = direction.cross(edge_2);
+            let determinant = edge_1.dot(p);
+            if determinant.abs() < f32::EPSILON {
+                return None;
+            }
+            let to_origin = origin - a;
+            let u = to_origin.dot(p) / determinant;
+            let q = to_origin.cross(edge_1);
+            let v = direction.dot(q) / determinant;
+            let distance = edge_2.dot(q) / determinant;
+            if u < 0. || v < 0. || u + v > 1. || distance <= 0. {
+                return None;
+            }
+            let normal = edge_1.cross(edge_2).normalize();
+            Some(RayHit {
+                distance,
+                position: origin + direction * distance,
+                normal: -normal * normal.dot(direction).signum(),
+                first_index: triangle_index * 3,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
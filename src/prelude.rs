@@ -0,0 +1,32 @@
+//! Types most examples need, `use vulkan_example_rs::prelude::*;` replaces their import blocks.
+//!
+//! `ash`, `glam` and `winit` are also re-exported from the crate root in the exact versions
+//! this crate is built against, for code that does not depend on them directly.
+
+pub use std::{cell::RefCell, rc::Rc};
+
+pub use ash::vk;
+pub use glam::{vec2, vec3, vec4, Mat4, Quat, Vec2, Vec3, Vec4};
+pub use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::Window};
+
+pub use crate::{
+    app::{
+        ClearValue, ComputePipelineBuilder, FixedVulkanStuff, FullscreenPass, HdrTarget, PerFrame,
+        PipelineBuilder,
+    },
+    camera::{Camera, CameraController, Frustum, MVPMatrix},
+    error::{RenderError, RenderResult},
+    impl_embedded_spv_fns, impl_pipeline_builder_fns, impl_vertex_layout, include_spv,
+    mesh::{Vertex, VertexLayout},
+    vulkan_wrappers::{
+        Buffer, BufferView, DescriptorSetBinder, DescriptorSetBuilder, Device, Instance,
+        OffscreenRenderTarget, QueueRequest, SetIndex, SurfaceChange, Texture, VulkanApiVersion,
+        VulkanDebugInfoStrategy,
+    },
+};
+
+#[cfg(feature = "ui")]
+pub use crate::{
+    app::{FrameContext, FrameCounter, UIOverlay, WindowApp},
+    impl_drop_trait, impl_window_fns,
+};
@@ -1,27 +1,79 @@
 mod instance;
-pub use instance::{Instance, InstanceBuilder, VulkanApiVersion, VulkanDebugInfoStrategy};
+pub use instance::{
+    ApiDump, DevicePreference, Instance, InstanceBuilder, ValidationFeatures, VulkanApiVersion,
+    VulkanDebugInfoStrategy, DEVICE_PREFERENCE_ENV,
+};
 
 mod surface;
-pub use surface::{extent_helper, Surface, SurfaceAttributes};
+pub use surface::{extent_helper, Surface, SurfaceAttributes, SurfaceChange};
 
 mod queue;
-pub use queue::{QueueInfo, QueueState};
+pub use queue::{NamedQueue, QueueInfo, QueueRequest, QueueState};
 
 mod device;
-pub use device::Device;
+pub use device::{Device, DeviceBuilder, VideoDecodeFns};
+
+mod capabilities;
+pub use capabilities::DeviceCapabilities;
 
 mod swapchain;
 pub use swapchain::SwapChainBatch;
 
 mod shader;
-pub use shader::{ShaderCreate, ShaderModule};
+pub use shader::{ShaderCreate, ShaderModule, SpecializationConstants};
 
 mod command;
 pub use command::OneTimeCommand;
 
+mod submit_graph;
+pub use submit_graph::{FrameSubmit, SubmitGraph, SubmitNode};
+
+mod descriptor;
+pub use descriptor::{
+    DescriptorSetBinder, DescriptorSetBuilder, DescriptorUpdateTemplate, SetIndex,
+};
+
 mod buffer;
 pub(crate) use buffer::memory_helper;
-pub use buffer::Buffer;
+pub use buffer::{Buffer, BufferView};
+
+mod memory_usage;
+pub use memory_usage::MemoryTypeUsage;
+
+mod staging_pool;
+pub use staging_pool::{StagingPool, StagingRegion};
+
+mod instance_buffer;
+pub use instance_buffer::InstanceBuffer;
+
+mod push_constants;
+pub use push_constants::PushConstants;
 
 mod image;
-pub use image::{DepthStencil, Texture};
+pub use image::{aspect_mask, DepthStencil, Texture};
+
+mod cubemap;
+
+mod render_pass;
+pub use render_pass::RenderPassBuilder;
+
+mod render_target;
+pub use render_target::OffscreenRenderTarget;
+
+mod scissor;
+pub use scissor::ScissorGuard;
+
+mod pipeline_cache;
+pub use pipeline_cache::{load_pipeline_cache, pipeline_cache_path, save_pipeline_cache};
+
+mod profile;
+pub use profile::{Profile, ProfileReport};
+
+mod sampler;
+pub use sampler::{SamplerCache, SamplerSettings};
+
+mod ycbcr;
+pub use ycbcr::{Nv12Texture, YcbcrSampler};
+
+mod video;
+pub use video::{DecodedPictureBuffer, H264DecodeProfile, VideoDecodeCapabilities};
@@ -2,17 +2,21 @@ use std::{ffi::c_void, marker::PhantomData, rc::Rc};
 
 use ash::{prelude::VkResult, vk};
 
-use super::{Device, OneTimeCommand};
+use super::{Device, MemoryTypeUsage, OneTimeCommand};
 use crate::error::{RenderError, RenderResult};
 
 pub struct Buffer<T> {
     buffer: vk::Buffer,
     device_momory: vk::DeviceMemory,
     size_in_bytes: vk::DeviceSize,
+    /// Of the memory, at least `size_in_bytes`
+    allocation_size: vk::DeviceSize,
     alignment: vk::DeviceSize,
     usage: vk::BufferUsageFlags,
     properties: vk::MemoryPropertyFlags,
     mapped_ptr: Option<*mut c_void>,
+    /// Offset and size of the mapped memory while mapped
+    mapped_range: (vk::DeviceSize, vk::DeviceSize),
     device: Rc<Device>,
     phantom: PhantomData<T>,
 }
@@ -42,7 +46,8 @@ impl<T> Buffer<T> {
                     properties,
                 )?)
                 .build();
-            let device_momory = device.allocate_memory(&allocate_info, None)?;
+            let device_momory = device
+                .allocate_tracked_memory(&allocate_info, MemoryTypeUsage::is_hot_buffer(usage))?;
 
             device.bind_buffer_memory(buffer, device_momory, 0)?;
 
@@ -50,10 +55,12 @@ impl<T> Buffer<T> {
                 buffer,
                 device_momory,
                 size_in_bytes,
+                allocation_size: memory_requirements.size,
                 alignment: memory_requirements.alignment,
                 usage,
                 properties,
                 mapped_ptr: None,
+                mapped_range: (0, 0),
                 device,
                 phantom: PhantomData::<T>,
             })
@@ -100,6 +107,73 @@ impl<T> Buffer<T> {
         self.mapped_ptr
     }
 
+    /// Device local storage buffer left uninitialized, e.g. the output of a compute shader.
+    /// `usage` is what the buffer is used for besides storage, e.g. `INDIRECT_BUFFER`.
+    pub fn storage(
+        element_num: usize,
+        usage: vk::BufferUsageFlags,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Self::new(
+            element_num,
+            vk::BufferUsageFlags::STORAGE_BUFFER | usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            device,
+        )
+    }
+
+    /// Storage buffer mapped in host coherent memory, for results read back by the host
+    pub fn host_storage(
+        element_num: usize,
+        usage: vk::BufferUsageFlags,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let mut buffer = Self::new(
+            element_num,
+            vk::BufferUsageFlags::STORAGE_BUFFER | usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            device,
+        )?;
+        buffer.map_memory_all()?;
+        Ok(buffer)
+    }
+
+    /// View of `range` bytes from `offset` as texels of `format`, for a buffer created
+    /// with `UNIFORM_TEXEL_BUFFER` or `STORAGE_TEXEL_BUFFER` usage
+    pub fn view(
+        &self,
+        format: vk::Format,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> RenderResult<BufferView> {
+        assert!(self.usage.intersects(
+            vk::BufferUsageFlags::UNIFORM_TEXEL_BUFFER | vk::BufferUsageFlags::STORAGE_TEXEL_BUFFER
+        ));
+        assert!(range == vk::WHOLE_SIZE || offset + range <= self.size_in_bytes);
+        let view = unsafe {
+            self.device.create_buffer_view(
+                &vk::BufferViewCreateInfo::builder()
+                    .buffer(self.buffer)
+                    .format(format)
+                    .offset(offset)
+                    .range(range)
+                    .build(),
+                None,
+            )?
+        };
+        Ok(BufferView {
+            view,
+            format,
+            usage: self.usage,
+            device: self.device.clone(),
+        })
+    }
+
+    /// View of the whole buffer as texels of `format`
+    pub fn view_default(&self, format: vk::Format) -> RenderResult<BufferView> {
+        self.view(format, 0, vk::WHOLE_SIZE)
+    }
+
     pub fn descriptor(
         &self,
         offset: vk::DeviceSize,
@@ -116,13 +190,15 @@ impl<T> Buffer<T> {
         self.descriptor(0, self.size_in_bytes)
     }
 
+    /// `vk::WHOLE_SIZE` maps up to the end of the memory, past the buffer when the
+    /// allocation is larger
     pub fn map_memory(
         &mut self,
         offset: vk::DeviceSize,
         size_in_bytes: vk::DeviceSize,
     ) -> VkResult<*mut c_void> {
         assert!(!self.is_mapped());
-        assert!(offset + size_in_bytes <= self.size_in_bytes);
+        assert!(size_in_bytes == vk::WHOLE_SIZE || offset + size_in_bytes <= self.size_in_bytes);
         unsafe {
             let ptr = self.device.map_memory(
                 self.device_momory,
@@ -131,12 +207,18 @@ impl<T> Buffer<T> {
                 vk::MemoryMapFlags::default(),
             )?;
             self.mapped_ptr = Some(ptr);
+            self.mapped_range = match size_in_bytes {
+                vk::WHOLE_SIZE => (offset, self.allocation_size - offset),
+                size => (offset, size),
+            };
             Ok(ptr)
         }
     }
 
+    /// Maps the whole memory, so that flushes and invalidations rounded to
+    /// `nonCoherentAtomSize` stay in the mapped range
     pub fn map_memory_all(&mut self) -> VkResult<*mut c_void> {
-        self.map_memory(0, self.size_in_bytes)
+        self.map_memory(0, vk::WHOLE_SIZE)
     }
 
     pub fn unmap_memory(&mut self) {
@@ -172,23 +254,114 @@ impl<T> Buffer<T> {
         queue: &vk::Queue,
     ) -> VkResult<()> {
         assert!(self.size_in_bytes == dst.size_in_bytes);
+        self.copy_region_to(dst, 0, 0, self.size_in_bytes, command_pool, queue)
+    }
 
+    /// Copies `size` bytes from `src_offset` to `dst_offset` of `dst` in a one-time command
+    pub fn copy_region_to<V>(
+        &self,
+        dst: &Buffer<V>,
+        src_offset: vk::DeviceSize,
+        dst_offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> VkResult<()> {
+        let region = vk::BufferCopy::builder()
+            .src_offset(src_offset)
+            .dst_offset(dst_offset)
+            .size(size)
+            .build();
+        OneTimeCommand::new(&self.device, command_pool)?.take_and_execute(
+            |command_buffer| {
+                self.cmd_copy_regions_to(command_buffer, dst, &[region]);
+                Ok(())
+            },
+            queue,
+        )
+    }
+
+    /// Records the copy of `regions`, in bytes, into `command_buffer`, e.g. along with the
+    /// other commands of a frame. Synchronizing with the accesses around it is up to the caller.
+    pub fn cmd_copy_regions_to<V>(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        dst: &Buffer<V>,
+        regions: &[vk::BufferCopy],
+    ) {
+        regions.iter().for_each(|region| {
+            assert!(region.src_offset + region.size <= self.size_in_bytes);
+            assert!(region.dst_offset + region.size <= dst.size_in_bytes);
+        });
+        unsafe {
+            self.device
+                .cmd_copy_buffer(command_buffer, self.buffer, dst.buffer, regions)
+        };
+    }
+
+    /// Copies the buffer into host visible memory in a one-time command, waits for it and
+    /// returns the elements, e.g. to verify what a compute shader wrote. The buffer needs
+    /// `TRANSFER_SRC` usage, and writes to it submitted to other queues must have finished.
+    pub fn read_back(
+        &self,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Vec<T>>
+    where
+        T: Copy,
+    {
+        assert!(self.usage.contains(vk::BufferUsageFlags::TRANSFER_SRC));
+        let mut readback = Buffer::<T>::new(
+            self.element_num(),
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            self.device.clone(),
+        )?;
         OneTimeCommand::new(&self.device, command_pool)?.take_and_execute(
             |command_buffer| unsafe {
-                self.device.cmd_copy_buffer(
+                // Writes submitted earlier to the same queue land before the copy
+                self.device.cmd_pipeline_barrier(
                     command_buffer,
-                    self.buffer,
-                    dst.buffer,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier::builder()
+                        .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                        .build()],
+                    &[],
+                    &[],
+                );
+                self.cmd_copy_regions_to(
+                    command_buffer,
+                    &readback,
                     &[vk::BufferCopy::builder().size(self.size_in_bytes).build()],
                 );
+                self.device.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::HOST,
+                    vk::DependencyFlags::empty(),
+                    &[vk::MemoryBarrier::builder()
+                        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                        .dst_access_mask(vk::AccessFlags::HOST_READ)
+                        .build()],
+                    &[],
+                    &[],
+                );
                 Ok(())
             },
             queue,
         )?;
 
-        Ok(())
+        let ptr = readback.map_memory_all()? as *const T;
+        let elements = unsafe { std::slice::from_raw_parts(ptr, self.element_num()) }.to_vec();
+        Ok(elements)
     }
 
+    /// Writes `data` directly when [`Device::supports_direct_upload`],
+    /// through a staging buffer otherwise. `usage` is what the buffer is used for besides
+    /// being written, e.g. `VERTEX_BUFFER | INDIRECT_BUFFER`.
     pub fn new_device_local(
         data: &[T],
         usage: vk::BufferUsageFlags,
@@ -196,49 +369,192 @@ impl<T> Buffer<T> {
         command_pool: &vk::CommandPool,
         queue: &vk::Queue,
     ) -> RenderResult<Self> {
-        assert!(
-            usage == vk::BufferUsageFlags::INDEX_BUFFER
-                || usage == vk::BufferUsageFlags::VERTEX_BUFFER
-        );
-
-        let element_num = data.len();
-
-        let staging_buffer = {
-            let mut buffer = Buffer::<T>::new(
-                element_num,
-                vk::BufferUsageFlags::TRANSFER_SRC,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                device.clone(),
-            )?;
-            buffer.load_data(data, 0)?;
-            buffer
-        };
+        if device.supports_direct_upload() {
+            Self::new_device_local_direct(data, usage, device)
+        } else {
+            Self::new_device_local_staged(data, usage, device, command_pool, queue)
+        }
+    }
+
+    /// Maps device local memory and writes `data` without a staging copy
+    pub fn new_device_local_direct(
+        data: &[T],
+        usage: vk::BufferUsageFlags,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let mut buffer = Buffer::<T>::new(
+            data.len(),
+            usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+            device,
+        )?;
+        buffer.load_data(data, 0)?;
+
+        Ok(buffer)
+    }
+
+    pub fn device_local_vertex(
+        vertices: &[T],
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        Self::new_device_local(
+            vertices,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            device,
+            command_pool,
+            queue,
+        )
+    }
 
+    pub fn device_local_index(
+        indices: &[T],
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        Self::new_device_local(
+            indices,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            device,
+            command_pool,
+            queue,
+        )
+    }
+
+    pub fn device_local_storage(
+        elements: &[T],
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        Self::new_device_local(
+            elements,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            device,
+            command_pool,
+            queue,
+        )
+    }
+
+    pub fn new_device_local_staged(
+        data: &[T],
+        usage: vk::BufferUsageFlags,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
         let device_local_buffer = Buffer::<T>::new(
-            element_num,
+            data.len(),
             vk::BufferUsageFlags::TRANSFER_DST | usage,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
             device.clone(),
         )?;
-        staging_buffer.copy_to(&device_local_buffer, command_pool, queue)?;
+
+        let mut staging_pool = device.staging_pool();
+        let region = staging_pool.stage(data, &device)?;
+        staging_pool.submit(&region, |fence| {
+            OneTimeCommand::new(&device, command_pool)?.take_and_execute_with_fence(
+                |command_buffer| unsafe {
+                    device.cmd_copy_buffer(
+                        command_buffer,
+                        region.buffer,
+                        device_local_buffer.buffer,
+                        &[vk::BufferCopy::builder()
+                            .src_offset(region.offset)
+                            .size(region.size)
+                            .build()],
+                    );
+                    Ok(())
+                },
+                queue,
+                fence,
+            )
+        })?;
 
         Ok(device_local_buffer)
     }
 
+    /// Makes host writes to the whole mapped range visible to the device, see
+    /// [`Self::flush_range`]
     pub fn flush(&self) -> VkResult<()> {
-        assert!(
-            self.properties & vk::MemoryPropertyFlags::HOST_COHERENT
-                != vk::MemoryPropertyFlags::HOST_COHERENT
-        );
-        assert!(self.is_mapped());
+        self.flush_range(self.mapped_range.0, vk::WHOLE_SIZE)
+    }
+
+    /// Makes host writes to `size` bytes from `offset` visible to the device. Nothing to do
+    /// in `HOST_COHERENT` memory, so callers may flush whatever memory they got.
+    ///
+    /// The range is rounded to whole `nonCoherentAtomSize` atoms within the mapped range,
+    /// whose offset should be a multiple of them. `vk::WHOLE_SIZE` reaches the end of it.
+    pub fn flush_range(&self, offset: vk::DeviceSize, size: vk::DeviceSize) -> VkResult<()> {
+        if self.is_coherent() {
+            return Ok(());
+        }
         unsafe {
             self.device
-                .flush_mapped_memory_ranges(&[vk::MappedMemoryRange::builder()
-                    .offset(0)
-                    .memory(self.device_momory)
-                    .build()])
+                .flush_mapped_memory_ranges(&[self.non_coherent_range(offset, size)])
         }
     }
+
+    /// Makes device writes to the whole mapped range visible to the host, see
+    /// [`Self::invalidate_range`]
+    pub fn invalidate(&self) -> VkResult<()> {
+        self.invalidate_range(self.mapped_range.0, vk::WHOLE_SIZE)
+    }
+
+    /// Makes device writes to `size` bytes from `offset` visible to the host, once they
+    /// are available to it, e.g. after a fence waited for them. Rounded like
+    /// [`Self::flush_range`], and nothing to do in `HOST_COHERENT` memory either.
+    pub fn invalidate_range(&self, offset: vk::DeviceSize, size: vk::DeviceSize) -> VkResult<()> {
+        if self.is_coherent() {
+            return Ok(());
+        }
+        unsafe {
+            self.device
+                .invalidate_mapped_memory_ranges(&[self.non_coherent_range(offset, size)])
+        }
+    }
+
+    fn is_coherent(&self) -> bool {
+        self.properties
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    /// `size` bytes from `offset` grown to whole atoms, clamped to the mapped range
+    fn non_coherent_range(
+        &self,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> vk::MappedMemoryRange {
+        assert!(self.is_mapped());
+        let (mapped_offset, mapped_size) = self.mapped_range;
+        let mapped_end = mapped_offset + mapped_size;
+        let end = match size {
+            vk::WHOLE_SIZE => mapped_end,
+            size => offset + size,
+        };
+        assert!(mapped_offset <= offset && end <= mapped_end);
+
+        let atom_size = self.device.non_coherent_atom_size();
+        let start = (offset - offset % atom_size).max(mapped_offset);
+        let end = end.next_multiple_of(atom_size);
+        let size = if end < mapped_end {
+            end - start
+        } else if mapped_end == self.allocation_size {
+            // An end past the memory is only valid as the end of it
+            vk::WHOLE_SIZE
+        } else {
+            mapped_end - start
+        };
+        vk::MappedMemoryRange::builder()
+            .memory(self.device_momory)
+            .offset(start)
+            .size(size)
+            .build()
+    }
 }
 
 impl<T> Drop for Buffer<T> {
@@ -248,35 +564,76 @@ impl<T> Drop for Buffer<T> {
                 self.unmap_memory();
             }
             self.device.destroy_buffer(self.buffer, None);
-            self.device.free_memory(self.device_momory, None);
+            self.device.free_tracked_memory(self.device_momory);
         }
     }
 }
 
+/// Texel view of a [`Buffer`], which must outlive it
+pub struct BufferView {
+    view: vk::BufferView,
+    format: vk::Format,
+    usage: vk::BufferUsageFlags,
+    device: Rc<Device>,
+}
+
+impl BufferView {
+    pub fn view(&self) -> vk::BufferView {
+        self.view
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// Usage of the viewed buffer
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+}
+
+impl Drop for BufferView {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_buffer_view(self.view, None) }
+    }
+}
+
 pub mod memory_helper {
     use super::*;
 
+    /// Host visible device local memory without resizable BAR
+    pub const BAR_WINDOW_SIZE: vk::DeviceSize = 256 * 1024 * 1024;
+
+    pub fn upload_heap_size(
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    ) -> Option<vk::DeviceSize> {
+        let upload_properties = vk::MemoryPropertyFlags::DEVICE_LOCAL
+            | vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::HOST_COHERENT;
+        memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+            .iter()
+            .filter(|memory_type| memory_type.property_flags.contains(upload_properties))
+            .map(|memory_type| memory_properties.memory_heaps[memory_type.heap_index as usize].size)
+            .max()
+    }
+
     pub fn find_memory_type(
         device: &Device,
         requirement: &vk::MemoryRequirements,
         properties: vk::MemoryPropertyFlags,
     ) -> RenderResult<u32> {
-        unsafe {
-            let physical_mem_properties = device.instance().get_physical_device_memory_properties(
-                *device.physical_device().upgrade().unwrap(),
-            );
-            for i in 0..physical_mem_properties.memory_type_count {
-                if (requirement.memory_type_bits & (1 << i)) != 0
-                    && (physical_mem_properties.memory_types[i as usize].property_flags
-                        & properties)
-                        == properties
-                {
-                    return Ok(i);
-                }
-            }
-            Err(RenderError::MemoryTypeNotSupported(
-                "Failed to find suitable memory type".to_string(),
-            ))
-        }
+        let memory_properties = device.memory_properties();
+        (0..memory_properties.memory_type_count)
+            .find(|&i| {
+                (requirement.memory_type_bits & (1 << i)) != 0
+                    && memory_properties.memory_types[i as usize]
+                        .property_flags
+                        .contains(properties)
+            })
+            .ok_or_else(|| {
+                RenderError::MemoryTypeNotSupported(
+                    "Failed to find suitable memory type".to_string(),
+                )
+            })
     }
 }
@@ -0,0 +1,118 @@
+use std::fmt::Display;
+
+use ash::vk;
+
+/// Depth formats in order of preference
+const DEPTH_FORMAT_CANDIDATES: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+/// Limits and format support of the physical device, read once when the device is created,
+/// for subsystems to clamp their settings to instead of failing at creation time
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceCapabilities {
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_cube: u32,
+    pub max_image_array_layers: u32,
+    pub max_push_constants_size: u32,
+    /// 1 when `samplerAnisotropy` is not enabled
+    pub max_sampler_anisotropy: f32,
+    /// Supported by both color and depth framebuffer attachments
+    pub framebuffer_sample_counts: vk::SampleCountFlags,
+    /// Usable as optimally tiled depth attachments, in order of preference
+    pub depth_formats: Vec<vk::Format>,
+}
+
+impl DeviceCapabilities {
+    pub(super) fn query(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        enabled_features: &vk::PhysicalDeviceFeatures,
+    ) -> Self {
+        let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+        let depth_formats = DEPTH_FORMAT_CANDIDATES
+            .into_iter()
+            .filter(|&format| {
+                unsafe { instance.get_physical_device_format_properties(physical_device, format) }
+                    .optimal_tiling_features
+                    .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+            })
+            .collect();
+        Self {
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_image_dimension_cube: limits.max_image_dimension_cube,
+            max_image_array_layers: limits.max_image_array_layers,
+            max_push_constants_size: limits.max_push_constants_size,
+            max_sampler_anisotropy: match enabled_features.sampler_anisotropy {
+                vk::TRUE => limits.max_sampler_anisotropy.max(1.),
+                _ => 1.,
+            },
+            framebuffer_sample_counts: limits.framebuffer_color_sample_counts
+                & limits.framebuffer_depth_sample_counts,
+            depth_formats,
+        }
+    }
+
+    /// Highest of [`Self::framebuffer_sample_counts`]
+    pub fn max_usable_sample_count(&self) -> vk::SampleCountFlags {
+        self.clamp_sample_count(vk::SampleCountFlags::TYPE_64)
+    }
+
+    /// Highest supported sample count not above `requested`, `TYPE_1` being always supported
+    pub fn clamp_sample_count(&self, requested: vk::SampleCountFlags) -> vk::SampleCountFlags {
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|&count| {
+            count.as_raw() <= requested.as_raw() && self.framebuffer_sample_counts.contains(count)
+        })
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+
+    /// Anisotropy is disabled at 1
+    pub fn clamp_anisotropy(&self, requested: f32) -> f32 {
+        requested.clamp(1., self.max_sampler_anisotropy)
+    }
+
+    /// Each side clamped to [`Self::max_image_dimension_2d`]
+    pub fn clamp_extent(&self, extent: vk::Extent2D) -> vk::Extent2D {
+        vk::Extent2D {
+            width: extent.width.clamp(1, self.max_image_dimension_2d),
+            height: extent.height.clamp(1, self.max_image_dimension_2d),
+        }
+    }
+
+    pub fn preferred_depth_format(&self) -> Option<vk::Format> {
+        self.depth_formats.first().copied()
+    }
+}
+
+impl Display for DeviceCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Max 2D image dimension: {}, cube: {}, array layers: {}",
+            self.max_image_dimension_2d, self.max_image_dimension_cube, self.max_image_array_layers
+        )?;
+        writeln!(
+            f,
+            "Max push constants size: {} bytes",
+            self.max_push_constants_size
+        )?;
+        writeln!(f, "Max sampler anisotropy: {}", self.max_sampler_anisotropy)?;
+        writeln!(
+            f,
+            "Max usable sample count: {}",
+            self.max_usable_sample_count().as_raw()
+        )?;
+        write!(f, "Depth formats: {:?}", self.depth_formats)
+    }
+}
@@ -50,10 +50,19 @@ impl<'a> OneTimeCommand<'a> {
 
     pub fn end_and_submit(&self, queue: &vk::Queue) -> VkResult<()> {
         unsafe {
-            self.device.end_command_buffer(self.command_buffer)?;
             let fence = self
                 .device
                 .create_fence(&vk::FenceCreateInfo::default(), None)?;
+            let result = self.end_and_submit_with_fence(queue, fence);
+            self.device.destroy_fence(fence, None);
+            result
+        }
+    }
+
+    /// Signals `fence`, owned by the caller, and waits for it
+    pub fn end_and_submit_with_fence(&self, queue: &vk::Queue, fence: vk::Fence) -> VkResult<()> {
+        unsafe {
+            self.device.end_command_buffer(self.command_buffer)?;
             self.device.queue_submit(
                 *queue,
                 &[vk::SubmitInfo::builder()
@@ -62,7 +71,6 @@ impl<'a> OneTimeCommand<'a> {
                 fence,
             )?;
             self.device.wait_for_fences(&[fence], true, u64::MAX)?;
-            self.device.destroy_fence(fence, None);
         }
 
         Ok(())
@@ -77,6 +85,21 @@ impl<'a> OneTimeCommand<'a> {
         self.end_and_submit(queue)?;
         Ok(())
     }
+
+    pub fn take_and_execute_with_fence<F>(
+        &self,
+        mut f: F,
+        queue: &vk::Queue,
+        fence: vk::Fence,
+    ) -> VkResult<()>
+    where
+        F: FnMut(vk::CommandBuffer) -> VkResult<()>,
+    {
+        self.begin()?;
+        f(self.command_buffer)?;
+        self.end_and_submit_with_fence(queue, fence)?;
+        Ok(())
+    }
 }
 
 impl<'a> Drop for OneTimeCommand<'a> {
@@ -0,0 +1,264 @@
+use std::{path::Path, rc::Rc};
+
+use ash::vk;
+
+use super::{Buffer, Device, OneTimeCommand, ShaderCreate, Texture};
+use crate::{error::RenderResult, include_spv};
+
+impl Texture {
+    /// Storage images of this format are supported by every implementation
+    pub const HDR_CUBEMAP_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+    /// Loads a Radiance `.hdr` panorama in equirectangular projection, y up, into a
+    /// [`Self::HDR_CUBEMAP_FORMAT`] cube with faces a quarter of its width and a full
+    /// mip chain. Returned in `SHADER_READ_ONLY_OPTIMAL` with an image view and a
+    /// linear sampler. `queue` must support compute and waits for the conversion.
+    pub fn cubemap_from_equirect_hdr<P: AsRef<Path>>(
+        path: P,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        let panorama = image_loader::io::Reader::open(&path)?.decode()?.to_rgb32f();
+        let (width, height) = panorama.dimensions();
+        let panorama_buffer =
+            Buffer::device_local_storage(panorama.as_raw(), device.clone(), command_pool, queue)?;
+
+        let face_size = (width / 4).max(1);
+        let mip_levels = face_size.ilog2() + 1;
+        let mut cubemap = Self::builder(
+            face_size,
+            face_size,
+            Self::HDR_CUBEMAP_FORMAT,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            device.clone(),
+        )
+        .mip_levels(mip_levels)
+        .array_layers(6)
+        .cube_compatible()
+        .build()?;
+
+        let mut converter = EquirectConverter::new(mip_levels, device.clone())?;
+        let command = OneTimeCommand::new_and_begin(&device, command_pool)?;
+        let command_buffer = *command.command_buffer();
+        cubemap.transition_layout(
+            command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+        );
+        converter.cmd_convert(command_buffer, &panorama_buffer, [width, height], &cubemap)?;
+        cubemap.transition_layout(
+            command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+        command.end_and_submit(queue)?;
+
+        cubemap.spawn_image_view()?;
+        cubemap.spawn_sampler(vk::Filter::LINEAR)?;
+        Ok(cubemap)
+    }
+}
+
+/// Compute pipeline writing every mip of a cube from a panorama in a storage buffer,
+/// with one descriptor set and storage view per mip
+struct EquirectConverter {
+    device: Rc<Device>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    storage_views: Vec<vk::ImageView>,
+}
+
+impl EquirectConverter {
+    fn new(mip_levels: u32, device: Rc<Device>) -> RenderResult<Self> {
+        let descriptor_pool = {
+            let pool_sizes = [
+                vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(mip_levels)
+                    .build(),
+                vk::DescriptorPoolSize::builder()
+                    .ty(vk::DescriptorType::STORAGE_IMAGE)
+                    .descriptor_count(mip_levels)
+                    .build(),
+            ];
+            let create_info = vk::DescriptorPoolCreateInfo::builder()
+                .pool_sizes(&pool_sizes)
+                .max_sets(mip_levels)
+                .build();
+            unsafe { device.create_descriptor_pool(&create_info, None)? }
+        };
+
+        let descriptor_set_layout = {
+            let bindings = [
+                vk::DescriptorSetLayoutBinding::builder()
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .binding(0)
+                    .descriptor_count(1)
+                    .build(),
+                vk::DescriptorSetLayoutBinding::builder()
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .binding(1)
+                    .descriptor_count(1)
+                    .build(),
+            ];
+            let create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            unsafe { device.create_descriptor_set_layout(&create_info, None)? }
+        };
+
+        let descriptor_sets = unsafe {
+            let set_layouts = vec![descriptor_set_layout; mip_levels as usize];
+            let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts)
+                .build();
+            device.allocate_descriptor_sets(&allocate_info)?
+        };
+
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(&[descriptor_set_layout])
+                    .push_constant_ranges(&[vk::PushConstantRange::builder()
+                        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                        .size(std::mem::size_of::<[u32; 2]>() as u32)
+                        .build()])
+                    .build(),
+                None,
+            )?
+        };
+        let shader_create = ShaderCreate::from_bytes(
+            include_spv!("src/shaders/equirect_to_cube.comp.spv"),
+            vk::ShaderStageFlags::COMPUTE,
+            device.clone(),
+        )?;
+        let pipeline = unsafe {
+            device
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    &[vk::ComputePipelineCreateInfo::builder()
+                        .stage(shader_create.stage_create_info)
+                        .layout(pipeline_layout)
+                        .build()],
+                    None,
+                )
+                .map_err(|(_, e)| e)?[0]
+        };
+
+        Ok(Self {
+            device,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            pipeline_layout,
+            pipeline,
+            storage_views: Vec::new(),
+        })
+    }
+
+    /// `cubemap` must be in `GENERAL`
+    fn cmd_convert(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        panorama: &Buffer<f32>,
+        panorama_extent: [u32; 2],
+        cubemap: &Texture,
+    ) -> RenderResult<()> {
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&panorama_extent),
+            );
+        }
+
+        for mip_level in 0..cubemap.mip_levels() {
+            let storage_view = {
+                let create_info = vk::ImageViewCreateInfo::builder()
+                    .image(*cubemap.image())
+                    .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                    .format(cubemap.format())
+                    .subresource_range(
+                        vk::ImageSubresourceRange::builder()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .base_mip_level(mip_level)
+                            .level_count(1)
+                            .base_array_layer(0)
+                            .layer_count(6)
+                            .build(),
+                    )
+                    .build();
+                unsafe { self.device.create_image_view(&create_info, None)? }
+            };
+            self.storage_views.push(storage_view);
+
+            let descriptor_set = self.descriptor_sets[mip_level as usize];
+            let descriptor_writes = [
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&[panorama.descriptor_default()])
+                    .build(),
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(descriptor_set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(&[vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::GENERAL)
+                        .image_view(storage_view)
+                        .build()])
+                    .build(),
+            ];
+            let size = (cubemap.extent2d().width >> mip_level).max(1);
+            unsafe {
+                self.device.update_descriptor_sets(&descriptor_writes, &[]);
+                self.device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline_layout,
+                    0,
+                    &[descriptor_set],
+                    &[],
+                );
+                // Must match the local size of the compute shader
+                self.device
+                    .cmd_dispatch(command_buffer, size.div_ceil(8), size.div_ceil(8), 6);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EquirectConverter {
+    fn drop(&mut self) {
+        unsafe {
+            for &view in &self.storage_views {
+                self.device.destroy_image_view(view, None);
+            }
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
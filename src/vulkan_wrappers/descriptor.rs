@@ -0,0 +1,453 @@
+use std::{ffi::c_void, mem::size_of, rc::Rc};
+
+use ash::vk;
+
+use super::{Buffer, BufferView, Device, Texture};
+use crate::error::{RenderError, RenderResult};
+
+/// One element of the data an update template reads, with a fixed stride for all entries
+#[repr(C)]
+#[derive(Clone, Copy)]
+union DescriptorInfo {
+    buffer: vk::DescriptorBufferInfo,
+    image: vk::DescriptorImageInfo,
+    texel_buffer_view: vk::BufferView,
+}
+
+/// Which member of [`DescriptorInfo`] a write holds
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum DescriptorKind {
+    Buffer,
+    Image,
+    TexelBuffer,
+}
+
+impl DescriptorKind {
+    fn of(descriptor_type: vk::DescriptorType) -> Self {
+        match descriptor_type {
+            vk::DescriptorType::UNIFORM_BUFFER
+            | vk::DescriptorType::STORAGE_BUFFER
+            | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+            | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => Self::Buffer,
+            vk::DescriptorType::UNIFORM_TEXEL_BUFFER | vk::DescriptorType::STORAGE_TEXEL_BUFFER => {
+                Self::TexelBuffer
+            }
+            _ => Self::Image,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct DescriptorWrite {
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    kind: DescriptorKind,
+}
+
+/// Collects the descriptors of a set and writes them, each checked against the
+/// binding of the same number in the bindings the set layout was created with
+pub struct DescriptorSetBuilder<'a> {
+    bindings: &'a [vk::DescriptorSetLayoutBinding],
+    writes: Vec<DescriptorWrite>,
+    infos: Vec<DescriptorInfo>,
+    device: Rc<Device>,
+}
+
+impl<'a> DescriptorSetBuilder<'a> {
+    pub fn new(bindings: &'a [vk::DescriptorSetLayoutBinding], device: Rc<Device>) -> Self {
+        Self {
+            bindings,
+            writes: vec![],
+            infos: vec![],
+            device,
+        }
+    }
+
+    pub fn uniform_buffer<T>(self, binding: u32, buffer: &Buffer<T>) -> Self {
+        self.buffer_info(
+            binding,
+            vk::DescriptorType::UNIFORM_BUFFER,
+            buffer.descriptor_default(),
+        )
+    }
+
+    /// `buffer` must have been created with `STORAGE_BUFFER` usage, e.g. by
+    /// [`Buffer::storage`]
+    pub fn storage_buffer<T>(self, binding: u32, buffer: &Buffer<T>) -> Self {
+        debug_assert!(buffer
+            .usage()
+            .contains(vk::BufferUsageFlags::STORAGE_BUFFER));
+        self.buffer_info(
+            binding,
+            vk::DescriptorType::STORAGE_BUFFER,
+            buffer.descriptor_default(),
+        )
+    }
+
+    /// Elements `first..first + count` of a storage buffer
+    pub fn storage_buffer_range<T>(
+        self,
+        binding: u32,
+        buffer: &Buffer<T>,
+        first: usize,
+        count: usize,
+    ) -> Self {
+        assert!(first + count <= buffer.element_num());
+        self.buffer_info(
+            binding,
+            vk::DescriptorType::STORAGE_BUFFER,
+            buffer.descriptor(
+                first as vk::DeviceSize * Buffer::<T>::element_size_in_bytes(),
+                count as vk::DeviceSize * Buffer::<T>::element_size_in_bytes(),
+            ),
+        )
+    }
+
+    pub fn uniform_texel_buffer(self, binding: u32, view: &BufferView) -> Self {
+        debug_assert!(view
+            .usage()
+            .contains(vk::BufferUsageFlags::UNIFORM_TEXEL_BUFFER));
+        self.texel_buffer_view(
+            binding,
+            vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+            view.view(),
+        )
+    }
+
+    pub fn storage_texel_buffer(self, binding: u32, view: &BufferView) -> Self {
+        debug_assert!(view
+            .usage()
+            .contains(vk::BufferUsageFlags::STORAGE_TEXEL_BUFFER));
+        self.texel_buffer_view(
+            binding,
+            vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+            view.view(),
+        )
+    }
+
+    /// `texture` must have its image view and sampler spawned
+    pub fn combined_image_sampler(self, binding: u32, texture: &Texture) -> Self {
+        self.image_info(
+            binding,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            texture.descriptor_default(),
+        )
+    }
+
+    /// `texture` must have `STORAGE` usage, e.g. built by [`Texture::builder`] with
+    /// `storage()`, and its image view spawned. It is accessed in `GENERAL` layout.
+    pub fn storage_image(self, binding: u32, texture: &Texture) -> Self {
+        self.image_info(
+            binding,
+            vk::DescriptorType::STORAGE_IMAGE,
+            texture.storage_descriptor(),
+        )
+    }
+
+    pub fn buffer_info(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        info: vk::DescriptorBufferInfo,
+    ) -> Self {
+        self.writes.push(DescriptorWrite {
+            binding,
+            descriptor_type,
+            kind: DescriptorKind::Buffer,
+        });
+        self.infos.push(DescriptorInfo { buffer: info });
+        self
+    }
+
+    pub fn image_info(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        info: vk::DescriptorImageInfo,
+    ) -> Self {
+        self.writes.push(DescriptorWrite {
+            binding,
+            descriptor_type,
+            kind: DescriptorKind::Image,
+        });
+        self.infos.push(DescriptorInfo { image: info });
+        self
+    }
+
+    pub fn texel_buffer_view(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        view: vk::BufferView,
+    ) -> Self {
+        self.writes.push(DescriptorWrite {
+            binding,
+            descriptor_type,
+            kind: DescriptorKind::TexelBuffer,
+        });
+        self.infos.push(DescriptorInfo {
+            texel_buffer_view: view,
+        });
+        self
+    }
+
+    /// Allocates a set of `layout` from `pool` and writes the descriptors to it
+    pub fn build(
+        &self,
+        pool: vk::DescriptorPool,
+        layout: vk::DescriptorSetLayout,
+    ) -> RenderResult<vk::DescriptorSet> {
+        self.validate()?;
+        let set = unsafe {
+            self.device.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::builder()
+                    .descriptor_pool(pool)
+                    .set_layouts(&[layout])
+                    .build(),
+            )?[0]
+        };
+        self.write_unchecked(set);
+        Ok(set)
+    }
+
+    /// Writes the descriptors to an already allocated `set`,
+    /// which must not be in use by pending command buffers
+    pub fn update(&self, set: vk::DescriptorSet) -> RenderResult<()> {
+        self.validate()?;
+        self.write_unchecked(set);
+        Ok(())
+    }
+
+    /// Writes through `template` when it is backed by a native update template,
+    /// `template` must have been created from a builder with the same bindings in the same order
+    pub fn update_with_template(
+        &self,
+        template: &DescriptorUpdateTemplate,
+        set: vk::DescriptorSet,
+    ) -> RenderResult<()> {
+        if template.writes != self.writes {
+            return Err(RenderError::DescriptorSetInvalid(
+                "descriptors differ from those of the update template".to_string(),
+            ));
+        }
+        match template.template {
+            Some(template) => unsafe {
+                self.device.update_descriptor_set_with_template(
+                    set,
+                    template,
+                    self.infos.as_ptr() as *const c_void,
+                )
+            },
+            None => self.write_unchecked(set),
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> RenderResult<()> {
+        self.writes.iter().try_for_each(|write| {
+            let binding = self
+                .bindings
+                .iter()
+                .find(|binding| binding.binding == write.binding)
+                .ok_or_else(|| {
+                    RenderError::DescriptorSetInvalid(format!(
+                        "binding {} is not in the set layout",
+                        write.binding
+                    ))
+                })?;
+            if binding.descriptor_type != write.descriptor_type {
+                return Err(RenderError::DescriptorSetInvalid(format!(
+                    "binding {} is {:?} in the set layout, but written as {:?}",
+                    write.binding, binding.descriptor_type, write.descriptor_type
+                )));
+            }
+            if DescriptorKind::of(write.descriptor_type) != write.kind {
+                return Err(RenderError::DescriptorSetInvalid(format!(
+                    "binding {} is {:?}, but written with {:?} info",
+                    write.binding, write.descriptor_type, write.kind
+                )));
+            }
+            Ok(())
+        })
+    }
+
+    fn write_unchecked(&self, set: vk::DescriptorSet) {
+        let writes = self
+            .writes
+            .iter()
+            .zip(self.infos.iter())
+            .map(|(write, info)| {
+                let builder = vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(write.binding)
+                    .dst_array_element(0)
+                    .descriptor_type(write.descriptor_type);
+                // The union member is the one `kind` tells was written
+                unsafe {
+                    match write.kind {
+                        DescriptorKind::Buffer => builder
+                            .buffer_info(std::slice::from_ref(&info.buffer))
+                            .build(),
+                        DescriptorKind::Image => builder
+                            .image_info(std::slice::from_ref(&info.image))
+                            .build(),
+                        DescriptorKind::TexelBuffer => builder
+                            .texel_buffer_view(std::slice::from_ref(&info.texel_buffer_view))
+                            .build(),
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        unsafe { self.device.update_descriptor_sets(&writes, &[]) }
+    }
+}
+
+/// Replays the descriptor writes of a [`DescriptorSetBuilder`] with a single call,
+/// falling back to plain descriptor writes without `VK_KHR_descriptor_update_template`
+pub struct DescriptorUpdateTemplate {
+    template: Option<vk::DescriptorUpdateTemplate>,
+    writes: Vec<DescriptorWrite>,
+    device: Rc<Device>,
+}
+
+impl DescriptorUpdateTemplate {
+    /// Takes the bindings written by `builder`, whose resources may differ on each update
+    pub fn new(
+        builder: &DescriptorSetBuilder,
+        layout: vk::DescriptorSetLayout,
+    ) -> RenderResult<Self> {
+        builder.validate()?;
+        let template = if builder.device.supports_descriptor_update_templates() {
+            let entries = builder
+                .writes
+                .iter()
+                .enumerate()
+                .map(|(i, write)| {
+                    vk::DescriptorUpdateTemplateEntry::builder()
+                        .dst_binding(write.binding)
+                        .dst_array_element(0)
+                        .descriptor_count(1)
+                        .descriptor_type(write.descriptor_type)
+                        .offset(i * size_of::<DescriptorInfo>())
+                        .stride(size_of::<DescriptorInfo>())
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            Some(
+                builder.device.create_descriptor_update_template(
+                    &vk::DescriptorUpdateTemplateCreateInfo::builder()
+                        .descriptor_update_entries(&entries)
+                        .template_type(vk::DescriptorUpdateTemplateType::DESCRIPTOR_SET)
+                        .descriptor_set_layout(layout)
+                        .build(),
+                )?,
+            )
+        } else {
+            None
+        };
+        Ok(Self {
+            template,
+            writes: builder.writes.clone(),
+            device: builder.device.clone(),
+        })
+    }
+
+    /// Whether updates go through a native update template instead of the fallback
+    pub fn is_native(&self) -> bool {
+        self.template.is_some()
+    }
+}
+
+impl Drop for DescriptorUpdateTemplate {
+    fn drop(&mut self) {
+        if let Some(template) = self.template {
+            self.device.destroy_descriptor_update_template(template);
+        }
+    }
+}
+
+/// Set numbers by update frequency. Sets that change less often come first, so that
+/// rebinding one leaves the lower ones bound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SetIndex {
+    /// Camera, lights and other data of a whole frame
+    Frame = 0,
+    /// Textures and factors shared by the draws of one material
+    Material = 1,
+    /// Data of a single draw, e.g. a node transform too large for push constants
+    Draw = 2,
+}
+
+impl From<SetIndex> for u32 {
+    fn from(index: SetIndex) -> Self {
+        index as u32
+    }
+}
+
+/// Tracks the descriptor sets bound on a command buffer for one pipeline layout and
+/// skips the binds of sets already in place, e.g. materials shared by consecutive draws.
+/// Dynamic offsets are not supported.
+pub struct DescriptorSetBinder {
+    bind_point: vk::PipelineBindPoint,
+    pipeline_layout: vk::PipelineLayout,
+    bound: Vec<vk::DescriptorSet>,
+    device: Rc<Device>,
+}
+
+impl DescriptorSetBinder {
+    pub fn new(
+        bind_point: vk::PipelineBindPoint,
+        pipeline_layout: vk::PipelineLayout,
+        device: Rc<Device>,
+    ) -> Self {
+        Self {
+            bind_point,
+            pipeline_layout,
+            bound: vec![],
+            device,
+        }
+    }
+
+    /// Forgets every bound set, to call when recording a new command buffer
+    pub fn reset(&mut self) {
+        self.bound.clear();
+    }
+
+    /// Binds through `pipeline_layout` from now on. The sets bound so far are forgotten
+    /// when it differs, layouts are not checked for compatibility.
+    pub fn set_pipeline_layout(&mut self, pipeline_layout: vk::PipelineLayout) {
+        if pipeline_layout != self.pipeline_layout {
+            self.pipeline_layout = pipeline_layout;
+            self.reset();
+        }
+    }
+
+    /// Binds `set` at `set_index` unless it is already bound there, returns whether a
+    /// bind was recorded
+    pub fn bind(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        set_index: impl Into<u32>,
+        set: vk::DescriptorSet,
+    ) -> bool {
+        let set_index = set_index.into() as usize;
+        if self.bound.get(set_index) == Some(&set) {
+            return false;
+        }
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                self.bind_point,
+                self.pipeline_layout,
+                set_index as u32,
+                &[set],
+                &[],
+            );
+        }
+        if self.bound.len() <= set_index {
+            self.bound.resize(set_index + 1, vk::DescriptorSet::null());
+        }
+        self.bound[set_index] = set;
+        true
+    }
+}
@@ -1,57 +1,530 @@
 use std::{
-    ffi::CStr,
+    cell::{RefCell, RefMut},
+    ffi::{CStr, CString},
     ops::Deref,
     rc::{Rc, Weak},
 };
 
-use ash::{prelude::VkResult, vk};
+use ash::{
+    extensions::khr::{
+        DynamicRendering, GetPhysicalDeviceProperties2, PresentWait, Synchronization2,
+    },
+    prelude::VkResult,
+    vk,
+};
+
+use super::{
+    memory_helper, memory_usage::MemoryTracker, queue::queue_create_priorities, DeviceCapabilities,
+    Instance, MemoryTypeUsage, NamedQueue, QueueInfo, QueueRequest, QueueState, StagingPool,
+    VulkanApiVersion,
+};
+use crate::error::{RenderError, RenderResult};
+
+/// `VK_KHR_sampler_ycbcr_conversion` and the extensions it depends on
+const YCBCR_CONVERSION_EXTENSIONS: [&CStr; 4] = [
+    vk::KhrSamplerYcbcrConversionFn::name(),
+    vk::KhrMaintenance1Fn::name(),
+    vk::KhrBindMemory2Fn::name(),
+    vk::KhrGetMemoryRequirements2Fn::name(),
+];
+
+/// `VK_KHR_video_decode_h264` and the extensions it depends on
+const VIDEO_DECODE_H264_EXTENSIONS: [&CStr; 4] = [
+    vk::KhrVideoQueueFn::name(),
+    vk::KhrVideoDecodeQueueFn::name(),
+    vk::KhrVideoDecodeH264Fn::name(),
+    vk::KhrSynchronization2Fn::name(),
+];
+
+/// Core since Vulkan 1.1, the extension is enabled on every version for one code path
+const DESCRIPTOR_UPDATE_TEMPLATE_EXTENSIONS: [&CStr; 1] =
+    [vk::KhrDescriptorUpdateTemplateFn::name()];
+
+/// `VK_KHR_present_wait` and the extension it depends on
+const PRESENT_WAIT_EXTENSIONS: [&CStr; 2] =
+    [vk::KhrPresentIdFn::name(), vk::KhrPresentWaitFn::name()];
+
+/// `VK_KHR_dynamic_rendering` and the extensions it depends on, core since Vulkan 1.3
+const DYNAMIC_RENDERING_EXTENSIONS: [&CStr; 5] = [
+    vk::KhrDynamicRenderingFn::name(),
+    vk::KhrDepthStencilResolveFn::name(),
+    vk::KhrCreateRenderpass2Fn::name(),
+    vk::KhrMultiviewFn::name(),
+    vk::KhrMaintenance2Fn::name(),
+];
+
+/// Lets pipelines leave the vertex input state to the command buffer
+const VERTEX_INPUT_DYNAMIC_STATE_EXTENSIONS: [&CStr; 1] =
+    [vk::ExtVertexInputDynamicStateFn::name()];
 
-use super::{Instance, QueueInfo, QueueState};
+/// Lets pipelines clip depth independently of depth clamping
+const DEPTH_CLIP_ENABLE_EXTENSIONS: [&CStr; 1] = [vk::ExtDepthClipEnableFn::name()];
+
+/// Lets shaders call `debugPrintfEXT`, whose output the validation layer collects
+const SHADER_NON_SEMANTIC_INFO_EXTENSIONS: [&CStr; 1] = [vk::KhrShaderNonSemanticInfoFn::name()];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DynamicRenderingSupport {
+    Core,
+    Extension,
+}
+
+/// Commands of dynamic rendering, either the core Vulkan 1.3 ones or the `KHR` ones
+enum DynamicRenderingFns {
+    Core,
+    Extension(DynamicRendering),
+}
 
 pub struct Device {
     inner: ash::Device,
     instance: Rc<Instance>,
     physical_device: Weak<vk::PhysicalDevice>,
     queue_state: QueueState,
+    named_queues: Vec<NamedQueue>,
     physical_device_name: String,
+    ycbcr_conversion_fn: Option<vk::KhrSamplerYcbcrConversionFn>,
+    descriptor_update_template_fn: Option<vk::KhrDescriptorUpdateTemplateFn>,
+    vertex_input_dynamic_state_fn: Option<vk::ExtVertexInputDynamicStateFn>,
+    video_decode_fns: Option<VideoDecodeFns>,
+    present_wait: Option<PresentWait>,
+    dynamic_rendering_fns: Option<DynamicRenderingFns>,
+    shader_printf_supported: bool,
+    depth_clip_enable_supported: bool,
+    enabled_features: vk::PhysicalDeviceFeatures,
+    enabled_extensions: Vec<CString>,
+    upload_heap_size: Option<vk::DeviceSize>,
+    non_coherent_atom_size: vk::DeviceSize,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    capabilities: DeviceCapabilities,
+    memory_tracker: RefCell<MemoryTracker>,
+    staging_pool: RefCell<StagingPool>,
+}
+
+/// Function tables loaded when H.264 video decoding is enabled
+pub struct VideoDecodeFns {
+    pub video_queue: vk::KhrVideoQueueFn,
+    pub video_decode_queue: vk::KhrVideoDecodeQueueFn,
+    pub synchronization2: Synchronization2,
+}
+
+/// Names of the members of `VkPhysicalDeviceFeatures`, in declaration order
+const FEATURE_NAMES: [&str; 55] = [
+    "robustBufferAccess",
+    "fullDrawIndexUint32",
+    "imageCubeArray",
+    "independentBlend",
+    "geometryShader",
+    "tessellationShader",
+    "sampleRateShading",
+    "dualSrcBlend",
+    "logicOp",
+    "multiDrawIndirect",
+    "drawIndirectFirstInstance",
+    "depthClamp",
+    "depthBiasClamp",
+    "fillModeNonSolid",
+    "depthBounds",
+    "wideLines",
+    "largePoints",
+    "alphaToOne",
+    "multiViewport",
+    "samplerAnisotropy",
+    "textureCompressionETC2",
+    "textureCompressionASTC_LDR",
+    "textureCompressionBC",
+    "occlusionQueryPrecise",
+    "pipelineStatisticsQuery",
+    "vertexPipelineStoresAndAtomics",
+    "fragmentStoresAndAtomics",
+    "shaderTessellationAndGeometryPointSize",
+    "shaderImageGatherExtended",
+    "shaderStorageImageExtendedFormats",
+    "shaderStorageImageMultisample",
+    "shaderStorageImageReadWithoutFormat",
+    "shaderStorageImageWriteWithoutFormat",
+    "shaderUniformBufferArrayDynamicIndexing",
+    "shaderSampledImageArrayDynamicIndexing",
+    "shaderStorageBufferArrayDynamicIndexing",
+    "shaderStorageImageArrayDynamicIndexing",
+    "shaderClipDistance",
+    "shaderCullDistance",
+    "shaderFloat64",
+    "shaderInt64",
+    "shaderInt16",
+    "shaderResourceResidency",
+    "shaderResourceMinLod",
+    "sparseBinding",
+    "sparseResidencyBuffer",
+    "sparseResidencyImage2D",
+    "sparseResidencyImage3D",
+    "sparseResidency2Samples",
+    "sparseResidency4Samples",
+    "sparseResidency8Samples",
+    "sparseResidency16Samples",
+    "sparseResidencyAliased",
+    "variableMultisampleRate",
+    "inheritedQueries",
+];
+
+const _: () = assert!(
+    FEATURE_NAMES.len() * std::mem::size_of::<vk::Bool32>()
+        == std::mem::size_of::<vk::PhysicalDeviceFeatures>()
+);
+
+/// Features and extensions to create a [`Device`] with, on top of the ones its
+/// optional wrappers such as dynamic rendering or present wait enable by themselves.
+///
+/// Required ones fail [`DeviceBuilder::build`] when the physical device lacks them,
+/// optional ones are dropped, see [`Device::enabled_features`] and
+/// [`Device::extension_enabled`] for what was actually enabled.
+pub struct DeviceBuilder<'a> {
+    instance: Rc<Instance>,
+    queue_info: QueueInfo,
+    queue_requests: Vec<QueueRequest>,
+    required_features: vk::PhysicalDeviceFeatures,
+    optional_features: vk::PhysicalDeviceFeatures,
+    required_extensions: Vec<&'a CStr>,
+    optional_extensions: Vec<&'a CStr>,
+    feature_chain: Vec<*mut vk::BaseOutStructure>,
+}
+
+impl<'a> DeviceBuilder<'a> {
+    pub fn new(instance: Rc<Instance>, queue_info: QueueInfo) -> Self {
+        Self {
+            instance,
+            queue_info,
+            queue_requests: vec![],
+            required_features: vk::PhysicalDeviceFeatures::default(),
+            optional_features: vk::PhysicalDeviceFeatures::default(),
+            required_extensions: vec![],
+            optional_extensions: vec![],
+            feature_chain: vec![],
+        }
+    }
+
+    /// Creates an extra queue, in the graphic or compute family when they support the
+    /// requested flags. See [`Device::UPLOAD_QUEUE`] and [`Device::ASYNC_COMPUTE_QUEUE`] for
+    /// the names the device picks up by itself.
+    pub fn queue_request(mut self, request: QueueRequest) -> Self {
+        self.queue_requests.push(request);
+        self
+    }
+
+    pub fn queue_requests(mut self, requests: &[QueueRequest]) -> Self {
+        self.queue_requests.extend_from_slice(requests);
+        self
+    }
+
+    pub fn required_features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
+        self.required_features = features;
+        self
+    }
+
+    pub fn optional_features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
+        self.optional_features = features;
+        self
+    }
+
+    pub fn required_extensions(mut self, names: &[&'a CStr]) -> Self {
+        self.required_extensions.extend_from_slice(names);
+        self
+    }
+
+    pub fn optional_extensions(mut self, names: &[&'a CStr]) -> Self {
+        self.optional_extensions.extend_from_slice(names);
+        self
+    }
+
+    /// Chains a feature struct such as `VkPhysicalDeviceDescriptorIndexingFeatures` into
+    /// the device create info. It is passed as given, so it must only enable what the
+    /// device supports, and must not be one of the structs the device already chains for
+    /// YCbCr conversion, synchronization2, present id/wait, dynamic rendering, vertex input
+    /// dynamic state or depth clip enable.
+    pub fn push_next<T: vk::ExtendsDeviceCreateInfo>(mut self, next: &'a mut T) -> Self {
+        self.feature_chain
+            .push(next as *mut T as *mut vk::BaseOutStructure);
+        self
+    }
+
+    pub fn build(self) -> RenderResult<Device> {
+        Device::from_builder(self)
+    }
 }
 
 impl Device {
-    pub fn new(instance: Rc<Instance>, queue_info: QueueInfo) -> VkResult<Self> {
+    /// Name of the [`QueueRequest`] picked up by [`Self::upload_queue`]
+    pub const UPLOAD_QUEUE: &'static str = "upload";
+    /// Name of the [`QueueRequest`] picked up by [`Self::compute_queue`]
+    pub const ASYNC_COMPUTE_QUEUE: &'static str = "async compute";
+
+    pub fn builder<'a>(instance: Rc<Instance>, queue_info: QueueInfo) -> DeviceBuilder<'a> {
+        DeviceBuilder::new(instance, queue_info)
+    }
+
+    /// A device with every feature the physical device reports enabled
+    pub fn new(instance: Rc<Instance>, queue_info: QueueInfo) -> RenderResult<Self> {
+        Self::with_queue_requests(instance, queue_info, &[])
+    }
+
+    /// Like [`Self::new`], also creating the given extra queues
+    pub fn with_queue_requests(
+        instance: Rc<Instance>,
+        queue_info: QueueInfo,
+        queue_requests: &[QueueRequest],
+    ) -> RenderResult<Self> {
+        Self::builder(instance, queue_info)
+            .optional_features(all_features())
+            .queue_requests(queue_requests)
+            .build()
+    }
+
+    fn from_builder(builder: DeviceBuilder) -> RenderResult<Self> {
+        let DeviceBuilder {
+            instance,
+            mut queue_info,
+            queue_requests,
+            required_features,
+            optional_features,
+            required_extensions,
+            optional_extensions,
+            feature_chain,
+        } = builder;
         let physical_device = instance.pick_physical_device();
-        let inner = {
-            let queue_infos = queue_info.merge_queue_family_index_and_priority();
-            let indexs = queue_infos.iter().map(|x| x.0).collect::<Vec<_>>();
-            let priorities = queue_infos.iter().map(|x| x.1).collect::<Vec<_>>();
-            let queue_create_infos = indexs
+
+        let supported_features =
+            unsafe { instance.get_physical_device_features(*physical_device.upgrade().unwrap()) };
+        let missing_features = FEATURE_NAMES
+            .iter()
+            .zip(feature_bits(&required_features))
+            .zip(feature_bits(&supported_features))
+            .filter(|((_, &required), &supported)| required == vk::TRUE && supported != vk::TRUE)
+            .map(|((&name, _), _)| name)
+            .collect::<Vec<_>>();
+        if !missing_features.is_empty() {
+            return Err(RenderError::PhysicalDeviceNotSupported(format!(
+                "Missing required device features: {}",
+                missing_features.join(", ")
+            )));
+        }
+        let mut enabled_features = vk::PhysicalDeviceFeatures::default();
+        feature_bits_mut(&mut enabled_features)
+            .iter_mut()
+            .zip(feature_bits(&required_features))
+            .zip(feature_bits(&optional_features))
+            .zip(feature_bits(&supported_features))
+            .for_each(|(((enabled, &required), &optional), &supported)| {
+                *enabled = (required == vk::TRUE || optional & supported == vk::TRUE).into();
+            });
+
+        let available_extensions = unsafe {
+            instance.enumerate_device_extension_properties(*physical_device.upgrade().unwrap())?
+        };
+        let extension_available = |name: &CStr| {
+            available_extensions
+                .iter()
+                .any(|property| unsafe { CStr::from_ptr(property.extension_name.as_ptr()) } == name)
+        };
+        let missing_extensions = required_extensions
+            .iter()
+            .filter(|&&name| !extension_available(name))
+            .map(|name| name.to_string_lossy())
+            .collect::<Vec<_>>();
+        if !missing_extensions.is_empty() {
+            return Err(RenderError::PhysicalDeviceNotSupported(format!(
+                "Missing required device extensions: {}",
+                missing_extensions.join(", ")
+            )));
+        }
+        let requested_extensions = required_extensions.into_iter().chain(
+            optional_extensions
                 .into_iter()
-                .enumerate()
-                .map(|(i, index)| {
+                .filter(|&name| extension_available(name)),
+        );
+
+        let ycbcr_conversion_supported =
+            supports_ycbcr_conversion(&instance, *physical_device.upgrade().unwrap())?;
+        // Decoded pictures are multi-planar, so they are only presentable with a YCbCr conversion
+        let video_decode_supported = ycbcr_conversion_supported
+            && queue_info.video_decode_family_index_priority.is_some()
+            && supports_video_decode_h264(&instance, *physical_device.upgrade().unwrap())?;
+        let present_wait_supported =
+            supports_present_wait(&instance, *physical_device.upgrade().unwrap())?;
+        let dynamic_rendering_support =
+            dynamic_rendering_support(&instance, *physical_device.upgrade().unwrap())?;
+        let vertex_input_dynamic_state_supported =
+            supports_vertex_input_dynamic_state(&instance, *physical_device.upgrade().unwrap())?;
+        let depth_clip_enable_supported =
+            supports_depth_clip_enable(&instance, *physical_device.upgrade().unwrap())?;
+        let descriptor_update_template_supported = extensions_available(
+            &instance,
+            *physical_device.upgrade().unwrap(),
+            &DESCRIPTOR_UPDATE_TEMPLATE_EXTENSIONS,
+        )?;
+        let shader_printf_supported = instance.shader_printf_enabled()
+            && extensions_available(
+                &instance,
+                *physical_device.upgrade().unwrap(),
+                &SHADER_NON_SEMANTIC_INFO_EXTENSIONS,
+            )?;
+        if !video_decode_supported {
+            queue_info.video_decode_family_index_priority = None;
+        }
+        let queue_priorities = queue_create_priorities(&queue_info, &queue_requests, &unsafe {
+            instance
+                .get_physical_device_queue_family_properties(*physical_device.upgrade().unwrap())
+        })?;
+        let enabled_extensions: Vec<CString>;
+        let inner = {
+            let queue_create_infos = queue_priorities
+                .families
+                .iter()
+                .map(|(index, priorities)| {
                     vk::DeviceQueueCreateInfo::builder()
-                        .queue_family_index(index)
-                        .queue_priorities(&priorities[i..i + 1])
+                        .queue_family_index(*index)
+                        .queue_priorities(priorities)
                         .build()
                 })
                 .collect::<Vec<_>>();
 
-            let device_extension_names = [
+            let mut device_extension_names = vec![
                 #[cfg(any(target_os = "macos", target_os = "ios"))]
                 vk::KhrPortabilitySubsetFn::name().as_ptr(),
-                vk::KhrSwapchainFn::name().as_ptr(),
             ];
+            // Headless devices never present, and may lack the extension altogether
+            if instance.support_window() {
+                device_extension_names.push(vk::KhrSwapchainFn::name().as_ptr());
+            }
+            if ycbcr_conversion_supported {
+                YCBCR_CONVERSION_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if descriptor_update_template_supported {
+                DESCRIPTOR_UPDATE_TEMPLATE_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if shader_printf_supported {
+                SHADER_NON_SEMANTIC_INFO_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if video_decode_supported {
+                VIDEO_DECODE_H264_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if present_wait_supported {
+                PRESENT_WAIT_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if dynamic_rendering_support == Some(DynamicRenderingSupport::Extension) {
+                DYNAMIC_RENDERING_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if vertex_input_dynamic_state_supported {
+                VERTEX_INPUT_DYNAMIC_STATE_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            if depth_clip_enable_supported {
+                DEPTH_CLIP_ENABLE_EXTENSIONS
+                    .iter()
+                    .for_each(|name| device_extension_names.push(name.as_ptr()));
+            }
+            for name in requested_extensions {
+                if !device_extension_names
+                    .iter()
+                    .any(|&enabled| unsafe { CStr::from_ptr(enabled) } == name)
+                {
+                    device_extension_names.push(name.as_ptr());
+                }
+            }
+            enabled_extensions = device_extension_names
+                .iter()
+                .map(|&name| unsafe { CStr::from_ptr(name) }.to_owned())
+                .collect();
 
-            let create_info = vk::DeviceCreateInfo::builder()
-                .queue_create_infos(&queue_create_infos)
-                .enabled_features(unsafe {
-                    &instance.get_physical_device_features(*physical_device.upgrade().unwrap())
-                })
-                .enabled_extension_names(&device_extension_names)
+            let mut ycbcr_conversion_features =
+                vk::PhysicalDeviceSamplerYcbcrConversionFeatures::builder()
+                    .sampler_ycbcr_conversion(true)
+                    .build();
+            let mut synchronization2_features =
+                vk::PhysicalDeviceSynchronization2Features::builder()
+                    .synchronization2(true)
+                    .build();
+            let mut present_id_features = vk::PhysicalDevicePresentIdFeaturesKHR::builder()
+                .present_id(true)
+                .build();
+            let mut present_wait_features = vk::PhysicalDevicePresentWaitFeaturesKHR::builder()
+                .present_wait(true)
                 .build();
+            let mut dynamic_rendering_features =
+                vk::PhysicalDeviceDynamicRenderingFeatures::builder()
+                    .dynamic_rendering(true)
+                    .build();
+            let mut vertex_input_dynamic_state_features =
+                vk::PhysicalDeviceVertexInputDynamicStateFeaturesEXT::builder()
+                    .vertex_input_dynamic_state(true)
+                    .build();
+            let mut depth_clip_enable_features =
+                vk::PhysicalDeviceDepthClipEnableFeaturesEXT::builder()
+                    .depth_clip_enable(true)
+                    .build();
+            let mut create_info = vk::DeviceCreateInfo::builder()
+                .queue_create_infos(&queue_create_infos)
+                .enabled_features(&enabled_features)
+                .enabled_extension_names(&device_extension_names);
+            if ycbcr_conversion_supported {
+                create_info = create_info.push_next(&mut ycbcr_conversion_features);
+            }
+            if video_decode_supported {
+                create_info = create_info.push_next(&mut synchronization2_features);
+            }
+            if present_wait_supported {
+                create_info = create_info
+                    .push_next(&mut present_id_features)
+                    .push_next(&mut present_wait_features);
+            }
+            if dynamic_rendering_support.is_some() {
+                create_info = create_info.push_next(&mut dynamic_rendering_features);
+            }
+            if vertex_input_dynamic_state_supported {
+                create_info = create_info.push_next(&mut vertex_input_dynamic_state_features);
+            }
+            if depth_clip_enable_supported {
+                create_info = create_info.push_next(&mut depth_clip_enable_features);
+            }
+            let mut create_info = create_info.build();
+            // Spliced in front like `push_next` does, each struct keeps its own chain
+            for next in feature_chain {
+                unsafe {
+                    let mut last = next;
+                    while !(*last).p_next.is_null() {
+                        last = (*last).p_next;
+                    }
+                    (*last).p_next = create_info.p_next as *mut vk::BaseOutStructure;
+                    create_info.p_next = next as *const std::ffi::c_void;
+                }
+            }
 
             unsafe {
                 instance.create_device(*physical_device.upgrade().unwrap(), &create_info, None)?
             }
         };
+        let named_queues = queue_requests
+            .iter()
+            .zip(queue_priorities.placements)
+            .map(|(request, (family_index, queue_index))| NamedQueue {
+                name: request.name,
+                queue: unsafe { inner.get_device_queue(family_index, queue_index) },
+                family_index,
+                queue_index,
+                priority: request.priority,
+            })
+            .collect::<Vec<_>>();
         let queue_state = unsafe {
             QueueState {
                 info: queue_info,
@@ -59,26 +532,114 @@ impl Device {
                     .get_device_queue(queue_info.graphic_family_index_priority.0, 0),
                 present_queue: inner
                     .get_device_queue(queue_info.present_family_index_priority.0, 0),
+                compute_queue: inner
+                    .get_device_queue(queue_info.compute_family_index_priority.0, 0),
+                video_decode_queue: queue_info
+                    .video_decode_family_index_priority
+                    .map(|(index, _)| inner.get_device_queue(index, 0)),
             }
         };
+        let physical_device_properties =
+            unsafe { instance.get_physical_device_properties(*physical_device.upgrade().unwrap()) };
         let physical_device_name = format!("{:?}", unsafe {
-            CStr::from_ptr(
-                instance
-                    .get_physical_device_properties(*physical_device.upgrade().unwrap())
-                    .device_name
-                    .as_ptr(),
-            )
+            CStr::from_ptr(physical_device_properties.device_name.as_ptr())
+        });
+
+        let ycbcr_conversion_fn = ycbcr_conversion_supported.then(|| {
+            vk::KhrSamplerYcbcrConversionFn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(inner.handle(), name.as_ptr()))
+            })
+        });
+
+        let vertex_input_dynamic_state_fn = vertex_input_dynamic_state_supported.then(|| {
+            vk::ExtVertexInputDynamicStateFn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(inner.handle(), name.as_ptr()))
+            })
+        });
+
+        let descriptor_update_template_fn = descriptor_update_template_supported.then(|| {
+            vk::KhrDescriptorUpdateTemplateFn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(inner.handle(), name.as_ptr()))
+            })
         });
 
+        // Physical device level commands such as `vkGetPhysicalDeviceVideoCapabilitiesKHR`
+        // are only reachable through the instance
+        let video_decode_fns = video_decode_supported.then(|| unsafe {
+            let load_instance_level = |name: &CStr| {
+                std::mem::transmute(
+                    instance
+                        .entry()
+                        .get_instance_proc_addr(instance.handle(), name.as_ptr()),
+                )
+            };
+            VideoDecodeFns {
+                video_queue: vk::KhrVideoQueueFn::load(load_instance_level),
+                video_decode_queue: vk::KhrVideoDecodeQueueFn::load(|name| {
+                    std::mem::transmute(
+                        instance.get_device_proc_addr(inner.handle(), name.as_ptr()),
+                    )
+                }),
+                synchronization2: Synchronization2::new(&instance, &inner),
+            }
+        });
+
+        let present_wait = present_wait_supported.then(|| PresentWait::new(&instance, &inner));
+
+        let dynamic_rendering_fns = dynamic_rendering_support.map(|support| match support {
+            DynamicRenderingSupport::Core => DynamicRenderingFns::Core,
+            DynamicRenderingSupport::Extension => {
+                DynamicRenderingFns::Extension(DynamicRendering::new(&instance, &inner))
+            }
+        });
+
+        let memory_properties = unsafe {
+            instance.get_physical_device_memory_properties(*physical_device.upgrade().unwrap())
+        };
+        let upload_heap_size = memory_helper::upload_heap_size(&memory_properties);
+        let capabilities = DeviceCapabilities::query(
+            &instance,
+            *physical_device.upgrade().unwrap(),
+            &enabled_features,
+        );
+
         Ok(Self {
             inner,
             instance,
             physical_device,
             queue_state,
+            named_queues,
             physical_device_name,
+            ycbcr_conversion_fn,
+            descriptor_update_template_fn,
+            vertex_input_dynamic_state_fn,
+            video_decode_fns,
+            present_wait,
+            dynamic_rendering_fns,
+            shader_printf_supported,
+            depth_clip_enable_supported,
+            enabled_features,
+            enabled_extensions,
+            upload_heap_size,
+            non_coherent_atom_size: physical_device_properties.limits.non_coherent_atom_size,
+            memory_properties,
+            capabilities,
+            memory_tracker: RefCell::default(),
+            staging_pool: RefCell::new(StagingPool::new(StagingPool::DEFAULT_CHUNK_SIZE)),
         })
     }
 
+    /// Core features the device was created with
+    pub fn enabled_features(&self) -> &vk::PhysicalDeviceFeatures {
+        &self.enabled_features
+    }
+
+    pub fn extension_enabled(&self, name: &CStr) -> bool {
+        self.enabled_extensions
+            .iter()
+            .any(|enabled| enabled.as_c_str() == name)
+    }
+
     pub fn instance(&self) -> &Rc<Instance> {
         &self.instance
     }
@@ -91,6 +652,84 @@ impl Device {
         &self.physical_device
     }
 
+    /// Whether shaders may call `debugPrintfEXT`, which needs debugPrintf enabled on the
+    /// instance and `VK_KHR_shader_non_semantic_info` on the device
+    pub fn supports_shader_printf(&self) -> bool {
+        self.shader_printf_supported
+    }
+
+    /// Size of the largest `DEVICE_LOCAL` heap the host can write to coherently
+    pub fn upload_heap_size(&self) -> Option<vk::DeviceSize> {
+        self.upload_heap_size
+    }
+
+    /// With resizable BAR, or on integrated GPUs, the host writes to device local memory
+    /// directly, larger than the classic 256 MiB BAR window which is kept for the driver
+    pub fn supports_direct_upload(&self) -> bool {
+        self.upload_heap_size
+            .is_some_and(|size| size > memory_helper::BAR_WINDOW_SIZE)
+    }
+
+    /// Granularity of flushes and invalidations of mapped memory that is not `HOST_COHERENT`
+    pub fn non_coherent_atom_size(&self) -> vk::DeviceSize {
+        self.non_coherent_atom_size
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    /// Limits and format support to clamp settings to
+    pub fn capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
+    /// Highest sample count supported by both color and depth attachments
+    pub fn max_usable_sample_count(&self) -> vk::SampleCountFlags {
+        self.capabilities.max_usable_sample_count()
+    }
+
+    /// Allocates memory counted in [`Self::memory_type_usage`] until it is freed with
+    /// [`Self::free_tracked_memory`]. `hot` memory backs what the GPU reads every frame.
+    pub fn allocate_tracked_memory(
+        &self,
+        allocate_info: &vk::MemoryAllocateInfo,
+        hot: bool,
+    ) -> VkResult<vk::DeviceMemory> {
+        let memory = unsafe { self.allocate_memory(allocate_info, None)? };
+        self.memory_tracker
+            .borrow_mut()
+            .allocated(memory, allocate_info, hot);
+        Ok(memory)
+    }
+
+    pub fn free_tracked_memory(&self, memory: vk::DeviceMemory) {
+        self.memory_tracker.borrow_mut().freed(memory);
+        unsafe { self.free_memory(memory, None) };
+    }
+
+    /// Live tracked allocations, indexed like the memory types of [`Self::memory_properties`]
+    pub fn memory_type_usage(&self) -> Vec<MemoryTypeUsage> {
+        self.memory_tracker.borrow().usage()[..self.memory_properties.memory_type_count as usize]
+            .to_vec()
+    }
+
+    /// Staging memory shared by the uploads to device local memory
+    pub fn staging_pool(&self) -> RefMut<'_, StagingPool> {
+        self.staging_pool.borrow_mut()
+    }
+
+    /// Memory properties of buffers the host writes every frame, such as uniform buffers
+    pub fn host_write_memory_properties(&self) -> vk::MemoryPropertyFlags {
+        let properties =
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        if self.supports_direct_upload() {
+            properties | vk::MemoryPropertyFlags::DEVICE_LOCAL
+        } else {
+            properties
+        }
+    }
+
     pub fn queue_family_indices(&self) -> Vec<u32> {
         self.queue_state
             .info
@@ -119,6 +758,491 @@ impl Device {
     pub fn present_queue_family_index(&self) -> u32 {
         self.queue_state.info.present_family_index_priority.0
     }
+
+    /// The [`Self::ASYNC_COMPUTE_QUEUE`] when it was requested in the compute family
+    pub fn compute_queue(&self) -> vk::Queue {
+        self.named_queue(Self::ASYNC_COMPUTE_QUEUE)
+            .filter(|named| named.family_index == self.compute_queue_family_index())
+            .map_or(self.queue_state.compute_queue, |named| named.queue)
+    }
+
+    /// Queue the device local uploads are submitted to: the [`Self::UPLOAD_QUEUE`] when it
+    /// was requested in the graphic family, so that no ownership transfer is needed,
+    /// the graphic queue otherwise
+    pub fn upload_queue(&self) -> vk::Queue {
+        self.named_queue(Self::UPLOAD_QUEUE)
+            .filter(|named| named.family_index == self.graphic_queue_family_index())
+            .map_or(self.queue_state.graphic_queue, |named| named.queue)
+    }
+
+    pub fn named_queue(&self, name: &str) -> Option<&NamedQueue> {
+        self.named_queues.iter().find(|named| named.name == name)
+    }
+
+    pub fn named_queues(&self) -> &[NamedQueue] {
+        &self.named_queues
+    }
+
+    pub fn compute_queue_family_index(&self) -> u32 {
+        self.queue_state.info.compute_family_index_priority.0
+    }
+
+    pub fn video_decode_queue(&self) -> Option<vk::Queue> {
+        self.queue_state.video_decode_queue
+    }
+
+    pub fn video_decode_queue_family_index(&self) -> Option<u32> {
+        self.queue_state
+            .info
+            .video_decode_family_index_priority
+            .map(|x| x.0)
+    }
+
+    /// Whether `VK_KHR_video_decode_h264` was enabled and a video decode queue created
+    pub fn supports_video_decode(&self) -> bool {
+        self.video_decode_fns.is_some()
+    }
+
+    pub fn video_decode_fns(&self) -> RenderResult<&VideoDecodeFns> {
+        self.video_decode_fns.as_ref().ok_or_else(|| {
+            RenderError::PhysicalDeviceNotSupported(
+                "VK_KHR_video_decode_h264 is not supported".to_string(),
+            )
+        })
+    }
+
+    /// Set when `VK_KHR_present_wait` was enabled, swapchain presents then carry present ids
+    pub fn present_wait(&self) -> Option<&PresentWait> {
+        self.present_wait.as_ref()
+    }
+
+    /// Whether render pass instances can be begun without render pass and framebuffer objects,
+    /// through Vulkan 1.3 or `VK_KHR_dynamic_rendering`
+    pub fn supports_dynamic_rendering(&self) -> bool {
+        self.dynamic_rendering_fns.is_some()
+    }
+
+    pub fn cmd_begin_dynamic_rendering(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        rendering_info: &vk::RenderingInfo,
+    ) -> RenderResult<()> {
+        match self.dynamic_rendering_fns.as_ref() {
+            Some(DynamicRenderingFns::Core) => unsafe {
+                self.inner
+                    .cmd_begin_rendering(command_buffer, rendering_info)
+            },
+            Some(DynamicRenderingFns::Extension(dynamic_rendering)) => unsafe {
+                dynamic_rendering.cmd_begin_rendering(command_buffer, rendering_info)
+            },
+            None => {
+                return Err(RenderError::PhysicalDeviceNotSupported(
+                    "VK_KHR_dynamic_rendering is not supported".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn cmd_end_dynamic_rendering(&self, command_buffer: vk::CommandBuffer) {
+        match self.dynamic_rendering_fns.as_ref() {
+            Some(DynamicRenderingFns::Core) => unsafe {
+                self.inner.cmd_end_rendering(command_buffer)
+            },
+            Some(DynamicRenderingFns::Extension(dynamic_rendering)) => unsafe {
+                dynamic_rendering.cmd_end_rendering(command_buffer)
+            },
+            None => (),
+        }
+    }
+
+    /// Opens a label region in `command_buffer`, a no-op without `VK_EXT_debug_utils`
+    pub fn cmd_begin_debug_label(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        name: &str,
+        color: [f32; 4],
+    ) {
+        if let Some(debug_utils) = self.instance.debug_utils() {
+            let name = CString::new(name).unwrap();
+            unsafe {
+                debug_utils.cmd_begin_debug_utils_label(
+                    command_buffer,
+                    &vk::DebugUtilsLabelEXT::builder()
+                        .label_name(&name)
+                        .color(color)
+                        .build(),
+                )
+            }
+        }
+    }
+
+    pub fn cmd_end_debug_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(debug_utils) = self.instance.debug_utils() {
+            unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) }
+        }
+    }
+
+    /// Opens a label region on `queue` spanning the following submissions and presents
+    pub fn queue_begin_debug_label(&self, queue: vk::Queue, name: &str, color: [f32; 4]) {
+        if let Some(debug_utils) = self.instance.debug_utils() {
+            let name = CString::new(name).unwrap();
+            unsafe {
+                debug_utils.queue_begin_debug_utils_label(
+                    queue,
+                    &vk::DebugUtilsLabelEXT::builder()
+                        .label_name(&name)
+                        .color(color)
+                        .build(),
+                )
+            }
+        }
+    }
+
+    pub fn queue_end_debug_label(&self, queue: vk::Queue) {
+        if let Some(debug_utils) = self.instance.debug_utils() {
+            unsafe { debug_utils.queue_end_debug_utils_label(queue) }
+        }
+    }
+
+    /// Whether `VK_KHR_sampler_ycbcr_conversion` was enabled, i.e. multi-planar formats can be sampled
+    pub fn supports_ycbcr_conversion(&self) -> bool {
+        self.ycbcr_conversion_fn.is_some()
+    }
+
+    pub fn create_sampler_ycbcr_conversion(
+        &self,
+        create_info: &vk::SamplerYcbcrConversionCreateInfo,
+    ) -> RenderResult<vk::SamplerYcbcrConversion> {
+        let ycbcr_conversion_fn = self.ycbcr_conversion_fn.as_ref().ok_or_else(|| {
+            RenderError::PhysicalDeviceNotSupported(
+                "VK_KHR_sampler_ycbcr_conversion is not supported".to_string(),
+            )
+        })?;
+        let mut conversion = vk::SamplerYcbcrConversion::null();
+        unsafe {
+            (ycbcr_conversion_fn.create_sampler_ycbcr_conversion_khr)(
+                self.inner.handle(),
+                create_info,
+                std::ptr::null(),
+                &mut conversion,
+            )
+            .result()?;
+        }
+        Ok(conversion)
+    }
+
+    pub fn destroy_sampler_ycbcr_conversion(&self, conversion: vk::SamplerYcbcrConversion) {
+        if let Some(ycbcr_conversion_fn) = self.ycbcr_conversion_fn.as_ref() {
+            unsafe {
+                (ycbcr_conversion_fn.destroy_sampler_ycbcr_conversion_khr)(
+                    self.inner.handle(),
+                    conversion,
+                    std::ptr::null(),
+                )
+            };
+        }
+    }
+
+    /// Whether `VK_EXT_vertex_input_dynamic_state` was enabled, i.e. one pipeline built with
+    /// `VERTEX_INPUT_EXT` dynamic state can draw any vertex layout
+    pub fn supports_vertex_input_dynamic_state(&self) -> bool {
+        self.vertex_input_dynamic_state_fn.is_some()
+    }
+
+    pub fn cmd_set_vertex_input(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bindings: &[vk::VertexInputBindingDescription2EXT],
+        attributes: &[vk::VertexInputAttributeDescription2EXT],
+    ) -> RenderResult<()> {
+        let vertex_input_dynamic_state_fn =
+            self.vertex_input_dynamic_state_fn.as_ref().ok_or_else(|| {
+                RenderError::PhysicalDeviceNotSupported(
+                    "VK_EXT_vertex_input_dynamic_state is not supported".to_string(),
+                )
+            })?;
+        unsafe {
+            (vertex_input_dynamic_state_fn.cmd_set_vertex_input_ext)(
+                command_buffer,
+                bindings.len() as u32,
+                bindings.as_ptr(),
+                attributes.len() as u32,
+                attributes.as_ptr(),
+            )
+        };
+        Ok(())
+    }
+
+    /// Whether the `depthClamp` feature was enabled, letting pipelines clamp fragment depths
+    /// to the viewport range instead of clipping primitives at the near and far planes
+    pub fn supports_depth_clamp(&self) -> bool {
+        self.enabled_features.depth_clamp == vk::TRUE
+    }
+
+    /// Whether `VK_EXT_depth_clip_enable` was enabled, letting pipelines choose depth
+    /// clipping independently of depth clamping
+    pub fn supports_depth_clip_enable(&self) -> bool {
+        self.depth_clip_enable_supported
+    }
+
+    /// Whether `VK_KHR_descriptor_update_template` was enabled
+    pub fn supports_descriptor_update_templates(&self) -> bool {
+        self.descriptor_update_template_fn.is_some()
+    }
+
+    pub fn create_descriptor_update_template(
+        &self,
+        create_info: &vk::DescriptorUpdateTemplateCreateInfo,
+    ) -> RenderResult<vk::DescriptorUpdateTemplate> {
+        let descriptor_update_template_fn =
+            self.descriptor_update_template_fn.as_ref().ok_or_else(|| {
+                RenderError::PhysicalDeviceNotSupported(
+                    "VK_KHR_descriptor_update_template is not supported".to_string(),
+                )
+            })?;
+        let mut template = vk::DescriptorUpdateTemplate::null();
+        unsafe {
+            (descriptor_update_template_fn.create_descriptor_update_template_khr)(
+                self.inner.handle(),
+                create_info,
+                std::ptr::null(),
+                &mut template,
+            )
+            .result()?;
+        }
+        Ok(template)
+    }
+
+    pub fn destroy_descriptor_update_template(&self, template: vk::DescriptorUpdateTemplate) {
+        if let Some(descriptor_update_template_fn) = self.descriptor_update_template_fn.as_ref() {
+            unsafe {
+                (descriptor_update_template_fn.destroy_descriptor_update_template_khr)(
+                    self.inner.handle(),
+                    template,
+                    std::ptr::null(),
+                )
+            };
+        }
+    }
+
+    /// # Safety
+    /// `data` must be laid out as the entries of `template` describe
+    pub unsafe fn update_descriptor_set_with_template(
+        &self,
+        set: vk::DescriptorSet,
+        template: vk::DescriptorUpdateTemplate,
+        data: *const std::ffi::c_void,
+    ) {
+        if let Some(descriptor_update_template_fn) = self.descriptor_update_template_fn.as_ref() {
+            (descriptor_update_template_fn.update_descriptor_set_with_template_khr)(
+                self.inner.handle(),
+                set,
+                template,
+                data,
+            );
+        }
+    }
+}
+
+fn supports_ycbcr_conversion(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<bool> {
+    if !instance.physical_device_properties2_enabled() {
+        return Ok(false);
+    }
+
+    if !extensions_available(instance, physical_device, &YCBCR_CONVERSION_EXTENSIONS)? {
+        return Ok(false);
+    }
+
+    let mut ycbcr_conversion_features = vk::PhysicalDeviceSamplerYcbcrConversionFeatures::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut ycbcr_conversion_features)
+        .build();
+    unsafe {
+        GetPhysicalDeviceProperties2::new(instance.entry(), instance)
+            .get_physical_device_features2(physical_device, &mut features)
+    };
+    Ok(ycbcr_conversion_features.sampler_ycbcr_conversion == vk::TRUE)
+}
+
+fn supports_vertex_input_dynamic_state(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<bool> {
+    if !instance.physical_device_properties2_enabled()
+        || !extensions_available(
+            instance,
+            physical_device,
+            &VERTEX_INPUT_DYNAMIC_STATE_EXTENSIONS,
+        )?
+    {
+        return Ok(false);
+    }
+
+    let mut vertex_input_dynamic_state_features =
+        vk::PhysicalDeviceVertexInputDynamicStateFeaturesEXT::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut vertex_input_dynamic_state_features)
+        .build();
+    unsafe {
+        GetPhysicalDeviceProperties2::new(instance.entry(), instance)
+            .get_physical_device_features2(physical_device, &mut features)
+    };
+    Ok(vertex_input_dynamic_state_features.vertex_input_dynamic_state == vk::TRUE)
+}
+
+fn supports_depth_clip_enable(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<bool> {
+    if !instance.physical_device_properties2_enabled()
+        || !extensions_available(instance, physical_device, &DEPTH_CLIP_ENABLE_EXTENSIONS)?
+    {
+        return Ok(false);
+    }
+
+    let mut depth_clip_enable_features = vk::PhysicalDeviceDepthClipEnableFeaturesEXT::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut depth_clip_enable_features)
+        .build();
+    unsafe {
+        GetPhysicalDeviceProperties2::new(instance.entry(), instance)
+            .get_physical_device_features2(physical_device, &mut features)
+    };
+    Ok(depth_clip_enable_features.depth_clip_enable == vk::TRUE)
+}
+
+/// `VK_KHR_video_queue` requires Vulkan 1.1
+fn supports_video_decode_h264(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<bool> {
+    if matches!(instance.vulkan_api_version(), VulkanApiVersion::V1_0)
+        || !instance.physical_device_properties2_enabled()
+    {
+        return Ok(false);
+    }
+
+    if !extensions_available(instance, physical_device, &VIDEO_DECODE_H264_EXTENSIONS)? {
+        return Ok(false);
+    }
+
+    let mut synchronization2_features = vk::PhysicalDeviceSynchronization2Features::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut synchronization2_features)
+        .build();
+    unsafe {
+        GetPhysicalDeviceProperties2::new(instance.entry(), instance)
+            .get_physical_device_features2(physical_device, &mut features)
+    };
+    Ok(synchronization2_features.synchronization2 == vk::TRUE)
+}
+
+fn supports_present_wait(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<bool> {
+    if !instance.physical_device_properties2_enabled()
+        || !extensions_available(instance, physical_device, &PRESENT_WAIT_EXTENSIONS)?
+    {
+        return Ok(false);
+    }
+
+    let mut present_id_features = vk::PhysicalDevicePresentIdFeaturesKHR::default();
+    let mut present_wait_features = vk::PhysicalDevicePresentWaitFeaturesKHR::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut present_id_features)
+        .push_next(&mut present_wait_features)
+        .build();
+    unsafe {
+        GetPhysicalDeviceProperties2::new(instance.entry(), instance)
+            .get_physical_device_features2(physical_device, &mut features)
+    };
+    Ok(
+        present_id_features.present_id == vk::TRUE
+            && present_wait_features.present_wait == vk::TRUE,
+    )
+}
+
+/// The core commands are only used when both the instance and the device are Vulkan 1.3
+fn dynamic_rendering_support(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> VkResult<Option<DynamicRenderingSupport>> {
+    if !instance.physical_device_properties2_enabled() {
+        return Ok(None);
+    }
+
+    let device_api_version =
+        unsafe { instance.get_physical_device_properties(physical_device) }.api_version;
+    let support = if matches!(instance.vulkan_api_version(), VulkanApiVersion::V1_3)
+        && device_api_version >= vk::API_VERSION_1_3
+    {
+        DynamicRenderingSupport::Core
+    } else if extensions_available(instance, physical_device, &DYNAMIC_RENDERING_EXTENSIONS)? {
+        DynamicRenderingSupport::Extension
+    } else {
+        return Ok(None);
+    };
+
+    let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut dynamic_rendering_features)
+        .build();
+    unsafe {
+        GetPhysicalDeviceProperties2::new(instance.entry(), instance)
+            .get_physical_device_features2(physical_device, &mut features)
+    };
+    Ok((dynamic_rendering_features.dynamic_rendering == vk::TRUE).then_some(support))
+}
+
+/// Looks a `VkPhysicalDeviceFeatures` member up by name, `None` if there is no such member
+pub(super) fn feature_supported(features: &vk::PhysicalDeviceFeatures, name: &str) -> Option<bool> {
+    let index = FEATURE_NAMES.iter().position(|&feature| feature == name)?;
+    Some(feature_bits(features)[index] == vk::TRUE)
+}
+
+/// A feature struct with every member set
+fn all_features() -> vk::PhysicalDeviceFeatures {
+    let mut features = vk::PhysicalDeviceFeatures::default();
+    feature_bits_mut(&mut features).fill(vk::TRUE);
+    features
+}
+
+/// `VkPhysicalDeviceFeatures` holds nothing but `VkBool32` members
+fn feature_bits(features: &vk::PhysicalDeviceFeatures) -> &[vk::Bool32] {
+    unsafe {
+        std::slice::from_raw_parts(
+            features as *const vk::PhysicalDeviceFeatures as *const vk::Bool32,
+            FEATURE_NAMES.len(),
+        )
+    }
+}
+
+fn feature_bits_mut(features: &mut vk::PhysicalDeviceFeatures) -> &mut [vk::Bool32] {
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            features as *mut vk::PhysicalDeviceFeatures as *mut vk::Bool32,
+            FEATURE_NAMES.len(),
+        )
+    }
+}
+
+fn extensions_available(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    names: &[&CStr],
+) -> VkResult<bool> {
+    let available_extensions =
+        unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+    Ok(names.iter().all(|&name| {
+        available_extensions
+            .iter()
+            .any(|property| unsafe { CStr::from_ptr(property.extension_name.as_ptr()) } == name)
+    }))
 }
 
 impl Deref for Device {
@@ -131,6 +1255,7 @@ impl Deref for Device {
 impl Drop for Device {
     fn drop(&mut self) {
         unsafe {
+            self.staging_pool.borrow_mut().destroy(self);
             self.destroy_device(None);
         }
     }
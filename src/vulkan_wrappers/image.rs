@@ -1,11 +1,16 @@
+use std::ffi::c_void;
 use std::path::Path;
 use std::rc::Rc;
 
 use ash::{prelude::VkResult, vk};
+#[cfg(feature = "ktx")]
 use ktx::KtxInfo;
 
-use super::{Buffer, Device, OneTimeCommand};
-use crate::error::{RenderError, RenderResult};
+use super::{Buffer, Device, MemoryTypeUsage, OneTimeCommand};
+use crate::{
+    error::{RenderError, RenderResult},
+    jobs::JobSystem,
+};
 
 pub struct TextureBuilder {
     width: u32,
@@ -17,6 +22,8 @@ pub struct TextureBuilder {
     format: vk::Format,
     tiling: vk::ImageTiling,
     usage: vk::ImageUsageFlags,
+    flags: vk::ImageCreateFlags,
+    memory_properties: vk::MemoryPropertyFlags,
     device: Rc<Device>,
 }
 
@@ -38,6 +45,8 @@ impl TextureBuilder {
             format,
             tiling: vk::ImageTiling::OPTIMAL,
             usage,
+            flags: vk::ImageCreateFlags::empty(),
+            memory_properties: vk::MemoryPropertyFlags::DEVICE_LOCAL,
             device,
         }
     }
@@ -67,7 +76,45 @@ impl TextureBuilder {
         self
     }
 
+    pub fn image_create_flags(mut self, flags: vk::ImageCreateFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Every 6 consecutive array layers are viewed as the +X, -X, +Y, -Y, +Z, -Z faces of a cube
+    pub fn cube_compatible(mut self) -> Self {
+        self.flags |= vk::ImageCreateFlags::CUBE_COMPATIBLE;
+        self
+    }
+
+    /// Adds `STORAGE` usage, so compute shaders can write the texture through
+    /// [`Texture::storage_descriptor`] once it is in `GENERAL` layout
+    pub fn storage(mut self) -> Self {
+        self.usage |= vk::ImageUsageFlags::STORAGE;
+        self
+    }
+
+    pub fn memory_properties(mut self, memory_properties: vk::MemoryPropertyFlags) -> Self {
+        self.memory_properties = memory_properties;
+        self
+    }
+
+    /// `LINEAR` tiling in host visible and coherent memory, so texels can be read
+    /// and written through [`Texture::map_memory`] without a staging buffer. The image starts
+    /// `PREINITIALIZED`, so texels written before its first transition are kept
+    pub fn host_accessible(self) -> Self {
+        self.image_tiling(vk::ImageTiling::LINEAR)
+            .image_layout(vk::ImageLayout::PREINITIALIZED)
+            .memory_properties(
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )
+    }
+
     pub fn build(&self) -> RenderResult<Texture> {
+        if self.tiling == vk::ImageTiling::LINEAR {
+            // Linear images are only guaranteed for single level, single layer 2D images
+            self.check_linear_support()?;
+        }
         Texture::new(
             self.width,
             self.height,
@@ -78,9 +125,112 @@ impl TextureBuilder {
             self.format,
             self.tiling,
             self.usage,
+            self.flags,
+            self.memory_properties,
             self.device.clone(),
         )
     }
+
+    fn check_linear_support(&self) -> RenderResult<()> {
+        let properties = unsafe {
+            self.device
+                .instance()
+                .get_physical_device_image_format_properties(
+                    *self.device.physical_device().upgrade().unwrap(),
+                    self.format,
+                    if self.depth > 1 {
+                        vk::ImageType::TYPE_3D
+                    } else {
+                        vk::ImageType::TYPE_2D
+                    },
+                    vk::ImageTiling::LINEAR,
+                    self.usage,
+                    self.flags,
+                )
+                .map_err(|_| {
+                    RenderError::FormatNotSupported(format!(
+                        "{:?} with LINEAR tiling and usage {:?}",
+                        self.format, self.usage
+                    ))
+                })?
+        };
+        if self.mip_levels > properties.max_mip_levels
+            || self.array_layers > properties.max_array_layers
+        {
+            return Err(RenderError::FormatNotSupported(format!(
+                "{:?} with LINEAR tiling supports at most {} mip levels and {} array layers",
+                self.format, properties.max_mip_levels, properties.max_array_layers
+            )));
+        }
+        Ok(())
+    }
+
+    /// Builds a layered texture with one picture per array layer, pictures not matching
+    /// the builder extent are resized to it. Only the base mip level is filled.
+    ///
+    /// With [`TextureBuilder::cube_compatible`] the pictures are taken as cube faces,
+    /// so their count must be a multiple of 6 and the extent must be square.
+    pub fn build_from_rgba8_pictures<P: AsRef<Path>>(
+        mut self,
+        paths: &[P],
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Texture> {
+        if !matches!(
+            self.format,
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM
+        ) {
+            return Err(RenderError::FormatNotSupported(format!(
+                "{:?} can not hold rgba8 pictures",
+                self.format
+            )));
+        }
+        if paths.is_empty() {
+            return Err(RenderError::ImageDataInvalid(
+                "No picture to build texture from".to_string(),
+            ));
+        }
+        if self.flags.contains(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+            && (!paths.len().is_multiple_of(6) || self.width != self.height)
+        {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "Cubemap needs square faces in multiples of 6, got {} pictures of {}x{}",
+                paths.len(),
+                self.width,
+                self.height
+            )));
+        }
+
+        // Pictures are decoded and resized in parallel, the upload stays on this thread
+        let (width, height) = (self.width, self.height);
+        let pictures = JobSystem::global().map(
+            "decode picture",
+            paths.iter().map(|path| path.as_ref().to_path_buf()),
+            move |path| -> RenderResult<Vec<u8>> {
+                let mut picture = image_loader::io::Reader::open(path)?.decode()?.to_rgba8();
+                if picture.dimensions() != (width, height) {
+                    picture = image_loader::imageops::resize(
+                        &picture,
+                        width,
+                        height,
+                        image_loader::imageops::FilterType::Triangle,
+                    );
+                }
+                Ok(picture.into_raw())
+            },
+        );
+        let mut data = Vec::with_capacity(paths.len() * (width * height * 4) as usize);
+        for picture in pictures {
+            data.extend_from_slice(&picture?);
+        }
+
+        self.array_layers = paths.len() as u32;
+        self.usage |= vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED;
+
+        let mut texture = self.build()?;
+        texture.upload_layers(&data, command_pool, queue)?;
+        Ok(texture)
+    }
 }
 
 pub struct Texture {
@@ -93,6 +243,11 @@ pub struct Texture {
     mip_levels: u32,
     array_layers: u32,
     format: vk::Format,
+    tiling: vk::ImageTiling,
+    usage: vk::ImageUsageFlags,
+    flags: vk::ImageCreateFlags,
+    memory_properties: vk::MemoryPropertyFlags,
+    mapped_ptr: Option<*mut c_void>,
     image_view: Option<Rc<vk::ImageView>>,
     sampler: Option<Rc<vk::Sampler>>,
     device: Rc<Device>,
@@ -110,9 +265,12 @@ impl Texture {
         format: vk::Format,
         tiling: vk::ImageTiling,
         usage: vk::ImageUsageFlags,
+        flags: vk::ImageCreateFlags,
+        memory_properties: vk::MemoryPropertyFlags,
         device: Rc<Device>,
     ) -> RenderResult<Self> {
         let create_info = vk::ImageCreateInfo::builder()
+            .flags(flags)
             .image_type(if depth > 1 {
                 vk::ImageType::TYPE_3D
             } else {
@@ -143,10 +301,13 @@ impl Texture {
                 .memory_type_index(super::memory_helper::find_memory_type(
                     &device,
                     &memory_requirement,
-                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    memory_properties,
                 )?)
                 .build();
-            let device_momory = device.allocate_memory(&memory_alloc_info, None)?;
+            let device_momory = device.allocate_tracked_memory(
+                &memory_alloc_info,
+                MemoryTypeUsage::is_hot_image(usage),
+            )?;
 
             device.bind_image_memory(image, device_momory, 0)?;
 
@@ -160,6 +321,11 @@ impl Texture {
                 mip_levels,
                 array_layers,
                 format,
+                tiling,
+                usage,
+                flags,
+                memory_properties,
+                mapped_ptr: None,
                 image_view: None,
                 sampler: None,
                 device,
@@ -189,6 +355,10 @@ impl Texture {
         &self.device_momory
     }
 
+    pub fn device(&self) -> &Rc<Device> {
+        &self.device
+    }
+
     pub fn format(&self) -> vk::Format {
         self.format
     }
@@ -201,6 +371,448 @@ impl Texture {
         self.image_layout
     }
 
+    pub fn array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
+    /// Greater than 1 only for 3D textures
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    pub fn tiling(&self) -> vk::ImageTiling {
+        self.tiling
+    }
+
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+
+    pub fn memory_property_flags(&self) -> vk::MemoryPropertyFlags {
+        self.memory_properties
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.mapped_ptr.is_some()
+    }
+
+    pub fn mapped_ptr(&self) -> Option<*mut c_void> {
+        self.mapped_ptr
+    }
+
+    /// Maps the whole image memory, the texture must live in `HOST_VISIBLE` memory
+    pub fn map_memory(&mut self) -> VkResult<*mut c_void> {
+        assert!(!self.is_mapped());
+        assert!(self
+            .memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE));
+        unsafe {
+            let ptr = self.device.map_memory(
+                self.device_momory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::default(),
+            )?;
+            self.mapped_ptr = Some(ptr);
+            Ok(ptr)
+        }
+    }
+
+    pub fn unmap_memory(&mut self) {
+        assert!(self.is_mapped());
+        unsafe { self.device.unmap_memory(self.device_momory) };
+        self.mapped_ptr.take();
+    }
+
+    /// Memory layout of one color subresource, only meaningful for `LINEAR` tiling
+    pub fn subresource_layout(&self, mip_level: u32, array_layer: u32) -> vk::SubresourceLayout {
+        assert!(self.tiling == vk::ImageTiling::LINEAR);
+        unsafe {
+            self.device.get_image_subresource_layout(
+                self.image,
+                vk::ImageSubresource::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(mip_level)
+                    .array_layer(array_layer)
+                    .build(),
+            )
+        }
+    }
+
+    /// Copies one subresource of a mapped `LINEAR` texture into tightly packed rows.
+    ///
+    /// The caller is responsible for the GPU having finished writing the image
+    pub fn read_texels(&self, mip_level: u32, array_layer: u32) -> RenderResult<Vec<u8>> {
+        let (row_size, row_offsets) = self.mapped_rows(mip_level, array_layer)?;
+        let mapped_ptr = self.mapped_ptr.unwrap() as *const u8;
+        let mut texels = vec![0u8; row_size * row_offsets.len()];
+        texels
+            .chunks_exact_mut(row_size)
+            .zip(row_offsets)
+            .for_each(|(dst, row_offset)| unsafe {
+                let src = mapped_ptr.add(row_offset);
+                std::ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), row_size);
+            });
+        Ok(texels)
+    }
+
+    /// Writes tightly packed rows into one subresource of a mapped `LINEAR` texture
+    pub fn write_texels(
+        &mut self,
+        mip_level: u32,
+        array_layer: u32,
+        texels: &[u8],
+    ) -> RenderResult<()> {
+        let (row_size, row_offsets) = self.mapped_rows(mip_level, array_layer)?;
+        if texels.len() != row_size * row_offsets.len() {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "expected {} bytes of texels, got {}",
+                row_size * row_offsets.len(),
+                texels.len()
+            )));
+        }
+        let mapped_ptr = self.mapped_ptr.unwrap() as *mut u8;
+        texels
+            .chunks_exact(row_size)
+            .zip(row_offsets)
+            .for_each(|(src, row_offset)| unsafe {
+                let dst = mapped_ptr.add(row_offset);
+                std::ptr::copy_nonoverlapping(src.as_ptr(), dst, row_size);
+            });
+        Ok(())
+    }
+
+    /// Copies one subresource into host visible memory in a one-time command, waits for
+    /// it and returns its tightly packed rows, e.g. to verify what the GPU rendered into an
+    /// `OPTIMAL` tiled image. Only the depth of depth/stencil formats is copied. The image
+    /// needs `TRANSFER_SRC` usage and goes back to its layout afterwards, or stays in
+    /// `TRANSFER_SRC_OPTIMAL` when that was `UNDEFINED` or `PREINITIALIZED`.
+    pub fn read_back(
+        &mut self,
+        mip_level: u32,
+        array_layer: u32,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Vec<u8>> {
+        assert!(mip_level < self.mip_levels && array_layer < self.array_layers);
+        let (aspect, texel_size) = read_back_aspect(self.format).ok_or_else(|| {
+            RenderError::FormatNotSupported(format!("texel size of {:?} is unknown", self.format))
+        })?;
+        let extent = vk::Extent3D {
+            width: (self.extent_2d.width >> mip_level).max(1),
+            height: (self.extent_2d.height >> mip_level).max(1),
+            depth: (self.depth >> mip_level).max(1),
+        };
+        let size = extent.width as usize * extent.height as usize * extent.depth as usize;
+        let mut readback = Buffer::<u8>::new(
+            size * texel_size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            self.device.clone(),
+        )?;
+
+        let device = self.device.clone();
+        let old_layout = self.image_layout;
+        let final_layout = match old_layout {
+            vk::ImageLayout::UNDEFINED | vk::ImageLayout::PREINITIALIZED => {
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+            }
+            layout => layout,
+        };
+        OneTimeCommand::new(&device, command_pool)?.take_and_execute(
+            |command_buffer| {
+                self.transition_layout(
+                    command_buffer,
+                    old_layout,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::TRANSFER,
+                );
+                unsafe {
+                    device.cmd_copy_image_to_buffer(
+                        command_buffer,
+                        self.image,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        readback.buffer(),
+                        &[vk::BufferImageCopy::builder()
+                            .image_subresource(
+                                vk::ImageSubresourceLayers::builder()
+                                    .aspect_mask(aspect)
+                                    .mip_level(mip_level)
+                                    .base_array_layer(array_layer)
+                                    .layer_count(1)
+                                    .build(),
+                            )
+                            .image_extent(extent)
+                            .build()],
+                    );
+                    device.cmd_pipeline_barrier(
+                        command_buffer,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::PipelineStageFlags::HOST,
+                        vk::DependencyFlags::empty(),
+                        &[vk::MemoryBarrier::builder()
+                            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                            .dst_access_mask(vk::AccessFlags::HOST_READ)
+                            .build()],
+                        &[],
+                        &[],
+                    );
+                }
+                if final_layout != vk::ImageLayout::TRANSFER_SRC_OPTIMAL {
+                    self.transition_layout(
+                        command_buffer,
+                        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        final_layout,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::PipelineStageFlags::ALL_COMMANDS,
+                    );
+                }
+                Ok(())
+            },
+            queue,
+        )?;
+
+        let ptr = readback.map_memory_all()? as *const u8;
+        Ok(unsafe { std::slice::from_raw_parts(ptr, readback.size_in_bytes() as usize) }.to_vec())
+    }
+
+    /// Tightly packed texels of the base mip level, layer after layer, see [`Self::read_back`]
+    pub fn download(
+        &mut self,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Vec<u8>> {
+        let mut texels = Vec::new();
+        for array_layer in 0..self.array_layers {
+            texels.extend(self.read_back(0, array_layer, command_pool, queue)?);
+        }
+        Ok(texels)
+    }
+
+    /// Saves the base mip level for inspection, layers stacked from top to bottom. Single
+    /// channel formats become grayscale, float ones and depth stretched from their minimum
+    /// to their maximum. Float colors are clamped to `[0, 1]`.
+    pub fn save_png<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<()> {
+        let texels = self.download(command_pool, queue)?;
+        let rgba = texels_to_rgba8(self.format, &texels).ok_or_else(|| {
+            RenderError::FormatNotSupported(format!("{:?} can not be saved", self.format))
+        })?;
+        image_loader::RgbaImage::from_raw(
+            self.extent_2d.width,
+            self.extent_2d.height * self.depth * self.array_layers,
+            rgba,
+        )
+        .ok_or_else(|| RenderError::ImageDataInvalid("Downloaded texels too few".into()))?
+        .save(path)?;
+        Ok(())
+    }
+
+    /// Records a blit of the base mip level of every array layer both textures have to
+    /// `dst`, scaled to its extent with `filter`. Both textures end in the layout they were
+    /// in, or in their transfer layout when it was `UNDEFINED` or `PREINITIALIZED`.
+    pub fn blit_to(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        dst: &mut Texture,
+        filter: vk::Filter,
+    ) -> RenderResult<()> {
+        self.check_format_features(vk::FormatFeatureFlags::BLIT_SRC)?;
+        dst.check_format_features(vk::FormatFeatureFlags::BLIT_DST)?;
+        if filter == vk::Filter::LINEAR {
+            self.check_format_features(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)?;
+        }
+        self.cmd_transfer_to(command_buffer, dst, |device, src, dst| {
+            let (src_subresource, dst_subresource) = src.base_level_subresources(dst);
+            unsafe {
+                device.cmd_blit_image(
+                    command_buffer,
+                    src.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    dst.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[vk::ImageBlit::builder()
+                        .src_subresource(src_subresource)
+                        .src_offsets([vk::Offset3D::default(), src.base_level_end()])
+                        .dst_subresource(dst_subresource)
+                        .dst_offsets([vk::Offset3D::default(), dst.base_level_end()])
+                        .build()],
+                    filter,
+                )
+            }
+        });
+        Ok(())
+    }
+
+    /// Records a copy of the base mip level of every array layer both textures have to
+    /// `dst`, over the extent both cover, without scaling nor format conversion. The texel
+    /// sizes of the formats must match. Layouts are handled as by [`Self::blit_to`].
+    pub fn copy_to(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        dst: &mut Texture,
+    ) -> RenderResult<()> {
+        let texel_size = texel_size_in_bytes(self.format);
+        if texel_size.is_none() || texel_size != texel_size_in_bytes(dst.format) {
+            return Err(RenderError::FormatNotSupported(format!(
+                "{:?} can not be copied to {:?}",
+                self.format, dst.format
+            )));
+        }
+        self.cmd_transfer_to(command_buffer, dst, |device, src, dst| {
+            let (src_subresource, dst_subresource) = src.base_level_subresources(dst);
+            let (src_end, dst_end) = (src.base_level_end(), dst.base_level_end());
+            unsafe {
+                device.cmd_copy_image(
+                    command_buffer,
+                    src.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    dst.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[vk::ImageCopy::builder()
+                        .src_subresource(src_subresource)
+                        .dst_subresource(dst_subresource)
+                        .extent(vk::Extent3D {
+                            width: src_end.x.min(dst_end.x) as u32,
+                            height: src_end.y.min(dst_end.y) as u32,
+                            depth: src_end.z.min(dst_end.z) as u32,
+                        })
+                        .build()],
+                )
+            }
+        });
+        Ok(())
+    }
+
+    /// Moves both textures to their transfer layout around `record` and back
+    fn cmd_transfer_to(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        dst: &mut Texture,
+        record: impl FnOnce(&Device, &Texture, &Texture),
+    ) {
+        let layouts = [
+            (self.image_layout, vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+            (dst.image_layout, vk::ImageLayout::TRANSFER_DST_OPTIMAL),
+        ];
+        for (texture, (old_layout, transfer_layout)) in
+            [&mut *self, &mut *dst].into_iter().zip(layouts)
+        {
+            texture.transition_layout(
+                command_buffer,
+                old_layout,
+                transfer_layout,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+            );
+        }
+        record(&self.device, self, dst);
+        for (texture, (old_layout, transfer_layout)) in
+            [&mut *self, &mut *dst].into_iter().zip(layouts)
+        {
+            let initial_only = matches!(
+                old_layout,
+                vk::ImageLayout::UNDEFINED | vk::ImageLayout::PREINITIALIZED
+            );
+            if !initial_only && old_layout != transfer_layout {
+                texture.transition_layout(
+                    command_buffer,
+                    transfer_layout,
+                    old_layout,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                );
+            }
+        }
+    }
+
+    /// Base mip level of the array layers shared with `dst`, of both textures
+    fn base_level_subresources(
+        &self,
+        dst: &Texture,
+    ) -> (vk::ImageSubresourceLayers, vk::ImageSubresourceLayers) {
+        let layer_count = self.array_layers.min(dst.array_layers);
+        let subresource = |format| {
+            vk::ImageSubresourceLayers::builder()
+                .aspect_mask(aspect_mask(format))
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(layer_count)
+                .build()
+        };
+        (subresource(self.format), subresource(dst.format))
+    }
+
+    /// Far corner of the base mip level
+    fn base_level_end(&self) -> vk::Offset3D {
+        vk::Offset3D {
+            x: self.extent_2d.width as i32,
+            y: self.extent_2d.height as i32,
+            z: self.depth as i32,
+        }
+    }
+
+    fn check_format_features(&self, features: vk::FormatFeatureFlags) -> RenderResult<()> {
+        let properties = unsafe {
+            self.device
+                .instance()
+                .get_physical_device_format_properties(
+                    *self.device.physical_device().upgrade().unwrap(),
+                    self.format,
+                )
+        };
+        let supported = match self.tiling {
+            vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+            _ => properties.optimal_tiling_features,
+        };
+        if supported.contains(features) {
+            Ok(())
+        } else {
+            Err(RenderError::FormatNotSupported(format!(
+                "{:?} with {:?} tiling lacks {:?}",
+                self.format, self.tiling, features
+            )))
+        }
+    }
+
+    /// Tightly packed row size and mapped offsets of the rows of a subresource, the slices
+    /// of 3D textures one after the other
+    fn mapped_rows(&self, mip_level: u32, array_layer: u32) -> RenderResult<(usize, Vec<usize>)> {
+        assert!(self.is_mapped());
+        assert!(mip_level < self.mip_levels && array_layer < self.array_layers);
+        let texel_size = texel_size_in_bytes(self.format).ok_or_else(|| {
+            RenderError::FormatNotSupported(format!("texel size of {:?} is unknown", self.format))
+        })?;
+        let width = (self.extent_2d.width >> mip_level).max(1) as usize;
+        let height = (self.extent_2d.height >> mip_level).max(1) as usize;
+        let depth = (self.depth >> mip_level).max(1) as usize;
+        let layout = self.subresource_layout(mip_level, array_layer);
+        let row_offsets = (0..depth)
+            .flat_map(|slice| {
+                (0..height).map(move |row| {
+                    (layout.offset
+                        + slice as u64 * layout.depth_pitch
+                        + row as u64 * layout.row_pitch) as usize
+                })
+            })
+            .collect();
+        Ok((width * texel_size, row_offsets))
+    }
+
+    pub fn is_cube(&self) -> bool {
+        self.flags.contains(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+            && self.array_layers.is_multiple_of(6)
+    }
+
     pub fn image_view(&self) -> Option<&vk::ImageView> {
         self.image_view.as_deref()
     }
@@ -213,6 +825,12 @@ impl Texture {
         let image_view = {
             let image_view_type = if self.depth > 1 {
                 vk::ImageViewType::TYPE_3D
+            } else if self.is_cube() {
+                if self.array_layers > 6 {
+                    vk::ImageViewType::CUBE_ARRAY
+                } else {
+                    vk::ImageViewType::CUBE
+                }
             } else if self.array_layers > 1 {
                 vk::ImageViewType::TYPE_2D_ARRAY
             } else {
@@ -267,6 +885,33 @@ impl Texture {
         self.descriptor(*self.image_view().unwrap(), *self.sampler().unwrap())
     }
 
+    /// Descriptor of a storage image, without sampler, in `GENERAL` layout which is the
+    /// layout storage images must be in when accessed. The image view must be spawned.
+    pub fn storage_descriptor(&self) -> vk::DescriptorImageInfo {
+        debug_assert!(self.usage.contains(vk::ImageUsageFlags::STORAGE));
+        vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::GENERAL)
+            .image_view(*self.image_view().unwrap())
+            .build()
+    }
+
+    /// Transitions from the current layout to `GENERAL`, for shaders to write the texture
+    /// in `dst_stage_mask`. The content is discarded if the current layout is `UNDEFINED`.
+    pub fn transition_to_general(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        src_stage_mask: vk::PipelineStageFlags,
+        dst_stage_mask: vk::PipelineStageFlags,
+    ) {
+        self.transition_layout(
+            command_buffer,
+            self.image_layout,
+            vk::ImageLayout::GENERAL,
+            src_stage_mask,
+            dst_stage_mask,
+        );
+    }
+
     pub fn transition_layout(
         &mut self,
         command_buffer: vk::CommandBuffer,
@@ -279,9 +924,10 @@ impl Texture {
             &self.device,
             command_buffer,
             self.image,
+            self.format,
             Some(
                 vk::ImageSubresourceRange::builder()
-                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .aspect_mask(aspect_mask(self.format))
                     .base_mip_level(0)
                     .layer_count(self.array_layers)
                     .base_array_layer(0)
@@ -303,82 +949,51 @@ impl Texture {
         queue: &vk::Queue,
     ) -> RenderResult<Self> {
         let image_data = image_loader::io::Reader::open(&path)?.decode()?.to_rgba8();
-        let size = image_data.len();
-
-        let staging_buffer = {
-            let mut buffer = Buffer::<u8>::new(
-                size,
-                vk::BufferUsageFlags::TRANSFER_SRC,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                device.clone(),
-            )?;
-            buffer.load_data(&image_data, 0)?;
-            buffer
-        };
-
-        let mut texture = Self::builder(
+        Self::from_rgba8_pixels(
             image_data.width(),
             image_data.height(),
+            &image_data,
             vk::Format::R8G8B8A8_SRGB,
-            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-            device.clone(),
+            device,
+            command_pool,
+            queue,
         )
-        .build()?;
-
-        OneTimeCommand::new(&device, command_pool)?.take_and_execute(
-            |command_buffer| {
-                texture.transition_layout(
-                    command_buffer,
-                    vk::ImageLayout::UNDEFINED,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    vk::PipelineStageFlags::TOP_OF_PIPE,
-                    vk::PipelineStageFlags::TRANSFER,
-                );
-
-                let image_copy = vk::BufferImageCopy::builder()
-                    .image_subresource(
-                        vk::ImageSubresourceLayers::builder()
-                            .aspect_mask(vk::ImageAspectFlags::COLOR)
-                            .mip_level(0)
-                            .base_array_layer(0)
-                            .layer_count(1)
-                            .build(),
-                    )
-                    .image_offset(vk::Offset3D::default())
-                    .image_extent(
-                        vk::Extent3D::builder()
-                            .width(image_data.width())
-                            .height(image_data.height())
-                            .depth(1)
-                            .build(),
-                    )
-                    .build();
-                unsafe {
-                    device.cmd_copy_buffer_to_image(
-                        command_buffer,
-                        staging_buffer.buffer(),
-                        texture.image,
-                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        &[image_copy],
-                    );
-                }
+    }
 
-                texture.transition_layout(
-                    command_buffer,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::PipelineStageFlags::FRAGMENT_SHADER,
-                );
+    /// Uploads tightly packed RGBA8 texels decoded elsewhere, `format` tells whether
+    /// they are sRGB encoded
+    pub fn from_rgba8_pixels(
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        format: vk::Format,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "{} bytes can not hold {width}x{height} rgba8 texels",
+                pixels.len()
+            )));
+        }
 
-                Ok(())
-            },
-            queue,
-        )?;
+        let mut texture = Self::builder(
+            width,
+            height,
+            format,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            device,
+        )
+        .build()?;
+        texture.upload_layers(pixels, command_pool, queue)?;
 
         Ok(texture)
     }
 
+    /// Loads every array layer and mip level of a KTX file,
+    /// returns the texture and its layer count
+    #[cfg(feature = "ktx")]
     pub fn from_ktx<P: AsRef<Path>>(
         path: P,
         device: Rc<Device>,
@@ -396,104 +1011,295 @@ impl Texture {
                 x
             }
         };
-        let data: Vec<Vec<u8>> = decoder.read_textures().collect();
+        let levels = read_ktx_levels(decoder)?;
 
-        // TODO: deal with multi level data
-        assert!(data.len() == 1);
+        let mut texture = Texture::builder(
+            width,
+            height,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            device,
+        )
+        .mip_levels(levels.len() as u32)
+        .array_layers(layer_count)
+        .build()?;
+        texture.upload_levels(
+            &levels.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+            command_pool,
+            queue,
+        )?;
 
-        let data = data.concat();
-        let size = data.len();
-        let size_per_layer = size as u32 / layer_count;
+        Ok((texture, layer_count))
+    }
 
-        assert!(size_per_layer * layer_count == size as u32);
+    /// Loads PNG/JPG pictures of equal extent as the layers of an array texture,
+    /// returns the texture and its layer count like [`Texture::from_ktx`]
+    pub fn array_from_paths<P: AsRef<Path>>(
+        paths: &[P],
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<(Self, u32)> {
+        if paths.is_empty() {
+            return Err(RenderError::ImageDataInvalid(
+                "No picture to build texture from".to_string(),
+            ));
+        }
 
-        let staging_buffer = {
-            let mut buffer = Buffer::<u8>::new(
-                size,
-                vk::BufferUsageFlags::TRANSFER_SRC,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                device.clone(),
-            )?;
-            buffer.load_data(&data, 0)?;
-            buffer
-        };
+        // Only the headers are read here, the pictures are decoded by the builder
+        let mut extent = None;
+        for path in paths {
+            let dimensions = image_loader::image_dimensions(path)?;
+            let (width, height) = *extent.get_or_insert(dimensions);
+            if dimensions != (width, height) {
+                return Err(RenderError::ImageDataInvalid(format!(
+                    "{} is {}x{}, but the first array layer is {width}x{height}",
+                    path.as_ref().display(),
+                    dimensions.0,
+                    dimensions.1
+                )));
+            }
+        }
+        let (width, height) = extent.unwrap();
+
+        let texture = Texture::builder(
+            width,
+            height,
+            vk::Format::R8G8B8A8_SRGB,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            device,
+        )
+        .build_from_rgba8_pictures(paths, command_pool, queue)?;
+
+        let layer_count = texture.array_layers();
+        Ok((texture, layer_count))
+    }
+
+    /// Loads the 6 faces of a KTX cubemap with all their mip levels into a `CUBE_COMPATIBLE`
+    /// texture, [`Texture::spawn_image_view`] then creates a cube view.
+    #[cfg(feature = "ktx")]
+    pub fn cubemap_from_ktx<P: AsRef<Path>>(
+        path: P,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        let buf_reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let decoder = ktx::Decoder::new(buf_reader)?;
+        let (width, height) = (decoder.pixel_width(), decoder.pixel_height());
+        if decoder.faces() != 6 || decoder.array_elements() != 0 {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "expected a single cubemap with 6 faces, got {} faces and {} array elements",
+                decoder.faces(),
+                decoder.array_elements()
+            )));
+        }
+        if width != height {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "cubemap faces must be square, got {width}x{height}"
+            )));
+        }
+
+        // Faces of a level are stored in +X, -X, +Y, -Y, +Z, -Z order, as cube array layers
+        let levels = read_ktx_levels(decoder)?;
 
         let mut texture = Texture::builder(
             width,
             height,
             vk::Format::R8G8B8A8_UNORM,
             vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-            device.clone(),
+            device,
         )
-        .array_layers(layer_count)
+        .mip_levels(levels.len() as u32)
+        .array_layers(6)
+        .cube_compatible()
         .build()?;
+        texture.upload_levels(
+            &levels.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+            command_pool,
+            queue,
+        )?;
 
-        OneTimeCommand::new(&device, command_pool)?.take_and_execute(
-            |command_buffer| {
-                texture.transition_layout(
-                    command_buffer,
-                    vk::ImageLayout::UNDEFINED,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    vk::PipelineStageFlags::TOP_OF_PIPE,
-                    vk::PipelineStageFlags::TRANSFER,
-                );
+        Ok(texture)
+    }
 
-                let image_copies = (0..layer_count)
-                    .map(|layer| {
-                        vk::BufferImageCopy::builder()
-                            .image_subresource(
-                                vk::ImageSubresourceLayers::builder()
-                                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                                    .mip_level(0)
-                                    .base_array_layer(layer)
-                                    .layer_count(1)
-                                    .build(),
-                            )
-                            .image_offset(vk::Offset3D::default())
-                            .image_extent(
-                                vk::Extent3D::builder()
-                                    .width(width)
-                                    .height(height)
-                                    .depth(texture.depth)
-                                    .build(),
-                            )
-                            .buffer_offset((size_per_layer * layer) as u64)
-                            .build()
-                    })
-                    .collect::<Vec<_>>();
+    /// Loads a KTX2 file with all its mip levels, keeping block compressed BC, ETC2 and ASTC
+    /// payloads as they are, returns the texture and its layer count like [`Texture::from_ktx`].
+    /// Files with 6 faces become `CUBE_COMPATIBLE` textures.
+    ///
+    /// Fails with [`RenderError::FormatNotSupported`] when the physical device can not sample
+    /// the format, so callers can fall back to another asset, and with
+    /// [`RenderError::ImageDataInvalid`] for supercompressed or Basis Universal files.
+    #[cfg(feature = "ktx")]
+    pub fn from_ktx2<P: AsRef<Path>>(
+        path: P,
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<(Self, u32)> {
+        let path = path.as_ref();
+        let file = std::fs::read(path)?;
+        let reader = ktx2::Reader::new(file.as_slice())
+            .map_err(|e| RenderError::ImageDataInvalid(format!("{}: {e}", path.display())))?;
+        let header = reader.header();
+
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "{}: supercompression {scheme:?} is not supported",
+                path.display()
+            )));
+        }
+        let format = match header.format {
+            Some(format) => vk::Format::from_raw(format.0.get() as i32),
+            None => {
+                return Err(RenderError::ImageDataInvalid(format!(
+                    "{}: Basis Universal payloads need transcoding, which is not supported",
+                    path.display()
+                )))
+            }
+        };
+        check_sampled_format_support(&device, format)?;
 
-                unsafe {
-                    device.cmd_copy_buffer_to_image(
+        let layer_count = header.layer_count.max(1);
+        let is_cube = header.face_count == 6;
+        let levels = reader.levels().collect::<Vec<_>>();
+
+        let mut builder = Texture::builder(
+            header.pixel_width,
+            header.pixel_height.max(1),
+            format,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            device,
+        )
+        .mip_levels(levels.len() as u32)
+        .array_layers(layer_count * header.face_count);
+        if is_cube {
+            builder = builder.cube_compatible();
+        }
+        let mut texture = builder.build()?;
+        texture.upload_levels(&levels, command_pool, queue)?;
+
+        Ok((texture, layer_count))
+    }
+
+    /// Fills the base mip level of every array layer from tightly packed `data`
+    /// and leaves the image in `SHADER_READ_ONLY_OPTIMAL`
+    fn upload_layers(
+        &mut self,
+        data: &[u8],
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<()> {
+        self.upload_levels(&[data], command_pool, queue)
+    }
+
+    /// Fills one mip level per element of `levels`, each holding the tightly packed
+    /// data of every array layer, and leaves the image in `SHADER_READ_ONLY_OPTIMAL`
+    fn upload_levels(
+        &mut self,
+        levels: &[&[u8]],
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<()> {
+        if levels.len() as u32 > self.mip_levels {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "{} mip levels of data for a texture of {}",
+                levels.len(),
+                self.mip_levels
+            )));
+        }
+        if let Some((level, data)) = levels
+            .iter()
+            .enumerate()
+            .find(|(_, data)| data.len() % self.array_layers as usize != 0)
+        {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "{} bytes of mip level {level} can not be split into {} array layers",
+                data.len(),
+                self.array_layers
+            )));
+        }
+
+        let device = self.device.clone();
+        let mut staging_pool = device.staging_pool();
+        let region = staging_pool.stage(&levels.concat(), &device)?;
+
+        let mut image_copies = vec![];
+        let mut level_offset = 0;
+        for (level, data) in levels.iter().enumerate() {
+            let size_per_layer = data.len() as u32 / self.array_layers;
+
+            let extent = vk::Extent3D::builder()
+                .width((self.extent_2d.width >> level).max(1))
+                .height((self.extent_2d.height >> level).max(1))
+                .depth((self.depth >> level).max(1))
+                .build();
+            image_copies.extend((0..self.array_layers).map(|layer| {
+                vk::BufferImageCopy::builder()
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::builder()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .mip_level(level as u32)
+                            .base_array_layer(layer)
+                            .layer_count(1)
+                            .build(),
+                    )
+                    .image_offset(vk::Offset3D::default())
+                    .image_extent(extent)
+                    .buffer_offset(region.offset + level_offset + (size_per_layer * layer) as u64)
+                    .build()
+            }));
+            level_offset += data.len() as u64;
+        }
+
+        staging_pool.submit(&region, |fence| {
+            OneTimeCommand::new(&device, command_pool)?.take_and_execute_with_fence(
+                |command_buffer| {
+                    self.transition_layout(
                         command_buffer,
-                        staging_buffer.buffer(),
-                        texture.image,
+                        vk::ImageLayout::UNDEFINED,
                         vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        &image_copies,
+                        vk::PipelineStageFlags::TOP_OF_PIPE,
+                        vk::PipelineStageFlags::TRANSFER,
                     );
-                }
 
-                texture.transition_layout(
-                    command_buffer,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    vk::PipelineStageFlags::TRANSFER,
-                    vk::PipelineStageFlags::FRAGMENT_SHADER,
-                );
+                    unsafe {
+                        device.cmd_copy_buffer_to_image(
+                            command_buffer,
+                            region.buffer,
+                            self.image,
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            &image_copies,
+                        );
+                    }
+
+                    self.transition_layout(
+                        command_buffer,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    );
 
-                Ok(())
-            },
-            queue,
-        )?;
+                    Ok(())
+                },
+                queue,
+                fence,
+            )
+        })?;
 
-        Ok((texture, layer_count))
+        Ok(())
     }
 }
 
 impl Drop for Texture {
     fn drop(&mut self) {
         unsafe {
+            if self.is_mapped() {
+                self.unmap_memory();
+            }
             self.device.destroy_image(self.image, None);
-            self.device.free_memory(self.device_momory, None);
+            self.device.free_tracked_memory(self.device_momory);
             if let Some(view) = &self.image_view {
                 if Rc::strong_count(view) == 1 {
                     self.device.destroy_image_view(**view, None);
@@ -512,11 +1318,21 @@ pub struct DepthStencil(Texture);
 
 impl DepthStencil {
     pub fn new(extent: vk::Extent2D, format: vk::Format, device: Rc<Device>) -> RenderResult<Self> {
+        Self::with_usage(extent, format, vk::ImageUsageFlags::empty(), device)
+    }
+
+    /// `usage` is added to `DEPTH_STENCIL_ATTACHMENT`, e.g. `SAMPLED` for shadow maps
+    pub fn with_usage(
+        extent: vk::Extent2D,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
         let mut buffer = Texture::builder(
             extent.width,
             extent.height,
             format,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | usage,
             device.clone(),
         )
         .build()?;
@@ -555,25 +1371,179 @@ impl DepthStencil {
         self.0.format()
     }
 
+    pub fn sampler(&self) -> Option<&vk::Sampler> {
+        self.0.sampler()
+    }
+
+    /// Only meaningful when created with `SAMPLED` usage, the view covers the depth aspect only
+    pub fn set_sampler(&mut self, sampler: Rc<vk::Sampler>) {
+        self.0.set_sampler(sampler)
+    }
+
     pub fn find_depth_format(device: &Device) -> RenderResult<vk::Format> {
-        filter_supported_format(
-            &vec![
-                vk::Format::D32_SFLOAT,
-                vk::Format::D32_SFLOAT_S8_UINT,
-                vk::Format::D24_UNORM_S8_UINT,
-            ],
-            vk::ImageTiling::OPTIMAL,
-            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-            device,
-        )
+        device
+            .capabilities()
+            .preferred_depth_format()
+            .ok_or_else(|| {
+                RenderError::FormatNotSupported("No depth attachment format".to_string())
+            })
     }
 
     pub fn has_stencil_component(&self) -> bool {
-        self.buffer().format == vk::Format::D32_SFLOAT_S8_UINT
-            || self.buffer().format == vk::Format::D24_UNORM_S8_UINT
+        aspect_mask(self.format()).contains(vk::ImageAspectFlags::STENCIL)
+    }
+}
+
+fn texel_size_in_bytes(format: vk::Format) -> Option<usize> {
+    match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB => Some(1),
+        vk::Format::R8G8_UNORM | vk::Format::R16_SFLOAT | vk::Format::D16_UNORM => Some(2),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::R32_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::D32_SFLOAT => Some(4),
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_SFLOAT => Some(8),
+        vk::Format::R32G32B32A32_SFLOAT => Some(16),
+        _ => None,
+    }
+}
+
+/// Aspect [`Texture::read_back`] copies and the size of its texels once copied, the depth
+/// of depth/stencil formats
+fn read_back_aspect(format: vk::Format) -> Option<(vk::ImageAspectFlags, usize)> {
+    match format {
+        vk::Format::D16_UNORM_S8_UINT => Some((vk::ImageAspectFlags::DEPTH, 2)),
+        vk::Format::X8_D24_UNORM_PACK32
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => Some((vk::ImageAspectFlags::DEPTH, 4)),
+        _ => Some((aspect_mask(format), texel_size_in_bytes(format)?)),
+    }
+}
+
+/// RGBA8 texels of what [`Texture::read_back`] returns for `format`
+fn texels_to_rgba8(format: vk::Format, texels: &[u8]) -> Option<Vec<u8>> {
+    let words = |size: usize| texels.chunks_exact(size);
+    let gray = |values: Vec<f32>| {
+        let (min, max) = values.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+        let scale = if max > min { 1. / (max - min) } else { 0. };
+        values
+            .into_iter()
+            .flat_map(|v| {
+                let v = ((v - min) * scale * 255.).round() as u8;
+                [v, v, v, 255]
+            })
+            .collect()
+    };
+    let clamped = |values: Vec<f32>| {
+        values
+            .into_iter()
+            .map(|v| (v.clamp(0., 1.) * 255.).round() as u8)
+            .collect()
+    };
+    Some(match format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => texels.to_vec(),
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => {
+            words(4).flat_map(|t| [t[2], t[1], t[0], t[3]]).collect()
+        }
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB => {
+            texels.iter().flat_map(|&v| [v, v, v, 255]).collect()
+        }
+        vk::Format::R8G8_UNORM => words(2).flat_map(|t| [t[0], t[1], 0, 255]).collect(),
+        vk::Format::R16_SFLOAT => gray(
+            words(2)
+                .map(|t| f16_to_f32(u16::from_le_bytes([t[0], t[1]])))
+                .collect(),
+        ),
+        vk::Format::D16_UNORM | vk::Format::D16_UNORM_S8_UINT => gray(
+            words(2)
+                .map(|t| u16::from_le_bytes([t[0], t[1]]) as f32)
+                .collect(),
+        ),
+        vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D24_UNORM_S8_UINT => gray(
+            words(4)
+                .map(|t| (u32::from_le_bytes(t.try_into().unwrap()) & 0xFF_FFFF) as f32)
+                .collect(),
+        ),
+        vk::Format::R32_SFLOAT | vk::Format::D32_SFLOAT | vk::Format::D32_SFLOAT_S8_UINT => gray(
+            words(4)
+                .map(|t| f32::from_le_bytes(t.try_into().unwrap()))
+                .collect(),
+        ),
+        vk::Format::R16G16B16A16_SFLOAT => clamped(
+            words(2)
+                .map(|t| f16_to_f32(u16::from_le_bytes([t[0], t[1]])))
+                .collect(),
+        ),
+        vk::Format::R32G32B32A32_SFLOAT => clamped(
+            words(4)
+                .map(|t| f32::from_le_bytes(t.try_into().unwrap()))
+                .collect(),
+        ),
+        _ => return None,
+    })
+}
+
+/// IEEE 754 half precision bits to `f32`
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1. } else { 1. };
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let mantissa = (bits & 0x3FF) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0. => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1. + mantissa / 1024.) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Reads every mip level of a KTX file, each holding the data of all its layers and faces
+#[cfg(feature = "ktx")]
+fn read_ktx_levels<R: std::io::Read>(decoder: ktx::Decoder<R>) -> RenderResult<Vec<Vec<u8>>> {
+    let level_count = decoder.mipmap_levels() as usize;
+    if level_count == 0 {
+        return Err(RenderError::ImageDataInvalid(
+            "KTX file asks for runtime mip generation, which is not supported".to_owned(),
+        ));
+    }
+    let levels = decoder.read_textures().collect::<Vec<_>>();
+    if levels.len() != level_count {
+        return Err(RenderError::ImageDataInvalid(format!(
+            "KTX file declares {level_count} mip levels, but only {} could be read",
+            levels.len()
+        )));
+    }
+    Ok(levels)
+}
+
+/// Name of the block compression family `format` belongs to, if any
+#[cfg(feature = "ktx")]
+fn compressed_format_family(format: vk::Format) -> Option<&'static str> {
+    match format.as_raw() {
+        131..=146 => Some("BC"),
+        147..=156 => Some("ETC2/EAC"),
+        157..=184 => Some("ASTC"),
+        _ => None,
     }
 }
 
+#[cfg(feature = "ktx")]
+fn check_sampled_format_support(device: &Device, format: vk::Format) -> RenderResult<()> {
+    let features = vk::FormatFeatureFlags::SAMPLED_IMAGE | vk::FormatFeatureFlags::TRANSFER_DST;
+    filter_supported_format(&vec![format], vk::ImageTiling::OPTIMAL, features, device)
+        .map(|_| ())
+        .map_err(|_| {
+            RenderError::FormatNotSupported(match compressed_format_family(format) {
+                Some(family) => format!("{family} compressed {format:?} can not be sampled"),
+                None => format!("{format:?} can not be sampled"),
+            })
+        })
+}
+
 fn default_texture_sampler(device: &Device, filter: vk::Filter) -> VkResult<vk::Sampler> {
     let create_info = vk::SamplerCreateInfo::builder()
         .mag_filter(filter)
@@ -581,14 +1551,8 @@ fn default_texture_sampler(device: &Device, filter: vk::Filter) -> VkResult<vk::
         .address_mode_u(vk::SamplerAddressMode::REPEAT)
         .address_mode_v(vk::SamplerAddressMode::REPEAT)
         .address_mode_w(vk::SamplerAddressMode::REPEAT)
-        .anisotropy_enable(true)
-        .max_anisotropy(unsafe {
-            device
-                .instance()
-                .get_physical_device_properties(*device.physical_device().upgrade().unwrap())
-                .limits
-                .max_sampler_anisotropy
-        })
+        .anisotropy_enable(device.capabilities().max_sampler_anisotropy > 1.)
+        .max_anisotropy(device.capabilities().max_sampler_anisotropy)
         .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
         .unnormalized_coordinates(false)
         .compare_enable(false)
@@ -602,11 +1566,31 @@ fn default_texture_sampler(device: &Device, filter: vk::Filter) -> VkResult<vk::
     unsafe { device.create_sampler(&create_info, None) }
 }
 
+/// Aspects of images of `format`, both of them for combined depth stencil formats, whose
+/// layout transitions have to cover both
+pub fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// Without `subsource_range`, transitions the first mip level and layer of the aspects of
+/// `format`
 #[allow(clippy::too_many_arguments)]
 fn set_image_layout(
     device: &Device,
     command_buffer: vk::CommandBuffer,
     image: vk::Image,
+    format: vk::Format,
     subsource_range: Option<vk::ImageSubresourceRange>,
     old_layout: vk::ImageLayout,
     new_layout: vk::ImageLayout,
@@ -629,10 +1613,31 @@ fn set_image_layout(
             vk::AccessFlags::HOST_WRITE
         }
         vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => {
+            // Image is a color attachment
+            // Make sure any writes to the color buffer have been finished
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        }
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL => {
             // Image is a depth/stencil attachment
             // Make sure any writes to the depth/stencil buffer have been finished
             vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
         }
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL => {
+            // Image is a read only depth/stencil attachment, or sampled
+            // Make sure any reads from the image have been finished
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ
+        }
+        vk::ImageLayout::PRESENT_SRC_KHR => {
+            // Image was presented
+            // The acquire semaphore orders the reads of the presentation engine
+            vk::AccessFlags::NONE
+        }
         vk::ImageLayout::TRANSFER_SRC_OPTIMAL => {
             // Image is a transfer source
             // Make sure any reads from the image have been finished
@@ -648,7 +1653,12 @@ fn set_image_layout(
             // Make sure any shader reads from the image have been finished
             vk::AccessFlags::SHADER_READ
         }
-        _ => unimplemented!(),
+        vk::ImageLayout::GENERAL => {
+            // Image is a storage image
+            // Make sure any shader writes to the image have been finished
+            vk::AccessFlags::SHADER_WRITE
+        }
+        _ => unimplemented!("transition from {old_layout:?}"),
     };
     // Destination access mask controls the dependency for the new image layout
     let dst_access_mask = match new_layout {
@@ -667,10 +1677,27 @@ fn set_image_layout(
             // Make sure any writes to the color buffer have been finished
             vk::AccessFlags::COLOR_ATTACHMENT_WRITE
         }
-        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => {
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL => {
             // Image layout will be used as a depth/stencil attachment
             // Make sure any writes to depth/stencil buffer have been finished
-            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+        }
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL => {
+            // Image will be depth tested without writes, or sampled
+            // Make sure any writes to the image have been finished
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ
+        }
+        vk::ImageLayout::PRESENT_SRC_KHR => {
+            // Image will be presented
+            // The render finished semaphore makes the writes visible to the presentation engine
+            vk::AccessFlags::NONE
         }
         vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
             // Image will be read in a shader (sampler, input attachment)
@@ -680,7 +1707,12 @@ fn set_image_layout(
             }
             vk::AccessFlags::SHADER_READ
         }
-        _ => unimplemented!(),
+        vk::ImageLayout::GENERAL => {
+            // Image will be used as a storage image
+            // Make sure any access of the old layout has been finished before shader writes
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE
+        }
+        _ => unimplemented!("transition to {new_layout:?}"),
     };
 
     let barrier = vk::ImageMemoryBarrier::builder()
@@ -692,7 +1724,7 @@ fn set_image_layout(
         .subresource_range(
             subsource_range.unwrap_or(
                 vk::ImageSubresourceRange::builder()
-                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .aspect_mask(aspect_mask(format))
                     .base_mip_level(0)
                     .layer_count(1)
                     .base_array_layer(0)
@@ -717,6 +1749,7 @@ fn set_image_layout(
     }
 }
 
+#[cfg(feature = "ktx")]
 fn filter_supported_format(
     candidates: &Vec<vk::Format>,
     tiling: vk::ImageTiling,
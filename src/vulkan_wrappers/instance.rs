@@ -2,17 +2,23 @@ use std::{
     borrow::Cow,
     ffi::{c_char, CStr, CString},
     ops::Deref,
+    path::PathBuf,
     rc::{Rc, Weak},
 };
 
 use ash::{extensions::ext::DebugUtils, vk, Entry};
+use log::Level;
 use raw_window_handle::HasRawDisplayHandle;
 use winit::window::Window;
 
-use crate::error::{RenderError, RenderResult};
+use crate::{
+    error::{RenderError, RenderResult},
+    logging::report,
+};
 
 const VALIDATION_LAYER_NAME: &CStr =
     unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") };
+const API_DUMP_LAYER_NAME: &CStr = c"VK_LAYER_LUNARG_api_dump";
 
 #[derive(Clone, Copy, Debug)]
 pub enum VulkanDebugInfoStrategy {
@@ -35,6 +41,116 @@ pub enum VulkanApiVersion {
     V1_3,
 }
 
+/// Optional checks of the validation layer, enabled through `VK_EXT_validation_features`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValidationFeatures {
+    /// Instruments shaders to catch out of bounds descriptor and buffer accesses
+    pub gpu_assisted: bool,
+    /// Reports hazards between commands missing barriers or semaphores
+    pub synchronization: bool,
+    /// Warns about valid usage that is slow on some vendors
+    pub best_practices: bool,
+}
+
+impl ValidationFeatures {
+    /// Picks the features named by `--gpu-assisted-validation`, `--sync-validation` and
+    /// `--best-practices`, other arguments are ignored
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let mut features = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "--gpu-assisted-validation" => features.gpu_assisted = true,
+                "--sync-validation" => features.synchronization = true,
+                "--best-practices" => features.best_practices = true,
+                _ => {}
+            }
+        }
+        features
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Capture of every Vulkan call made during a range of frames, written by the
+/// `VK_LAYER_LUNARG_api_dump` layer to a text file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiDump {
+    pub path: PathBuf,
+    /// Frames are counted by the layer from the first `vkQueuePresentKHR`
+    pub first_frame: u32,
+    /// 0 captures every frame from `first_frame` on
+    pub frame_count: u32,
+}
+
+impl ApiDump {
+    pub const DEFAULT_PATH: &str = "api_dump.txt";
+
+    /// Parses `--api-dump=FIRST:COUNT` into a capture written to [`Self::DEFAULT_PATH`],
+    /// `--api-dump` alone captures the first frame
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        args.into_iter().find_map(|arg| {
+            let range = arg.strip_prefix("--api-dump")?;
+            let (first_frame, frame_count) = match range.strip_prefix('=') {
+                Some(range) => {
+                    let (first, count) = range.split_once(':').unwrap_or((range, "1"));
+                    (first.parse().ok()?, count.parse().ok()?)
+                }
+                None if range.is_empty() => (0, 1),
+                None => return None,
+            };
+            Some(Self {
+                path: Self::DEFAULT_PATH.into(),
+                first_frame,
+                frame_count,
+            })
+        })
+    }
+
+    /// The layer only reads its settings from the environment or `vk_layer_settings.txt`,
+    /// so they are exported for this process before the instance is created
+    fn export_layer_settings(&self) {
+        std::env::set_var("VK_APIDUMP_FILE", "true");
+        std::env::set_var("VK_APIDUMP_LOG_FILENAME", &self.path);
+        std::env::set_var("VK_APIDUMP_OUTPUT_FORMAT", "text");
+        std::env::set_var(
+            "VK_APIDUMP_OUTPUT_RANGE",
+            format!("{}-{}-1", self.first_frame, self.frame_count),
+        );
+    }
+}
+
+/// Environment variable overriding [`InstanceBuilder::prefer_device`], holding
+/// `discrete`, `integrated`, a device index or part of a device name
+pub const DEVICE_PREFERENCE_ENV: &str = "VULKAN_EXAMPLES_DEVICE";
+
+/// Which physical device the instance picks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DevicePreference<'a> {
+    /// The first discrete GPU, falling back to an integrated GPU and then a CPU
+    Discrete,
+    /// The first integrated GPU, falling back to a discrete GPU and then a CPU
+    Integrated,
+    /// The first device whose name contains this, ignoring case
+    ByName(&'a str),
+    /// Index in the order the driver enumerates GPUs and CPUs
+    ByIndex(usize),
+}
+
+impl<'a> DevicePreference<'a> {
+    fn parse(value: &'a str) -> Self {
+        match value.trim() {
+            value if value.eq_ignore_ascii_case("discrete") => Self::Discrete,
+            value if value.eq_ignore_ascii_case("integrated") => Self::Integrated,
+            value => value
+                .parse()
+                .map(Self::ByIndex)
+                .unwrap_or(Self::ByName(value)),
+        }
+    }
+}
+
 pub struct InstanceBuilder<'a> {
     window: Option<&'a Window>,
     app_name: Option<&'a str>,
@@ -43,6 +159,10 @@ pub struct InstanceBuilder<'a> {
     engine_version: u32,
     vulkan_api_version: VulkanApiVersion,
     debug_strategy: VulkanDebugInfoStrategy,
+    shader_printf: bool,
+    validation_features: ValidationFeatures,
+    device_preference: DevicePreference<'a>,
+    api_dump: Option<ApiDump>,
 }
 
 impl<'a> Default for InstanceBuilder<'a> {
@@ -55,6 +175,10 @@ impl<'a> Default for InstanceBuilder<'a> {
             engine_version: 0,
             vulkan_api_version: VulkanApiVersion::V1_0,
             debug_strategy: VulkanDebugInfoStrategy::Idle,
+            shader_printf: false,
+            validation_features: ValidationFeatures::default(),
+            device_preference: DevicePreference::Discrete,
+            api_dump: None,
         }
     }
 }
@@ -91,7 +215,46 @@ impl<'a> InstanceBuilder<'a> {
         self
     }
 
+    /// Turns on the validation layer's debugPrintf, so `debugPrintfEXT` calls in shaders
+    /// reach the debug callback as INFO messages named `WARNING-DEBUG-PRINTF`.
+    ///
+    /// Requires a debug strategy other than [`VulkanDebugInfoStrategy::Idle`] and a layer
+    /// providing `VK_EXT_validation_features`, see [`Instance::shader_printf_enabled`].
+    /// The layer settings are chosen here instead of in `vk_layer_settings.txt`:
+    /// debugPrintf replaces GPU-assisted validation, and output goes to the callback
+    /// unless `VK_LAYER_PRINTF_TO_STDOUT` is set. Long shader output may need a larger
+    /// `VK_LAYER_PRINTF_BUFFER_SIZE` than the layer's default of 1024 bytes.
+    pub fn shader_printf(mut self, enabled: bool) -> Self {
+        self.shader_printf = enabled;
+        self
+    }
+
+    /// Like [`Self::shader_printf`] these need a debug strategy other than
+    /// [`VulkanDebugInfoStrategy::Idle`]. The layer can't run GPU-assisted validation
+    /// alongside debugPrintf, so it is dropped when both are asked for.
+    pub fn validation_features(mut self, features: ValidationFeatures) -> Self {
+        self.validation_features = features;
+        self
+    }
+
+    /// Overridden by [`DEVICE_PREFERENCE_ENV`] when it is set, so users of hybrid
+    /// laptops can choose without rebuilding
+    pub fn prefer_device(mut self, preference: DevicePreference<'a>) -> Self {
+        self.device_preference = preference;
+        self
+    }
+
+    /// Enables `VK_LAYER_LUNARG_api_dump` ahead of any validation layer, so the file
+    /// holds exactly the calls the application makes. Instance creation fails with
+    /// `ERROR_LAYER_NOT_PRESENT` if the layer is not installed.
+    pub fn api_dump(mut self, api_dump: Option<ApiDump>) -> Self {
+        self.api_dump = api_dump;
+        self
+    }
+
     pub fn build(&self) -> RenderResult<Instance> {
+        let entry = Entry::linked();
+
         let (mut extensions, support_window) = if let Some(window) = self.window {
             (
                 ash_window::enumerate_required_extensions(window.raw_display_handle())?.to_vec(),
@@ -110,10 +273,59 @@ impl<'a> InstanceBuilder<'a> {
         .into_iter()
         .for_each(|x| extensions.push(x));
 
-        match self.debug_strategy {
-            VulkanDebugInfoStrategy::Idle => {}
-            _ => extensions.push(DebugUtils::name().as_ptr()),
+        let available_extensions = entry.enumerate_instance_extension_properties(None)?;
+        let extension_available = |name: &CStr| {
+            available_extensions
+                .iter()
+                .any(|property| unsafe { CStr::from_ptr(property.extension_name.as_ptr()) } == name)
+        };
+
+        // Needed by device extensions such as `VK_KHR_sampler_ycbcr_conversion` on Vulkan 1.0
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        if extension_available(vk::KhrGetPhysicalDeviceProperties2Fn::name()) {
+            extensions.push(vk::KhrGetPhysicalDeviceProperties2Fn::name().as_ptr());
+        }
+        let physical_device_properties2_enabled = extensions.iter().any(|&name| unsafe {
+            CStr::from_ptr(name) == vk::KhrGetPhysicalDeviceProperties2Fn::name()
+        });
+
+        // Without it surfaces only report sRGB color spaces, hiding HDR capable displays
+        let swapchain_colorspace_enabled =
+            support_window && extension_available(vk::ExtSwapchainColorspaceFn::name());
+        if swapchain_colorspace_enabled {
+            extensions.push(vk::ExtSwapchainColorspaceFn::name().as_ptr());
+        }
+
+        // Also enabled without validation so that profilers such as Nsight or PIX
+        // see the labels of queues and command buffers
+        let debug_utils_enabled = !matches!(self.debug_strategy, VulkanDebugInfoStrategy::Idle)
+            || extension_available(DebugUtils::name());
+        if debug_utils_enabled {
+            extensions.push(DebugUtils::name().as_ptr());
+        }
+
+        // `VK_EXT_validation_features` is provided by the validation layer itself
+        let validation_features_enabled = (self.shader_printf
+            || !self.validation_features.is_empty())
+            && !matches!(self.debug_strategy, VulkanDebugInfoStrategy::Idle)
+            && entry
+                .enumerate_instance_extension_properties(Some(VALIDATION_LAYER_NAME))?
+                .iter()
+                .any(|property| unsafe {
+                    CStr::from_ptr(property.extension_name.as_ptr())
+                        == vk::ExtValidationFeaturesFn::name()
+                });
+        if validation_features_enabled {
+            extensions.push(vk::ExtValidationFeaturesFn::name().as_ptr());
         }
+        let shader_printf_enabled = validation_features_enabled && self.shader_printf;
+        let validation_features = match validation_features_enabled {
+            true => ValidationFeatures {
+                gpu_assisted: self.validation_features.gpu_assisted && !shader_printf_enabled,
+                ..self.validation_features
+            },
+            false => ValidationFeatures::default(),
+        };
 
         let app_info = vk::ApplicationInfo::builder()
             .application_name(&CString::new(self.app_name.unwrap_or("")).unwrap())
@@ -128,31 +340,69 @@ impl<'a> InstanceBuilder<'a> {
             })
             .build();
 
-        let layer_names: Vec<*const c_char> = match self.debug_strategy {
-            VulkanDebugInfoStrategy::Idle => vec![],
-            _ => vec![VALIDATION_LAYER_NAME.as_ptr()],
-        };
+        let mut layer_names: Vec<*const c_char> = vec![];
+        if let Some(api_dump) = self.api_dump.as_ref() {
+            api_dump.export_layer_settings();
+            layer_names.push(API_DUMP_LAYER_NAME.as_ptr());
+        }
+        match self.debug_strategy {
+            VulkanDebugInfoStrategy::Idle => {}
+            _ => layer_names.push(VALIDATION_LAYER_NAME.as_ptr()),
+        }
 
         #[cfg(any(target_os = "macos", target_os = "ios"))]
         let create_flags = vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
         #[cfg(not(any(target_os = "macos", target_os = "ios")))]
         let create_flags = vk::InstanceCreateFlags::default();
 
-        let instance_create_info = vk::InstanceCreateInfo::builder()
+        let enabled_validation_features = [
+            (
+                shader_printf_enabled,
+                vk::ValidationFeatureEnableEXT::DEBUG_PRINTF,
+            ),
+            (
+                validation_features.gpu_assisted,
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+            ),
+            // Keeps the last descriptor set slot free for the instrumented shaders
+            (
+                validation_features.gpu_assisted,
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+            ),
+            (
+                validation_features.synchronization,
+                vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION,
+            ),
+            (
+                validation_features.best_practices,
+                vk::ValidationFeatureEnableEXT::BEST_PRACTICES,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, feature)| enabled.then_some(feature))
+        .collect::<Vec<_>>();
+        let mut validation_features_ext = vk::ValidationFeaturesEXT::builder()
+            .enabled_validation_features(&enabled_validation_features)
+            .build();
+
+        let mut instance_create_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
             .enabled_extension_names(&extensions)
             .enabled_layer_names(&layer_names)
-            .flags(create_flags)
-            .build();
+            .flags(create_flags);
+        if validation_features_enabled {
+            instance_create_info = instance_create_info.push_next(&mut validation_features_ext);
+        }
+        let instance_create_info = instance_create_info.build();
 
-        let entry = Entry::linked();
         let vk_instance = unsafe { entry.create_instance(&instance_create_info, None)? };
 
-        let debug_worker = match self.debug_strategy {
+        let debug_utils = debug_utils_enabled.then(|| DebugUtils::new(&entry, &vk_instance));
+        let debug_messenger = match self.debug_strategy {
             VulkanDebugInfoStrategy::Idle => None,
             VulkanDebugInfoStrategy::PrintAll(p_fn)
             | VulkanDebugInfoStrategy::PanicOnErrorsPrintOthers(p_fn) => {
-                let debug_utils_loader = DebugUtils::new(&entry, &vk_instance);
+                let debug_utils_loader = debug_utils.as_ref().unwrap();
                 let messenger_create_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
                     .message_severity(
                         vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
@@ -169,36 +419,57 @@ impl<'a> InstanceBuilder<'a> {
                 let debug_messenger = unsafe {
                     debug_utils_loader.create_debug_utils_messenger(&messenger_create_info, None)?
                 };
-                Some((debug_utils_loader, debug_messenger))
+                Some(debug_messenger)
             }
         };
 
         let physical_devices = {
             let devices = unsafe { vk_instance.enumerate_physical_devices()? };
-            let mut collection = PhysicalDeviceCollection::default();
-            for device in devices {
-                let property = unsafe { vk_instance.get_physical_device_properties(device) };
-                if property.device_type == vk::PhysicalDeviceType::DISCRETE_GPU {
-                    collection.discrete.push(Rc::new(device));
-                } else if property.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU {
-                    collection.integrated.push(Rc::new(device));
-                } else if property.device_type == vk::PhysicalDeviceType::CPU {
-                    collection.cpu.push(Rc::new(device));
-                }
-            }
-            if collection.is_empty() {
+            let mut collection = PhysicalDeviceCollection {
+                devices: devices
+                    .into_iter()
+                    .filter_map(|device| {
+                        let property =
+                            unsafe { vk_instance.get_physical_device_properties(device) };
+                        matches!(
+                            property.device_type,
+                            vk::PhysicalDeviceType::DISCRETE_GPU
+                                | vk::PhysicalDeviceType::INTEGRATED_GPU
+                                | vk::PhysicalDeviceType::CPU
+                        )
+                        .then(|| PhysicalDeviceEntry {
+                            handle: Rc::new(device),
+                            device_type: property.device_type,
+                            name: unsafe { CStr::from_ptr(property.device_name.as_ptr()) }
+                                .to_string_lossy()
+                                .into_owned(),
+                        })
+                    })
+                    .collect(),
+                picked: 0,
+            };
+            if collection.devices.is_empty() {
                 return Err(RenderError::PhysicalDeviceNotSupported(
                     "Fail to find available physical device".to_string(),
                 ));
             }
 
+            let env_preference = std::env::var(DEVICE_PREFERENCE_ENV).ok();
+            collection.pick(
+                env_preference
+                    .as_deref()
+                    .map(DevicePreference::parse)
+                    .unwrap_or(self.device_preference),
+            )?;
+
             collection
         };
 
         Ok(Instance {
             inner: vk_instance,
             entry,
-            debug_worker,
+            debug_utils,
+            debug_messenger,
             physical_devices,
             app_name_and_version: self
                 .app_name
@@ -208,6 +479,10 @@ impl<'a> InstanceBuilder<'a> {
                 .map(|name| (name.to_string(), self.engine_version)),
             vulkan_api_version: self.vulkan_api_version,
             support_window,
+            physical_device_properties2_enabled,
+            swapchain_colorspace_enabled,
+            shader_printf_enabled,
+            validation_features,
         })
     }
 }
@@ -218,9 +493,14 @@ pub struct Instance {
     physical_devices: PhysicalDeviceCollection,
     app_name_and_version: Option<(String, u32)>,
     engine_name_and_version: Option<(String, u32)>,
-    debug_worker: Option<(DebugUtils, vk::DebugUtilsMessengerEXT)>,
+    debug_utils: Option<DebugUtils>,
+    debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
     vulkan_api_version: VulkanApiVersion,
     support_window: bool,
+    physical_device_properties2_enabled: bool,
+    swapchain_colorspace_enabled: bool,
+    shader_printf_enabled: bool,
+    validation_features: ValidationFeatures,
 }
 
 impl Instance {
@@ -232,8 +512,10 @@ impl Instance {
         &self.entry
     }
 
+    /// The physical device chosen by [`InstanceBuilder::prefer_device`] or the
+    /// `VULKAN_EXAMPLES_DEVICE` environment variable
     pub fn pick_physical_device(&self) -> Weak<vk::PhysicalDevice> {
-        Rc::downgrade(&self.physical_devices.pick_first().unwrap())
+        Rc::downgrade(&self.physical_devices.picked())
     }
 
     pub fn app_name_and_version(&self) -> &Option<(String, u32)> {
@@ -245,7 +527,12 @@ impl Instance {
     }
 
     pub fn validation_layer_enabled(&self) -> bool {
-        self.debug_worker.is_some()
+        self.debug_messenger.is_some()
+    }
+
+    /// Loader of `VK_EXT_debug_utils`, enabled whenever the extension is available
+    pub fn debug_utils(&self) -> Option<&DebugUtils> {
+        self.debug_utils.as_ref()
     }
 
     pub fn vulkan_api_version(&self) -> VulkanApiVersion {
@@ -255,6 +542,26 @@ impl Instance {
     pub fn support_window(&self) -> bool {
         self.support_window
     }
+
+    /// Whether `VK_KHR_get_physical_device_properties2` is enabled on this instance
+    pub fn physical_device_properties2_enabled(&self) -> bool {
+        self.physical_device_properties2_enabled
+    }
+
+    /// Whether `VK_EXT_swapchain_colorspace` is enabled on this instance
+    pub fn swapchain_colorspace_enabled(&self) -> bool {
+        self.swapchain_colorspace_enabled
+    }
+
+    /// Whether the validation layer's debugPrintf is enabled on this instance
+    pub fn shader_printf_enabled(&self) -> bool {
+        self.shader_printf_enabled
+    }
+
+    /// The optional validation checks actually enabled on this instance
+    pub fn validation_features(&self) -> ValidationFeatures {
+        self.validation_features
+    }
 }
 
 impl Deref for Instance {
@@ -268,40 +575,76 @@ impl Drop for Instance {
     fn drop(&mut self) {
         self.physical_devices.check_can_be_freed();
         unsafe {
-            if let Some((debug_utils, debug_messenger)) = self.debug_worker.as_ref() {
-                debug_utils.destroy_debug_utils_messenger(*debug_messenger, None)
+            if let (Some(debug_utils), Some(debug_messenger)) =
+                (self.debug_utils.as_ref(), self.debug_messenger)
+            {
+                debug_utils.destroy_debug_utils_messenger(debug_messenger, None)
             }
             self.destroy_instance(None);
         }
     }
 }
 
-#[derive(Default)]
+/// Physical devices of the supported types, in the order the driver enumerates them
 struct PhysicalDeviceCollection {
-    discrete: Vec<Rc<vk::PhysicalDevice>>,
-    integrated: Vec<Rc<vk::PhysicalDevice>>,
-    cpu: Vec<Rc<vk::PhysicalDevice>>,
+    devices: Vec<PhysicalDeviceEntry>,
+    picked: usize,
+}
+
+struct PhysicalDeviceEntry {
+    handle: Rc<vk::PhysicalDevice>,
+    device_type: vk::PhysicalDeviceType,
+    name: String,
 }
 
 impl PhysicalDeviceCollection {
-    fn is_empty(&self) -> bool {
-        self.discrete.is_empty() && self.integrated.is_empty() && self.cpu.is_empty()
+    fn pick(&mut self, preference: DevicePreference) -> RenderResult<()> {
+        let first_of_type = |device_type| {
+            self.devices
+                .iter()
+                .position(|device| device.device_type == device_type)
+        };
+        let picked = match preference {
+            DevicePreference::Discrete => first_of_type(vk::PhysicalDeviceType::DISCRETE_GPU)
+                .or_else(|| first_of_type(vk::PhysicalDeviceType::INTEGRATED_GPU)),
+            DevicePreference::Integrated => first_of_type(vk::PhysicalDeviceType::INTEGRATED_GPU)
+                .or_else(|| first_of_type(vk::PhysicalDeviceType::DISCRETE_GPU)),
+            DevicePreference::ByName(name) => {
+                let name = name.to_lowercase();
+                self.devices
+                    .iter()
+                    .position(|device| device.name.to_lowercase().contains(&name))
+            }
+            DevicePreference::ByIndex(index) => (index < self.devices.len()).then_some(index),
+        };
+        let picked = match preference {
+            DevicePreference::Discrete | DevicePreference::Integrated => {
+                picked.or_else(|| first_of_type(vk::PhysicalDeviceType::CPU))
+            }
+            _ => picked,
+        };
+        self.picked = picked.ok_or_else(|| {
+            RenderError::PhysicalDeviceNotSupported(format!(
+                "No physical device matches {preference:?}, available: {}",
+                self.devices
+                    .iter()
+                    .enumerate()
+                    .map(|(index, device)| format!("[{index}] {}", device.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+        Ok(())
     }
 
-    fn pick_first(&self) -> Option<Rc<vk::PhysicalDevice>> {
-        self.chained_iter().next().map(Clone::clone)
+    fn picked(&self) -> Rc<vk::PhysicalDevice> {
+        self.devices[self.picked].handle.clone()
     }
 
     fn check_can_be_freed(&self) {
-        self.chained_iter()
-            .for_each(|pd| assert!(Rc::strong_count(pd) == 1));
-    }
-
-    fn chained_iter(&self) -> impl Iterator<Item = &Rc<vk::PhysicalDevice>> {
-        self.discrete
+        self.devices
             .iter()
-            .chain(self.integrated.iter())
-            .chain(self.cpu.iter())
+            .for_each(|device| assert!(Rc::strong_count(&device.handle) == 1));
     }
 }
 
@@ -326,9 +669,21 @@ unsafe extern "system" fn vulkan_debug_callback_print_all(
         CStr::from_ptr(callback_data.p_message).to_string_lossy()
     };
 
-    println!(
-        "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n",
-    );
+    if let Some(output) = shader_printf_output(&message_id_name, &message) {
+        report(
+            Level::Info,
+            "shader printf",
+            format_args!("shader printf: {output}"),
+        );
+    } else {
+        report(
+            severity_level(message_severity),
+            "vulkan",
+            format_args!(
+                "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n",
+            ),
+        );
+    }
 
     vk::FALSE
 }
@@ -354,15 +709,42 @@ unsafe extern "system" fn vulkan_debug_callback_panic_on_errors_print_others(
         CStr::from_ptr(callback_data.p_message).to_string_lossy()
     };
 
-    if message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
+    if let Some(output) = shader_printf_output(&message_id_name, &message) {
+        report(
+            Level::Info,
+            "shader printf",
+            format_args!("shader printf: {output}"),
+        );
+    } else if message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
         panic!(
             "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n",
         );
     } else {
-        println!(
-            "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n",
+        report(
+            severity_level(message_severity),
+            "vulkan",
+            format_args!(
+                "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n",
+            ),
         );
     }
 
     vk::FALSE
 }
+
+fn severity_level(severity: vk::DebugUtilsMessageSeverityFlagsEXT) -> Level {
+    match severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => Level::Error,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => Level::Info,
+        _ => Level::Trace,
+    }
+}
+
+/// The text a shader printed, without the object and message id preamble the layer
+/// puts in front of it, separated by `|`
+fn shader_printf_output<'a>(message_id_name: &str, message: &'a str) -> Option<&'a str> {
+    message_id_name
+        .contains("DEBUG-PRINTF")
+        .then(|| message.rsplit('|').next().unwrap_or(message).trim())
+}
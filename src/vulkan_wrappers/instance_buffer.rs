@@ -0,0 +1,75 @@
+use std::rc::Rc;
+
+use ash::vk;
+
+use super::{Buffer, Device};
+use crate::error::RenderResult;
+
+/// Per-instance vertex data, bound to a binding stepped with [`vk::VertexInputRate::INSTANCE`],
+/// see [`crate::mesh::VertexLayout::with_instances`]. Instances written every frame need
+/// one buffer per frame in flight, static ones are uploaded once with [`Self::from_data`].
+pub struct InstanceBuffer<T> {
+    buffer: Buffer<T>,
+    len: usize,
+}
+
+impl<T> InstanceBuffer<T> {
+    /// Host writable and persistently mapped, filled with [`Self::write`]
+    pub fn new(capacity: usize, device: Rc<Device>) -> RenderResult<Self> {
+        let mut buffer = Buffer::new(
+            capacity,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            device.host_write_memory_properties(),
+            device,
+        )?;
+        buffer.map_memory_all()?;
+        Ok(Self { buffer, len: 0 })
+    }
+
+    /// Device local, the instances can not be written afterwards
+    pub fn from_data(
+        data: &[T],
+        device: Rc<Device>,
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<Self> {
+        Ok(Self {
+            buffer: Buffer::device_local_vertex(data, device, command_pool, queue)?,
+            len: data.len(),
+        })
+    }
+
+    /// Replaces the instances, `data` must fit in [`Self::capacity`]
+    pub fn write(&mut self, data: &[T]) {
+        assert!(
+            self.buffer.is_mapped(),
+            "Instances uploaded with from_data can not be written"
+        );
+        assert!(data.len() <= self.capacity());
+        self.buffer.load_data_when_mapped(data, 0);
+        self.len = data.len();
+    }
+
+    /// Instance count of the last write, to pass to the draw command
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.element_num()
+    }
+
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    pub fn cmd_bind(&self, device: &Device, command_buffer: vk::CommandBuffer, binding: u32) {
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, binding, &[self.buffer.buffer()], &[0])
+        };
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use ash::vk;
+
+/// Live allocations of one memory type made through [`super::Device::allocate_tracked_memory`]
+#[derive(Clone, Copy, Default)]
+pub struct MemoryTypeUsage {
+    pub bytes: vk::DeviceSize,
+    pub allocations: u32,
+    /// Bytes backing what the GPU reads every frame, vertex, index, storage and indirect
+    /// buffers or sampled and storage images, which belong in `DEVICE_LOCAL` memory
+    pub hot_bytes: vk::DeviceSize,
+}
+
+impl MemoryTypeUsage {
+    pub fn is_hot_buffer(usage: vk::BufferUsageFlags) -> bool {
+        usage.intersects(
+            vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::INDEX_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER,
+        )
+    }
+
+    pub fn is_hot_image(usage: vk::ImageUsageFlags) -> bool {
+        usage.intersects(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE)
+    }
+}
+
+struct TrackedAllocation {
+    memory_type_index: u32,
+    size: vk::DeviceSize,
+    hot: bool,
+}
+
+#[derive(Default)]
+pub(super) struct MemoryTracker {
+    usage: [MemoryTypeUsage; vk::MAX_MEMORY_TYPES],
+    allocations: HashMap<vk::DeviceMemory, TrackedAllocation>,
+}
+
+impl MemoryTracker {
+    pub(super) fn allocated(
+        &mut self,
+        memory: vk::DeviceMemory,
+        allocate_info: &vk::MemoryAllocateInfo,
+        hot: bool,
+    ) {
+        let usage = &mut self.usage[allocate_info.memory_type_index as usize];
+        usage.bytes += allocate_info.allocation_size;
+        usage.allocations += 1;
+        if hot {
+            usage.hot_bytes += allocate_info.allocation_size;
+        }
+        self.allocations.insert(
+            memory,
+            TrackedAllocation {
+                memory_type_index: allocate_info.memory_type_index,
+                size: allocate_info.allocation_size,
+                hot,
+            },
+        );
+    }
+
+    pub(super) fn freed(&mut self, memory: vk::DeviceMemory) {
+        if let Some(allocation) = self.allocations.remove(&memory) {
+            let usage = &mut self.usage[allocation.memory_type_index as usize];
+            usage.bytes -= allocation.size;
+            usage.allocations -= 1;
+            if allocation.hot {
+                usage.hot_bytes -= allocation.size;
+            }
+        }
+    }
+
+    pub(super) fn usage(&self) -> &[MemoryTypeUsage] {
+        &self.usage
+    }
+}
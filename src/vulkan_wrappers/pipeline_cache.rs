@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use ash::{prelude::VkResult, vk};
+
+use super::Device;
+use crate::error::RenderResult;
+
+/// Size of `VkPipelineCacheHeaderVersionOne`, which starts every pipeline cache blob
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// One cache file per GPU model, in the temporary directory
+pub fn pipeline_cache_path(device: &Device) -> PathBuf {
+    let properties = physical_device_properties(device);
+    std::env::temp_dir()
+        .join("vulkan-examples-rs")
+        .join(format!(
+            "pipeline_cache_{:04x}_{:04x}.bin",
+            properties.vendor_id, properties.device_id
+        ))
+}
+
+/// Creates a pipeline cache seeded from `path`. A missing or unreadable file, or one
+/// written by another device or driver version, leaves the cache empty.
+pub fn load_pipeline_cache(path: &Path, device: &Device) -> VkResult<vk::PipelineCache> {
+    let initial_data = std::fs::read(path)
+        .ok()
+        .filter(|data| header_matches(data, &physical_device_properties(device)));
+    if let Some(initial_data) = initial_data {
+        let create_info = vk::PipelineCacheCreateInfo::builder()
+            .initial_data(&initial_data)
+            .build();
+        // Drivers may still reject the blob, e.g. if the file was truncated
+        if let Ok(pipeline_cache) = unsafe { device.create_pipeline_cache(&create_info, None) } {
+            return Ok(pipeline_cache);
+        }
+    }
+    unsafe { device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None) }
+}
+
+/// Writes the cache next to `path` first and then renames it, so an interrupted
+/// save never leaves a partial file behind
+pub fn save_pipeline_cache(
+    pipeline_cache: vk::PipelineCache,
+    path: &Path,
+    device: &Device,
+) -> RenderResult<()> {
+    let data = unsafe { device.get_pipeline_cache_data(pipeline_cache)? };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temporary_path = path.with_extension("tmp");
+    std::fs::write(&temporary_path, data)?;
+    std::fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+fn physical_device_properties(device: &Device) -> vk::PhysicalDeviceProperties {
+    unsafe {
+        device
+            .instance()
+            .get_physical_device_properties(*device.physical_device().upgrade().unwrap())
+    }
+}
+
+/// The header fields are stored least significant byte first on every host
+fn header_matches(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    let word =
+        |index: usize| u32::from_le_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+    word(0) as usize >= HEADER_SIZE
+        && word(1) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        && word(2) == properties.vendor_id
+        && word(3) == properties.device_id
+        && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+}
@@ -0,0 +1,380 @@
+use std::{ffi::CStr, fmt::Display, path::Path};
+
+use ash::vk;
+use serde_json::Value;
+
+use super::{device::feature_supported, Device};
+use crate::error::{RenderError, RenderResult};
+
+/// Requirements of a Vulkan profile which a physical device is checked against.
+///
+/// Only the API version, device extensions, core `VkPhysicalDeviceFeatures` and the
+/// limits of `VkPhysicalDeviceProperties` are checked. Other feature and property
+/// structs of a JSON profile are listed as unchecked in the [`ProfileReport`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    /// Minimum `(major, minor)` API version of the physical device
+    pub api_version: (u32, u32),
+    pub extensions: Vec<String>,
+    /// Members of `VkPhysicalDeviceFeatures`, e.g. `samplerAnisotropy`
+    pub features: Vec<String>,
+    /// Members of `VkPhysicalDeviceLimits` and their required value per component.
+    /// `max*` limits are minimums, `*Alignment` and `*Granularity` limits are maximums.
+    pub limits: Vec<(String, Vec<f64>)>,
+    /// Requirements read from a JSON profile which are not checked
+    pub unchecked: Vec<String>,
+}
+
+impl Profile {
+    /// Core requirements of `VP_KHR_roadmap_2022`
+    pub fn roadmap_2022() -> Self {
+        let limit = |name: &str, values: &[f64]| (name.to_string(), values.to_vec());
+        Self {
+            name: "VP_KHR_roadmap_2022".to_string(),
+            api_version: (1, 3),
+            extensions: vec!["VK_KHR_global_priority".to_string()],
+            features: [
+                "fullDrawIndexUint32",
+                "imageCubeArray",
+                "independentBlend",
+                "sampleRateShading",
+                "drawIndirectFirstInstance",
+                "depthClamp",
+                "depthBiasClamp",
+                "samplerAnisotropy",
+                "occlusionQueryPrecise",
+                "fragmentStoresAndAtomics",
+                "shaderStorageImageExtendedFormats",
+                "shaderUniformBufferArrayDynamicIndexing",
+                "shaderSampledImageArrayDynamicIndexing",
+                "shaderStorageBufferArrayDynamicIndexing",
+                "shaderStorageImageArrayDynamicIndexing",
+                "shaderImageGatherExtended",
+                "shaderInt16",
+                "largePoints",
+            ]
+            .map(String::from)
+            .to_vec(),
+            limits: vec![
+                limit("maxImageDimension1D", &[8192.]),
+                limit("maxImageDimension2D", &[8192.]),
+                limit("maxImageDimensionCube", &[8192.]),
+                limit("maxImageArrayLayers", &[2048.]),
+                limit("maxUniformBufferRange", &[65536.]),
+                limit("maxPerStageDescriptorSamplers", &[64.]),
+                limit("maxPerStageDescriptorUniformBuffers", &[15.]),
+                limit("maxPerStageDescriptorStorageBuffers", &[30.]),
+                limit("maxPerStageDescriptorSampledImages", &[200.]),
+                limit("maxPerStageDescriptorStorageImages", &[16.]),
+                limit("maxPerStageResources", &[200.]),
+                limit("maxDescriptorSetSamplers", &[576.]),
+                limit("maxDescriptorSetUniformBuffers", &[90.]),
+                limit("maxDescriptorSetStorageBuffers", &[96.]),
+                limit("maxDescriptorSetSampledImages", &[1800.]),
+                limit("maxDescriptorSetStorageImages", &[144.]),
+                limit("maxFragmentCombinedOutputResources", &[16.]),
+                limit("maxComputeWorkGroupInvocations", &[256.]),
+                limit("maxComputeWorkGroupSize", &[256., 256., 64.]),
+                limit("maxSamplerLodBias", &[14.]),
+                limit("maxColorAttachments", &[7.]),
+            ],
+            unchecked: vec![],
+        }
+    }
+
+    /// Reads a profile from a Khronos profiles JSON file, `name` picks one of its
+    /// `profiles`, the first one is used otherwise. When a capability lists
+    /// alternatives, the first alternative is required.
+    pub fn from_json_file(path: impl AsRef<Path>, name: Option<&str>) -> RenderResult<Self> {
+        let invalid = |reason: String| {
+            RenderError::ProfileInvalid(format!("{}: {reason}", path.as_ref().display()))
+        };
+        let root: Value = serde_json::from_slice(&std::fs::read(path.as_ref())?)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        let profiles = root["profiles"]
+            .as_object()
+            .ok_or_else(|| invalid("no profiles".to_string()))?;
+        let (profile_name, profile) = match name {
+            Some(name) => profiles.get_key_value(name),
+            None => profiles.iter().next(),
+        }
+        .ok_or_else(|| invalid(format!("no profile named {name:?}")))?;
+
+        let api_version = profile["api-version"]
+            .as_str()
+            .and_then(|version| {
+                let mut numbers = version.split('.').map(str::parse::<u32>);
+                Some((numbers.next()?.ok()?, numbers.next()?.ok()?))
+            })
+            .unwrap_or((1, 0));
+
+        let mut result = Self {
+            name: profile_name.clone(),
+            api_version,
+            extensions: vec![],
+            features: vec![],
+            limits: vec![],
+            unchecked: vec![],
+        };
+        let capability_names = profile["capabilities"].as_array().into_iter().flatten();
+        for capability_name in capability_names {
+            let capability_name = match capability_name {
+                Value::Array(alternatives) => alternatives.first().and_then(Value::as_str),
+                name => name.as_str(),
+            }
+            .ok_or_else(|| invalid("malformed capability list".to_string()))?;
+            let capability = &root["capabilities"][capability_name];
+            if capability.is_null() {
+                return Err(invalid(format!("undefined capability {capability_name}")));
+            }
+            result.add_capability(capability);
+        }
+        Ok(result)
+    }
+
+    /// Picks `--profile=roadmap-2022` or `--profile=<profiles JSON file>`
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> RenderResult<Option<Self>> {
+        let Some(profile) = args
+            .into_iter()
+            .find_map(|arg| arg.strip_prefix("--profile=").map(String::from))
+        else {
+            return Ok(None);
+        };
+        match profile.as_str() {
+            "roadmap-2022" => Ok(Some(Self::roadmap_2022())),
+            path => Self::from_json_file(path, None).map(Some),
+        }
+    }
+
+    fn add_capability(&mut self, capability: &Value) {
+        for extension in capability["extensions"].as_object().into_iter().flatten() {
+            self.extensions.push(extension.0.clone());
+        }
+        for (struct_name, members) in capability["features"].as_object().into_iter().flatten() {
+            if struct_name == "VkPhysicalDeviceFeatures" {
+                self.features.extend(
+                    members
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .filter(|(_, enabled)| enabled.as_bool() == Some(true))
+                        .map(|(name, _)| name.clone()),
+                );
+            } else {
+                self.unchecked.push(struct_name.clone());
+            }
+        }
+        for (struct_name, members) in capability["properties"].as_object().into_iter().flatten() {
+            if struct_name != "VkPhysicalDeviceProperties" {
+                self.unchecked.push(struct_name.clone());
+                continue;
+            }
+            for (name, value) in members["limits"].as_object().into_iter().flatten() {
+                let values = match value {
+                    Value::Array(values) => values.iter().filter_map(Value::as_f64).collect(),
+                    value => value.as_f64().into_iter().collect(),
+                };
+                self.limits.push((name.clone(), values));
+            }
+        }
+        if capability.get("formats").is_some() {
+            self.unchecked.push("formats".to_string());
+        }
+    }
+
+    /// Compares the physical device of `device` with the profile
+    pub fn check(&self, device: &Device) -> RenderResult<ProfileReport> {
+        let instance = device.instance();
+        let physical_device = *device.physical_device().upgrade().unwrap();
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let features = unsafe { instance.get_physical_device_features(physical_device) };
+        let available_extensions =
+            unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+
+        let mut missing = vec![];
+        let api_version = (
+            vk::api_version_major(properties.api_version),
+            vk::api_version_minor(properties.api_version),
+        );
+        if api_version < self.api_version {
+            missing.push(format!(
+                "Vulkan {}.{} (device has {}.{})",
+                self.api_version.0, self.api_version.1, api_version.0, api_version.1
+            ));
+        }
+        for extension in &self.extensions {
+            if !available_extensions.iter().any(|property| {
+                unsafe { CStr::from_ptr(property.extension_name.as_ptr()) }.to_bytes()
+                    == extension.as_bytes()
+            }) {
+                missing.push(format!("extension {extension}"));
+            }
+        }
+        let mut unchecked = self.unchecked.clone();
+        for feature in &self.features {
+            match feature_supported(&features, feature) {
+                Some(true) => {}
+                Some(false) => missing.push(format!("feature {feature}")),
+                None => unchecked.push(format!("feature {feature}")),
+            }
+        }
+        for (name, required) in &self.limits {
+            let Some(actual) = limit(&properties.limits, name) else {
+                unchecked.push(format!("limit {name}"));
+                continue;
+            };
+            let is_maximum = name.ends_with("Alignment") || name.ends_with("Granularity");
+            let satisfied =
+                actual
+                    .iter()
+                    .zip(required)
+                    .all(|(&actual, &required)| match is_maximum {
+                        true => actual <= required,
+                        false => actual >= required,
+                    });
+            if !satisfied {
+                missing.push(format!("limit {name} {required:?} (device has {actual:?})"));
+            }
+        }
+
+        Ok(ProfileReport {
+            profile: self.name.clone(),
+            device: device.physical_device_name().to_string(),
+            missing,
+            unchecked,
+        })
+    }
+}
+
+/// Outcome of [`Profile::check`]
+#[derive(Clone, Debug)]
+pub struct ProfileReport {
+    pub profile: String,
+    pub device: String,
+    pub missing: Vec<String>,
+    pub unchecked: Vec<String>,
+}
+
+impl ProfileReport {
+    pub fn is_satisfied(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_satisfied() {
+            true => write!(f, "{} satisfies {}", self.device, self.profile)?,
+            false => {
+                write!(
+                    f,
+                    "{} does not satisfy {}, missing:",
+                    self.device, self.profile
+                )?;
+                for missing in &self.missing {
+                    write!(f, "\n  {missing}")?;
+                }
+            }
+        }
+        if !self.unchecked.is_empty() {
+            write!(f, "\nnot checked: {}", self.unchecked.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits the profiles commonly require, by their `VkPhysicalDeviceLimits` member name
+fn limit(limits: &vk::PhysicalDeviceLimits, name: &str) -> Option<Vec<f64>> {
+    let value = |value: u32| Some(vec![value as f64]);
+    match name {
+        "maxImageDimension1D" => value(limits.max_image_dimension1_d),
+        "maxImageDimension2D" => value(limits.max_image_dimension2_d),
+        "maxImageDimension3D" => value(limits.max_image_dimension3_d),
+        "maxImageDimensionCube" => value(limits.max_image_dimension_cube),
+        "maxImageArrayLayers" => value(limits.max_image_array_layers),
+        "maxTexelBufferElements" => value(limits.max_texel_buffer_elements),
+        "maxUniformBufferRange" => value(limits.max_uniform_buffer_range),
+        "maxStorageBufferRange" => value(limits.max_storage_buffer_range),
+        "maxPushConstantsSize" => value(limits.max_push_constants_size),
+        "maxMemoryAllocationCount" => value(limits.max_memory_allocation_count),
+        "maxSamplerAllocationCount" => value(limits.max_sampler_allocation_count),
+        "bufferImageGranularity" => Some(vec![limits.buffer_image_granularity as f64]),
+        "maxBoundDescriptorSets" => value(limits.max_bound_descriptor_sets),
+        "maxPerStageDescriptorSamplers" => value(limits.max_per_stage_descriptor_samplers),
+        "maxPerStageDescriptorUniformBuffers" => {
+            value(limits.max_per_stage_descriptor_uniform_buffers)
+        }
+        "maxPerStageDescriptorStorageBuffers" => {
+            value(limits.max_per_stage_descriptor_storage_buffers)
+        }
+        "maxPerStageDescriptorSampledImages" => {
+            value(limits.max_per_stage_descriptor_sampled_images)
+        }
+        "maxPerStageDescriptorStorageImages" => {
+            value(limits.max_per_stage_descriptor_storage_images)
+        }
+        "maxPerStageDescriptorInputAttachments" => {
+            value(limits.max_per_stage_descriptor_input_attachments)
+        }
+        "maxPerStageResources" => value(limits.max_per_stage_resources),
+        "maxDescriptorSetSamplers" => value(limits.max_descriptor_set_samplers),
+        "maxDescriptorSetUniformBuffers" => value(limits.max_descriptor_set_uniform_buffers),
+        "maxDescriptorSetUniformBuffersDynamic" => {
+            value(limits.max_descriptor_set_uniform_buffers_dynamic)
+        }
+        "maxDescriptorSetStorageBuffers" => value(limits.max_descriptor_set_storage_buffers),
+        "maxDescriptorSetStorageBuffersDynamic" => {
+            value(limits.max_descriptor_set_storage_buffers_dynamic)
+        }
+        "maxDescriptorSetSampledImages" => value(limits.max_descriptor_set_sampled_images),
+        "maxDescriptorSetStorageImages" => value(limits.max_descriptor_set_storage_images),
+        "maxDescriptorSetInputAttachments" => value(limits.max_descriptor_set_input_attachments),
+        "maxVertexInputAttributes" => value(limits.max_vertex_input_attributes),
+        "maxVertexInputBindings" => value(limits.max_vertex_input_bindings),
+        "maxVertexOutputComponents" => value(limits.max_vertex_output_components),
+        "maxFragmentInputComponents" => value(limits.max_fragment_input_components),
+        "maxFragmentOutputAttachments" => value(limits.max_fragment_output_attachments),
+        "maxFragmentCombinedOutputResources" => {
+            value(limits.max_fragment_combined_output_resources)
+        }
+        "maxComputeSharedMemorySize" => value(limits.max_compute_shared_memory_size),
+        "maxComputeWorkGroupCount" => Some(
+            limits
+                .max_compute_work_group_count
+                .map(|count| count as f64)
+                .to_vec(),
+        ),
+        "maxComputeWorkGroupInvocations" => value(limits.max_compute_work_group_invocations),
+        "maxComputeWorkGroupSize" => Some(
+            limits
+                .max_compute_work_group_size
+                .map(|size| size as f64)
+                .to_vec(),
+        ),
+        "maxSamplerLodBias" => Some(vec![limits.max_sampler_lod_bias as f64]),
+        "maxSamplerAnisotropy" => Some(vec![limits.max_sampler_anisotropy as f64]),
+        "maxViewports" => value(limits.max_viewports),
+        "maxViewportDimensions" => Some(
+            limits
+                .max_viewport_dimensions
+                .map(|dimension| dimension as f64)
+                .to_vec(),
+        ),
+        "maxFramebufferWidth" => value(limits.max_framebuffer_width),
+        "maxFramebufferHeight" => value(limits.max_framebuffer_height),
+        "maxFramebufferLayers" => value(limits.max_framebuffer_layers),
+        "maxColorAttachments" => value(limits.max_color_attachments),
+        "minUniformBufferOffsetAlignment" => {
+            Some(vec![limits.min_uniform_buffer_offset_alignment as f64])
+        }
+        "minStorageBufferOffsetAlignment" => {
+            Some(vec![limits.min_storage_buffer_offset_alignment as f64])
+        }
+        "minTexelBufferOffsetAlignment" => {
+            Some(vec![limits.min_texel_buffer_offset_alignment as f64])
+        }
+        _ => None,
+    }
+}
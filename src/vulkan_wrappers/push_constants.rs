@@ -0,0 +1,85 @@
+use std::marker::PhantomData;
+
+use ash::vk;
+
+use super::Device;
+use crate::error::{RenderError, RenderResult};
+
+/// Push constant block of type `T`, checked against `maxPushConstantsSize` once so
+/// that every [`Self::cmd_push`] only copies bytes. Its [`Self::range`] goes in
+/// [`crate::app::PipelineBuilder::push_constant_ranges`].
+#[derive(Clone, Copy, Debug)]
+pub struct PushConstants<T> {
+    stages: vk::ShaderStageFlags,
+    offset: u32,
+    _block: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> PushConstants<T> {
+    pub const SIZE: u32 = std::mem::size_of::<T>() as u32;
+
+    pub fn new(stages: vk::ShaderStageFlags, device: &Device) -> RenderResult<Self> {
+        Self::with_offset(stages, 0, device)
+    }
+
+    /// Placed after other blocks of the same pipeline layout, `offset` must be a
+    /// multiple of 4 like the size of `T`
+    pub fn with_offset(
+        stages: vk::ShaderStageFlags,
+        offset: u32,
+        device: &Device,
+    ) -> RenderResult<Self> {
+        assert!(
+            offset.is_multiple_of(4) && Self::SIZE.is_multiple_of(4),
+            "Push constant offset and size must be multiples of 4"
+        );
+        let max_size = device.capabilities().max_push_constants_size;
+        if offset + Self::SIZE > max_size {
+            return Err(RenderError::PhysicalDeviceNotSupported(format!(
+                "{} bytes of push constants at offset {offset} exceed maxPushConstantsSize {max_size}",
+                Self::SIZE
+            )));
+        }
+        Ok(Self {
+            stages,
+            offset,
+            _block: PhantomData,
+        })
+    }
+
+    pub fn range(&self) -> vk::PushConstantRange {
+        vk::PushConstantRange::builder()
+            .stage_flags(self.stages)
+            .offset(self.offset)
+            .size(Self::SIZE)
+            .build()
+    }
+
+    pub fn stages(&self) -> vk::ShaderStageFlags {
+        self.stages
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// `layout` must have been created with [`Self::range`]. Takes the `ash::Device` so
+    /// worker threads recording secondary command buffers can push too.
+    pub fn cmd_push(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        block: &T,
+    ) {
+        unsafe {
+            device.cmd_push_constants(
+                command_buffer,
+                layout,
+                self.stages,
+                self.offset,
+                bytemuck::bytes_of(block),
+            )
+        };
+    }
+}
@@ -1,12 +1,17 @@
-use ash::vk;
+use ash::{extensions::khr::GetPhysicalDeviceProperties2, vk};
 
-use super::Surface;
+use super::{Instance, Surface};
 use crate::error::{RenderError, RenderResult};
 
 #[derive(Default, Clone, Copy)]
 pub struct QueueInfo {
     pub graphic_family_index_priority: (u32, f32),
     pub present_family_index_priority: (u32, f32),
+    /// The graphic family when it supports compute, so that resources written by compute
+    /// shaders can be used for rendering without queue family ownership transfers
+    pub compute_family_index_priority: (u32, f32),
+    /// Queue family able to decode H.264, only used when the device enables video decoding
+    pub video_decode_family_index_priority: Option<(u32, f32)>,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -14,6 +19,110 @@ pub struct QueueState {
     pub info: QueueInfo,
     pub graphic_queue: vk::Queue,
     pub present_queue: vk::Queue,
+    pub compute_queue: vk::Queue,
+    pub video_decode_queue: Option<vk::Queue>,
+}
+
+/// Extra queue created along the ones of [`QueueInfo`], e.g. a low priority queue for
+/// uploads next to the graphic queue, see [`super::DeviceBuilder::queue_request`]
+#[derive(Clone, Copy, Debug)]
+pub struct QueueRequest {
+    /// Looked up with [`super::Device::named_queue`]
+    pub name: &'static str,
+    /// Capabilities of the family, `TRANSFER` is implied by `GRAPHICS` and `COMPUTE`
+    pub flags: vk::QueueFlags,
+    /// Within 0 and 1, only relative to the other queues of the device
+    pub priority: f32,
+}
+
+impl QueueRequest {
+    pub fn new(name: &'static str, flags: vk::QueueFlags, priority: f32) -> Self {
+        assert!((0. ..=1.).contains(&priority));
+        Self {
+            name,
+            flags,
+            priority,
+        }
+    }
+}
+
+/// Queue created for a [`QueueRequest`]
+#[derive(Clone, Copy, Debug)]
+pub struct NamedQueue {
+    pub name: &'static str,
+    pub queue: vk::Queue,
+    pub family_index: u32,
+    /// Index within the family, requests beyond the queue count of the family share its
+    /// last queue
+    pub queue_index: u32,
+    pub priority: f32,
+}
+
+pub(super) struct QueuePriorities {
+    /// Family index and the priorities of its queues
+    pub families: Vec<(u32, Vec<f32>)>,
+    /// Family and queue index of every request
+    pub placements: Vec<(u32, u32)>,
+}
+
+/// Priorities of the queues to create per family, the first queue of a family is the one
+/// of [`QueueInfo`] if any, followed by the requested ones
+pub(super) fn queue_create_priorities(
+    queue_info: &QueueInfo,
+    requests: &[QueueRequest],
+    family_properties: &[vk::QueueFamilyProperties],
+) -> RenderResult<QueuePriorities> {
+    let mut families = queue_info
+        .queue_create_family_index_and_priority()
+        .into_iter()
+        .map(|(index, priority)| (index, vec![priority]))
+        .collect::<Vec<_>>();
+    let supports = |family_index: u32, flags: vk::QueueFlags| {
+        let mut family_flags = family_properties[family_index as usize].queue_flags;
+        if family_flags.intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE) {
+            family_flags |= vk::QueueFlags::TRANSFER;
+        }
+        family_flags.contains(flags)
+    };
+
+    let mut placements = vec![];
+    for request in requests {
+        // The families of the existing queues come first, so that resources need no
+        // ownership transfer between the requested queue and them
+        let family_index = [
+            queue_info.graphic_family_index_priority.0,
+            queue_info.compute_family_index_priority.0,
+        ]
+        .into_iter()
+        .chain(0..family_properties.len() as u32)
+        .find(|&index| supports(index, request.flags))
+        .ok_or_else(|| {
+            RenderError::QueueFamilyNotSupported(format!(
+                "No queue family supports {:?} for the {} queue",
+                request.flags, request.name
+            ))
+        })?;
+        let queue_count = family_properties[family_index as usize].queue_count;
+        let priorities = match families
+            .iter_mut()
+            .find(|(index, _)| *index == family_index)
+        {
+            Some((_, priorities)) => priorities,
+            None => {
+                families.push((family_index, vec![]));
+                &mut families.last_mut().unwrap().1
+            }
+        };
+        if (priorities.len() as u32) < queue_count {
+            priorities.push(request.priority);
+        }
+        placements.push((family_index, priorities.len() as u32 - 1));
+    }
+
+    Ok(QueuePriorities {
+        families,
+        placements,
+    })
 }
 
 impl QueueInfo {
@@ -53,7 +162,11 @@ impl QueueInfo {
         }
 
         if graphic_ok && present_ok {
-            Ok(queue_info)
+            queue_info.find_compute_and_video_families(
+                surface.instance(),
+                *physical_device,
+                &family_properties,
+            )
         } else {
             Err(RenderError::QueueFamilyNotSupported(
                 "Fail to find suitable queue families".to_string(),
@@ -61,6 +174,55 @@ impl QueueInfo {
         }
     }
 
+    /// Queue families of a device without a surface, presentation goes to the graphic family
+    pub fn headless(instance: &Instance) -> RenderResult<Self> {
+        let physical_device = instance.pick_physical_device().upgrade().unwrap();
+        let family_properties =
+            unsafe { instance.get_physical_device_queue_family_properties(*physical_device) };
+        let graphic_family_index = family_properties
+            .iter()
+            .position(|fp| fp.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .ok_or_else(|| {
+                RenderError::QueueFamilyNotSupported("Fail to find a graphic queue family".into())
+            })? as u32;
+        let queue_info = QueueInfo {
+            graphic_family_index_priority: (graphic_family_index, 1.0),
+            present_family_index_priority: (graphic_family_index, 1.0),
+            ..Default::default()
+        };
+        queue_info.find_compute_and_video_families(instance, *physical_device, &family_properties)
+    }
+
+    fn find_compute_and_video_families(
+        mut self,
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        family_properties: &[vk::QueueFamilyProperties],
+    ) -> RenderResult<Self> {
+        let graphic_family_index = self.graphic_family_index_priority.0 as usize;
+        let compute_family_index = if family_properties
+            .get(graphic_family_index)
+            .is_some_and(|fp| fp.queue_flags.contains(vk::QueueFlags::COMPUTE))
+        {
+            Some(graphic_family_index)
+        } else {
+            family_properties
+                .iter()
+                .position(|fp| fp.queue_flags.contains(vk::QueueFlags::COMPUTE))
+        };
+        let Some(compute_family_index) = compute_family_index else {
+            return Err(RenderError::QueueFamilyNotSupported(
+                "Fail to find suitable queue families".to_string(),
+            ));
+        };
+        self.compute_family_index_priority = (compute_family_index as u32, 1.0);
+
+        self.video_decode_family_index_priority =
+            find_video_decode_h264_family(instance, physical_device).map(|index| (index, 1.0));
+
+        Ok(self)
+    }
+
     pub fn merge_queue_family_index_and_priority(&self) -> Vec<(u32, f32)> {
         let mut ret = std::collections::HashMap::new();
         [
@@ -74,4 +236,57 @@ impl QueueInfo {
 
         ret.drain().collect()
     }
+
+    /// Families queues are created from, the presentation families
+    /// of [`Self::merge_queue_family_index_and_priority`] plus the compute and video ones
+    pub fn queue_create_family_index_and_priority(&self) -> Vec<(u32, f32)> {
+        let mut ret = self.merge_queue_family_index_and_priority();
+        std::iter::once(self.compute_family_index_priority)
+            .chain(self.video_decode_family_index_priority)
+            .for_each(|x| {
+                if ret.iter().all(|y| y.0 != x.0) {
+                    ret.push(x);
+                }
+            });
+        ret
+    }
+}
+
+/// Codec operations of a queue family can only be queried through
+/// `VK_KHR_get_physical_device_properties2`, without it no video family is reported
+fn find_video_decode_h264_family(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Option<u32> {
+    if !instance.physical_device_properties2_enabled() {
+        return None;
+    }
+
+    let loader = GetPhysicalDeviceProperties2::new(instance.entry(), instance);
+    let count = unsafe { loader.get_physical_device_queue_family_properties2_len(physical_device) };
+    let mut video_properties = vec![vk::QueueFamilyVideoPropertiesKHR::default(); count];
+    let mut family_properties = video_properties
+        .iter_mut()
+        .map(|video_properties| {
+            vk::QueueFamilyProperties2::builder()
+                .push_next(video_properties)
+                .build()
+        })
+        .collect::<Vec<_>>();
+    unsafe {
+        loader.get_physical_device_queue_family_properties2(physical_device, &mut family_properties)
+    };
+
+    family_properties
+        .iter()
+        .zip(video_properties.iter())
+        .position(|(fp, video_properties)| {
+            fp.queue_family_properties
+                .queue_flags
+                .contains(vk::QueueFlags::VIDEO_DECODE_KHR)
+                && video_properties
+                    .video_codec_operations
+                    .contains(vk::VideoCodecOperationFlagsKHR::DECODE_H264)
+        })
+        .map(|index| index as u32)
 }
@@ -0,0 +1,130 @@
+use std::rc::Rc;
+
+use ash::{prelude::VkResult, vk};
+
+use super::Device;
+
+struct Subpass {
+    color_attachments: Vec<vk::AttachmentReference>,
+    depth_stencil_attachment: Option<vk::AttachmentReference>,
+    input_attachments: Vec<vk::AttachmentReference>,
+}
+
+/// Collects attachments, graphics subpasses and dependencies of a render pass,
+/// attachments are referred to by the order they were added in
+pub struct RenderPassBuilder {
+    attachments: Vec<vk::AttachmentDescription>,
+    subpasses: Vec<Subpass>,
+    dependencies: Vec<vk::SubpassDependency>,
+    device: Rc<Device>,
+}
+
+impl RenderPassBuilder {
+    pub fn new(device: Rc<Device>) -> Self {
+        Self {
+            attachments: vec![],
+            subpasses: vec![],
+            dependencies: vec![],
+            device,
+        }
+    }
+
+    /// Single sampled attachment whose stencil aspect, if any, is not kept
+    pub fn attachment(
+        self,
+        format: vk::Format,
+        load_op: vk::AttachmentLoadOp,
+        store_op: vk::AttachmentStoreOp,
+        initial_layout: vk::ImageLayout,
+        final_layout: vk::ImageLayout,
+    ) -> Self {
+        self.attachment_description(
+            vk::AttachmentDescription::builder()
+                .format(format)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .load_op(load_op)
+                .store_op(store_op)
+                .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .initial_layout(initial_layout)
+                .final_layout(final_layout)
+                .build(),
+        )
+    }
+
+    /// For multisampled attachments or stencil operations
+    pub fn attachment_description(mut self, description: vk::AttachmentDescription) -> Self {
+        self.attachments.push(description);
+        self
+    }
+
+    /// Graphics subpass writing `color_attachments` and testing against `depth_stencil_attachment`
+    pub fn subpass(self, color_attachments: &[u32], depth_stencil_attachment: Option<u32>) -> Self {
+        self.subpass_with_inputs(color_attachments, depth_stencil_attachment, &[])
+    }
+
+    /// Like [`RenderPassBuilder::subpass`], also reading `input_attachments`
+    /// written by earlier subpasses, e.g. the G-buffer of deferred shading
+    pub fn subpass_with_inputs(
+        mut self,
+        color_attachments: &[u32],
+        depth_stencil_attachment: Option<u32>,
+        input_attachments: &[u32],
+    ) -> Self {
+        let references = |attachments: &[u32], layout| {
+            attachments
+                .iter()
+                .map(|attachment| vk::AttachmentReference {
+                    attachment: *attachment,
+                    layout,
+                })
+                .collect::<Vec<_>>()
+        };
+        self.subpasses.push(Subpass {
+            color_attachments: references(
+                color_attachments,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            depth_stencil_attachment: depth_stencil_attachment.map(|attachment| {
+                vk::AttachmentReference {
+                    attachment,
+                    layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                }
+            }),
+            input_attachments: references(
+                input_attachments,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+        });
+        self
+    }
+
+    pub fn dependency(mut self, dependency: vk::SubpassDependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    pub fn build(&self) -> VkResult<vk::RenderPass> {
+        assert!(!self.subpasses.is_empty());
+        let subpasses = self
+            .subpasses
+            .iter()
+            .map(|subpass| {
+                let mut description = vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(&subpass.color_attachments)
+                    .input_attachments(&subpass.input_attachments);
+                if let Some(depth_stencil_attachment) = &subpass.depth_stencil_attachment {
+                    description = description.depth_stencil_attachment(depth_stencil_attachment);
+                }
+                description.build()
+            })
+            .collect::<Vec<_>>();
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&self.attachments)
+            .subpasses(&subpasses)
+            .dependencies(&self.dependencies)
+            .build();
+        unsafe { self.device.create_render_pass(&create_info, None) }
+    }
+}
@@ -0,0 +1,454 @@
+use std::rc::Rc;
+
+use ash::{prelude::VkResult, vk};
+
+use super::{extent_helper, DepthStencil, Device, RenderPassBuilder, Texture};
+use crate::error::RenderResult;
+
+/// Color + depth target which can be rendered into and then sampled in a later pass,
+/// a color-only target such as the input of a post-processing pass,
+/// or a sampled depth-only target such as a shadow map
+pub struct OffscreenRenderTarget {
+    color: Option<Texture>,
+    depth_stencil: Option<DepthStencil>,
+    /// Whether the depth attachment is stored and left readable by shaders
+    sampled_depth: bool,
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    device: Rc<Device>,
+}
+
+impl OffscreenRenderTarget {
+    pub fn new(
+        extent: vk::Extent2D,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Self::with_attachments(
+            extent,
+            Some(color_format),
+            Some(depth_format),
+            false,
+            device,
+        )
+    }
+
+    /// Like [`OffscreenRenderTarget::new`], but the depth attachment is also left in
+    /// `SHADER_READ_ONLY_OPTIMAL`, e.g. for [`crate::app::DepthInspector`]
+    pub fn with_sampled_depth(
+        extent: vk::Extent2D,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Self::with_attachments(extent, Some(color_format), Some(depth_format), true, device)
+    }
+
+    /// Color attachment only, for passes without depth testing such as fullscreen triangles
+    pub fn color_only(
+        extent: vk::Extent2D,
+        color_format: vk::Format,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Self::with_attachments(extent, Some(color_format), None, false, device)
+    }
+
+    /// Depth attachment only, left in `SHADER_READ_ONLY_OPTIMAL` after the render pass
+    /// to be sampled through [`OffscreenRenderTarget::depth_descriptor`]
+    pub fn depth_only(
+        extent: vk::Extent2D,
+        depth_format: vk::Format,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        Self::with_attachments(extent, None, Some(depth_format), true, device)
+    }
+
+    fn with_attachments(
+        extent: vk::Extent2D,
+        color_format: Option<vk::Format>,
+        depth_format: Option<vk::Format>,
+        sampled_depth: bool,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let render_pass =
+            create_offscreen_renderpass(color_format, depth_format, sampled_depth, &device)?;
+        let (color, depth_stencil) =
+            create_attachments(extent, color_format, depth_format, sampled_depth, &device)?;
+        let framebuffer = create_framebuffer(
+            render_pass,
+            &attachment_views(&color, &depth_stencil),
+            extent,
+            &device,
+        )?;
+
+        Ok(Self {
+            color,
+            depth_stencil,
+            sampled_depth,
+            render_pass,
+            framebuffer,
+            extent,
+            device,
+        })
+    }
+
+    /// Recreates the attachments with the same formats, e.g. to follow the window size.
+    /// The render pass is kept, so pipelines built against it stay valid, descriptors
+    /// of the attachments have to be written again.
+    pub fn resize(&mut self, extent: vk::Extent2D) -> RenderResult<()> {
+        unsafe { self.device.device_wait_idle()? };
+        let (color, depth_stencil) = create_attachments(
+            extent,
+            self.color.as_ref().map(Texture::format),
+            self.depth_stencil.as_ref().map(DepthStencil::format),
+            self.sampled_depth,
+            &self.device,
+        )?;
+        let framebuffer = create_framebuffer(
+            self.render_pass,
+            &attachment_views(&color, &depth_stencil),
+            extent,
+            &self.device,
+        )?;
+        unsafe { self.device.destroy_framebuffer(self.framebuffer, None) };
+        self.color = color;
+        self.depth_stencil = depth_stencil;
+        self.framebuffer = framebuffer;
+        self.extent = extent;
+        Ok(())
+    }
+
+    pub fn color(&self) -> Option<&Texture> {
+        self.color.as_ref()
+    }
+
+    /// `None` for targets created with [`OffscreenRenderTarget::color_only`]
+    pub fn depth_stencil(&self) -> Option<&DepthStencil> {
+        self.depth_stencil.as_ref()
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    pub fn framebuffer(&self) -> vk::Framebuffer {
+        self.framebuffer
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    /// The render pass leaves the color attachment in `SHADER_READ_ONLY_OPTIMAL`,
+    /// panics for depth-only targets
+    pub fn color_descriptor(&self) -> vk::DescriptorImageInfo {
+        let color = self
+            .color
+            .as_ref()
+            .expect("Depth-only render target has no color attachment");
+        vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(*color.image_view().unwrap())
+            .sampler(*color.sampler().unwrap())
+            .build()
+    }
+
+    /// Only available for targets created with [`OffscreenRenderTarget::depth_only`]
+    /// or [`OffscreenRenderTarget::with_sampled_depth`]
+    pub fn depth_descriptor(&self) -> vk::DescriptorImageInfo {
+        assert!(self.sampled_depth);
+        let depth_stencil = self.depth_stencil.as_ref().unwrap();
+        vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(*depth_stencil.image_view())
+            .sampler(*depth_stencil.sampler().unwrap())
+            .build()
+    }
+
+    /// One clear value per attachment, color first then depth
+    pub fn cmd_begin_render_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        clear_values: &[vk::ClearValue],
+    ) {
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &vk::RenderPassBeginInfo::builder()
+                    .render_pass(self.render_pass)
+                    .framebuffer(self.framebuffer)
+                    .render_area(extent_helper::scissor_from_extent(self.extent))
+                    .clear_values(clear_values)
+                    .build(),
+                vk::SubpassContents::INLINE,
+            );
+        }
+    }
+
+    pub fn cmd_set_viewport_and_scissor(&self, command_buffer: vk::CommandBuffer) {
+        extent_helper::cmd_set_viewport_and_scissor(&self.device, command_buffer, self.extent);
+    }
+}
+
+impl Drop for OffscreenRenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_framebuffer(self.framebuffer, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+        }
+    }
+}
+
+fn clamp_to_edge_sampler(device: &Device) -> VkResult<vk::Sampler> {
+    let create_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .max_lod(1.)
+        .build();
+    unsafe { device.create_sampler(&create_info, None) }
+}
+
+/// Outside of the shadow map everything is lit, hence the white border
+fn shadow_map_sampler(device: &Device) -> VkResult<vk::Sampler> {
+    let create_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::NEAREST)
+        .min_filter(vk::Filter::NEAREST)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .max_lod(1.)
+        .build();
+    unsafe { device.create_sampler(&create_info, None) }
+}
+
+fn create_framebuffer(
+    render_pass: vk::RenderPass,
+    attachments: &[vk::ImageView],
+    extent: vk::Extent2D,
+    device: &Device,
+) -> VkResult<vk::Framebuffer> {
+    unsafe {
+        device.create_framebuffer(
+            &vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass)
+                .attachments(attachments)
+                .width(extent.width)
+                .height(extent.height)
+                .layers(1)
+                .build(),
+            None,
+        )
+    }
+}
+
+/// With `sampled_depth` the depth attachment is stored and left readable by shaders,
+/// which is implied without `color_format`
+fn create_offscreen_renderpass(
+    color_format: Option<vk::Format>,
+    depth_format: Option<vk::Format>,
+    sampled_depth: bool,
+    device: &Rc<Device>,
+) -> VkResult<vk::RenderPass> {
+    match (color_format, depth_format) {
+        (Some(color_format), depth_format) => {
+            create_color_renderpass(color_format, depth_format, sampled_depth, device)
+        }
+        (None, Some(depth_format)) => create_depth_only_renderpass(depth_format, device),
+        (None, None) => panic!("Render target without attachments"),
+    }
+}
+
+/// The color attachment is sampled, a depth attachment of a color target only with
+/// `sampled_depth`
+fn create_attachments(
+    extent: vk::Extent2D,
+    color_format: Option<vk::Format>,
+    depth_format: Option<vk::Format>,
+    sampled_depth: bool,
+    device: &Rc<Device>,
+) -> RenderResult<(Option<Texture>, Option<DepthStencil>)> {
+    let color = color_format
+        .map(|color_format| {
+            let mut color = Texture::builder(
+                extent.width,
+                extent.height,
+                color_format,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC,
+                device.clone(),
+            )
+            .build()?;
+            color.spawn_image_view()?;
+            color.set_sampler(Rc::new(clamp_to_edge_sampler(device)?));
+            RenderResult::Ok(color)
+        })
+        .transpose()?;
+    let depth_stencil = depth_format
+        .map(|depth_format| match sampled_depth {
+            false => DepthStencil::new(extent, depth_format, device.clone()),
+            true => {
+                let mut depth_stencil = DepthStencil::with_usage(
+                    extent,
+                    depth_format,
+                    vk::ImageUsageFlags::SAMPLED,
+                    device.clone(),
+                )?;
+                depth_stencil.set_sampler(Rc::new(shadow_map_sampler(device)?));
+                Ok(depth_stencil)
+            }
+        })
+        .transpose()?;
+    Ok((color, depth_stencil))
+}
+
+fn attachment_views(
+    color: &Option<Texture>,
+    depth_stencil: &Option<DepthStencil>,
+) -> Vec<vk::ImageView> {
+    color
+        .iter()
+        .map(|color| *color.image_view().unwrap())
+        .chain(depth_stencil.iter().map(|depth| *depth.image_view()))
+        .collect()
+}
+
+fn create_depth_only_renderpass(
+    depth_format: vk::Format,
+    device: &Rc<Device>,
+) -> VkResult<vk::RenderPass> {
+    let [before_depth_writes, after_depth_writes] = sampled_depth_dependencies();
+    RenderPassBuilder::new(device.clone())
+        .attachment(
+            depth_format,
+            vk::AttachmentLoadOp::CLEAR,
+            vk::AttachmentStoreOp::STORE,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+        .subpass(&[], Some(0))
+        .dependency(before_depth_writes)
+        .dependency(after_depth_writes)
+        .build()
+}
+
+/// Waits for any previous sampling of a sampled depth attachment before writing to it,
+/// then makes the depth writes visible to fragment and compute shaders of later passes,
+/// which may read any texel, so the dependencies are not by region
+fn sampled_depth_dependencies() -> [vk::SubpassDependency; 2] {
+    let shader_stages =
+        vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER;
+    let depth_stages =
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+    [
+        vk::SubpassDependency::builder()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(shader_stages)
+            .dst_stage_mask(depth_stages)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .build(),
+        vk::SubpassDependency::builder()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+            .dst_stage_mask(shader_stages)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .build(),
+    ]
+}
+
+fn create_color_renderpass(
+    color_format: vk::Format,
+    depth_format: Option<vk::Format>,
+    sampled_depth: bool,
+    device: &Rc<Device>,
+) -> VkResult<vk::RenderPass> {
+    let mut builder = RenderPassBuilder::new(device.clone()).attachment(
+        color_format,
+        vk::AttachmentLoadOp::CLEAR,
+        vk::AttachmentStoreOp::STORE,
+        vk::ImageLayout::UNDEFINED,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    );
+    let (mut src_stage_mask, mut src_access_mask) = (
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::AccessFlags::SHADER_READ,
+    );
+    let (mut dst_stage_mask, mut dst_access_mask) = (
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+    );
+    if let Some(depth_format) = depth_format {
+        builder = match sampled_depth {
+            false => builder.attachment(
+                depth_format,
+                vk::AttachmentLoadOp::CLEAR,
+                vk::AttachmentStoreOp::DONT_CARE,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+            true => {
+                let [before_depth_writes, after_depth_writes] = sampled_depth_dependencies();
+                builder
+                    .attachment(
+                        depth_format,
+                        vk::AttachmentLoadOp::CLEAR,
+                        vk::AttachmentStoreOp::STORE,
+                        vk::ImageLayout::UNDEFINED,
+                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    )
+                    .dependency(before_depth_writes)
+                    .dependency(after_depth_writes)
+            }
+        };
+        let depth_stages = vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        // The clear writes the depth the previous frame wrote too
+        src_stage_mask |= depth_stages;
+        src_access_mask |= vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+        dst_stage_mask |= depth_stages;
+        dst_access_mask |= vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    }
+    builder
+        .subpass(&[0], depth_format.map(|_| 1))
+        // Wait for any previous sampling of the color attachment and depth writes before
+        // writing to them. Not by region, later passes sample any texel of the target
+        .dependency(
+            vk::SubpassDependency::builder()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(src_stage_mask)
+                .dst_stage_mask(dst_stage_mask)
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .build(),
+        )
+        // Make the color writes visible to the shaders and transfers of later passes
+        .dependency(
+            vk::SubpassDependency::builder()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                        | vk::PipelineStageFlags::COMPUTE_SHADER
+                        | vk::PipelineStageFlags::TRANSFER,
+                )
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::TRANSFER_READ)
+                .build(),
+        )
+        .build()
+}
@@ -0,0 +1,153 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use ash::{prelude::VkResult, vk};
+
+use super::Device;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerSettings {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_modes: [vk::SamplerAddressMode; 3],
+    /// Anisotropic filtering is disabled when not greater than 1
+    pub max_anisotropy: f32,
+    pub mip_lod_bias: f32,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_modes: [vk::SamplerAddressMode::REPEAT; 3],
+            max_anisotropy: 1.,
+            mip_lod_bias: 0.,
+        }
+    }
+}
+
+impl SamplerSettings {
+    pub fn with_filter(mut self, filter: vk::Filter) -> Self {
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn with_address_mode(mut self, address_mode: vk::SamplerAddressMode) -> Self {
+        self.address_modes = [address_mode; 3];
+        self
+    }
+
+    pub fn with_max_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    pub fn create_info(&self) -> vk::SamplerCreateInfo {
+        vk::SamplerCreateInfo::builder()
+            .mag_filter(self.mag_filter)
+            .min_filter(self.min_filter)
+            .mipmap_mode(self.mipmap_mode)
+            .address_mode_u(self.address_modes[0])
+            .address_mode_v(self.address_modes[1])
+            .address_mode_w(self.address_modes[2])
+            .anisotropy_enable(self.max_anisotropy > 1.)
+            .max_anisotropy(self.max_anisotropy.max(1.))
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .compare_op(vk::CompareOp::NEVER)
+            .mip_lod_bias(self.mip_lod_bias)
+            .min_lod(0.)
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .build()
+    }
+}
+
+/// Floats are compared by bits, as they are hashed, so that `Eq` holds for the cache keys
+impl PartialEq for SamplerSettings {
+    fn eq(&self, other: &Self) -> bool {
+        self.mag_filter == other.mag_filter
+            && self.min_filter == other.min_filter
+            && self.mipmap_mode == other.mipmap_mode
+            && self.address_modes == other.address_modes
+            && self.max_anisotropy.to_bits() == other.max_anisotropy.to_bits()
+            && self.mip_lod_bias.to_bits() == other.mip_lod_bias.to_bits()
+    }
+}
+
+impl Eq for SamplerSettings {}
+
+impl Hash for SamplerSettings {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.mag_filter.hash(state);
+        self.min_filter.hash(state);
+        self.mipmap_mode.hash(state);
+        self.address_modes.hash(state);
+        self.max_anisotropy.to_bits().hash(state);
+        self.mip_lod_bias.to_bits().hash(state);
+    }
+}
+
+/// Shares one [`vk::Sampler`] between all textures using the same [`SamplerSettings`]
+pub struct SamplerCache {
+    samplers: RefCell<HashMap<SamplerSettings, Rc<vk::Sampler>>>,
+    device: Rc<Device>,
+}
+
+impl SamplerCache {
+    pub fn new(device: Rc<Device>) -> Self {
+        Self {
+            samplers: RefCell::new(HashMap::new()),
+            device,
+        }
+    }
+
+    pub fn max_anisotropy_limit(&self) -> f32 {
+        self.device.capabilities().max_sampler_anisotropy
+    }
+
+    /// Anisotropy beyond the device limit is clamped before looking the sampler up
+    pub fn get(&self, settings: &SamplerSettings) -> VkResult<Rc<vk::Sampler>> {
+        let mut settings = *settings;
+        settings.max_anisotropy = self
+            .device
+            .capabilities()
+            .clamp_anisotropy(settings.max_anisotropy);
+
+        if let Some(sampler) = self.samplers.borrow().get(&settings) {
+            return Ok(sampler.clone());
+        }
+
+        let sampler =
+            Rc::new(unsafe { self.device.create_sampler(&settings.create_info(), None)? });
+        self.samplers.borrow_mut().insert(settings, sampler.clone());
+        Ok(sampler)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.borrow().is_empty()
+    }
+}
+
+impl Drop for SamplerCache {
+    fn drop(&mut self) {
+        // Samplers still shared with a texture are destroyed by that texture instead
+        self.samplers.borrow().values().for_each(|sampler| {
+            if Rc::strong_count(sampler) == 1 {
+                unsafe { self.device.destroy_sampler(**sampler, None) };
+            }
+        });
+    }
+}
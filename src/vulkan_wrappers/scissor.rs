@@ -0,0 +1,83 @@
+use ash::vk;
+
+use super::extent_helper;
+
+/// Sets scissors for 2D draws within a framebuffer of `extent`, clamping every rect
+/// to it. The full-extent scissor is restored on drop, so later draws of the pass are
+/// not clipped by the last rect.
+pub struct ScissorGuard<'a> {
+    device: &'a ash::Device,
+    command_buffer: vk::CommandBuffer,
+    extent: vk::Extent2D,
+}
+
+impl<'a> ScissorGuard<'a> {
+    pub fn new(
+        device: &'a ash::Device,
+        command_buffer: vk::CommandBuffer,
+        extent: vk::Extent2D,
+    ) -> Self {
+        Self {
+            device,
+            command_buffer,
+            extent,
+        }
+    }
+
+    /// Clamps `rect`, given as `[min_x, min_y, max_x, max_y]` in pixels like imgui clip
+    /// rects, to `extent`. Returns `None` when nothing of it is left
+    pub fn clamp(extent: vk::Extent2D, rect: [f32; 4]) -> Option<vk::Rect2D> {
+        let (width, height) = (extent.width as f32, extent.height as f32);
+        // NaN coordinates saturate to 0 on the casts below
+        let min_x = rect[0].floor().clamp(0., width) as u32;
+        let min_y = rect[1].floor().clamp(0., height) as u32;
+        let max_x = rect[2].ceil().clamp(0., width) as u32;
+        let max_y = rect[3].ceil().clamp(0., height) as u32;
+        if max_x <= min_x || max_y <= min_y {
+            return None;
+        }
+        Some(
+            vk::Rect2D::builder()
+                .offset(vk::Offset2D {
+                    x: min_x as i32,
+                    y: min_y as i32,
+                })
+                .extent(vk::Extent2D {
+                    width: max_x - min_x,
+                    height: max_y - min_y,
+                })
+                .build(),
+        )
+    }
+
+    /// Sets the scissor to `rect` clamped to the framebuffer, returns `false` without
+    /// recording anything when the draw it clips would cover no pixels and can be skipped
+    pub fn set(&self, rect: [f32; 4]) -> bool {
+        match Self::clamp(self.extent, rect) {
+            Some(scissor) => {
+                unsafe {
+                    self.device
+                        .cmd_set_scissor(self.command_buffer, 0, &[scissor])
+                };
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+}
+
+impl Drop for ScissorGuard<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.cmd_set_scissor(
+                self.command_buffer,
+                0,
+                &[extent_helper::scissor_from_extent(self.extent)],
+            )
+        };
+    }
+}
@@ -1,10 +1,12 @@
 use std::path::Path;
 use std::rc::Rc;
-use std::{ffi::CStr, fs};
+use std::{ffi::CStr, fs, io};
 
 use ash::vk;
 
 use super::Device;
+#[cfg(feature = "shaderc")]
+use crate::error::RenderError;
 use crate::error::RenderResult;
 
 /// # Caution
@@ -19,6 +21,64 @@ impl Drop for ShaderModule {
     }
 }
 
+/// Values of the `layout(constant_id = ...)` constants of a shader stage, baked in when
+/// the pipeline is created so that branches on them compile away
+#[derive(Clone, Debug, Default)]
+pub struct SpecializationConstants {
+    entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl SpecializationConstants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `T` must match the type of the constant in the shader, e.g. `u32`, `i32` or `f32`
+    pub fn constant<T: bytemuck::Pod>(mut self, constant_id: u32, value: T) -> Self {
+        assert!(
+            self.entries
+                .iter()
+                .all(|entry| entry.constant_id != constant_id),
+            "Specialization constant {constant_id} set twice"
+        );
+        self.entries.push(vk::SpecializationMapEntry {
+            constant_id,
+            offset: self.data.len() as u32,
+            size: std::mem::size_of::<T>(),
+        });
+        self.data.extend_from_slice(bytemuck::bytes_of(&value));
+        self
+    }
+
+    /// GLSL `bool` constants are 32 bits wide
+    pub fn bool(self, constant_id: u32, value: bool) -> Self {
+        self.constant(constant_id, value as vk::Bool32)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Points into `self`, which must outlive the pipeline creation
+    pub fn info(&self) -> vk::SpecializationInfo {
+        vk::SpecializationInfo::builder()
+            .map_entries(&self.entries)
+            .data(&self.data)
+            .build()
+    }
+}
+
+/// Embeds a SPIR-V file compiled by build.rs as a `&'static [u8]` for
+/// [`ShaderCreate::from_bytes`], so that no loose .spv file is read at runtime.
+/// The path is relative to the manifest directory of the calling crate.
+#[macro_export]
+macro_rules! include_spv {
+    ($spv_path: literal) => {
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $spv_path)) as &'static [u8]
+    };
+}
+
 pub struct ShaderCreate {
     pub stage_create_info: vk::PipelineShaderStageCreateInfo,
     pub module: ShaderModule,
@@ -73,4 +133,111 @@ impl ShaderCreate {
             device,
         )
     }
+
+    /// Decodes SPIR-V `bytes`, such as the ones embedded with [`crate::include_spv!`], which
+    /// need not be 4-byte aligned
+    pub fn from_bytes(
+        bytes: &[u8],
+        stage_flag: vk::ShaderStageFlags,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let binary = ash::util::read_spv(&mut io::Cursor::new(bytes))?;
+        Self::new(binary, stage_flag, Self::DEFAULT_SHADER_START_NAME, device)
+    }
+
+    /// Compiles GLSL `source` at runtime, `file_name` tags compile errors and is the base
+    /// of relative `#include` directives
+    #[cfg(feature = "shaderc")]
+    pub fn from_glsl_source(
+        source: &str,
+        file_name: &str,
+        stage_flag: vk::ShaderStageFlags,
+        start_name: &CStr,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let binary = compile_glsl(source, file_name, stage_flag, start_name)?;
+        Self::new(binary, stage_flag, start_name, device)
+    }
+
+    #[cfg(feature = "shaderc")]
+    pub fn from_glsl_path<P: AsRef<Path>>(
+        shader_glsl_path: P,
+        stage_flag: vk::ShaderStageFlags,
+        start_name: &CStr,
+        device: Rc<Device>,
+    ) -> RenderResult<Self> {
+        let source = fs::read_to_string(&shader_glsl_path)?;
+        Self::from_glsl_source(
+            &source,
+            &shader_glsl_path.as_ref().to_string_lossy(),
+            stage_flag,
+            start_name,
+            device,
+        )
+    }
+}
+
+#[cfg(feature = "shaderc")]
+fn shader_kind(stage_flag: vk::ShaderStageFlags) -> RenderResult<shaderc::ShaderKind> {
+    Ok(match stage_flag {
+        vk::ShaderStageFlags::VERTEX => shaderc::ShaderKind::Vertex,
+        vk::ShaderStageFlags::FRAGMENT => shaderc::ShaderKind::Fragment,
+        vk::ShaderStageFlags::COMPUTE => shaderc::ShaderKind::Compute,
+        vk::ShaderStageFlags::GEOMETRY => shaderc::ShaderKind::Geometry,
+        vk::ShaderStageFlags::TESSELLATION_CONTROL => shaderc::ShaderKind::TessControl,
+        vk::ShaderStageFlags::TESSELLATION_EVALUATION => shaderc::ShaderKind::TessEvaluation,
+        _ => {
+            return Err(RenderError::ShaderCompileError(
+                shaderc::Error::InvalidStage(format!("{stage_flag:?}")),
+            ))
+        }
+    })
+}
+
+/// `#include "x"` is looked up next to the including file, `#include <x>` in the working directory
+#[cfg(feature = "shaderc")]
+fn resolve_include(
+    requested: &str,
+    include_type: shaderc::IncludeType,
+    requesting: &str,
+    _depth: usize,
+) -> shaderc::IncludeCallbackResult {
+    let path = match include_type {
+        shaderc::IncludeType::Relative => Path::new(requesting)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(requested),
+        shaderc::IncludeType::Standard => Path::new(requested).to_path_buf(),
+    };
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("failed to include {}: {e}", path.display()))?;
+    Ok(shaderc::ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+    })
+}
+
+#[cfg(feature = "shaderc")]
+fn compile_glsl(
+    source: &str,
+    file_name: &str,
+    stage_flag: vk::ShaderStageFlags,
+    start_name: &CStr,
+) -> RenderResult<Vec<u32>> {
+    let compiler_error = || {
+        RenderError::ShaderCompileError(shaderc::Error::InternalError(
+            "failed to initialize shaderc".to_string(),
+        ))
+    };
+    let compiler = shaderc::Compiler::new().ok_or_else(compiler_error)?;
+    let mut options = shaderc::CompileOptions::new().ok_or_else(compiler_error)?;
+    options.set_include_callback(resolve_include);
+    let artifact = compiler.compile_into_spirv(
+        source,
+        shader_kind(stage_flag)?,
+        file_name,
+        &start_name.to_string_lossy(),
+        Some(&options),
+    )?;
+    Ok(artifact.as_binary().to_vec())
 }
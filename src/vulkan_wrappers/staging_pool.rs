@@ -0,0 +1,199 @@
+use ash::{prelude::VkResult, vk};
+
+use super::{memory_helper, Device};
+use crate::error::RenderResult;
+
+/// Part of a [`StagingPool`] chunk holding staged data, the source of a transfer
+#[derive(Clone, Copy)]
+pub struct StagingRegion {
+    pub buffer: vk::Buffer,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    chunk: usize,
+}
+
+struct StagingChunk {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    mapped_ptr: *mut u8,
+    size: vk::DeviceSize,
+    used: vk::DeviceSize,
+    /// Signaled once the transfers reading the chunk finished
+    fence: vk::Fence,
+    in_flight: bool,
+}
+
+/// Ring of host visible chunks the staged uploads copy their data into, reused once the
+/// fence of the transfers reading them signaled instead of allocating a staging buffer
+/// per upload. [`Device::staging_pool`] is the one [`super::Buffer::new_device_local`]
+/// and [`super::Texture`] uploads draw from.
+///
+/// Regions are suballocated from an open chunk until [`Self::submit`] closes it,
+/// data larger than [`Self::chunk_size`] gets a chunk of its own, freed when reclaimed.
+pub struct StagingPool {
+    chunks: Vec<Option<StagingChunk>>,
+    chunk_size: vk::DeviceSize,
+}
+
+impl StagingPool {
+    pub const DEFAULT_CHUNK_SIZE: vk::DeviceSize = 32 * 1024 * 1024;
+    /// Satisfies the texel block size of every format with a size power of two
+    const REGION_ALIGNMENT: vk::DeviceSize = 16;
+
+    pub fn new(chunk_size: vk::DeviceSize) -> Self {
+        Self {
+            chunks: vec![],
+            chunk_size,
+        }
+    }
+
+    pub fn chunk_size(&self) -> vk::DeviceSize {
+        self.chunk_size
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.iter().flatten().count()
+    }
+
+    /// Copies `data` into an open chunk, allocating one when none has room left
+    pub fn stage<T>(&mut self, data: &[T], device: &Device) -> RenderResult<StagingRegion> {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        self.reclaim(device)?;
+
+        let chunk = match self.chunks.iter().position(|chunk| {
+            chunk.as_ref().is_some_and(|chunk| {
+                !chunk.in_flight
+                    && chunk.used.next_multiple_of(Self::REGION_ALIGNMENT) + size <= chunk.size
+            })
+        }) {
+            Some(chunk) => chunk,
+            None => {
+                let chunk = StagingChunk::new(size.max(self.chunk_size), device)?;
+                match self.chunks.iter().position(Option::is_none) {
+                    Some(free) => {
+                        self.chunks[free] = Some(chunk);
+                        free
+                    }
+                    None => {
+                        self.chunks.push(Some(chunk));
+                        self.chunks.len() - 1
+                    }
+                }
+            }
+        };
+
+        let staging_chunk = self.chunks[chunk].as_mut().unwrap();
+        let offset = staging_chunk.used.next_multiple_of(Self::REGION_ALIGNMENT);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const u8,
+                staging_chunk.mapped_ptr.add(offset as usize),
+                size as usize,
+            );
+        }
+        staging_chunk.used = offset + size;
+
+        Ok(StagingRegion {
+            buffer: staging_chunk.buffer,
+            offset,
+            size,
+            chunk,
+        })
+    }
+
+    /// Runs `submit` with the fence the submission reading `region`, and every other region
+    /// staged into the same chunk, must signal. The chunk is closed only once `submit`
+    /// succeeded, a failed one leaves it open instead of waiting for a fence never signaled.
+    pub fn submit<T>(
+        &mut self,
+        region: &StagingRegion,
+        submit: impl FnOnce(vk::Fence) -> VkResult<T>,
+    ) -> VkResult<T> {
+        let chunk = self.chunks[region.chunk]
+            .as_mut()
+            .expect("Staging region of a reclaimed chunk");
+        assert!(!chunk.in_flight, "Staging chunk submitted twice");
+        let result = submit(chunk.fence)?;
+        chunk.in_flight = true;
+        Ok(result)
+    }
+
+    /// Reopens the chunks whose fence signaled, freeing those of oversized data
+    pub fn reclaim(&mut self, device: &Device) -> RenderResult<()> {
+        let chunk_size = self.chunk_size;
+        for slot in self.chunks.iter_mut() {
+            let Some(chunk) = slot.as_mut().filter(|chunk| chunk.in_flight) else {
+                continue;
+            };
+            if !unsafe { device.get_fence_status(chunk.fence)? } {
+                continue;
+            }
+            if chunk.size > chunk_size {
+                slot.take().unwrap().destroy(device);
+            } else {
+                unsafe { device.reset_fences(&[chunk.fence])? };
+                chunk.used = 0;
+                chunk.in_flight = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Every submission reading the pool must have finished
+    pub fn destroy(&mut self, device: &Device) {
+        self.chunks
+            .drain(..)
+            .flatten()
+            .for_each(|chunk| chunk.destroy(device));
+    }
+}
+
+impl StagingChunk {
+    fn new(size: vk::DeviceSize, device: &Device) -> RenderResult<Self> {
+        unsafe {
+            let buffer = device.create_buffer(
+                &vk::BufferCreateInfo::builder()
+                    .size(size)
+                    .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .build(),
+                None,
+            )?;
+            let memory_requirements = device.get_buffer_memory_requirements(buffer);
+            let memory = device.allocate_tracked_memory(
+                &vk::MemoryAllocateInfo::builder()
+                    .allocation_size(memory_requirements.size)
+                    .memory_type_index(memory_helper::find_memory_type(
+                        device,
+                        &memory_requirements,
+                        vk::MemoryPropertyFlags::HOST_VISIBLE
+                            | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    )?)
+                    .build(),
+                false,
+            )?;
+            device.bind_buffer_memory(buffer, memory, 0)?;
+            let mapped_ptr =
+                device.map_memory(memory, 0, size, vk::MemoryMapFlags::default())? as *mut u8;
+            let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+
+            Ok(Self {
+                buffer,
+                memory,
+                mapped_ptr,
+                size,
+                used: 0,
+                fence,
+                in_flight: false,
+            })
+        }
+    }
+
+    fn destroy(self, device: &Device) {
+        unsafe {
+            device.destroy_fence(self.fence, None);
+            device.destroy_buffer(self.buffer, None);
+            device.free_tracked_memory(self.memory);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::rc::Rc;
+
+use ash::{prelude::VkResult, vk};
+
+use super::Device;
+
+/// Submission added with [`SubmitGraph::add_node`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubmitNode(usize);
+
+struct NodeInfo {
+    name: &'static str,
+    queue: vk::Queue,
+}
+
+struct Dependency {
+    before: usize,
+    after: usize,
+    wait_stage: vk::PipelineStageFlags,
+    /// One per frame in flight
+    semaphores: Vec<vk::Semaphore>,
+}
+
+/// Submissions of a frame to possibly different queues, e.g. upload → compute skinning →
+/// render, chained by semaphores the graph creates once per frame in flight.
+///
+/// The nodes are declared once, every frame then gives the command buffers of the nodes
+/// it submits with [`Self::frame`]. A dependency only holds between two nodes submitted
+/// in the same frame, a skipped node signals and waits for nothing.
+pub struct SubmitGraph {
+    device: Rc<Device>,
+    frames_in_flight: usize,
+    nodes: Vec<NodeInfo>,
+    dependencies: Vec<Dependency>,
+    /// Node indices, every node after the ones it depends on
+    order: Vec<usize>,
+}
+
+impl SubmitGraph {
+    const NODE_LABEL_COLOR: [f32; 4] = [0.8, 0.6, 0.2, 1.0];
+
+    pub fn new(device: Rc<Device>, frames_in_flight: usize) -> Self {
+        Self {
+            device,
+            frames_in_flight,
+            nodes: vec![],
+            dependencies: vec![],
+            order: vec![],
+        }
+    }
+
+    /// `name` labels the submissions of the node in debuggers and profilers
+    pub fn add_node(&mut self, name: &'static str, queue: vk::Queue) -> SubmitNode {
+        self.nodes.push(NodeInfo { name, queue });
+        self.order = self.sorted_nodes();
+        SubmitNode(self.nodes.len() - 1)
+    }
+
+    /// `after` waits at `wait_stage` until `before` finished, panics if `before` already
+    /// depends on `after`
+    pub fn add_dependency(
+        &mut self,
+        before: SubmitNode,
+        after: SubmitNode,
+        wait_stage: vk::PipelineStageFlags,
+    ) -> VkResult<()> {
+        assert!(
+            before != after && !self.depends_on(before.0, after.0),
+            "{} can not run both before and after {}",
+            self.nodes[before.0].name,
+            self.nodes[after.0].name
+        );
+        let semaphores = (0..self.frames_in_flight)
+            .map(|_| unsafe {
+                self.device
+                    .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+            })
+            .collect::<VkResult<Vec<_>>>()?;
+        self.dependencies.push(Dependency {
+            before: before.0,
+            after: after.0,
+            wait_stage,
+            semaphores,
+        });
+        self.order = self.sorted_nodes();
+        Ok(())
+    }
+
+    pub fn node_name(&self, node: SubmitNode) -> &'static str {
+        self.nodes[node.0].name
+    }
+
+    pub fn node_queue(&self, node: SubmitNode) -> vk::Queue {
+        self.nodes[node.0].queue
+    }
+
+    /// Collects the submissions of frame `frame_index`, see [`FrameSubmit::submit`]
+    pub fn frame(&self, frame_index: usize) -> FrameSubmit<'_> {
+        debug_assert!(frame_index < self.frames_in_flight);
+        FrameSubmit {
+            graph: self,
+            frame_index,
+            submits: (0..self.nodes.len()).map(|_| None).collect(),
+        }
+    }
+
+    /// Whether `node` transitively waits for `dependency`
+    fn depends_on(&self, node: usize, dependency: usize) -> bool {
+        self.dependencies
+            .iter()
+            .filter(|edge| edge.after == node)
+            .any(|edge| edge.before == dependency || self.depends_on(edge.before, dependency))
+    }
+
+    fn sorted_nodes(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while order.len() < self.nodes.len() {
+            let next = (0..self.nodes.len())
+                .find(|node| {
+                    !order.contains(node)
+                        && self
+                            .dependencies
+                            .iter()
+                            .filter(|edge| edge.after == *node)
+                            .all(|edge| order.contains(&edge.before))
+                })
+                .expect("Dependencies are acyclic");
+            order.push(next);
+        }
+        order
+    }
+}
+
+impl Drop for SubmitGraph {
+    fn drop(&mut self) {
+        unsafe {
+            self.dependencies
+                .iter()
+                .flat_map(|edge| &edge.semaphores)
+                .for_each(|semaphore| self.device.destroy_semaphore(*semaphore, None));
+        }
+    }
+}
+
+#[derive(Default)]
+struct NodeSubmit {
+    command_buffers: Vec<vk::CommandBuffer>,
+    wait_semaphores: Vec<vk::Semaphore>,
+    wait_stages: Vec<vk::PipelineStageFlags>,
+    signal_semaphores: Vec<vk::Semaphore>,
+    fence: vk::Fence,
+}
+
+/// Submissions of one frame, a node is submitted once any of its command buffers,
+/// semaphores or fence is given
+pub struct FrameSubmit<'a> {
+    graph: &'a SubmitGraph,
+    frame_index: usize,
+    submits: Vec<Option<NodeSubmit>>,
+}
+
+impl<'a> FrameSubmit<'a> {
+    pub fn commands(mut self, node: SubmitNode, command_buffers: &[vk::CommandBuffer]) -> Self {
+        self.node_submit(node)
+            .command_buffers
+            .extend_from_slice(command_buffers);
+        self
+    }
+
+    /// Waits for a semaphore signaled outside the graph, such as the swapchain image
+    /// acquisition
+    pub fn wait(
+        mut self,
+        node: SubmitNode,
+        semaphore: vk::Semaphore,
+        stage: vk::PipelineStageFlags,
+    ) -> Self {
+        let submit = self.node_submit(node);
+        submit.wait_semaphores.push(semaphore);
+        submit.wait_stages.push(stage);
+        self
+    }
+
+    /// Signals a semaphore waited for outside the graph, such as by the present
+    pub fn signal(mut self, node: SubmitNode, semaphore: vk::Semaphore) -> Self {
+        self.node_submit(node).signal_semaphores.push(semaphore);
+        self
+    }
+
+    pub fn fence(mut self, node: SubmitNode, fence: vk::Fence) -> Self {
+        self.node_submit(node).fence = fence;
+        self
+    }
+
+    /// Submits the given nodes in dependency order, each to its own queue
+    pub fn submit(mut self) -> VkResult<()> {
+        let graph = self.graph;
+        for edge in &graph.dependencies {
+            if self.submits[edge.before].is_none() || self.submits[edge.after].is_none() {
+                continue;
+            }
+            let semaphore = edge.semaphores[self.frame_index];
+            let after = self.submits[edge.after].as_mut().unwrap();
+            after.wait_semaphores.push(semaphore);
+            after.wait_stages.push(edge.wait_stage);
+            let before = self.submits[edge.before].as_mut().unwrap();
+            before.signal_semaphores.push(semaphore);
+        }
+
+        for &node in &graph.order {
+            let Some(submit) = &self.submits[node] else {
+                continue;
+            };
+            let NodeInfo { name, queue } = graph.nodes[node];
+            let submit_info = vk::SubmitInfo::builder()
+                .wait_semaphores(&submit.wait_semaphores)
+                .wait_dst_stage_mask(&submit.wait_stages)
+                .command_buffers(&submit.command_buffers)
+                .signal_semaphores(&submit.signal_semaphores)
+                .build();
+            graph
+                .device
+                .queue_begin_debug_label(queue, name, SubmitGraph::NODE_LABEL_COLOR);
+            let result = unsafe {
+                graph
+                    .device
+                    .queue_submit(queue, &[submit_info], submit.fence)
+            };
+            graph.device.queue_end_debug_label(queue);
+            result?;
+        }
+        Ok(())
+    }
+
+    fn node_submit(&mut self, node: SubmitNode) -> &mut NodeSubmit {
+        self.submits[node.0].get_or_insert_with(NodeSubmit::default)
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     cell::{Ref, RefCell},
+    f32::consts::{FRAC_PI_2, PI},
     rc::{Rc, Weak},
 };
 
@@ -10,6 +11,13 @@ use winit::window::Window;
 use super::Instance;
 use crate::error::{RenderError, RenderResult};
 
+const HDR_COLOR_SPACES: [vk::ColorSpaceKHR; 4] = [
+    vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    vk::ColorSpaceKHR::HDR10_HLG_EXT,
+    vk::ColorSpaceKHR::DOLBYVISION_EXT,
+    vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+];
+
 pub struct SurfaceAttributes {
     capabilities: vk::SurfaceCapabilitiesKHR,
     format: vk::SurfaceFormatKHR,
@@ -17,8 +25,26 @@ pub struct SurfaceAttributes {
     extent: vk::Extent2D,
 }
 
+/// What a [`Surface::refit_surface_attribute`] changed, resources sized or formatted after
+/// the swapchain must be rebuilt accordingly
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SurfaceChange {
+    pub extent_changed: bool,
+    /// The current format is not supported anymore, e.g. after the window moved to another
+    /// monitor
+    pub format_changed: bool,
+}
+
+impl SurfaceChange {
+    pub fn any(&self) -> bool {
+        self.extent_changed || self.format_changed
+    }
+}
+
 pub struct Surface {
     attributes: RefCell<SurfaceAttributes>,
+    /// Asked for at creation, selected again when the current format is not supported anymore
+    preferred_format: vk::Format,
     loader: SurfaceLoader,
     inner: vk::SurfaceKHR,
     /// Ensure [`Surface`] is dropped before [`Instance`]
@@ -45,12 +71,14 @@ impl Surface {
             &surface_khr,
             &loader,
             format,
+            None,
             &physical_device.upgrade().unwrap(),
             window,
         )?);
 
         Ok(Self {
             attributes,
+            preferred_format: format,
             loader,
             inner: surface_khr,
             physical_device,
@@ -74,19 +102,69 @@ impl Surface {
         Ref::map(self.attributes.borrow(), |x| &x.capabilities)
     }
 
+    /// Transform the swapchain is created with, the current one when it is a pure rotation.
+    /// Rendering must then be rotated by [`Self::pre_rotation_angle`]
+    pub fn pre_transform(&self) -> vk::SurfaceTransformFlagsKHR {
+        pre_transform(&self.capabilities())
+    }
+
+    /// Clockwise rotation in radians to apply in clip space, e.g. on a rotated Android display
+    pub fn pre_rotation_angle(&self) -> f32 {
+        match self.pre_transform() {
+            vk::SurfaceTransformFlagsKHR::ROTATE_90 => FRAC_PI_2,
+            vk::SurfaceTransformFlagsKHR::ROTATE_180 => PI,
+            vk::SurfaceTransformFlagsKHR::ROTATE_270 => 3. * FRAC_PI_2,
+            _ => 0.,
+        }
+    }
+
     pub fn present_mode(&self) -> vk::PresentModeKHR {
         self.attributes.borrow().present_mode
     }
 
-    pub fn refit_surface_attribute(&self, window: &Window) -> RenderResult<()> {
-        *self.attributes.borrow_mut() = get_surface_attrs(
+    /// HDR color spaces the surface can present in, always empty
+    /// when the instance does not enable `VK_EXT_swapchain_colorspace`
+    pub fn hdr_color_spaces(&self) -> RenderResult<Vec<vk::ColorSpaceKHR>> {
+        let formats = unsafe {
+            self.loader.get_physical_device_surface_formats(
+                *self.physical_device.upgrade().unwrap(),
+                self.inner,
+            )?
+        };
+        let mut color_spaces = formats
+            .into_iter()
+            .map(|f| f.color_space)
+            .filter(|color_space| HDR_COLOR_SPACES.contains(color_space))
+            .collect::<Vec<_>>();
+        color_spaces.sort_by_key(|color_space| color_space.as_raw());
+        color_spaces.dedup();
+        Ok(color_spaces)
+    }
+
+    /// Queries the capabilities, extent and formats again. When the current format is not
+    /// supported anymore another one is selected, see [`SurfaceChange::format_changed`].
+    ///
+    /// Minimized or occluded windows may report a zero extent, which no swapchain can be
+    /// created with. The attributes are then kept and `None` is returned.
+    pub fn refit_surface_attribute(&self, window: &Window) -> RenderResult<Option<SurfaceChange>> {
+        let (old_extent, old_format) = (self.extent(), self.attributes.borrow().format);
+        let attributes = get_surface_attrs(
             &self.inner,
             &self.loader,
-            self.format(),
+            self.preferred_format,
+            Some(old_format),
             &self.physical_device.upgrade().unwrap(),
             window,
         )?;
-        Ok(())
+        if attributes.extent.width == 0 || attributes.extent.height == 0 {
+            return Ok(None);
+        }
+        let change = SurfaceChange {
+            extent_changed: attributes.extent != old_extent,
+            format_changed: attributes.format != old_format,
+        };
+        *self.attributes.borrow_mut() = attributes;
+        Ok(Some(change))
     }
 
     pub fn surface_khr(&self) -> &vk::SurfaceKHR {
@@ -112,10 +190,13 @@ impl Drop for Surface {
     }
 }
 
+/// Without `current_format`, fails when `preferred_format` is not supported. Otherwise keeps
+/// `current_format` while supported, then falls back to `preferred_format` and any sRGB format.
 fn get_surface_attrs(
     surface: &vk::SurfaceKHR,
     surface_loader: &SurfaceLoader,
-    format: vk::Format,
+    preferred_format: vk::Format,
+    current_format: Option<vk::SurfaceFormatKHR>,
     device: &vk::PhysicalDevice,
     window: &Window,
 ) -> RenderResult<SurfaceAttributes> {
@@ -123,10 +204,24 @@ fn get_surface_attrs(
         let capabilities =
             surface_loader.get_physical_device_surface_capabilities(*device, *surface)?;
         let extent = get_window_extent(&capabilities, window);
-        let format = surface_loader
-            .get_physical_device_surface_formats(*device, *surface)?
-            .into_iter()
-            .find(|f| f.format == format && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
+        let formats = surface_loader.get_physical_device_surface_formats(*device, *surface)?;
+        let find = |format: vk::Format| {
+            formats
+                .iter()
+                .find(|f| f.format == format && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
+                .copied()
+        };
+        // Pipelines are built against the current format, keep it as long as it is supported
+        let format = current_format
+            .and_then(|current_format| find(current_format.format))
+            .or_else(|| find(preferred_format))
+            .or_else(|| {
+                current_format?;
+                formats
+                    .iter()
+                    .find(|f| f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
+                    .copied()
+            })
             .map_or_else(
                 || {
                     Err(RenderError::FormatNotSupported(
@@ -170,9 +265,56 @@ pub mod extent_helper {
             .extent(extent)
             .build()
     }
+
+    /// Sets the dynamic viewport and scissor to cover `extent`, which must be the extent of
+    /// the framebuffer the commands render to
+    pub fn cmd_set_viewport_and_scissor(
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        extent: vk::Extent2D,
+    ) {
+        unsafe {
+            device.cmd_set_viewport(command_buffer, 0, &[viewport_from_extent(extent)]);
+            device.cmd_set_scissor(command_buffer, 0, &[scissor_from_extent(extent)]);
+        }
+    }
+}
+
+fn pre_transform(capabilities: &vk::SurfaceCapabilitiesKHR) -> vk::SurfaceTransformFlagsKHR {
+    let rotations = vk::SurfaceTransformFlagsKHR::IDENTITY
+        | vk::SurfaceTransformFlagsKHR::ROTATE_90
+        | vk::SurfaceTransformFlagsKHR::ROTATE_180
+        | vk::SurfaceTransformFlagsKHR::ROTATE_270;
+    if rotations.contains(capabilities.current_transform)
+        || !capabilities
+            .supported_transforms
+            .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+    {
+        capabilities.current_transform
+    } else {
+        vk::SurfaceTransformFlagsKHR::IDENTITY
+    }
 }
 
+/// Swapchain images of a 90 or 270 degree pre-rotated surface are in the
+/// native orientation of the display, so the window extent is swapped
 fn get_window_extent(capabilities: &vk::SurfaceCapabilitiesKHR, window: &Window) -> vk::Extent2D {
+    let extent = get_unrotated_window_extent(capabilities, window);
+    match pre_transform(capabilities) {
+        vk::SurfaceTransformFlagsKHR::ROTATE_90 | vk::SurfaceTransformFlagsKHR::ROTATE_270 => {
+            vk::Extent2D::builder()
+                .width(extent.height)
+                .height(extent.width)
+                .build()
+        }
+        _ => extent,
+    }
+}
+
+fn get_unrotated_window_extent(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    window: &Window,
+) -> vk::Extent2D {
     if capabilities.current_extent.width != u32::MAX {
         capabilities.current_extent
     } else {
@@ -1,4 +1,7 @@
-use std::rc::{Rc, Weak};
+use std::{
+    cell::Cell,
+    rc::{Rc, Weak},
+};
 
 use ash::{extensions::khr::Swapchain as SwapChainLoader, prelude::VkResult, vk};
 
@@ -11,6 +14,7 @@ pub struct SwapChainBatch {
     image_views: Vec<vk::ImageView>,
     device: Rc<Device>,
     surface: Rc<Surface>,
+    last_present_id: Cell<u64>,
 }
 
 impl SwapChainBatch {
@@ -22,7 +26,7 @@ impl SwapChainBatch {
         ));
         let loader = SwapChainLoader::new(device.instance(), &device);
         let (swapchain, images, image_views) =
-            create_swapchain_image_and_views(&surface, &device, &loader)?;
+            create_swapchain_image_and_views(&surface, &device, &loader, vk::SwapchainKHR::null())?;
         Ok(Self {
             loader,
             swapchain,
@@ -30,14 +34,21 @@ impl SwapChainBatch {
             image_views,
             device,
             surface,
+            last_present_id: Cell::new(0),
         })
     }
 
+    /// The old swapchain is retired rather than destroyed first, so the presentation
+    /// engine keeps showing its images until the new swapchain is ready
     pub fn recreate(&mut self) -> VkResult<()> {
+        let (swapchain, images, image_views) = create_swapchain_image_and_views(
+            &self.surface,
+            &self.device,
+            &self.loader,
+            self.swapchain,
+        )?;
         self.dispose_gpu_resources();
-
-        (self.swapchain, self.images, self.image_views) =
-            create_swapchain_image_and_views(&self.surface, &self.device, &self.loader)?;
+        (self.swapchain, self.images, self.image_views) = (swapchain, images, image_views);
 
         Ok(())
     }
@@ -61,15 +72,46 @@ impl SwapChainBatch {
     ) -> VkResult<bool> {
         assert!((image_index as usize) < self.images.len());
 
-        let present_info_khr = vk::PresentInfoKHR::builder()
-            .wait_semaphores(wait_semaphores)
-            .swapchains(&[self.swapchain])
-            .image_indices(&[image_index])
+        // Ids keep increasing across swapchain recreations, which only requires them to
+        // be greater than those of previous presents to the same swapchain
+        let present_ids = [self.last_present_id.get() + 1];
+        let mut present_id = vk::PresentIdKHR::builder()
+            .present_ids(&present_ids)
             .build();
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
+        let mut present_info_khr = vk::PresentInfoKHR::builder()
+            .wait_semaphores(wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+        if self.device.present_wait().is_some() {
+            present_info_khr = present_info_khr.push_next(&mut present_id);
+            self.last_present_id.set(present_ids[0]);
+        }
+        let present_info_khr = present_info_khr.build();
 
         unsafe { self.loader.queue_present(*queue, &present_info_khr) }
     }
 
+    /// Id of the last present, `None` without `VK_KHR_present_wait` or before the first present
+    pub fn last_present_id(&self) -> Option<u64> {
+        (self.last_present_id.get() > 0).then(|| self.last_present_id.get())
+    }
+
+    /// Waits until the present with `present_id` is displayed,
+    /// returns `false` when `timeout` nanoseconds elapsed first
+    pub fn wait_for_present(&self, present_id: u64, timeout: u64) -> VkResult<bool> {
+        let present_wait = self
+            .device
+            .present_wait()
+            .ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+        match unsafe { present_wait.wait_for_present(self.swapchain, present_id, timeout) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn loader(&self) -> &SwapChainLoader {
         &self.loader
     }
@@ -86,6 +128,11 @@ impl SwapChainBatch {
         &self.image_views
     }
 
+    /// Besides `COLOR_ATTACHMENT`, images can be copied from and to where the surface allows
+    pub fn image_usage(&self) -> vk::ImageUsageFlags {
+        swapchain_image_usage(&self.surface.capabilities())
+    }
+
     fn dispose_gpu_resources(&self) {
         unsafe {
             self.image_views
@@ -106,8 +153,14 @@ fn create_swapchain_image_and_views(
     surface: &Surface,
     device: &Device,
     loader: &SwapChainLoader,
+    old_swapchain: vk::SwapchainKHR,
 ) -> VkResult<(vk::SwapchainKHR, Vec<vk::Image>, Vec<vk::ImageView>)> {
-    let swapchain = create_swapchain(loader, surface, &device.queue_family_indices())?;
+    let swapchain = create_swapchain(
+        loader,
+        surface,
+        &device.queue_family_indices(),
+        old_swapchain,
+    )?;
     let images = unsafe { loader.get_swapchain_images(swapchain)? };
 
     let mut image_views = vec![];
@@ -131,35 +184,43 @@ fn create_swapchain_image_and_views(
     Ok((swapchain, images, image_views))
 }
 
+fn swapchain_image_usage(capabilities: &vk::SurfaceCapabilitiesKHR) -> vk::ImageUsageFlags {
+    vk::ImageUsageFlags::COLOR_ATTACHMENT
+        | (capabilities.supported_usage_flags
+            & (vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST))
+}
+
 fn create_swapchain(
     swapchain_loader: &SwapChainLoader,
     surface: &Surface,
     family_indices: &Vec<u32>,
+    old_swapchain: vk::SwapchainKHR,
 ) -> VkResult<vk::SwapchainKHR> {
+    let capabilities = surface.capabilities();
+    // A max image count of 0 means there is no limit
+    let min_image_count = match capabilities.max_image_count {
+        0 => capabilities.min_image_count + 1,
+        max_image_count => max_image_count.min(capabilities.min_image_count + 1),
+    };
     let create_info = vk::SwapchainCreateInfoKHR::builder()
         .surface(*surface.surface_khr())
-        .min_image_count(
-            surface
-                .capabilities()
-                .max_image_count
-                .min(surface.capabilities().min_image_count + 1),
-        )
+        .min_image_count(min_image_count)
         .image_format(surface.format())
         .image_color_space(surface.color_space())
         .image_extent(surface.extent())
         .image_array_layers(1)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_usage(swapchain_image_usage(&capabilities))
         .image_sharing_mode(if family_indices.len() > 1 {
             vk::SharingMode::CONCURRENT
         } else {
             vk::SharingMode::EXCLUSIVE
         })
         .queue_family_indices(family_indices)
-        .pre_transform(surface.capabilities().current_transform)
+        .pre_transform(surface.pre_transform())
         .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
         .present_mode(surface.present_mode())
         .clipped(true)
-        .old_swapchain(vk::SwapchainKHR::null())
+        .old_swapchain(old_swapchain)
         .build();
 
     Ok(unsafe { swapchain_loader.create_swapchain(&create_info, None)? })
@@ -0,0 +1,443 @@
+use std::rc::Rc;
+
+use ash::vk::{self, native};
+
+use super::{memory_helper, Device, Nv12Texture, YcbcrSampler};
+use crate::error::{RenderError, RenderResult};
+
+/// Progressive 8 bit 4:2:0 H.264 decode profile
+pub struct H264DecodeProfile {
+    profile: vk::VideoProfileInfoKHR,
+    // Pointed to by `profile`, boxed so that moving `self` keeps the chain valid
+    _h264_profile: Box<vk::VideoDecodeH264ProfileInfoKHR>,
+}
+
+impl H264DecodeProfile {
+    pub fn main() -> Self {
+        Self::with_profile_idc(native::StdVideoH264ProfileIdc_STD_VIDEO_H264_PROFILE_IDC_MAIN)
+    }
+
+    pub fn with_profile_idc(std_profile_idc: native::StdVideoH264ProfileIdc) -> Self {
+        let mut h264_profile = Box::new(
+            vk::VideoDecodeH264ProfileInfoKHR::builder()
+                .std_profile_idc(std_profile_idc)
+                .picture_layout(vk::VideoDecodeH264PictureLayoutFlagsKHR::PROGRESSIVE)
+                .build(),
+        );
+        let profile = vk::VideoProfileInfoKHR::builder()
+            .push_next(h264_profile.as_mut())
+            .video_codec_operation(vk::VideoCodecOperationFlagsKHR::DECODE_H264)
+            .chroma_subsampling(vk::VideoChromaSubsamplingFlagsKHR::TYPE_420)
+            .luma_bit_depth(vk::VideoComponentBitDepthFlagsKHR::TYPE_8)
+            .chroma_bit_depth(vk::VideoComponentBitDepthFlagsKHR::TYPE_8)
+            .build();
+
+        Self {
+            profile,
+            _h264_profile: h264_profile,
+        }
+    }
+
+    pub fn profile(&self) -> &vk::VideoProfileInfoKHR {
+        &self.profile
+    }
+}
+
+/// What the device can decode with a [`H264DecodeProfile`]
+#[derive(Clone, Copy, Debug)]
+pub struct VideoDecodeCapabilities {
+    pub min_coded_extent: vk::Extent2D,
+    pub max_coded_extent: vk::Extent2D,
+    pub picture_access_granularity: vk::Extent2D,
+    pub max_dpb_slots: u32,
+    pub max_active_reference_pictures: u32,
+    pub max_level_idc: u32,
+    /// Decoded pictures are written to the DPB image itself, which can then be sampled
+    pub dpb_and_output_coincide: bool,
+    /// Format of DPB pictures, `None` when they can not be sampled
+    pub picture_format: Option<vk::Format>,
+}
+
+impl VideoDecodeCapabilities {
+    pub fn query(device: &Device, profile: &H264DecodeProfile) -> RenderResult<Self> {
+        let video_queue_fn = &device.video_decode_fns()?.video_queue;
+        let physical_device = *device.physical_device().upgrade().unwrap();
+
+        let mut h264_capabilities = vk::VideoDecodeH264CapabilitiesKHR::default();
+        let mut decode_capabilities = vk::VideoDecodeCapabilitiesKHR::default();
+        let mut capabilities = vk::VideoCapabilitiesKHR::builder()
+            .push_next(&mut decode_capabilities)
+            .push_next(&mut h264_capabilities)
+            .build();
+        unsafe {
+            (video_queue_fn.get_physical_device_video_capabilities_khr)(
+                physical_device,
+                profile.profile(),
+                &mut capabilities,
+            )
+            .result()?;
+        }
+
+        let dpb_and_output_coincide = decode_capabilities
+            .flags
+            .contains(vk::VideoDecodeCapabilityFlagsKHR::DPB_AND_OUTPUT_COINCIDE);
+        let picture_format = if dpb_and_output_coincide {
+            let formats = picture_formats(device, profile, DecodedPictureBuffer::IMAGE_USAGE)?;
+            // Prefer the format `YcbcrSampler`s are usually created with
+            formats
+                .iter()
+                .find(|&&format| format == Nv12Texture::FORMAT)
+                .or(formats.first())
+                .copied()
+        } else {
+            None
+        };
+
+        Ok(Self {
+            min_coded_extent: capabilities.min_coded_extent,
+            max_coded_extent: capabilities.max_coded_extent,
+            picture_access_granularity: capabilities.picture_access_granularity,
+            max_dpb_slots: capabilities.max_dpb_slots,
+            max_active_reference_pictures: capabilities.max_active_reference_pictures,
+            max_level_idc: h264_capabilities.max_level_idc,
+            dpb_and_output_coincide,
+            picture_format,
+        })
+    }
+}
+
+fn picture_formats(
+    device: &Device,
+    profile: &H264DecodeProfile,
+    image_usage: vk::ImageUsageFlags,
+) -> RenderResult<Vec<vk::Format>> {
+    let video_queue_fn = &device.video_decode_fns()?.video_queue;
+    let physical_device = *device.physical_device().upgrade().unwrap();
+
+    let profiles = [*profile.profile()];
+    let mut profile_list = vk::VideoProfileListInfoKHR::builder()
+        .profiles(&profiles)
+        .build();
+    let format_info = vk::PhysicalDeviceVideoFormatInfoKHR::builder()
+        .push_next(&mut profile_list)
+        .image_usage(image_usage)
+        .build();
+
+    unsafe {
+        let mut count = 0;
+        (video_queue_fn.get_physical_device_video_format_properties_khr)(
+            physical_device,
+            &format_info,
+            &mut count,
+            std::ptr::null_mut(),
+        )
+        .result()?;
+        let mut properties = vec![vk::VideoFormatPropertiesKHR::default(); count as usize];
+        (video_queue_fn.get_physical_device_video_format_properties_khr)(
+            physical_device,
+            &format_info,
+            &mut count,
+            properties.as_mut_ptr(),
+        )
+        .result()?;
+        Ok(properties
+            .iter()
+            .take(count as usize)
+            .map(|x| x.format)
+            .collect())
+    }
+}
+
+/// Decoded picture buffer whose slots double as decode output and can be sampled
+/// through a [`YcbcrSampler`] to present decoded frames.
+///
+/// Only devices reporting `DPB_AND_OUTPUT_COINCIDE` are supported. The image is shared
+/// concurrently between the video decode and graphics queues, so no ownership transfer
+/// is needed, but decode submissions must be ordered with rendering by semaphores
+pub struct DecodedPictureBuffer {
+    image: vk::Image,
+    device_memory: vk::DeviceMemory,
+    picture_views: Vec<vk::ImageView>,
+    sampled_views: Vec<vk::ImageView>,
+    layouts: Vec<vk::ImageLayout>,
+    coded_extent: vk::Extent2D,
+    sampler: Rc<YcbcrSampler>,
+    device: Rc<Device>,
+}
+
+impl DecodedPictureBuffer {
+    pub const IMAGE_USAGE: vk::ImageUsageFlags = vk::ImageUsageFlags::from_raw(
+        vk::ImageUsageFlags::VIDEO_DECODE_DPB_KHR.as_raw()
+            | vk::ImageUsageFlags::VIDEO_DECODE_DST_KHR.as_raw()
+            | vk::ImageUsageFlags::SAMPLED.as_raw(),
+    );
+
+    /// One array layer is allocated per slot, `slot_count` is usually the
+    /// `max_dec_frame_buffering` of the stream plus one for the current picture
+    pub fn new(
+        coded_extent: vk::Extent2D,
+        slot_count: u32,
+        profile: &H264DecodeProfile,
+        sampler: Rc<YcbcrSampler>,
+    ) -> RenderResult<Self> {
+        let device = sampler.device().clone();
+        let capabilities = VideoDecodeCapabilities::query(&device, profile)?;
+        if !capabilities.dpb_and_output_coincide {
+            return Err(RenderError::PhysicalDeviceNotSupported(
+                "Decode output images distinct from the DPB are not supported".to_string(),
+            ));
+        }
+        if capabilities.picture_format != Some(sampler.format()) {
+            return Err(RenderError::FormatNotSupported(format!(
+                "{:?} is not a decoded picture format, expected {:?}",
+                sampler.format(),
+                capabilities.picture_format
+            )));
+        }
+        if slot_count == 0 || slot_count > capabilities.max_dpb_slots {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "DPB slot count must be in 1..={}, got {slot_count}",
+                capabilities.max_dpb_slots
+            )));
+        }
+        let (min, max) = (capabilities.min_coded_extent, capabilities.max_coded_extent);
+        if coded_extent.width < min.width
+            || coded_extent.height < min.height
+            || coded_extent.width > max.width
+            || coded_extent.height > max.height
+        {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "Coded extent must be between {}x{} and {}x{}, got {}x{}",
+                min.width,
+                min.height,
+                max.width,
+                max.height,
+                coded_extent.width,
+                coded_extent.height
+            )));
+        }
+
+        let mut queue_family_indices = vec![device.graphic_queue_family_index()];
+        let video_decode_family_index = device.video_decode_queue_family_index().unwrap();
+        if video_decode_family_index != queue_family_indices[0] {
+            queue_family_indices.push(video_decode_family_index);
+        }
+
+        let profiles = [*profile.profile()];
+        let mut profile_list = vk::VideoProfileListInfoKHR::builder()
+            .profiles(&profiles)
+            .build();
+        let create_info = vk::ImageCreateInfo::builder()
+            .push_next(&mut profile_list)
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(
+                vk::Extent3D::builder()
+                    .width(coded_extent.width)
+                    .height(coded_extent.height)
+                    .depth(1)
+                    .build(),
+            )
+            .mip_levels(1)
+            .array_layers(slot_count)
+            .format(sampler.format())
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(Self::IMAGE_USAGE)
+            .sharing_mode(if queue_family_indices.len() > 1 {
+                vk::SharingMode::CONCURRENT
+            } else {
+                vk::SharingMode::EXCLUSIVE
+            })
+            .queue_family_indices(&queue_family_indices)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .build();
+
+        let (image, device_memory) = unsafe {
+            let image = device.create_image(&create_info, None)?;
+            let memory_requirement = device.get_image_memory_requirements(image);
+            let memory_alloc_info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(memory_requirement.size)
+                .memory_type_index(memory_helper::find_memory_type(
+                    &device,
+                    &memory_requirement,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )?)
+                .build();
+            let device_memory = device.allocate_tracked_memory(&memory_alloc_info, true)?;
+            device.bind_image_memory(image, device_memory, 0)?;
+            (image, device_memory)
+        };
+
+        // Views used by the decoder must not carry a YCbCr conversion while views with
+        // sampled usage must, so every slot gets one view of each kind
+        let create_view = |slot, usage, conversion: Option<vk::SamplerYcbcrConversion>| {
+            let mut usage_info = vk::ImageViewUsageCreateInfo::builder().usage(usage).build();
+            let mut conversion_info = vk::SamplerYcbcrConversionInfo::builder()
+                .conversion(conversion.unwrap_or_default())
+                .build();
+            let mut create_info = vk::ImageViewCreateInfo::builder()
+                .push_next(&mut usage_info)
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(sampler.format())
+                .subresource_range(
+                    vk::ImageSubresourceRange::builder()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .base_mip_level(0)
+                        .level_count(1)
+                        .base_array_layer(slot)
+                        .layer_count(1)
+                        .build(),
+                );
+            if conversion.is_some() {
+                create_info = create_info.push_next(&mut conversion_info);
+            }
+            unsafe { device.create_image_view(&create_info, None) }
+        };
+        let picture_views = (0..slot_count)
+            .map(|slot| {
+                create_view(
+                    slot,
+                    vk::ImageUsageFlags::VIDEO_DECODE_DPB_KHR
+                        | vk::ImageUsageFlags::VIDEO_DECODE_DST_KHR,
+                    None,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let sampled_views = (0..slot_count)
+            .map(|slot| {
+                create_view(
+                    slot,
+                    vk::ImageUsageFlags::SAMPLED,
+                    Some(sampler.conversion()),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            image,
+            device_memory,
+            picture_views,
+            sampled_views,
+            layouts: vec![vk::ImageLayout::UNDEFINED; slot_count as usize],
+            coded_extent,
+            sampler,
+            device,
+        })
+    }
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn slot_count(&self) -> u32 {
+        self.layouts.len() as u32
+    }
+
+    pub fn coded_extent(&self) -> vk::Extent2D {
+        self.coded_extent
+    }
+
+    pub fn layout(&self, slot: u32) -> vk::ImageLayout {
+        self.layouts[slot as usize]
+    }
+
+    pub fn sampler(&self) -> &Rc<YcbcrSampler> {
+        &self.sampler
+    }
+
+    /// Picture resource of `slot`, used both as setup reference slot and decode output
+    pub fn picture_resource(&self, slot: u32) -> vk::VideoPictureResourceInfoKHR {
+        vk::VideoPictureResourceInfoKHR::builder()
+            .coded_offset(vk::Offset2D::default())
+            .coded_extent(self.coded_extent)
+            .base_array_layer(0)
+            .image_view_binding(self.picture_views[slot as usize])
+            .build()
+    }
+
+    /// The sampler part is ignored, the descriptor set layout binding must use
+    /// [`YcbcrSampler::layout_binding`] as immutable sampler
+    pub fn descriptor(&self, slot: u32) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(self.sampled_views[slot as usize])
+            .sampler(self.sampler.sampler())
+            .build()
+    }
+
+    /// Records a layout transition of `slot` into a command buffer of the video decode queue,
+    /// e.g. to `VIDEO_DECODE_DPB_KHR` before decoding into it and to `SHADER_READ_ONLY_OPTIMAL`
+    /// before signaling the semaphore the graphics queue waits on to present the picture
+    pub fn cmd_transition_slot(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        slot: u32,
+        new_layout: vk::ImageLayout,
+    ) -> RenderResult<()> {
+        let synchronization2 = &self.device.video_decode_fns()?.synchronization2;
+        let old_layout = self.layouts[slot as usize];
+        let (src_stage_mask, src_access_mask) = video_decode_stage_and_access(old_layout)?;
+        let (dst_stage_mask, dst_access_mask) = video_decode_stage_and_access(new_layout)?;
+
+        let barrier = vk::ImageMemoryBarrier2::builder()
+            .src_stage_mask(src_stage_mask)
+            .src_access_mask(src_access_mask)
+            .dst_stage_mask(dst_stage_mask)
+            .dst_access_mask(dst_access_mask)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(
+                vk::ImageSubresourceRange::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .base_mip_level(0)
+                    .level_count(1)
+                    .base_array_layer(slot)
+                    .layer_count(1)
+                    .build(),
+            )
+            .build();
+        let dependency_info = vk::DependencyInfo::builder()
+            .image_memory_barriers(std::slice::from_ref(&barrier))
+            .build();
+        unsafe { synchronization2.cmd_pipeline_barrier2(command_buffer, &dependency_info) };
+
+        self.layouts[slot as usize] = new_layout;
+        Ok(())
+    }
+}
+
+/// Stages and accesses of the video decode queue touching an image in `layout`,
+/// shader reads happen on another queue and are ordered by semaphores instead
+fn video_decode_stage_and_access(
+    layout: vk::ImageLayout,
+) -> RenderResult<(vk::PipelineStageFlags2, vk::AccessFlags2)> {
+    match layout {
+        vk::ImageLayout::UNDEFINED | vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
+            Ok((vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE))
+        }
+        vk::ImageLayout::VIDEO_DECODE_DPB_KHR | vk::ImageLayout::VIDEO_DECODE_DST_KHR => Ok((
+            vk::PipelineStageFlags2::VIDEO_DECODE_KHR,
+            vk::AccessFlags2::VIDEO_DECODE_READ_KHR | vk::AccessFlags2::VIDEO_DECODE_WRITE_KHR,
+        )),
+        _ => Err(RenderError::LayoutTransitionNotSupported(format!(
+            "{layout:?} is not a decoded picture layout"
+        ))),
+    }
+}
+
+impl Drop for DecodedPictureBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.picture_views
+                .iter()
+                .chain(self.sampled_views.iter())
+                .for_each(|&view| self.device.destroy_image_view(view, None));
+            self.device.destroy_image(self.image, None);
+            self.device.free_tracked_memory(self.device_memory);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::rc::Rc;
+
+use ash::vk;
+
+use super::{Buffer, Device, OneTimeCommand, Texture};
+use crate::error::{RenderError, RenderResult};
+
+/// Sampler doing YCbCr to RGB conversion of a multi-planar format.
+///
+/// Such a sampler can only be used as an immutable sampler of a descriptor set layout,
+/// and image views sampled through it must be created with the same conversion
+pub struct YcbcrSampler {
+    conversion: vk::SamplerYcbcrConversion,
+    sampler: vk::Sampler,
+    format: vk::Format,
+    device: Rc<Device>,
+}
+
+impl YcbcrSampler {
+    pub fn new(format: vk::Format, device: Rc<Device>) -> RenderResult<Self> {
+        let format_features = unsafe {
+            device
+                .instance()
+                .get_physical_device_format_properties(
+                    *device.physical_device().upgrade().unwrap(),
+                    format,
+                )
+                .optimal_tiling_features
+        };
+        // Prefer midpoint chroma samples, which is what most CPU decoders output
+        let chroma_offset =
+            if format_features.contains(vk::FormatFeatureFlags::MIDPOINT_CHROMA_SAMPLES) {
+                vk::ChromaLocation::MIDPOINT
+            } else if format_features.contains(vk::FormatFeatureFlags::COSITED_CHROMA_SAMPLES) {
+                vk::ChromaLocation::COSITED_EVEN
+            } else {
+                return Err(RenderError::FormatNotSupported(format!(
+                    "{format:?} can not be sampled with a YCbCr conversion"
+                )));
+            };
+        let chroma_filter = if format_features
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_YCBCR_CONVERSION_LINEAR_FILTER)
+        {
+            vk::Filter::LINEAR
+        } else {
+            vk::Filter::NEAREST
+        };
+
+        let conversion = device.create_sampler_ycbcr_conversion(
+            &vk::SamplerYcbcrConversionCreateInfo::builder()
+                .format(format)
+                .ycbcr_model(vk::SamplerYcbcrModelConversion::YCBCR_709)
+                .ycbcr_range(vk::SamplerYcbcrRange::ITU_NARROW)
+                .components(vk::ComponentMapping::default())
+                .x_chroma_offset(chroma_offset)
+                .y_chroma_offset(chroma_offset)
+                .chroma_filter(chroma_filter)
+                .force_explicit_reconstruction(false)
+                .build(),
+        )?;
+
+        let mut conversion_info = vk::SamplerYcbcrConversionInfo::builder()
+            .conversion(conversion)
+            .build();
+        // Address modes must be clamp to edge and anisotropy disabled with a YCbCr conversion
+        let create_info = vk::SamplerCreateInfo::builder()
+            .push_next(&mut conversion_info)
+            .mag_filter(chroma_filter)
+            .min_filter(chroma_filter)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(false)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .build();
+        let sampler = match unsafe { device.create_sampler(&create_info, None) } {
+            Ok(sampler) => sampler,
+            Err(e) => {
+                device.destroy_sampler_ycbcr_conversion(conversion);
+                return Err(e.into());
+            }
+        };
+
+        Ok(Self {
+            conversion,
+            sampler,
+            format,
+            device,
+        })
+    }
+
+    pub fn conversion(&self) -> vk::SamplerYcbcrConversion {
+        self.conversion
+    }
+
+    pub fn sampler(&self) -> vk::Sampler {
+        self.sampler
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn device(&self) -> &Rc<Device> {
+        &self.device
+    }
+
+    /// Layout binding with this sampler as its immutable sampler, borrowing `self` for the
+    /// pointer stored in the binding
+    pub fn layout_binding(
+        &self,
+        binding: u32,
+        stage_flags: vk::ShaderStageFlags,
+    ) -> vk::DescriptorSetLayoutBinding {
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(binding)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(stage_flags)
+            .immutable_samplers(std::slice::from_ref(&self.sampler))
+            .build()
+    }
+}
+
+impl Drop for YcbcrSampler {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_sampler(self.sampler, None) };
+        self.device
+            .destroy_sampler_ycbcr_conversion(self.conversion);
+    }
+}
+
+/// Two plane 4:2:0 texture, a full resolution Y plane followed by a half resolution
+/// interleaved CbCr plane, as produced by most video decoders
+pub struct Nv12Texture {
+    texture: Texture,
+    sampler: Rc<YcbcrSampler>,
+}
+
+impl Nv12Texture {
+    pub const FORMAT: vk::Format = vk::Format::G8_B8R8_2PLANE_420_UNORM;
+
+    pub fn new(width: u32, height: u32, device: Rc<Device>) -> RenderResult<Self> {
+        let sampler = Rc::new(YcbcrSampler::new(Self::FORMAT, device)?);
+        Self::with_sampler(width, height, sampler)
+    }
+
+    /// Shares `sampler`, e.g. between the textures of all frames in flight,
+    /// so that a single immutable sampler can be used in the descriptor set layout
+    pub fn with_sampler(width: u32, height: u32, sampler: Rc<YcbcrSampler>) -> RenderResult<Self> {
+        assert!(sampler.format() == Self::FORMAT);
+        let device = sampler.device.clone();
+        if !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "NV12 extent must be even, got {width}x{height}"
+            )));
+        }
+
+        let mut texture = Texture::builder(
+            width,
+            height,
+            Self::FORMAT,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            device.clone(),
+        )
+        .build()?;
+
+        let image_view = {
+            let mut conversion_info = vk::SamplerYcbcrConversionInfo::builder()
+                .conversion(sampler.conversion())
+                .build();
+            let create_info = vk::ImageViewCreateInfo::builder()
+                .push_next(&mut conversion_info)
+                .image(*texture.image())
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(Self::FORMAT)
+                .subresource_range(
+                    vk::ImageSubresourceRange::builder()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .base_mip_level(0)
+                        .level_count(1)
+                        .base_array_layer(0)
+                        .layer_count(1)
+                        .build(),
+                )
+                .build();
+            unsafe { device.create_image_view(&create_info, None)? }
+        };
+        texture.set_image_view(Rc::new(image_view));
+
+        Ok(Self { texture, sampler })
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn sampler(&self) -> &Rc<YcbcrSampler> {
+        &self.sampler
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.texture.extent2d()
+    }
+
+    /// The sampler part is ignored, the descriptor set layout binding must use
+    /// [`YcbcrSampler::layout_binding`] as immutable sampler
+    pub fn descriptor(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(*self.texture.image_view().unwrap())
+            .sampler(self.sampler.sampler())
+            .build()
+    }
+
+    /// Uploads one decoded frame and leaves the image in `SHADER_READ_ONLY_OPTIMAL`,
+    /// must not be called while a frame sampling it is in flight
+    pub fn upload_frame(
+        &mut self,
+        y_plane: &[u8],
+        cbcr_plane: &[u8],
+        command_pool: &vk::CommandPool,
+        queue: &vk::Queue,
+    ) -> RenderResult<()> {
+        let extent = self.extent();
+        let y_size = (extent.width * extent.height) as usize;
+        if y_plane.len() != y_size || cbcr_plane.len() != y_size / 2 {
+            return Err(RenderError::ImageDataInvalid(format!(
+                "NV12 planes of a {}x{} frame must be {} and {} bytes, got {} and {}",
+                extent.width,
+                extent.height,
+                y_size,
+                y_size / 2,
+                y_plane.len(),
+                cbcr_plane.len()
+            )));
+        }
+
+        let staging_buffer = {
+            let mut buffer = Buffer::<u8>::new(
+                y_size + y_size / 2,
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                self.texture.device().clone(),
+            )?;
+            buffer.load_data(&[y_plane, cbcr_plane].concat(), 0)?;
+            buffer
+        };
+
+        let plane_copy = |aspect_mask, buffer_offset, width, height| {
+            vk::BufferImageCopy::builder()
+                .buffer_offset(buffer_offset)
+                .image_subresource(
+                    vk::ImageSubresourceLayers::builder()
+                        .aspect_mask(aspect_mask)
+                        .mip_level(0)
+                        .base_array_layer(0)
+                        .layer_count(1)
+                        .build(),
+                )
+                .image_offset(vk::Offset3D::default())
+                .image_extent(
+                    vk::Extent3D::builder()
+                        .width(width)
+                        .height(height)
+                        .depth(1)
+                        .build(),
+                )
+                .build()
+        };
+        let image_copies = [
+            plane_copy(
+                vk::ImageAspectFlags::PLANE_0,
+                0,
+                extent.width,
+                extent.height,
+            ),
+            plane_copy(
+                vk::ImageAspectFlags::PLANE_1,
+                y_size as vk::DeviceSize,
+                extent.width / 2,
+                extent.height / 2,
+            ),
+        ];
+
+        let device = self.texture.device().clone();
+        OneTimeCommand::new(&device, command_pool)?.take_and_execute(
+            |command_buffer| {
+                // The previous frame content is discarded
+                self.texture.transition_layout(
+                    command_buffer,
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::PipelineStageFlags::TRANSFER,
+                );
+                unsafe {
+                    device.cmd_copy_buffer_to_image(
+                        command_buffer,
+                        staging_buffer.buffer(),
+                        *self.texture.image(),
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &image_copies,
+                    );
+                }
+                self.texture.transition_layout(
+                    command_buffer,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                );
+                Ok(())
+            },
+            queue,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+#version 450
+
+// Fixture of the shader-structs unit tests, std140 layouts needing padding. Compiled with
+// `glslangValidator -V blocks.vert -o blocks.vert.spv` when changed
+
+struct Light {
+    vec3 position;
+    float range;
+    vec2 spot;
+};
+
+layout(set = 1, binding = 2) uniform Scene {
+    mat4 viewProjection;
+    vec3 cameraPosition;
+    float weights[3];
+    mat3 normalMatrix;
+    Light lights[2];
+    uint flags;
+} scene;
+
+layout(push_constant) uniform PushConsts {
+    mat4 model;
+    float roughness;
+} pushConsts;
+
+layout(location = 0) in vec3 inPosition;
+
+void main() {
+    vec3 normal = scene.normalMatrix * inPosition * scene.weights[2];
+    float light = scene.lights[1].range * scene.lights[0].spot.x + float(scene.flags);
+    gl_Position = scene.viewProjection * pushConsts.model * vec4(normal + scene.cameraPosition, light * pushConsts.roughness);
+}
@@ -0,0 +1,125 @@
+//! Repository chores, run with `cargo xtask <task>`
+
+mod shader_structs;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const USAGE: &str = "Usage: cargo xtask new-example <snake_case_name>
+       cargo xtask shader-structs <shader.spv>... [--out <file.rs>]";
+
+const MAIN_TEMPLATE: &str = include_str!("../templates/main.rs.template");
+const VERTEX_SHADER_TEMPLATE: &str = include_str!("../templates/shader.vert.template");
+const FRAGMENT_SHADER_TEMPLATE: &str = include_str!("../templates/shader.frag.template");
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["new-example", name] => new_example(name),
+        ["shader-structs", ref spv_paths @ ..] if !spv_paths.is_empty() => {
+            shader_structs(spv_paths)
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes `examples/<name>/main.rs` drawing a cube with a `SimpleMeshRenderer` and the UI
+/// overlay, its shaders in `examples/shaders/<name>/` and the `[[example]]` entry of the manifest
+fn new_example(name: &str) -> Result<(), String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(format!("{name:?} is not a snake_case name\n{USAGE}"));
+    }
+
+    let root = repository_root();
+    let example_dir = root.join("examples").join(name);
+    if example_dir.exists() {
+        return Err(format!("{} already exists", example_dir.display()));
+    }
+    let shader_dir = root.join("examples").join("shaders").join(name);
+
+    let fill = |template: &str| {
+        template
+            .replace("{{name}}", name)
+            .replace("{{App}}", &app_name(name))
+    };
+    write(&example_dir.join("main.rs"), &fill(MAIN_TEMPLATE))?;
+    write(&shader_dir.join("shader.vert"), VERTEX_SHADER_TEMPLATE)?;
+    write(&shader_dir.join("shader.frag"), FRAGMENT_SHADER_TEMPLATE)?;
+
+    let manifest_path = root.join("Cargo.toml");
+    let mut manifest = fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Fail to read {}: {e}", manifest_path.display()))?;
+    manifest.push_str(&format!(
+        "\n[[example]]\nname = \"{name}\"\nrequired-features = [\"ui\"]\n"
+    ));
+    write(&manifest_path, &manifest)?;
+
+    println!("Run it with `cargo run --example {name}`");
+    Ok(())
+}
+
+/// Prints, or writes with `--out`, the Rust structs of the uniform and push constant blocks of
+/// SPIR-V modules built by `build.rs`, with their offsets and sizes checked at compile time
+fn shader_structs(args: &[&str]) -> Result<(), String> {
+    let (spv_paths, out) = match args {
+        [spv_paths @ .., "--out", out] => (spv_paths, Some(out)),
+        spv_paths => (spv_paths, None),
+    };
+    if spv_paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let mut generator = shader_structs::Generator::default();
+    for spv_path in spv_paths {
+        generator.add_spv(Path::new(spv_path))?;
+    }
+    let sources = spv_paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    let code = generator.finish(&sources);
+    match out {
+        Some(out) => write(Path::new(out), &code),
+        None => {
+            print!("{code}");
+            Ok(())
+        }
+    }
+}
+
+/// `gpu_culling` → `GpuCullingApp`
+fn app_name(name: &str) -> String {
+    let mut app_name = name
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| word[..1].to_ascii_uppercase() + &word[1..])
+        .collect::<String>();
+    app_name.push_str("App");
+    app_name
+}
+
+fn repository_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask lives in the repository root")
+        .to_path_buf()
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Fail to create {}: {e}", dir.display()))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("Fail to write {}: {e}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
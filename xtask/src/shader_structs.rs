@@ -0,0 +1,608 @@
+//! `#[repr(C)]` Rust structs matching the uniform and push constant blocks of SPIR-V modules,
+//! read from the offsets and strides the compiler decorated them with
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+const MAGIC: u32 = 0x0723_0203;
+
+const OP_NAME: u32 = 5;
+const OP_MEMBER_NAME: u32 = 6;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_ROW_MAJOR: u32 = 4;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+
+#[derive(Clone, Debug)]
+enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    /// Literal operands of each decoration of an id
+    decorations: HashMap<(u32, u32), Vec<u32>>,
+    member_decorations: HashMap<(u32, u32, u32), Vec<u32>>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    /// Id, pointer type and storage class of each variable
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl Module {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.len().is_multiple_of(4) || bytes.len() < 20 {
+            return Err("not a SPIR-V module".to_string());
+        }
+        let words = bytes
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        if words[0] != MAGIC {
+            return Err("not a little endian SPIR-V module".to_string());
+        }
+
+        let mut module = Self::default();
+        let mut i = 5;
+        while i < words.len() {
+            let (count, opcode) = ((words[i] >> 16) as usize, words[i] & 0xFFFF);
+            if count == 0 || i + count > words.len() {
+                return Err(format!("truncated instruction at word {i}"));
+            }
+            let ops = &words[i + 1..i + count];
+            match opcode {
+                OP_NAME => {
+                    module.names.insert(ops[0], string(&ops[1..]));
+                }
+                OP_MEMBER_NAME => {
+                    module
+                        .member_names
+                        .insert((ops[0], ops[1]), string(&ops[2..]));
+                }
+                OP_DECORATE => {
+                    module
+                        .decorations
+                        .insert((ops[0], ops[1]), ops[2..].to_vec());
+                }
+                OP_MEMBER_DECORATE => {
+                    module
+                        .member_decorations
+                        .insert((ops[0], ops[1], ops[2]), ops[3..].to_vec());
+                }
+                OP_TYPE_BOOL => {
+                    module.types.insert(ops[0], Type::Bool);
+                }
+                OP_TYPE_INT => {
+                    module.types.insert(
+                        ops[0],
+                        Type::Int {
+                            width: ops[1],
+                            signed: ops[2] != 0,
+                        },
+                    );
+                }
+                OP_TYPE_FLOAT => {
+                    module.types.insert(ops[0], Type::Float { width: ops[1] });
+                }
+                OP_TYPE_VECTOR => {
+                    module.types.insert(
+                        ops[0],
+                        Type::Vector {
+                            component: ops[1],
+                            count: ops[2],
+                        },
+                    );
+                }
+                OP_TYPE_MATRIX => {
+                    module.types.insert(
+                        ops[0],
+                        Type::Matrix {
+                            column: ops[1],
+                            count: ops[2],
+                        },
+                    );
+                }
+                OP_TYPE_ARRAY => {
+                    module.types.insert(
+                        ops[0],
+                        Type::Array {
+                            element: ops[1],
+                            length: ops[2],
+                        },
+                    );
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(ops[0], Type::RuntimeArray);
+                }
+                OP_TYPE_STRUCT => {
+                    module.types.insert(
+                        ops[0],
+                        Type::Struct {
+                            members: ops[1..].to_vec(),
+                        },
+                    );
+                }
+                OP_TYPE_POINTER => {
+                    module
+                        .types
+                        .insert(ops[0], Type::Pointer { pointee: ops[2] });
+                }
+                // Only 32 bit constants are used as array lengths
+                OP_CONSTANT => {
+                    module.constants.insert(ops[1], ops[2]);
+                }
+                OP_VARIABLE => module.variables.push((ops[1], ops[0], ops[2])),
+                _ => {}
+            }
+            i += count;
+        }
+        Ok(module)
+    }
+
+    fn ty(&self, id: u32) -> Result<&Type, String> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| format!("type %{id} is not declared"))
+    }
+
+    fn member_decoration(&self, ty: u32, member: u32, decoration: u32) -> Option<&[u32]> {
+        self.member_decorations
+            .get(&(ty, member, decoration))
+            .map(Vec::as_slice)
+    }
+
+    /// Size in bytes of a value of type `id` inside a block, matrices taking their stride from
+    /// the member `(parent, member)` they are declared as
+    fn size(&self, id: u32, member_of: Option<(u32, u32)>) -> Result<u32, String> {
+        Ok(match self.ty(id)? {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector { component, count } => self.size(*component, None)? * count,
+            Type::Matrix { column, count } => {
+                let (stride, vectors) = self.matrix_layout(*column, *count, member_of)?;
+                stride * vectors
+            }
+            Type::Array { length, .. } => self.array_stride(id)? * self.array_length(*length)?,
+            Type::RuntimeArray => 0,
+            Type::Struct { members } => match members.len() {
+                0 => 0,
+                n => {
+                    let last = n as u32 - 1;
+                    self.member_offset(id, last)?
+                        + self.size(members[last as usize], Some((id, last)))?
+                }
+            },
+            Type::Pointer { .. } => return Err(format!("pointer %{id} inside a block")),
+        })
+    }
+
+    /// Stride and count of the vectors a matrix is stored as, columns unless row major
+    fn matrix_layout(
+        &self,
+        column: u32,
+        count: u32,
+        member_of: Option<(u32, u32)>,
+    ) -> Result<(u32, u32), String> {
+        let (ty, member) = member_of.ok_or("matrix outside of a struct member")?;
+        let stride = self
+            .member_decoration(ty, member, DECORATION_MATRIX_STRIDE)
+            .and_then(|literals| literals.first().copied())
+            .ok_or_else(|| format!("member {member} of %{ty} has no MatrixStride"))?;
+        let Type::Vector { count: rows, .. } = self.ty(column)? else {
+            return Err(format!("matrix column %{column} is not a vector"));
+        };
+        let row_major = self
+            .member_decoration(ty, member, DECORATION_ROW_MAJOR)
+            .is_some();
+        Ok((stride, if row_major { *rows } else { count }))
+    }
+
+    fn array_stride(&self, id: u32) -> Result<u32, String> {
+        self.decorations
+            .get(&(id, DECORATION_ARRAY_STRIDE))
+            .and_then(|literals| literals.first().copied())
+            .ok_or_else(|| format!("array %{id} has no ArrayStride"))
+    }
+
+    fn array_length(&self, length: u32) -> Result<u32, String> {
+        self.constants
+            .get(&length)
+            .copied()
+            .ok_or_else(|| format!("array length %{length} is not a constant"))
+    }
+
+    fn member_offset(&self, ty: u32, member: u32) -> Result<u32, String> {
+        self.member_decoration(ty, member, DECORATION_OFFSET)
+            .and_then(|literals| literals.first().copied())
+            .ok_or_else(|| format!("member {member} of %{ty} has no Offset"))
+    }
+
+    fn struct_name(&self, id: u32) -> String {
+        match self.names.get(&id) {
+            Some(name) if !name.is_empty() => type_name(name),
+            _ => format!("Struct{id}"),
+        }
+    }
+}
+
+/// Generated code and the glam types it uses, shared by every module so structs declared in
+/// several stages are emitted once
+#[derive(Default)]
+pub struct Generator {
+    code: String,
+    /// Fields of the structs emitted so far, by name
+    emitted: BTreeMap<String, String>,
+    glam_types: BTreeSet<&'static str>,
+}
+
+impl Generator {
+    pub fn add_spv(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = fs::read(path).map_err(|e| format!("Fail to read {}: {e}", path.display()))?;
+        let module = Module::parse(&bytes).map_err(|e| format!("{}: {e}", path.display()))?;
+
+        for &(variable, pointer, storage_class) in &module.variables {
+            if !matches!(
+                storage_class,
+                STORAGE_CLASS_UNIFORM | STORAGE_CLASS_PUSH_CONSTANT
+            ) {
+                continue;
+            }
+            let Ok(&Type::Pointer { pointee, .. }) = module.ty(pointer) else {
+                continue;
+            };
+            // Uniform buffers are `Block`, `BufferBlock` ones are storage buffers
+            if !module
+                .decorations
+                .contains_key(&(pointee, DECORATION_BLOCK))
+            {
+                continue;
+            }
+            let comment = match storage_class {
+                STORAGE_CLASS_PUSH_CONSTANT => "Push constant block".to_string(),
+                _ => {
+                    let literal = |decoration| {
+                        module
+                            .decorations
+                            .get(&(variable, decoration))
+                            .and_then(|literals| literals.first().copied())
+                            .unwrap_or(0)
+                    };
+                    format!(
+                        "Uniform block at set {} binding {}",
+                        literal(DECORATION_DESCRIPTOR_SET),
+                        literal(DECORATION_BINDING)
+                    )
+                }
+            };
+            self.emit_struct(&module, pointee, Some(&comment), path)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Structs reached from a block first, then the block
+    fn emit_struct(
+        &mut self,
+        module: &Module,
+        id: u32,
+        comment: Option<&str>,
+        path: &Path,
+    ) -> Result<(), String> {
+        let name = module.struct_name(id);
+        let Type::Struct { members } = module.ty(id)? else {
+            return Err(format!("%{id} is not a struct"));
+        };
+
+        let mut fields = String::new();
+        let mut asserts = String::new();
+        let mut cursor = 0;
+        let mut padding_count = 0;
+        for (member, &member_type) in members.iter().enumerate() {
+            let member = member as u32;
+            let field = match module.member_names.get(&(id, member)) {
+                Some(field) if !field.is_empty() => field_name(field),
+                _ => format!("member{member}"),
+            };
+            let offset = module.member_offset(id, member)?;
+            if matches!(module.ty(member_type)?, Type::RuntimeArray) {
+                writeln!(
+                    fields,
+                    "    // `{field}` is a runtime array, bound separately"
+                )
+                .unwrap();
+                continue;
+            }
+            if offset > cursor {
+                writeln!(
+                    fields,
+                    "    pub _padding{padding_count}: [u8; {}],",
+                    offset - cursor
+                )
+                .unwrap();
+                padding_count += 1;
+            }
+            let rust_type = self.rust_type(module, member_type, Some((id, member)), path)?;
+            writeln!(fields, "    pub {field}: {rust_type},").unwrap();
+            writeln!(
+                asserts,
+                "    assert!(std::mem::offset_of!({name}, {field}) == {offset});"
+            )
+            .unwrap();
+            cursor = offset + module.size(member_type, Some((id, member)))?;
+        }
+        // Arrays of the struct may need it padded to their stride
+        let size = module
+            .types
+            .iter()
+            .filter_map(|(&array, ty)| match ty {
+                Type::Array { element, .. } if *element == id => module.array_stride(array).ok(),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(cursor)
+            .max(cursor);
+        if size > cursor {
+            writeln!(
+                fields,
+                "    pub _padding{padding_count}: [u8; {}],",
+                size - cursor
+            )
+            .unwrap();
+        }
+        match self.emitted.get(&name) {
+            Some(emitted) if *emitted == fields => return Ok(()),
+            Some(_) => {
+                return Err(format!(
+                    "`{name}` differs from the struct of the same name in an earlier shader"
+                ))
+            }
+            None => {}
+        }
+
+        if let Some(comment) = comment {
+            writeln!(self.code, "/// {comment}").unwrap();
+        }
+        writeln!(self.code, "#[repr(C)]").unwrap();
+        writeln!(self.code, "#[derive(Clone, Copy, Debug)]").unwrap();
+        writeln!(self.code, "pub struct {name} {{\n{fields}}}\n").unwrap();
+        writeln!(self.code, "unsafe impl bytemuck::Pod for {name} {{}}").unwrap();
+        writeln!(
+            self.code,
+            "unsafe impl bytemuck::Zeroable for {name} {{}}\n"
+        )
+        .unwrap();
+        writeln!(
+            self.code,
+            "const _: () = {{\n{asserts}    assert!(std::mem::size_of::<{name}>() == {size});\n}};\n"
+        )
+        .unwrap();
+        self.emitted.insert(name, fields);
+        Ok(())
+    }
+
+    fn rust_type(
+        &mut self,
+        module: &Module,
+        id: u32,
+        member_of: Option<(u32, u32)>,
+        path: &Path,
+    ) -> Result<String, String> {
+        Ok(match module.ty(id)? {
+            // 32 bit in blocks
+            Type::Bool => "u32".to_string(),
+            Type::Int { width, signed } => format!("{}{width}", if *signed { "i" } else { "u" }),
+            Type::Float { width } => format!("f{width}"),
+            Type::Vector { component, count } => match (module.ty(*component)?, count) {
+                // `Vec4` is 16 byte aligned, which would round the size of blocks up
+                (Type::Float { width: 32 }, 2..=3) => {
+                    let glam_type = ["Vec2", "Vec3"][*count as usize - 2];
+                    self.glam_types.insert(glam_type);
+                    glam_type.to_string()
+                }
+                _ => format!(
+                    "[{}; {count}]",
+                    self.rust_type(module, *component, None, path)?
+                ),
+            },
+            Type::Matrix { column, count } => {
+                let (stride, vectors) = module.matrix_layout(*column, *count, member_of)?;
+                // Columns, or rows when row major, each padded to the matrix stride. Arrays
+                // rather than the 16 byte aligned `Mat4`, e.g. from `Mat4::to_cols_array_2d`
+                format!("[[f32; {}]; {vectors}]", stride / 4)
+            }
+            Type::Array { element, length } => {
+                let length = module.array_length(*length)?;
+                let stride = module.array_stride(id)?;
+                if let Type::Struct { .. } = module.ty(*element)? {
+                    self.emit_struct(module, *element, None, path)?;
+                    format!("[{}; {length}]", module.struct_name(*element))
+                } else if module.size(*element, member_of)? == stride {
+                    format!(
+                        "[{}; {length}]",
+                        self.rust_type(module, *element, member_of, path)?
+                    )
+                } else {
+                    // Elements padded to the stride, e.g. `float` arrays of std140 blocks
+                    let scalar = match module.ty(*element)? {
+                        Type::Vector { component, .. } => *component,
+                        _ => *element,
+                    };
+                    format!(
+                        "[[{}; {}]; {length}]",
+                        self.rust_type(module, scalar, None, path)?,
+                        stride / module.size(scalar, None)?
+                    )
+                }
+            }
+            Type::Struct { .. } => {
+                self.emit_struct(module, id, None, path)?;
+                module.struct_name(id)
+            }
+            Type::RuntimeArray | Type::Pointer { .. } => {
+                return Err(format!("%{id} can not be a block member"))
+            }
+        })
+    }
+
+    pub fn finish(self, sources: &[String]) -> String {
+        let mut out = format!(
+            "// Generated by `cargo xtask shader-structs {}`\n\n",
+            sources.join(" ")
+        );
+        if !self.glam_types.is_empty() {
+            let types = self.glam_types.into_iter().collect::<Vec<_>>();
+            match &types[..] {
+                [glam_type] => writeln!(out, "use glam::{glam_type};\n").unwrap(),
+                _ => writeln!(out, "use glam::{{{}}};\n", types.join(", ")).unwrap(),
+            }
+        }
+        out.push_str(&self.code);
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    }
+}
+
+/// Null terminated UTF-8 packed in words
+fn string(words: &[u32]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&b| b != 0)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// `UBO` → `Ubo`, `pushConsts` → `PushConsts`
+fn type_name(name: &str) -> String {
+    let name = if name.chars().all(|c| !c.is_ascii_lowercase()) {
+        name.to_ascii_lowercase()
+    } else {
+        name.to_string()
+    };
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| word[..1].to_ascii_uppercase() + &word[1..])
+        .collect()
+}
+
+/// `arrayIndex` → `array_index`
+fn field_name(name: &str) -> String {
+    let mut field = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !field.ends_with('_') {
+                field.push('_');
+            }
+            field.push(c.to_ascii_lowercase());
+        } else {
+            field.push(c);
+        }
+    }
+    match field.as_str() {
+        "type" | "move" | "ref" | "match" | "mod" | "use" | "fn" | "in" | "loop" => {
+            format!("r#{field}")
+        }
+        _ => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `fixtures/blocks.vert` covers std140 vec3 packing, array strides,
+    /// matrix columns, nested struct arrays and a push constant block
+    fn generate_fixture() -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/blocks.vert.spv");
+        let mut generator = Generator::default();
+        generator.add_spv(&path).unwrap();
+        generator.finish(&["fixtures/blocks.vert.spv".to_string()])
+    }
+
+    /// Slice from `pub struct name {` through the end of its const assertions
+    fn struct_section<'a>(code: &'a str, name: &str) -> &'a str {
+        let start = code.find(&format!("pub struct {name} {{")).unwrap();
+        let end = start + code[start..].find("};").unwrap();
+        &code[start..end]
+    }
+
+    #[test]
+    fn nested_struct_is_padded_to_base_alignment() {
+        let code = generate_fixture();
+        let light = struct_section(&code, "Light");
+        assert!(light.contains("    pub position: Vec3,\n    pub range: f32,\n    pub spot: Vec2,\n    pub _padding0: [u8; 8],\n}"));
+        assert!(light.contains("offset_of!(Light, range) == 12"));
+        assert!(light.contains("offset_of!(Light, spot) == 16"));
+        assert!(light.contains("size_of::<Light>() == 32"));
+    }
+
+    #[test]
+    fn uniform_block_offsets_follow_std140() {
+        let code = generate_fixture();
+        assert!(code.contains("/// Uniform block at set 1 binding 2\n"));
+        let scene = struct_section(&code, "Scene");
+        assert!(scene.contains("    pub camera_position: Vec3,\n    pub _padding0: [u8; 4],\n    pub weights: [[f32; 4]; 3],"));
+        assert!(scene.contains("    pub normal_matrix: [[f32; 4]; 3],\n    pub lights: [Light; 2],\n    pub flags: u32,\n}"));
+        for (field, offset) in [
+            ("view_projection", 0),
+            ("camera_position", 64),
+            ("weights", 80),
+            ("normal_matrix", 128),
+            ("lights", 176),
+            ("flags", 240),
+        ] {
+            assert!(scene.contains(&format!("offset_of!(Scene, {field}) == {offset});")));
+        }
+        assert!(scene.contains("size_of::<Scene>() == 244"));
+    }
+
+    #[test]
+    fn push_constant_block_has_no_tail_padding() {
+        let code = generate_fixture();
+        assert!(code.contains("/// Push constant block\n"));
+        let push = struct_section(&code, "PushConsts");
+        assert!(!push.contains("_padding"));
+        assert!(push.contains("offset_of!(PushConsts, roughness) == 64"));
+        assert!(push.contains("size_of::<PushConsts>() == 68"));
+    }
+
+    #[test]
+    fn fixture_imports_only_used_glam_types() {
+        assert!(generate_fixture().contains("\nuse glam::{Vec2, Vec3};\n"));
+    }
+}